@@ -0,0 +1,47 @@
+/*
+ * The tahini runtime.
+ *
+ * Compiled programs allocate everything tahini puts on the heap (closure environments and boxed
+ * variables) through these functions, which take the memory from the Boehm GC. String literals
+ * are constants of the program and are never allocated. Memory that C code gets from `malloc` is
+ * untouched.
+ *
+ * Build with -DTAHINI_NO_GC where libgc is not available: memory then comes from `malloc` and is
+ * never freed.
+ */
+
+#include <stddef.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+#ifdef TAHINI_NO_GC
+#define GC_INIT()
+#define GC_MALLOC(size) calloc(1, size)
+#define GC_MALLOC_ATOMIC(size) malloc(size)
+#else
+#include <gc.h>
+#endif
+
+static void *checked(void *memory) {
+    if (memory == NULL) {
+        fputs("tahini: out of memory\n", stderr);
+        abort();
+    }
+    return memory;
+}
+
+/* Start the collector. Called by the C `main` before the tahini `main`. */
+void tahini_init(void) {
+    GC_INIT();
+}
+
+/* Zeroed memory that may hold pointers to other collected memory. */
+void *tahini_alloc(size_t size) {
+    return checked(GC_MALLOC(size));
+}
+
+/* Memory that holds no pointers, such as boxed numbers and arrays of numbers. The collector does
+ * not scan it, and it is not zeroed. */
+void *tahini_alloc_atomic(size_t size) {
+    return checked(GC_MALLOC_ATOMIC(size));
+}
@@ -1,6 +1,34 @@
+/// A type parameter of a generic function or type, e.g. `T` or `T:numeric`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Generic {
+    pub name: String,
+    pub bound: Option<String>,
+}
+
+impl Generic {
+    pub fn new(name: &str) -> Self {
+        Generic {
+            name: name.to_string(),
+            bound: None,
+        }
+    }
+
+    pub fn bounded(name: &str, bound: &str) -> Self {
+        Generic {
+            name: name.to_string(),
+            bound: Some(bound.to_string()),
+        }
+    }
+}
+
+/// The names of the type parameters `generics`.
+pub fn generic_names(generics: &[Generic]) -> Vec<String> {
+    generics.iter().map(|g| g.name.clone()).collect()
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct FnDef {
-    pub generic_types: Option<Vec<String>>,
+    pub generic_types: Option<Vec<Generic>>,
     pub parameters: Vec<(String, VarType)>,
     pub return_type: VarType,
     pub statement: Statement,
@@ -34,10 +62,19 @@ pub enum Statement {
     DoBlock(Vec<Statement>),
     Call(String, Vec<Statement>),
     DefVar(DefVar<Box<Statement>>),
+    /// `(def (:x T))` declares the local `x` of type `T` without a value. Reads before it is
+    /// assigned are rejected by [`crate::typeck::init`].
+    Declare(String, VarType),
     If(Box<Statement>, Box<Statement>),
     IfElse(Box<Statement>, Box<Statement>, Box<Statement>),
     For(Box<Statement>, Box<Statement>),
-    ForRange(String, Box<Statement>, Box<Statement>),
+    ForRange(String, Box<Range<Statement>>, Box<Statement>),
+    /// `(break)` leaves the innermost loop.
+    Break,
+    /// `(continue)` starts the next iteration of the innermost loop.
+    Continue,
+    /// `(return value)` leaves the innermost function with `value`, `(return)` without one.
+    Return(Option<Box<Statement>>),
     GenericCall(String, Vec<String>, Vec<Statement>),
 
     GetField(String, Box<Statement>),
@@ -47,6 +84,91 @@ pub enum Statement {
     SetIndexed(Box<Statement>, Box<Statement>, Box<Statement>),
 }
 
+/// What a `(for (range x ...) body)` loop walks over. Every part is evaluated once, before the
+/// first iteration, and the loop variable is a fresh local in each iteration; assigning it does
+/// not change the iteration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Range<T> {
+    /// `(range x e)`: the integers from `0` up to but excluding `e`, or the elements of the
+    /// sized array `e`.
+    Each(T),
+    /// `(range i start end)` or `(range i start end step)`: `start`, `start + step`, ... while
+    /// below `end`, or above it if `step` is negative. The step defaults to `1`.
+    Bounds(T, T, Option<T>),
+    /// `(range x :slice xs start end)`: the elements of `xs` from index `start` up to but
+    /// excluding `end`. `xs` can be an array of any length or a pointer.
+    Slice(T, T, T),
+}
+
+impl<T> Range<T> {
+    /// The expressions of the range in evaluation order.
+    pub fn parts(&self) -> Vec<&T> {
+        match self {
+            Range::Each(value) => vec![value],
+            Range::Bounds(start, end, step) => [start, end].into_iter().chain(step).collect(),
+            Range::Slice(array, start, end) => vec![array, start, end],
+        }
+    }
+
+    pub fn parts_mut(&mut self) -> Vec<&mut T> {
+        match self {
+            Range::Each(value) => vec![value],
+            Range::Bounds(start, end, step) => [start, end].into_iter().chain(step).collect(),
+            Range::Slice(array, start, end) => vec![array, start, end],
+        }
+    }
+
+    /// Apply `f` to every part, in evaluation order.
+    pub fn map<U>(&self, mut f: impl FnMut(&T) -> U) -> Range<U> {
+        match self {
+            Range::Each(value) => Range::Each(f(value)),
+            Range::Bounds(start, end, step) => {
+                let start = f(start);
+                let end = f(end);
+                Range::Bounds(start, end, step.as_ref().map(f))
+            }
+            Range::Slice(array, start, end) => {
+                let array = f(array);
+                let start = f(start);
+                Range::Slice(array, start, f(end))
+            }
+        }
+    }
+
+    pub fn into_map<U>(self, mut f: impl FnMut(T) -> U) -> Range<U> {
+        match self {
+            Range::Each(value) => Range::Each(f(value)),
+            Range::Bounds(start, end, step) => {
+                let start = f(start);
+                let end = f(end);
+                Range::Bounds(start, end, step.map(f))
+            }
+            Range::Slice(array, start, end) => {
+                let array = f(array);
+                let start = f(start);
+                Range::Slice(array, start, f(end))
+            }
+        }
+    }
+}
+
+/// Prints the parts after the loop variable, as in `(range x :slice xs 0 n)`.
+impl<T: std::fmt::Display> std::fmt::Display for Range<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Range::Slice(..) = self {
+            write!(f, ":slice ")?;
+        }
+        let parts = self.parts();
+        for (i, part) in parts.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", part)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum VarType {
     Int8,
@@ -67,21 +189,25 @@ pub enum VarType {
     Void,
     IdentType(String),
     ArraySized(Box<VarType>, usize),
+    /// An array whose length is a constant expression, as in `[i32 (* N 2)]`. Constant
+    /// evaluation replaces it with `ArraySized` before type checking.
+    ArrayConstSized(Box<VarType>, Box<Statement>),
     ArrayUnsized(Box<VarType>),
     GenericArraySized(String, usize),
     GenericArrayUnsized(String),
     Data(Vec<(String, Vec<VarType>)>),
-    GenericData(Vec<String>, Vec<(String, Vec<VarType>)>),
+    GenericData(Vec<Generic>, Vec<(String, Vec<VarType>)>),
     Ptr(Box<VarType>),
     GenericPtr(String),
     Tuple(Vec<VarType>),
-    GenericTuple(Vec<String>, Vec<VarType>),
+    GenericTuple(Vec<Generic>, Vec<VarType>),
     Struct(Vec<(String, VarType)>),
-    GenericStruct(Vec<String>, Vec<(String, VarType)>),
+    GenericStruct(Vec<Generic>, Vec<(String, VarType)>),
     Fn(Vec<VarType>, Box<VarType>),
     FnWithVarArgs(Vec<VarType>, Box<VarType>),
-    GenericFn(Vec<String>, Vec<VarType>, Box<VarType>),
-    GenericFnWithVarArgs(Vec<String>, Vec<VarType>, Box<VarType>),
+    GenericFn(Vec<Generic>, Vec<VarType>, Box<VarType>),
+    GenericFnWithVarArgs(Vec<Generic>, Vec<VarType>, Box<VarType>),
+    GenericInstance(String, Vec<VarType>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -103,6 +229,9 @@ pub enum TopLevelDef {
     Literal(Literal),
     Typed(VarType),
     FnDef(FnDef),
+    /// A value computed at compile time, as in `(def area (* PI 25.0))`. Constant evaluation
+    /// replaces it with a `Literal`.
+    Const(Statement),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -113,4 +242,170 @@ pub enum TopLevelStatement {
     UseHeader(String, String),
     ExportAll(),
     Export(Vec<String>),
+    /// `(allow :unused-local ...)` silences the named lints for the whole file.
+    Allow(Vec<String>),
+}
+
+fn write_list<T: std::fmt::Display>(
+    f: &mut std::fmt::Formatter<'_>,
+    items: &[T],
+) -> std::fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+fn write_generics(f: &mut std::fmt::Formatter<'_>, generics: &[Generic]) -> std::fmt::Result {
+    write!(f, "<")?;
+    write_list(f, generics)?;
+    write!(f, ">")
+}
+
+impl std::fmt::Display for Generic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.bound {
+            Some(bound) => write!(f, "{}:{}", self.name, bound),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+fn write_data(
+    f: &mut std::fmt::Formatter<'_>,
+    generics: Option<&[Generic]>,
+    variants: &[(String, Vec<VarType>)],
+) -> std::fmt::Result {
+    write!(f, "(data")?;
+    if let Some(generics) = generics {
+        write_generics(f, generics)?;
+    }
+    for (tag, types) in variants {
+        write!(f, " [:{}", tag)?;
+        for var_type in types {
+            write!(f, " {}", var_type)?;
+        }
+        write!(f, "]")?;
+    }
+    write!(f, ")")
+}
+
+fn write_struct(
+    f: &mut std::fmt::Formatter<'_>,
+    generics: Option<&[Generic]>,
+    fields: &[(String, VarType)],
+) -> std::fmt::Result {
+    write!(f, "(struct")?;
+    if let Some(generics) = generics {
+        write_generics(f, generics)?;
+    }
+    for (name, var_type) in fields {
+        write!(f, " (:{} {})", name, var_type)?;
+    }
+    write!(f, ")")
+}
+
+fn write_fn(
+    f: &mut std::fmt::Formatter<'_>,
+    generics: Option<&[Generic]>,
+    params: &[VarType],
+    var_args: bool,
+    ret: &VarType,
+) -> std::fmt::Result {
+    write!(f, "fn")?;
+    if let Some(generics) = generics {
+        write_generics(f, generics)?;
+    }
+    write!(f, " [")?;
+    write_list(f, params)?;
+    if var_args {
+        write!(f, "{}...", if params.is_empty() { "" } else { " " })?;
+    }
+    write!(f, "] {}", ret)
+}
+
+/// Prints an array size expression. The parser only accepts names, numbers and calls there.
+fn write_const_expr(f: &mut std::fmt::Formatter<'_>, statement: &Statement) -> std::fmt::Result {
+    match statement {
+        Statement::Ident(name) => write!(f, "{}", name),
+        Statement::Literal(Literal::Int(value)) => write!(f, "{}", value),
+        Statement::Literal(Literal::Float(value)) => write!(f, "{:?}", value),
+        Statement::Call(name, args) => {
+            write!(f, "({}", name)?;
+            for arg in args {
+                write!(f, " ")?;
+                write_const_expr(f, arg)?;
+            }
+            write!(f, ")")
+        }
+        other => write!(f, "{:?}", other),
+    }
+}
+
+/// Prints types in the same syntax the parser accepts.
+impl std::fmt::Display for VarType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VarType::Int8 => write!(f, "i8"),
+            VarType::Int16 => write!(f, "i16"),
+            VarType::Int32 => write!(f, "i32"),
+            VarType::Int64 => write!(f, "i64"),
+            VarType::Int128 => write!(f, "i128"),
+            VarType::UInt8 => write!(f, "u8"),
+            VarType::UInt16 => write!(f, "u16"),
+            VarType::UInt32 => write!(f, "u32"),
+            VarType::UInt64 => write!(f, "u64"),
+            VarType::UInt128 => write!(f, "u128"),
+            VarType::Float16 => write!(f, "f16"),
+            VarType::Float32 => write!(f, "f32"),
+            VarType::Float64 => write!(f, "f64"),
+            VarType::Float128 => write!(f, "f128"),
+            VarType::Bool => write!(f, "bool"),
+            VarType::Void => write!(f, "void"),
+            VarType::IdentType(name) => write!(f, "{}", name),
+            VarType::ArraySized(inner, size) => write!(f, "[{} {}]", inner, size),
+            VarType::ArrayConstSized(inner, size) => {
+                write!(f, "[{} ", inner)?;
+                write_const_expr(f, size)?;
+                write!(f, "]")
+            }
+            VarType::ArrayUnsized(inner) => write!(f, "[{}]", inner),
+            VarType::GenericArraySized(name, size) => write!(f, "(array<{}> {})", name, size),
+            VarType::GenericArrayUnsized(name) => write!(f, "(array<{}>)", name),
+            VarType::Data(variants) => write_data(f, None, variants),
+            VarType::GenericData(generics, variants) => write_data(f, Some(generics), variants),
+            VarType::Ptr(inner) => write!(f, "(ptr {})", inner),
+            VarType::GenericPtr(name) => write!(f, "(ptr<{}>)", name),
+            VarType::Tuple(types) => {
+                write!(f, "{{")?;
+                write_list(f, types)?;
+                write!(f, "}}")
+            }
+            VarType::GenericTuple(generics, types) => {
+                write!(f, "(tuple")?;
+                write_generics(f, generics)?;
+                write!(f, " ")?;
+                write_list(f, types)?;
+                write!(f, ")")
+            }
+            VarType::Struct(fields) => write_struct(f, None, fields),
+            VarType::GenericStruct(generics, fields) => write_struct(f, Some(generics), fields),
+            VarType::Fn(params, ret) => write_fn(f, None, params, false, ret),
+            VarType::FnWithVarArgs(params, ret) => write_fn(f, None, params, true, ret),
+            VarType::GenericFn(generics, params, ret) => {
+                write_fn(f, Some(generics), params, false, ret)
+            }
+            VarType::GenericFnWithVarArgs(generics, params, ret) => {
+                write_fn(f, Some(generics), params, true, ret)
+            }
+            VarType::GenericInstance(name, args) => {
+                write!(f, "{}<", name)?;
+                write_list(f, args)?;
+                write!(f, ">")
+            }
+        }
+    }
 }
@@ -0,0 +1,43 @@
+//! Names that are in scope in every module without being defined or imported.
+
+pub const ARITHMETIC: &[&str] = &["+", "-", "*", "/", "%"];
+pub const COMPARISON: &[&str] = &["=", "!=", "<", ">", "<=", ">="];
+pub const LOGICAL: &[&str] = &["&&", "||", "!", "and", "or", "not"];
+pub const BITWISE: &[&str] = &["&", "|", "^", "<<", ">>"];
+
+/// Type names that have no `VarType` variant of their own.
+pub const TYPES: &[&str] = &["str", "atom"];
+
+/// Intrinsic standing for a closure value: `($make_closure lambda$N captured...)`. Intrinsics
+/// are introduced by closure conversion; `$` cannot appear in source identifiers.
+pub const MAKE_CLOSURE: &str = "$make_closure";
+
+/// Intrinsics for locals boxed on the GC heap: `($box value)` allocates a cell holding `value`,
+/// `($unbox cell)` reads it and `($set-box cell value)` writes it.
+pub const BOX: &str = "$box";
+pub const UNBOX: &str = "$unbox";
+pub const SET_BOX: &str = "$set-box";
+
+/// Bounds a type parameter can carry, as in `<T:numeric>`.
+pub const BOUNDS: &[&str] = &["numeric", "integer", "float"];
+
+pub fn is_builtin_fn(name: &str) -> bool {
+    [ARITHMETIC, COMPARISON, LOGICAL, BITWISE]
+        .iter()
+        .any(|names| names.contains(&name))
+}
+
+pub fn is_builtin_type(name: &str) -> bool {
+    TYPES.contains(&name)
+}
+
+/// Split a `module/member` path into its module prefix and the member path.
+/// Operators like `/` are never treated as paths.
+pub fn split_path(name: &str) -> Option<(&str, &str)> {
+    if is_builtin_fn(name) {
+        return None;
+    }
+
+    name.split_once('/')
+        .filter(|(module, member)| !module.is_empty() && !member.is_empty())
+}
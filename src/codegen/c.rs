@@ -0,0 +1,1196 @@
+//! C11 source backend.
+//!
+//! A program becomes a single translation unit that any C11 compiler can build. Names are
+//! mangled into C identifiers (see [`super::mangle`]) and prefixed so they cannot collide
+//! with C keywords or the C library: functions and globals with `th_`, locals with `l_`,
+//! struct fields with `f_`. Types are `th_<name>_t`. Functions declared with a typed `def` and
+//! the undeclared functions of imported headers keep their C names; the declared members of
+//! headers are `th_` names bound to the C symbols with `__asm__` labels.
+//!
+//! Types map onto C as follows:
+//!
+//! - integers are the `<stdint.h>` types, except that `i8` is `char` so that `str` and
+//!   `(ptr i8)` are the same type; `i128`, `u128` and `f16` need `__int128` and `_Float16`.
+//! - structs, tuples and sized arrays are C structs; an array wraps its elements in an `items`
+//!   member so that it can be passed and returned by value. Unsized arrays are pointers.
+//! - a `data` value is a `u32` tag (the index of its variant) followed by a union of one
+//!   struct per variant that has members.
+//! - every function value is a `tahini_closure`: a code pointer and an environment pointer.
+//!   The code is a trampoline that takes the environment first, unpacks it and calls the
+//!   function. Environments are copied to the heap of the runtime (`runtime/tahini.c`), which
+//!   the garbage collector manages. C functions take `fn` parameters as function pointers, so
+//!   functions passed to them are trampolines without the environment, `th_<name>_callback`.
+//! - atoms are pointers to one string per atom, so they compare by address.
+//!
+//! String literals are copied as they are written, so their escape sequences are C's.
+//!
+//! [`header`] writes the declarations of a module's exports for C code that calls it, with the
+//! same types, so the layouts agree with the definitions.
+//!
+//! Every IR temporary and slot becomes a C variable declared at the top of its function, and
+//! blocks become labels that are only reached by `goto`. A tahini `main` is called from a C
+//! `main` that starts the runtime and passes on `argc` and `argv` if it takes them.
+
+use super::mangle;
+use crate::ast::VarType;
+use crate::builtins;
+use crate::ir::{Block, Const, Function, Instruction, Operand, Place, Program, Terminator, Value};
+use crate::transformer::lambda;
+use crate::transformer::layout::holds_pointers;
+use crate::typeck::types::{atom_type, is_float, str_type, Aliases};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+const PRELUDE: &str = "\
+/* The tahini runtime (runtime/tahini.c). */
+void tahini_init(void);
+void *tahini_alloc(size_t size);
+void *tahini_alloc_atomic(size_t size);
+
+typedef const char *tahini_atom;
+
+typedef struct {
+    void (*fn)(void);
+    void *env;
+} tahini_closure;
+
+static inline void *tahini_copy(const void *value, size_t size, void *(*alloc)(size_t)) {
+    void *copy = alloc(size);
+    memcpy(copy, value, size);
+    return copy;
+}
+";
+
+/// The C translation unit for `program`. Fails on types that have no C representation, such
+/// as generic types that survived monomorphization.
+pub fn emit(program: &Program) -> Result<String, String> {
+    let mut emitter = Emitter::new(program);
+
+    let mut externs = String::new();
+    if program
+        .externs
+        .iter()
+        .any(|(name, _)| builtins::split_path(name).is_some())
+    {
+        // The symbols of header members are given with the prefix of the platform.
+        externs.push_str("#define TAHINI_STR(x) #x\n#define TAHINI_XSTR(x) TAHINI_STR(x)\n");
+    }
+    for (name, var_type) in &program.externs {
+        externs.push_str(&emitter.extern_declaration(name, var_type)?);
+    }
+    for (name, var_type) in &program.imports {
+        let symbol = format!("th_{}", mangle(name));
+        externs.push_str(&emitter.declaration(&symbol, var_type, false)?);
+    }
+    let mut prototypes = String::new();
+    let mut bodies = String::new();
+    for function in &program.functions {
+        writeln!(prototypes, "{};", emitter.signature(function)?).unwrap();
+        bodies.push('\n');
+        bodies.push_str(&emitter.function(function)?);
+    }
+    let mut globals = String::new();
+    for global in &program.globals {
+        let name = format!("th_{}", mangle(&global.name));
+        let variable = emitter.variable(&global.var_type, &name)?;
+        let value = emitter.constant(&global.value)?;
+        writeln!(globals, "{} = {};", variable, value).unwrap();
+    }
+    let trampolines = emitter.trampolines()?;
+    let entry = emitter.entry()?;
+
+    let mut out = String::from("/* Generated by tahini. */\n\n");
+    for header in [
+        "stdbool.h",
+        "stddef.h",
+        "stdint.h",
+        "stdlib.h",
+        "string.h",
+        "math.h",
+    ] {
+        writeln!(out, "#include <{}>", header).unwrap();
+    }
+    for header in &program.headers {
+        writeln!(out, "#include {:?}", header).unwrap();
+    }
+    out.push('\n');
+    out.push_str(PRELUDE);
+    for section in [
+        &emitter.forward,
+        &emitter.definitions,
+        &emitter.atom_definitions(),
+        &externs,
+        &prototypes,
+        &trampolines,
+        &globals,
+    ] {
+        if !section.is_empty() {
+            out.push('\n');
+            out.push_str(section);
+        }
+    }
+    out.push_str(&bodies);
+    out.push_str(&entry);
+    Ok(out)
+}
+
+/// `name` as a readable C identifier, with every character other than a letter or a digit
+/// replaced by `_`. Unlike [`mangle`], different names can become the same identifier.
+pub fn identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// A C header for code that calls into `program`: the types and the declarations of the
+/// functions and globals named in `exports`, each paired with the C name a macro gives it, and
+/// the named `types`. Types are named after the module, `<prefix>_<name>`, so the headers of
+/// two modules can be included together, and the tags of `data` types are constants
+/// `<prefix>_<type>_<variant>`.
+pub fn header(
+    program: &Program,
+    prefix: &str,
+    exports: &[(String, String)],
+    types: &[String],
+) -> Result<String, String> {
+    let mut emitter = Emitter::new(program);
+    emitter.prefix = Some(prefix.to_string());
+    let mut declarations = String::new();
+    for (name, c_name) in exports {
+        let function = program.functions.iter().find(|f| f.name == *name);
+        let global = program.globals.iter().find(|g| g.name == *name);
+        let declaration = match (function, global) {
+            (Some(function), _) => format!("{};", emitter.signature(function)?),
+            (None, Some(global)) => {
+                let symbol = format!("th_{}", mangle(name));
+                format!("extern {};", emitter.variable(&global.var_type, &symbol)?)
+            }
+            (None, None) => return Err(format!("`{}` is not defined", name)),
+        };
+        writeln!(declarations, "{}", declaration).unwrap();
+        writeln!(declarations, "#define {} th_{}", c_name, mangle(name)).unwrap();
+    }
+    let mut names = String::new();
+    for name in types {
+        let c_name = format!("{}_{}", prefix, identifier(name));
+        let var_type = VarType::IdentType(name.clone());
+        // Aggregates are declared under their name already, other aliases are transparent.
+        if emitter.c_type(&var_type)? != c_name {
+            writeln!(names, "typedef {};", emitter.variable(&var_type, &c_name)?).unwrap();
+        }
+    }
+    for (name, var_type) in &program.types {
+        if let (VarType::Data(variants), true) = (var_type, emitter.type_names.contains_key(name)) {
+            let readable = identifier(name);
+            let tags: Vec<String> = variants
+                .iter()
+                .enumerate()
+                .map(|(tag, (variant, _))| {
+                    format!("{}_{}_{} = {}", prefix, readable, identifier(variant), tag)
+                })
+                .collect();
+            writeln!(names, "enum {{ {} }};", tags.join(", ")).unwrap();
+        }
+    }
+
+    let mut out = String::from("/* Generated by tahini. */\n\n");
+    writeln!(
+        out,
+        "#ifndef TAHINI_{0}_H\n#define TAHINI_{0}_H\n",
+        prefix.to_uppercase()
+    )
+    .unwrap();
+    for header in ["stdbool.h", "stddef.h", "stdint.h"] {
+        writeln!(out, "#include <{}>", header).unwrap();
+    }
+    out.push_str("\n#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+    out.push_str("/* Starts the tahini runtime; call it before anything else. */\n");
+    out.push_str("void tahini_init(void);\n");
+    let atoms = [&emitter.definitions, &declarations]
+        .iter()
+        .any(|section| section.contains("tahini_atom"));
+    if atoms {
+        out.push_str("\ntypedef const char *tahini_atom;\n");
+    }
+    for section in [
+        &emitter.forward,
+        &emitter.definitions,
+        &names,
+        &declarations,
+    ] {
+        if !section.is_empty() {
+            out.push('\n');
+            out.push_str(section);
+        }
+    }
+    out.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n#endif\n");
+    Ok(out)
+}
+
+/// How a trampoline passes its environment on.
+#[derive(Clone, Copy, PartialEq)]
+enum Trampoline {
+    /// A lifted function takes its environment struct as the first parameter.
+    Lifted,
+    /// A top-level or external function has no environment.
+    Plain,
+    /// A function pointer passed to C takes no environment; a lifted function that captures
+    /// nothing is given an empty one.
+    Callback,
+}
+
+struct Emitter<'a> {
+    program: &'a Program,
+    aliases: Aliases,
+    /// Parameter and return types of everything that is called by name.
+    signatures: HashMap<String, (Vec<VarType>, VarType)>,
+    externs: HashSet<String>,
+    globals: HashMap<String, VarType>,
+
+    /// C names of the aggregate types declared so far, keyed by the alias name or the printed
+    /// type.
+    type_names: HashMap<String, String>,
+    /// The module a header is for, which names its aggregates instead of the symbols of the
+    /// backend.
+    prefix: Option<String>,
+    /// `typedef`s of every aggregate, so that definitions can point at each other.
+    forward: String,
+    /// Aggregate definitions; members that are stored by value are defined first.
+    definitions: String,
+    atoms: BTreeSet<String>,
+    /// Functions used as values, in the order they were first used.
+    trampolines: Vec<(String, Trampoline)>,
+
+    /// The types of the slots and temporaries of the function being emitted.
+    locals: HashMap<String, VarType>,
+    temps: HashMap<usize, VarType>,
+}
+
+impl<'a> Emitter<'a> {
+    fn new(program: &'a Program) -> Self {
+        let mut aliases = Aliases::default();
+        aliases.types.extend(program.types.iter().cloned());
+        let mut emitter = Emitter {
+            program,
+            aliases,
+            signatures: HashMap::new(),
+            externs: HashSet::new(),
+            globals: HashMap::new(),
+            type_names: HashMap::new(),
+            prefix: None,
+            forward: String::new(),
+            definitions: String::new(),
+            atoms: BTreeSet::new(),
+            trampolines: Vec::new(),
+            locals: HashMap::new(),
+            temps: HashMap::new(),
+        };
+        for function in &program.functions {
+            let params = function.parameters.iter().map(|(_, t)| t.clone()).collect();
+            let signature = (params, function.return_type.clone());
+            emitter.signatures.insert(function.name.clone(), signature);
+        }
+        for (name, var_type) in &program.externs {
+            emitter.externs.insert(name.clone());
+            match var_type {
+                VarType::Fn(params, ret) | VarType::FnWithVarArgs(params, ret) => {
+                    let signature = (params.clone(), *ret.clone());
+                    emitter.signatures.insert(name.clone(), signature);
+                }
+                other => {
+                    emitter.globals.insert(name.clone(), other.clone());
+                }
+            }
+        }
+        for (name, var_type) in &program.imports {
+            match var_type {
+                VarType::Fn(params, ret) => {
+                    let signature = (params.clone(), *ret.clone());
+                    emitter.signatures.insert(name.clone(), signature);
+                }
+                other => {
+                    emitter.globals.insert(name.clone(), other.clone());
+                }
+            }
+        }
+        for global in &program.globals {
+            emitter
+                .globals
+                .insert(global.name.clone(), global.var_type.clone());
+        }
+        emitter
+    }
+
+    /// The C type of `var_type`, declaring the aggregates it needs.
+    fn c_type(&mut self, var_type: &VarType) -> Result<String, String> {
+        Ok(match var_type {
+            VarType::Int8 => "char".to_string(),
+            VarType::Int16 => "int16_t".to_string(),
+            VarType::Int32 => "int32_t".to_string(),
+            VarType::Int64 => "int64_t".to_string(),
+            VarType::Int128 => "__int128".to_string(),
+            VarType::UInt8 => "uint8_t".to_string(),
+            VarType::UInt16 => "uint16_t".to_string(),
+            VarType::UInt32 => "uint32_t".to_string(),
+            VarType::UInt64 => "uint64_t".to_string(),
+            VarType::UInt128 => "unsigned __int128".to_string(),
+            VarType::Float16 => "_Float16".to_string(),
+            VarType::Float32 => "float".to_string(),
+            VarType::Float64 => "double".to_string(),
+            VarType::Float128 => "long double".to_string(),
+            VarType::Bool => "bool".to_string(),
+            VarType::Void => "void".to_string(),
+            VarType::IdentType(_) if *var_type == str_type() => "char *".to_string(),
+            VarType::IdentType(_) if *var_type == atom_type() => "tahini_atom".to_string(),
+            VarType::IdentType(name) => match self.aliases.types.get(name).cloned() {
+                Some(
+                    target @ (VarType::Struct(_)
+                    | VarType::Tuple(_)
+                    | VarType::Data(_)
+                    | VarType::ArraySized(..)),
+                ) => {
+                    let c_name = match &self.prefix {
+                        Some(prefix) => format!("{}_{}", prefix, identifier(name)),
+                        None => format!("th_{}_t", mangle(name)),
+                    };
+                    self.aggregate(name, &target, c_name)?
+                }
+                // Other aliases are transparent.
+                Some(target) => self.c_type(&target)?,
+                None => return Err(format!("unknown type `{}`", name)),
+            },
+            VarType::Ptr(inner) | VarType::ArrayUnsized(inner) => {
+                let inner = self.c_type(inner)?;
+                match inner.ends_with('*') {
+                    true => format!("{}*", inner),
+                    false => format!("{} *", inner),
+                }
+            }
+            VarType::Fn(..) | VarType::FnWithVarArgs(..) => "tahini_closure".to_string(),
+            VarType::Struct(_) | VarType::Tuple(_) | VarType::Data(_) | VarType::ArraySized(..) => {
+                // An anonymous type that is also declared under a name is the named type.
+                let alias = self
+                    .program
+                    .types
+                    .iter()
+                    .find(|(_, target)| target == var_type)
+                    .map(|(name, _)| name.clone());
+                if let Some(alias) = alias {
+                    return self.c_type(&VarType::IdentType(alias));
+                }
+                let kind = match var_type {
+                    VarType::Struct(_) => "struct",
+                    VarType::Tuple(_) => "tuple",
+                    VarType::Data(_) => "data",
+                    _ => "array",
+                };
+                let prefix = self.prefix.as_deref().unwrap_or("tahini");
+                let name = format!("{}_{}_{}", prefix, kind, self.type_names.len());
+                self.aggregate(&var_type.to_string(), var_type, name)?
+            }
+            other => return Err(format!("type `{}` has no C representation", other)),
+        })
+    }
+
+    /// Declare the aggregate `var_type` as `name` unless it is known under `key` already.
+    fn aggregate(&mut self, key: &str, var_type: &VarType, name: String) -> Result<String, String> {
+        if let Some(known) = self.type_names.get(key) {
+            return Ok(known.clone());
+        }
+        // Readable names in headers can clash, as `add-one` and `add_one`.
+        if let Some((other, _)) = self.type_names.iter().find(|(_, known)| **known == name) {
+            return Err(format!(
+                "`{}` and `{}` are both `{}` in C",
+                other, key, name
+            ));
+        }
+        self.type_names.insert(key.to_string(), name.clone());
+        writeln!(self.forward, "typedef struct {0} {0};", name).unwrap();
+
+        let mut body = String::new();
+        match var_type {
+            VarType::Struct(fields) => {
+                for (field, field_type) in fields {
+                    let member = self.variable(field_type, &format!("f_{}", mangle(field)))?;
+                    writeln!(body, "    {};", member).unwrap();
+                }
+            }
+            VarType::Tuple(types) => {
+                for (i, member) in types.iter().enumerate() {
+                    writeln!(body, "    {};", self.variable(member, &format!("_{}", i))?).unwrap();
+                }
+            }
+            VarType::ArraySized(element, length) => {
+                let items = self.variable(element, &format!("items[{}]", length))?;
+                writeln!(body, "    {};", items).unwrap();
+            }
+            VarType::Data(variants) => {
+                body.push_str("    uint32_t tag;\n");
+                let mut payloads = String::new();
+                for (tag, members) in variants.iter().filter(|(_, m)| !m.is_empty()) {
+                    payloads.push_str("        struct {\n");
+                    for (i, member) in members.iter().enumerate() {
+                        let member = self.variable(member, &format!("_{}", i))?;
+                        writeln!(payloads, "            {};", member).unwrap();
+                    }
+                    writeln!(payloads, "        }} v_{};", mangle(tag)).unwrap();
+                }
+                if !payloads.is_empty() {
+                    writeln!(body, "    union {{\n{}    }} as;", payloads).unwrap();
+                }
+            }
+            _ => unreachable!("only aggregates are declared as structs"),
+        }
+        if body.is_empty() {
+            // C has no empty structs.
+            body.push_str("    char empty;\n");
+        }
+        if !self.definitions.is_empty() {
+            self.definitions.push('\n');
+        }
+        writeln!(self.definitions, "struct {} {{\n{}}};", name, body).unwrap();
+        Ok(name)
+    }
+
+    /// `T name`, with the C type of `var_type`.
+    fn variable(&mut self, var_type: &VarType, name: &str) -> Result<String, String> {
+        let c_type = self.c_type(var_type)?;
+        match c_type.ends_with('*') {
+            true => Ok(format!("{}{}", c_type, name)),
+            false => Ok(format!("{} {}", c_type, name)),
+        }
+    }
+
+    /// The C name of a function that is called by `name`.
+    fn callee(&self, name: &str) -> String {
+        if self.externs.contains(name) {
+            return self.extern_symbol(name);
+        }
+        match builtins::split_path(name) {
+            Some((_, member)) if !self.signatures.contains_key(name) => member.to_string(),
+            _ => format!("th_{}", mangle(name)),
+        }
+    }
+
+    /// The C name of an external function or global: its own for a typed `def`, and for a
+    /// member of an imported header a name of its own bound to the symbol of the member, so
+    /// that it does not clash with the declaration of the header.
+    fn extern_symbol(&self, name: &str) -> String {
+        match builtins::split_path(name) {
+            Some(_) => format!("th_{}", mangle(name)),
+            None => name.to_string(),
+        }
+    }
+
+    fn extern_declaration(&mut self, name: &str, var_type: &VarType) -> Result<String, String> {
+        let Some((_, member)) = builtins::split_path(name) else {
+            if mangle(name) != name {
+                return Err(format!("`{}` is not a valid C name", name));
+            }
+            return self.declaration(name, var_type, true);
+        };
+        let declaration = self.declaration(&self.extern_symbol(name), var_type, true)?;
+        Ok(format!(
+            "{} __asm__(TAHINI_XSTR(__USER_LABEL_PREFIX__) \"{}\");\n",
+            declaration.trim_end().trim_end_matches(';'),
+            member
+        ))
+    }
+
+    /// The declaration of the function or global `symbol`, defined in another object file. The
+    /// `fn` parameters of C functions are function pointers rather than closures.
+    fn declaration(
+        &mut self,
+        symbol: &str,
+        var_type: &VarType,
+        c_abi: bool,
+    ) -> Result<String, String> {
+        let (params, ret, variadic) = match var_type {
+            VarType::Fn(params, ret) => (params, ret, false),
+            VarType::FnWithVarArgs(params, ret) => (params, ret, true),
+            other => return Ok(format!("extern {};\n", self.variable(other, symbol)?)),
+        };
+        let mut list = Vec::new();
+        for param in params {
+            let normalized = self.aliases.normalize(param);
+            list.push(match normalized {
+                VarType::Fn(..) | VarType::FnWithVarArgs(..) if c_abi => {
+                    self.function_pointer(&normalized)?
+                }
+                _ => self.c_type(param)?,
+            });
+        }
+        if variadic {
+            list.push("...".to_string());
+        }
+        if list.is_empty() {
+            list.push("void".to_string());
+        }
+        Ok(format!(
+            "{} {}({});\n",
+            self.c_type(ret)?,
+            symbol,
+            list.join(", ")
+        ))
+    }
+
+    /// The C function pointer type of the `fn` type `fn_type`.
+    fn function_pointer(&mut self, fn_type: &VarType) -> Result<String, String> {
+        let (params, ret, variadic) = match fn_type {
+            VarType::Fn(params, ret) => (params, ret, false),
+            VarType::FnWithVarArgs(params, ret) => (params, ret, true),
+            other => return self.c_type(other),
+        };
+        let mut list = Vec::new();
+        for param in params {
+            list.push(self.c_type(param)?);
+        }
+        if variadic {
+            list.push("...".to_string());
+        }
+        if list.is_empty() {
+            list.push("void".to_string());
+        }
+        Ok(format!("{} (*)({})", self.c_type(ret)?, list.join(", ")))
+    }
+
+    fn signature(&mut self, function: &Function) -> Result<String, String> {
+        let mut params = Vec::new();
+        for (name, var_type) in &function.parameters {
+            params.push(self.variable(var_type, &format!("l_{}", mangle(name)))?);
+        }
+        if params.is_empty() {
+            params.push("void".to_string());
+        }
+        Ok(format!(
+            "{} th_{}({})",
+            self.c_type(&function.return_type)?,
+            mangle(&function.name),
+            params.join(", ")
+        ))
+    }
+
+    fn function(&mut self, function: &Function) -> Result<String, String> {
+        self.locals = function.locals.iter().cloned().collect();
+        self.temps = function
+            .blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+            .filter_map(|instruction| match instruction {
+                Instruction::Let(temp, var_type, _) => Some((temp.0, var_type.clone())),
+                _ => None,
+            })
+            .collect();
+
+        let mut out = format!("{} {{\n", self.signature(function)?);
+        for (name, var_type) in function.locals.iter().skip(function.parameters.len()) {
+            let declaration = self.variable(var_type, &format!("l_{}", mangle(name)))?;
+            writeln!(out, "    {};", declaration).unwrap();
+        }
+        let mut temps: Vec<_> = self.temps.clone().into_iter().collect();
+        temps.sort_by_key(|(temp, _)| *temp);
+        for (temp, var_type) in temps {
+            writeln!(
+                out,
+                "    {};",
+                self.variable(&var_type, &format!("t{}", temp))?
+            )
+            .unwrap();
+        }
+
+        let targets: HashSet<usize> = function
+            .blocks
+            .iter()
+            .flat_map(|block| match block.terminator {
+                Terminator::Jump(target) => vec![target.0],
+                Terminator::Branch(_, then, otherwise) => vec![then.0, otherwise.0],
+                _ => Vec::new(),
+            })
+            .collect();
+        for block in &function.blocks {
+            if targets.contains(&block.id.0) {
+                writeln!(out, "{}:;", block.id).unwrap();
+            }
+            out.push_str(&self.block(block, &function.return_type)?);
+        }
+        out.push_str("}\n");
+        Ok(out)
+    }
+
+    fn block(&mut self, block: &Block, return_type: &VarType) -> Result<String, String> {
+        let mut out = String::new();
+        for instruction in &block.instructions {
+            match instruction {
+                Instruction::Let(temp, var_type, value) => {
+                    let value = self.value(value, Some(var_type))?;
+                    writeln!(out, "    t{} = {};", temp.0, value).unwrap();
+                }
+                Instruction::Store(_, Operand::Const(Const::Void)) => {}
+                Instruction::Store(place, value) => {
+                    let place_type = self.place_type(place);
+                    let value = self.operand_as(value, &place_type)?;
+                    writeln!(out, "    {} = {};", self.place(place)?, value).unwrap();
+                }
+                Instruction::Eval(value) => {
+                    writeln!(out, "    {};", self.value(value, None)?).unwrap();
+                }
+            }
+        }
+        let terminator = match &block.terminator {
+            Terminator::Jump(target) => format!("goto {};", target),
+            Terminator::Branch(condition, then, otherwise) => format!(
+                "if ({}) goto {}; else goto {};",
+                self.operand(condition)?,
+                then,
+                otherwise
+            ),
+            Terminator::Return(Some(value)) => {
+                format!("return {};", self.operand_as(value, return_type)?)
+            }
+            Terminator::Return(None) => "return;".to_string(),
+            Terminator::Unreachable => "abort();".to_string(),
+        };
+        writeln!(out, "    {}", terminator).unwrap();
+        Ok(out)
+    }
+
+    fn place(&mut self, place: &Place) -> Result<String, String> {
+        Ok(match place {
+            Place::Local(name) => format!("l_{}", mangle(name)),
+            Place::Global(name) if self.externs.contains(name) => self.extern_symbol(name),
+            Place::Global(name) => format!("th_{}", mangle(name)),
+            Place::Deref(address) => format!("*{}", self.operand(address)?),
+        })
+    }
+
+    fn place_type(&self, place: &Place) -> VarType {
+        match place {
+            Place::Local(name) => self.locals.get(name).cloned(),
+            Place::Global(name) => self.globals.get(name).cloned(),
+            Place::Deref(address) => match self.aliases.normalize(&self.operand_type(address)) {
+                VarType::Ptr(pointee) => Some(*pointee),
+                _ => None,
+            },
+        }
+        .unwrap_or(VarType::Void)
+    }
+
+    fn operand_type(&self, operand: &Operand) -> VarType {
+        match operand {
+            Operand::Temp(temp) => self.temps.get(&temp.0).cloned().unwrap_or(VarType::Void),
+            Operand::Const(value) => match value {
+                Const::Int(_, var_type)
+                | Const::Float(_, var_type)
+                | Const::Tuple(_, var_type)
+                | Const::Array(_, var_type)
+                | Const::Data(_, _, var_type) => var_type.clone(),
+                Const::Bool(_) => VarType::Bool,
+                Const::Char(_) => VarType::Int8,
+                Const::String(_) => str_type(),
+                Const::Atom(_) => atom_type(),
+                Const::Fn(name) => match self.signatures.get(name) {
+                    Some((params, ret)) => VarType::Fn(params.clone(), Box::new(ret.clone())),
+                    None => VarType::Void,
+                },
+                Const::Callback(_) => VarType::Ptr(Box::new(VarType::Void)),
+                Const::Void => VarType::Void,
+            },
+        }
+    }
+
+    fn operand(&mut self, operand: &Operand) -> Result<String, String> {
+        match operand {
+            Operand::Temp(temp) => Ok(format!("t{}", temp.0)),
+            Operand::Const(Const::Fn(name)) => {
+                let code = self.trampoline(name, Trampoline::Plain);
+                Ok(format!("(tahini_closure){{{}, NULL}}", code))
+            }
+            Operand::Const(value) => self.constant(value),
+        }
+    }
+
+    /// `operand` converted to `target` where C does not convert implicitly: a sized array
+    /// passed as an unsized one decays to a pointer to its elements, and a callback is cast to
+    /// the function pointer C takes, whose pointer parameters may point to other types.
+    fn operand_as(&mut self, operand: &Operand, target: &VarType) -> Result<String, String> {
+        let value = self.operand(operand)?;
+        let from = self.aliases.normalize(&self.operand_type(operand));
+        let target = self.aliases.normalize(target);
+        match (from, &target) {
+            (VarType::ArraySized(..), VarType::ArrayUnsized(_)) => Ok(format!("{}.items", value)),
+            (_, VarType::Fn(..) | VarType::FnWithVarArgs(..))
+                if matches!(operand, Operand::Const(Const::Callback(_))) =>
+            {
+                Ok(format!("({}){}", self.function_pointer(&target)?, value))
+            }
+            _ => Ok(value),
+        }
+    }
+
+    fn operands_as(&mut self, operands: &[Operand], types: &[VarType]) -> Result<String, String> {
+        let mut list = Vec::new();
+        for (i, operand) in operands.iter().enumerate() {
+            list.push(match types.get(i) {
+                Some(target) => self.operand_as(operand, target)?,
+                None => self.operand(operand)?,
+            });
+        }
+        Ok(list.join(", "))
+    }
+
+    /// A constant as a C constant expression. Functions are closures, which like tuples, arrays
+    /// and `data` values are only constant as initializers.
+    fn constant(&mut self, value: &Const) -> Result<String, String> {
+        Ok(match value {
+            Const::Int(value, var_type) => int_literal(*value, var_type),
+            Const::Float(value, var_type) => float_literal(*value, var_type),
+            Const::Bool(value) => value.to_string(),
+            Const::Char(value) => match value {
+                ' '..='~' if !matches!(value, '\'' | '\\') => format!("'{}'", value),
+                other => (*other as u32).to_string(),
+            },
+            Const::String(value) => string_literal(value),
+            Const::Atom(name) => {
+                self.atoms.insert(name.clone());
+                format!("tahini_atom_{}", mangle(name))
+            }
+            Const::Fn(name) => {
+                let code = self.trampoline(name, Trampoline::Plain);
+                format!("{{{}, NULL}}", code)
+            }
+            // C functions are passed as they are.
+            Const::Callback(name) if self.externs.contains(name) => self.extern_symbol(name),
+            Const::Callback(name) => self.trampoline(name, Trampoline::Callback),
+            Const::Tuple(members, _) | Const::Array(members, _) if members.is_empty() => {
+                "{0}".to_string()
+            }
+            Const::Tuple(members, _) => format!("{{{}}}", self.constants(members)?),
+            Const::Array(members, _) => format!("{{{{{}}}}}", self.constants(members)?),
+            Const::Data(tag, members, var_type) => {
+                let variants = self.aliases.data_variants(var_type).unwrap_or_default();
+                let Some(index) = variants.iter().position(|(t, _)| t == tag) else {
+                    return Err(format!("`{}` has no variant `:{}`", var_type, tag));
+                };
+                match members.is_empty() {
+                    true => format!("{{.tag = {}}}", index),
+                    false => format!(
+                        "{{.tag = {}, .as.v_{} = {{{}}}}}",
+                        index,
+                        mangle(tag),
+                        self.constants(members)?
+                    ),
+                }
+            }
+            Const::Void => "0".to_string(),
+        })
+    }
+
+    fn constants(&mut self, values: &[Const]) -> Result<String, String> {
+        let mut list = Vec::new();
+        for value in values {
+            list.push(self.constant(value)?);
+        }
+        Ok(list.join(", "))
+    }
+
+    fn value(&mut self, value: &Value, var_type: Option<&VarType>) -> Result<String, String> {
+        let result_type = || var_type.cloned().unwrap_or(VarType::Void);
+        Ok(match value {
+            Value::Load(place) => self.place(place)?,
+            Value::Addr(Place::Deref(address)) => self.operand(address)?,
+            Value::Addr(place) => format!("&{}", self.place(place)?),
+            Value::Field(address, field) => {
+                format!("&{}->f_{}", self.operand(address)?, mangle(field))
+            }
+            Value::Index(address, index) => {
+                let pointee = match self.aliases.normalize(&self.operand_type(address)) {
+                    VarType::Ptr(pointee) => self.aliases.normalize(&pointee),
+                    // An unsized array is a pointer to its first element.
+                    VarType::ArrayUnsized(element) => self.aliases.normalize(&element),
+                    other => return Err(format!("cannot index through `{}`", other)),
+                };
+                let base = self.operand(address)?;
+                match (pointee, index) {
+                    (VarType::ArraySized(..), _) => {
+                        format!("&{}->items[{}]", base, self.operand(index)?)
+                    }
+                    (VarType::ArrayUnsized(_), _) => {
+                        format!("&(*{})[{}]", base, self.operand(index)?)
+                    }
+                    (VarType::Tuple(_), Operand::Const(Const::Int(i, _))) => {
+                        format!("&{}->_{}", base, i)
+                    }
+                    (VarType::Tuple(_), _) => {
+                        return Err("tuples can only be indexed with constants".to_string())
+                    }
+                    _ => format!("&{}[{}]", base, self.operand(index)?),
+                }
+            }
+            Value::Unary(op, operand) => {
+                let op = match op.as_str() {
+                    "-" => "-",
+                    "!" | "not" => "!",
+                    "~" => "~",
+                    other => return Err(format!("`{}` is not a unary operator", other)),
+                };
+                format!("{}({})", op, self.operand(operand)?)
+            }
+            Value::Binary(op, left, right) => {
+                let operand_type = self.aliases.normalize(&self.operand_type(left));
+                let (left, right) = (self.operand(left)?, self.operand(right)?);
+                if op == "%" && is_float(&operand_type) {
+                    let function = match operand_type {
+                        VarType::Float32 => "fmodf",
+                        VarType::Float128 => "fmodl",
+                        _ => "fmod",
+                    };
+                    return Ok(format!("{}({}, {})", function, left, right));
+                }
+                let op = match op.as_str() {
+                    "=" => "==",
+                    "and" => "&&",
+                    "or" => "||",
+                    other => other,
+                };
+                format!("{} {} {}", left, op, right)
+            }
+            Value::Call(name, args) => {
+                let params = self
+                    .signatures
+                    .get(name)
+                    .map(|(params, _)| params.clone())
+                    .unwrap_or_default();
+                let args = self.operands_as(args, &params)?;
+                format!("{}({})", self.callee(name), args)
+            }
+            Value::CallClosure(closure, args) => {
+                let (params, ret) = match self.aliases.normalize(&self.operand_type(closure)) {
+                    VarType::Fn(params, ret) => (params, *ret),
+                    other => return Err(format!("cannot call a value of type `{}`", other)),
+                };
+                let code_type = self.code_type(&params, &ret)?;
+                let closure = self.operand(closure)?;
+                let mut all = vec![format!("{}.env", closure)];
+                if !args.is_empty() {
+                    all.push(self.operands_as(args, &params)?);
+                }
+                format!("(({}){}.fn)({})", code_type, closure, all.join(", "))
+            }
+            Value::Make(var_type, members) => {
+                let c_type = self.c_type(var_type)?;
+                match self.aliases.normalize(var_type) {
+                    VarType::Struct(fields) => {
+                        let types: Vec<_> = fields.into_iter().map(|(_, t)| t).collect();
+                        format!(
+                            "({}){}",
+                            c_type,
+                            braces(&self.operands_as(members, &types)?)
+                        )
+                    }
+                    VarType::Tuple(types) => {
+                        format!(
+                            "({}){}",
+                            c_type,
+                            braces(&self.operands_as(members, &types)?)
+                        )
+                    }
+                    VarType::ArraySized(element, _) => {
+                        let types = vec![*element; members.len()];
+                        format!(
+                            "({}){{{}}}",
+                            c_type,
+                            braces(&self.operands_as(members, &types)?)
+                        )
+                    }
+                    other => {
+                        let members = self.operands_as(members, &[other])?;
+                        format!("({})({})", c_type, members)
+                    }
+                }
+            }
+            Value::Tuple(items) => {
+                let var_type = result_type();
+                let types = match self.aliases.normalize(&var_type) {
+                    VarType::Tuple(types) => types,
+                    _ => Vec::new(),
+                };
+                let c_type = self.c_type(&var_type)?;
+                format!("({}){}", c_type, braces(&self.operands_as(items, &types)?))
+            }
+            Value::Array(items) => {
+                let var_type = result_type();
+                let types = match self.aliases.normalize(&var_type) {
+                    VarType::ArraySized(element, length) => vec![*element; length],
+                    _ => Vec::new(),
+                };
+                let c_type = self.c_type(&var_type)?;
+                match items.is_empty() {
+                    true => format!("({}){{0}}", c_type),
+                    false => format!("({}){{{{{}}}}}", c_type, self.operands_as(items, &types)?),
+                }
+            }
+            Value::Data(tag, members) => {
+                let var_type = result_type();
+                let variants = self.aliases.data_variants(&var_type).unwrap_or_default();
+                let Some(index) = variants.iter().position(|(t, _)| t == tag) else {
+                    return Err(format!("`{}` has no variant `:{}`", var_type, tag));
+                };
+                let c_type = self.c_type(&var_type)?;
+                match members.is_empty() {
+                    true => format!("({}){{.tag = {}}}", c_type, index),
+                    false => {
+                        let members = self.operands_as(members, &variants[index].1)?;
+                        format!(
+                            "({}){{.tag = {}, .as.v_{} = {{{}}}}}",
+                            c_type,
+                            index,
+                            mangle(tag),
+                            members
+                        )
+                    }
+                }
+            }
+            Value::MakeClosure(name, env) => {
+                let env_type = match self.signatures.get(name) {
+                    Some((params, _)) if !params.is_empty() => params[0].clone(),
+                    _ => return Err(format!("`{}` is not a lifted function", name)),
+                };
+                let fields = self.aliases.struct_fields(&env_type).unwrap_or_default();
+                let types: Vec<_> = fields.into_iter().map(|(_, t)| t).collect();
+                let members = self.operands_as(env, &types)?;
+                let c_type = self.c_type(&env_type)?;
+                let code = self.trampoline(name, Trampoline::Lifted);
+                let copy = self.copy(&env_type, &c_type, &members);
+                format!("(tahini_closure){{{}, {}}}", code, copy)
+            }
+            Value::Box(value) => {
+                let pointee = match self.aliases.normalize(&result_type()) {
+                    VarType::Ptr(pointee) => *pointee,
+                    _ => self.operand_type(value),
+                };
+                let c_type = self.c_type(&pointee)?;
+                let value = self.operand_as(value, &pointee)?;
+                format!("({} *){}", c_type, self.copy(&pointee, &c_type, &value))
+            }
+        })
+    }
+
+    /// A copy of the value `(c_type){members}` on the collected heap, which the collector only
+    /// scans if the value can hold pointers.
+    fn copy(&self, var_type: &VarType, c_type: &str, members: &str) -> String {
+        let alloc = match holds_pointers(var_type, &self.aliases) {
+            true => "tahini_alloc",
+            false => "tahini_alloc_atomic",
+        };
+        format!(
+            "tahini_copy(&({}){}, sizeof({}), {})",
+            c_type,
+            braces(members),
+            c_type,
+            alloc
+        )
+    }
+
+    /// The C type of the code pointer of a closure: the environment comes first.
+    fn code_type(&mut self, params: &[VarType], ret: &VarType) -> Result<String, String> {
+        let mut list = vec!["void *".to_string()];
+        for param in params {
+            list.push(self.c_type(param)?);
+        }
+        Ok(format!("{} (*)({})", self.c_type(ret)?, list.join(", ")))
+    }
+
+    /// The trampoline of the function `name`: a generic code pointer for closures, and the
+    /// function itself for callbacks.
+    fn trampoline(&mut self, name: &str, kind: Trampoline) -> String {
+        if !self.trampolines.contains(&(name.to_string(), kind)) {
+            self.trampolines.push((name.to_string(), kind));
+        }
+        match kind {
+            Trampoline::Callback => format!("th_{}_callback", mangle(name)),
+            _ => format!("(void (*)(void))th_{}_closure", mangle(name)),
+        }
+    }
+
+    /// Whether `name` is a lifted lambda, which takes its environment first.
+    fn is_lifted(&self, name: &str) -> bool {
+        self.program.functions.iter().any(|function| {
+            function.name == name
+                && function
+                    .parameters
+                    .first()
+                    .is_some_and(|(param, _)| param == lambda::ENV)
+        })
+    }
+
+    /// The definitions of all trampolines used so far.
+    fn trampolines(&mut self) -> Result<String, String> {
+        let mut out = String::new();
+        for (name, kind) in self.trampolines.clone() {
+            let Some((params, ret)) = self.signatures.get(&name).cloned() else {
+                return Err(format!("`{}` cannot be used as a value", name));
+            };
+            let lifted = match kind {
+                Trampoline::Lifted => true,
+                Trampoline::Plain => false,
+                Trampoline::Callback => self.is_lifted(&name),
+            };
+            let mut list = Vec::new();
+            let mut args = Vec::new();
+            match kind {
+                Trampoline::Lifted => {
+                    list.push("void *env".to_string());
+                    args.push(format!("*({} *)env", self.c_type(&params[0])?));
+                }
+                Trampoline::Plain => list.push("void *env".to_string()),
+                Trampoline::Callback if lifted => {
+                    args.push(format!("({}){{0}}", self.c_type(&params[0])?));
+                }
+                Trampoline::Callback => {}
+            }
+            for (i, param) in params.iter().enumerate().skip(lifted as usize) {
+                list.push(self.variable(param, &format!("a{}", i))?);
+                args.push(format!("a{}", i));
+            }
+            if list.is_empty() {
+                list.push("void".to_string());
+            }
+            let call = format!("{}({})", self.callee(&name), args.join(", "));
+            let call = match ret == VarType::Void {
+                true => format!("{};", call),
+                false => format!("return {};", call),
+            };
+            let (suffix, body) = match kind {
+                Trampoline::Plain => ("closure", format!("(void)env;\n    {}", call)),
+                Trampoline::Lifted => ("closure", call),
+                Trampoline::Callback => ("callback", call),
+            };
+            writeln!(
+                out,
+                "static {} th_{}_{}({}) {{\n    {}\n}}",
+                self.c_type(&ret)?,
+                mangle(&name),
+                suffix,
+                list.join(", "),
+                body
+            )
+            .unwrap();
+        }
+        Ok(out)
+    }
+
+    fn atom_definitions(&self) -> String {
+        let mut out = String::new();
+        for atom in &self.atoms {
+            writeln!(
+                out,
+                "static const char tahini_atom_{}[] = {};",
+                mangle(atom),
+                string_literal(atom)
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    /// The C `main` that calls the program's `main`, if there is one.
+    fn entry(&mut self) -> Result<String, String> {
+        let Some((params, ret)) = self.signatures.get("main").cloned() else {
+            return Ok(String::new());
+        };
+        if self.externs.contains("main") {
+            return Ok(String::new());
+        }
+        let (signature, args) = match params.as_slice() {
+            [] => ("int main(void)", String::new()),
+            [argc, argv] => {
+                let args = format!("({})argc, ({})argv", self.c_type(argc)?, self.c_type(argv)?);
+                ("int main(int argc, char **argv)", args)
+            }
+            _ => return Err("`main` takes no parameters or `argc` and `argv`".to_string()),
+        };
+        let body = match ret {
+            VarType::Void => format!("tahini_init();\n    th_main({});\n    return 0;", args),
+            _ => format!("tahini_init();\n    return (int)th_main({});", args),
+        };
+        Ok(format!("\n{} {{\n    {}\n}}\n", signature, body))
+    }
+}
+
+fn int_literal(value: i64, var_type: &VarType) -> String {
+    let literal = match var_type {
+        VarType::UInt64 | VarType::UInt128 => format!("UINT64_C({})", value as u64),
+        VarType::UInt32 => format!("{}u", value as u32),
+        _ if value == i64::MIN => "(-INT64_MAX - 1)".to_string(),
+        VarType::Int64 | VarType::Int128 => format!("INT64_C({})", value),
+        _ => value.to_string(),
+    };
+    match value < 0 && !literal.starts_with('(') {
+        true => format!("({})", literal),
+        false => literal,
+    }
+}
+
+fn float_literal(value: f64, var_type: &VarType) -> String {
+    if value.is_nan() {
+        return "NAN".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 {
+            "INFINITY"
+        } else {
+            "(-INFINITY)"
+        }
+        .to_string();
+    }
+    let suffix = match var_type {
+        VarType::Float32 | VarType::Float16 => "f",
+        VarType::Float128 => "L",
+        _ => "",
+    };
+    let literal = format!("{:?}{}", value, suffix);
+    match value < 0.0 {
+        true => format!("({})", literal),
+        false => literal,
+    }
+}
+
+/// An initializer list of `members`. C has no empty braces, and every type it is used for
+/// has a first member, so no members are `{0}`.
+fn braces(members: &str) -> String {
+    match members.is_empty() {
+        true => "{0}".to_string(),
+        false => format!("{{{}}}", members),
+    }
+}
+
+/// `value` as a C string literal. Escape sequences are kept; control characters, `?` (which
+/// could start a trigraph) and characters beyond ASCII are escaped.
+fn string_literal(value: &str) -> String {
+    let mut literal = String::from("\"");
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                literal.push('\\');
+                if let Some(escaped) = chars.next() {
+                    literal.push(escaped);
+                }
+            }
+            '"' => literal.push_str("\\\""),
+            '?' => literal.push_str("\\?"),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\r' => literal.push_str("\\r"),
+            ' '..='~' => literal.push(c),
+            other => {
+                let mut bytes = [0; 4];
+                for byte in other.encode_utf8(&mut bytes).bytes() {
+                    write!(literal, "\\{:03o}", byte).unwrap();
+                }
+            }
+        }
+    }
+    literal.push('"');
+    literal
+}
@@ -0,0 +1,198 @@
+#[cfg(test)]
+mod tests {
+    use crate::codegen::c::emit;
+    use crate::codegen::{mangle, RUNTIME};
+    use crate::ir::lower::lower;
+    use crate::parser::parser;
+    use crate::transformer::transform;
+    use chumsky::Parser;
+    use std::path::Path;
+    use std::process::Command;
+
+    fn emitted(src: &str) -> String {
+        let module = parser().parse(src).into_output().unwrap();
+        let transformed = transform(&module).unwrap_or_else(|errors| panic!("{:?}", errors));
+        let program = lower(&transformed).unwrap_or_else(|errors| panic!("{:?}", errors));
+        emit(&program).unwrap()
+    }
+
+    /// Compare the C of `golden/<name>.th` with `golden/<name>.c`.
+    /// Run with `UPDATE_GOLDEN=1` to rewrite the expected output.
+    fn check_golden(name: &str) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/codegen/golden");
+        let source = std::fs::read_to_string(dir.join(format!("{}.th", name))).unwrap();
+        let c = emitted(&source);
+
+        let golden = dir.join(format!("{}.c", name));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&golden, &c).unwrap();
+        }
+        let expected = std::fs::read_to_string(&golden).unwrap();
+        assert_eq!(c, expected, "golden C of `{}` changed", name);
+    }
+
+    /// Compile `src` with the system C compiler, link it with the runtime without the collector
+    /// and run it, or `None` if there is no `cc`.
+    fn run(name: &str, src: &str) -> Option<(i32, String)> {
+        let dir = std::env::temp_dir().join(format!("tahini-c-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let c = dir.join("main.c");
+        let runtime = dir.join("runtime.c");
+        let binary = dir.join("main");
+        std::fs::write(&c, emitted(src)).unwrap();
+        std::fs::write(&runtime, RUNTIME).unwrap();
+
+        let status = Command::new("cc")
+            .args([
+                "-std=c11",
+                "-pedantic-errors",
+                "-Werror=implicit-function-declaration",
+                "-DTAHINI_NO_GC",
+            ])
+            .arg(&c)
+            .arg(&runtime)
+            .arg("-o")
+            .arg(&binary)
+            .arg("-lm")
+            .status()
+            .ok()?;
+        assert!(status.success(), "`cc` rejected the C of `{}`", name);
+        let output = Command::new(&binary).output().unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        Some((
+            output.status.code().unwrap(),
+            String::from_utf8(output.stdout).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn test_golden_closures() {
+        check_golden("closures");
+    }
+
+    #[test]
+    fn test_golden_data() {
+        check_golden("data");
+    }
+
+    #[test]
+    fn test_mangle() {
+        assert_eq!(mangle("fib"), "fib");
+        assert_eq!(mangle("make-counter"), "make_2Dcounter");
+        assert_eq!(mangle("lambda$0$env"), "lambda_240_24env");
+        assert_eq!(mangle("snake_case"), "snake__case");
+        assert_eq!(mangle("π"), "_u0003C0");
+    }
+
+    #[test]
+    fn test_compiled_program_runs() {
+        let src = "(def stdio (use :header \"stdio.h\")) \n
+            (type point (struct (:x i64) (:y i64))) \n
+            (def fib (fn [(:n i64)] i64 (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))) \n
+            (def make-adder (fn [(:n i64)] fn [i64] i64 (fn [(:x i64)] i64 (+ x n)))) \n
+            (def main (fn [] i32 \n
+                (do \n
+                    (def add5 (make-adder 5)) \n
+                    (def p (point 1 2)) \n
+                    ($ :y p (add5 ($ :y p))) \n
+                    (stdio/printf \"%ld %ld\\n\" (fib 10) ($ :y p)) \n
+                    3)))";
+        let Some((status, stdout)) = run("program", src) else {
+            return;
+        };
+        assert_eq!(status, 3);
+        assert_eq!(stdout, "55 7\n");
+    }
+
+    #[test]
+    fn test_lambdas_without_captures_run() {
+        let src = "(def stdio (use :header \"stdio.h\")) \n
+            (def apply (fn [(:f fn [i64] i64) (:x i64)] i64 (f x))) \n
+            (def main (fn [] i32 \n
+                (do \n
+                    (stdio/printf \"%ld\\n\" (apply (fn [(:x i64)] i64 (* x 2)) 21)) \n
+                    0)))";
+        let Some((status, stdout)) = run("lambdas", src) else {
+            return;
+        };
+        assert_eq!(status, 0);
+        assert_eq!(stdout, "42\n");
+    }
+
+    #[test]
+    fn test_folded_globals_are_initialized() {
+        let src = "(def stdio (use :header \"stdio.h\")) \n
+            (type shape (data [:circle f64] [:rect i32 i32] [:none])) \n
+            (def PAIR {(+ 1 1) [(* 2 2) 5]}) \n
+            (def SHAPES [[:circle 1.5] [:none] [:rect 7 8]]) \n
+            (def main (fn [] i32 \n
+                (do \n
+                    (def inner ($ [1] PAIR)) \n
+                    (stdio/printf \"%d %d %d\\n\" ($ [0] PAIR) ($ [0] inner) ($ [1] inner)) \n
+                    0)))";
+        let Some((status, stdout)) = run("globals", src) else {
+            return;
+        };
+        assert_eq!(status, 0);
+        assert_eq!(stdout, "2 4 5\n");
+    }
+
+    #[test]
+    fn test_unsized_arrays_are_pointers() {
+        let c = emitted(
+            "(def first (fn [(:xs [i32])] i32 ($ [0] xs))) \n
+            (def total (fn [] i32 (first [1 2 3])))",
+        );
+        assert!(c.contains("t2 = &t1[INT64_C(0)];"), "{}", c);
+        assert!(c.contains("t1 = th_first(t0.items);"), "{}", c);
+    }
+
+    #[test]
+    fn test_unary_operators() {
+        let c = emitted("(def f (fn [(:x i32) (:b bool)] i32 (if (not b) (- (+ x)) x)))");
+        assert!(c.contains("t1 = !(t0);"), "{}", c);
+        assert!(c.contains("t3 = -(t2);"), "{}", c);
+        assert!(!c.contains("+("), "{}", c);
+    }
+
+    #[test]
+    fn test_strings_keep_their_escapes() {
+        let c = emitted("(def greeting \"who?? \\tme\\n\")");
+        assert!(
+            c.contains(r#"char *th_greeting = "who\?\? \tme\n";"#),
+            "{}",
+            c
+        );
+    }
+
+    #[test]
+    fn test_callbacks_are_function_pointers() {
+        let c = emitted(
+            "(def sortints fn [(ptr i32) fn [(ptr i32) (ptr i32)] i32] void) \n
+            (def less (fn [(:a (ptr i32)) (:b (ptr i32))] i32 (- ($ [0] a) ($ [0] b)))) \n
+            (def f (fn [(:xs (ptr i32))] void \n
+                (do (sortints xs less) (sortints xs (fn [(:a (ptr i32)) (:b (ptr i32))] i32 0)))))",
+        );
+        assert!(
+            c.contains("void sortints(int32_t *, int32_t (*)(int32_t *, int32_t *));"),
+            "{}",
+            c
+        );
+        assert!(
+            c.contains("static int32_t th_less_callback(int32_t *a0, int32_t *a1) {"),
+            "{}",
+            c
+        );
+        // A lambda that captures nothing gets an empty environment.
+        assert!(
+            c.contains("return th_lambda_240((th_lambda_240_24env_t){0}, a1, a2);"),
+            "{}",
+            c
+        );
+        assert!(
+            c.contains("sortints(t0, (int32_t (*)(int32_t *, int32_t *))th_less_callback);"),
+            "{}",
+            c
+        );
+    }
+}
@@ -0,0 +1,107 @@
+/* Generated by tahini. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+#include <math.h>
+
+/* The tahini runtime (runtime/tahini.c). */
+void tahini_init(void);
+void *tahini_alloc(size_t size);
+void *tahini_alloc_atomic(size_t size);
+
+typedef const char *tahini_atom;
+
+typedef struct {
+    void (*fn)(void);
+    void *env;
+} tahini_closure;
+
+static inline void *tahini_copy(const void *value, size_t size, void *(*alloc)(size_t)) {
+    void *copy = alloc(size);
+    memcpy(copy, value, size);
+    return copy;
+}
+
+typedef struct th_lambda_240_24env_t th_lambda_240_24env_t;
+
+struct th_lambda_240_24env_t {
+    int64_t *f_count_24box;
+};
+
+int64_t th_lambda_240(th_lambda_240_24env_t l__24env);
+tahini_closure th_make_2Dcounter(void);
+int64_t th_apply(tahini_closure l_f, int64_t l_x);
+int64_t th_double(int64_t l_x);
+int64_t th_twice(void);
+
+static int64_t th_lambda_240_closure(void *env) {
+    return th_lambda_240(*(th_lambda_240_24env_t *)env);
+}
+static int64_t th_double_closure(void *env, int64_t a0) {
+    (void)env;
+    return th_double(a0);
+}
+
+int64_t th_lambda_240(th_lambda_240_24env_t l__24env) {
+    int64_t *l_count_24box;
+    th_lambda_240_24env_t *t0;
+    int64_t **t1;
+    int64_t *t2;
+    int64_t *t3;
+    int64_t *t4;
+    int64_t t5;
+    int64_t t6;
+    int64_t *t7;
+    int64_t t8;
+    t0 = &l__24env;
+    t1 = &t0->f_count_24box;
+    t2 = *t1;
+    l_count_24box = t2;
+    t3 = l_count_24box;
+    t4 = l_count_24box;
+    t5 = *t4;
+    t6 = t5 + INT64_C(1);
+    *t3 = t6;
+    t7 = l_count_24box;
+    t8 = *t7;
+    return t8;
+}
+
+tahini_closure th_make_2Dcounter(void) {
+    int64_t *l_count_24box;
+    int64_t *t0;
+    int64_t *t1;
+    tahini_closure t2;
+    t0 = (int64_t *)tahini_copy(&(int64_t){INT64_C(0)}, sizeof(int64_t), tahini_alloc_atomic);
+    l_count_24box = t0;
+    t1 = l_count_24box;
+    t2 = (tahini_closure){(void (*)(void))th_lambda_240_closure, tahini_copy(&(th_lambda_240_24env_t){t1}, sizeof(th_lambda_240_24env_t), tahini_alloc)};
+    return t2;
+}
+
+int64_t th_apply(tahini_closure l_f, int64_t l_x) {
+    tahini_closure t0;
+    int64_t t1;
+    int64_t t2;
+    t0 = l_f;
+    t1 = l_x;
+    t2 = ((int64_t (*)(void *, int64_t))t0.fn)(t0.env, t1);
+    return t2;
+}
+
+int64_t th_double(int64_t l_x) {
+    int64_t t0;
+    int64_t t1;
+    t0 = l_x;
+    t1 = t0 * INT64_C(2);
+    return t1;
+}
+
+int64_t th_twice(void) {
+    int64_t t0;
+    t0 = th_apply((tahini_closure){(void (*)(void))th_double_closure, NULL}, INT64_C(21));
+    return t0;
+}
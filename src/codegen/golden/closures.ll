@@ -0,0 +1,89 @@
+; Generated by tahini.
+
+declare ptr @tahini_alloc(i64)
+declare ptr @tahini_alloc_atomic(i64)
+
+define private i64 @th_lambda_240_closure(ptr %env) {
+  %unpacked = load { ptr }, ptr %env
+  %result = call i64 @th_lambda_240({ ptr } %unpacked)
+  ret i64 %result
+}
+
+define private i64 @th_double_closure(ptr %env, i64 %a0) {
+  %result = call i64 @th_double(i64 %a0)
+  ret i64 %result
+}
+
+
+define i64 @th_lambda_240({ ptr } %arg.$env) {
+entry:
+  %slot.$env = alloca { ptr }
+  %slot.count$box = alloca ptr
+  store { ptr } %arg.$env, ptr %slot.$env
+  br label %bb0
+bb0:
+  %t0 = getelementptr i8, ptr %slot.$env, i64 0
+  %t1 = getelementptr { ptr }, ptr %t0, i32 0, i32 0
+  %t2 = load ptr, ptr %t1
+  store ptr %t2, ptr %slot.count$box
+  %t3 = load ptr, ptr %slot.count$box
+  %t4 = load ptr, ptr %slot.count$box
+  %t5 = load i64, ptr %t4
+  %t6 = add i64 %t5, 1
+  store i64 %t6, ptr %t3
+  %t7 = load ptr, ptr %slot.count$box
+  %t8 = load i64, ptr %t7
+  ret i64 %t8
+}
+
+define { ptr, ptr } @th_make_2Dcounter() {
+entry:
+  %slot.count$box = alloca ptr
+  br label %bb0
+bb0:
+  %h0 = call ptr @tahini_alloc_atomic(i64 ptrtoint (ptr getelementptr (i64, ptr null, i32 1) to i64))
+  store i64 0, ptr %h0
+  %t0 = getelementptr i8, ptr %h0, i64 0
+  store ptr %t0, ptr %slot.count$box
+  %t1 = load ptr, ptr %slot.count$box
+  %h1 = insertvalue { ptr } undef, ptr %t1, 0
+  %h2 = call ptr @tahini_alloc(i64 ptrtoint (ptr getelementptr ({ ptr }, ptr null, i32 1) to i64))
+  store { ptr } %h1, ptr %h2
+  %t2 = insertvalue { ptr, ptr } { ptr @th_lambda_240_closure, ptr undef }, ptr %h2, 1
+  ret { ptr, ptr } %t2
+}
+
+define i64 @th_apply({ ptr, ptr } %arg.f, i64 %arg.x) {
+entry:
+  %slot.f = alloca { ptr, ptr }
+  %slot.x = alloca i64
+  store { ptr, ptr } %arg.f, ptr %slot.f
+  store i64 %arg.x, ptr %slot.x
+  br label %bb0
+bb0:
+  %t0 = load { ptr, ptr }, ptr %slot.f
+  %t1 = load i64, ptr %slot.x
+  %h0 = extractvalue { ptr, ptr } %t0, 0
+  %h1 = extractvalue { ptr, ptr } %t0, 1
+  %t2 = call i64 %h0(ptr %h1, i64 %t1)
+  ret i64 %t2
+}
+
+define i64 @th_double(i64 %arg.x) {
+entry:
+  %slot.x = alloca i64
+  store i64 %arg.x, ptr %slot.x
+  br label %bb0
+bb0:
+  %t0 = load i64, ptr %slot.x
+  %t1 = mul i64 %t0, 2
+  ret i64 %t1
+}
+
+define i64 @th_twice() {
+entry:
+  br label %bb0
+bb0:
+  %t0 = call i64 @th_apply({ ptr, ptr } { ptr @th_double_closure, ptr null }, i64 21)
+  ret i64 %t0
+}
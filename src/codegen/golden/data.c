@@ -0,0 +1,167 @@
+/* Generated by tahini. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+#include <math.h>
+#include "stdio.h"
+
+/* The tahini runtime (runtime/tahini.c). */
+void tahini_init(void);
+void *tahini_alloc(size_t size);
+void *tahini_alloc_atomic(size_t size);
+
+typedef const char *tahini_atom;
+
+typedef struct {
+    void (*fn)(void);
+    void *env;
+} tahini_closure;
+
+static inline void *tahini_copy(const void *value, size_t size, void *(*alloc)(size_t)) {
+    void *copy = alloc(size);
+    memcpy(copy, value, size);
+    return copy;
+}
+
+typedef struct th_shape_t th_shape_t;
+typedef struct th_point_t th_point_t;
+typedef struct tahini_array_2 tahini_array_2;
+
+struct th_point_t {
+    double f_x;
+    double f_y;
+};
+
+struct th_shape_t {
+    uint32_t tag;
+    union {
+        struct {
+            double _0;
+        } v_circle;
+        struct {
+            th_point_t _0;
+            th_point_t _1;
+        } v_rect;
+    } as;
+};
+
+struct tahini_array_2 {
+    int32_t items[4];
+};
+
+static const char tahini_atom_ok[] = "ok";
+
+th_shape_t th_unit(void);
+void th_stretch(th_point_t *l_p);
+int32_t th_sum(tahini_array_2 l_xs);
+int32_t th_main(void);
+
+double th_scale = 2.0;
+tahini_atom th_status = tahini_atom_ok;
+
+th_shape_t th_unit(void) {
+    th_shape_t t0;
+    t0 = (th_shape_t){.tag = 0, .as.v_circle = {1.0}};
+    return t0;
+}
+
+void th_stretch(th_point_t *l_p) {
+    th_point_t **t0;
+    th_point_t *t1;
+    double *t2;
+    th_point_t **t3;
+    th_point_t *t4;
+    double *t5;
+    double t6;
+    double t7;
+    double t8;
+    t0 = &l_p;
+    t1 = *t0;
+    t2 = &t1->f_x;
+    t3 = &l_p;
+    t4 = *t3;
+    t5 = &t4->f_x;
+    t6 = *t5;
+    t7 = th_scale;
+    t8 = t6 * t7;
+    *t2 = t8;
+    return;
+}
+
+int32_t th_sum(tahini_array_2 l_xs) {
+    int32_t l_total;
+    tahini_array_2 l__240;
+    int64_t l__241;
+    int32_t l_x;
+    tahini_array_2 t0;
+    tahini_array_2 *t1;
+    int64_t t2;
+    bool t3;
+    int32_t *t4;
+    int32_t t5;
+    int32_t t6;
+    int32_t t7;
+    int32_t t8;
+    int64_t t9;
+    int64_t t10;
+    int32_t t11;
+    l_total = 0;
+    t0 = l_xs;
+    l__240 = t0;
+    t1 = &l__240;
+    l__241 = INT64_C(0);
+    goto bb1;
+bb1:;
+    t2 = l__241;
+    t3 = t2 < INT64_C(4);
+    if (t3) goto bb2; else goto bb4;
+bb2:;
+    t4 = &t1->items[t2];
+    t5 = *t4;
+    l_x = t5;
+    t6 = l_total;
+    t7 = l_x;
+    t8 = t6 + t7;
+    l_total = t8;
+    goto bb3;
+bb3:;
+    t9 = l__241;
+    t10 = t9 + INT64_C(1);
+    l__241 = t10;
+    goto bb1;
+bb4:;
+    t11 = l_total;
+    return t11;
+}
+
+int32_t th_main(void) {
+    int32_t l__240;
+    tahini_array_2 t0;
+    int32_t t1;
+    tahini_atom t2;
+    bool t3;
+    int32_t t4;
+    t0 = (tahini_array_2){{1, 2, 3, 4}};
+    t1 = th_sum(t0);
+    printf("%d\n", t1);
+    t2 = th_status;
+    t3 = t2 == tahini_atom_ok;
+    if (t3) goto bb1; else goto bb2;
+bb1:;
+    l__240 = 0;
+    goto bb3;
+bb2:;
+    l__240 = 1;
+    goto bb3;
+bb3:;
+    t4 = l__240;
+    return t4;
+}
+
+int main(void) {
+    tahini_init();
+    return (int)th_main();
+}
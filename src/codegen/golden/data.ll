@@ -0,0 +1,112 @@
+; Generated by tahini.
+
+@.str.0 = private unnamed_addr constant [4 x i8] c"%d\0A\00"
+@"tahini.atom.ok" = private constant [3 x i8] c"ok\00"
+
+@th_scale = global double 0x4000000000000000
+@th_status = global ptr @"tahini.atom.ok"
+
+declare void @printf(...)
+declare void @tahini_init()
+
+define { i32, [4 x i64] } @th_unit() {
+entry:
+  %h0 = alloca { i32, [4 x i64] }
+  br label %bb0
+bb0:
+  store i32 0, ptr %h0
+  %h1 = insertvalue { double } undef, double 0x3FF0000000000000, 0
+  %h2 = getelementptr { i32, [4 x i64] }, ptr %h0, i32 0, i32 1
+  store { double } %h1, ptr %h2
+  %t0 = load { i32, [4 x i64] }, ptr %h0
+  ret { i32, [4 x i64] } %t0
+}
+
+define void @th_stretch(ptr %arg.p) {
+entry:
+  %slot.p = alloca ptr
+  store ptr %arg.p, ptr %slot.p
+  br label %bb0
+bb0:
+  %t0 = getelementptr i8, ptr %slot.p, i64 0
+  %t1 = load ptr, ptr %t0
+  %t2 = getelementptr { double, double }, ptr %t1, i32 0, i32 0
+  %t3 = getelementptr i8, ptr %slot.p, i64 0
+  %t4 = load ptr, ptr %t3
+  %t5 = getelementptr { double, double }, ptr %t4, i32 0, i32 0
+  %t6 = load double, ptr %t5
+  %t7 = load double, ptr @th_scale
+  %t8 = fmul double %t6, %t7
+  store double %t8, ptr %t2
+  ret void
+}
+
+define i32 @th_sum([4 x i32] %arg.xs) {
+entry:
+  %slot.xs = alloca [4 x i32]
+  %slot.total = alloca i32
+  %slot.$0 = alloca [4 x i32]
+  %slot.$1 = alloca i64
+  %slot.x = alloca i32
+  store [4 x i32] %arg.xs, ptr %slot.xs
+  br label %bb0
+bb0:
+  store i32 0, ptr %slot.total
+  %t0 = load [4 x i32], ptr %slot.xs
+  store [4 x i32] %t0, ptr %slot.$0
+  %t1 = getelementptr i8, ptr %slot.$0, i64 0
+  store i64 0, ptr %slot.$1
+  br label %bb1
+bb1:
+  %t2 = load i64, ptr %slot.$1
+  %t3 = icmp slt i64 %t2, 4
+  br i1 %t3, label %bb2, label %bb4
+bb2:
+  %t4 = getelementptr [4 x i32], ptr %t1, i64 0, i64 %t2
+  %t5 = load i32, ptr %t4
+  store i32 %t5, ptr %slot.x
+  %t6 = load i32, ptr %slot.total
+  %t7 = load i32, ptr %slot.x
+  %t8 = add i32 %t6, %t7
+  store i32 %t8, ptr %slot.total
+  br label %bb3
+bb3:
+  %t9 = load i64, ptr %slot.$1
+  %t10 = add i64 %t9, 1
+  store i64 %t10, ptr %slot.$1
+  br label %bb1
+bb4:
+  %t11 = load i32, ptr %slot.total
+  ret i32 %t11
+}
+
+define i32 @th_main() {
+entry:
+  %slot.$0 = alloca i32
+  br label %bb0
+bb0:
+  %h0 = insertvalue [4 x i32] undef, i32 1, 0
+  %h1 = insertvalue [4 x i32] %h0, i32 2, 1
+  %h2 = insertvalue [4 x i32] %h1, i32 3, 2
+  %t0 = insertvalue [4 x i32] %h2, i32 4, 3
+  %t1 = call i32 @th_sum([4 x i32] %t0)
+  call void (...) @printf(ptr @.str.0, i32 %t1)
+  %t2 = load ptr, ptr @th_status
+  %t3 = icmp eq ptr %t2, @"tahini.atom.ok"
+  br i1 %t3, label %bb1, label %bb2
+bb1:
+  store i32 0, ptr %slot.$0
+  br label %bb3
+bb2:
+  store i32 1, ptr %slot.$0
+  br label %bb3
+bb3:
+  %t4 = load i32, ptr %slot.$0
+  ret i32 %t4
+}
+
+define i32 @main(i32 %argc, ptr %argv) {
+  call void @tahini_init()
+  %status = call i32 @th_main()
+  ret i32 %status
+}
@@ -0,0 +1,1282 @@
+//! LLVM IR backend.
+//!
+//! A program becomes the text of one LLVM module (`.ll`) that `llc` or `clang` compile, so the
+//! crate does not link against LLVM. The text uses opaque pointers (`ptr`), the default since
+//! LLVM 15.
+//!
+//! Functions and globals get the names the C backend gives them (`th_` and the mangled name),
+//! so objects from both backends link against each other. Functions declared with a typed
+//! `def` keep their C names. Functions of imported headers are declared with the types of
+//! their prototypes, read by [`crate::header`]. Only members that the header does not declare
+//! are declared variadic, `(...)`, returning the type the call expects; their arguments get
+//! C's default promotions.
+//!
+//! Types map onto LLVM as follows:
+//!
+//! - integers are `iN` of their width, signed or not; `f16`, `f32`, `f64` and `f128` are
+//!   `half`, `float`, `double` and `fp128`; `bool` is `i1`.
+//! - pointers, unsized arrays, `str` and atoms are `ptr`.
+//! - structs and tuples are literal structs, sized arrays are arrays. Named types are
+//!   expanded, so a type and its alias are the same LLVM type.
+//! - a `data` value is `{ i32, [N x iA] }`: the tag, then the payload as `N` integers of the
+//!   largest alignment `A` of its variants, so its layout is the one of `transformer::layout`.
+//! - a function value is a closure, `{ ptr, ptr }`: a trampoline that takes the environment
+//!   first, and the environment. A function passed to the `fn` parameter of a C function is a
+//!   plain `ptr` to a trampoline without the environment.
+//!
+//! Environments and boxes are allocated on the collected heap of the runtime
+//! (`runtime/tahini.c`), and a tahini `main` is called from a C `main` that starts the runtime
+//! first.
+//!
+//! Every slot of a function is an `alloca` in its entry block; temporaries are SSA values.
+
+use super::{mangle, unescape};
+use crate::ast::VarType;
+use crate::builtins;
+use crate::ir::{Const, Function, Instruction, Operand, Place, Program, Terminator, Value};
+use crate::transformer::lambda;
+use crate::transformer::layout::{holds_pointers, member_offsets};
+use crate::typeck::types::{atom_type, is_float, str_type, Aliases};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+const CLOSURE: &str = "{ ptr, ptr }";
+
+/// The LLVM module for `program`. Fails on types that have no LLVM representation, such as
+/// generic types that survived monomorphization.
+pub fn emit(program: &Program) -> Result<String, String> {
+    let mut emitter = Emitter::new(program);
+
+    let mut declarations = String::new();
+    let mut symbols = HashSet::new();
+    for (name, var_type) in &program.externs {
+        // Two imports of a header declare its members twice.
+        if symbols.insert(emitter.extern_symbol(name)) {
+            declarations.push_str(&emitter.extern_declaration(name, var_type)?);
+        }
+    }
+    for (name, var_type) in &program.imports {
+        let symbol = format!("th_{}", mangle(name));
+        declarations.push_str(&emitter.declaration(&symbol, var_type)?);
+    }
+    let mut globals = String::new();
+    for global in &program.globals {
+        let (var_type, value) = emitter.initializer(&global.value, &global.var_type)?;
+        writeln!(
+            globals,
+            "@th_{} = global {} {}",
+            mangle(&global.name),
+            var_type,
+            value
+        )
+        .unwrap();
+    }
+    let mut bodies = String::new();
+    for function in &program.functions {
+        bodies.push('\n');
+        bodies.push_str(&emitter.function(function)?);
+    }
+    let trampolines = emitter.trampolines()?;
+    let entry = emitter.entry()?;
+    for (name, ret) in &emitter.header_functions {
+        if symbols.contains(name) {
+            continue;
+        }
+        writeln!(declarations, "declare {} @{}(...)", ret, name).unwrap();
+    }
+    for alloc in &emitter.allocators {
+        writeln!(declarations, "declare ptr @{}(i64)", alloc).unwrap();
+    }
+    if !entry.is_empty() {
+        declarations.push_str("declare void @tahini_init()\n");
+    }
+
+    let mut out = String::from("; Generated by tahini.\n");
+    for section in [&emitter.constants(), &globals, &declarations, &trampolines] {
+        if !section.is_empty() {
+            out.push('\n');
+            out.push_str(section);
+        }
+    }
+    out.push_str(&bodies);
+    out.push_str(&entry);
+    Ok(out)
+}
+
+/// How a trampoline passes its environment on.
+#[derive(Clone, Copy, PartialEq)]
+enum Trampoline {
+    /// A lifted function takes its environment struct as the first parameter.
+    Lifted,
+    /// A top-level or external function has no environment.
+    Plain,
+    /// A function pointer passed to C takes no environment; a lifted function that captures
+    /// nothing is given an empty one.
+    Callback,
+}
+
+struct Emitter {
+    aliases: Aliases,
+    /// Parameter and return types of everything that is called by name, and whether it takes
+    /// more arguments than it declares.
+    signatures: HashMap<String, (Vec<VarType>, VarType, bool)>,
+    externs: HashSet<String>,
+    globals: HashMap<String, VarType>,
+
+    /// Functions of imported headers that are called, with the return type of the first call.
+    header_functions: BTreeMap<String, String>,
+    /// The bytes of every string literal, numbered in order of appearance.
+    strings: Vec<Vec<u8>>,
+    atoms: Vec<String>,
+    /// Functions used as values, in the order they were first used.
+    trampolines: Vec<(String, Trampoline)>,
+    /// Lifted lambdas, which take their environment first.
+    lifted: HashSet<String>,
+    /// The allocation functions of the runtime that are called.
+    allocators: BTreeSet<&'static str>,
+
+    /// The types of the slots and temporaries of the function being emitted.
+    locals: HashMap<String, VarType>,
+    temps: HashMap<usize, VarType>,
+    /// `alloca`s of the function being emitted, which all go to its entry block.
+    allocas: String,
+    /// Instructions of the block being emitted.
+    code: String,
+    next_helper: usize,
+}
+
+impl Emitter {
+    fn new(program: &Program) -> Self {
+        let mut aliases = Aliases::default();
+        aliases.types.extend(program.types.iter().cloned());
+        let mut emitter = Emitter {
+            aliases,
+            signatures: HashMap::new(),
+            externs: HashSet::new(),
+            globals: HashMap::new(),
+            header_functions: BTreeMap::new(),
+            strings: Vec::new(),
+            atoms: Vec::new(),
+            trampolines: Vec::new(),
+            lifted: HashSet::new(),
+            allocators: BTreeSet::new(),
+            locals: HashMap::new(),
+            temps: HashMap::new(),
+            allocas: String::new(),
+            code: String::new(),
+            next_helper: 0,
+        };
+        for function in &program.functions {
+            let params = function.parameters.iter().map(|(_, t)| t.clone()).collect();
+            let signature = (params, function.return_type.clone(), false);
+            emitter.signatures.insert(function.name.clone(), signature);
+            if function
+                .parameters
+                .first()
+                .is_some_and(|(param, _)| param == lambda::ENV)
+            {
+                emitter.lifted.insert(function.name.clone());
+            }
+        }
+        for (name, var_type) in &program.externs {
+            emitter.externs.insert(name.clone());
+            match var_type {
+                VarType::Fn(params, ret) => {
+                    let params = c_params(params, &emitter.aliases);
+                    let signature = (params, *ret.clone(), false);
+                    emitter.signatures.insert(name.clone(), signature);
+                }
+                VarType::FnWithVarArgs(params, ret) => {
+                    let params = c_params(params, &emitter.aliases);
+                    let signature = (params, *ret.clone(), true);
+                    emitter.signatures.insert(name.clone(), signature);
+                }
+                other => {
+                    emitter.globals.insert(name.clone(), other.clone());
+                }
+            }
+        }
+        for (name, var_type) in &program.imports {
+            match var_type {
+                VarType::Fn(params, ret) => {
+                    let signature = (params.clone(), *ret.clone(), false);
+                    emitter.signatures.insert(name.clone(), signature);
+                }
+                other => {
+                    emitter.globals.insert(name.clone(), other.clone());
+                }
+            }
+        }
+        for global in &program.globals {
+            emitter
+                .globals
+                .insert(global.name.clone(), global.var_type.clone());
+        }
+        emitter
+    }
+
+    fn ll_type(&self, var_type: &VarType) -> Result<String, String> {
+        Ok(match self.aliases.normalize(var_type) {
+            VarType::Int8 | VarType::UInt8 => "i8".to_string(),
+            VarType::Int16 | VarType::UInt16 => "i16".to_string(),
+            VarType::Int32 | VarType::UInt32 => "i32".to_string(),
+            VarType::Int64 | VarType::UInt64 => "i64".to_string(),
+            VarType::Int128 | VarType::UInt128 => "i128".to_string(),
+            VarType::Float16 => "half".to_string(),
+            VarType::Float32 => "float".to_string(),
+            VarType::Float64 => "double".to_string(),
+            VarType::Float128 => "fp128".to_string(),
+            VarType::Bool => "i1".to_string(),
+            VarType::Void => "void".to_string(),
+            atom if atom == atom_type() => "ptr".to_string(),
+            VarType::Ptr(_) | VarType::ArrayUnsized(_) => "ptr".to_string(),
+            VarType::Fn(..) | VarType::FnWithVarArgs(..) => CLOSURE.to_string(),
+            VarType::ArraySized(element, length) => {
+                format!("[{} x {}]", length, self.ll_type(&element)?)
+            }
+            VarType::Tuple(members) => self.struct_type(&members)?,
+            VarType::Struct(fields) => {
+                let members: Vec<_> = fields.into_iter().map(|(_, t)| t).collect();
+                self.struct_type(&members)?
+            }
+            VarType::Data(variants) => match self.payload(&variants)? {
+                (0, _) => "{ i32 }".to_string(),
+                (words, align) => format!("{{ i32, [{} x i{}] }}", words, align * 8),
+            },
+            other => return Err(format!("type `{}` has no LLVM representation", other)),
+        })
+    }
+
+    /// The payload of a `data` value: the number of integers of the largest alignment of its
+    /// variants that hold the members of any variant, and that alignment.
+    fn payload(&self, variants: &[(String, Vec<VarType>)]) -> Result<(usize, usize), String> {
+        let mut size = 0;
+        let mut align = 1;
+        for (_, members) in variants {
+            let (_, layout) = member_offsets(members, &self.aliases)?;
+            size = size.max(layout.size);
+            align = align.max(layout.align);
+        }
+        Ok((size.div_ceil(align), align))
+    }
+
+    fn struct_type(&self, members: &[VarType]) -> Result<String, String> {
+        if members.is_empty() {
+            return Ok("{}".to_string());
+        }
+        let members = members
+            .iter()
+            .map(|member| self.ll_type(member))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("{{ {} }}", members.join(", ")))
+    }
+
+    /// The symbol of a function that is called by `name`.
+    fn callee(&self, name: &str) -> String {
+        if self.externs.contains(name) {
+            return format!("@{}", self.extern_symbol(name));
+        }
+        match builtins::split_path(name) {
+            Some((_, member)) if !self.signatures.contains_key(name) => format!("@{}", member),
+            _ => format!("@th_{}", mangle(name)),
+        }
+    }
+
+    /// The symbol of an external function or global: the member of a header member, and the
+    /// name of a typed `def`.
+    fn extern_symbol(&self, name: &str) -> String {
+        match builtins::split_path(name) {
+            Some((_, member)) => member.to_string(),
+            None => name.to_string(),
+        }
+    }
+
+    fn extern_declaration(&mut self, name: &str, var_type: &VarType) -> Result<String, String> {
+        let symbol = self.extern_symbol(name);
+        if mangle(&symbol) != symbol {
+            return Err(format!("`{}` is not a valid C name", name));
+        }
+        let var_type = match var_type {
+            VarType::Fn(params, ret) => VarType::Fn(c_params(params, &self.aliases), ret.clone()),
+            VarType::FnWithVarArgs(params, ret) => {
+                VarType::FnWithVarArgs(c_params(params, &self.aliases), ret.clone())
+            }
+            other => other.clone(),
+        };
+        self.declaration(&symbol, &var_type)
+    }
+
+    /// The declaration of the function or global `symbol`, defined in another object file.
+    fn declaration(&mut self, symbol: &str, var_type: &VarType) -> Result<String, String> {
+        let (params, ret, variadic) = match var_type {
+            VarType::Fn(params, ret) => (params, ret, false),
+            VarType::FnWithVarArgs(params, ret) => (params, ret, true),
+            other => {
+                let var_type = self.ll_type(other)?;
+                return Ok(format!("@{} = external global {}\n", symbol, var_type));
+            }
+        };
+        let mut list = params
+            .iter()
+            .map(|param| self.ll_type(param))
+            .collect::<Result<Vec<_>, _>>()?;
+        if variadic {
+            list.push("...".to_string());
+        }
+        let ret = self.ll_type(ret)?;
+        Ok(format!(
+            "declare {} @{}({})\n",
+            ret,
+            symbol,
+            list.join(", ")
+        ))
+    }
+
+    fn fresh(&mut self) -> String {
+        let name = format!("%h{}", self.next_helper);
+        self.next_helper += 1;
+        name
+    }
+
+    fn emit(&mut self, instruction: String) {
+        self.code.push_str("  ");
+        self.code.push_str(&instruction);
+        self.code.push('\n');
+    }
+
+    fn function(&mut self, function: &Function) -> Result<String, String> {
+        self.locals = function.locals.iter().cloned().collect();
+        self.temps = function
+            .blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+            .filter_map(|instruction| match instruction {
+                Instruction::Let(temp, var_type, _) => Some((temp.0, var_type.clone())),
+                _ => None,
+            })
+            .collect();
+        self.allocas.clear();
+        self.next_helper = 0;
+
+        let mut params = Vec::new();
+        for (name, var_type) in &function.parameters {
+            params.push(format!(
+                "{} {}",
+                self.ll_type(var_type)?,
+                local(name, "arg")
+            ));
+        }
+        let mut prologue = String::new();
+        for (i, (name, var_type)) in function.locals.iter().enumerate() {
+            let var_type = self.ll_type(var_type)?;
+            writeln!(prologue, "  {} = alloca {}", local(name, "slot"), var_type).unwrap();
+            if i < function.parameters.len() {
+                let arg = local(name, "arg");
+                let slot = local(name, "slot");
+                self.code
+                    .push_str(&format!("  store {} {}, ptr {}\n", var_type, arg, slot));
+            }
+        }
+        let stores = std::mem::take(&mut self.code);
+
+        let mut blocks = String::new();
+        for block in &function.blocks {
+            writeln!(blocks, "{}:", block.id).unwrap();
+            for instruction in &block.instructions {
+                self.instruction(instruction)?;
+            }
+            let terminator = match &block.terminator {
+                Terminator::Jump(target) => format!("br label %{}", target),
+                Terminator::Branch(condition, then, otherwise) => format!(
+                    "br i1 {}, label %{}, label %{}",
+                    self.operand(condition)?,
+                    then,
+                    otherwise
+                ),
+                Terminator::Return(Some(value)) => {
+                    let value = self.coerce(value, &function.return_type)?;
+                    format!("ret {} {}", self.ll_type(&function.return_type)?, value)
+                }
+                Terminator::Return(None) => "ret void".to_string(),
+                Terminator::Unreachable => "unreachable".to_string(),
+            };
+            self.emit(terminator);
+            blocks.push_str(&std::mem::take(&mut self.code));
+        }
+
+        Ok(format!(
+            "define {} @th_{}({}) {{\nentry:\n{}{}{}  br label %bb0\n{}}}\n",
+            self.ll_type(&function.return_type)?,
+            mangle(&function.name),
+            params.join(", "),
+            prologue,
+            std::mem::take(&mut self.allocas),
+            stores,
+            blocks
+        ))
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) -> Result<(), String> {
+        match instruction {
+            Instruction::Let(temp, var_type, value) => {
+                self.value(&format!("%t{}", temp.0), value, Some(var_type))
+            }
+            Instruction::Store(_, Operand::Const(Const::Void)) => Ok(()),
+            Instruction::Store(place, value) => {
+                let place_type = self.place_type(place);
+                let value = self.coerce(value, &place_type)?;
+                let var_type = self.ll_type(&place_type)?;
+                let address = self.place(place)?;
+                self.emit(format!("store {} {}, ptr {}", var_type, value, address));
+                Ok(())
+            }
+            Instruction::Eval(value) => {
+                let dest = self.fresh();
+                self.value(&dest, value, None)
+            }
+        }
+    }
+
+    fn place(&mut self, place: &Place) -> Result<String, String> {
+        Ok(match place {
+            Place::Local(name) => local(name, "slot"),
+            Place::Global(name) if self.externs.contains(name) => {
+                format!("@{}", self.extern_symbol(name))
+            }
+            Place::Global(name) => format!("@th_{}", mangle(name)),
+            Place::Deref(address) => self.operand(address)?,
+        })
+    }
+
+    fn place_type(&self, place: &Place) -> VarType {
+        match place {
+            Place::Local(name) => self.locals.get(name).cloned(),
+            Place::Global(name) => self.globals.get(name).cloned(),
+            Place::Deref(address) => self.pointee(address),
+        }
+        .unwrap_or(VarType::Void)
+    }
+
+    fn pointee(&self, address: &Operand) -> Option<VarType> {
+        match self.aliases.normalize(&self.operand_type(address)) {
+            VarType::Ptr(pointee) | VarType::ArrayUnsized(pointee) => Some(*pointee),
+            _ => None,
+        }
+    }
+
+    fn operand_type(&self, operand: &Operand) -> VarType {
+        match operand {
+            Operand::Temp(temp) => self.temps.get(&temp.0).cloned().unwrap_or(VarType::Void),
+            Operand::Const(value) => match value {
+                Const::Int(_, var_type)
+                | Const::Float(_, var_type)
+                | Const::Tuple(_, var_type)
+                | Const::Array(_, var_type)
+                | Const::Data(_, _, var_type) => var_type.clone(),
+                Const::Bool(_) => VarType::Bool,
+                Const::Char(_) => VarType::Int8,
+                Const::String(_) => str_type(),
+                Const::Atom(_) => atom_type(),
+                Const::Fn(name) => match self.signatures.get(name) {
+                    Some((params, ret, _)) => VarType::Fn(params.clone(), Box::new(ret.clone())),
+                    None => VarType::Void,
+                },
+                Const::Callback(_) => VarType::Ptr(Box::new(VarType::Void)),
+                Const::Void => VarType::Void,
+            },
+        }
+    }
+
+    fn operand(&mut self, operand: &Operand) -> Result<String, String> {
+        match operand {
+            Operand::Temp(temp) => Ok(format!("%t{}", temp.0)),
+            Operand::Const(value) => self.constant(value),
+        }
+    }
+
+    /// `T value` for an operand.
+    fn typed(&mut self, operand: &Operand) -> Result<String, String> {
+        let var_type = self.ll_type(&self.operand_type(operand))?;
+        Ok(format!("{} {}", var_type, self.operand(operand)?))
+    }
+
+    /// `operand` converted to `target` where LLVM does not convert implicitly: a sized array
+    /// passed as an unsized one is spilled to memory and passed by address.
+    fn coerce(&mut self, operand: &Operand, target: &VarType) -> Result<String, String> {
+        let from = self.operand_type(operand);
+        match (
+            self.aliases.normalize(&from),
+            self.aliases.normalize(target),
+        ) {
+            (VarType::ArraySized(..), VarType::ArrayUnsized(_)) => {
+                let array = self.ll_type(&from)?;
+                let slot = self.fresh();
+                writeln!(self.allocas, "  {} = alloca {}", slot, array).unwrap();
+                let value = self.operand(operand)?;
+                self.emit(format!("store {} {}, ptr {}", array, value, slot));
+                Ok(slot)
+            }
+            _ => self.operand(operand),
+        }
+    }
+
+    /// The arguments of a call, converted to `params`. Arguments beyond them get C's default
+    /// argument promotions: small integers become `i32` and `float` becomes `double`.
+    fn arguments(&mut self, args: &[Operand], params: &[VarType]) -> Result<String, String> {
+        let mut list = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            if let Some(param) = params.get(i) {
+                let value = self.coerce(arg, param)?;
+                list.push(format!("{} {}", self.ll_type(param)?, value));
+                continue;
+            }
+            let arg_type = self.aliases.normalize(&self.operand_type(arg));
+            let value = self.operand(arg)?;
+            let promoted = match arg_type {
+                VarType::Bool | VarType::UInt8 | VarType::UInt16 => Some(("zext", "i32")),
+                VarType::Int8 | VarType::Int16 => Some(("sext", "i32")),
+                VarType::Float16 | VarType::Float32 => Some(("fpext", "double")),
+                _ => None,
+            };
+            match promoted {
+                Some((cast, target)) => {
+                    let from = self.ll_type(&arg_type)?;
+                    let dest = self.fresh();
+                    self.emit(format!(
+                        "{} = {} {} {} to {}",
+                        dest, cast, from, value, target
+                    ));
+                    list.push(format!("{} {}", target, dest));
+                }
+                None => list.push(self.typed(arg)?),
+            }
+        }
+        Ok(list.join(", "))
+    }
+
+    fn constant(&mut self, value: &Const) -> Result<String, String> {
+        Ok(match value {
+            Const::Int(value, _) => value.to_string(),
+            Const::Float(value, var_type) => {
+                float_literal(*value, &self.aliases.normalize(var_type))
+            }
+            Const::Bool(value) => value.to_string(),
+            Const::Char(value) => (*value as u32 as u8 as i8).to_string(),
+            Const::String(value) => {
+                let bytes = unescape(value);
+                let index = match self.strings.iter().position(|known| *known == bytes) {
+                    Some(index) => index,
+                    None => {
+                        self.strings.push(bytes);
+                        self.strings.len() - 1
+                    }
+                };
+                format!("@.str.{}", index)
+            }
+            Const::Atom(name) => {
+                if !self.atoms.contains(name) {
+                    self.atoms.push(name.clone());
+                }
+                global_name("tahini.atom.", name)
+            }
+            Const::Fn(name) => {
+                let code = self.trampoline(name, Trampoline::Plain);
+                format!("{{ ptr {}, ptr null }}", code)
+            }
+            // C functions are passed as they are.
+            Const::Callback(name) if self.externs.contains(name) => self.callee(name),
+            Const::Callback(name) => self.trampoline(name, Trampoline::Callback),
+            Const::Tuple(..) | Const::Array(..) | Const::Data(..) => {
+                return Err(format!("`{}` only initializes globals", value))
+            }
+            Const::Void => "undef".to_string(),
+        })
+    }
+
+    /// The LLVM type and value that initialize a global of `var_type` to `value`. The type of
+    /// a `data` value is the one of its variant: the tag, padding up to the payload, the
+    /// members and padding up to the size of the largest variant, all aligned like the payload.
+    fn initializer(
+        &mut self,
+        value: &Const,
+        var_type: &VarType,
+    ) -> Result<(String, String), String> {
+        let (types, values) = match value {
+            Const::Tuple(members, _) | Const::Array(members, _) if members.is_empty() => {
+                return Ok((self.ll_type(var_type)?, "zeroinitializer".to_string()))
+            }
+            Const::Tuple(members, _) => {
+                let types = match self.aliases.normalize(var_type) {
+                    VarType::Struct(fields) => fields.into_iter().map(|(_, t)| t).collect(),
+                    VarType::Tuple(types) => types,
+                    other => return Err(format!("`{}` is not a tuple", other)),
+                };
+                self.initializers(members, &types)?
+            }
+            Const::Array(members, _) => {
+                let VarType::ArraySized(element, length) = self.aliases.normalize(var_type) else {
+                    return Err(format!("`{}` is not a sized array", var_type));
+                };
+                let (types, values) = self.initializers(members, &vec![*element; length])?;
+                // Elements of different variants have different types, so they are a struct
+                // of the same layout.
+                if types.iter().all(|t| *t == types[0]) {
+                    return Ok((
+                        format!("[{} x {}]", length, types[0]),
+                        format!("[{}]", typed_list(&types, &values)),
+                    ));
+                }
+                (types, values)
+            }
+            Const::Data(tag, members, _) => {
+                let variants = self.aliases.data_variants(var_type).unwrap_or_default();
+                let Some(index) = variants.iter().position(|(t, _)| t == tag) else {
+                    return Err(format!("`{}` has no variant `:{}`", var_type, tag));
+                };
+                let (words, align) = self.payload(&variants)?;
+                if words == 0 {
+                    return Ok(("{ i32 }".to_string(), format!("{{ i32 {} }}", index)));
+                }
+                let fields = &variants[index].1;
+                let (_, layout) = member_offsets(fields, &self.aliases)?;
+                let (member_types, member_values) = self.initializers(members, fields)?;
+                let (payload_type, payload) = match members.is_empty() {
+                    true => ("{}".to_string(), "zeroinitializer".to_string()),
+                    false => (
+                        format!("{{ {} }}", member_types.join(", ")),
+                        format!("{{ {} }}", typed_list(&member_types, &member_values)),
+                    ),
+                };
+                let padding = 4usize.next_multiple_of(align) - 4;
+                let tail = words * align - layout.size;
+                (
+                    vec![
+                        format!("[0 x i{}]", align * 8),
+                        "i32".to_string(),
+                        format!("[{} x i8]", padding),
+                        payload_type,
+                        format!("[{} x i8]", tail),
+                    ],
+                    vec![
+                        "zeroinitializer".to_string(),
+                        index.to_string(),
+                        "zeroinitializer".to_string(),
+                        payload,
+                        "zeroinitializer".to_string(),
+                    ],
+                )
+            }
+            scalar => return Ok((self.ll_type(var_type)?, self.constant(scalar)?)),
+        };
+        Ok((
+            format!("{{ {} }}", types.join(", ")),
+            format!("{{ {} }}", typed_list(&types, &values)),
+        ))
+    }
+
+    fn initializers(
+        &mut self,
+        values: &[Const],
+        types: &[VarType],
+    ) -> Result<(Vec<String>, Vec<String>), String> {
+        let mut lists = (Vec::new(), Vec::new());
+        for (value, var_type) in values.iter().zip(types) {
+            let (var_type, value) = self.initializer(value, var_type)?;
+            lists.0.push(var_type);
+            lists.1.push(value);
+        }
+        Ok(lists)
+    }
+
+    /// Emit the instructions computing `value` into `dest`, of type `var_type` if it has one.
+    fn value(
+        &mut self,
+        dest: &str,
+        value: &Value,
+        var_type: Option<&VarType>,
+    ) -> Result<(), String> {
+        let result_type = var_type.cloned().unwrap_or(VarType::Void);
+        match value {
+            Value::Load(place) => {
+                let address = self.place(place)?;
+                let var_type = self.ll_type(&result_type)?;
+                self.emit(format!("{} = load {}, ptr {}", dest, var_type, address));
+            }
+            Value::Addr(place) => {
+                let address = self.place(place)?;
+                self.emit(format!(
+                    "{} = getelementptr i8, ptr {}, i64 0",
+                    dest, address
+                ));
+            }
+            Value::Field(address, field) => {
+                let pointee = self.pointee(address).unwrap_or(VarType::Void);
+                let fields = self.aliases.struct_fields(&pointee).unwrap_or_default();
+                let Some(index) = fields.iter().position(|(name, _)| name == field) else {
+                    return Err(format!("`{}` has no field `{}`", pointee, field));
+                };
+                let struct_type = self.ll_type(&pointee)?;
+                let address = self.operand(address)?;
+                self.emit(format!(
+                    "{} = getelementptr {}, ptr {}, i32 0, i32 {}",
+                    dest, struct_type, address, index
+                ));
+            }
+            Value::Index(address, index) => {
+                let base = self.operand(address)?;
+                let address_type = self.aliases.normalize(&self.operand_type(address));
+                let pointee = match &address_type {
+                    VarType::Ptr(pointee) => self.aliases.normalize(pointee),
+                    // An unsized array is a pointer to its first element.
+                    VarType::ArrayUnsized(element) => self.aliases.normalize(element),
+                    other => return Err(format!("cannot index through `{}`", other)),
+                };
+                let instruction = match (&pointee, index) {
+                    (VarType::ArraySized(..), _) => format!(
+                        "getelementptr {}, ptr {}, i64 0, {}",
+                        self.ll_type(&pointee)?,
+                        base,
+                        self.typed(index)?
+                    ),
+                    (VarType::ArrayUnsized(element), _) => {
+                        let pointer = self.fresh();
+                        self.emit(format!("{} = load ptr, ptr {}", pointer, base));
+                        format!(
+                            "getelementptr {}, ptr {}, {}",
+                            self.ll_type(element)?,
+                            pointer,
+                            self.typed(index)?
+                        )
+                    }
+                    (VarType::Tuple(_), Operand::Const(Const::Int(i, _))) => format!(
+                        "getelementptr {}, ptr {}, i32 0, i32 {}",
+                        self.ll_type(&pointee)?,
+                        base,
+                        i
+                    ),
+                    (VarType::Tuple(_), _) => {
+                        return Err("tuples can only be indexed with constants".to_string())
+                    }
+                    _ => format!(
+                        "getelementptr {}, ptr {}, {}",
+                        self.ll_type(&pointee)?,
+                        base,
+                        self.typed(index)?
+                    ),
+                };
+                self.emit(format!("{} = {}", dest, instruction));
+            }
+            Value::Unary(op, operand) => {
+                let operand_type = self.aliases.normalize(&self.operand_type(operand));
+                let var_type = self.ll_type(&operand_type)?;
+                let value = self.operand(operand)?;
+                let instruction = match op.as_str() {
+                    "!" | "not" => format!("xor i1 {}, true", value),
+                    "-" if is_float(&operand_type) => format!("fneg {} {}", var_type, value),
+                    "-" => format!("sub {} 0, {}", var_type, value),
+                    "~" => format!("xor {} {}, -1", var_type, value),
+                    other => return Err(format!("`{}` is not a unary operator", other)),
+                };
+                self.emit(format!("{} = {}", dest, instruction));
+            }
+            Value::Binary(op, left, right) => {
+                let operand_type = self.aliases.normalize(&self.operand_type(left));
+                let instruction = binary(op, &operand_type)?;
+                let var_type = self.ll_type(&operand_type)?;
+                let (left, right) = (self.operand(left)?, self.operand(right)?);
+                self.emit(format!(
+                    "{} = {} {} {}, {}",
+                    dest, instruction, var_type, left, right
+                ));
+            }
+            Value::Call(name, args) => {
+                let (call_type, args) = match self.signatures.get(name).cloned() {
+                    Some((params, ret, variadic)) => {
+                        let ret = self.ll_type(&ret)?;
+                        let args = self.arguments(args, &params)?;
+                        let call_type = match variadic {
+                            true => {
+                                let mut list = params
+                                    .iter()
+                                    .map(|param| self.ll_type(param))
+                                    .collect::<Result<Vec<_>, _>>()?;
+                                list.push("...".to_string());
+                                format!("{} ({})", ret, list.join(", "))
+                            }
+                            false => ret,
+                        };
+                        (call_type, args)
+                    }
+                    None => {
+                        let ret = self.ll_type(&result_type)?;
+                        if let Some((_, member)) = builtins::split_path(name) {
+                            self.header_functions
+                                .entry(member.to_string())
+                                .or_insert_with(|| ret.clone());
+                        }
+                        (format!("{} (...)", ret), self.arguments(args, &[])?)
+                    }
+                };
+                let callee = self.callee(name);
+                let call = format!("call {} {}({})", call_type, callee, args);
+                self.emit_result(dest, &result_type, call);
+            }
+            Value::CallClosure(closure, args) => {
+                let (params, ret) = match self.aliases.normalize(&self.operand_type(closure)) {
+                    VarType::Fn(params, ret) => (params, *ret),
+                    other => return Err(format!("cannot call a value of type `{}`", other)),
+                };
+                let closure = self.operand(closure)?;
+                let (code, env) = (self.fresh(), self.fresh());
+                self.emit(format!(
+                    "{} = extractvalue {} {}, 0",
+                    code, CLOSURE, closure
+                ));
+                self.emit(format!("{} = extractvalue {} {}, 1", env, CLOSURE, closure));
+                let mut list = vec![format!("ptr {}", env)];
+                if !args.is_empty() {
+                    list.push(self.arguments(args, &params)?);
+                }
+                let call = format!("call {} {}({})", self.ll_type(&ret)?, code, list.join(", "));
+                self.emit_result(dest, &ret, call);
+            }
+            Value::Make(var_type, members) => {
+                let types = match self.aliases.normalize(var_type) {
+                    VarType::Struct(fields) => fields.into_iter().map(|(_, t)| t).collect(),
+                    VarType::Tuple(types) => types,
+                    VarType::ArraySized(element, length) => vec![*element; length],
+                    other => {
+                        let value = self.coerce(&members[0], &other)?;
+                        let other = self.ll_type(&other)?;
+                        self.emit(format!(
+                            "{} = select i1 true, {} {}, {} {}",
+                            dest, other, value, other, value
+                        ));
+                        return Ok(());
+                    }
+                };
+                self.aggregate(dest, var_type, members, &types)?;
+            }
+            Value::Tuple(items) | Value::Array(items) => {
+                let types = match self.aliases.normalize(&result_type) {
+                    VarType::Tuple(types) => types,
+                    VarType::ArraySized(element, length) => vec![*element; length],
+                    _ => Vec::new(),
+                };
+                self.aggregate(dest, &result_type, items, &types)?;
+            }
+            Value::Data(tag, members) => {
+                let variants = self.aliases.data_variants(&result_type).unwrap_or_default();
+                let Some(index) = variants.iter().position(|(t, _)| t == tag) else {
+                    return Err(format!("`{}` has no variant `:{}`", result_type, tag));
+                };
+                let data_type = self.ll_type(&result_type)?;
+                let slot = self.fresh();
+                writeln!(self.allocas, "  {} = alloca {}", slot, data_type).unwrap();
+                self.emit(format!("store i32 {}, ptr {}", index, slot));
+                if !members.is_empty() {
+                    let payload_type = VarType::Tuple(variants[index].1.clone());
+                    let payload = self.fresh();
+                    self.aggregate(&payload, &payload_type, members, &variants[index].1)?;
+                    let address = self.fresh();
+                    self.emit(format!(
+                        "{} = getelementptr {}, ptr {}, i32 0, i32 1",
+                        address, data_type, slot
+                    ));
+                    let payload_type = self.ll_type(&payload_type)?;
+                    self.emit(format!(
+                        "store {} {}, ptr {}",
+                        payload_type, payload, address
+                    ));
+                }
+                self.emit(format!("{} = load {}, ptr {}", dest, data_type, slot));
+            }
+            Value::MakeClosure(name, env) => {
+                let env_type = match self.signatures.get(name) {
+                    Some((params, _, _)) if !params.is_empty() => params[0].clone(),
+                    _ => return Err(format!("`{}` is not a lifted function", name)),
+                };
+                let fields = self.aliases.struct_fields(&env_type).unwrap_or_default();
+                let types: Vec<_> = fields.into_iter().map(|(_, t)| t).collect();
+                let value = self.fresh();
+                self.aggregate(&value, &env_type, env, &types)?;
+                let cell = self.allocate(&env_type, &value)?;
+                let code = self.trampoline(name, Trampoline::Lifted);
+                let closure = format!("{{ ptr {}, ptr undef }}", code);
+                self.emit(format!(
+                    "{} = insertvalue {} {}, ptr {}, 1",
+                    dest, CLOSURE, closure, cell
+                ));
+            }
+            Value::Box(value) => {
+                let pointee = match self.aliases.normalize(&result_type) {
+                    VarType::Ptr(pointee) => *pointee,
+                    _ => self.operand_type(value),
+                };
+                let value = self.coerce(value, &pointee)?;
+                let cell = self.allocate(&pointee, &value)?;
+                self.emit(format!("{} = getelementptr i8, ptr {}, i64 0", dest, cell));
+            }
+        }
+        Ok(())
+    }
+
+    /// Emit a call, binding its result to `dest` unless it has none.
+    fn emit_result(&mut self, dest: &str, var_type: &VarType, call: String) {
+        match var_type {
+            VarType::Void => self.emit(call),
+            _ => self.emit(format!("{} = {}", dest, call)),
+        }
+    }
+
+    /// Build the struct, tuple or array `var_type` from `members` with `insertvalue`s.
+    fn aggregate(
+        &mut self,
+        dest: &str,
+        var_type: &VarType,
+        members: &[Operand],
+        types: &[VarType],
+    ) -> Result<(), String> {
+        let aggregate = self.ll_type(var_type)?;
+        let mut current = "undef".to_string();
+        for (i, member) in members.iter().enumerate() {
+            let member_type = types
+                .get(i)
+                .cloned()
+                .unwrap_or_else(|| self.operand_type(member));
+            let value = self.coerce(member, &member_type)?;
+            let member_type = self.ll_type(&member_type)?;
+            let next = match i + 1 == members.len() {
+                true => dest.to_string(),
+                false => self.fresh(),
+            };
+            self.emit(format!(
+                "{} = insertvalue {} {}, {} {}, {}",
+                next, aggregate, current, member_type, value, i
+            ));
+            current = next;
+        }
+        if members.is_empty() {
+            self.emit(format!(
+                "{} = select i1 true, {} zeroinitializer, {} zeroinitializer",
+                dest, aggregate, aggregate
+            ));
+        }
+        Ok(())
+    }
+
+    /// Copy `value` to a fresh cell on the collected heap and return the cell's address. The
+    /// collector only scans the cell if the value can hold pointers.
+    fn allocate(&mut self, var_type: &VarType, value: &str) -> Result<String, String> {
+        let alloc = match holds_pointers(var_type, &self.aliases) {
+            true => "tahini_alloc",
+            false => "tahini_alloc_atomic",
+        };
+        self.allocators.insert(alloc);
+        let var_type = self.ll_type(var_type)?;
+        let cell = self.fresh();
+        self.emit(format!(
+            "{} = call ptr @{}(i64 ptrtoint (ptr getelementptr ({}, ptr null, i32 1) to i64))",
+            cell, alloc, var_type
+        ));
+        self.emit(format!("store {} {}, ptr {}", var_type, value, cell));
+        Ok(cell)
+    }
+
+    /// The trampoline of the function `name`.
+    fn trampoline(&mut self, name: &str, kind: Trampoline) -> String {
+        if !self.trampolines.contains(&(name.to_string(), kind)) {
+            self.trampolines.push((name.to_string(), kind));
+        }
+        match kind {
+            Trampoline::Callback => format!("@th_{}_callback", mangle(name)),
+            _ => format!("@th_{}_closure", mangle(name)),
+        }
+    }
+
+    /// The definitions of all trampolines used so far.
+    fn trampolines(&mut self) -> Result<String, String> {
+        let mut out = String::new();
+        for (name, kind) in self.trampolines.clone() {
+            let Some((params, ret, _)) = self.signatures.get(&name).cloned() else {
+                return Err(format!("`{}` cannot be used as a value", name));
+            };
+            let lifted = match kind {
+                Trampoline::Lifted => true,
+                Trampoline::Plain => false,
+                Trampoline::Callback => self.lifted.contains(&name),
+            };
+            let mut list = Vec::new();
+            let mut args = Vec::new();
+            let mut body = String::new();
+            match kind {
+                Trampoline::Lifted => {
+                    let env_type = self.ll_type(&params[0])?;
+                    list.push("ptr %env".to_string());
+                    writeln!(body, "  %unpacked = load {}, ptr %env", env_type).unwrap();
+                    args.push(format!("{} %unpacked", env_type));
+                }
+                Trampoline::Plain => list.push("ptr %env".to_string()),
+                Trampoline::Callback if lifted => {
+                    args.push(format!("{} zeroinitializer", self.ll_type(&params[0])?));
+                }
+                Trampoline::Callback => {}
+            }
+            for (i, param) in params.iter().enumerate().skip(lifted as usize) {
+                let param = self.ll_type(param)?;
+                list.push(format!("{} %a{}", param, i));
+                args.push(format!("{} %a{}", param, i));
+            }
+            let ret = self.ll_type(&ret)?;
+            let call = format!("call {} {}({})", ret, self.callee(&name), args.join(", "));
+            match ret.as_str() {
+                "void" => writeln!(body, "  {}\n  ret void", call),
+                _ => writeln!(body, "  %result = {}\n  ret {} %result", call, ret),
+            }
+            .unwrap();
+            let suffix = match kind {
+                Trampoline::Callback => "callback",
+                _ => "closure",
+            };
+            writeln!(
+                out,
+                "define private {} @th_{}_{}({}) {{\n{}}}\n",
+                ret,
+                mangle(&name),
+                suffix,
+                list.join(", "),
+                body
+            )
+            .unwrap();
+        }
+        Ok(out)
+    }
+
+    /// String literals and atoms.
+    fn constants(&self) -> String {
+        let mut out = String::new();
+        for (i, bytes) in self.strings.iter().enumerate() {
+            writeln!(
+                out,
+                "@.str.{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"",
+                i,
+                bytes.len() + 1,
+                escape(bytes)
+            )
+            .unwrap();
+        }
+        // Atoms compare by address, so they must not be merged with other constants.
+        for atom in &self.atoms {
+            writeln!(
+                out,
+                "{} = private constant [{} x i8] c\"{}\\00\"",
+                global_name("tahini.atom.", atom),
+                atom.len() + 1,
+                escape(atom.as_bytes())
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    /// The C `main` that initializes the GC and calls the program's `main`, if there is one.
+    fn entry(&mut self) -> Result<String, String> {
+        let Some((params, ret, _)) = self.signatures.get("main").cloned() else {
+            return Ok(String::new());
+        };
+        if self.externs.contains("main") {
+            return Ok(String::new());
+        }
+        let args = match params.as_slice() {
+            [] => String::new(),
+            [argc, argv] => format!(
+                "{} %argc, {} %argv",
+                self.ll_type(argc)?,
+                self.ll_type(argv)?
+            ),
+            _ => return Err("`main` takes no parameters or `argc` and `argv`".to_string()),
+        };
+        let ret_type = self.ll_type(&ret)?;
+        let call = format!("call {} @th_main({})", ret_type, args);
+        let body = match ret_type.as_str() {
+            "void" => format!("  {}\n  ret i32 0", call),
+            "i32" => format!("  %status = {}\n  ret i32 %status", call),
+            _ => return Err("`main` returns `i32` or `void`".to_string()),
+        };
+        Ok(format!(
+            "\ndefine i32 @main(i32 %argc, ptr %argv) {{\n  call void @tahini_init()\n{}\n}}\n",
+            body
+        ))
+    }
+}
+
+/// The binary instruction for `op` on operands of `operand_type`.
+fn binary(op: &str, operand_type: &VarType) -> Result<&'static str, String> {
+    let float = is_float(operand_type);
+    let unsigned = matches!(
+        operand_type,
+        VarType::UInt8 | VarType::UInt16 | VarType::UInt32 | VarType::UInt64 | VarType::UInt128
+    ) || matches!(operand_type, VarType::Ptr(_) | VarType::IdentType(_));
+    Ok(match (op, float, unsigned) {
+        ("+", false, _) => "add",
+        ("+", true, _) => "fadd",
+        ("-", false, _) => "sub",
+        ("-", true, _) => "fsub",
+        ("*", false, _) => "mul",
+        ("*", true, _) => "fmul",
+        ("/", false, false) => "sdiv",
+        ("/", false, true) => "udiv",
+        ("/", true, _) => "fdiv",
+        ("%", false, false) => "srem",
+        ("%", false, true) => "urem",
+        ("%", true, _) => "frem",
+        ("&" | "&&" | "and", false, _) => "and",
+        ("|" | "||" | "or", false, _) => "or",
+        ("^", false, _) => "xor",
+        ("<<", false, _) => "shl",
+        (">>", false, false) => "ashr",
+        (">>", false, true) => "lshr",
+        ("=", false, _) => "icmp eq",
+        ("!=", false, _) => "icmp ne",
+        ("<", false, false) => "icmp slt",
+        ("<", false, true) => "icmp ult",
+        (">", false, false) => "icmp sgt",
+        (">", false, true) => "icmp ugt",
+        ("<=", false, false) => "icmp sle",
+        ("<=", false, true) => "icmp ule",
+        (">=", false, false) => "icmp sge",
+        (">=", false, true) => "icmp uge",
+        ("=", true, _) => "fcmp oeq",
+        ("!=", true, _) => "fcmp une",
+        ("<", true, _) => "fcmp olt",
+        (">", true, _) => "fcmp ogt",
+        ("<=", true, _) => "fcmp ole",
+        (">=", true, _) => "fcmp oge",
+        (op, _, _) => {
+            return Err(format!(
+                "`{}` has no LLVM instruction for `{}`",
+                op, operand_type
+            ))
+        }
+    })
+}
+
+/// The slot (`%slot.x`) or incoming argument (`%arg.x`) of a local, quoted if needed.
+/// The parameters of a C function as it takes them: a `fn` parameter is a function pointer.
+fn c_params(params: &[VarType], aliases: &Aliases) -> Vec<VarType> {
+    params
+        .iter()
+        .map(|param| match aliases.normalize(param) {
+            VarType::Fn(..) | VarType::FnWithVarArgs(..) => VarType::Ptr(Box::new(VarType::Void)),
+            _ => param.clone(),
+        })
+        .collect()
+}
+
+/// `T value, ...` for the types and values of the members of an aggregate constant.
+fn typed_list(types: &[String], values: &[String]) -> String {
+    let members: Vec<_> = types
+        .iter()
+        .zip(values)
+        .map(|(var_type, value)| format!("{} {}", var_type, value))
+        .collect();
+    members.join(", ")
+}
+
+fn local(name: &str, kind: &str) -> String {
+    let name = format!("{}.{}", kind, name);
+    match name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-$._".contains(c))
+    {
+        true => format!("%{}", name),
+        false => format!("%\"{}\"", escape(name.as_bytes())),
+    }
+}
+
+fn global_name(prefix: &str, name: &str) -> String {
+    format!("@\"{}{}\"", prefix, escape(name.as_bytes()))
+}
+
+/// Bytes for an LLVM string: printable ASCII stays, everything else is `\XX`.
+fn escape(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for &byte in bytes {
+        match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => out.push(byte as char),
+            _ => write!(out, "\\{:02X}", byte).unwrap(),
+        }
+    }
+    out
+}
+
+/// A float constant in LLVM's exact hexadecimal forms: `half` as `0xH`, `fp128` as `0xL`
+/// (low 64 bits first) and `float` and `double` as the bits of the `double` they equal.
+fn float_literal(value: f64, var_type: &VarType) -> String {
+    match var_type {
+        VarType::Float16 => format!("0xH{:04X}", half_bits(value)),
+        VarType::Float32 => format!("0x{:016X}", (value as f32 as f64).to_bits()),
+        VarType::Float128 => {
+            let bits = quad_bits(value);
+            format!("0xL{:016X}{:016X}", bits as u64, (bits >> 64) as u64)
+        }
+        _ => format!("0x{:016X}", value.to_bits()),
+    }
+}
+
+/// The IEEE half-precision bits of `value`, rounded to nearest even.
+fn half_bits(value: f64) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 48) & 0x8000) as u16;
+    let exponent = ((bits >> 52) & 0x7FF) as i32;
+    let mantissa = bits & ((1 << 52) - 1);
+    if exponent == 0x7FF {
+        return sign | 0x7C00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let unbiased = exponent - 1023;
+    if unbiased > 15 {
+        return sign | 0x7C00;
+    }
+    // The significand with its implicit bit, and how far it is shifted to fit ten bits.
+    let significand = mantissa | (1 << 52);
+    let shift = if unbiased < -14 {
+        if unbiased < -25 {
+            return sign;
+        }
+        42 + (-14 - unbiased)
+    } else {
+        42
+    } as u32;
+    let mut half = (significand >> shift) as u32;
+    let rest = significand & ((1 << shift) - 1);
+    let halfway = 1u64 << (shift - 1);
+    if rest > halfway || (rest == halfway && half & 1 == 1) {
+        half += 1;
+    }
+    if unbiased < -14 {
+        return sign | half as u16;
+    }
+    let combined = (((unbiased + 15) as u32) << 10) + (half - 0x400);
+    // Rounding up can carry into the exponent, up to infinity.
+    sign | combined.min(0x7C00) as u16
+}
+
+/// The IEEE quadruple-precision bits of `value`, which it represents exactly.
+fn quad_bits(value: f64) -> u128 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 63) as u128) << 127;
+    let exponent = ((bits >> 52) & 0x7FF) as i64;
+    let mut mantissa = (bits & ((1 << 52) - 1)) as u128;
+    let exponent = match exponent {
+        0 if mantissa == 0 => return sign,
+        0 => {
+            // Subnormal doubles are normal quads.
+            let shift = mantissa.leading_zeros() as i64 - (128 - 52);
+            mantissa = (mantissa << (shift + 1)) & ((1 << 52) - 1);
+            16383 - 1022 - shift - 1
+        }
+        0x7FF => 0x7FFF,
+        exponent => exponent - 1023 + 16383,
+    };
+    sign | ((exponent as u128) << 112) | (mantissa << 60)
+}
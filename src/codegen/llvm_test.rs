@@ -0,0 +1,235 @@
+#[cfg(test)]
+mod tests {
+    use crate::codegen::llvm::emit;
+    use crate::codegen::RUNTIME;
+    use crate::ir::lower::lower;
+    use crate::parser::parser;
+    use crate::transformer::transform;
+    use chumsky::Parser;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    fn emitted(src: &str) -> String {
+        let module = parser().parse(src).into_output().unwrap();
+        let transformed = transform(&module).unwrap_or_else(|errors| panic!("{:?}", errors));
+        let program = lower(&transformed).unwrap_or_else(|errors| panic!("{:?}", errors));
+        emit(&program).unwrap()
+    }
+
+    /// Compare the LLVM IR of `golden/<name>.th` with `golden/<name>.ll`.
+    /// Run with `UPDATE_GOLDEN=1` to rewrite the expected output.
+    fn check_golden(name: &str) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/codegen/golden");
+        let source = std::fs::read_to_string(dir.join(format!("{}.th", name))).unwrap();
+        let ll = emitted(&source);
+
+        let golden = dir.join(format!("{}.ll", name));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&golden, &ll).unwrap();
+        }
+        let expected = std::fs::read_to_string(&golden).unwrap();
+        assert_eq!(ll, expected, "golden LLVM IR of `{}` changed", name);
+        validate(name, &ll);
+    }
+
+    /// Check that `llc` accepts `ll`, if it is installed.
+    fn validate(name: &str, ll: &str) {
+        if let Some(object) = compile(name, ll) {
+            std::fs::remove_dir_all(object.parent().unwrap()).ok();
+        }
+    }
+
+    /// Compile `ll` to an object file with `llc`, or `None` if there is no `llc`.
+    fn compile(name: &str, ll: &str) -> Option<PathBuf> {
+        let version = Command::new("llc").arg("--version").output().ok()?;
+        let version = String::from_utf8_lossy(&version.stdout).to_string();
+        let major: u32 = version
+            .split("LLVM version ")
+            .nth(1)
+            .and_then(|rest| rest.split('.').next())
+            .and_then(|major| major.parse().ok())
+            .unwrap_or(u32::MAX);
+
+        let dir = std::env::temp_dir().join(format!("tahini-ll-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("main.ll");
+        let object = dir.join("main.o");
+        std::fs::write(&source, ll).unwrap();
+
+        let mut llc = Command::new("llc");
+        // Opaque pointers are the default from LLVM 15 on.
+        if major < 15 {
+            llc.arg("-opaque-pointers");
+        }
+        let output = llc
+            .args(["-relocation-model=pic", "-filetype=obj"])
+            .arg(&source)
+            .arg("-o")
+            .arg(&object)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "`llc` rejected the LLVM IR of `{}`: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        Some(object)
+    }
+
+    /// Compile `src` with `llc`, link it with the runtime without the collector and run it, or
+    /// `None` if `llc` or `cc` is missing.
+    fn run(name: &str, src: &str) -> Option<(i32, String)> {
+        let object = compile(name, &emitted(src))?;
+        let dir = object.parent().unwrap().to_path_buf();
+        let runtime = dir.join("runtime.c");
+        let binary = dir.join("main");
+        std::fs::write(&runtime, RUNTIME).unwrap();
+
+        let status = Command::new("cc")
+            .arg("-DTAHINI_NO_GC")
+            .arg(&object)
+            .arg(&runtime)
+            .arg("-o")
+            .arg(&binary)
+            .status()
+            .ok()?;
+        assert!(status.success(), "`cc` could not link `{}`", name);
+        let output = Command::new(&binary).output().unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        Some((
+            output.status.code().unwrap(),
+            String::from_utf8(output.stdout).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn test_golden_closures() {
+        check_golden("closures");
+    }
+
+    #[test]
+    fn test_golden_data() {
+        check_golden("data");
+    }
+
+    #[test]
+    fn test_compiled_program_runs() {
+        let src = "(def stdio (use :header \"stdio.h\")) \n
+            (type point (struct (:x i64) (:y i64))) \n
+            (def fib (fn [(:n i64)] i64 (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))) \n
+            (def make-adder (fn [(:n i64)] fn [i64] i64 (fn [(:x i64)] i64 (+ x n)))) \n
+            (def main (fn [] i32 \n
+                (do \n
+                    (def add5 (make-adder 5)) \n
+                    (def p (point 1 2)) \n
+                    ($ :y p (add5 ($ :y p))) \n
+                    (stdio/printf \"%ld %ld\\n\" (fib 10) ($ :y p)) \n
+                    3)))";
+        let Some((status, stdout)) = run("program", src) else {
+            return;
+        };
+        assert_eq!(status, 3);
+        assert_eq!(stdout, "55 7\n");
+    }
+
+    #[test]
+    fn test_primitive_types() {
+        let ll = emitted(
+            "(def a (fn [(:x i128) (:y u16)] u16 y)) \n
+            (def b (fn [(:x f16)] f16 (+ x 0.5))) \n
+            (def c (fn [(:x f32)] f32 (* x 0.1))) \n
+            (def d (fn [(:x f128)] f128 (- x 1.25)))",
+        );
+        assert!(
+            ll.contains("define i16 @th_a(i128 %arg.x, i16 %arg.y)"),
+            "{}",
+            ll
+        );
+        assert!(ll.contains("fadd half %t0, 0xH3800"), "{}", ll);
+        assert!(ll.contains("fmul float %t0, 0x3FB99999A0000000"), "{}", ll);
+        assert!(
+            ll.contains("fsub fp128 %t0, 0xL00000000000000003FFF400000000000"),
+            "{}",
+            ll
+        );
+        validate("primitives", &ll);
+    }
+
+    #[test]
+    fn test_folded_globals_are_initialized() {
+        let src = "(def stdio (use :header \"stdio.h\")) \n
+            (type shape (data [:circle f64] [:rect i32 i32] [:none])) \n
+            (def PAIR {(+ 1 1) [(* 2 2) 5]}) \n
+            (def SHAPES [[:circle 1.5] [:none] [:rect 7 8]]) \n
+            (def main (fn [] i32 \n
+                (do \n
+                    (def inner ($ [1] PAIR)) \n
+                    (stdio/printf \"%d %d %d\\n\" ($ [0] PAIR) ($ [0] inner) ($ [1] inner)) \n
+                    0)))";
+        let Some((status, stdout)) = run("globals", src) else {
+            return;
+        };
+        assert_eq!(status, 0);
+        assert_eq!(stdout, "2 4 5\n");
+    }
+
+    #[test]
+    fn test_unsized_arrays_are_pointers() {
+        let ll = emitted(
+            "(def first (fn [(:xs [i32])] i32 ($ [0] xs))) \n
+            (def total (fn [] i32 (first [1 2 3])))",
+        );
+        assert!(ll.contains("define i32 @th_first(ptr %arg.xs)"), "{}", ll);
+        assert!(ll.contains("store [3 x i32] %t0, ptr %h2"), "{}", ll);
+        assert!(ll.contains("call i32 @th_first(ptr %h2)"), "{}", ll);
+        validate("unsized", &ll);
+    }
+
+    #[test]
+    fn test_strings_are_decoded() {
+        let ll = emitted("(def greeting \"who?? \\tme\\n\")");
+        assert!(
+            ll.contains(
+                r#"@.str.0 = private unnamed_addr constant [11 x i8] c"who?? \09me\0A\00""#
+            ),
+            "{}",
+            ll
+        );
+        assert!(ll.contains("@th_greeting = global ptr @.str.0"), "{}", ll);
+
+        let ll = emitted("(def long \"\\x123456789!\")");
+        assert!(
+            ll.contains(r#"@.str.0 = private unnamed_addr constant [3 x i8] c"\89!\00""#),
+            "{}",
+            ll
+        );
+    }
+
+    #[test]
+    fn test_callbacks_are_function_pointers() {
+        let ll = emitted(
+            "(def sortints fn [(ptr i32) fn [(ptr i32) (ptr i32)] i32] void) \n
+            (def less (fn [(:a (ptr i32)) (:b (ptr i32))] i32 (- ($ [0] a) ($ [0] b)))) \n
+            (def f (fn [(:xs (ptr i32))] void \n
+                (do (sortints xs less) (sortints xs (fn [(:a (ptr i32)) (:b (ptr i32))] i32 0)))))",
+        );
+        assert!(ll.contains("declare void @sortints(ptr, ptr)"), "{}", ll);
+        assert!(
+            ll.contains("define private i32 @th_less_callback(ptr %a0, ptr %a1) {"),
+            "{}",
+            ll
+        );
+        assert!(
+            ll.contains("call i32 @th_lambda_240({} zeroinitializer, ptr %a1, ptr %a2)"),
+            "{}",
+            ll
+        );
+        assert!(
+            ll.contains("call void @sortints(ptr %t0, ptr @th_less_callback)"),
+            "{}",
+            ll
+        );
+        validate("callbacks", &ll);
+    }
+}
@@ -0,0 +1,82 @@
+//! Backends that turn the IR into source code for other compilers.
+
+pub mod c;
+pub mod llvm;
+
+mod c_test;
+mod llvm_test;
+
+/// The C source of the runtime that compiled programs link with. Compile it with
+/// `-DTAHINI_NO_GC` to allocate with `malloc` instead of the Boehm GC.
+pub const RUNTIME: &str = include_str!("../../runtime/tahini.c");
+
+/// `name` as a C identifier: letters and digits stay, `_` is doubled and every other character
+/// becomes `_` and its code in upper-case hex (two digits, or `u` and six digits beyond ASCII).
+/// Read from the left, every escape starts with `__`, `_u` or `_` and a digit or upper-case
+/// letter, so suffixes like `_closure` never collide with other names.
+pub fn mangle(name: &str) -> String {
+    let mut mangled = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => mangled.push(c),
+            '_' => mangled.push_str("__"),
+            c if c.is_ascii() => mangled.push_str(&format!("_{:02X}", c as u32)),
+            c => mangled.push_str(&format!("_u{:06X}", c as u32)),
+        }
+    }
+    mangled
+}
+
+/// The bytes of a string literal, whose escape sequences are C's.
+pub fn unescape(value: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        let Some(escaped) = chars.next() else {
+            bytes.push(b'\\');
+            break;
+        };
+        match escaped {
+            'n' => bytes.push(b'\n'),
+            't' => bytes.push(b'\t'),
+            'r' => bytes.push(b'\r'),
+            'a' => bytes.push(0x07),
+            'b' => bytes.push(0x08),
+            'f' => bytes.push(0x0C),
+            'v' => bytes.push(0x0B),
+            'e' => bytes.push(0x1B),
+            'x' => {
+                let mut value = 0u32;
+                while let Some(digit) = chars.peek().and_then(|c| c.to_digit(16)) {
+                    // C keeps the low byte of escapes too long for one.
+                    value = value.wrapping_mul(16).wrapping_add(digit);
+                    chars.next();
+                }
+                bytes.push(value as u8);
+            }
+            '0'..='7' => {
+                let mut value = escaped.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                bytes.push(value as u8);
+            }
+            other => {
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(other.encode_utf8(&mut buffer).as_bytes());
+            }
+        }
+    }
+    bytes
+}
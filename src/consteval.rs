@@ -0,0 +1,858 @@
+//! Compile-time evaluation of constant expressions.
+//!
+//! Top-level `def`s whose value is an expression, like `(def area (* PI (square 5.0)))`, and
+//! array lengths that are expressions, like `[i32 (* N 2)]`, are evaluated before type
+//! checking and replaced by literals. Constant expressions can use literals, other constants
+//! of the module, the builtin operators and calls of the module's own functions, which are
+//! interpreted. Anything with effects (external functions, imports, closures) is rejected.
+//!
+//! Integers are evaluated as `i64` and overflow is an error; values passed to and returned from
+//! functions must fit the declared integer types. Local `def`s follow the checker: a `def` of a
+//! visible local assigns to it, any other `def` declares a new local in the innermost scope.
+
+use crate::ast::{FnDef, Literal, Range, Statement, TopLevelDef, TopLevelStatement, VarType};
+use crate::builtins;
+use crate::diagnostic::Diagnostic;
+use crate::typeck::types::{is_float, Aliases};
+use crate::visit::{noop_fold_top_level_def, noop_fold_var_type, Fold};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Statements evaluated for one constant before it is considered non-terminating.
+const MAX_STEPS: usize = 1_000_000;
+
+/// Nested function calls allowed while evaluating a constant.
+const MAX_DEPTH: usize = 256;
+
+/// Evaluate every constant `def` and array length of `module` and replace them with literals.
+pub fn run(module: &[TopLevelStatement]) -> Result<Vec<TopLevelStatement>, Vec<Diagnostic>> {
+    let mut evaluator = Evaluator::new(module);
+    for (item, statement) in module.iter().enumerate() {
+        if let TopLevelStatement::TopLevelDef(def) = statement {
+            if let TopLevelDef::Const(_) = def.instruction {
+                evaluator.item = item;
+                let _ = evaluator.constant(&def.name);
+            }
+        }
+    }
+
+    let mut folder = Folder { evaluator, item: 0 };
+    let folded: Vec<TopLevelStatement> = module
+        .iter()
+        .enumerate()
+        .map(|(item, statement)| {
+            folder.item = item;
+            folder.fold_top_level(statement.clone())
+        })
+        .collect();
+
+    let diagnostics = folder.evaluator.diagnostics;
+    if diagnostics.is_empty() {
+        Ok(folded)
+    } else {
+        Err(diagnostics)
+    }
+}
+
+/// Evaluate `statement` in the global scope of `module`.
+pub fn evaluate(module: &[TopLevelStatement], statement: &Statement) -> Result<Literal, String> {
+    let mut evaluator = Evaluator::new(module);
+    let value = evaluator
+        .eval_root(statement)
+        .map_err(|error| match error {
+            Error::Message(message) => message,
+            Error::Reported | Error::Break | Error::Continue | Error::Return(_) => evaluator
+                .diagnostics
+                .iter()
+                .map(|d| d.message.clone())
+                .collect::<Vec<_>>()
+                .join("; "),
+        })?;
+    value.to_literal()
+}
+
+struct Folder<'a> {
+    evaluator: Evaluator<'a>,
+    item: usize,
+}
+
+impl Fold for Folder<'_> {
+    fn fold_top_level_def(&mut self, def: TopLevelDef) -> TopLevelDef {
+        let TopLevelDef::Const(statement) = def else {
+            return noop_fold_top_level_def(self, def);
+        };
+        let name = self.evaluator.names.get(&self.item).cloned();
+        match name.and_then(|name| self.evaluator.constants.get(&name)) {
+            Some(Constant::Done(value)) => match value.to_literal() {
+                Ok(literal) => TopLevelDef::Literal(literal),
+                Err(message) => {
+                    self.evaluator.error_at(self.item, message);
+                    TopLevelDef::Const(statement)
+                }
+            },
+            _ => TopLevelDef::Const(statement),
+        }
+    }
+
+    fn fold_var_type(&mut self, var_type: VarType) -> VarType {
+        match noop_fold_var_type(self, var_type) {
+            VarType::ArrayConstSized(element, size) => {
+                self.evaluator.item = self.item;
+                match self.evaluator.eval_root(&size) {
+                    Ok(Value::Int(length)) if length >= 0 => {
+                        VarType::ArraySized(element, length as usize)
+                    }
+                    Ok(value) => {
+                        self.evaluator.error_at(
+                            self.item,
+                            format!(
+                                "array length must be a non-negative integer, found `{}`",
+                                value
+                            ),
+                        );
+                        VarType::ArrayConstSized(element, size)
+                    }
+                    Err(error) => {
+                        self.evaluator.report(error);
+                        VarType::ArrayConstSized(element, size)
+                    }
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Void,
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Char(char),
+    String(String),
+    Atom(String),
+    Tuple(Vec<Value>),
+    Array(Vec<Value>),
+    Data(String, Vec<Value>),
+}
+
+impl Value {
+    fn from_literal(literal: &Literal) -> Option<Value> {
+        Some(match literal {
+            Literal::Int(value) => Value::Int(*value),
+            Literal::Float(value) => Value::Float(*value),
+            Literal::Bool(value) => Value::Bool(*value),
+            Literal::Char(value) => Value::Char(*value),
+            Literal::String(value) => Value::String(value.clone()),
+            Literal::Atom(value) => Value::Atom(value.clone()),
+            _ => return None,
+        })
+    }
+
+    fn to_literal(&self) -> Result<Literal, String> {
+        let all = |values: &[Value]| -> Result<Vec<Statement>, String> {
+            values
+                .iter()
+                .map(|value| value.to_literal().map(Statement::Literal))
+                .collect()
+        };
+        Ok(match self {
+            Value::Void => return Err("expression has no value".to_string()),
+            Value::Int(value) => Literal::Int(*value),
+            Value::Float(value) => Literal::Float(*value),
+            Value::Bool(value) => Literal::Bool(*value),
+            Value::Char(value) => Literal::Char(*value),
+            Value::String(value) => Literal::String(value.clone()),
+            Value::Atom(value) => Literal::Atom(value.clone()),
+            Value::Tuple(values) => Literal::Tuple(all(values)?),
+            Value::Array(values) => Literal::Array(all(values)?),
+            Value::Data(tag, values) => Literal::Data(tag.clone(), all(values)?),
+        })
+    }
+}
+
+fn write_values(f: &mut fmt::Formatter<'_>, values: &[Value]) -> fmt::Result {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", value)?;
+    }
+    Ok(())
+}
+
+/// Prints values in the syntax of the literals they came from.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Void => write!(f, "void"),
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Char(value) => write!(f, "'{}'", value),
+            Value::String(value) => write!(f, "{:?}", value),
+            Value::Atom(value) => write!(f, ":{}", value),
+            Value::Tuple(values) => {
+                write!(f, "{{")?;
+                write_values(f, values)?;
+                write!(f, "}}")
+            }
+            Value::Array(values) => {
+                write!(f, "[")?;
+                write_values(f, values)?;
+                write!(f, "]")
+            }
+            Value::Data(tag, values) => {
+                write!(f, "[:{}", tag)?;
+                for value in values {
+                    write!(f, " {}", value)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+enum Error {
+    Message(String),
+    /// The evaluation depends on a constant whose error was already reported at its own item.
+    Reported,
+    /// `(break)` on its way out to the innermost loop.
+    Break,
+    /// `(continue)` on its way out to the innermost loop.
+    Continue,
+    /// `(return)` on its way out to the innermost function, with the value it returns.
+    Return(Value),
+}
+
+type Eval<T = Value> = Result<T, Error>;
+
+fn fail<T>(message: String) -> Eval<T> {
+    Err(Error::Message(message))
+}
+
+/// Stop jumps at the edge of a function or constant that nothing inside it caught.
+fn outside_loop(error: Error) -> Error {
+    match error {
+        Error::Break => Error::Message("`(break)` outside of a `for` loop".to_string()),
+        Error::Continue => Error::Message("`(continue)` outside of a `for` loop".to_string()),
+        Error::Return(_) => Error::Message("`(return)` outside of a function".to_string()),
+        other => other,
+    }
+}
+
+/// Finish one iteration of a loop body; `false` when the body left the loop with `(break)`.
+fn iterate(result: Eval) -> Eval<bool> {
+    match result {
+        Ok(_) | Err(Error::Continue) => Ok(true),
+        Err(Error::Break) => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+enum Constant {
+    Evaluating,
+    Done(Value),
+    Failed,
+}
+
+struct Evaluator<'a> {
+    definitions: HashMap<&'a str, (usize, &'a TopLevelDef)>,
+    /// The name of the constant defined by each item.
+    names: HashMap<usize, String>,
+    aliases: Aliases,
+    constants: HashMap<String, Constant>,
+    diagnostics: Vec<Diagnostic>,
+    item: usize,
+    /// Local scopes of the functions being interpreted, innermost call last.
+    frames: Vec<Vec<HashMap<String, Value>>>,
+    steps: usize,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(module: &'a [TopLevelStatement]) -> Self {
+        let mut evaluator = Evaluator {
+            definitions: HashMap::new(),
+            names: HashMap::new(),
+            aliases: Aliases::default(),
+            constants: HashMap::new(),
+            diagnostics: Vec::new(),
+            item: 0,
+            frames: Vec::new(),
+            steps: 0,
+        };
+        for (item, statement) in module.iter().enumerate() {
+            match statement {
+                TopLevelStatement::TopLevelDef(def) => {
+                    evaluator
+                        .definitions
+                        .insert(&def.name, (item, &def.instruction));
+                    evaluator.names.insert(item, def.name.clone());
+                }
+                TopLevelStatement::TypeAlias(name, var_type) => {
+                    evaluator
+                        .aliases
+                        .types
+                        .insert(name.clone(), var_type.clone());
+                }
+                _ => {}
+            }
+        }
+        evaluator
+    }
+
+    fn error_at(&mut self, item: usize, message: String) {
+        self.diagnostics.push(Diagnostic::error(item, message));
+    }
+
+    fn report(&mut self, error: Error) {
+        if let Error::Message(message) = error {
+            self.error_at(self.item, message);
+        }
+    }
+
+    /// Evaluate an expression outside of any function, with a fresh step budget.
+    fn eval_root(&mut self, statement: &Statement) -> Eval {
+        let saved = (std::mem::take(&mut self.frames), self.steps);
+        self.frames.push(vec![HashMap::new()]);
+        self.steps = 0;
+        let result = self.eval(statement).map_err(outside_loop);
+        (self.frames, self.steps) = saved;
+        result
+    }
+
+    /// The value of the top-level constant or literal `name`, evaluating it on first use.
+    /// Errors of constants are reported at the item that defines them.
+    fn constant(&mut self, name: &str) -> Eval {
+        match self.constants.get(name) {
+            Some(Constant::Done(value)) => return Ok(value.clone()),
+            Some(Constant::Failed) => return Err(Error::Reported),
+            Some(Constant::Evaluating) => {
+                return fail(format!("constant `{}` depends on itself", name))
+            }
+            None => {}
+        }
+        let (item, def) = self.definitions[name];
+        let TopLevelDef::Const(statement) = def else {
+            unreachable!("only constants are evaluated on demand")
+        };
+
+        self.constants
+            .insert(name.to_string(), Constant::Evaluating);
+        let caller = std::mem::replace(&mut self.item, item);
+        let result = self.eval_root(statement);
+        self.item = caller;
+
+        match result {
+            Ok(Value::Void) => {
+                self.error_at(item, format!("constant `{}` has no value", name));
+                self.constants.insert(name.to_string(), Constant::Failed);
+                Err(Error::Reported)
+            }
+            Ok(value) => {
+                self.constants
+                    .insert(name.to_string(), Constant::Done(value.clone()));
+                Ok(value)
+            }
+            Err(error) => {
+                if let Error::Message(message) = error {
+                    self.error_at(
+                        item,
+                        format!("cannot evaluate constant `{}`: {}", name, message),
+                    );
+                }
+                self.constants.insert(name.to_string(), Constant::Failed);
+                Err(Error::Reported)
+            }
+        }
+    }
+
+    fn scopes(&mut self) -> &mut Vec<HashMap<String, Value>> {
+        self.frames.last_mut().expect("evaluation runs in a frame")
+    }
+
+    fn lookup_local(&mut self, name: &str) -> Option<&mut Value> {
+        self.scopes()
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+    }
+
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes().push(HashMap::new());
+        let result = f(self);
+        self.scopes().pop();
+        result
+    }
+
+    fn lookup(&mut self, name: &str) -> Eval {
+        if let Some(value) = self.lookup_local(name) {
+            return Ok(value.clone());
+        }
+        match self.definitions.get(name).copied() {
+            Some((_, TopLevelDef::Literal(literal))) => self.eval_literal(literal),
+            Some((_, TopLevelDef::Const(_))) => self.constant(name),
+            Some((_, TopLevelDef::FnDef(_))) => fail(format!(
+                "function `{}` cannot be used as a constant value",
+                name
+            )),
+            Some((_, TopLevelDef::Typed(_))) => fail(format!("`{}` has no value", name)),
+            None if builtins::split_path(name).is_some() => fail(format!(
+                "`{}` is imported; only definitions of this module can be evaluated",
+                name
+            )),
+            None => fail(format!("`{}` is not defined", name)),
+        }
+    }
+
+    fn eval_literal(&mut self, literal: &Literal) -> Eval {
+        if let Some(value) = Value::from_literal(literal) {
+            return Ok(value);
+        }
+        match literal {
+            Literal::Tuple(items) => Ok(Value::Tuple(self.eval_all(items)?)),
+            Literal::Array(items) => Ok(Value::Array(self.eval_all(items)?)),
+            Literal::Data(tag, args) => Ok(Value::Data(tag.clone(), self.eval_all(args)?)),
+            _ => fail("functions cannot be evaluated at compile time".to_string()),
+        }
+    }
+
+    fn eval_all(&mut self, statements: &[Statement]) -> Eval<Vec<Value>> {
+        statements.iter().map(|s| self.eval(s)).collect()
+    }
+
+    fn eval_bool(&mut self, statement: &Statement) -> Eval<bool> {
+        match self.eval(statement)? {
+            Value::Bool(value) => Ok(value),
+            other => fail(format!("expected a `bool`, found `{}`", other)),
+        }
+    }
+
+    fn eval_int(&mut self, statement: &Statement) -> Eval<i64> {
+        match self.eval(statement)? {
+            Value::Int(value) => Ok(value),
+            other => fail(format!("expected an integer, found `{}`", other)),
+        }
+    }
+
+    /// The values the loop variable of `(for (range x ...) ...)` takes, in order.
+    fn range_items(&mut self, range: &Range<Statement>) -> Eval<Vec<Value>> {
+        match range {
+            Range::Each(range) => match self.eval(range)? {
+                Value::Int(end) => Ok((0..end.max(0)).map(Value::Int).collect()),
+                Value::Array(items) => Ok(items),
+                other => fail(format!("cannot iterate over `{}`", other)),
+            },
+            Range::Bounds(start, end, step) => {
+                let start = self.eval_int(start)?;
+                let end = self.eval_int(end)?;
+                let step = match step {
+                    Some(step) => self.eval_int(step)?,
+                    None => 1,
+                };
+                let mut items = Vec::new();
+                let mut i = start;
+                while (step > 0 && i < end) || (step < 0 && i > end) {
+                    items.push(Value::Int(i));
+                    if items.len() > MAX_STEPS {
+                        return fail(format!(
+                            "evaluation did not finish within {} steps",
+                            MAX_STEPS
+                        ));
+                    }
+                    let Some(next) = i.checked_add(step) else {
+                        break;
+                    };
+                    i = next;
+                }
+                Ok(items)
+            }
+            Range::Slice(array, start, end) => {
+                let array = self.eval(array)?;
+                let start = self.eval_int(start)?;
+                let end = self.eval_int(end)?;
+                let Value::Array(items) = array else {
+                    return fail(format!("cannot slice `{}`", array));
+                };
+                if start < 0 || start > end || end > items.len() as i64 {
+                    return fail(format!(
+                        "slice {}..{} is out of bounds for an array of {} elements",
+                        start,
+                        end,
+                        items.len()
+                    ));
+                }
+                Ok(items[start as usize..end as usize].to_vec())
+            }
+        }
+    }
+
+    fn eval(&mut self, statement: &Statement) -> Eval {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return fail(format!(
+                "evaluation did not finish within {} steps",
+                MAX_STEPS
+            ));
+        }
+
+        match statement {
+            Statement::Ident(name) => self.lookup(name),
+            Statement::Literal(literal) => self.eval_literal(literal),
+            Statement::DoBlock(statements) => self.scoped(|evaluator| {
+                let mut last = Value::Void;
+                for statement in statements {
+                    last = evaluator.eval(statement)?;
+                }
+                Ok(last)
+            }),
+            Statement::DefVar(def) => {
+                let value = self.eval(&def.instruction)?;
+                match self.lookup_local(&def.name) {
+                    Some(existing) => *existing = value,
+                    None => {
+                        let scope = self.scopes().last_mut().expect("frames have a scope");
+                        scope.insert(def.name.clone(), value);
+                    }
+                }
+                Ok(Value::Void)
+            }
+            Statement::Declare(name, _) => {
+                let scope = self.scopes().last_mut().expect("frames have a scope");
+                scope.insert(name.clone(), Value::Void);
+                Ok(Value::Void)
+            }
+            Statement::If(condition, then) => {
+                if self.eval_bool(condition)? {
+                    self.scoped(|evaluator| evaluator.eval(then))?;
+                }
+                Ok(Value::Void)
+            }
+            Statement::IfElse(condition, then, otherwise) => {
+                let branch = if self.eval_bool(condition)? {
+                    then
+                } else {
+                    otherwise
+                };
+                self.scoped(|evaluator| evaluator.eval(branch))
+            }
+            Statement::For(condition, body) => {
+                while self.eval_bool(condition)? {
+                    if !iterate(self.scoped(|evaluator| evaluator.eval(body)))? {
+                        break;
+                    }
+                }
+                Ok(Value::Void)
+            }
+            Statement::ForRange(name, range, body) => {
+                for item in self.range_items(range)? {
+                    let result = self.scoped(|evaluator| {
+                        evaluator
+                            .scopes()
+                            .last_mut()
+                            .expect("scoped pushes a scope")
+                            .insert(name.clone(), item);
+                        evaluator.eval(body)
+                    });
+                    if !iterate(result)? {
+                        break;
+                    }
+                }
+                Ok(Value::Void)
+            }
+            Statement::Break => Err(Error::Break),
+            Statement::Continue => Err(Error::Continue),
+            Statement::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Void,
+                };
+                Err(Error::Return(value))
+            }
+            Statement::Call(name, args) | Statement::GenericCall(name, _, args) => {
+                self.eval_call(name, args)
+            }
+            Statement::GetField(field, _) => {
+                fail(format!("field `{}` cannot be read at compile time", field))
+            }
+            Statement::GetIndexed(index, target) => {
+                let target = self.eval(target)?;
+                let index = self.eval(index)?;
+                Ok(element(&target, &index)?.clone())
+            }
+            Statement::SetIndexed(index, target, value) => {
+                let Statement::Ident(name) = target.as_ref() else {
+                    return fail("only locals can be assigned at compile time".to_string());
+                };
+                let index = self.eval(index)?;
+                let value = self.eval(value)?;
+                let Some(target) = self.lookup_local(name) else {
+                    return fail(format!(
+                        "`{}` is not a local and cannot be assigned at compile time",
+                        name
+                    ));
+                };
+                *element_mut(target, &index)? = value;
+                Ok(Value::Void)
+            }
+            Statement::SetField(field, ..) => fail(format!(
+                "field `{}` cannot be assigned at compile time",
+                field
+            )),
+        }
+    }
+
+    fn eval_call(&mut self, name: &str, args: &[Statement]) -> Eval {
+        if let Some(all) = match name {
+            "&&" | "and" => Some(true),
+            "||" | "or" => Some(false),
+            _ => None,
+        } {
+            // `&&` stops at the first `false`, `||` at the first `true`.
+            for arg in args {
+                if self.eval_bool(arg)? != all {
+                    return Ok(Value::Bool(!all));
+                }
+            }
+            return Ok(Value::Bool(all));
+        }
+
+        let is_local = self.lookup_local(name).is_some();
+        if !is_local && builtins::is_builtin_fn(name) {
+            let mut values = Vec::with_capacity(args.len());
+            for arg in args {
+                values.push(self.eval(arg)?);
+            }
+            return apply_builtin(name, values);
+        }
+
+        match self.definitions.get(name).copied() {
+            Some((_, TopLevelDef::FnDef(fn_def))) if !is_local => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(self.eval(arg)?);
+                }
+                self.call(name, fn_def, values)
+            }
+            _ if self.aliases.types.contains_key(name) => fail(format!(
+                "values of `{}` cannot be built at compile time",
+                name
+            )),
+            _ => fail(format!("`{}` cannot be called at compile time", name)),
+        }
+    }
+
+    fn call(&mut self, name: &str, fn_def: &FnDef, args: Vec<Value>) -> Eval {
+        if self.frames.len() > MAX_DEPTH {
+            return fail(format!(
+                "calls nested deeper than {} while calling `{}`",
+                MAX_DEPTH, name
+            ));
+        }
+        if args.len() != fn_def.parameters.len() {
+            return fail(format!(
+                "`{}` takes {} arguments, found {}",
+                name,
+                fn_def.parameters.len(),
+                args.len()
+            ));
+        }
+
+        let mut scope = HashMap::new();
+        for ((parameter, var_type), value) in fn_def.parameters.iter().zip(args) {
+            let value = self.coerce(value, var_type).map_err(|value| {
+                format!(
+                    "argument `{}` of `{}` overflows `{}`",
+                    value, name, var_type
+                )
+            });
+            scope.insert(parameter.clone(), value.map_err(Error::Message)?);
+        }
+
+        self.frames.push(vec![scope]);
+        let result = match self.eval(&fn_def.statement) {
+            Err(Error::Return(value)) => Ok(value),
+            result => result.map_err(outside_loop),
+        };
+        self.frames.pop();
+
+        let value = result?;
+        self.coerce(value, &fn_def.return_type).map_err(|value| {
+            Error::Message(format!(
+                "value `{}` returned by `{}` overflows `{}`",
+                value, name, fn_def.return_type
+            ))
+        })
+    }
+
+    /// Convert an integer to a float where a float is declared, and check that integers fit
+    /// their declared type. Fails with the value that does not fit.
+    fn coerce(&self, value: Value, var_type: &VarType) -> Result<Value, Value> {
+        let var_type = self.aliases.normalize(var_type);
+        match value {
+            Value::Int(n) if is_float(&var_type) => Ok(Value::Float(n as f64)),
+            Value::Int(n) if !fits(n, &var_type) => Err(value),
+            other => Ok(other),
+        }
+    }
+}
+
+fn fits(n: i64, var_type: &VarType) -> bool {
+    match var_type {
+        VarType::Int8 => i8::try_from(n).is_ok(),
+        VarType::Int16 => i16::try_from(n).is_ok(),
+        VarType::Int32 => i32::try_from(n).is_ok(),
+        VarType::UInt8 => u8::try_from(n).is_ok(),
+        VarType::UInt16 => u16::try_from(n).is_ok(),
+        VarType::UInt32 => u32::try_from(n).is_ok(),
+        VarType::UInt64 | VarType::UInt128 => n >= 0,
+        _ => true,
+    }
+}
+
+fn position(index: &Value, length: usize) -> Result<usize, Error> {
+    match index {
+        Value::Int(i) if *i >= 0 && (*i as usize) < length => Ok(*i as usize),
+        Value::Int(i) => Err(Error::Message(format!(
+            "index {} is out of bounds for length {}",
+            i, length
+        ))),
+        other => Err(Error::Message(format!(
+            "index must be an integer, found `{}`",
+            other
+        ))),
+    }
+}
+
+fn element<'v>(target: &'v Value, index: &Value) -> Result<&'v Value, Error> {
+    match target {
+        Value::Array(items) | Value::Tuple(items) => Ok(&items[position(index, items.len())?]),
+        other => Err(Error::Message(format!("`{}` cannot be indexed", other))),
+    }
+}
+
+fn element_mut<'v>(target: &'v mut Value, index: &Value) -> Result<&'v mut Value, Error> {
+    match target {
+        Value::Array(items) | Value::Tuple(items) => {
+            let i = position(index, items.len())?;
+            Ok(&mut items[i])
+        }
+        other => Err(Error::Message(format!("`{}` cannot be indexed", other))),
+    }
+}
+
+/// Apply an arithmetic, comparison, bitwise or `!`/`not` operator. Operands mixing integers and
+/// floats are computed as floats, like literals adopting the type of a float operand.
+fn apply_builtin(name: &str, args: Vec<Value>) -> Eval {
+    if name == "!" || name == "not" {
+        return match args.as_slice() {
+            [Value::Bool(value)] => Ok(Value::Bool(!value)),
+            _ => fail(format!("`{}` takes one `bool`", name)),
+        };
+    }
+
+    let any_float = args.iter().any(|arg| matches!(arg, Value::Float(_)));
+    let args: Vec<Value> = if any_float {
+        args.into_iter()
+            .map(|arg| match arg {
+                Value::Int(n) => Value::Float(n as f64),
+                other => other,
+            })
+            .collect()
+    } else {
+        args
+    };
+
+    if builtins::COMPARISON.contains(&name) {
+        // `(< a b c)` holds if every adjacent pair does.
+        for pair in args.windows(2) {
+            if !compare(name, &pair[0], &pair[1])? {
+                return Ok(Value::Bool(false));
+            }
+        }
+        return Ok(Value::Bool(true));
+    }
+
+    let mut args = args.into_iter();
+    let Some(first) = args.next() else {
+        return fail(format!("`{}` needs an operand", name));
+    };
+    if args.len() == 0 {
+        return match (name, first) {
+            ("-", Value::Int(n)) => n
+                .checked_neg()
+                .map(Value::Int)
+                .ok_or_else(|| Error::Message("integer overflow in `-`".to_string())),
+            ("-", Value::Float(x)) => Ok(Value::Float(-x)),
+            (_, value @ (Value::Int(_) | Value::Float(_))) => Ok(value),
+            (_, other) => fail(format!("`{}` cannot be applied to `{}`", name, other)),
+        };
+    }
+    args.try_fold(first, |left, right| binary(name, left, right))
+}
+
+fn compare(name: &str, left: &Value, right: &Value) -> Eval<bool> {
+    use std::cmp::Ordering;
+    let ordering = match (left, right) {
+        (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::Char(a), Value::Char(b)) => a.partial_cmp(b),
+        _ if name == "=" => return Ok(left == right),
+        _ if name == "!=" => return Ok(left != right),
+        _ => {
+            return fail(format!(
+                "`{}` cannot compare `{}` and `{}`",
+                name, left, right
+            ))
+        }
+    };
+    // NaN is unordered and only `!=` to anything.
+    let Some(ordering) = ordering else {
+        return Ok(name == "!=");
+    };
+    Ok(match name {
+        "=" => ordering == Ordering::Equal,
+        "!=" => ordering != Ordering::Equal,
+        "<" => ordering == Ordering::Less,
+        ">" => ordering == Ordering::Greater,
+        "<=" => ordering != Ordering::Greater,
+        _ => ordering != Ordering::Less,
+    })
+}
+
+fn binary(name: &str, left: Value, right: Value) -> Eval {
+    let overflow = || Error::Message(format!("integer overflow in `{}`", name));
+    match (left, right) {
+        (Value::Int(a), Value::Int(b)) => {
+            if (name == "/" || name == "%") && b == 0 {
+                return fail("division by zero".to_string());
+            }
+            let result = match name {
+                "+" => a.checked_add(b),
+                "-" => a.checked_sub(b),
+                "*" => a.checked_mul(b),
+                "/" => a.checked_div(b),
+                "%" => a.checked_rem(b),
+                "&" => Some(a & b),
+                "|" => Some(a | b),
+                "^" => Some(a ^ b),
+                "<<" => u32::try_from(b).ok().and_then(|b| a.checked_shl(b)),
+                _ => u32::try_from(b).ok().and_then(|b| a.checked_shr(b)),
+            };
+            result.map(Value::Int).ok_or_else(overflow)
+        }
+        (Value::Float(a), Value::Float(b)) => Ok(Value::Float(match name {
+            "+" => a + b,
+            "-" => a - b,
+            "*" => a * b,
+            "/" => a / b,
+            "%" => a % b,
+            _ => return fail(format!("`{}` cannot be applied to floats", name)),
+        })),
+        (left, right) => fail(format!(
+            "`{}` cannot be applied to `{}` and `{}`",
+            name, left, right
+        )),
+    }
+}
@@ -0,0 +1,199 @@
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Literal, Statement, TopLevelDef, TopLevelStatement, VarType};
+    use crate::consteval::{evaluate, run};
+    use crate::parser::{parser, statement};
+    use chumsky::Parser;
+
+    fn module(src: &str) -> Vec<TopLevelStatement> {
+        parser().parse(src).into_output().unwrap()
+    }
+
+    fn eval(src: &str, expression: &str) -> Result<Literal, String> {
+        evaluate(&module(src), &statement().parse(expression).unwrap())
+    }
+
+    fn errors(src: &str) -> Vec<String> {
+        run(&module(src))
+            .unwrap_err()
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+
+    fn def(statement: &TopLevelStatement) -> &TopLevelDef {
+        match statement {
+            TopLevelStatement::TopLevelDef(def) => &def.instruction,
+            other => panic!("not a def: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_folds_operators() {
+        assert_eq!(eval("", "(+ 1 2 3)"), Ok(Literal::Int(6)));
+        assert_eq!(eval("", "(- 5)"), Ok(Literal::Int(-5)));
+        assert_eq!(eval("", "(* 2 2.5)"), Ok(Literal::Float(5.0)));
+        assert_eq!(eval("", "(/ 7 2)"), Ok(Literal::Int(3)));
+        assert_eq!(eval("", "(<< 1 4)"), Ok(Literal::Int(16)));
+        assert_eq!(eval("", "(< 1 2 3)"), Ok(Literal::Bool(true)));
+        assert_eq!(eval("", "(= :a :b)"), Ok(Literal::Bool(false)));
+        assert_eq!(eval("", "(and true (not false))"), Ok(Literal::Bool(true)));
+    }
+
+    #[test]
+    fn test_arithmetic_errors() {
+        assert_eq!(eval("", "(/ 1 0)"), Err("division by zero".to_string()));
+        assert_eq!(
+            eval("", "(* 9223372036854775807 2)"),
+            Err("integer overflow in `*`".to_string())
+        );
+        assert_eq!(
+            eval("", "(+ true 1)"),
+            Err("`+` cannot be applied to `true` and `1`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        // The right operand would fail if it were evaluated.
+        assert_eq!(eval("", "(|| true (= 1 (/ 1 0)))"), Ok(Literal::Bool(true)));
+    }
+
+    #[test]
+    fn test_calls_module_functions() {
+        let src = "(def PI 3.5)
+            (def square (fn [(:x f64)] f64 (* x x)))
+            (def fact (fn [(:n i64)] i64 (if (< n 2) 1 (* n (fact (- n 1))))))
+            (def sum-to (fn [(:n i64)] i64
+              (do
+                (def total 0)
+                (for (range i n) (def total (+ total i)))
+                total)))";
+        assert_eq!(eval(src, "(* PI (square 2))"), Ok(Literal::Float(14.0)));
+        assert_eq!(eval(src, "(fact 10)"), Ok(Literal::Int(3628800)));
+        assert_eq!(eval(src, "(sum-to 5)"), Ok(Literal::Int(10)));
+    }
+
+    #[test]
+    fn test_loops() {
+        let src = "(def sum (fn [(:start i64) (:end i64) (:step i64)] i64
+              (do
+                (def total 0)
+                (for (range i start end step) (def total (+ total i)))
+                total)))
+            (def first-over (fn [(:limit i64)] i64
+              (do
+                (def found 0)
+                (for (range x :slice [1 5 9 12] 1 4)
+                  (do
+                    (if (<= x limit) (continue))
+                    (def found x)
+                    (break)))
+                found)))";
+        assert_eq!(eval(src, "(sum 1 5 1)"), Ok(Literal::Int(10)));
+        assert_eq!(eval(src, "(sum 10 0 (- 0 3))"), Ok(Literal::Int(22)));
+        assert_eq!(eval(src, "(sum 0 10 0)"), Ok(Literal::Int(0)));
+        assert_eq!(eval(src, "(first-over 6)"), Ok(Literal::Int(9)));
+        assert_eq!(
+            eval("", "(for (range x :slice [1 2] 1 3) x)"),
+            Err("slice 1..3 is out of bounds for an array of 2 elements".to_string())
+        );
+    }
+
+    #[test]
+    fn test_declared_integer_types_are_checked() {
+        let src = "(def twice (fn [(:x u8)] u8 (* x 2)))";
+        assert_eq!(eval(src, "(twice 100)"), Ok(Literal::Int(200)));
+        assert_eq!(
+            eval(src, "(twice 200)"),
+            Err("value `400` returned by `twice` overflows `u8`".to_string())
+        );
+        assert_eq!(
+            eval(src, "(twice (- 0 1))"),
+            Err("argument `-1` of `twice` overflows `u8`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_effects_are_rejected() {
+        let src = "(def printf fn [str ...] i32)
+            (def greet (fn [] i32 (printf \"hi\")))";
+        assert_eq!(
+            eval(src, "(greet)"),
+            Err("`printf` cannot be called at compile time".to_string())
+        );
+        assert_eq!(
+            eval(src, "(* math/PI 2.0)"),
+            Err(
+                "`math/PI` is imported; only definitions of this module can be evaluated"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_non_terminating_evaluation_is_stopped() {
+        let src = "(def spin (fn [] i32 (do (for true (do)) 0)))";
+        assert_eq!(
+            eval(src, "(spin)"),
+            Err("evaluation did not finish within 1000000 steps".to_string())
+        );
+    }
+
+    #[test]
+    fn test_run_folds_constants_and_array_sizes() {
+        let folded = run(&module(
+            "(def AREA (* SIDE SIDE))
+            (def SIDE (+ 2 2))
+            (type grid [i32 AREA])
+            (def sum (fn [(:xs (array i32 (- AREA 1)))] i32 0))",
+        ))
+        .unwrap();
+
+        assert_eq!(def(&folded[0]), &TopLevelDef::Literal(Literal::Int(16)));
+        assert_eq!(def(&folded[1]), &TopLevelDef::Literal(Literal::Int(4)));
+        assert_eq!(
+            folded[2],
+            TopLevelStatement::TypeAlias(
+                "grid".to_string(),
+                VarType::ArraySized(Box::new(VarType::Int32), 16)
+            )
+        );
+        let TopLevelDef::FnDef(sum) = def(&folded[3]) else {
+            panic!("`sum` is not a function");
+        };
+        assert_eq!(
+            sum.parameters[0].1,
+            VarType::ArraySized(Box::new(VarType::Int32), 15)
+        );
+    }
+
+    #[test]
+    fn test_run_folds_compound_values() {
+        let folded = run(&module("(def PAIR {(+ 1 1) [(* 2 2) 5]})")).unwrap();
+        let item = |value| Statement::Literal(Literal::Int(value));
+        assert_eq!(
+            def(&folded[0]),
+            &TopLevelDef::Literal(Literal::Tuple(vec![
+                item(2),
+                Statement::Literal(Literal::Array(vec![item(4), item(5)])),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_run_reports_each_failure_once() {
+        assert_eq!(
+            errors("(def A (/ 1 0)) (def B (+ A 1)) (type t [i8 B])"),
+            vec!["cannot evaluate constant `A`: division by zero"]
+        );
+        assert_eq!(
+            errors("(def A (+ B 1)) (def B (* A 2))"),
+            vec!["cannot evaluate constant `B`: constant `A` depends on itself"]
+        );
+        assert_eq!(
+            errors("(type t [i8 (- 0 1)])"),
+            vec!["array length must be a non-negative integer, found `-1`"]
+        );
+    }
+}
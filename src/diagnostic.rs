@@ -0,0 +1,115 @@
+use crate::parser::Span;
+use chumsky::error::{Rich, RichPattern, RichReason};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found by one of the semantic passes.
+///
+/// The AST does not carry source locations, so diagnostics refer to the top-level statement
+/// they were raised in by index. [`Diagnostic::span`] maps that index back to the source when
+/// the program was parsed with [`crate::parser::spanned_parser`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub item: usize,
+}
+
+impl Diagnostic {
+    pub fn error(item: usize, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+            item,
+        }
+    }
+
+    pub fn warning(item: usize, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: message.into(),
+            item,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    pub fn span(&self, spans: &[Span]) -> Option<Span> {
+        spans.get(self.item).copied()
+    }
+
+    /// Render as `line:column: severity: message`, using the span of the offending item.
+    pub fn render(&self, source: &str, spans: &[Span]) -> String {
+        match self.span(spans) {
+            Some(span) => {
+                let (line, column) = position(source, span.start);
+                format!("{}:{}: {}", line, column, self)
+            }
+            None => self.to_string(),
+        }
+    }
+}
+
+/// Render a parse error as `line:column: error: unexpected ..., expected ...`.
+pub fn render_parse_error(source: &str, error: &Rich<'_, char>) -> String {
+    let start = error.span().start;
+    let (line, column) = position(source, start);
+    let message = match error.reason() {
+        RichReason::ExpectedFound { expected, .. } => {
+            // Filters report no token, so take what was found from the source.
+            let found = match source.get(start..).and_then(|rest| rest.chars().next()) {
+                Some(c) => format!("'{}'", c.escape_debug()),
+                None => "end of input".to_string(),
+            };
+            let expected: Vec<String> = expected.iter().filter_map(pattern).collect();
+            match expected.split_last() {
+                None => format!("unexpected {}", found),
+                Some((last, [])) => format!("unexpected {}, expected {}", found, last),
+                Some((last, rest)) => format!(
+                    "unexpected {}, expected {} or {}",
+                    found,
+                    rest.join(", "),
+                    last
+                ),
+            }
+        }
+        RichReason::Custom(message) => message.clone(),
+    };
+    format!("{}:{}: error: {}", line, column, message)
+}
+
+fn pattern(pattern: &RichPattern<'_, char>) -> Option<String> {
+    match pattern {
+        RichPattern::Token(c) => Some(format!("'{}'", c.escape_debug())),
+        RichPattern::Label(label) => Some(label.to_string()),
+        RichPattern::Identifier(word) => Some(format!("`{}`", word)),
+        RichPattern::EndOfInput => Some("end of input".to_string()),
+        RichPattern::Any | RichPattern::SomethingElse => None,
+    }
+}
+
+/// The 1-based line and column of the byte `offset` in `source`.
+fn position(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = before[start..].chars().count() + 1;
+    (line, column)
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
@@ -0,0 +1,779 @@
+//! The compiler driver behind `tahini build`.
+//!
+//! Every `.th` file is a compilation unit that becomes its own object file. The driver loads
+//! the files it is given and, transitively, the tahini modules they import (`(use "math")` is
+//! `math.th` next to the importing file), checks and compiles each of them with the C or LLVM
+//! backend, and links the objects with the runtime (`runtime/tahini.c`) and the Boehm GC using
+//! the system C compiler.
+//!
+//! A unit only sees the exports of the modules it imports: their functions and globals are
+//! declared in it as `alias/member` before type checking, and [`link::qualify`] gives every
+//! unit's definitions the names `module/name` so that the declarations and the definitions
+//! meet at link time. The module name of a file is its path relative to the directory of the
+//! first input, without the extension. The members of C headers (`(use :header "stdio.h")`)
+//! are declared the same way, from what [`header::import`] reads of the header.
+
+use crate::ast::{DefVar, Literal, TopLevelDef, TopLevelStatement, VarType};
+use crate::codegen;
+use crate::consteval;
+use crate::diagnostic::{render_parse_error, Diagnostic};
+use crate::header;
+use crate::ir::{link, lower, Program};
+use crate::lint;
+use crate::parser::{spanned_parser, Span};
+use crate::resolve::{self, BindingKind, Target};
+use crate::transformer::{self, ast::Module};
+use crate::typeck::types::Aliases;
+use crate::typeck::{self, Checker};
+use chumsky::Parser;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// What `tahini build` produces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Emit {
+    /// The AST after constant folding, monomorphization and closure conversion.
+    Ast,
+    Ir,
+    C,
+    /// A C header declaring the exports of each module, for C code that calls them.
+    Header,
+    Llvm,
+    Obj,
+    Exe,
+}
+
+impl Emit {
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "ast" => Emit::Ast,
+            "ir" => Emit::Ir,
+            "c" => Emit::C,
+            "h" => Emit::Header,
+            "llvm" => Emit::Llvm,
+            "obj" => Emit::Obj,
+            "exe" => Emit::Exe,
+            _ => return None,
+        })
+    }
+
+    /// The extension of the file written for each unit.
+    fn extension(self) -> &'static str {
+        match self {
+            Emit::Ast => "ast",
+            Emit::Ir => "ir",
+            Emit::C => "c",
+            Emit::Header => "h",
+            Emit::Llvm => "ll",
+            Emit::Obj => "o",
+            Emit::Exe => "",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    C,
+    Llvm,
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub emit: Emit,
+    /// The backend that object files and executables are built with.
+    pub backend: Backend,
+    /// The output file. By default every unit is written next to its source with the extension
+    /// of `emit`, and an executable is named after the first input.
+    pub output: Option<PathBuf>,
+    /// Header directories (`-I`) for the C compiler, and library directories (`-L`) and
+    /// libraries (`-l`) to link with.
+    pub include_dirs: Vec<String>,
+    pub lib_dirs: Vec<String>,
+    pub libs: Vec<String>,
+    /// Whether the runtime allocates from the Boehm GC. Without it, memory comes from `malloc`
+    /// and is never freed.
+    pub gc: bool,
+    /// The C compiler that compiles C and links, `$CC` or `cc`.
+    pub cc: String,
+    /// The LLVM compiler that turns LLVM IR into objects, `$LLC` or `llc`.
+    pub llc: String,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            emit: Emit::Exe,
+            backend: Backend::C,
+            output: None,
+            include_dirs: Vec::new(),
+            lib_dirs: Vec::new(),
+            libs: Vec::new(),
+            gc: true,
+            cc: std::env::var("CC").unwrap_or_else(|_| "cc".to_string()),
+            llc: std::env::var("LLC").unwrap_or_else(|_| "llc".to_string()),
+        }
+    }
+}
+
+/// A source file and the module it is compiled as.
+pub struct Unit {
+    /// The name that qualifies the symbols of the unit, e.g. `graphics/rendering`.
+    pub module: String,
+    pub path: PathBuf,
+    pub source: String,
+    pub statements: Vec<TopLevelStatement>,
+    pub spans: Vec<Span>,
+    /// Import aliases and the modules they name.
+    pub imports: BTreeMap<String, String>,
+}
+
+impl Unit {
+    /// `path:line:column: severity: message`.
+    fn render(&self, diagnostic: &Diagnostic) -> String {
+        format!(
+            "{}:{}",
+            self.path.display(),
+            diagnostic.render(&self.source, &self.spans)
+        )
+    }
+}
+
+/// Build `inputs` as `options` say. Returns the warnings, or the errors and warnings, rendered
+/// with their file and position.
+pub fn build(inputs: &[PathBuf], options: &Options) -> Result<Vec<String>, Vec<String>> {
+    let units = load(inputs).map_err(|error| vec![error])?;
+    let targets = match options.emit {
+        // An executable needs every module; other outputs are only written for the inputs.
+        Emit::Exe => units.len(),
+        _ => inputs.len(),
+    };
+    if options.emit != Emit::Exe && targets > 1 && options.output.is_some() {
+        return Err(vec![
+            "`-o` names a single file; build one module at a time".to_string()
+        ]);
+    }
+
+    let mut messages = Vec::new();
+    let mut compiled = Vec::new();
+    for unit in &units[..targets] {
+        match compile(unit, &units, options) {
+            Ok((module, program, warnings)) => {
+                messages.extend(warnings);
+                compiled.push((unit, module, program));
+            }
+            Err(errors) => messages.extend(errors),
+        }
+    }
+    if compiled.len() < targets {
+        return Err(messages);
+    }
+
+    let scratch = scratch_dir();
+    let result = emit(&compiled, options, &scratch);
+    std::fs::remove_dir_all(&scratch).ok();
+    match result {
+        Ok(()) => Ok(messages),
+        Err(error) => {
+            messages.push(error);
+            Err(messages)
+        }
+    }
+}
+
+/// Parse `inputs` and every module they import, inputs first.
+pub fn load(inputs: &[PathBuf]) -> Result<Vec<Unit>, String> {
+    let Some(first) = inputs.first() else {
+        return Err("no input files".to_string());
+    };
+    let root = first.parent().unwrap_or(Path::new("")).to_path_buf();
+    let mut units: Vec<Unit> = Vec::new();
+    let mut queue: Vec<PathBuf> = inputs.to_vec();
+    let mut next = 0;
+    while next < queue.len() {
+        let path = queue[next].clone();
+        next += 1;
+        let source = std::fs::read_to_string(&path)
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+        let parsed = spanned_parser()
+            .parse(source.as_str())
+            .into_result()
+            .map_err(|errors| {
+                let rendered = errors.iter().map(|error| {
+                    format!("{}:{}", path.display(), render_parse_error(&source, error))
+                });
+                rendered.collect::<Vec<_>>().join("\n")
+            })?;
+        let (statements, spans): (Vec<_>, Vec<_>) = parsed.into_iter().unzip();
+
+        let mut imports = BTreeMap::new();
+        for statement in &statements {
+            if let TopLevelStatement::Use(alias, import) = statement {
+                let file = path
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .join(format!("{}.th", import));
+                // A file imported under another path keeps the module name it was queued as.
+                let file = match queue.iter().find(|known| same_file(known, &file)) {
+                    Some(known) => known.clone(),
+                    None => {
+                        queue.push(file.clone());
+                        file
+                    }
+                };
+                imports.insert(alias.clone(), module_name(&root, &file));
+            }
+        }
+        units.push(Unit {
+            module: module_name(&root, &path),
+            path,
+            source,
+            statements,
+            spans,
+            imports,
+        });
+    }
+    Ok(units)
+}
+
+/// The functions and globals `unit` exports, with their types.
+pub fn interface(unit: &Unit) -> BTreeMap<String, VarType> {
+    // Constants get their types once they are evaluated. Errors are reported when the unit
+    // itself is compiled.
+    let folded = consteval::run(&unit.statements).unwrap_or_else(|_| unit.statements.clone());
+    let globals = Checker::new(&folded).globals;
+    let definitions = folded.iter().filter_map(|statement| match statement {
+        TopLevelStatement::TopLevelDef(def)
+            if !matches!(def.instruction, TopLevelDef::Typed(_)) =>
+        {
+            Some(def.name.as_str())
+        }
+        _ => None,
+    });
+    let exported: Vec<&str> = match folded
+        .iter()
+        .any(|statement| matches!(statement, TopLevelStatement::ExportAll()))
+    {
+        true => definitions.collect(),
+        false => {
+            let definitions: Vec<&str> = definitions.collect();
+            folded
+                .iter()
+                .filter_map(|statement| match statement {
+                    TopLevelStatement::Export(names) => Some(names),
+                    _ => None,
+                })
+                .flatten()
+                .map(String::as_str)
+                .filter(|name| definitions.contains(name))
+                .collect()
+        }
+    };
+    exported
+        .into_iter()
+        .filter_map(|name| Some((name.to_string(), globals.get(name)?.clone())))
+        .collect()
+}
+
+/// Check and lower `unit`, with the exports of the modules it imports from `units`. Returns the
+/// transformed module, the program and the warnings, or the rendered errors and warnings.
+pub fn compile(
+    unit: &Unit,
+    units: &[Unit],
+    options: &Options,
+) -> Result<(Module, Program, Vec<String>), Vec<String>> {
+    let (lowered, mut diagnostics) = analyze(unit, units, options);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.item);
+    diagnostics.dedup();
+    let rendered = diagnostics.iter().map(|d| unit.render(d)).collect();
+    match lowered {
+        Some((module, mut program)) => {
+            link::qualify(&mut program, &unit.module, &unit.imports);
+            Ok((module, program, rendered))
+        }
+        None => Err(rendered),
+    }
+}
+
+/// The statements of `unit` followed by declarations of the exports of its imports and of the
+/// members of C headers it uses, as `alias/member`, which is what `unit` is checked as.
+pub fn declarations(unit: &Unit, units: &[Unit], options: &Options) -> Vec<TopLevelStatement> {
+    declare(unit, units, options).0
+}
+
+/// [`declarations`], and warnings for the headers that could not be imported.
+fn declare(
+    unit: &Unit,
+    units: &[Unit],
+    options: &Options,
+) -> (Vec<TopLevelStatement>, Vec<Diagnostic>) {
+    let mut statements = unit.statements.clone();
+    // `analyze` reports imports that are not loaded.
+    for (alias, interface) in imported(unit, units).unwrap_or_default() {
+        for (member, var_type) in interface {
+            statements.push(TopLevelStatement::TopLevelDef(DefVar {
+                name: format!("{}/{}", alias, member),
+                instruction: TopLevelDef::Typed(var_type),
+            }));
+        }
+    }
+
+    // Only the members a unit refers to are declared, with the types they name.
+    let resolution = resolve::resolve(&unit.statements);
+    let mut members: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for reference in &resolution.references {
+        if let Target::Member { module, member } = &reference.target {
+            let binding = resolution.binding(*module);
+            if binding.kind == BindingKind::Header {
+                let members = members.entry(binding.name.as_str()).or_default();
+                members.insert(member.as_str());
+            }
+        }
+    }
+    let flags: Vec<String> = options
+        .include_dirs
+        .iter()
+        .map(|dir| format!("-I{}", dir))
+        .collect();
+    let mut warnings = Vec::new();
+    for (item, statement) in unit.statements.iter().enumerate() {
+        let TopLevelStatement::UseHeader(alias, path) = statement else {
+            continue;
+        };
+        let Some(members) = members.get(alias.as_str()) else {
+            continue;
+        };
+        match header::import(path, &options.cc, &flags) {
+            Ok(header) => statements.extend(header.declarations(alias, members)),
+            Err(error) => warnings.push(Diagnostic::warning(
+                item,
+                format!(
+                    "cannot import `{}`: {}; its functions are called without declarations",
+                    path, error
+                ),
+            )),
+        }
+    }
+    (statements, warnings)
+}
+
+/// Check `unit` like [`compile`], without qualifying its symbols. Returns the transformed
+/// module and the program unless there are errors, and the diagnostics.
+pub fn analyze(
+    unit: &Unit,
+    units: &[Unit],
+    options: &Options,
+) -> (Option<(Module, Program)>, Vec<Diagnostic>) {
+    let resolution = resolve::resolve(&unit.statements);
+    let mut diagnostics = resolution.diagnostics.clone();
+    diagnostics.extend(lint::lint(&unit.statements, &resolution));
+
+    let exports = match imported(unit, units) {
+        Ok(exports) => exports,
+        Err(error) => {
+            diagnostics.push(error);
+            return (None, diagnostics);
+        }
+    };
+    for reference in &resolution.references {
+        let Target::Member { module, member } = &reference.target else {
+            continue;
+        };
+        let binding = resolution.binding(*module);
+        if binding.kind != BindingKind::Module {
+            continue;
+        }
+        let message = match exports[binding.name.as_str()].get(member) {
+            None => format!("`{}` does not export `{}`", binding.name, member),
+            Some(var_type) if is_generic(var_type) => format!(
+                "`{}` is generic and cannot be used from another module",
+                reference.name
+            ),
+            Some(_) => continue,
+        };
+        diagnostics.push(Diagnostic::error(reference.item, message));
+    }
+
+    let (statements, warnings) = declare(unit, units, options);
+    diagnostics.extend(warnings);
+    diagnostics.extend(typeck::check(&statements));
+    let lowered = match diagnostics.iter().any(Diagnostic::is_error) {
+        true => None,
+        false => match transformer::transform(&statements) {
+            Ok(module) => match lower::lower(&module) {
+                Ok(program) => Some((module, program)),
+                Err(errors) => {
+                    diagnostics.extend(errors);
+                    None
+                }
+            },
+            Err(errors) => {
+                diagnostics.extend(errors);
+                None
+            }
+        },
+    };
+    (lowered, diagnostics)
+}
+
+/// The C header that declares the exports of `unit`, compiled and qualified as `program`. Every
+/// export is declared under its symbol and defined as a macro of its module path and name
+/// (`math/square-root` is `math_square_root`). Exported types, and the types the exports use,
+/// are named the same way. Exports that C cannot call or read, as closures and generic
+/// functions, are errors.
+pub fn c_header(unit: &Unit, program: &Program) -> Result<String, Vec<String>> {
+    let mut aliases = Aliases::default();
+    for statement in &unit.statements {
+        if let TopLevelStatement::TypeAlias(name, var_type) = statement {
+            aliases.types.insert(name.clone(), var_type.clone());
+        }
+    }
+    let prefix = codegen::c::identifier(&unit.module);
+    let mut diagnostics = Vec::new();
+    let mut exports = Vec::new();
+    let mut c_names: BTreeMap<String, String> = BTreeMap::new();
+    for (name, var_type) in interface(unit) {
+        let (item, function) = unit
+            .statements
+            .iter()
+            .enumerate()
+            .find_map(|(item, statement)| match statement {
+                TopLevelStatement::TopLevelDef(def) if def.name == name => {
+                    let function = matches!(
+                        def.instruction,
+                        TopLevelDef::FnDef(_) | TopLevelDef::Literal(Literal::Fn(_))
+                    );
+                    Some((item, function))
+                }
+                _ => None,
+            })
+            .unwrap_or((0, false));
+        let problem = match &var_type {
+            VarType::Fn(params, ret) if function => params
+                .iter()
+                .chain([&**ret])
+                .find_map(|var_type| unrepresentable(var_type, &aliases, &mut Vec::new())),
+            _ if is_generic(&var_type) => {
+                Some("it is generic, and C has no generic functions".to_string())
+            }
+            other => unrepresentable(other, &aliases, &mut Vec::new()),
+        };
+        if let Some(problem) = problem {
+            let message = format!("`{}` cannot be exported to C: {}", name, problem);
+            diagnostics.push(Diagnostic::error(item, message));
+            continue;
+        }
+
+        let c_name = format!("{}_{}", prefix, codegen::c::identifier(&name));
+        if let Some(other) = c_names.insert(c_name.clone(), name.clone()) {
+            let message = format!("`{}` and `{}` are both `{}` in C", other, name, c_name);
+            diagnostics.push(Diagnostic::error(item, message));
+            continue;
+        }
+        let symbol = match name == lint::ENTRY_POINT {
+            true => name,
+            false => format!("{}/{}", unit.module, name),
+        };
+        exports.push((symbol, c_name));
+    }
+
+    let export_all = unit
+        .statements
+        .iter()
+        .any(|statement| matches!(statement, TopLevelStatement::ExportAll()));
+    let named: BTreeSet<&str> = unit
+        .statements
+        .iter()
+        .filter_map(|statement| match statement {
+            TopLevelStatement::Export(names) => Some(names),
+            _ => None,
+        })
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    let mut types = Vec::new();
+    for (item, statement) in unit.statements.iter().enumerate() {
+        let TopLevelStatement::TypeAlias(name, _) = statement else {
+            continue;
+        };
+        if !export_all && !named.contains(name.as_str()) {
+            continue;
+        }
+        let var_type = VarType::IdentType(name.clone());
+        if let Some(problem) = unrepresentable(&var_type, &aliases, &mut Vec::new()) {
+            let message = format!("`{}` cannot be exported to C: {}", name, problem);
+            diagnostics.push(Diagnostic::error(item, message));
+            continue;
+        }
+        let c_name = format!("{}_{}", prefix, codegen::c::identifier(name));
+        if let Some(other) = c_names.insert(c_name.clone(), name.clone()) {
+            let message = format!("`{}` and `{}` are both `{}` in C", other, name, c_name);
+            diagnostics.push(Diagnostic::error(item, message));
+            continue;
+        }
+        types.push(name.clone());
+    }
+
+    if !diagnostics.is_empty() {
+        diagnostics.sort_by_key(|diagnostic| diagnostic.item);
+        return Err(diagnostics.iter().map(|d| unit.render(d)).collect());
+    }
+    codegen::c::header(program, &prefix, &exports, &types)
+        .map_err(|error| vec![format!("{}: error: {}", unit.path.display(), error)])
+}
+
+/// Why C cannot represent `var_type`, if it cannot: closures have an environment that only
+/// tahini can call them with, and C has no generic types. `seen` are the aliases on the way.
+fn unrepresentable(
+    var_type: &VarType,
+    aliases: &Aliases,
+    seen: &mut Vec<String>,
+) -> Option<String> {
+    let mut check = |var_type: &VarType| unrepresentable(var_type, aliases, seen);
+    match var_type {
+        VarType::Fn(..) | VarType::FnWithVarArgs(..) => Some(format!(
+            "`{}` is a closure, which carries an environment that C cannot pass",
+            var_type
+        )),
+        VarType::GenericArraySized(..)
+        | VarType::GenericArrayUnsized(_)
+        | VarType::GenericData(..)
+        | VarType::GenericPtr(_)
+        | VarType::GenericTuple(..)
+        | VarType::GenericStruct(..)
+        | VarType::GenericFn(..)
+        | VarType::GenericFnWithVarArgs(..)
+        | VarType::GenericInstance(..) => Some(format!(
+            "`{}` is generic, and C has no generic types",
+            var_type
+        )),
+        VarType::IdentType(name) => {
+            if seen.contains(name) {
+                return None;
+            }
+            seen.push(name.clone());
+            match aliases.types.get(name)? {
+                VarType::GenericData(..)
+                | VarType::GenericTuple(..)
+                | VarType::GenericStruct(..) => {
+                    Some(format!("`{}` is generic, and C has no generic types", name))
+                }
+                target => unrepresentable(target, aliases, seen),
+            }
+        }
+        VarType::Ptr(inner)
+        | VarType::ArraySized(inner, _)
+        | VarType::ArrayConstSized(inner, _)
+        | VarType::ArrayUnsized(inner) => check(inner),
+        VarType::Tuple(types) => types.iter().find_map(check),
+        VarType::Struct(fields) => fields.iter().find_map(|(_, t)| check(t)),
+        VarType::Data(variants) => variants.iter().flat_map(|(_, m)| m).find_map(check),
+        _ => None,
+    }
+}
+
+/// The interfaces of the modules `unit` imports, by alias. Fails on an import of a module
+/// that is not among `units`.
+fn imported<'a>(
+    unit: &'a Unit,
+    units: &[Unit],
+) -> Result<BTreeMap<&'a str, BTreeMap<String, VarType>>, Diagnostic> {
+    unit.imports
+        .iter()
+        .map(
+            |(alias, module)| match units.iter().find(|other| other.module == *module) {
+                Some(imported) => Ok((alias.as_str(), interface(imported))),
+                None => {
+                    let item = unit.statements.iter().position(|statement| {
+                    matches!(statement, TopLevelStatement::Use(other, _) if other == alias)
+                });
+                    let message = format!(
+                        "`{}` imports module `{}`, which is not loaded",
+                        alias, module
+                    );
+                    Err(Diagnostic::error(item.unwrap_or(0), message))
+                }
+            },
+        )
+        .collect()
+}
+
+fn emit(
+    compiled: &[(&Unit, Module, Program)],
+    options: &Options,
+    scratch: &Path,
+) -> Result<(), String> {
+    if options.emit == Emit::Exe {
+        let mut objects = Vec::new();
+        for (unit, _, program) in compiled {
+            let object = scratch.join(format!("{}.o", codegen::mangle(&unit.module)));
+            compile_object(program, options, scratch, &object)?;
+            objects.push(object);
+        }
+        objects.push(compile_runtime(options, scratch)?);
+        let first = &compiled[0].0.path;
+        let output = options
+            .output
+            .clone()
+            .unwrap_or_else(|| first.with_extension(""));
+        return link_objects(&objects, options, &output);
+    }
+
+    for (unit, module, program) in compiled {
+        let output = options
+            .output
+            .clone()
+            .unwrap_or_else(|| unit.path.with_extension(options.emit.extension()));
+        let text = match options.emit {
+            Emit::Ast => module.to_string(),
+            Emit::Ir => program.to_string(),
+            Emit::C => codegen::c::emit(program)?,
+            Emit::Header => c_header(unit, program).map_err(|errors| errors.join("\n"))?,
+            Emit::Llvm => codegen::llvm::emit(program)?,
+            Emit::Obj | Emit::Exe => {
+                compile_object(program, options, scratch, &output)?;
+                continue;
+            }
+        };
+        if output == Path::new("-") {
+            print!("{}", text);
+        } else {
+            std::fs::write(&output, text)
+                .map_err(|error| format!("{}: {}", output.display(), error))?;
+        }
+    }
+    Ok(())
+}
+
+/// Compile `program` to the object file `object` with the backend of `options`.
+fn compile_object(
+    program: &Program,
+    options: &Options,
+    scratch: &Path,
+    object: &Path,
+) -> Result<(), String> {
+    std::fs::create_dir_all(scratch).map_err(|error| error.to_string())?;
+    let stem = object.file_stem().unwrap_or_default().to_string_lossy();
+    let mut command = match options.backend {
+        Backend::C => {
+            let source = scratch.join(format!("{}.c", stem));
+            std::fs::write(&source, codegen::c::emit(program)?).map_err(|e| e.to_string())?;
+            let mut command = c_compiler(options);
+            command.arg(source);
+            command
+        }
+        Backend::Llvm => {
+            let source = scratch.join(format!("{}.ll", stem));
+            std::fs::write(&source, codegen::llvm::emit(program)?).map_err(|e| e.to_string())?;
+            let mut command = Command::new(&options.llc);
+            // Opaque pointers are the default from LLVM 15 on.
+            if llvm_version(&options.llc).is_some_and(|major| major < 15) {
+                command.arg("-opaque-pointers");
+            }
+            command
+                .args(["-relocation-model=pic", "-filetype=obj"])
+                .arg(source);
+            command
+        }
+    };
+    run(command.arg("-o").arg(object))
+}
+
+/// Compile the runtime to an object file in `scratch`.
+fn compile_runtime(options: &Options, scratch: &Path) -> Result<PathBuf, String> {
+    let source = scratch.join("tahini-runtime.c");
+    let object = scratch.join("tahini-runtime.o");
+    std::fs::write(&source, codegen::RUNTIME).map_err(|error| error.to_string())?;
+    let mut command = c_compiler(options);
+    if !options.gc {
+        command.arg("-DTAHINI_NO_GC");
+    }
+    run(command.arg(source).arg("-o").arg(&object))?;
+    Ok(object)
+}
+
+/// The C compiler, set up to compile one file to an object.
+fn c_compiler(options: &Options) -> Command {
+    let mut command = Command::new(&options.cc);
+    command.args(["-std=c11", "-c"]);
+    for dir in &options.include_dirs {
+        command.arg(format!("-I{}", dir));
+    }
+    command
+}
+
+fn link_objects(objects: &[PathBuf], options: &Options, output: &Path) -> Result<(), String> {
+    let mut command = Command::new(&options.cc);
+    command.args(objects).arg("-o").arg(output);
+    for dir in &options.lib_dirs {
+        command.arg(format!("-L{}", dir));
+    }
+    for lib in &options.libs {
+        command.arg(format!("-l{}", lib));
+    }
+    if options.gc {
+        command.arg("-lgc");
+    }
+    run(command.arg("-lm"))
+}
+
+fn run(command: &mut Command) -> Result<(), String> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let output = command
+        .output()
+        .map_err(|error| format!("cannot run `{}`: {}", program, error))?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(format!(
+            "`{}` failed:\n{}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        )),
+    }
+}
+
+/// The major version of the LLVM that `llc` belongs to.
+fn llvm_version(llc: &str) -> Option<u32> {
+    let output = Command::new(llc).arg("--version").output().ok()?;
+    let version = String::from_utf8_lossy(&output.stdout).to_string();
+    version
+        .split("LLVM version ")
+        .nth(1)?
+        .split('.')
+        .next()?
+        .parse()
+        .ok()
+}
+
+fn module_name(root: &Path, file: &Path) -> String {
+    let relative = file.strip_prefix(root).unwrap_or(file).with_extension("");
+    let parts: Vec<_> = relative
+        .components()
+        .filter_map(|part| match part {
+            std::path::Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect();
+    parts.join("/")
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn is_generic(var_type: &VarType) -> bool {
+    matches!(
+        var_type,
+        VarType::GenericFn(..) | VarType::GenericFnWithVarArgs(..)
+    )
+}
+
+fn scratch_dir() -> PathBuf {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.subsec_nanos());
+    std::env::temp_dir().join(format!("tahini-build-{}-{}", std::process::id(), nanos))
+}
@@ -0,0 +1,417 @@
+#[cfg(test)]
+mod tests {
+    use crate::driver::{build, compile, load, Backend, Emit, Options};
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    /// A fresh directory holding `files`.
+    fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("tahini-driver-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        for (path, source) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+        dir
+    }
+
+    const MATH: &str = "(def PI 3.5) \n
+        (def square (fn [(:x i64)] i64 (* x x))) \n
+        (def cube (fn [(:x i64)] i64 (* x (square x)))) \n
+        (export square cube PI)";
+
+    const APP: &str = "(def math (use \"lib/math\")) \n
+        (def stdio (use :header \"stdio.h\")) \n
+        (def square (fn [(:x i64)] i64 (+ x 1))) \n
+        (def main (fn [] i32 \n
+            (do \n
+                (stdio/printf \"%ld %ld %.1f\\n\" (math/square 7) (square 7) math/PI) \n
+                (if (= (math/cube 2) 8) 4 1))))";
+
+    #[test]
+    fn test_load_follows_imports() {
+        let dir = project("load", &[("app.th", APP), ("lib/math.th", MATH)]);
+        let units = load(&[dir.join("app.th")]).unwrap();
+        let modules: Vec<_> = units.iter().map(|unit| unit.module.as_str()).collect();
+        assert_eq!(modules, vec!["app", "lib/math"]);
+        assert_eq!(units[0].imports["math"], "lib/math");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_parse_errors_are_located() {
+        let dir = project(
+            "parse",
+            &[("app.th", "(def one 1) \n(def two ]\n(def three 3)")],
+        );
+        let error = load(&[dir.join("app.th")]).err().unwrap();
+        let app = dir.join("app.th").display().to_string();
+        assert_eq!(error, format!("{}:2:10: error: unexpected ']'", app));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_symbols_are_qualified() {
+        let dir = project("qualify", &[("app.th", APP), ("lib/math.th", MATH)]);
+        let units = load(&[dir.join("app.th")]).unwrap();
+        let (_, program, _) = compile(&units[0], &units, &Options::default()).unwrap();
+        let ir = program.to_string();
+        assert!(ir.contains("import @lib/math/PI: f64"), "{}", ir);
+        assert!(
+            ir.contains("import @lib/math/square: fn [i64] i64"),
+            "{}",
+            ir
+        );
+        assert!(ir.contains("call @lib/math/square(7)"), "{}", ir);
+        assert!(ir.contains("fn app/square(x: i64) -> i64"), "{}", ir);
+        assert!(ir.contains("fn main() -> i32"), "{}", ir);
+
+        let (_, program, _) = compile(&units[1], &units, &Options::default()).unwrap();
+        let ir = program.to_string();
+        assert!(ir.contains("call @lib/math/square(%1)"), "{}", ir);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_only_exports_are_visible() {
+        let math = "(def square (fn [(:x i64)] i64 (* x x))) \n
+            (def hidden (fn [] i64 1)) \n
+            (export square)";
+        let app = "(def math (use \"math\")) \n
+            (def main (fn [] i32 (do (math/hidden) (math/square true) 0)))";
+        let dir = project("exports", &[("app.th", app), ("math.th", math)]);
+        let units = load(&[dir.join("app.th")]).unwrap();
+        let errors = compile(&units[0], &units, &Options::default())
+            .err()
+            .unwrap();
+        let app = dir.join("app.th").display().to_string();
+        assert_eq!(
+            errors,
+            vec![
+                format!("{}:3:13: error: `math` does not export `hidden`", app),
+                format!(
+                    "{}:3:13: error: type mismatch in argument 1 of `math/square`: \
+                     expected `i64`, found `bool`",
+                    app
+                ),
+            ]
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_parent_dir_imports() {
+        let util = "(def two (fn [] i64 2)) \n(export two)";
+        let app = "(def math (use \"../lib/math\")) \n
+            (def a (use \"util\")) \n
+            (def b (use \"../app/util\")) \n
+            (def main (fn [] i32 (if (= (math/square (a/two)) (+ (b/two) 2)) 0 1)))";
+        let dir = project(
+            "parent",
+            &[
+                ("app/app.th", app),
+                ("app/util.th", util),
+                ("lib/math.th", MATH),
+            ],
+        );
+        let units = load(&[dir.join("app/app.th")]).unwrap();
+        let modules: Vec<_> = units.iter().map(|unit| unit.module.as_str()).collect();
+        assert_eq!(modules, vec!["app", "lib/math", "util"]);
+        assert_eq!(units[0].imports["b"], "util");
+        for unit in &units {
+            compile(unit, &units, &Options::default()).unwrap();
+        }
+
+        // Imports of modules that were not loaded are errors, not panics.
+        let errors = compile(&units[0], &units[..2], &Options::default())
+            .err()
+            .unwrap();
+        let app = dir.join("app/app.th").display().to_string();
+        assert_eq!(
+            errors,
+            vec![format!(
+                "{}:3:13: error: `a` imports module `util`, which is not loaded",
+                app
+            )]
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_build_executables() {
+        let dir = project("exe", &[("app.th", APP), ("lib/math.th", MATH)]);
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        for backend in [Backend::C, Backend::Llvm] {
+            if backend == Backend::Llvm && Command::new("llc").arg("--version").output().is_err() {
+                continue;
+            }
+            let binary = dir.join(format!("app-{:?}", backend));
+            let options = Options {
+                backend,
+                output: Some(binary.clone()),
+                gc: false,
+                ..Options::default()
+            };
+            build(&[dir.join("app.th")], &options).unwrap();
+            let output = Command::new(&binary).output().unwrap();
+            assert_eq!(output.status.code(), Some(4), "{:?}", backend);
+            assert_eq!(String::from_utf8_lossy(&output.stdout), "49 8 3.5\n");
+        }
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_emit_writes_next_to_the_source() {
+        let dir = project("emit", &[("app.th", APP), ("lib/math.th", MATH)]);
+        let options = Options {
+            emit: Emit::C,
+            ..Options::default()
+        };
+        build(&[dir.join("app.th")], &options).unwrap();
+        let c = std::fs::read_to_string(dir.join("app.c")).unwrap();
+        assert!(
+            c.contains("int64_t th_lib_2Fmath_2Fsquare(int64_t);"),
+            "{}",
+            c
+        );
+        // Imported modules are only compiled for executables.
+        assert!(!dir.join("lib/math.c").exists());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_headers_are_declared() {
+        let app = "(def c (use :header \"stdlib.h\")) \n
+            (def s (use :header \"string.h\")) \n
+            (def main (fn [] i32 \n
+                (do \n
+                    (def d (c/div 17 5)) \n
+                    (if (= (s/strlen \"four\") 4) (+ ($ :rem d) c/EXIT_FAILURE) 0))))";
+        let dir = project("headers", &[("app.th", app)]);
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        let binary = dir.join("app");
+        let options = Options {
+            output: Some(binary.clone()),
+            gc: false,
+            ..Options::default()
+        };
+        build(&[dir.join("app.th")], &options).unwrap();
+        let output = Command::new(&binary).output().unwrap();
+        assert_eq!(output.status.code(), Some(3));
+
+        let units = load(&[dir.join("app.th")]).unwrap();
+        let (_, program, _) = compile(&units[0], &units, &Options::default()).unwrap();
+        let ir = program.to_string();
+        assert!(ir.contains("extern @s/strlen: fn [(ptr i8)] u64"), "{}", ir);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_missing_headers_are_warnings() {
+        let app = "(def lib (use :header \"no_such_header.h\")) \n
+            (def main (fn [] i32 (lib/answer)))";
+        let dir = project("missing-header", &[("app.th", app)]);
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        let units = load(&[dir.join("app.th")]).unwrap();
+        let (_, _, warnings) = compile(&units[0], &units, &Options::default()).unwrap();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(
+            warnings[0].contains("warning: cannot import `no_such_header.h`"),
+            "{}",
+            warnings[0]
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_functions_are_passed_to_c() {
+        let app = "(def stdlib (use :header \"stdlib.h\")) \n
+            (def stdio (use :header \"stdio.h\")) \n
+            (def ascending (fn [(:a (ptr i32)) (:b (ptr i32))] i32 (- ($ [0] a) ($ [0] b)))) \n
+            (def show (fn [(:xs (ptr i32))] void \n
+                (stdio/printf \"%d %d %d %d\\n\" ($ [0] xs) ($ [1] xs) ($ [2] xs) ($ [3] xs)))) \n
+            (def main (fn [] i32 \n
+                (do \n
+                    (def xs (stdlib/calloc 4 4)) \n
+                    (show-sorted xs) \n
+                    0))) \n
+            (def show-sorted (fn [(:xs (ptr i32))] void \n
+                (do \n
+                    ($ [0] xs 5) ($ [1] xs 3) ($ [2] xs 9) ($ [3] xs 1) \n
+                    (stdlib/qsort xs 4 4 ascending) \n
+                    (show xs) \n
+                    (stdlib/qsort xs 4 4 (fn [(:a (ptr i32)) (:b (ptr i32))] i32 \n
+                        (- ($ [0] b) ($ [0] a)))) \n
+                    (show xs))))";
+        let dir = project("callbacks", &[("app.th", app)]);
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        for backend in [Backend::C, Backend::Llvm] {
+            if backend == Backend::Llvm && Command::new("llc").arg("--version").output().is_err() {
+                continue;
+            }
+            let binary = dir.join(format!("app-{:?}", backend));
+            let options = Options {
+                backend,
+                output: Some(binary.clone()),
+                gc: false,
+                ..Options::default()
+            };
+            build(&[dir.join("app.th")], &options).unwrap();
+            let output = Command::new(&binary).output().unwrap();
+            assert_eq!(
+                String::from_utf8_lossy(&output.stdout),
+                "1 3 5 9\n9 5 3 1\n",
+                "{:?}",
+                backend
+            );
+        }
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_closures_cannot_be_passed_to_c() {
+        let app = "(def stdlib (use :header \"stdlib.h\")) \n
+            (def sort-by (fn [(:xs (ptr i32)) (:k i32)] void \n
+                (stdlib/qsort xs 4 4 (fn [(:a (ptr i32)) (:b (ptr i32))] i32 \n
+                    (* k (- ($ [0] a) ($ [0] b))))))) \n
+            (export sort-by)";
+        let dir = project("closures-to-c", &[("app.th", app)]);
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        let units = load(&[dir.join("app.th")]).unwrap();
+        let errors = compile(&units[0], &units, &Options::default())
+            .err()
+            .unwrap();
+        assert_eq!(
+            errors,
+            vec![format!(
+                "{}:3:13: error: argument 4 of `stdlib/qsort` is a C function pointer, which has \
+                 no room for the variables the lambda captures (`k`); pass a top-level function \
+                 or a lambda that captures nothing",
+                dir.join("app.th").display()
+            )]
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    const GEO: &str = "(type vec2 (struct (:x f64) (:y f64))) \n
+        (type shape (data [:circle f64] [:square f64])) \n
+        (def SCALE 2.5) \n
+        (def scale (fn [(:v vec2) (:k f64)] vec2 (vec2 (* ($ :x v) k) (* ($ :y v) k)))) \n
+        (def unit-square (fn [] shape [:square 1.0])) \n
+        (type meters f64) \n
+        (export scale unit-square SCALE vec2 meters)";
+
+    /// Has a type of the same name as one of [`GEO`].
+    const PLOT: &str = "(type vec2 (struct (:x i32) (:y i32))) \n
+        (def origin (fn [] vec2 (vec2 0 0))) \n
+        (export origin)";
+
+    #[test]
+    fn test_c_headers() {
+        let dir = project("c-header", &[("geo.th", GEO), ("plot.th", PLOT)]);
+        let inputs = [dir.join("geo.th"), dir.join("plot.th")];
+        let options = Options {
+            emit: Emit::Header,
+            ..Options::default()
+        };
+        build(&inputs, &options).unwrap();
+        let header = std::fs::read_to_string(dir.join("geo.h")).unwrap();
+        for declaration in [
+            "#ifndef TAHINI_GEO_H",
+            "typedef struct geo_vec2 geo_vec2;",
+            "typedef double geo_meters;",
+            "enum { geo_shape_circle = 0, geo_shape_square = 1 };",
+            "extern double th_geo_2FSCALE;\n#define geo_SCALE th_geo_2FSCALE",
+            "geo_vec2 th_geo_2Fscale(geo_vec2 l_v, double l_k);\n\
+             #define geo_scale th_geo_2Fscale",
+        ] {
+            assert!(header.contains(declaration), "{}", header);
+        }
+        assert!(!header.contains("th_vec2_t"), "{}", header);
+
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        let options = Options {
+            emit: Emit::Obj,
+            ..Options::default()
+        };
+        build(&inputs, &options).unwrap();
+        let main = "#include <stdio.h>\n#include \"geo.h\"\n#include \"plot.h\"\n
+            int main(void) {
+                tahini_init();
+                geo_vec2 v = geo_scale((geo_vec2){1.0, -2.0}, geo_SCALE);
+                geo_shape s = geo_unit_square();
+                geo_meters m = 1.5;
+                plot_vec2 o = plot_origin();
+                printf(\"%.1f %.1f %d %.1f %d\\n\", v.f_x, v.f_y, s.tag == geo_shape_square, m, o.f_x);
+                return 0;
+            }";
+        std::fs::write(dir.join("main.c"), main).unwrap();
+        std::fs::write(dir.join("runtime.c"), crate::codegen::RUNTIME).unwrap();
+        let status = Command::new("cc")
+            .current_dir(&dir)
+            .args(["-std=c11", "-Wall", "-Werror", "-DTAHINI_NO_GC"])
+            .args(["main.c", "geo.o", "plot.o", "runtime.c", "-o", "main"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let output = Command::new(dir.join("main")).output().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "2.5 -5.0 1 1.5 0\n"
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_c_headers_reject_closures_and_generics() {
+        let src = "(type pair (struct<T> (:a T) (:b T))) \n
+            (type handler (struct (:on-event fn [i32] void))) \n
+            (def adder (fn [(:n i32)] fn [i32] i32 (fn [(:x i32)] i32 (+ x n)))) \n
+            (def id (fn<T> [(:x T)] T x)) \n
+            (def first (fn [(:p pair<i32>)] i32 ($ :a p))) \n
+            (def fire (fn [(:h handler)] handler h)) \n
+            (def double-it (fn [(:x i32)] i32 (* 2 x))) \n
+            (def double_it (fn [(:x i32)] i32 (* 2 x))) \n
+            (export adder id first fire double-it double_it pair)";
+        let dir = project("c-header-errors", &[("lib.th", src)]);
+        let options = Options {
+            emit: Emit::Header,
+            ..Options::default()
+        };
+        let errors = build(&[dir.join("lib.th")], &options).err().unwrap();
+        let lib = dir.join("lib.th").display().to_string();
+        assert_eq!(
+            errors.join("\n"),
+            [
+                "1:1: error: `pair` cannot be exported to C: `pair` is generic, \
+                 and C has no generic types",
+                "5:13: error: `adder` cannot be exported to C: `fn [i32] i32` is a closure, \
+                 which carries an environment that C cannot pass",
+                "7:13: error: `id` cannot be exported to C: it is generic, \
+                 and C has no generic functions",
+                "9:13: error: `first` cannot be exported to C: `pair<i32>` is generic, \
+                 and C has no generic types",
+                "11:13: error: `fire` cannot be exported to C: `fn [i32] void` is a closure, \
+                 which carries an environment that C cannot pass",
+                "15:13: error: `double-it` and `double_it` are both `lib_double_it` in C",
+            ]
+            .map(|error| format!("{}:{}", lib, error))
+            .join("\n")
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,1461 @@
+//! Declarations of the C headers imported with `(use :header "stdio.h")`.
+//!
+//! A header is run through the C preprocessor (`cc -E`) and the declarations of the output are
+//! read with a small C parser: function prototypes, `extern` globals, structs, unions, enums
+//! and typedefs. A second run (`cc -E -dM`) lists the macros, of which the ones that expand to
+//! a number, a character or a string are constants. Types map onto tahini as
+//! `docs/c_interop.md` describes:
+//!
+//! - integers, floats and `_Bool` are the scalar of their size and signedness, and pointers
+//!   are `ptr` of what they point to. `char *` is `(ptr i8)`.
+//! - a struct is a `struct` type named after its tag, or after the typedef that names it, and a
+//!   typedef of a named struct is an alias of it. Other typedefs are replaced by their type.
+//! - a union is a `struct` of its largest member, if that member has the size and alignment of
+//!   the whole union.
+//! - an enum is an alias of `i32` and its enumerators are constants.
+//! - a function pointer parameter is a `fn` type; elsewhere function pointers are
+//!   `(ptr void)`, which has their layout.
+//!
+//! Structs with bit-fields, flexible array members or members of other types tahini cannot
+//! represent are opaque: pointers to them are `(ptr void)`, and the functions that take or
+//! return them by value are left out. So are `static` functions, function-like macros and
+//! integer constants that do not fit `i32`, the type of an integer literal.
+//!
+//! Headers are imported once per compiler and flags, and the results are kept for the rest of
+//! the process. A third run (`cc -M`) lists the files a header includes, and an import is read
+//! again once one of them changes, as when a REPL session loads a file again after its header
+//! was edited.
+
+use crate::ast::{DefVar, Literal, TopLevelDef, TopLevelStatement, VarType};
+use crate::transformer::layout::layout_of;
+use crate::typeck::types::Aliases;
+use crate::visit::{self, VisitorMut};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// What a header declares, under the names of the C code.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Header {
+    /// Functions and globals.
+    pub values: BTreeMap<String, VarType>,
+    /// Structs, unions, enums and typedefs of them. Their types refer to each other by these
+    /// names.
+    pub types: BTreeMap<String, VarType>,
+    /// Macros and enumerators with a constant value.
+    pub constants: BTreeMap<String, Literal>,
+}
+
+type Key = (String, String, Vec<String>);
+
+/// When a file was last modified and its length, or `None` if it cannot be read.
+type Stamp = Option<(SystemTime, u64)>;
+
+/// An imported header and the stamps of the files it was read from.
+struct Imported {
+    header: Arc<Header>,
+    files: Vec<(PathBuf, Stamp)>,
+}
+
+/// Imported headers by header, compiler and flags.
+static IMPORTED: Mutex<BTreeMap<Key, Imported>> = Mutex::new(BTreeMap::new());
+
+/// The declarations of `header`, preprocessed by `cc` with `flags`.
+pub fn import(header: &str, cc: &str, flags: &[String]) -> Result<Arc<Header>, String> {
+    let key = (header.to_string(), cc.to_string(), flags.to_vec());
+    if let Some(imported) = IMPORTED.lock().unwrap().get(&key) {
+        if imported
+            .files
+            .iter()
+            .all(|(file, seen)| stamp(file) == *seen)
+        {
+            return Ok(imported.header.clone());
+        }
+    }
+    let include = format!("#include {:?}\n", header);
+    let source = preprocess(&include, cc, flags, &[])?;
+    let macros = preprocess(&include, cc, flags, &["-dM"])?;
+    let files = dependencies(&preprocess(&include, cc, flags, &["-M"])?);
+    let header = Arc::new(parse(&source, &macros));
+    let imported = Imported {
+        header: header.clone(),
+        files,
+    };
+    IMPORTED.lock().unwrap().insert(key, imported);
+    Ok(header)
+}
+
+/// The files of the make rule `cc -M` prints, `-: a.h b.h \`, with their current stamps.
+fn dependencies(rule: &str) -> Vec<(PathBuf, Stamp)> {
+    let files = rule.split_once(':').map_or("", |(_, files)| files);
+    files
+        .split_whitespace()
+        .filter(|file| *file != "\\")
+        .map(|file| (PathBuf::from(file), stamp(file.as_ref())))
+        .collect()
+}
+
+fn stamp(file: &Path) -> Stamp {
+    let metadata = std::fs::metadata(file).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// `source` preprocessed by `cc` with `flags` and `extra`.
+fn preprocess(source: &str, cc: &str, flags: &[String], extra: &[&str]) -> Result<String, String> {
+    let mut child = Command::new(cc)
+        .args(["-E", "-P", "-x", "c"])
+        .args(flags)
+        .args(extra)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("cannot run `{}`: {}", cc, error))?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .map_err(|error| error.to_string())?;
+    let output = child
+        .wait_with_output()
+        .map_err(|error| error.to_string())?;
+    match output.status.success() {
+        true => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
+        false => {
+            let error = String::from_utf8_lossy(&output.stderr);
+            let first = error
+                .lines()
+                .find(|line| line.contains("error"))
+                .unwrap_or("");
+            Err(first.trim().to_string())
+        }
+    }
+}
+
+/// The declarations of the preprocessed C `source`, with constants from the `#define` lines
+/// of `macros`.
+pub fn parse(source: &str, macros: &str) -> Header {
+    let mut parser = Parser::new(tokenize(source));
+    while parser.pos < parser.tokens.len() {
+        let start = parser.pos;
+        if parser.declaration().is_none() {
+            parser.pos = start;
+            parser.recover();
+        }
+    }
+    let mut header = parser.convert();
+    let mut constants = Constants {
+        macros: macro_bodies(macros),
+        declarations: &parser,
+        values: HashMap::new(),
+    };
+    for name in constants.macros.keys().cloned().collect::<Vec<_>>() {
+        if let Some(literal) = constants.literal(&name) {
+            header.constants.insert(name, literal);
+        }
+    }
+    for (name, value) in &parser.enumerators {
+        if let Ok(value) = i32::try_from(*value) {
+            let literal = Literal::Int(value as i64);
+            header.constants.entry(name.clone()).or_insert(literal);
+        }
+    }
+    header
+}
+
+impl Header {
+    /// Declarations of `members` as `alias/member`, with every type they name. Members the
+    /// header does not declare are left out.
+    pub fn declarations(&self, alias: &str, members: &BTreeSet<&str>) -> Vec<TopLevelStatement> {
+        let mut prefix = Prefix(alias);
+        let mut values = Vec::new();
+        let mut needed: Vec<String> = Vec::new();
+        for &member in members {
+            let name = format!("{}/{}", alias, member);
+            let instruction = if let Some(var_type) = self.values.get(member) {
+                named_types(var_type, &mut needed);
+                let mut var_type = var_type.clone();
+                prefix.visit_var_type(&mut var_type);
+                TopLevelDef::Typed(var_type)
+            } else if let Some(literal) = self.constants.get(member) {
+                TopLevelDef::Literal(literal.clone())
+            } else {
+                if self.types.contains_key(member) {
+                    needed.push(member.to_string());
+                }
+                continue;
+            };
+            values.push(TopLevelStatement::TopLevelDef(DefVar { name, instruction }));
+        }
+
+        let mut types = BTreeMap::new();
+        while let Some(name) = needed.pop() {
+            if types.contains_key(&name) {
+                continue;
+            }
+            let Some(var_type) = self.types.get(&name) else {
+                continue;
+            };
+            named_types(var_type, &mut needed);
+            let mut var_type = var_type.clone();
+            prefix.visit_var_type(&mut var_type);
+            types.insert(name, var_type);
+        }
+        let types = types.into_iter().map(|(name, var_type)| {
+            TopLevelStatement::TypeAlias(format!("{}/{}", alias, name), var_type)
+        });
+        types.chain(values).collect()
+    }
+}
+
+/// Adds the names of the types `var_type` refers to to `names`.
+fn named_types(var_type: &VarType, names: &mut Vec<String>) {
+    struct Names<'a>(&'a mut Vec<String>);
+    impl visit::Visitor for Names<'_> {
+        fn visit_var_type(&mut self, var_type: &VarType) {
+            if let VarType::IdentType(name) = var_type {
+                self.0.push(name.clone());
+            }
+            visit::walk_var_type(self, var_type);
+        }
+    }
+    visit::Visitor::visit_var_type(&mut Names(names), var_type);
+}
+
+/// Qualifies the names of header types with the import alias.
+struct Prefix<'a>(&'a str);
+
+impl VisitorMut for Prefix<'_> {
+    fn visit_var_type(&mut self, var_type: &mut VarType) {
+        if let VarType::IdentType(name) = var_type {
+            *name = format!("{}/{}", self.0, name);
+        }
+        visit::walk_mut_var_type(self, var_type);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(String),
+    Str(String),
+    Char(String),
+    Punct(&'static str),
+}
+
+const PUNCTUATION: &[&str] = &[
+    "...", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "->", "##", "(", ")", "[", "]", "{",
+    "}", ";", ",", "*", "=", ":", "?", "+", "-", "/", "%", "&", "|", "^", "~", "!", "<", ">", ".",
+    "#",
+];
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        if c.is_ascii_whitespace() {
+            i += 1;
+        } else if c == b'#' && (start == 0 || bytes[start - 1] == b'\n') {
+            // Line markers and pragmas.
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+        } else if c.is_ascii_alphabetic() || c == b'_' || c == b'$' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || b"_$".contains(&bytes[i]))
+            {
+                i += 1;
+            }
+            tokens.push(Token::Ident(source[start..i].to_string()));
+        } else if c.is_ascii_digit()
+            || (c == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit))
+        {
+            while i < bytes.len() {
+                let exponent = b"eEpP".contains(&bytes[i]) && !source[start..i].starts_with("0x");
+                if exponent && bytes.get(i + 1).is_some_and(|sign| b"+-".contains(sign)) {
+                    i += 2;
+                } else if bytes[i].is_ascii_alphanumeric() || b"._".contains(&bytes[i]) {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Number(source[start..i].to_string()));
+        } else if c == b'"' || c == b'\'' {
+            i += 1;
+            while i < bytes.len() && bytes[i] != c {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+            let text = source[start + 1..i.min(bytes.len())].to_string();
+            i += 1;
+            tokens.push(match c {
+                b'"' => Token::Str(text),
+                _ => Token::Char(text),
+            });
+        } else {
+            let punct = PUNCTUATION
+                .iter()
+                .find(|punct| bytes[start..].starts_with(punct.as_bytes()));
+            match punct {
+                Some(punct) => {
+                    tokens.push(Token::Punct(punct));
+                    i += punct.len();
+                }
+                None => i += 1,
+            }
+        }
+    }
+    tokens
+}
+
+/// A C type, before it is converted to tahini.
+#[derive(Clone, Debug, PartialEq)]
+enum CType {
+    Scalar(VarType),
+    /// A struct or union, by index into [`Parser::records`].
+    Record(usize),
+    /// A struct or union by tag, which may be defined later: `struct X`.
+    Tag(String),
+    /// An enum, named by its tag if it has one.
+    Enum(Option<String>),
+    Typedef(String),
+    Pointer(Box<CType>),
+    Array(Box<CType>, Option<usize>),
+    Function(Vec<CType>, bool, Box<CType>),
+    Unsupported,
+}
+
+#[derive(Clone, Debug)]
+struct Record {
+    tag: Option<String>,
+    union: bool,
+    /// `None` until the record is defined, or if it has members tahini cannot lay out.
+    members: Option<Vec<(String, CType)>>,
+}
+
+/// How a declarator derives the type of its name from the type of the specifiers.
+enum Derive {
+    Pointer,
+    Array(Option<usize>),
+    Function(Vec<CType>, bool),
+}
+
+#[derive(Default)]
+struct Specifiers {
+    typedef: bool,
+    is_static: bool,
+    base: Option<CType>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    typedefs: HashMap<String, CType>,
+    records: Vec<Record>,
+    /// Records by tag, as `struct X` or `union X`.
+    tags: HashMap<String, usize>,
+    enums: HashSet<String>,
+    enumerators: BTreeMap<String, i128>,
+    values: BTreeMap<String, CType>,
+    /// Whether an attribute that changes the layout of a record, as `packed`, was skipped
+    /// since the record started.
+    aligned: bool,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Parser {
+            tokens,
+            pos: 0,
+            typedefs: HashMap::new(),
+            records: Vec::new(),
+            tags: HashMap::new(),
+            enums: HashSet::new(),
+            enumerators: BTreeMap::new(),
+            values: BTreeMap::new(),
+            aligned: false,
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn is(&self, punct: &str) -> bool {
+        self.peek() == Some(&Token::Punct(punctuation(punct)))
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let matches = self.is(punct);
+        if matches {
+            self.pos += 1;
+        }
+        matches
+    }
+
+    fn expect(&mut self, punct: &str) -> Option<()> {
+        self.eat(punct).then_some(())
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    /// Skip the balanced brackets that start at the current token.
+    fn skip_balanced(&mut self) -> Option<()> {
+        let mut depth = 0;
+        loop {
+            match self.peek()? {
+                Token::Punct("(" | "[" | "{") => depth += 1,
+                Token::Punct(")" | "]" | "}") => depth -= 1,
+                _ => {}
+            }
+            self.pos += 1;
+            if depth == 0 {
+                return Some(());
+            }
+        }
+    }
+
+    /// Skip the rest of a declaration that does not parse: up to a `;`, or the end of a
+    /// function body.
+    fn recover(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            let body = self.pos > 0 && self.tokens[self.pos - 1] == Token::Punct(")");
+            match token {
+                Token::Punct(";") if depth == 0 => {
+                    self.pos += 1;
+                    return;
+                }
+                Token::Punct("{") if depth == 0 && body => {
+                    self.skip_balanced();
+                    return;
+                }
+                Token::Punct("(" | "[" | "{") => depth += 1,
+                Token::Punct(")" | "]" | "}") => depth -= 1,
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Skip `__attribute__((...))`, `__asm__(...)` and the like.
+    fn skip_extensions(&mut self) {
+        while let Some(Token::Ident(name)) = self.peek() {
+            match name.as_str() {
+                "__attribute__" | "__attribute" | "__asm__" | "__asm" | "asm" | "__declspec"
+                | "_Alignas" | "alignas" => {
+                    let start = self.pos;
+                    self.pos += 1;
+                    if self.is("(") && self.skip_balanced().is_none() {
+                        return;
+                    }
+                    self.aligned |= self.tokens[start..self.pos].iter().any(|token| {
+                        matches!(token, Token::Ident(name) if LAYOUT_ATTRIBUTES.contains(&name.as_str()))
+                    });
+                }
+                "__extension__" | "_Nonnull" | "_Nullable" | "_Null_unspecified" => self.pos += 1,
+                _ => return,
+            }
+        }
+    }
+
+    /// One top-level declaration. `None` if it does not parse.
+    fn declaration(&mut self) -> Option<()> {
+        self.skip_extensions();
+        if self.eat(";") {
+            return Some(());
+        }
+        if self.peek() == Some(&Token::Ident("_Static_assert".to_string())) {
+            return None;
+        }
+        let specifiers = self.specifiers()?;
+        let base = specifiers.base.clone()?;
+        if self.eat(";") {
+            return Some(());
+        }
+        loop {
+            let (name, derived) = self.declarator()?;
+            let name = name?;
+            let c_type = derive(base.clone(), derived);
+            self.skip_extensions();
+            if specifiers.typedef {
+                self.typedefs.insert(name, c_type);
+            } else if !specifiers.is_static {
+                self.values.insert(name, c_type.clone());
+            }
+            if self.is("{") {
+                // A function definition, as of an inline function.
+                return self.skip_balanced();
+            }
+            if self.eat("=") {
+                self.skip_initializer()?;
+            }
+            if self.eat(";") {
+                return Some(());
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn skip_initializer(&mut self) -> Option<()> {
+        while !self.is(",") && !self.is(";") {
+            match self.peek()? {
+                Token::Punct("(" | "[" | "{") => self.skip_balanced()?,
+                _ => self.pos += 1,
+            }
+        }
+        Some(())
+    }
+
+    /// Declaration specifiers: storage classes, qualifiers and the type.
+    fn specifiers(&mut self) -> Option<Specifiers> {
+        let mut specifiers = Specifiers::default();
+        let (mut signed, mut unsigned, mut short, mut longs) = (false, false, false, 0);
+        let mut scalar: Option<&str> = None;
+        loop {
+            self.skip_extensions();
+            let Some(Token::Ident(name)) = self.peek().cloned() else {
+                break;
+            };
+            match name.as_str() {
+                "typedef" => specifiers.typedef = true,
+                "static" => specifiers.is_static = true,
+                "extern" | "inline" | "__inline" | "__inline__" | "_Noreturn" | "register"
+                | "auto" | "_Thread_local" | "__thread" | "const" | "__const" | "__const__"
+                | "volatile" | "__volatile" | "__volatile__" | "restrict" | "__restrict"
+                | "__restrict__" | "_Atomic" => {}
+                "signed" | "__signed" | "__signed__" => signed = true,
+                "unsigned" => unsigned = true,
+                "short" => short = true,
+                "long" => longs += 1,
+                "int" if scalar.is_none() => scalar = Some("int"),
+                "int" => {}
+                "char" | "void" | "float" | "double" | "_Bool" | "bool" | "__int128"
+                | "_Float16" | "_Float32" | "_Float64" | "_Float128" | "__float128"
+                | "_Float32x" | "_Float64x" | "_Complex" | "__complex__" => {
+                    scalar = Some(match name.as_str() {
+                        "_Complex" | "__complex__" => "complex",
+                        other => PRIMITIVES.iter().find(|p| **p == other).unwrap(),
+                    })
+                }
+                "__builtin_va_list" => {
+                    specifiers.base = Some(CType::Pointer(Box::new(CType::Scalar(VarType::Void))))
+                }
+                "struct" | "union" => {
+                    self.pos += 1;
+                    specifiers.base = Some(self.record(name == "union")?);
+                    continue;
+                }
+                "enum" => {
+                    self.pos += 1;
+                    specifiers.base = Some(self.enumeration()?);
+                    continue;
+                }
+                "typeof" | "__typeof__" | "__typeof" => {
+                    self.pos += 1;
+                    self.skip_balanced()?;
+                    specifiers.base = Some(CType::Unsupported);
+                    continue;
+                }
+                _ if specifiers.base.is_none()
+                    && scalar.is_none()
+                    && !(signed || unsigned || short || longs > 0)
+                    && self.typedefs.contains_key(&name) =>
+                {
+                    specifiers.base = Some(CType::Typedef(name.clone()));
+                }
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        if specifiers.base.is_none() {
+            let any = scalar.is_some() || signed || unsigned || short || longs > 0;
+            if any {
+                specifiers.base = Some(primitive(scalar, unsigned, short, longs));
+            }
+        }
+        Some(specifiers)
+    }
+
+    /// `struct X`, `struct X { ... }` or `struct { ... }`, after the keyword.
+    fn record(&mut self, union: bool) -> Option<CType> {
+        let outer = std::mem::take(&mut self.aligned);
+        self.skip_extensions();
+        let tag = self.ident();
+        let key = tag
+            .as_ref()
+            .map(|tag| format!("{} {}", if union { "union" } else { "struct" }, tag));
+        if !self.is("{") {
+            self.aligned |= outer;
+            return Some(CType::Tag(key?));
+        }
+        self.pos += 1;
+        let index = match key.as_ref().and_then(|key| self.tags.get(key)) {
+            Some(&index) => index,
+            None => {
+                self.records.push(Record {
+                    tag: tag.clone(),
+                    union,
+                    members: None,
+                });
+                self.records.len() - 1
+            }
+        };
+        if let Some(key) = key {
+            self.tags.insert(key, index);
+        }
+        let mut members = Some(Vec::new());
+        while !self.eat("}") {
+            self.skip_extensions();
+            if self.eat(";") {
+                continue;
+            }
+            let specifiers = self.specifiers()?;
+            let base = specifiers.base?;
+            if self.eat(";") {
+                // An anonymous struct or union member, whose members C reaches directly.
+                members = None;
+                continue;
+            }
+            loop {
+                let (name, derived) = match self.is(":") {
+                    true => (None, Vec::new()),
+                    false => self.declarator()?,
+                };
+                if self.eat(":") {
+                    self.expression()?;
+                    members = None;
+                }
+                self.skip_extensions();
+                let member = derive(base.clone(), derived);
+                let flexible = matches!(member, CType::Array(_, None | Some(0)));
+                match (name, &mut members) {
+                    (Some(name), Some(list)) if !flexible => list.push((name, member)),
+                    _ => members = None,
+                }
+                if self.eat(";") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        self.skip_extensions();
+        // Records laid out other than C does by default are opaque.
+        if !self.aligned {
+            self.records[index].members = members;
+        }
+        self.aligned |= outer;
+        Some(CType::Record(index))
+    }
+
+    /// `enum X`, `enum X { ... }` or `enum { ... }`, after the keyword.
+    fn enumeration(&mut self) -> Option<CType> {
+        self.skip_extensions();
+        let tag = self.ident();
+        if let Some(tag) = &tag {
+            self.enums.insert(tag.clone());
+        }
+        if !self.eat("{") {
+            return Some(CType::Enum(Some(tag?)));
+        }
+        let mut next = Some(0i128);
+        while !self.eat("}") {
+            let name = self.ident()?;
+            self.skip_extensions();
+            if self.eat("=") {
+                next = self.expression();
+            }
+            if let Some(value) = next {
+                self.enumerators.insert(name, value);
+            }
+            next = next.map(|value| value + 1);
+            if !self.eat(",") {
+                self.expect("}")?;
+                break;
+            }
+        }
+        Some(CType::Enum(tag))
+    }
+
+    /// A declarator: the name it declares, if any, and how it derives its type, in the order
+    /// the derivations apply to the type of the specifiers.
+    fn declarator(&mut self) -> Option<(Option<String>, Vec<Derive>)> {
+        let mut pointers = 0;
+        loop {
+            self.skip_extensions();
+            if self.eat("*") {
+                pointers += 1;
+                continue;
+            }
+            match self.peek() {
+                Some(Token::Ident(name)) if is_qualifier(name) => self.pos += 1,
+                _ => break,
+            }
+        }
+        self.skip_extensions();
+
+        let mut name = None;
+        let mut inner = Vec::new();
+        if self.is("(") && self.nested_declarator() {
+            self.pos += 1;
+            (name, inner) = self.declarator()?;
+            self.expect(")")?;
+        } else if let Some(Token::Ident(_)) = self.peek() {
+            name = self.ident();
+        }
+
+        let mut suffixes = Vec::new();
+        loop {
+            self.skip_extensions();
+            if self.eat("[") {
+                let mut size = None;
+                while let Some(Token::Ident(name)) = self.peek() {
+                    match is_qualifier(name) || name == "static" {
+                        true => self.pos += 1,
+                        false => break,
+                    }
+                }
+                if !self.is("]") {
+                    let start = self.pos;
+                    size = self
+                        .expression()
+                        .and_then(|size| usize::try_from(size).ok());
+                    if !self.is("]") {
+                        self.pos = start;
+                        while !self.is("]") {
+                            self.pos += 1;
+                            self.peek()?;
+                        }
+                    }
+                }
+                self.expect("]")?;
+                suffixes.push(Derive::Array(size));
+            } else if self.eat("(") {
+                let (params, variadic) = self.parameters()?;
+                suffixes.push(Derive::Function(params, variadic));
+            } else {
+                break;
+            }
+        }
+
+        let mut derived: Vec<Derive> = (0..pointers).map(|_| Derive::Pointer).collect();
+        derived.extend(suffixes.into_iter().rev());
+        derived.extend(inner);
+        Some((name, derived))
+    }
+
+    /// Whether the `(` at the current token starts a nested declarator, as in `(*f)(int)`,
+    /// rather than the parameters of an abstract one.
+    fn nested_declarator(&self) -> bool {
+        match self.peek_at(1) {
+            Some(Token::Punct("*" | "(" | "[")) => true,
+            Some(Token::Ident(name)) => {
+                !is_specifier_keyword(name)
+                    && !self.typedefs.contains_key(name.as_str())
+                    && !name.starts_with("__attribute")
+            }
+            _ => false,
+        }
+    }
+
+    /// The parameters of a function declarator, after the `(`.
+    fn parameters(&mut self) -> Option<(Vec<CType>, bool)> {
+        let mut params = Vec::new();
+        if self.eat(")") {
+            return Some((params, false));
+        }
+        if self.peek() == Some(&Token::Ident("void".to_string()))
+            && self.peek_at(1) == Some(&Token::Punct(")"))
+        {
+            self.pos += 2;
+            return Some((params, false));
+        }
+        loop {
+            if self.eat("...") {
+                self.expect(")")?;
+                return Some((params, true));
+            }
+            let specifiers = self.specifiers()?;
+            let (_, derived) = self.declarator()?;
+            params.push(derive(specifiers.base?, derived));
+            self.skip_extensions();
+            if self.eat(")") {
+                return Some((params, false));
+            }
+            self.expect(",")?;
+        }
+    }
+
+    /// A constant expression, as of an enumerator or an array length.
+    fn expression(&mut self) -> Option<i128> {
+        let mut expression = Expression {
+            tokens: &self.tokens,
+            pos: self.pos,
+            declarations: self,
+            resolve: &mut |_| None,
+        };
+        let value = expression.conditional();
+        self.pos = expression.pos;
+        value
+    }
+
+    /// The tahini types of the declarations.
+    fn convert(&self) -> Header {
+        let mut converter = Converter {
+            parser: self,
+            aliases: Aliases::default(),
+            names: HashMap::new(),
+            representable: HashMap::new(),
+            converting: HashSet::new(),
+        };
+        // Records take the name of their tag, or of the first typedef of them.
+        for (index, record) in self.records.iter().enumerate() {
+            if let Some(tag) = &record.tag {
+                converter.names.insert(index, tag.clone());
+            }
+        }
+        let mut typedefs: Vec<(&String, &CType)> = self.typedefs.iter().collect();
+        typedefs.sort_by_key(|(name, _)| *name);
+        for (name, c_type) in &typedefs {
+            if let CType::Record(index) = c_type {
+                converter.names.entry(*index).or_insert((*name).clone());
+            }
+        }
+
+        let mut header = Header::default();
+        for (index, name) in converter.names.clone() {
+            if let Some(var_type) = converter.record_type(index) {
+                header.types.insert(name, var_type);
+            }
+        }
+        for name in &self.enums {
+            header.types.insert(name.clone(), VarType::Int32);
+        }
+        for (name, c_type) in &typedefs {
+            if header.types.contains_key(*name) {
+                continue;
+            }
+            let target = match converter.resolve(c_type) {
+                CType::Record(index) if converter.record_type(index).is_some() => {
+                    VarType::IdentType(converter.names[&index].clone())
+                }
+                CType::Enum(Some(tag)) => VarType::IdentType(tag),
+                CType::Enum(None) => VarType::Int32,
+                _ => continue,
+            };
+            header.types.insert((*name).clone(), target);
+        }
+        converter.aliases.types.extend(header.types.clone());
+
+        for (name, c_type) in &self.values {
+            let var_type = match converter.resolve(c_type) {
+                CType::Function(params, variadic, ret) => {
+                    converter.function(&params, variadic, &ret)
+                }
+                other => converter.value(&other),
+            };
+            if let Some(var_type) = var_type {
+                header.values.insert(name.clone(), var_type);
+            }
+        }
+
+        // Pointers to records that turned out to be opaque while they were converted.
+        let mut dangling = Dangling(header.types.keys().cloned().collect());
+        let types = header.types.values_mut();
+        for var_type in types.chain(header.values.values_mut()) {
+            dangling.visit_var_type(var_type);
+        }
+        header
+    }
+}
+
+/// Replaces pointers to types that are not declared with `(ptr void)`.
+struct Dangling(HashSet<String>);
+
+impl VisitorMut for Dangling {
+    fn visit_var_type(&mut self, var_type: &mut VarType) {
+        if let VarType::Ptr(target) = var_type {
+            if matches!(&**target, VarType::IdentType(name) if !self.0.contains(name)) {
+                **target = VarType::Void;
+            }
+        }
+        visit::walk_mut_var_type(self, var_type);
+    }
+}
+
+/// The attributes and keywords that change the layout of a record.
+const LAYOUT_ATTRIBUTES: &[&str] = &[
+    "packed",
+    "__packed__",
+    "aligned",
+    "__aligned__",
+    "_Alignas",
+    "alignas",
+];
+
+const PRIMITIVES: &[&str] = &[
+    "char",
+    "void",
+    "float",
+    "double",
+    "_Bool",
+    "bool",
+    "__int128",
+    "_Float16",
+    "_Float32",
+    "_Float64",
+    "_Float128",
+    "__float128",
+    "_Float32x",
+    "_Float64x",
+];
+
+fn is_qualifier(name: &str) -> bool {
+    matches!(
+        name,
+        "const"
+            | "__const"
+            | "__const__"
+            | "volatile"
+            | "__volatile"
+            | "__volatile__"
+            | "restrict"
+            | "__restrict"
+            | "__restrict__"
+            | "_Atomic"
+            | "_Nonnull"
+            | "_Nullable"
+    )
+}
+
+fn is_specifier_keyword(name: &str) -> bool {
+    is_qualifier(name)
+        || PRIMITIVES.contains(&name)
+        || matches!(
+            name,
+            "typedef"
+                | "static"
+                | "extern"
+                | "inline"
+                | "__inline"
+                | "__inline__"
+                | "_Noreturn"
+                | "register"
+                | "auto"
+                | "signed"
+                | "__signed"
+                | "__signed__"
+                | "unsigned"
+                | "short"
+                | "long"
+                | "int"
+                | "struct"
+                | "union"
+                | "enum"
+                | "_Complex"
+                | "__complex__"
+                | "__builtin_va_list"
+                | "typeof"
+                | "__typeof__"
+                | "__typeof"
+                | "__extension__"
+        )
+}
+
+/// The punctuation token spelled `punct`.
+fn punctuation(punct: &str) -> &'static str {
+    PUNCTUATION.iter().find(|p| **p == punct).unwrap()
+}
+
+/// The integer or float type of the specifiers: `scalar` is the keyword other than `signed`,
+/// `unsigned`, `short` and `long`.
+fn primitive(scalar: Option<&str>, unsigned: bool, short: bool, longs: usize) -> CType {
+    let var_type = match (scalar.unwrap_or("int"), unsigned) {
+        ("char", false) => VarType::Int8,
+        ("char", true) => VarType::UInt8,
+        ("int", false) if short => VarType::Int16,
+        ("int", true) if short => VarType::UInt16,
+        ("int", false) if longs > 0 => VarType::Int64,
+        ("int", true) if longs > 0 => VarType::UInt64,
+        ("int", false) => VarType::Int32,
+        ("int", true) => VarType::UInt32,
+        ("__int128", false) => VarType::Int128,
+        ("__int128", true) => VarType::UInt128,
+        ("void", _) => VarType::Void,
+        ("_Bool" | "bool", _) => VarType::Bool,
+        ("_Float16", _) => VarType::Float16,
+        ("float" | "_Float32", _) => VarType::Float32,
+        ("double", _) if longs > 0 => VarType::Float128,
+        ("double" | "_Float64" | "_Float32x", _) => VarType::Float64,
+        _ => return CType::Unsupported,
+    };
+    CType::Scalar(var_type)
+}
+
+/// `base` with the derivations of a declarator applied.
+fn derive(base: CType, derived: Vec<Derive>) -> CType {
+    derived
+        .into_iter()
+        .fold(base, |c_type, derive| match derive {
+            Derive::Pointer => CType::Pointer(Box::new(c_type)),
+            Derive::Array(size) => CType::Array(Box::new(c_type), size),
+            Derive::Function(params, variadic) => {
+                CType::Function(params, variadic, Box::new(c_type))
+            }
+        })
+}
+
+/// Evaluates constant expressions of C in `tokens`, with the enumerators and typedefs of
+/// `declarations`, and `resolve` giving the values of other identifiers.
+struct Expression<'a, 'b> {
+    tokens: &'a [Token],
+    pos: usize,
+    declarations: &'a Parser,
+    resolve: &'b mut dyn FnMut(&str) -> Option<i128>,
+}
+
+/// Binary operators by precedence, loosest first.
+const BINARY: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+impl Expression<'_, '_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let matches = self.peek() == Some(&Token::Punct(punctuation(punct)));
+        if matches {
+            self.pos += 1;
+        }
+        matches
+    }
+
+    /// Skip a cast at the current `(`: `Some(true)` for a cast to an integer type and
+    /// `Some(false)` for a cast to a pointer, which `NULL` is and integer constants are not.
+    fn cast(&mut self) -> Option<bool> {
+        let mut end = self.pos + 1;
+        let mut integer = true;
+        loop {
+            match self.tokens.get(end)? {
+                Token::Punct(")") => break,
+                Token::Punct("*") => integer = false,
+                Token::Ident(name)
+                    if is_specifier_keyword(name)
+                        || self.declarations.typedefs.contains_key(name.as_str()) => {}
+                _ => return None,
+            }
+            end += 1;
+        }
+        if end == self.pos + 1 {
+            return None;
+        }
+        self.pos = end + 1;
+        Some(integer)
+    }
+
+    fn conditional(&mut self) -> Option<i128> {
+        let condition = self.binary(0)?;
+        if !self.eat("?") {
+            return Some(condition);
+        }
+        let then = self.conditional()?;
+        self.eat(":").then_some(())?;
+        let otherwise = self.conditional()?;
+        Some(if condition != 0 { then } else { otherwise })
+    }
+
+    fn binary(&mut self, level: usize) -> Option<i128> {
+        if level == BINARY.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        loop {
+            let Some(Token::Punct(op)) = self.peek().cloned() else {
+                return Some(left);
+            };
+            if !BINARY[level].contains(&op) {
+                return Some(left);
+            }
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            left = match op {
+                "||" => (left != 0 || right != 0) as i128,
+                "&&" => (left != 0 && right != 0) as i128,
+                "|" => left | right,
+                "^" => left ^ right,
+                "&" => left & right,
+                "==" => (left == right) as i128,
+                "!=" => (left != right) as i128,
+                "<" => (left < right) as i128,
+                "<=" => (left <= right) as i128,
+                ">" => (left > right) as i128,
+                ">=" => (left >= right) as i128,
+                "<<" => left.checked_shl(u32::try_from(right).ok()?)?,
+                ">>" => left.checked_shr(u32::try_from(right).ok()?)?,
+                "+" => left.checked_add(right)?,
+                "-" => left.checked_sub(right)?,
+                "*" => left.checked_mul(right)?,
+                "/" => left.checked_div(right)?,
+                _ => left.checked_rem(right)?,
+            };
+        }
+    }
+
+    fn unary(&mut self) -> Option<i128> {
+        if self.peek() == Some(&Token::Punct("(")) {
+            match self.cast() {
+                Some(true) => return self.unary(),
+                Some(false) => return None,
+                None => {}
+            }
+        }
+        let token = self.peek()?.clone();
+        self.pos += 1;
+        match token {
+            Token::Punct("-") => self.unary()?.checked_neg(),
+            Token::Punct("+") => self.unary(),
+            Token::Punct("~") => Some(!self.unary()?),
+            Token::Punct("!") => Some((self.unary()? == 0) as i128),
+            Token::Punct("(") => {
+                let value = self.conditional()?;
+                self.eat(")").then_some(value)
+            }
+            Token::Number(number) => integer(&number),
+            Token::Char(text) => character(&text),
+            Token::Ident(name) => match self.declarations.enumerators.get(&name) {
+                Some(value) => Some(*value),
+                None => (self.resolve)(&name),
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The value of an integer literal of C.
+fn integer(number: &str) -> Option<i128> {
+    let digits = number.trim_end_matches(['u', 'U', 'l', 'L']);
+    let (digits, radix) = match digits.get(..2) {
+        Some("0x" | "0X") => (&digits[2..], 16),
+        Some("0b" | "0B") => (&digits[2..], 2),
+        _ if digits.len() > 1 && digits.starts_with('0') => (&digits[1..], 8),
+        _ => (digits, 10),
+    };
+    i128::from_str_radix(digits, radix).ok()
+}
+
+/// The value of a floating literal of C.
+fn float(number: &str) -> Option<f64> {
+    let is_hex = number.starts_with("0x") || number.starts_with("0X");
+    if is_hex || !number.contains(['.', 'e', 'E']) {
+        return None;
+    }
+    number.trim_end_matches(['f', 'F', 'l', 'L']).parse().ok()
+}
+
+/// The value of a character literal of C, its text without the quotes.
+fn character(text: &str) -> Option<i128> {
+    let bytes = crate::codegen::unescape(text);
+    match bytes.as_slice() {
+        [byte] => Some(*byte as i8 as i128),
+        _ => None,
+    }
+}
+
+/// The bodies of the object-like macros among the `#define` lines of `macros`.
+fn macro_bodies(macros: &str) -> BTreeMap<String, Vec<Token>> {
+    macros
+        .lines()
+        .filter_map(|line| {
+            let definition = line.strip_prefix("#define ")?;
+            let end = definition
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(definition.len());
+            let (name, body) = definition.split_at(end);
+            // Function-like macros have their parameters right after the name.
+            (!body.starts_with('(')).then(|| (name.to_string(), tokenize(body)))
+        })
+        .collect()
+}
+
+/// Evaluates macros that expand to constants.
+struct Constants<'a> {
+    macros: BTreeMap<String, Vec<Token>>,
+    /// The declarations of the header, for its enumerators and typedefs.
+    declarations: &'a Parser,
+    /// Integer values of the macros evaluated so far, `None` while one is being evaluated.
+    values: HashMap<String, Option<i128>>,
+}
+
+impl Constants<'_> {
+    fn literal(&mut self, name: &str) -> Option<Literal> {
+        let body = self.macros.get(name)?.clone();
+        let inner = strip_parens(&body);
+        match inner {
+            [Token::Str(_), ..] => {
+                let mut text = String::new();
+                for token in inner {
+                    let Token::Str(part) = token else {
+                        return None;
+                    };
+                    text.push_str(part);
+                }
+                return Some(Literal::String(text));
+            }
+            [Token::Number(number)] if float(number).is_some() => {
+                return Some(Literal::Float(float(number)?));
+            }
+            [Token::Punct("-"), Token::Number(number)] if float(number).is_some() => {
+                return Some(Literal::Float(-float(number)?));
+            }
+            _ => {}
+        }
+        let value = self.integer(name)?;
+        Some(Literal::Int(i32::try_from(value).ok()? as i64))
+    }
+
+    /// The value of the macro `name` as an integer constant expression.
+    fn integer(&mut self, name: &str) -> Option<i128> {
+        if let Some(value) = self.values.get(name) {
+            return *value;
+        }
+        let body = self.macros.get(name)?.clone();
+        self.values.insert(name.to_string(), None);
+        let declarations = self.declarations;
+        let mut expression = Expression {
+            tokens: &body,
+            pos: 0,
+            declarations,
+            resolve: &mut |name| self.integer(name),
+        };
+        let value = expression.conditional();
+        let value = value.filter(|_| expression.pos == body.len());
+        self.values.insert(name.to_string(), value);
+        value
+    }
+}
+
+fn strip_parens(mut tokens: &[Token]) -> &[Token] {
+    while let [Token::Punct("("), inner @ .., Token::Punct(")")] = tokens {
+        tokens = inner;
+    }
+    tokens
+}
+
+/// Converts the C types of a [`Parser`] to tahini.
+struct Converter<'a> {
+    parser: &'a Parser,
+    /// The header types converted so far, for the layouts of unions.
+    aliases: Aliases,
+    /// The tahini names of records.
+    names: HashMap<usize, String>,
+    /// The converted records, `None` for the ones tahini cannot represent.
+    representable: HashMap<usize, Option<VarType>>,
+    /// The records being converted, which pointers refer to by name.
+    converting: HashSet<usize>,
+}
+
+impl Converter<'_> {
+    /// `c_type` with typedefs and tags replaced by what they stand for, down to the first
+    /// record, enum or non-typedef type.
+    fn resolve(&self, c_type: &CType) -> CType {
+        let mut current = c_type.clone();
+        for _ in 0..64 {
+            current = match current {
+                CType::Typedef(ref name) => match self.parser.typedefs.get(name) {
+                    Some(target) => target.clone(),
+                    None => return CType::Unsupported,
+                },
+                CType::Tag(ref key) => match self.parser.tags.get(key) {
+                    Some(index) => CType::Record(*index),
+                    None => return CType::Unsupported,
+                },
+                other => return other,
+            };
+        }
+        CType::Unsupported
+    }
+
+    /// The struct type of the record at `index`, if tahini can represent it.
+    fn record_type(&mut self, index: usize) -> Option<VarType> {
+        if let Some(known) = self.representable.get(&index) {
+            return known.clone();
+        }
+        // Records do not contain themselves by value, but may point to themselves.
+        self.representable.insert(index, None);
+        self.converting.insert(index);
+        let record = &self.parser.records[index];
+        let var_type = match &record.members {
+            None => None,
+            Some(members) => {
+                let mut fields = Vec::new();
+                for (name, member) in members {
+                    match self.member(member) {
+                        Some(var_type) => fields.push((name.clone(), var_type)),
+                        None => {
+                            fields.clear();
+                            break;
+                        }
+                    }
+                }
+                match (fields.len() == members.len(), record.union) {
+                    (false, _) => None,
+                    (true, false) => Some(VarType::Struct(fields)),
+                    (true, true) => self.union(fields),
+                }
+            }
+        };
+        self.representable.insert(index, var_type.clone());
+        self.converting.remove(&index);
+        if let (Some(name), Some(var_type)) = (self.names.get(&index), &var_type) {
+            let name = name.clone();
+            self.aliases.types.insert(name, var_type.clone());
+        }
+        var_type
+    }
+
+    /// A union as a struct of its largest member, if that has the layout of the union.
+    fn union(&self, members: Vec<(String, VarType)>) -> Option<VarType> {
+        let mut layouts = Vec::new();
+        for (name, var_type) in members {
+            let layout = layout_of(&var_type, &self.aliases).ok()?;
+            layouts.push((layout, name, var_type));
+        }
+        let size = layouts.iter().map(|(layout, ..)| layout.size).max()?;
+        let align = layouts.iter().map(|(layout, ..)| layout.align).max()?;
+        let (layout, name, var_type) = layouts
+            .into_iter()
+            .filter(|(layout, ..)| layout.size == size)
+            .max_by_key(|(layout, ..)| layout.align)?;
+        (layout.align == align && layout.stride() == layout.size)
+            .then(|| VarType::Struct(vec![(name, var_type)]))
+    }
+
+    /// The type of a struct member or global: function pointers are `(ptr void)`.
+    fn member(&mut self, c_type: &CType) -> Option<VarType> {
+        match self.resolve(c_type) {
+            CType::Array(element, Some(size)) if size > 0 => {
+                Some(VarType::ArraySized(Box::new(self.member(&element)?), size))
+            }
+            other => self.value(&other),
+        }
+    }
+
+    /// The tahini type of a value of `c_type`, other than a parameter.
+    fn value(&mut self, c_type: &CType) -> Option<VarType> {
+        match self.resolve(c_type) {
+            CType::Scalar(var_type) => Some(var_type),
+            CType::Record(index) => {
+                self.record_type(index)?;
+                match self.names.get(&index) {
+                    Some(name) => Some(VarType::IdentType(name.clone())),
+                    None => self.record_type(index),
+                }
+            }
+            CType::Enum(_) => Some(self.named(c_type).unwrap_or(VarType::Int32)),
+            CType::Pointer(target) => Some(VarType::Ptr(Box::new(self.pointee(&target)))),
+            CType::Array(element, Some(size)) if size > 0 => {
+                Some(VarType::ArraySized(Box::new(self.member(&element)?), size))
+            }
+            _ => None,
+        }
+    }
+
+    /// The name `c_type` has as a header type, if it is a typedef of a record or enum.
+    fn named(&self, c_type: &CType) -> Option<VarType> {
+        match c_type {
+            CType::Typedef(name) => match self.aliases.types.get(name) {
+                Some(_) => Some(VarType::IdentType(name.clone())),
+                None => match self.parser.typedefs.get(name) {
+                    Some(CType::Enum(_)) | None => None,
+                    Some(target) => self.named(target),
+                },
+            },
+            CType::Enum(Some(tag)) => Some(VarType::IdentType(tag.clone())),
+            _ => None,
+        }
+    }
+
+    /// What a pointer to `c_type` points to: `void` for functions and for types tahini cannot
+    /// represent.
+    fn pointee(&mut self, c_type: &CType) -> VarType {
+        if let Some(named) = self.named(c_type) {
+            return named;
+        }
+        match self.resolve(c_type) {
+            CType::Function(..) => VarType::Void,
+            CType::Pointer(target) => VarType::Ptr(Box::new(self.pointee(&target))),
+            CType::Record(index) if self.converting.contains(&index) => {
+                match self.names.get(&index) {
+                    Some(name) => VarType::IdentType(name.clone()),
+                    None => VarType::Void,
+                }
+            }
+            other => self.value(&other).unwrap_or(VarType::Void),
+        }
+    }
+
+    /// The type of a parameter: arrays are pointers and function pointers are `fn` types.
+    fn parameter(&mut self, c_type: &CType) -> Option<VarType> {
+        if let Some(named) = self.named(c_type) {
+            return Some(named);
+        }
+        match self.resolve(c_type) {
+            CType::Array(element, _) => Some(VarType::Ptr(Box::new(self.pointee(&element)))),
+            CType::Function(params, variadic, ret) => self.function(&params, variadic, &ret),
+            CType::Pointer(target) => match self.resolve(&target) {
+                CType::Function(params, variadic, ret) => Some(
+                    self.function(&params, variadic, &ret)
+                        .unwrap_or(VarType::Ptr(Box::new(VarType::Void))),
+                ),
+                _ => Some(VarType::Ptr(Box::new(self.pointee(&target)))),
+            },
+            other => self.value(&other),
+        }
+    }
+
+    fn function(&mut self, params: &[CType], variadic: bool, ret: &CType) -> Option<VarType> {
+        let params = params
+            .iter()
+            .map(|param| self.parameter(param))
+            .collect::<Option<Vec<_>>>()?;
+        let ret = match self.named(ret) {
+            Some(named) => named,
+            None => self.value(ret)?,
+        };
+        Some(match variadic {
+            true => VarType::FnWithVarArgs(params, Box::new(ret)),
+            false => VarType::Fn(params, Box::new(ret)),
+        })
+    }
+}
@@ -0,0 +1,237 @@
+#[cfg(test)]
+mod tests {
+    use crate::ast::{DefVar, Literal, TopLevelDef, TopLevelStatement, VarType};
+    use crate::header::{import, parse};
+    use std::collections::BTreeSet;
+    use std::process::Command;
+    use std::sync::Arc;
+
+    const SOURCE: &str = "
+        typedef unsigned long size_t;
+        struct point { int x, y; };
+        typedef struct point point_t;
+        typedef struct { double re, im; } pair;
+        union number { int i; double d; };
+        union mixed { char bytes[12]; long l; };
+        struct flags { unsigned a : 1; };
+        struct tight { char c; int i; } __attribute__((packed));
+        struct list { struct list *next; int value; int padding[2]; };
+        struct opaque;
+        enum color { RED, GREEN = 5, BLUE };
+        typedef enum { SMALL = 1 << 2, LARGE = SMALL * 2 } size;
+        extern int counter;
+        extern struct opaque *handle;
+        size_t length(const char *text) __attribute__((__nonnull__(1)));
+        point_t move(struct point p, int dx, int dy);
+        void sort(void *base, size_t n, int (*compare)(const void *, const void *));
+        int print(const char *__restrict format, ...);
+        void draw(struct flags f);
+        void fill(int xs[8], int n);
+        static inline int twice(int x) { return 2 * x; }
+        _Static_assert(sizeof(int) == 4, \"int\");
+        long double precise(void);
+        enum color paint(size s, union number n, struct tight *t);
+    ";
+
+    const MACROS: &str = "
+#define ANSWER 42
+#define MASK (ANSWER << 4 | 0x3)
+#define NAME \"tahini\" \" \" \"header\"
+#define RATE 2.5e-1f
+#define HUGE 0x100000000
+#define TWICE(x) (2 * (x))
+#define NOTHING ((void *) 0)
+#define LIMIT ((size_t) 16)
+#define LETTER 'A'
+#define BLUE_TOO BLUE
+";
+
+    fn ident(name: &str) -> VarType {
+        VarType::IdentType(name.to_string())
+    }
+
+    fn ptr(var_type: VarType) -> VarType {
+        VarType::Ptr(Box::new(var_type))
+    }
+
+    fn function(params: Vec<VarType>, ret: VarType) -> VarType {
+        VarType::Fn(params, Box::new(ret))
+    }
+
+    #[test]
+    fn test_declarations_of_c() {
+        let header = parse(SOURCE, MACROS);
+        let values = |name: &str| header.values.get(name).cloned();
+        assert_eq!(values("counter"), Some(VarType::Int32));
+        assert_eq!(values("handle"), Some(ptr(VarType::Void)));
+        assert_eq!(
+            values("length"),
+            Some(function(vec![ptr(VarType::Int8)], VarType::UInt64))
+        );
+        assert_eq!(
+            values("move"),
+            Some(function(
+                vec![ident("point"), VarType::Int32, VarType::Int32],
+                ident("point_t")
+            ))
+        );
+        let compare = function(vec![ptr(VarType::Void), ptr(VarType::Void)], VarType::Int32);
+        assert_eq!(
+            values("sort"),
+            Some(function(
+                vec![ptr(VarType::Void), VarType::UInt64, compare],
+                VarType::Void
+            ))
+        );
+        assert_eq!(
+            values("print"),
+            Some(VarType::FnWithVarArgs(
+                vec![ptr(VarType::Int8)],
+                Box::new(VarType::Int32)
+            ))
+        );
+        assert_eq!(
+            values("fill"),
+            Some(function(
+                vec![ptr(VarType::Int32), VarType::Int32],
+                VarType::Void
+            ))
+        );
+        assert_eq!(values("precise"), Some(function(vec![], VarType::Float128)));
+        assert_eq!(
+            values("paint"),
+            Some(function(
+                vec![ident("size"), ident("number"), ptr(VarType::Void)],
+                ident("color")
+            ))
+        );
+        // Opaque structs by value and static functions have no declarations.
+        assert_eq!(values("draw"), None);
+        assert_eq!(values("twice"), None);
+
+        let types = |name: &str| header.types.get(name).cloned();
+        let struct_of = |fields: &[(&str, VarType)]| {
+            let fields = fields.iter().map(|(name, t)| (name.to_string(), t.clone()));
+            VarType::Struct(fields.collect())
+        };
+        let point = struct_of(&[("x", VarType::Int32), ("y", VarType::Int32)]);
+        assert_eq!(types("point"), Some(point));
+        assert_eq!(types("point_t"), Some(ident("point")));
+        let pair = struct_of(&[("re", VarType::Float64), ("im", VarType::Float64)]);
+        assert_eq!(types("pair"), Some(pair));
+        assert_eq!(types("number"), Some(struct_of(&[("d", VarType::Float64)])));
+        assert_eq!(
+            types("list"),
+            Some(struct_of(&[
+                ("next", ptr(ident("list"))),
+                ("value", VarType::Int32),
+                ("padding", VarType::ArraySized(Box::new(VarType::Int32), 2)),
+            ]))
+        );
+        assert_eq!(types("color"), Some(VarType::Int32));
+        assert_eq!(types("size"), Some(VarType::Int32));
+        for opaque in ["mixed", "flags", "tight", "opaque", "size_t"] {
+            assert_eq!(types(opaque), None, "{}", opaque);
+        }
+
+        let constants = |name: &str| header.constants.get(name).cloned();
+        assert_eq!(constants("ANSWER"), Some(Literal::Int(42)));
+        assert_eq!(constants("MASK"), Some(Literal::Int(675)));
+        assert_eq!(
+            constants("NAME"),
+            Some(Literal::String("tahini header".to_string()))
+        );
+        assert_eq!(constants("RATE"), Some(Literal::Float(0.25)));
+        assert_eq!(constants("LIMIT"), Some(Literal::Int(16)));
+        assert_eq!(constants("LETTER"), Some(Literal::Int(65)));
+        assert_eq!(constants("BLUE_TOO"), Some(Literal::Int(6)));
+        assert_eq!(constants("LARGE"), Some(Literal::Int(8)));
+        for skipped in ["HUGE", "TWICE", "NOTHING"] {
+            assert_eq!(constants(skipped), None, "{}", skipped);
+        }
+    }
+
+    #[test]
+    fn test_declarations_are_prefixed() {
+        let header = parse(SOURCE, MACROS);
+        let members = BTreeSet::from(["move", "ANSWER", "missing", "point_t"]);
+        let point = VarType::Struct(vec![
+            ("x".to_string(), VarType::Int32),
+            ("y".to_string(), VarType::Int32),
+        ]);
+        assert_eq!(
+            header.declarations("g", &members),
+            vec![
+                TopLevelStatement::TypeAlias("g/point".to_string(), point),
+                TopLevelStatement::TypeAlias("g/point_t".to_string(), ident("g/point")),
+                TopLevelStatement::TopLevelDef(DefVar {
+                    name: "g/ANSWER".to_string(),
+                    instruction: TopLevelDef::Literal(Literal::Int(42)),
+                }),
+                TopLevelStatement::TopLevelDef(DefVar {
+                    name: "g/move".to_string(),
+                    instruction: TopLevelDef::Typed(function(
+                        vec![ident("g/point"), VarType::Int32, VarType::Int32],
+                        ident("g/point_t")
+                    )),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_through_the_preprocessor() {
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        let header = import("stdlib.h", "cc", &[]).unwrap();
+        assert_eq!(
+            header.values.get("div"),
+            Some(&function(
+                vec![VarType::Int32, VarType::Int32],
+                ident("div_t")
+            ))
+        );
+        assert_eq!(header.constants.get("EXIT_FAILURE"), Some(&Literal::Int(1)));
+        assert!(Arc::ptr_eq(
+            &header,
+            &import("stdlib.h", "cc", &[]).unwrap()
+        ));
+
+        let error = import("no_such_header.h", "cc", &[]).unwrap_err();
+        assert!(error.contains("no_such_header.h"), "{}", error);
+    }
+
+    #[test]
+    fn test_edited_headers_are_imported_again() {
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        let dir = std::env::temp_dir().join(format!("tahini-header-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("shapes.h"), "int area(int w, int h);\n").unwrap();
+        let flags = vec![format!("-I{}", dir.display())];
+
+        let header = import("shapes.h", "cc", &flags).unwrap();
+        assert!(Arc::ptr_eq(
+            &header,
+            &import("shapes.h", "cc", &flags).unwrap()
+        ));
+        assert!(!header.values.contains_key("perimeter"));
+
+        std::fs::write(
+            dir.join("shapes.h"),
+            "int area(int w, int h);\nint perimeter(int w, int h);\n",
+        )
+        .unwrap();
+        let header = import("shapes.h", "cc", &flags).unwrap();
+        assert_eq!(
+            header.values.get("perimeter"),
+            Some(&function(
+                vec![VarType::Int32, VarType::Int32],
+                VarType::Int32
+            ))
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
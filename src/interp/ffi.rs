@@ -0,0 +1,740 @@
+//! Calls of C functions from the interpreter, with the x86-64 System V calling convention.
+//!
+//! Functions are looked up with `dlsym`: first among the symbols of the process, which include
+//! the C library, then in the shared libraries opened with [`Libraries::open`].
+//!
+//! Arguments are classified as the ABI does: integers and pointers go to the six integer
+//! registers, floats to the eight vector registers, structs of up to 16 bytes to the
+//! registers of the classes of their eightbytes, and whatever does not fit to the stack. Every
+//! call fills all of those registers and passes the stack words as the variadic arguments of
+//! a function type that takes all of the registers as fixed parameters, so one function type
+//! places any signature. Calling through a variadic type also sets `%al` to the number of
+//! vector registers, which variadic callees such as `printf` read and others ignore. Return
+//! values are read back from the two registers of their classes, or from the memory the
+//! caller passes for larger structs.
+//!
+//! The memory that arguments point to is copied to C memory before the call and back after
+//! it, so C functions can read strings and fill buffers, and pointers they return into those
+//! copies point back into the interpreter's cells. Other addresses C returns are
+//! [`Pointer::Foreign`]: they can be passed back to C, but the copies made for a call do not
+//! outlive it.
+//!
+//! `f16`, `f128`, `data` values and functions are not passed to C.
+
+use super::value::{Cell, Pointer, Value};
+use crate::ast::VarType;
+use crate::transformer::layout::{layout_of, member_offsets, Layout};
+use crate::typeck::types::{atom_type, Aliases};
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::rc::Rc;
+
+const RTLD_NOW: c_int = 2;
+const RTLD_GLOBAL: c_int = 0x100;
+
+/// The registers that pass arguments.
+const INT_REGISTERS: usize = 6;
+const SSE_REGISTERS: usize = 8;
+
+#[link(name = "dl")]
+extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlerror() -> *mut c_char;
+    fn fflush(stream: *mut c_void) -> c_int;
+}
+
+/// The libraries C functions are looked up in.
+pub struct Libraries {
+    /// `dlopen` handles, the process first. They stay open for the life of the program.
+    handles: Vec<usize>,
+    /// Directories to look for libraries in, as with `-L`.
+    dirs: Vec<String>,
+}
+
+impl Default for Libraries {
+    fn default() -> Self {
+        // SAFETY: a null file name opens the symbols of the process.
+        let process = unsafe { dlopen(std::ptr::null(), RTLD_NOW) };
+        Libraries {
+            handles: vec![process as usize],
+            dirs: Vec::new(),
+        }
+    }
+}
+
+impl Libraries {
+    /// Look for libraries named without a path in `dir` first.
+    pub fn add_dir(&mut self, dir: &str) {
+        self.dirs.push(dir.to_string());
+    }
+
+    /// Open the shared library `library`: a path, or a name such as `m` for `libm.so`.
+    pub fn open(&mut self, library: &str) -> Result<(), String> {
+        let file = match library.contains('/') || library.contains(".so") {
+            true => library.to_string(),
+            false => {
+                let file = format!("lib{}.so", library);
+                self.dirs
+                    .iter()
+                    .map(|dir| format!("{}/{}", dir, file))
+                    .find(|path| std::path::Path::new(path).exists())
+                    .unwrap_or(file)
+            }
+        };
+        let name = CString::new(file).map_err(|error| error.to_string())?;
+        // SAFETY: `name` is a NUL-terminated string.
+        let handle = unsafe { dlopen(name.as_ptr(), RTLD_NOW | RTLD_GLOBAL) };
+        if handle.is_null() {
+            // SAFETY: `dlerror` describes the failed `dlopen` in a NUL-terminated string.
+            let error = unsafe { CStr::from_ptr(dlerror()) };
+            let error = error.to_string_lossy().to_string();
+            // Libraries such as `libm.so` of glibc are linker scripts naming the real one.
+            let script = error.strip_suffix(": invalid ELF header");
+            return match script.and_then(script_input) {
+                Some(input) => self.open(&input),
+                None => Err(error),
+            };
+        }
+        self.handles.push(handle as usize);
+        Ok(())
+    }
+
+    /// The address of the function or global `name`.
+    pub fn symbol(&self, name: &str) -> Option<usize> {
+        let name = CString::new(name).ok()?;
+        self.handles.iter().find_map(|&handle| {
+            // SAFETY: `handle` came from `dlopen` and `name` is NUL-terminated.
+            let address = unsafe { dlsym(handle as *mut c_void, name.as_ptr()) };
+            (!address.is_null()).then_some(address as usize)
+        })
+    }
+}
+
+/// The first library the linker script at `path` links with, as in
+/// `GROUP ( /lib/libm.so.6 AS_NEEDED ( ... ) )`.
+fn script_input(path: &str) -> Option<String> {
+    let script = std::fs::read_to_string(path).ok()?;
+    let (_, inputs) = script
+        .split_once("GROUP")
+        .or_else(|| script.split_once("INPUT"))?;
+    inputs
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .find(|input| !input.is_empty())
+        .map(str::to_string)
+}
+
+/// The class of an eightbyte of an argument or return value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Class {
+    Integer,
+    Sse,
+}
+
+/// The eightbyte classes of a value of `layout` with scalars of the given classes at the given
+/// offsets, or `None` for values that are passed in memory.
+fn classify(layout: Layout, scalars: &[(usize, Class)]) -> Option<Vec<Class>> {
+    if layout.size > 16 {
+        return None;
+    }
+    let mut classes = vec![Class::Sse; layout.size.div_ceil(8)];
+    for &(offset, class) in scalars {
+        if class == Class::Integer {
+            classes[offset / 8] = Class::Integer;
+        }
+    }
+    Some(classes)
+}
+
+fn scalar_layout(var_type: &VarType) -> Layout {
+    layout_of(var_type, &Aliases::default()).expect("scalars have a layout")
+}
+
+/// The layout of `value` in C memory.
+fn value_layout(value: &Value) -> Result<Layout, String> {
+    Ok(match value {
+        Value::Bool(_) => scalar_layout(&VarType::Bool),
+        Value::Int(_, var_type) => scalar_layout(var_type),
+        Value::Float(_, var_type @ (VarType::Float32 | VarType::Float64)) => {
+            scalar_layout(var_type)
+        }
+        Value::Atom(_) | Value::Pointer(_) => scalar_layout(&atom_type()),
+        Value::Tuple(members) | Value::Struct(members) => {
+            let mut size: usize = 0;
+            let mut align = 1;
+            for member in members {
+                let layout = value_layout(member)?;
+                size = size.next_multiple_of(layout.align) + layout.size;
+                align = align.max(layout.align);
+            }
+            Layout {
+                size: size.next_multiple_of(align),
+                align,
+            }
+        }
+        Value::Array(elements) => match elements.first() {
+            Some(first) => {
+                let element = value_layout(first)?;
+                Layout {
+                    size: element.stride() * elements.len(),
+                    align: element.align,
+                }
+            }
+            None => Layout { size: 0, align: 1 },
+        },
+        Value::Closure(..) => {
+            return Err(format!(
+                "`{}` cannot be passed to C: the interpreter cannot turn tahini functions into C \
+                 function pointers; compile the program instead",
+                value
+            ))
+        }
+        other => return Err(format!("`{}` cannot be passed to C", other)),
+    })
+}
+
+/// The offsets of the members of a tuple, struct or array value.
+fn value_offsets(value: &Value) -> Result<Vec<usize>, String> {
+    match value {
+        Value::Tuple(members) | Value::Struct(members) => {
+            let mut offsets = Vec::with_capacity(members.len());
+            let mut size: usize = 0;
+            for member in members {
+                let layout = value_layout(member)?;
+                size = size.next_multiple_of(layout.align);
+                offsets.push(size);
+                size += layout.size;
+            }
+            Ok(offsets)
+        }
+        Value::Array(elements) => {
+            let stride = match elements.first() {
+                Some(first) => value_layout(first)?.stride(),
+                None => 0,
+            };
+            Ok((0..elements.len()).map(|i| i * stride).collect())
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// The scalars of `value` with their offsets and classes.
+fn value_scalars(
+    value: &Value,
+    base: usize,
+    scalars: &mut Vec<(usize, Class)>,
+) -> Result<(), String> {
+    match value {
+        Value::Float(..) => scalars.push((base, Class::Sse)),
+        Value::Tuple(members) | Value::Struct(members) | Value::Array(members) => {
+            for (member, offset) in members.iter().zip(value_offsets(value)?) {
+                value_scalars(member, base + offset, scalars)?;
+            }
+        }
+        _ => scalars.push((base, Class::Integer)),
+    }
+    Ok(())
+}
+
+/// The scalars of a value of `var_type` with their offsets and classes.
+fn type_scalars(
+    var_type: &VarType,
+    aliases: &Aliases,
+    base: usize,
+    scalars: &mut Vec<(usize, Class)>,
+) -> Result<(), String> {
+    match aliases.normalize(var_type) {
+        VarType::Float32 | VarType::Float64 => scalars.push((base, Class::Sse)),
+        VarType::Tuple(members) => {
+            let (offsets, _) = member_offsets(&members, aliases)?;
+            for (member, offset) in members.iter().zip(offsets) {
+                type_scalars(member, aliases, base + offset, scalars)?;
+            }
+        }
+        VarType::Struct(fields) => {
+            let members: Vec<VarType> = fields.into_iter().map(|(_, t)| t).collect();
+            type_scalars(&VarType::Tuple(members), aliases, base, scalars)?;
+        }
+        VarType::ArraySized(element, length) => {
+            let stride = layout_of(&element, aliases)?.stride();
+            for i in 0..length {
+                type_scalars(&element, aliases, base + i * stride, scalars)?;
+            }
+        }
+        _ => scalars.push((base, Class::Integer)),
+    }
+    Ok(())
+}
+
+/// Storage for C memory, aligned for any scalar.
+type Storage = Vec<u128>;
+
+fn storage(size: usize) -> Storage {
+    vec![0; size.div_ceil(16).max(1)]
+}
+
+fn bytes(storage: &Storage) -> &[u8] {
+    // SAFETY: the storage is plain memory of at least this many bytes.
+    unsafe { std::slice::from_raw_parts(storage.as_ptr() as *const u8, storage.len() * 16) }
+}
+
+/// The memory of one call: the copies of the cells its arguments point to.
+#[derive(Default)]
+struct Marshal {
+    /// Each cell with the storage its value was copied to.
+    copies: Vec<(Cell, Storage)>,
+    /// Atom names, which are not copied back.
+    atoms: Vec<Storage>,
+}
+
+impl Marshal {
+    /// The bytes of `value` in C memory, with the memory it points to copied.
+    fn encode(&mut self, value: &Value) -> Result<Vec<u8>, String> {
+        let mut bytes = vec![0; value_layout(value)?.size];
+        self.encode_at(value, &mut bytes, 0)?;
+        Ok(bytes)
+    }
+
+    fn encode_at(&mut self, value: &Value, bytes: &mut [u8], offset: usize) -> Result<(), String> {
+        let mut put = |data: &[u8]| bytes[offset..offset + data.len()].copy_from_slice(data);
+        match value {
+            Value::Bool(value) => put(&[*value as u8]),
+            Value::Int(bits, var_type) => {
+                put(&bits.to_le_bytes()[..scalar_layout(var_type).size]);
+            }
+            Value::Float(value, VarType::Float32) => put(&(*value as f32).to_le_bytes()),
+            Value::Float(value, _) => put(&value.to_le_bytes()),
+            Value::Atom(name) => {
+                let mut atom = storage(name.len() + 1);
+                // SAFETY: the storage has room for the name and its NUL.
+                unsafe {
+                    let start = atom.as_mut_ptr() as *mut u8;
+                    std::ptr::copy_nonoverlapping(name.as_ptr(), start, name.len());
+                }
+                put(&(atom.as_ptr() as u64).to_le_bytes());
+                self.atoms.push(atom);
+            }
+            Value::Pointer(pointer) => {
+                let address = self.address(pointer)?;
+                bytes[offset..offset + 8].copy_from_slice(&address.to_le_bytes());
+            }
+            Value::Tuple(members) | Value::Struct(members) | Value::Array(members) => {
+                for (member, member_offset) in members.iter().zip(value_offsets(value)?) {
+                    self.encode_at(member, bytes, offset + member_offset)?;
+                }
+            }
+            other => return Err(format!("`{}` cannot be passed to C", other)),
+        }
+        Ok(())
+    }
+
+    /// The address in C memory of what `pointer` points to, copying its cell the first time.
+    fn address(&mut self, pointer: &Pointer) -> Result<u64, String> {
+        let (cell, path) = match pointer {
+            Pointer::Null => return Ok(0),
+            Pointer::Foreign(address) => return Ok(*address as u64),
+            Pointer::Cell(cell, path) => (cell, path),
+        };
+        let value = cell.borrow().clone();
+        let copy = match self.copies.iter().position(|(c, _)| Rc::ptr_eq(c, cell)) {
+            Some(copy) => copy,
+            None => {
+                // The cell is registered before its members are encoded, which may point to it.
+                let size = value_layout(&value)?.size;
+                self.copies.push((cell.clone(), storage(size)));
+                let encoded = self.encode(&value)?;
+                let copy = self.copies.len() - 1;
+                let start = self.copies[copy].1.as_mut_ptr() as *mut u8;
+                // SAFETY: the storage was made for the layout of this value.
+                unsafe { std::ptr::copy_nonoverlapping(encoded.as_ptr(), start, encoded.len()) };
+                copy
+            }
+        };
+        let base = self.copies[copy].1.as_ptr() as u64;
+        Ok(base + path_offset(&value, path)? as u64)
+    }
+
+    /// The pointer for an address C hands back: into the copy of a cell if it points into one.
+    fn pointer(&self, address: u64) -> Pointer {
+        if address == 0 {
+            return Pointer::Null;
+        }
+        for (cell, storage) in &self.copies {
+            let base = storage.as_ptr() as u64;
+            let value = cell.borrow();
+            let size = value_layout(&value).map_or(0, |layout| layout.size) as u64;
+            if (base..=base + size).contains(&address) {
+                if let Some(path) = offset_path(&value, (address - base) as usize) {
+                    return Pointer::Cell(cell.clone(), path);
+                }
+            }
+        }
+        Pointer::Foreign(address as usize)
+    }
+
+    /// Copy the memory of every cell back, once C has returned.
+    fn write_back(&self) -> Result<(), String> {
+        for (cell, storage) in &self.copies {
+            let mut value = cell.borrow().clone();
+            self.decode_into(&mut value, bytes(storage), 0)?;
+            *cell.borrow_mut() = value;
+        }
+        Ok(())
+    }
+
+    /// Update `value` in place from its bytes at `offset`.
+    fn decode_into(&self, value: &mut Value, bytes: &[u8], offset: usize) -> Result<(), String> {
+        let offsets = value_offsets(value)?;
+        match value {
+            Value::Bool(flag) => *flag = bytes[offset] != 0,
+            Value::Int(bits, var_type) => {
+                let size = scalar_layout(var_type).size;
+                *bits = read(bytes, offset, size);
+            }
+            Value::Float(float, VarType::Float32) => {
+                *float = f32::from_bits(read(bytes, offset, 4) as u32) as f64;
+            }
+            Value::Float(float, _) => *float = f64::from_bits(read(bytes, offset, 8) as u64),
+            Value::Pointer(pointer) => {
+                let address = read(bytes, offset, 8) as u64;
+                // An unchanged pointer keeps pointing where it did, such as one past the end.
+                if self.encoded(pointer) != Some(address) {
+                    *pointer = self.pointer(address);
+                }
+            }
+            Value::Tuple(members) | Value::Struct(members) | Value::Array(members) => {
+                for (member, member_offset) in members.iter_mut().zip(offsets) {
+                    self.decode_into(member, bytes, offset + member_offset)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The address `pointer` was passed to C as.
+    fn encoded(&self, pointer: &Pointer) -> Option<u64> {
+        match pointer {
+            Pointer::Null => Some(0),
+            Pointer::Foreign(address) => Some(*address as u64),
+            Pointer::Cell(cell, path) => {
+                let (_, storage) = self.copies.iter().find(|(c, _)| Rc::ptr_eq(c, cell))?;
+                let offset = path_offset(&cell.borrow(), path).ok()?;
+                Some(storage.as_ptr() as u64 + offset as u64)
+            }
+        }
+    }
+
+    /// The value of `var_type` in `bytes` at `offset`.
+    fn decode(
+        &self,
+        var_type: &VarType,
+        aliases: &Aliases,
+        bytes: &[u8],
+        offset: usize,
+    ) -> Result<Value, String> {
+        let normalized = aliases.normalize(var_type);
+        Ok(match &normalized {
+            VarType::Void => Value::Void,
+            VarType::Bool => Value::Bool(bytes[offset] != 0),
+            VarType::Float32 => {
+                let bits = read(bytes, offset, 4) as u32;
+                Value::float(f32::from_bits(bits) as f64, &normalized)
+            }
+            VarType::Float64 => {
+                Value::float(f64::from_bits(read(bytes, offset, 8) as u64), &normalized)
+            }
+            VarType::Ptr(_) | VarType::ArrayUnsized(_) => {
+                Value::Pointer(self.pointer(read(bytes, offset, 8) as u64))
+            }
+            VarType::Tuple(members) => {
+                let (offsets, _) = member_offsets(members, aliases)?;
+                let members = members.iter().zip(offsets);
+                Value::Tuple(
+                    members
+                        .map(|(member, at)| self.decode(member, aliases, bytes, offset + at))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            VarType::Struct(fields) => {
+                let members: Vec<VarType> = fields.iter().map(|(_, t)| t.clone()).collect();
+                match self.decode(&VarType::Tuple(members), aliases, bytes, offset)? {
+                    Value::Tuple(members) => Value::Struct(members),
+                    _ => unreachable!("tuples decode as tuples"),
+                }
+            }
+            VarType::ArraySized(element, length) => {
+                let stride = layout_of(element, aliases)?.stride();
+                Value::Array(
+                    (0..*length)
+                        .map(|i| self.decode(element, aliases, bytes, offset + i * stride))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            t if scalar_int(t) => {
+                let size = scalar_layout(t).size;
+                Value::Int(read(bytes, offset, size), normalized.clone())
+            }
+            other => return Err(format!("C cannot return `{}` to the interpreter", other)),
+        })
+    }
+}
+
+fn scalar_int(var_type: &VarType) -> bool {
+    Value::zero(var_type, &Aliases::default())
+        .as_int()
+        .is_some()
+}
+
+/// `size` little-endian bytes at `offset`.
+fn read(bytes: &[u8], offset: usize, size: usize) -> u128 {
+    let mut buffer = [0; 16];
+    buffer[..size].copy_from_slice(&bytes[offset..offset + size]);
+    u128::from_le_bytes(buffer)
+}
+
+/// The byte offset of the member of `value` at `path`.
+fn path_offset(value: &Value, path: &[usize]) -> Result<usize, String> {
+    let Some((&index, rest)) = path.split_first() else {
+        return Ok(0);
+    };
+    let offsets = value_offsets(value)?;
+    let members = match value {
+        Value::Tuple(members) | Value::Struct(members) | Value::Array(members) => members,
+        _ => return Ok(0),
+    };
+    match members.get(index) {
+        Some(member) => Ok(offsets[index] + path_offset(member, rest)?),
+        // One past the end of an array.
+        None => Ok(value_layout(value)?.size),
+    }
+}
+
+/// The path of the innermost member of `value` that starts at byte `offset`.
+fn offset_path(value: &Value, offset: usize) -> Option<Vec<usize>> {
+    let members = match value {
+        Value::Tuple(members) | Value::Struct(members) | Value::Array(members) => members,
+        _ => return (offset == 0).then(Vec::new),
+    };
+    let offsets = value_offsets(value).ok()?;
+    for (index, (member, start)) in members.iter().zip(&offsets).enumerate() {
+        let size = value_layout(member).ok()?.size;
+        if (*start..start + size.max(1)).contains(&offset) {
+            let mut path = vec![index];
+            path.extend(offset_path(member, offset - start)?);
+            return Some(path);
+        }
+    }
+    match value {
+        Value::Array(_) if offset == value_layout(value).ok()?.size => Some(vec![members.len()]),
+        _ => (offset == 0).then(Vec::new),
+    }
+}
+
+/// The argument registers and stack words of a call.
+#[derive(Default)]
+struct Arguments {
+    ints: Vec<u64>,
+    floats: Vec<f64>,
+    stack: Vec<u64>,
+}
+
+impl Arguments {
+    /// Place a value of `bytes` and `layout`, passed in registers of `classes` while they last.
+    fn push(&mut self, bytes: &[u8], layout: Layout, classes: Option<Vec<Class>>) {
+        let mut words: Vec<u64> = bytes
+            .chunks(8)
+            .map(|chunk| read(chunk, 0, chunk.len()) as u64)
+            .collect();
+        if let Some(classes) = classes {
+            let ints = classes.iter().filter(|c| **c == Class::Integer).count();
+            let floats = classes.len() - ints;
+            if self.ints.len() + ints <= INT_REGISTERS
+                && self.floats.len() + floats <= SSE_REGISTERS
+            {
+                for (class, word) in classes.into_iter().zip(words) {
+                    match class {
+                        Class::Integer => self.ints.push(word),
+                        Class::Sse => self.floats.push(f64::from_bits(word)),
+                    }
+                }
+                return;
+            }
+        }
+        if layout.align > 8 && self.stack.len() % 2 == 1 {
+            self.stack.push(0);
+        }
+        words.resize(layout.size.div_ceil(8), 0);
+        self.stack.extend(words);
+    }
+}
+
+/// Return values in two registers, as the ABI returns 16-byte structs of each pair of classes.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct IntInt(u64, u64);
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SseSse(f64, f64);
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct IntSse(u64, f64);
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SseInt(f64, u64);
+
+/// Every argument register as a fixed parameter and the stack words as variadic arguments.
+type Function<R> = unsafe extern "C" fn(
+    u64,
+    u64,
+    u64,
+    u64,
+    u64,
+    u64,
+    f64,
+    f64,
+    f64,
+    f64,
+    f64,
+    f64,
+    f64,
+    f64,
+    ...
+) -> R;
+
+/// Call the function at `address` with `arguments`.
+///
+/// # Safety
+///
+/// `address` must be a function that takes the arguments as placed and returns a value of the
+/// registers of `R`.
+unsafe fn invoke<R>(address: usize, arguments: &Arguments) -> Result<R, String> {
+    let function: Function<R> = std::mem::transmute::<usize, Function<R>>(address);
+    let mut ints = [0; INT_REGISTERS];
+    ints[..arguments.ints.len()].copy_from_slice(&arguments.ints);
+    let mut floats = [0.0; SSE_REGISTERS];
+    floats[..arguments.floats.len()].copy_from_slice(&arguments.floats);
+    let [a, b, c, d, e, f] = ints;
+    let [x0, x1, x2, x3, x4, x5, x6, x7] = floats;
+    let s = &arguments.stack;
+    macro_rules! call {
+        ($($i:literal)*) => {
+            function(a, b, c, d, e, f, x0, x1, x2, x3, x4, x5, x6, x7, $(s[$i]),*)
+        };
+    }
+    Ok(match s.len() {
+        0 => call!(),
+        1 => call!(0),
+        2 => call!(0 1),
+        3 => call!(0 1 2),
+        4 => call!(0 1 2 3),
+        5 => call!(0 1 2 3 4),
+        6 => call!(0 1 2 3 4 5),
+        7 => call!(0 1 2 3 4 5 6),
+        8 => call!(0 1 2 3 4 5 6 7),
+        9 => call!(0 1 2 3 4 5 6 7 8),
+        10 => call!(0 1 2 3 4 5 6 7 8 9),
+        11 => call!(0 1 2 3 4 5 6 7 8 9 10),
+        12 => call!(0 1 2 3 4 5 6 7 8 9 10 11),
+        13 => call!(0 1 2 3 4 5 6 7 8 9 10 11 12),
+        14 => call!(0 1 2 3 4 5 6 7 8 9 10 11 12 13),
+        15 => call!(0 1 2 3 4 5 6 7 8 9 10 11 12 13 14),
+        16 => call!(0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15),
+        words => {
+            return Err(format!(
+                "the arguments take {} words of the stack, more than the interpreter passes",
+                words
+            ))
+        }
+    })
+}
+
+/// Call the C function at `address` with `args`, already converted to the types of its
+/// parameters, and read back a value of type `ret`.
+pub fn call(
+    address: usize,
+    args: &[Value],
+    ret: &VarType,
+    aliases: &Aliases,
+) -> Result<Value, String> {
+    if !cfg!(all(target_arch = "x86_64", unix)) {
+        return Err("the interpreter only calls C functions on x86-64".to_string());
+    }
+    let mut marshal = Marshal::default();
+    let mut arguments = Arguments::default();
+
+    let ret_layout = layout_of(ret, aliases)?;
+    let mut ret_scalars = Vec::new();
+    type_scalars(ret, aliases, 0, &mut ret_scalars)?;
+    let ret_classes = match ret_layout.size {
+        0 => Some(Vec::new()),
+        _ => classify(ret_layout, &ret_scalars),
+    };
+    // Larger structs are returned in memory the caller passes first.
+    let mut ret_memory = storage(ret_layout.size);
+    if ret_classes.is_none() {
+        arguments.ints.push(ret_memory.as_mut_ptr() as u64);
+    }
+
+    for arg in args {
+        let layout = value_layout(arg)?;
+        let mut scalars = Vec::new();
+        value_scalars(arg, 0, &mut scalars)?;
+        let bytes = marshal.encode(arg)?;
+        arguments.push(&bytes, layout, classify(layout, &scalars));
+    }
+
+    let classes = ret_classes.clone().unwrap_or_default();
+    // SAFETY: the arguments are placed as the ABI places the values of their types, and the
+    // registers read back are the ones a function returning `ret` sets.
+    let words: [u64; 2] = unsafe {
+        match classes.as_slice() {
+            [Class::Sse] | [Class::Sse, Class::Sse] => {
+                let SseSse(x0, x1) = invoke(address, &arguments)?;
+                [x0.to_bits(), x1.to_bits()]
+            }
+            [Class::Integer, Class::Sse] => {
+                let IntSse(a, x0) = invoke(address, &arguments)?;
+                [a, x0.to_bits()]
+            }
+            [Class::Sse, Class::Integer] => {
+                let SseInt(x0, a) = invoke(address, &arguments)?;
+                [x0.to_bits(), a]
+            }
+            _ => {
+                let IntInt(a, d) = invoke(address, &arguments)?;
+                [a, d]
+            }
+        }
+    };
+    // C buffers its output separately from the interpreter's.
+    // SAFETY: a null stream flushes every output stream.
+    unsafe { fflush(std::ptr::null_mut()) };
+
+    marshal.write_back()?;
+    let value = match ret_classes {
+        Some(_) => {
+            let mut bytes = [0; 16];
+            bytes[..8].copy_from_slice(&words[0].to_le_bytes());
+            bytes[8..].copy_from_slice(&words[1].to_le_bytes());
+            marshal.decode(ret, aliases, &bytes, 0)?
+        }
+        None => marshal.decode(ret, aliases, bytes(&ret_memory), 0)?,
+    };
+    Ok(value)
+}
+
+/// The value of type `var_type` stored at `address`, such as a global of a C library.
+pub fn load(address: usize, var_type: &VarType, aliases: &Aliases) -> Result<Value, String> {
+    let size = layout_of(var_type, aliases)?.size;
+    // SAFETY: `address` holds a value of `var_type`.
+    let bytes = unsafe { std::slice::from_raw_parts(address as *const u8, size) };
+    Marshal::default().decode(var_type, aliases, bytes, 0)
+}
+
+/// The bytes of the NUL-terminated string at `address`, which C returned.
+pub fn c_string(address: usize) -> Vec<u8> {
+    // SAFETY: C hands out strings as pointers to NUL-terminated bytes.
+    unsafe { CStr::from_ptr(address as *const c_char) }
+        .to_bytes()
+        .to_vec()
+}
@@ -0,0 +1,128 @@
+#[cfg(test)]
+mod tests {
+    use crate::ast::VarType;
+    use crate::interp::ffi::Libraries;
+    use crate::interp::Interpreter;
+    use crate::lint::ENTRY_POINT;
+    use crate::parser::parser;
+    use crate::transformer::transform;
+    use chumsky::Parser;
+    use std::process::Command;
+    use std::rc::Rc;
+
+    fn interpreter(src: &str) -> Interpreter {
+        let module = parser().parse(src).into_output().unwrap();
+        let transformed = transform(&module).unwrap_or_else(|errors| panic!("{:?}", errors));
+        Interpreter::new(transformed).unwrap_or_else(|errors| panic!("{:?}", errors))
+    }
+
+    /// `main` of `src` shown as `var_type`.
+    fn show(interpreter: &mut Interpreter, var_type: VarType) -> String {
+        let value = interpreter.call(ENTRY_POINT, Vec::new()).unwrap();
+        value.show(&var_type, interpreter.aliases())
+    }
+
+    const LIBRARY: &str = "
+        typedef struct { double x, y; } vec2;
+        typedef struct { long n; double x; } sample;
+        typedef struct { long a, b, c; } triple;
+
+        vec2 scale(vec2 v, double k) { return (vec2){v.x * k, v.y * k}; }
+        sample sample_of(double x) { return (sample){(long)x, x / 2}; }
+        triple shift(triple t, long k) { return (triple){t.a + k, t.b + k, t.c + k}; }
+        long weigh(long a, long b, long c, long d, long e, long f, long g, double x, long h) {
+            return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h + (long)x;
+        }
+        void fill(long *xs, long n) { for (long i = 0; i < n; i++) xs[i] = i * i; }
+    ";
+
+    /// A shared library of `LIBRARY`, or `None` if there is no `cc`.
+    fn library() -> Option<std::path::PathBuf> {
+        let dir = std::env::temp_dir().join(format!("tahini-ffi-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (source, library) = (dir.join("lib.c"), dir.join("libshapes.so"));
+        std::fs::write(&source, LIBRARY).unwrap();
+        let status = Command::new("cc")
+            .args(["-shared", "-fPIC"])
+            .arg(&source)
+            .arg("-o")
+            .arg(&library)
+            .status()
+            .ok()?;
+        assert!(status.success(), "`cc` rejected the test library");
+        Some(library)
+    }
+
+    #[test]
+    fn test_calls_of_the_c_library() {
+        let src = "(def c (use :header \"stdlib.h\")) \n
+            (def s (use :header \"string.h\")) \n
+            (def io (use :header \"stdio.h\")) \n
+            (type div-t (struct (:quot i32) (:rem i32))) \n
+            (def length (fn [(:text str)] i64 (s/strlen text))) \n
+            (def parse (fn [(:text str)] f64 (c/atof text))) \n
+            (def divide (fn [(:a i32) (:b i32)] div-t (c/div a b))) \n
+            (def find (fn [(:text str) (:c i8)] str (s/strchr text c))) \n
+            (def format (fn [(:buffer [i8]) (:x i32)] i8 \n
+                (do (io/snprintf buffer 16 \"<%d|%.1f>\\n\" x 2.5) ($ [6] buffer)))) \n
+            (def main (fn [] {i64 f64 div-t str i8} \n
+                {(length \"hello\") (parse \"-1.25e2\") (divide 17 5) (find \"tahini\" 104) \n
+                    (format [0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0] 42)}))";
+        let var_type = VarType::Tuple(vec![
+            VarType::Int64,
+            VarType::Float64,
+            VarType::IdentType("div-t".to_string()),
+            VarType::IdentType("str".to_string()),
+            VarType::Int8,
+        ]);
+        assert_eq!(
+            show(&mut interpreter(src), var_type),
+            format!("{{5 -125.0 (div-t 3 2) \"hini\" {}}}", b'5')
+        );
+    }
+
+    #[test]
+    fn test_missing_functions_are_errors() {
+        let src = "(def c (use :header \"stdlib.h\")) \n
+            (def main (fn [] i32 (c/no_such_function 1)))";
+        assert_eq!(
+            interpreter(src).call(ENTRY_POINT, Vec::new()),
+            Err("`no_such_function` is not in the C library or the libraries loaded".to_string())
+        );
+    }
+
+    #[test]
+    fn test_structs_and_stack_arguments() {
+        let Some(path) = library() else {
+            return;
+        };
+        let src = "(def shapes (use :header \"shapes.h\")) \n
+            (type vec2 (struct (:x f64) (:y f64))) \n
+            (type sample (struct (:n i64) (:x f64))) \n
+            (type triple (struct (:a i64) (:b i64) (:c i64))) \n
+            (def scale (fn [(:v vec2) (:k f64)] vec2 (shapes/scale v k))) \n
+            (def sample-of (fn [(:x f64)] sample (shapes/sample_of x))) \n
+            (def shift (fn [(:t triple) (:k i64)] triple (shapes/shift t k))) \n
+            (def weigh (fn [] i64 (shapes/weigh 1 1 1 1 1 1 1 9.5 1))) \n
+            (def squares (fn [(:xs [i64])] i64 (do (shapes/fill xs 4) ($ [3] xs)))) \n
+            (def main (fn [] {vec2 sample triple i64 i64} \n
+                {(scale (vec2 1.5 (- 0.0 2.0)) 2.0) (sample-of 7.0) (shift (triple 1 2 3) 10) \n
+                    (weigh) (squares [0 0 0 0])}))";
+        let mut libraries = Libraries::default();
+        libraries.open(path.to_str().unwrap()).unwrap();
+        let mut interpreter = interpreter(src);
+        interpreter.link(Rc::new(libraries));
+        let var_type = VarType::Tuple(
+            ["vec2", "sample", "triple"]
+                .iter()
+                .map(|name| VarType::IdentType(name.to_string()))
+                .chain([VarType::Int64, VarType::Int64])
+                .collect(),
+        );
+        assert_eq!(
+            show(&mut interpreter, var_type),
+            "{(vec2 3.0 -4.0) (sample 7 3.5) (triple 11 12 13) 45 9}"
+        );
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
@@ -0,0 +1,288 @@
+#[cfg(test)]
+mod tests {
+    use crate::ast::VarType;
+    use crate::codegen::{c, RUNTIME};
+    use crate::interp::value::Value;
+    use crate::interp::{Interpreter, STACK_SIZE};
+    use crate::ir::lower::lower;
+    use crate::lint::ENTRY_POINT;
+    use crate::parser::parser;
+    use crate::transformer::transform;
+    use chumsky::Parser;
+    use std::process::Command;
+
+    fn interpreter(src: &str) -> Interpreter {
+        let module = parser().parse(src).into_output().unwrap();
+        let transformed = transform(&module).unwrap_or_else(|errors| panic!("{:?}", errors));
+        Interpreter::new(transformed).unwrap_or_else(|errors| panic!("{:?}", errors))
+    }
+
+    /// The value `main` of `src` returns.
+    fn run(src: &str) -> Result<Value, String> {
+        interpreter(src).call(ENTRY_POINT, Vec::new())
+    }
+
+    fn int(value: i128, var_type: VarType) -> Value {
+        Value::int(value, &var_type)
+    }
+
+    /// The exit status of `src` compiled with the C backend, or `None` if there is no `cc`.
+    fn compiled_status(name: &str, src: &str) -> Option<i32> {
+        let module = parser().parse(src).into_output().unwrap();
+        let program = lower(&transform(&module).unwrap()).unwrap();
+        let dir =
+            std::env::temp_dir().join(format!("tahini-interp-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (source, runtime, binary) = (dir.join("main.c"), dir.join("rt.c"), dir.join("main"));
+        std::fs::write(&source, c::emit(&program).unwrap()).unwrap();
+        std::fs::write(&runtime, RUNTIME).unwrap();
+        let status = Command::new("cc")
+            .args(["-std=c11", "-DTAHINI_NO_GC"])
+            .arg(&source)
+            .arg(&runtime)
+            .arg("-o")
+            .arg(&binary)
+            .arg("-lm")
+            .status()
+            .ok()?;
+        assert!(status.success(), "`cc` rejected the C of `{}`", name);
+        let output = Command::new(&binary).output().unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        output.status.code()
+    }
+
+    #[test]
+    fn test_functions_and_control_flow() {
+        let src = "(def fib (fn [(:n i64)] i64 (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))) \n
+            (def tally (fn [(:total i64) (:n i64)] i64 \n
+                (do \n
+                    (for (range i n) (do (if (= (% i 2) 0) (continue)) (def total (+ total i)))) \n
+                    (for (range i 10 0 (- 0 3)) (def total (+ total 1))) \n
+                    (for true (if (> total 100) (break) (do (def total (+ total 7))))) \n
+                    total))) \n
+            (def main (fn [] i64 (+ (tally 0 10) (fib 15))))";
+        // 25 odd numbers, 4 steps down, then sevens until above 100.
+        assert_eq!(run(src), Ok(int(106 + 610, VarType::Int64)));
+    }
+
+    #[test]
+    fn test_integers_wrap_at_their_width() {
+        let src = "(def bump (fn [(:x i8)] i8 (+ x 1))) \n
+            (def drop (fn [(:x u8)] u8 (- x 1))) \n
+            (def halve (fn [(:x i32)] i32 (>> x 1))) \n
+            (def main (fn [] {i8 u8 i32 f32} {(bump 127) (drop 0) (halve (- 0 7)) (+ 0.1 0.2)}))";
+        let Value::Tuple(values) = run(src).unwrap() else {
+            panic!("not a tuple");
+        };
+        assert_eq!(values[0], int(-128, VarType::Int8));
+        assert_eq!(values[1], int(255, VarType::UInt8));
+        assert_eq!(values[2], int(-4, VarType::Int32));
+        assert_eq!(
+            values[3],
+            Value::float((0.1f32 + 0.2f32) as f64, &VarType::Float32)
+        );
+    }
+
+    #[test]
+    fn test_closures_share_boxed_locals() {
+        let src = "(def make-counter (fn [] fn [] i64 \n
+                (do (def count 0) (fn [] i64 (do (def count (+ count 1)) count))))) \n
+            (def apply (fn [(:f fn [i64] i64) (:x i64)] i64 (f x))) \n
+            (def double (fn [(:x i64)] i64 (* x 2))) \n
+            (def main (fn [] i64 \n
+                (do \n
+                    (def counter (make-counter)) \n
+                    (counter) \n
+                    (counter) \n
+                    (def n 100) \n
+                    (+ (counter) (apply double 5) (apply (fn [(:x i64)] i64 (+ x n)) 1)))))";
+        assert_eq!(run(src), Ok(int(3 + 10 + 101, VarType::Int64)));
+    }
+
+    #[test]
+    fn test_structs_arrays_and_data() {
+        let src = "(type point (struct (:x f64) (:y f64))) \n
+            (type segment (struct (:from point) (:to point))) \n
+            (type shape (data [:circle f64] [:rect point point])) \n
+            (def first (fn [(:xs [i32])] i32 ($ [0] xs))) \n
+            (def main (fn [] {segment [i32 3] shape i32} \n
+                (do \n
+                    (def s (segment (point 0.0 0.0) (point 1.0 2.0))) \n
+                    ($ :x ($ :to s) 5.0) \n
+                    (def xs [1 2 3]) \n
+                    ($ [1] xs 20) \n
+                    {s xs (shape :circle 1.5) (first xs)})))";
+        let mut interpreter = interpreter(src);
+        let value = interpreter.call(ENTRY_POINT, Vec::new()).unwrap();
+        let var_type = VarType::Tuple(vec![
+            VarType::IdentType("segment".to_string()),
+            VarType::ArraySized(Box::new(VarType::Int32), 3),
+            VarType::IdentType("shape".to_string()),
+            VarType::Int32,
+        ]);
+        assert_eq!(
+            value.show(&var_type, interpreter.aliases()),
+            "{(segment (point 0.0 0.0) (point 5.0 2.0)) [1 20 3] [:circle 1.5] 1}"
+        );
+    }
+
+    #[test]
+    fn test_strings_are_pointers_to_bytes() {
+        let src = "(def greeting \"hi!\") \n
+            (def main (fn [] {str i8 bool} {greeting ($ [1] greeting) (= greeting \"hi!\")}))";
+        let mut interpreter = interpreter(src);
+        let value = interpreter.call(ENTRY_POINT, Vec::new()).unwrap();
+        let var_type = VarType::Tuple(vec![
+            VarType::IdentType("str".to_string()),
+            VarType::Int8,
+            VarType::Bool,
+        ]);
+        assert_eq!(
+            value.show(&var_type, interpreter.aliases()),
+            "{\"hi!\" 105 true}"
+        );
+    }
+
+    #[test]
+    fn test_undefined_behavior_is_an_error() {
+        let divide = "(def main (fn [] i32 (do (def zero 0) (/ 1 zero))))";
+        assert_eq!(run(divide), Err("division by zero".to_string()));
+
+        let index = "(def main (fn [] i32 (do (def xs [1 2 3]) (def i 3) ($ [i] xs))))";
+        assert_eq!(
+            run(index),
+            Err("index 3 is out of bounds for length 3".to_string())
+        );
+
+        let error = on_interpreter_stack(|| run("(def main (fn [] i32 (main)))").err()).unwrap();
+        assert!(
+            error.starts_with("calls nested ") && error.ends_with("while calling `main`"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_runaway_recursion_on_small_stacks() {
+        let src = "(def f (fn [(:n i64)] i64 (f (+ n 1)))) \n
+            (def main (fn [] i64 (f 0)))";
+        for stack_size in [2 << 20, 8 << 20] {
+            let error = std::thread::Builder::new()
+                .stack_size(stack_size)
+                .spawn(move || run(src).err())
+                .unwrap()
+                .join()
+                .unwrap()
+                .unwrap();
+            assert!(error.ends_with("while calling `f`"), "{}", error);
+        }
+    }
+
+    /// Run `f` on a thread with the stack the interpreter is meant to run on.
+    fn on_interpreter_stack<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(f)
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        let src = "(def countdown (fn [(:n i64)] i64 (if (= n 0) 0 (+ 1 (countdown (- n 1)))))) \n
+            (def main (fn [] i64 (countdown 4000)))";
+        let depth = on_interpreter_stack(move || run(src).map(|value| value.as_int()));
+        assert_eq!(depth, Ok(Some(4000)));
+    }
+
+    /// Programs whose interpreted `main` must return what their compiled `main` exits with.
+    const DIFFERENTIAL: &[(&str, &str)] = &[
+        (
+            "arithmetic",
+            "(def main (fn [] i32 (do \n
+                (def a (- 0 17)) \n
+                (def b 5) \n
+                (def x (+ (/ a b) (% a b) (<< b 3) (>> a 2) (& a 255) (| b 64) (^ a b))) \n
+                (def small (fn [(:x u8)] u8 (* x 3))) \n
+                (if (= (small 200) 88) (+ x 1) x))))",
+        ),
+        (
+            "closures",
+            "(def make-counter (fn [] fn [] i64 \n
+                (do (def count 0) (fn [] i64 (do (def count (+ count 1)) count))))) \n
+            (def apply (fn [(:f fn [i64] i64) (:x i64)] i64 (f x))) \n
+            (def main (fn [] i32 (do \n
+                (def c (make-counter)) \n
+                (c) \n
+                (def k 40) \n
+                (if (= (+ (c) (apply (fn [(:x i64)] i64 (+ x k)) 2)) 44) 7 9))))",
+        ),
+        (
+            "memory",
+            "(type point (struct (:x i32) (:y i32))) \n
+            (type shape (data [:circle i32] [:rect point point])) \n
+            (def sum (fn [(:xs [i32 4])] i32 \n
+                (do (def total 0) (for (range x xs) (def total (+ total x))) total))) \n
+            (def main (fn [] i32 (do \n
+                (def p (point 3 4)) \n
+                ($ :y p (* ($ :y p) 10)) \n
+                (def grid [[1 2] [3 4]]) \n
+                ($ [1] ($ [0] grid) 9) \n
+                (def t {1 p}) \n
+                (+ (sum [1 2 3 4]) ($ :y p) ($ [1] ($ [0] grid)) ($ :x ($ [1] t))))))",
+        ),
+        (
+            "globals",
+            "(type shape (data [:circle i32] [:rect i32 i32])) \n
+            (def PAIR {(+ 1 1) [(* 2 2) 5]}) \n
+            (def SHAPES [[:circle 3] [:rect 4 5]]) \n
+            (def main (fn [] i32 (do \n
+                ($ [1] ($ [1] PAIR) 6) \n
+                (+ ($ [0] PAIR) ($ [0] ($ [1] PAIR)) ($ [1] ($ [1] PAIR))))))",
+        ),
+        (
+            "returns",
+            "(type point (struct (:x i32) (:y i32))) \n
+            (def find (fn [(:xs [i32 4]) (:wanted i32)] i32 \n
+              (do \n
+                (def found 0) \n
+                (for (range x xs) \n
+                  (do (if (= x wanted) (return found)) (def found (+ found 1)))) \n
+                (- 0 1)))) \n
+            (def corner (fn [(:far bool)] point \n
+              (do (if far (return (point 30 40))) (return (point 1 2))))) \n
+            (def check (fn [(:n i32)] void (do (if (> n 0) (return)) (return n)))) \n
+            (def main (fn [] i32 (do \n
+                (check 1) \n
+                (def twice (fn [(:x i32)] i32 (do (return (* x 2)) x))) \n
+                (+ (find [4 5 6 7] 6) (find [1 1 1 1] 2) ($ :y (corner true)) \n
+                   ($ :x (corner false)) (twice 5)))))",
+        ),
+        (
+            "loops",
+            "(def walk (fn [(:xs [i32 8]) (:lo i64) (:hi i64) (:step i64)] i32 \n
+              (do \n
+                (def total 0) \n
+                (for (range x :slice xs lo hi) \n
+                  (do (if (< x 0) (continue)) (def total (+ total x)))) \n
+                (for (range i 10 0 (- 0 2)) (def total (+ total 1))) \n
+                (for (range i lo hi step) (def total (+ total 1))) \n
+                (for true (if (> total 100) (break) (do (def total (+ total 3))))) \n
+                total))) \n
+            (def main (fn [] i32 (walk [5 (- 0 1) 7 8 (- 0 2) 10 11 12] 1 7 2)))",
+        ),
+    ];
+
+    #[test]
+    fn test_interpreted_programs_agree_with_compiled_ones() {
+        for (name, src) in DIFFERENTIAL {
+            let interpreted = run(src).unwrap_or_else(|error| panic!("{}: {}", name, error));
+            let Some(status) = compiled_status(name, src) else {
+                return;
+            };
+            let expected = interpreted.as_int().unwrap() as u8 as i32;
+            assert_eq!(status, expected, "`{}` returned {}", name, interpreted);
+        }
+    }
+}
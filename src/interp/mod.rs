@@ -0,0 +1,986 @@
+//! A tree-walking interpreter for checked programs.
+//!
+//! The interpreter evaluates the transformed module, after type checking, monomorphization and
+//! closure conversion, so it runs exactly the functions the backends compile. Every expression
+//! is evaluated with the type lowering gives it ([`crate::ir::lower::expression_types`]):
+//! literals take the width the compiled code gives them, and values keep the representation
+//! of compiled code (see [`value`]). That makes the interpreter the reference semantics that
+//! compiled programs are tested against; where C leaves behavior undefined, such as division
+//! by zero, indexing out of bounds or shifting by the width of a type, it stops with an error.
+//!
+//! Members of imported tahini modules are evaluated by the interpreters of those modules, which
+//! are attached with [`Interpreter::import`]. Functions of C headers are called in the C
+//! library, or the libraries given with [`Interpreter::link`] (see [`ffi`]).
+
+pub mod ffi;
+pub mod value;
+
+mod ffi_test;
+mod interp_test;
+
+use crate::ast::{Literal, Range, Statement, TopLevelDef, TopLevelStatement, VarType};
+use crate::builtins;
+use crate::codegen::unescape;
+use crate::diagnostic::Diagnostic;
+use crate::ir::lower::{self, ExprTypes};
+use crate::ir::Const;
+use crate::transformer::ast::{AccessSegment, Module, TransformedItem, TransformedStmt};
+use crate::typeck::types::Aliases;
+use ffi::Libraries;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{c_int, c_void};
+use std::rc::Rc;
+use value::{cell, out_of_bounds, Cell, Pointer, Value};
+
+/// The stack deep programs are meant to run on. Each call of the program takes several Rust
+/// frames, tens of kilobytes in unoptimized builds, so callers run deep programs on a thread of
+/// this size, as `tahini repl` does. Smaller stacks fail sooner, but with an error.
+pub const STACK_SIZE: usize = 256 << 20;
+
+/// How deep calls may nest when the bounds of the thread's stack can't be read.
+const MAX_DEPTH: usize = 1000;
+
+thread_local! {
+    /// The address of the stack where the outermost [`Interpreter::call`] of the thread started,
+    /// or 0 outside of one. Interpreters of imported modules share it.
+    static STACK_BASE: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    /// The bytes of stack the calls of a program may take below [`STACK_BASE`] before it is
+    /// considered to recurse without end, or 0 when [`MAX_DEPTH`] limits them instead. The rest
+    /// of the thread's stack is left to C functions.
+    static STACK_LIMIT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// The bytes of stack taken since the outermost [`Interpreter::call`], which grows downwards.
+fn stack_used() -> usize {
+    let here = 0u8;
+    STACK_BASE.get().saturating_sub(&here as *const u8 as usize)
+}
+
+/// The lowest address of the current thread's stack, where it runs out.
+fn stack_end() -> Option<usize> {
+    /// Room for a `pthread_attr_t`, which glibc and musl make 56 bytes.
+    type Attr = [u64; 8];
+    extern "C" {
+        fn pthread_self() -> usize;
+        fn pthread_getattr_np(thread: usize, attr: *mut Attr) -> c_int;
+        fn pthread_attr_getstack(
+            attr: *const Attr,
+            addr: *mut *mut c_void,
+            size: *mut usize,
+        ) -> c_int;
+        fn pthread_attr_destroy(attr: *mut Attr) -> c_int;
+    }
+    let mut attr: Attr = [0; 8];
+    let (mut addr, mut size) = (std::ptr::null_mut(), 0);
+    // SAFETY: `attr` is large enough for a `pthread_attr_t`, and is only read after
+    // `pthread_getattr_np` initialized it.
+    unsafe {
+        if pthread_getattr_np(pthread_self(), &mut attr) != 0 {
+            return None;
+        }
+        let found = pthread_attr_getstack(&attr, &mut addr, &mut size);
+        pthread_attr_destroy(&mut attr);
+        (found == 0 && !addr.is_null()).then_some(addr as usize)
+    }
+}
+
+enum Error {
+    Message(String),
+    /// `(break)` on its way out to the innermost loop.
+    Break,
+    /// `(continue)` on its way out to the innermost loop.
+    Continue,
+    /// `(return)` on its way out to the innermost function, with the value it returns.
+    Return(Value),
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Message(message)
+    }
+}
+
+type Eval<T = Value> = Result<T, Error>;
+
+fn fail<T>(message: String) -> Eval<T> {
+    Err(Error::Message(message))
+}
+
+/// Finish one iteration of a loop body; `false` when the body left the loop with `(break)`.
+fn iterate(result: Eval) -> Eval<bool> {
+    match result {
+        Ok(_) | Err(Error::Continue) => Ok(true),
+        Err(Error::Break) => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+pub struct Interpreter {
+    module: Rc<Module>,
+    types: ExprTypes,
+    aliases: Aliases,
+    /// The item of every function of the module.
+    functions: HashMap<String, usize>,
+    /// Functions and globals declared with a type but no value, e.g. C functions.
+    externs: HashMap<String, VarType>,
+    /// Names the C headers are imported under.
+    headers: HashSet<String>,
+    /// The interpreters of the imported tahini modules, by alias.
+    imports: HashMap<String, Rc<RefCell<Interpreter>>>,
+    /// Where the functions of C headers are found.
+    libraries: Rc<Libraries>,
+    globals: HashMap<String, Cell>,
+    /// The storage of every string literal, so that equal literals are the same pointer.
+    strings: HashMap<String, Pointer>,
+    /// Local scopes of the functions being interpreted, innermost call last.
+    frames: Vec<Vec<HashMap<String, Cell>>>,
+}
+
+impl Interpreter {
+    /// Prepare `module` for evaluation and initialize its globals. Fails where lowering the
+    /// module would.
+    pub fn new(module: Module) -> Result<Self, Vec<Diagnostic>> {
+        let module = Rc::new(module);
+        let types = lower::expression_types(&module)?;
+        let mut interpreter = Interpreter {
+            module: module.clone(),
+            types,
+            aliases: Aliases::default(),
+            functions: HashMap::new(),
+            externs: HashMap::new(),
+            headers: HashSet::new(),
+            imports: HashMap::new(),
+            libraries: Rc::new(Libraries::default()),
+            globals: HashMap::new(),
+            strings: HashMap::new(),
+            frames: Vec::new(),
+        };
+
+        for (item, statement) in module.items.iter().enumerate() {
+            match statement {
+                TransformedItem::Orig(TopLevelStatement::TypeAlias(name, var_type)) => {
+                    let types = &mut interpreter.aliases.types;
+                    types.insert(name.clone(), var_type.clone());
+                }
+                TransformedItem::Orig(TopLevelStatement::TopLevelDef(def)) => {
+                    if let TopLevelDef::Typed(var_type) = &def.instruction {
+                        let externs = &mut interpreter.externs;
+                        externs.insert(def.name.clone(), var_type.clone());
+                    }
+                }
+                TransformedItem::Orig(TopLevelStatement::UseHeader(name, _)) => {
+                    interpreter.headers.insert(name.clone());
+                }
+                TransformedItem::Orig(_) => {}
+                TransformedItem::Fn(name, _) => {
+                    interpreter.functions.insert(name.clone(), item);
+                }
+                TransformedItem::Global(..) => {}
+            }
+        }
+
+        // Lowering only accepts constants as the values of globals.
+        for item in module.items.iter() {
+            if let TransformedItem::Global(name, value) = item {
+                let var_type = module.inference.globals.get(name);
+                if let Some((value, _)) = lower::initializer(value, var_type, &interpreter.aliases)
+                {
+                    let value = interpreter.constant(value);
+                    interpreter.globals.insert(name.clone(), cell(value));
+                }
+            }
+        }
+        Ok(interpreter)
+    }
+
+    pub fn aliases(&self) -> &Aliases {
+        &self.aliases
+    }
+
+    /// Evaluate the members `alias/name` of the module with `module`.
+    pub fn import(&mut self, alias: &str, module: Rc<RefCell<Interpreter>>) {
+        self.imports.insert(alias.to_string(), module);
+    }
+
+    /// Look for the functions of C headers in `libraries`.
+    pub fn link(&mut self, libraries: Rc<Libraries>) {
+        self.libraries = libraries;
+    }
+
+    /// The value of the global `name` of the module.
+    pub fn global(&mut self, name: &str) -> Result<Value, String> {
+        self.lookup(name).map_err(|error| match error {
+            Error::Message(message) => message,
+            Error::Break | Error::Continue | Error::Return(_) => {
+                unreachable!("lookups do not jump")
+            }
+        })
+    }
+
+    /// Call the function `name` of the module with `args`. Fails with the first runtime error.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        let here = 0u8;
+        let outermost = STACK_BASE.get() == 0;
+        if outermost {
+            let base = &here as *const u8 as usize;
+            STACK_BASE.set(base);
+            let limit = stack_end().map_or(0, |end| base.saturating_sub(end) / 4 * 3);
+            STACK_LIMIT.set(limit);
+        }
+        let saved = std::mem::take(&mut self.frames);
+        let result = self.call_fn(name, args);
+        self.frames = saved;
+        if outermost {
+            STACK_BASE.set(0);
+        }
+        result.map_err(|error| match error {
+            Error::Message(message) => message,
+            Error::Break => "`(break)` outside of a `for` loop".to_string(),
+            Error::Continue => "`(continue)` outside of a `for` loop".to_string(),
+            Error::Return(_) => unreachable!("functions stop `(return)`"),
+        })
+    }
+
+    fn type_of(&self, statement: &TransformedStmt) -> VarType {
+        self.types.get(statement).cloned().unwrap_or(VarType::Void)
+    }
+
+    fn constant(&mut self, value: Const) -> Value {
+        match value {
+            Const::Int(value, var_type) => {
+                Value::int(value as i128, &self.aliases.normalize(&var_type))
+            }
+            Const::Float(value, var_type) => {
+                Value::float(value, &self.aliases.normalize(&var_type))
+            }
+            Const::Bool(value) => Value::Bool(value),
+            Const::Char(value) => Value::int(value as u32 as i128, &VarType::Int8),
+            Const::String(value) => Value::Pointer(self.string(&value)),
+            Const::Atom(name) => Value::Atom(name),
+            Const::Fn(name) | Const::Callback(name) => Value::Closure(name, None),
+            Const::Tuple(members, var_type) => match self.aliases.normalize(&var_type) {
+                VarType::Struct(_) => Value::Struct(self.constants(members)),
+                _ => Value::Tuple(self.constants(members)),
+            },
+            Const::Array(members, _) => Value::Array(self.constants(members)),
+            Const::Data(tag, members, _) => Value::Data(tag, self.constants(members)),
+            Const::Void => Value::Void,
+        }
+    }
+
+    fn constants(&mut self, values: Vec<Const>) -> Vec<Value> {
+        values
+            .into_iter()
+            .map(|value| self.constant(value))
+            .collect()
+    }
+
+    /// A pointer to the first byte of a NUL-terminated copy of `text`.
+    fn string(&mut self, text: &str) -> Pointer {
+        if let Some(pointer) = self.strings.get(text) {
+            return pointer.clone();
+        }
+        let bytes = unescape(text)
+            .into_iter()
+            .chain([0])
+            .map(|byte| Value::int(byte as i128, &VarType::Int8))
+            .collect();
+        let pointer = Pointer::to(cell(Value::Array(bytes))).member(0);
+        self.strings.insert(text.to_string(), pointer.clone());
+        pointer
+    }
+
+    /// `value` converted where the compiled code converts implicitly: a sized array passed as
+    /// an unsized one decays to a pointer to the first element of a copy.
+    fn convert(&self, value: Value, target: &VarType) -> Value {
+        match (value, self.aliases.normalize(target)) {
+            (array @ Value::Array(_), VarType::ArrayUnsized(_)) => {
+                Value::Pointer(Pointer::to(cell(array)).member(0))
+            }
+            (value, _) => value,
+        }
+    }
+
+    fn convert_all(&self, values: Vec<Value>, types: &[VarType]) -> Vec<Value> {
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| match types.get(i) {
+                Some(var_type) => self.convert(value, var_type),
+                None => value,
+            })
+            .collect()
+    }
+
+    fn scopes(&mut self) -> &mut Vec<HashMap<String, Cell>> {
+        self.frames.last_mut().expect("evaluation runs in a frame")
+    }
+
+    fn lookup_local(&self, name: &str) -> Option<Cell> {
+        let scopes = self.frames.last()?;
+        scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    fn declare(&mut self, name: &str, value: Value) {
+        let scope = self.scopes().last_mut().expect("frames have a scope");
+        scope.insert(name.to_string(), cell(value));
+    }
+
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes().push(HashMap::new());
+        let result = f(self);
+        self.scopes().pop();
+        result
+    }
+
+    fn lookup(&mut self, name: &str) -> Eval {
+        if let Some(local) = self.lookup_local(name) {
+            return Ok(local.borrow().clone());
+        }
+        if self.functions.contains_key(name) {
+            return Ok(Value::Closure(name.to_string(), None));
+        }
+        if let Some(global) = self.globals.get(name) {
+            return Ok(global.borrow().clone());
+        }
+        match self.externs.get(name) {
+            Some(VarType::Fn(..) | VarType::FnWithVarArgs(..)) => {
+                Ok(Value::Closure(name.to_string(), None))
+            }
+            Some(var_type) => {
+                if let Some(member) = self.header_member(name) {
+                    let address = self.symbol(member)?;
+                    return Ok(ffi::load(address, var_type, &self.aliases)?);
+                }
+                match self.imported(name)? {
+                    Some((mut module, member)) => Ok(module.global(member)?),
+                    None => fail(format!("`{}` has no value in this module", name)),
+                }
+            }
+            None => fail(format!("`{}` is not defined", name)),
+        }
+    }
+
+    /// The interpreter of the imported module that `name` is a member of, and the member.
+    fn imported<'a>(
+        &self,
+        name: &'a str,
+    ) -> Eval<Option<(std::cell::RefMut<'_, Interpreter>, &'a str)>> {
+        let Some((alias, member)) = builtins::split_path(name) else {
+            return Ok(None);
+        };
+        let Some(module) = self.imports.get(alias) else {
+            return Ok(None);
+        };
+        match module.try_borrow_mut() {
+            Ok(module) => Ok(Some((module, member))),
+            Err(_) => fail(format!(
+                "`{}` calls back into `{}` while it is running",
+                name, alias
+            )),
+        }
+    }
+
+    fn eval_all(&mut self, statements: &[TransformedStmt]) -> Eval<Vec<Value>> {
+        statements.iter().map(|s| self.eval(s)).collect()
+    }
+
+    fn eval_bool(&mut self, statement: &TransformedStmt) -> Eval<bool> {
+        match self.eval(statement)? {
+            Value::Bool(value) => Ok(value),
+            other => fail(format!("expected a `bool`, found `{}`", other)),
+        }
+    }
+
+    fn eval_int(&mut self, statement: &TransformedStmt) -> Eval<i128> {
+        let value = self.eval(statement)?;
+        match value.as_int() {
+            Some(value) => Ok(value),
+            None => fail(format!("expected an integer, found `{}`", value)),
+        }
+    }
+
+    fn eval_pointer(&mut self, statement: &TransformedStmt) -> Eval<Pointer> {
+        match self.eval(statement)? {
+            Value::Pointer(pointer) => Ok(pointer),
+            other => fail(format!("expected a pointer, found `{}`", other)),
+        }
+    }
+
+    fn eval(&mut self, statement: &TransformedStmt) -> Eval {
+        match statement {
+            TransformedStmt::Orig(Statement::Ident(name)) => self.lookup(name),
+            TransformedStmt::Orig(Statement::Literal(literal)) => {
+                Ok(self.eval_literal(statement, literal))
+            }
+            TransformedStmt::Orig(other) => {
+                unreachable!("`{:?}` is lowered by the transformer", other)
+            }
+            TransformedStmt::DoBlock(statements) => self.eval_block(statements),
+            TransformedStmt::Call(name, args) => self.eval_call(statement, name, args),
+            TransformedStmt::DefVar(def) => self.eval_def(&def.name, &def.instruction),
+            TransformedStmt::Declare(name, var_type) => {
+                let value = Value::zero(var_type, &self.aliases);
+                self.declare(name, value);
+                Ok(Value::Void)
+            }
+            TransformedStmt::If(condition, then) => {
+                if self.eval_bool(condition)? {
+                    self.eval_scoped(then)?;
+                }
+                Ok(Value::Void)
+            }
+            TransformedStmt::IfElse(condition, then, otherwise) => {
+                let branch = if self.eval_bool(condition)? {
+                    then
+                } else {
+                    otherwise
+                };
+                let value = self.eval_scoped(branch)?;
+                match self.types.get(statement) {
+                    Some(VarType::Void) => Ok(Value::Void),
+                    _ => Ok(value),
+                }
+            }
+            TransformedStmt::For(condition, body) => {
+                while self.eval_bool(condition)? {
+                    if !iterate(self.eval_scoped(body))? {
+                        break;
+                    }
+                }
+                Ok(Value::Void)
+            }
+            TransformedStmt::ForRange(name, range, body) => {
+                self.eval_for_range(name, range, body)?;
+                Ok(Value::Void)
+            }
+            TransformedStmt::Break => Err(Error::Break),
+            TransformedStmt::Continue => Err(Error::Continue),
+            TransformedStmt::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Void,
+                };
+                Err(Error::Return(value))
+            }
+            TransformedStmt::Tuple(items) | TransformedStmt::Array(items) => {
+                self.eval_aggregate(statement, items)
+            }
+            TransformedStmt::Data(tag, args) => self.eval_data(statement, tag, args),
+            TransformedStmt::Fn(_) => unreachable!("lambda lifting leaves no nested fn literals"),
+            TransformedStmt::ChainAccess { root, segments } => {
+                let (address, _) = self.chain_address(root, segments)?;
+                Ok(address.load()?)
+            }
+            TransformedStmt::ChainAssign {
+                root,
+                segments,
+                value,
+            } => self.eval_assign(root, segments, value),
+            TransformedStmt::MakeClosure { fn_name, env } => self.eval_closure(fn_name, env),
+            TransformedStmt::BoxNew(value) => self.eval_box(statement, value),
+            TransformedStmt::BoxGet(cell) => Ok(self.eval_pointer(cell)?.load()?),
+            TransformedStmt::BoxSet(cell, value) => {
+                let cell = self.eval_pointer(cell)?;
+                let value = self.eval(value)?;
+                cell.store(value)?;
+                Ok(Value::Void)
+            }
+        }
+    }
+
+    fn eval_literal(&mut self, statement: &TransformedStmt, literal: &Literal) -> Value {
+        let var_type = self.types.get(statement).cloned();
+        match lower::constant(literal, var_type.as_ref()) {
+            Some((value, _)) => self.constant(value),
+            None => unreachable!("only scalar literals stay original"),
+        }
+    }
+
+    fn eval_block(&mut self, statements: &[TransformedStmt]) -> Eval {
+        self.scoped(|interpreter| {
+            let mut last = Value::Void;
+            for statement in statements {
+                last = interpreter.eval(statement)?;
+            }
+            Ok(last)
+        })
+    }
+
+    fn eval_scoped(&mut self, statement: &TransformedStmt) -> Eval {
+        self.scoped(|interpreter| interpreter.eval(statement))
+    }
+
+    /// A `def` assigns the visible local of its name or declares a new one.
+    fn eval_def(&mut self, name: &str, value: &TransformedStmt) -> Eval {
+        let value = self.eval(value)?;
+        match self.lookup_local(name) {
+            Some(local) => *local.borrow_mut() = value,
+            None => self.declare(name, value),
+        }
+        Ok(Value::Void)
+    }
+
+    fn eval_aggregate(&mut self, statement: &TransformedStmt, items: &[TransformedStmt]) -> Eval {
+        let values = self.eval_all(items)?;
+        Ok(match self.aliases.normalize(&self.type_of(statement)) {
+            VarType::Tuple(types) => Value::Tuple(self.convert_all(values, &types)),
+            VarType::ArraySized(element, length) => {
+                Value::Array(self.convert_all(values, &vec![*element; length]))
+            }
+            _ if matches!(statement, TransformedStmt::Tuple(_)) => Value::Tuple(values),
+            _ => Value::Array(values),
+        })
+    }
+
+    fn eval_assign(
+        &mut self,
+        root: &TransformedStmt,
+        segments: &[AccessSegment<TransformedStmt>],
+        value: &TransformedStmt,
+    ) -> Eval {
+        let (address, var_type) = self.chain_address(root, segments)?;
+        let value = self.eval(value)?;
+        address.store(self.convert(value, &var_type))?;
+        Ok(Value::Void)
+    }
+
+    /// A closure of the lifted function `name` with a heap copy of its environment.
+    fn eval_closure(&mut self, name: &str, env: &[TransformedStmt]) -> Eval {
+        let values = self.eval_all(env)?;
+        let fields = self
+            .fn_params(name)
+            .and_then(|params| params.first().cloned())
+            .and_then(|env| self.aliases.struct_fields(&env))
+            .unwrap_or_default();
+        let types: Vec<_> = fields.into_iter().map(|(_, t)| t).collect();
+        let env = cell(Value::Struct(self.convert_all(values, &types)));
+        Ok(Value::Closure(name.to_string(), Some(Pointer::to(env))))
+    }
+
+    fn eval_box(&mut self, statement: &TransformedStmt, value: &TransformedStmt) -> Eval {
+        let value = self.eval(value)?;
+        let value = match self.aliases.normalize(&self.type_of(statement)) {
+            VarType::Ptr(pointee) => self.convert(value, &pointee),
+            _ => value,
+        };
+        Ok(Value::Pointer(Pointer::to(cell(value))))
+    }
+
+    /// The parameter types of the function of the module called `name`.
+    fn fn_params(&self, name: &str) -> Option<Vec<VarType>> {
+        match &self.module.items[*self.functions.get(name)?] {
+            TransformedItem::Fn(_, fn_def) => Some(
+                fn_def
+                    .parameters
+                    .iter()
+                    .map(|(_, var_type)| var_type.clone())
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    fn eval_call(
+        &mut self,
+        statement: &TransformedStmt,
+        name: &str,
+        args: &[TransformedStmt],
+    ) -> Eval {
+        if let Some(local) = self.lookup_local(name) {
+            let closure = local.borrow().clone();
+            let values = self.eval_all(args)?;
+            return self.call_closure(closure, values);
+        }
+
+        if self.functions.contains_key(name) || self.externs.contains_key(name) {
+            let values = self.eval_all(args)?;
+            return self.call_fn(name, values);
+        }
+
+        if let Some(alias) = self.aliases.types.get(name).cloned() {
+            if let (
+                VarType::Data(_),
+                Some(TransformedStmt::Orig(Statement::Literal(Literal::Atom(tag)))),
+            ) = (&alias, args.first())
+            {
+                return self.eval_data(statement, tag, &args[1..]);
+            }
+            let members = self.eval_all(args)?;
+            return Ok(match self.aliases.normalize(&alias) {
+                VarType::Struct(fields) => {
+                    let types: Vec<_> = fields.into_iter().map(|(_, t)| t).collect();
+                    Value::Struct(self.convert_all(members, &types))
+                }
+                VarType::Tuple(types) => Value::Tuple(self.convert_all(members, &types)),
+                VarType::ArraySized(element, length) => {
+                    Value::Array(self.convert_all(members, &vec![*element; length]))
+                }
+                other => {
+                    let member = members.into_iter().next().unwrap_or(Value::Void);
+                    self.convert(member, &other)
+                }
+            });
+        }
+
+        if builtins::is_builtin_fn(name) {
+            return self.eval_builtin(name, args);
+        }
+
+        if self.header_member(name).is_some() {
+            let values = self.eval_all(args)?;
+            return self.call_c(name, values, self.type_of(statement));
+        }
+        fail(format!("`{}` is not defined", name))
+    }
+
+    /// The member `name` names in an imported C header.
+    fn header_member<'a>(&self, name: &'a str) -> Option<&'a str> {
+        let (module, member) = builtins::split_path(name)?;
+        self.headers.contains(module).then_some(member)
+    }
+
+    fn symbol(&self, member: &str) -> Eval<usize> {
+        match self.libraries.symbol(member) {
+            Some(address) => Ok(address),
+            None => fail(format!(
+                "`{}` is not in the C library or the libraries loaded",
+                member
+            )),
+        }
+    }
+
+    /// Call the function `name` of a C header. Without a declaration of it, the arguments are
+    /// passed with C's default argument promotions and the result is read as `ret`, the type
+    /// the call is used as.
+    fn call_c(&mut self, name: &str, args: Vec<Value>, ret: VarType) -> Eval {
+        let (params, ret) = match self.externs.get(name) {
+            Some(VarType::Fn(params, ret) | VarType::FnWithVarArgs(params, ret)) => {
+                (params.clone(), (**ret).clone())
+            }
+            _ => (Vec::new(), ret),
+        };
+        let args: Vec<Value> = args
+            .into_iter()
+            .enumerate()
+            .map(|(i, arg)| match params.get(i) {
+                Some(param) => self.convert(arg, param),
+                None => value::promote(arg),
+            })
+            .collect();
+        let member = self
+            .header_member(name)
+            .expect("C functions are header members");
+        let address = self.symbol(member)?;
+        Ok(ffi::call(address, &args, &ret, &self.aliases)?)
+    }
+
+    fn call_closure(&mut self, closure: Value, mut args: Vec<Value>) -> Eval {
+        match closure {
+            Value::Closure(name, None) => self.call_fn(&name, args),
+            Value::Closure(name, Some(env)) => {
+                args.insert(0, env.load()?);
+                self.call_fn(&name, args)
+            }
+            other => fail(format!("`{}` cannot be called", other)),
+        }
+    }
+
+    fn call_fn(&mut self, name: &str, args: Vec<Value>) -> Eval {
+        let Some(&item) = self.functions.get(name) else {
+            if let Some((mut module, member)) = self.imported(name)? {
+                return Ok(module.call(member, args)?);
+            }
+            if self.header_member(name).is_some() {
+                return self.call_c(name, args, VarType::Void);
+            }
+            return fail(format!(
+                "`{}` has no definition in this module, so the interpreter cannot call it",
+                name
+            ));
+        };
+        let exhausted = match STACK_LIMIT.get() {
+            0 => self.frames.len() >= MAX_DEPTH,
+            limit => stack_used() > limit,
+        };
+        if exhausted {
+            return fail(format!(
+                "calls nested {} deep exhaust the stack while calling `{}`",
+                self.frames.len(),
+                name
+            ));
+        }
+        let module = self.module.clone();
+        let TransformedItem::Fn(_, fn_def) = &module.items[item] else {
+            unreachable!("functions are `Fn` items")
+        };
+        if args.len() != fn_def.parameters.len() {
+            return fail(format!(
+                "`{}` takes {} arguments, found {}",
+                name,
+                fn_def.parameters.len(),
+                args.len()
+            ));
+        }
+
+        let mut scope = HashMap::new();
+        for ((parameter, var_type), value) in fn_def.parameters.iter().zip(args) {
+            scope.insert(parameter.clone(), cell(self.convert(value, var_type)));
+        }
+        self.frames.push(vec![scope]);
+        let result = self.eval(&fn_def.statement);
+        self.frames.pop();
+
+        let value = match result {
+            Err(Error::Return(value)) => value,
+            result => result?,
+        };
+        match fn_def.return_type {
+            VarType::Void => Ok(Value::Void),
+            _ => Ok(value),
+        }
+    }
+
+    fn eval_builtin(&mut self, name: &str, args: &[TransformedStmt]) -> Eval {
+        match name {
+            "!" | "not" => {
+                let [arg] = args else {
+                    return fail(format!("`{}` takes 1 operand", name));
+                };
+                let operand = self.eval(arg)?;
+                return Ok(value::unary(name, operand)?);
+            }
+            "&&" | "and" | "||" | "or" => {
+                // `&&` stops at the first `false`, `||` at the first `true`.
+                let all = name == "&&" || name == "and";
+                for arg in args {
+                    if self.eval_bool(arg)? != all {
+                        return Ok(Value::Bool(!all));
+                    }
+                }
+                return Ok(Value::Bool(all));
+            }
+            _ => {}
+        }
+
+        // Literals have no effects, so evaluating them after the other operands, like
+        // lowering does, keeps the order of effects.
+        let is_literal = |s: &TransformedStmt| {
+            matches!(
+                s,
+                TransformedStmt::Orig(Statement::Literal(Literal::Int(_) | Literal::Float(_)))
+            )
+        };
+        let mut values = vec![None; args.len()];
+        for pass in [false, true] {
+            for (i, arg) in args.iter().enumerate() {
+                if is_literal(arg) == pass {
+                    values[i] = Some(self.eval(arg)?);
+                }
+            }
+        }
+        let values: Vec<Value> = values.into_iter().flatten().collect();
+
+        if builtins::COMPARISON.contains(&name) {
+            // `(< a b c)` holds if every adjacent pair does.
+            let mut holds = true;
+            for pair in values.windows(2) {
+                let result = value::binary(name, pair[0].clone(), pair[1].clone())?;
+                holds &= result == Value::Bool(true);
+            }
+            return Ok(Value::Bool(holds));
+        }
+
+        let mut values = values.into_iter();
+        let Some(first) = values.next() else {
+            return Ok(Value::Void);
+        };
+        if args.len() == 1 {
+            return Ok(value::unary(name, first)?);
+        }
+        Ok(values.try_fold(first, |left, right| value::binary(name, left, right))?)
+    }
+
+    fn eval_data(
+        &mut self,
+        statement: &TransformedStmt,
+        tag: &str,
+        args: &[TransformedStmt],
+    ) -> Eval {
+        let members = self.eval_all(args)?;
+        let fields = self
+            .aliases
+            .data_variants(&self.type_of(statement))
+            .and_then(|variants| variants.into_iter().find(|(t, _)| t == tag))
+            .map(|(_, fields)| fields)
+            .unwrap_or_default();
+        Ok(Value::Data(
+            tag.to_string(),
+            self.convert_all(members, &fields),
+        ))
+    }
+
+    /// `(for (range x ...) ...)`: the parts of the range are evaluated once, and every
+    /// iteration binds a fresh `x` to the counter or to the element it indexes.
+    fn eval_for_range(
+        &mut self,
+        name: &str,
+        range: &Range<TransformedStmt>,
+        body: &TransformedStmt,
+    ) -> Eval<()> {
+        let (start, end, step, elements) = match range {
+            Range::Each(range) => match self.eval(range)? {
+                Value::Array(items) => {
+                    let length = items.len() as i128;
+                    let base = Pointer::to(cell(Value::Array(items))).member(0);
+                    let index = |n| Value::int(n, &VarType::Int64);
+                    (index(0), index(length), None, Some(base))
+                }
+                Value::Int(end, var_type) => (
+                    Value::Int(0, var_type.clone()),
+                    Value::Int(end, var_type),
+                    None,
+                    None,
+                ),
+                other => return fail(format!("cannot iterate over `{}`", other)),
+            },
+            Range::Bounds(start, end, step) => {
+                let start = self.eval(start)?;
+                let end = self.eval(end)?;
+                let step = match step {
+                    Some(step) => Some(self.eval(step)?),
+                    None => None,
+                };
+                (start, end, step, None)
+            }
+            Range::Slice(array, start, end) => {
+                let base = match self.eval(array)? {
+                    Value::Pointer(pointer) => pointer,
+                    array @ Value::Array(_) => Pointer::to(cell(array)).member(0),
+                    other => return fail(format!("cannot slice `{}`", other)),
+                };
+                let start = self.eval(start)?;
+                let end = self.eval(end)?;
+                (start, end, None, Some(base))
+            }
+        };
+
+        let step = match step {
+            Some(step) => step,
+            None => match &start {
+                Value::Float(_, var_type) => Value::float(1.0, var_type),
+                Value::Int(_, var_type) => Value::int(1, var_type),
+                other => return fail(format!("cannot count from `{}`", other)),
+            },
+        };
+        let zero = match &step {
+            Value::Float(_, var_type) => Value::float(0.0, var_type),
+            Value::Int(_, var_type) => Value::int(0, var_type),
+            other => return fail(format!("cannot count by `{}`", other)),
+        };
+        let holds = |op: &str, left: &Value, right: &Value| {
+            value::binary(op, left.clone(), right.clone()).map(|v| v == Value::Bool(true))
+        };
+
+        let mut counter = start;
+        loop {
+            // Up to `end` for a positive step and down to it for a negative one; a zero step
+            // never iterates.
+            let rising = holds(">", &step, &zero)? && holds("<", &counter, &end)?;
+            let falling = holds("<", &step, &zero)? && holds(">", &counter, &end)?;
+            if !rising && !falling {
+                return Ok(());
+            }
+            let item = match &elements {
+                Some(base) => {
+                    let index = counter.as_int().unwrap_or_default();
+                    base.offset(index)?.load()?
+                }
+                None => counter.clone(),
+            };
+            let result = self.scoped(|interpreter| {
+                interpreter.declare(name, item);
+                interpreter.eval(body)
+            });
+            if !iterate(result)? {
+                return Ok(());
+            }
+            counter = value::binary("+", counter, step.clone())?;
+        }
+    }
+
+    /// The memory a `$` chain designates and the type stored there. Fields of a pointer, and
+    /// elements of a pointer or an unsized array, are reached through the pointer.
+    fn chain_address(
+        &mut self,
+        root: &TransformedStmt,
+        segments: &[AccessSegment<TransformedStmt>],
+    ) -> Eval<(Pointer, VarType)> {
+        let mut address = match root {
+            TransformedStmt::Orig(Statement::Ident(name)) if self.lookup_local(name).is_some() => {
+                Pointer::to(self.lookup_local(name).unwrap())
+            }
+            TransformedStmt::Orig(Statement::Ident(name)) if self.globals.contains_key(name) => {
+                Pointer::to(self.globals[name].clone())
+            }
+            TransformedStmt::BoxGet(cell) => self.eval_pointer(cell)?,
+            other => Pointer::to(cell(self.eval(other)?)),
+        };
+        let mut var_type = self.type_of(root);
+
+        for segment in segments {
+            let mut current = self.aliases.normalize(&var_type);
+            let through_pointer = matches!(
+                (&current, segment),
+                (VarType::Ptr(_), _) | (VarType::ArrayUnsized(_), AccessSegment::Index(_))
+            );
+            if through_pointer {
+                address = match address.load()? {
+                    Value::Pointer(pointer) => pointer,
+                    other => return fail(format!("expected a pointer, found `{}`", other)),
+                };
+            }
+
+            var_type = match segment {
+                AccessSegment::Field(field) => {
+                    if let VarType::Ptr(inner) = &current {
+                        current = self.aliases.normalize(inner);
+                    }
+                    let VarType::Struct(fields) = current else {
+                        unreachable!("`{}` has no fields", current)
+                    };
+                    let position = fields.iter().position(|(name, _)| name == field).unwrap();
+                    address = address.member(position);
+                    fields[position].1.clone()
+                }
+                AccessSegment::Index(index) => {
+                    let position = self.eval_int(index)?;
+                    match current {
+                        VarType::ArrayUnsized(element) | VarType::Ptr(element) => {
+                            address = address.offset(position)?;
+                            *element
+                        }
+                        VarType::ArraySized(element, length) => {
+                            if !(0..length as i128).contains(&position) {
+                                return fail(out_of_bounds(position, length));
+                            }
+                            address = address.member(position as usize);
+                            *element
+                        }
+                        VarType::Tuple(types) => {
+                            address = address.member(position as usize);
+                            types[position as usize].clone()
+                        }
+                        other => unreachable!("`{}` cannot be indexed", other),
+                    }
+                }
+            };
+        }
+        Ok((address, var_type))
+    }
+}
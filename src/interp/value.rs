@@ -0,0 +1,525 @@
+//! Values of the interpreter and the builtin operators on them.
+//!
+//! Values have the shape of their compiled representation, so that interpreted and compiled
+//! programs agree on results: integers carry their type and wrap around at its width, `f16`
+//! and `f32` results are rounded to their precision, tuples and structs hold their members in
+//! layout order, and a `data` value is the tag of its variant and that variant's members.
+//! `str`, unsized arrays, boxes and other pointers point into cells of memory, and a closure is
+//! a function and a pointer to its environment.
+//!
+//! `f128` is computed with the precision of `f64`.
+
+use super::ffi;
+use crate::ast::VarType;
+use crate::builtins;
+use crate::typeck::types::{is_float, str_type, Aliases};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// A memory cell: a local, a global, a heap box or the storage of a string literal.
+pub type Cell = Rc<RefCell<Value>>;
+
+pub fn cell(value: Value) -> Cell {
+    Rc::new(RefCell::new(value))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// The value of a `void` expression, and of memory that was declared but not assigned.
+    Void,
+    Bool(bool),
+    /// The bits of an integer of the given type, zero-extended from its width.
+    Int(u128, VarType),
+    /// A float of the given type, rounded to its precision.
+    Float(f64, VarType),
+    Atom(String),
+    Tuple(Vec<Value>),
+    /// The members of a struct, in the order of its fields.
+    Struct(Vec<Value>),
+    Array(Vec<Value>),
+    /// The tag of a `data` variant and its members.
+    Data(String, Vec<Value>),
+    Pointer(Pointer),
+    /// A function and the environment of a lifted lambda; top-level functions have none.
+    Closure(String, Option<Pointer>),
+}
+
+#[derive(Clone, Debug)]
+pub enum Pointer {
+    Null,
+    /// The member of the value in a cell reached by indexing members with `path`, from the
+    /// outside in. Pointer arithmetic moves the last index within its array.
+    Cell(Cell, Vec<usize>),
+    /// An address in memory of C, which a C function returned. The interpreter passes it back
+    /// to C but does not read or write through it.
+    Foreign(usize),
+}
+
+/// Pointers are equal if they point to the same member of the same cell.
+impl PartialEq for Pointer {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Pointer::Null, Pointer::Null) => true,
+            (Pointer::Foreign(a), Pointer::Foreign(b)) => a == b,
+            (Pointer::Cell(a, path_a), Pointer::Cell(b, path_b)) => {
+                Rc::ptr_eq(a, b) && path_a == path_b
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Pointer {
+    /// A pointer to the whole value of `cell`.
+    pub fn to(cell: Cell) -> Self {
+        Pointer::Cell(cell, Vec::new())
+    }
+
+    /// The pointer `count` elements after this one.
+    pub fn offset(&self, count: i128) -> Result<Pointer, String> {
+        if count == 0 {
+            return Ok(self.clone());
+        }
+        let (cell, path) = match self {
+            Pointer::Null => return Err("arithmetic on a null pointer".to_string()),
+            Pointer::Foreign(_) => return Err(foreign(self)),
+            Pointer::Cell(cell, path) => (cell, path),
+        };
+        let Some((last, parent)) = path.split_last() else {
+            return Err("pointer arithmetic outside of an array".to_string());
+        };
+        let length = match member(&cell.borrow(), parent)? {
+            Value::Array(items) => items.len() as i128,
+            _ => return Err("pointer arithmetic outside of an array".to_string()),
+        };
+        let index = *last as i128 + count;
+        if !(0..=length).contains(&index) {
+            return Err(format!(
+                "pointer moved to element {} of an array of {} elements",
+                index, length
+            ));
+        }
+        let mut path = parent.to_vec();
+        path.push(index as usize);
+        Ok(Pointer::Cell(cell.clone(), path))
+    }
+
+    /// The pointer to member `index` of the value this one points to.
+    pub fn member(&self, index: usize) -> Pointer {
+        match self {
+            Pointer::Null | Pointer::Foreign(_) => self.clone(),
+            Pointer::Cell(cell, path) => {
+                let mut path = path.clone();
+                path.push(index);
+                Pointer::Cell(cell.clone(), path)
+            }
+        }
+    }
+
+    pub fn load(&self) -> Result<Value, String> {
+        match self {
+            Pointer::Null => Err("null pointer dereference".to_string()),
+            Pointer::Foreign(_) => Err(foreign(self)),
+            Pointer::Cell(cell, path) => member(&cell.borrow(), path).cloned(),
+        }
+    }
+
+    pub fn store(&self, value: Value) -> Result<(), String> {
+        match self {
+            Pointer::Null => Err("null pointer dereference".to_string()),
+            Pointer::Foreign(_) => Err(foreign(self)),
+            Pointer::Cell(cell, path) => {
+                *member_mut(&mut cell.borrow_mut(), path)? = value;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn foreign(pointer: &Pointer) -> String {
+    match pointer {
+        Pointer::Foreign(address) => format!(
+            "{:#x} points to memory of C, which the interpreter does not access",
+            address
+        ),
+        _ => unreachable!("only foreign pointers point to memory of C"),
+    }
+}
+
+fn members(value: &Value) -> Option<&Vec<Value>> {
+    match value {
+        Value::Tuple(items) | Value::Struct(items) | Value::Array(items) => Some(items),
+        _ => None,
+    }
+}
+
+fn member<'v>(mut value: &'v Value, path: &[usize]) -> Result<&'v Value, String> {
+    for &index in path {
+        let items = members(value).ok_or_else(|| format!("`{}` has no members", value))?;
+        value = items
+            .get(index)
+            .ok_or_else(|| out_of_bounds(index, items.len()))?;
+    }
+    Ok(value)
+}
+
+fn member_mut<'v>(mut value: &'v mut Value, path: &[usize]) -> Result<&'v mut Value, String> {
+    for &index in path {
+        let items = match value {
+            Value::Tuple(items) | Value::Struct(items) | Value::Array(items) => items,
+            other => return Err(format!("`{}` has no members", other)),
+        };
+        let length = items.len();
+        value = items
+            .get_mut(index)
+            .ok_or_else(|| out_of_bounds(index, length))?;
+    }
+    Ok(value)
+}
+
+pub fn out_of_bounds(index: impl fmt::Display, length: usize) -> String {
+    format!("index {} is out of bounds for length {}", index, length)
+}
+
+/// The width in bits and the signedness of an integer type.
+fn int_kind(var_type: &VarType) -> Option<(u32, bool)> {
+    Some(match var_type {
+        VarType::Int8 => (8, true),
+        VarType::Int16 => (16, true),
+        VarType::Int32 => (32, true),
+        VarType::Int64 => (64, true),
+        VarType::Int128 => (128, true),
+        VarType::UInt8 => (8, false),
+        VarType::UInt16 => (16, false),
+        VarType::UInt32 => (32, false),
+        VarType::UInt64 => (64, false),
+        VarType::UInt128 => (128, false),
+        _ => return None,
+    })
+}
+
+fn truncate(bits: u128, width: u32) -> u128 {
+    match width {
+        128 => bits,
+        width => bits & ((1 << width) - 1),
+    }
+}
+
+/// The value of an integer's bits, sign-extended for signed types.
+fn extend(bits: u128, var_type: &VarType) -> i128 {
+    match int_kind(var_type) {
+        Some((width, true)) if width < 128 => {
+            let shift = 128 - width;
+            ((bits << shift) as i128) >> shift
+        }
+        _ => bits as i128,
+    }
+}
+
+/// Round `value` to the precision of `f16`, to nearest with ties to even.
+fn round_half(value: f64) -> f64 {
+    if !value.is_finite() || value == 0.0 {
+        return value;
+    }
+    if value.abs() >= 65520.0 {
+        return f64::INFINITY.copysign(value);
+    }
+    // Halves have 10 fraction bits, and subnormals are multiples of 2^-24.
+    let exponent = ((value.abs().to_bits() >> 52) as i32 - 1023).max(-14);
+    let quantum = 2f64.powi(exponent - 10);
+    (value / quantum).round_ties_even() * quantum
+}
+
+impl Value {
+    /// The integer `value` of type `var_type`, wrapped around at its width.
+    pub fn int(value: i128, var_type: &VarType) -> Value {
+        let (width, _) = int_kind(var_type).unwrap_or((128, true));
+        Value::Int(truncate(value as u128, width), var_type.clone())
+    }
+
+    /// The float `value` of type `var_type`, rounded to its precision.
+    pub fn float(value: f64, var_type: &VarType) -> Value {
+        let value = match var_type {
+            VarType::Float16 => round_half(value),
+            VarType::Float32 => value as f32 as f64,
+            _ => value,
+        };
+        Value::Float(value, var_type.clone())
+    }
+
+    /// The value of an integer, sign-extended for signed types.
+    pub fn as_int(&self) -> Option<i128> {
+        match self {
+            Value::Int(bits, var_type) => Some(extend(*bits, var_type)),
+            _ => None,
+        }
+    }
+
+    /// The value of a declared local of type `var_type` before it is assigned: zero where C
+    /// has one, `Void` for members the checker does not let the program read before writing.
+    pub fn zero(var_type: &VarType, aliases: &Aliases) -> Value {
+        match aliases.normalize(var_type) {
+            VarType::Bool => Value::Bool(false),
+            t if int_kind(&t).is_some() => Value::Int(0, t),
+            t if is_float(&t) => Value::Float(0.0, t),
+            VarType::Ptr(_) | VarType::ArrayUnsized(_) => Value::Pointer(Pointer::Null),
+            VarType::Tuple(members) => {
+                Value::Tuple(members.iter().map(|t| Value::zero(t, aliases)).collect())
+            }
+            VarType::Struct(fields) => Value::Struct(
+                fields
+                    .iter()
+                    .map(|(_, t)| Value::zero(t, aliases))
+                    .collect(),
+            ),
+            VarType::ArraySized(element, length) => {
+                Value::Array(vec![Value::zero(&element, aliases); length])
+            }
+            _ => Value::Void,
+        }
+    }
+
+    fn as_bool(&self, op: &str) -> Result<bool, String> {
+        match self {
+            Value::Bool(value) => Ok(*value),
+            other => Err(format!("`{}` expects a `bool`, found `{}`", op, other)),
+        }
+    }
+
+    /// Write the value for `var_type`, with the strings that `str` values point to and the
+    /// names of struct types.
+    pub fn show(&self, var_type: &VarType, aliases: &Aliases) -> String {
+        let all = |values: &[Value], types: &[VarType]| -> Vec<String> {
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| match types.get(i) {
+                    Some(var_type) => value.show(var_type, aliases),
+                    None => value.to_string(),
+                })
+                .collect()
+        };
+        match (self, aliases.normalize(var_type)) {
+            (Value::Pointer(pointer), _) if *var_type == str_type() => match c_string(pointer) {
+                Ok(string) => format!("{:?}", string),
+                Err(_) => self.to_string(),
+            },
+            (Value::Tuple(values), VarType::Tuple(types)) => {
+                format!("{{{}}}", all(values, &types).join(" "))
+            }
+            (Value::Struct(values), VarType::Struct(fields)) => {
+                let types: Vec<_> = fields.into_iter().map(|(_, t)| t).collect();
+                let name = match var_type {
+                    VarType::IdentType(name) => name.clone(),
+                    _ => "struct".to_string(),
+                };
+                let mut parts = vec![name];
+                parts.extend(all(values, &types));
+                format!("({})", parts.join(" "))
+            }
+            (Value::Array(values), VarType::ArraySized(element, _)) => {
+                let types = vec![*element; values.len()];
+                format!("[{}]", all(values, &types).join(" "))
+            }
+            (Value::Data(tag, values), VarType::Data(variants)) => {
+                let types = variants
+                    .into_iter()
+                    .find(|(t, _)| t == tag)
+                    .map(|(_, types)| types)
+                    .unwrap_or_default();
+                let mut parts = vec![format!(":{}", tag)];
+                parts.extend(all(values, &types));
+                format!("[{}]", parts.join(" "))
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// `value` with C's default argument promotions, which apply to the variadic arguments of C
+/// functions: `bool` and integers narrower than `int` become `i32`, and `f32` becomes `f64`.
+pub fn promote(value: Value) -> Value {
+    match value {
+        Value::Bool(flag) => Value::int(flag as i128, &VarType::Int32),
+        Value::Int(bits, var_type) if int_kind(&var_type).is_some_and(|(width, _)| width < 32) => {
+            Value::int(extend(bits, &var_type), &VarType::Int32)
+        }
+        Value::Float(value, VarType::Float32) => Value::Float(value, VarType::Float64),
+        other => other,
+    }
+}
+
+/// The bytes from `pointer` up to the first NUL, as text.
+pub fn c_string(pointer: &Pointer) -> Result<String, String> {
+    if let Pointer::Foreign(address) = pointer {
+        return Ok(String::from_utf8_lossy(&ffi::c_string(*address)).into_owned());
+    }
+    let mut bytes = Vec::new();
+    let mut current = pointer.clone();
+    loop {
+        match current.load()? {
+            Value::Int(0, _) => break,
+            Value::Int(byte, _) => bytes.push(byte as u8),
+            other => return Err(format!("`{}` is not a character", other)),
+        }
+        current = current.offset(1)?;
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn write_values(f: &mut fmt::Formatter<'_>, values: &[Value]) -> fmt::Result {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", value)?;
+    }
+    Ok(())
+}
+
+/// Prints values in the syntax of the literals they could come from; pointers and closures,
+/// which have none, print in angle brackets.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Void => write!(f, "void"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Int(bits, var_type) => write!(f, "{}", extend(*bits, var_type)),
+            Value::Float(value, _) => write!(f, "{:?}", value),
+            Value::Atom(name) => write!(f, ":{}", name),
+            Value::Tuple(values) => {
+                write!(f, "{{")?;
+                write_values(f, values)?;
+                write!(f, "}}")
+            }
+            Value::Struct(values) => {
+                write!(f, "(struct")?;
+                for value in values {
+                    write!(f, " {}", value)?;
+                }
+                write!(f, ")")
+            }
+            Value::Array(values) => {
+                write!(f, "[")?;
+                write_values(f, values)?;
+                write!(f, "]")
+            }
+            Value::Data(tag, values) => {
+                write!(f, "[:{}", tag)?;
+                for value in values {
+                    write!(f, " {}", value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Pointer(Pointer::Null) => write!(f, "<null>"),
+            Value::Pointer(Pointer::Foreign(address)) => write!(f, "<pointer {:#x}>", address),
+            Value::Pointer(_) => write!(f, "<pointer>"),
+            Value::Closure(name, _) => write!(f, "<fn {}>", name),
+        }
+    }
+}
+
+/// Apply `!`/`not`, or `-`/`+` to a single number.
+pub fn unary(op: &str, operand: Value) -> Result<Value, String> {
+    match (op, operand) {
+        ("!" | "not", operand) => Ok(Value::Bool(!operand.as_bool(op)?)),
+        ("-", Value::Int(bits, var_type)) => {
+            Ok(Value::int((bits as i128).wrapping_neg(), &var_type))
+        }
+        ("-", Value::Float(value, var_type)) => Ok(Value::float(-value, &var_type)),
+        ("+", number @ (Value::Int(..) | Value::Float(..))) => Ok(number),
+        (op, operand) => Err(format!("`{}` cannot be applied to `{}`", op, operand)),
+    }
+}
+
+/// Apply an arithmetic, bitwise or comparison operator, or `&&`/`||` of two evaluated
+/// operands, with the semantics of the compiled code: integer arithmetic wraps around, `/`
+/// truncates and `>>` is arithmetic for signed types.
+pub fn binary(op: &str, left: Value, right: Value) -> Result<Value, String> {
+    if builtins::COMPARISON.contains(&op) {
+        return compare(op, &left, &right).map(Value::Bool);
+    }
+    match (left, right) {
+        (Value::Int(a, var_type), Value::Int(b, _)) => int_binary(op, a, b, &var_type),
+        (Value::Float(a, var_type), Value::Float(b, _)) => {
+            let result = match op {
+                "+" => a + b,
+                "-" => a - b,
+                "*" => a * b,
+                "/" => a / b,
+                "%" => a % b,
+                _ => return Err(format!("`{}` cannot be applied to floats", op)),
+            };
+            Ok(Value::float(result, &var_type))
+        }
+        (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(match op {
+            "&&" | "and" | "&" => a && b,
+            "||" | "or" | "|" => a || b,
+            "^" => a != b,
+            _ => return Err(format!("`{}` cannot be applied to `bool`s", op)),
+        })),
+        (left, right) => Err(format!(
+            "`{}` cannot be applied to `{}` and `{}`",
+            op, left, right
+        )),
+    }
+}
+
+fn int_binary(op: &str, a: u128, b: u128, var_type: &VarType) -> Result<Value, String> {
+    let (width, signed) = int_kind(var_type).unwrap_or((128, true));
+    let (x, y) = (extend(a, var_type), extend(b, var_type));
+    let shift = || match u32::try_from(y) {
+        Ok(amount) if amount < width => Ok(amount),
+        _ => Err(format!("shift by {} is out of range for `{}`", y, var_type)),
+    };
+    let bits = match op {
+        "+" => a.wrapping_add(b),
+        "-" => a.wrapping_sub(b),
+        "*" => a.wrapping_mul(b),
+        "/" | "%" if b == 0 => return Err("division by zero".to_string()),
+        "/" if signed => x.wrapping_div(y) as u128,
+        "/" => a / b,
+        "%" if signed => x.wrapping_rem(y) as u128,
+        "%" => a % b,
+        "&" => a & b,
+        "|" => a | b,
+        "^" => a ^ b,
+        "<<" => a << shift()?,
+        ">>" if signed => (x >> shift()?) as u128,
+        ">>" => a >> shift()?,
+        _ => return Err(format!("`{}` cannot be applied to integers", op)),
+    };
+    Ok(Value::Int(truncate(bits, width), var_type.clone()))
+}
+
+fn compare(op: &str, left: &Value, right: &Value) -> Result<bool, String> {
+    use std::cmp::Ordering;
+    let ordering = match (left, right) {
+        (Value::Int(a, var_type), Value::Int(b, _)) => match int_kind(var_type) {
+            Some((_, false)) => a.partial_cmp(b),
+            _ => extend(*a, var_type).partial_cmp(&extend(*b, var_type)),
+        },
+        (Value::Float(a, _), Value::Float(b, _)) => a.partial_cmp(b),
+        _ if op == "=" => return Ok(left == right),
+        _ if op == "!=" => return Ok(left != right),
+        _ => {
+            return Err(format!(
+                "`{}` cannot compare `{}` and `{}`",
+                op, left, right
+            ))
+        }
+    };
+    // NaN is unordered and only `!=` to anything.
+    let Some(ordering) = ordering else {
+        return Ok(op == "!=");
+    };
+    Ok(match op {
+        "=" => ordering == Ordering::Equal,
+        "!=" => ordering != Ordering::Equal,
+        "<" => ordering == Ordering::Less,
+        ">" => ordering == Ordering::Greater,
+        "<=" => ordering != Ordering::Greater,
+        _ => ordering != Ordering::Less,
+    })
+}
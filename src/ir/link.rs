@@ -0,0 +1,124 @@
+//! Symbol names across compilation units.
+//!
+//! Every `.th` file compiles to its own object file, and the names of different modules must
+//! not collide when those are linked. [`qualify`] therefore renames the definitions of a
+//! program to `module/name`, the name an importer reaches them under once its import alias is
+//! replaced by the module path. The entry point keeps its name so that it can be found.
+
+use super::{Const, Instruction, Operand, Place, Program, Terminator, Value};
+use crate::builtins;
+use crate::lint::ENTRY_POINT;
+use std::collections::{BTreeMap, HashMap};
+
+/// Prefix the functions and globals of `program` with `module/`, and turn the declarations of
+/// `alias/member` for the tahini imports in `imports` (alias to module path) into imports of
+/// `path/member`. References to either are renamed to match.
+pub fn qualify(program: &mut Program, module: &str, imports: &BTreeMap<String, String>) {
+    let mut renamed = HashMap::new();
+    for function in &program.functions {
+        if function.name != ENTRY_POINT {
+            renamed.insert(
+                function.name.clone(),
+                format!("{}/{}", module, function.name),
+            );
+        }
+    }
+    for global in &program.globals {
+        renamed.insert(global.name.clone(), format!("{}/{}", module, global.name));
+    }
+    let (imported, externs) = std::mem::take(&mut program.externs)
+        .into_iter()
+        .partition::<Vec<_>, _>(|(name, _)| {
+            builtins::split_path(name).is_some_and(|(alias, _)| imports.contains_key(alias))
+        });
+    program.externs = externs;
+    for (name, var_type) in imported {
+        let (alias, member) = builtins::split_path(&name).unwrap();
+        let qualified = format!("{}/{}", imports[alias], member);
+        program.imports.push((qualified.clone(), var_type));
+        renamed.insert(name, qualified);
+    }
+
+    let rename = |name: &mut String| {
+        if let Some(new) = renamed.get(name.as_str()) {
+            *name = new.clone();
+        }
+    };
+    for global in &mut program.globals {
+        rename(&mut global.name);
+        rename_const(&mut global.value, &rename);
+    }
+    for function in &mut program.functions {
+        rename(&mut function.name);
+        for block in &mut function.blocks {
+            for instruction in &mut block.instructions {
+                match instruction {
+                    Instruction::Let(_, _, value) | Instruction::Eval(value) => {
+                        rename_value(value, &rename)
+                    }
+                    Instruction::Store(place, operand) => {
+                        rename_place(place, &rename);
+                        rename_operand(operand, &rename);
+                    }
+                }
+            }
+            match &mut block.terminator {
+                Terminator::Branch(operand, _, _) | Terminator::Return(Some(operand)) => {
+                    rename_operand(operand, &rename)
+                }
+                Terminator::Jump(_) | Terminator::Return(None) | Terminator::Unreachable => {}
+            }
+        }
+    }
+}
+
+fn rename_value(value: &mut Value, rename: &impl Fn(&mut String)) {
+    match value {
+        Value::Load(place) | Value::Addr(place) => rename_place(place, rename),
+        Value::Field(operand, _) | Value::Unary(_, operand) | Value::Box(operand) => {
+            rename_operand(operand, rename)
+        }
+        Value::Index(left, right) | Value::Binary(_, left, right) => {
+            rename_operand(left, rename);
+            rename_operand(right, rename);
+        }
+        Value::Call(name, operands) | Value::MakeClosure(name, operands) => {
+            rename(name);
+            operands.iter_mut().for_each(|o| rename_operand(o, rename));
+        }
+        Value::CallClosure(closure, operands) => {
+            rename_operand(closure, rename);
+            operands.iter_mut().for_each(|o| rename_operand(o, rename));
+        }
+        Value::Make(_, operands)
+        | Value::Tuple(operands)
+        | Value::Array(operands)
+        | Value::Data(_, operands) => {
+            operands.iter_mut().for_each(|o| rename_operand(o, rename));
+        }
+    }
+}
+
+fn rename_place(place: &mut Place, rename: &impl Fn(&mut String)) {
+    match place {
+        Place::Global(name) => rename(name),
+        Place::Deref(operand) => rename_operand(operand, rename),
+        Place::Local(_) => {}
+    }
+}
+
+fn rename_operand(operand: &mut Operand, rename: &impl Fn(&mut String)) {
+    if let Operand::Const(value) = operand {
+        rename_const(value, rename);
+    }
+}
+
+fn rename_const(value: &mut Const, rename: &impl Fn(&mut String)) {
+    match value {
+        Const::Fn(name) | Const::Callback(name) => rename(name),
+        Const::Tuple(members, _) | Const::Array(members, _) | Const::Data(_, members, _) => {
+            members.iter_mut().for_each(|m| rename_const(m, rename));
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,1283 @@
+//! Lowering of the transformed AST into the IR.
+//!
+//! The module is type checked, monomorphized and closure converted, so types are synthesized
+//! bottom-up without reporting mismatches: variables have their declared type or the one
+//! inferred for them (`Module::inference`), and untyped integer and float literals take the type
+//! their context expects, exactly as in the checker.
+//!
+//! Expressions are flattened in evaluation order. `if`/`else` with a value and the
+//! short-circuiting logical operators write their result to a fresh slot (`$N`) in each branch
+//! and load it where the branches join, so no φ-nodes are needed.
+
+use super::{
+    Block, BlockId, Const, Function, Global, Instruction, Operand, Place, Program, Temp,
+    Terminator, Value,
+};
+use crate::ast::{Literal, Range, Statement, TopLevelDef, TopLevelStatement, VarType};
+use crate::builtins;
+use crate::diagnostic::Diagnostic;
+use crate::transformer::ast::{
+    AccessSegment, Module, TransformedFn, TransformedItem, TransformedStmt,
+};
+use crate::typeck::infer::Inference;
+use crate::typeck::types::{atom_type, is_float, is_numeric, str_type, Aliases};
+use std::collections::{HashMap, HashSet};
+
+/// Lower every item of `module`. Fails on constructs the IR cannot represent, such as names of
+/// unresolved imports or iterating over an unsized array.
+pub fn lower(module: &Module) -> Result<Program, Vec<Diagnostic>> {
+    run(module).0
+}
+
+/// The types lowering gives the expressions of `module`, so that they can be evaluated with
+/// exactly the types the compiled code has (see [`crate::interp`]). Fails like [`lower`].
+pub fn expression_types(module: &Module) -> Result<ExprTypes, Vec<Diagnostic>> {
+    let (program, types) = run(module);
+    program.map(|_| types)
+}
+
+/// The type of every expression of a module and of the root of every `$` chain. Nodes are
+/// keyed by their address, so the types can only be looked up in the very module they were
+/// computed for, and only as long as it is not changed.
+#[derive(Clone, Debug, Default)]
+pub struct ExprTypes(HashMap<usize, VarType>);
+
+impl ExprTypes {
+    pub fn get(&self, statement: &TransformedStmt) -> Option<&VarType> {
+        self.0.get(&address(statement))
+    }
+
+    fn insert(&mut self, statement: &TransformedStmt, var_type: VarType) {
+        self.0.insert(address(statement), var_type);
+    }
+}
+
+fn address(statement: &TransformedStmt) -> usize {
+    statement as *const TransformedStmt as usize
+}
+
+fn run(module: &Module) -> (Result<Program, Vec<Diagnostic>>, ExprTypes) {
+    let mut lowerer = Lowerer::new(module);
+    let mut program = Program {
+        headers: Vec::new(),
+        types: Vec::new(),
+        externs: Vec::new(),
+        imports: Vec::new(),
+        globals: Vec::new(),
+        functions: Vec::new(),
+    };
+
+    for (item, statement) in module.items.iter().enumerate() {
+        lowerer.item = item;
+        match statement {
+            TransformedItem::Orig(TopLevelStatement::TypeAlias(name, var_type)) => {
+                program.types.push((name.clone(), var_type.clone()));
+            }
+            TransformedItem::Orig(TopLevelStatement::TopLevelDef(def)) => {
+                if let TopLevelDef::Typed(var_type) = &def.instruction {
+                    program.externs.push((def.name.clone(), var_type.clone()));
+                }
+            }
+            TransformedItem::Orig(TopLevelStatement::UseHeader(_, header)) => {
+                program.headers.push(header.clone());
+            }
+            // Imports and exports have no code of their own.
+            TransformedItem::Orig(_) => {}
+            TransformedItem::Global(name, value) => {
+                if let Some(global) = lowerer.lower_global(name, value) {
+                    program.globals.push(global);
+                }
+            }
+            TransformedItem::Fn(name, fn_def) => {
+                program.functions.push(lowerer.lower_fn(name, fn_def));
+            }
+        }
+    }
+
+    let types = std::mem::take(&mut lowerer.types);
+    if lowerer.diagnostics.is_empty() {
+        (Ok(program), types)
+    } else {
+        (Err(lowerer.diagnostics), types)
+    }
+}
+
+/// A loop that counts from `start` towards `end` by `step` (`1` if absent). With `elements`,
+/// the counter indexes the array or pointer at the address `base` and the loop variable is the
+/// element instead of the counter.
+struct Counting {
+    counter_type: VarType,
+    start: Operand,
+    end: Operand,
+    step: Option<Operand>,
+    elements: Option<(Operand, VarType)>,
+}
+
+impl Counting {
+    fn elements(base: Operand, element: VarType, end: Operand) -> Self {
+        Counting {
+            counter_type: VarType::Int64,
+            start: Operand::Const(Const::Int(0, VarType::Int64)),
+            end,
+            step: None,
+            elements: Some((base, element)),
+        }
+    }
+}
+
+struct Lowerer<'a> {
+    aliases: Aliases,
+    /// Types of all top-level names.
+    globals: HashMap<String, VarType>,
+    /// Top-level functions and external declarations, which are called directly.
+    functions: HashSet<String>,
+    /// Names the C headers are imported under.
+    headers: HashSet<String>,
+    /// External C functions, whose `fn` parameters are plain function pointers.
+    c_functions: HashSet<String>,
+    inference: &'a Inference,
+    diagnostics: Vec<Diagnostic>,
+    item: usize,
+
+    /// Bindings seen so far per name in the current item, to look up inferred types.
+    counts: HashMap<String, usize>,
+    /// Source names in scope mapped to their slots, innermost last.
+    scopes: Vec<HashMap<String, String>>,
+    locals: Vec<(String, VarType)>,
+    blocks: Vec<Block>,
+    current: BlockId,
+    /// The `(continue)` and `(break)` targets of the enclosing loops, innermost last.
+    loops: Vec<(BlockId, BlockId)>,
+    /// The return type of the function being lowered, or `None` if it returns nothing.
+    return_type: Option<VarType>,
+    next_temp: usize,
+    next_slot: usize,
+    types: ExprTypes,
+}
+
+impl<'a> Lowerer<'a> {
+    fn new(module: &'a Module) -> Self {
+        let mut lowerer = Lowerer {
+            aliases: Aliases::default(),
+            globals: HashMap::new(),
+            functions: HashSet::new(),
+            headers: HashSet::new(),
+            c_functions: HashSet::new(),
+            inference: &module.inference,
+            diagnostics: Vec::new(),
+            item: 0,
+            counts: HashMap::new(),
+            scopes: Vec::new(),
+            locals: Vec::new(),
+            blocks: Vec::new(),
+            current: BlockId(0),
+            loops: Vec::new(),
+            return_type: None,
+            next_temp: 0,
+            next_slot: 0,
+            types: ExprTypes::default(),
+        };
+
+        // Typed functions are C functions unless they are members of tahini modules.
+        let mut modules = HashSet::new();
+        let mut declared = Vec::new();
+        for item in &module.items {
+            match item {
+                TransformedItem::Orig(TopLevelStatement::TypeAlias(name, var_type)) => {
+                    lowerer.aliases.types.insert(name.clone(), var_type.clone());
+                }
+                TransformedItem::Orig(TopLevelStatement::TopLevelDef(def)) => {
+                    if let TopLevelDef::Typed(var_type) = &def.instruction {
+                        if matches!(var_type, VarType::Fn(..) | VarType::FnWithVarArgs(..)) {
+                            lowerer.functions.insert(def.name.clone());
+                            declared.push(def.name.clone());
+                        }
+                        lowerer.globals.insert(def.name.clone(), var_type.clone());
+                    }
+                }
+                TransformedItem::Orig(TopLevelStatement::UseHeader(name, _)) => {
+                    lowerer.headers.insert(name.clone());
+                }
+                TransformedItem::Orig(TopLevelStatement::Use(name, _)) => {
+                    modules.insert(name.clone());
+                }
+                TransformedItem::Orig(_) => {}
+                TransformedItem::Fn(name, fn_def) => {
+                    let params = fn_def.parameters.iter().map(|(_, t)| t.clone()).collect();
+                    let fn_type = VarType::Fn(params, Box::new(fn_def.return_type.clone()));
+                    lowerer.functions.insert(name.clone());
+                    lowerer.globals.insert(name.clone(), fn_type);
+                }
+                TransformedItem::Global(name, value) => {
+                    let inferred = module.inference.globals.get(name).cloned();
+                    let var_type = match (inferred, value) {
+                        (Some(var_type), _) => Some(var_type),
+                        (None, TransformedStmt::Orig(Statement::Literal(literal))) => {
+                            constant(literal, None).map(|(_, var_type)| var_type)
+                        }
+                        _ => None,
+                    };
+                    if let Some(var_type) = var_type {
+                        lowerer.globals.insert(name.clone(), var_type);
+                    }
+                }
+            }
+        }
+        lowerer.c_functions = declared
+            .into_iter()
+            .filter(|name| {
+                builtins::split_path(name).is_none_or(|(alias, _)| !modules.contains(alias))
+            })
+            .collect();
+        lowerer
+    }
+
+    fn error(&mut self, message: String) {
+        self.diagnostics.push(Diagnostic::error(self.item, message));
+    }
+
+    fn lower_global(&mut self, name: &str, value: &TransformedStmt) -> Option<Global> {
+        let var_type = self.globals.get(name).cloned();
+        match initializer(value, var_type.as_ref(), &self.aliases) {
+            Some((value, var_type)) => Some(Global {
+                name: name.to_string(),
+                var_type,
+                value,
+            }),
+            None => {
+                self.error(format!(
+                    "global `{}` must be initialized with a constant",
+                    name
+                ));
+                None
+            }
+        }
+    }
+
+    fn lower_fn(&mut self, name: &str, fn_def: &TransformedFn) -> Function {
+        self.counts.clear();
+        self.scopes = vec![HashMap::new()];
+        self.locals.clear();
+        self.blocks.clear();
+        self.next_temp = 0;
+        self.next_slot = 0;
+
+        let entry = self.new_block();
+        self.switch_to(entry);
+        for (param, var_type) in &fn_def.parameters {
+            self.declare(param, Some(var_type.clone()));
+        }
+
+        self.return_type = match fn_def.return_type {
+            VarType::Void => None,
+            ref return_type => Some(return_type.clone()),
+        };
+        let expected = self.return_type.clone();
+        let (value, _) = self.lower_expr(&fn_def.statement, expected.as_ref());
+        // After a `(return)` or an endless loop there is no value to return.
+        if self.reachable(self.current) {
+            self.terminate(Terminator::Return(expected.map(|_| value)));
+        }
+
+        Function {
+            name: name.to_string(),
+            parameters: fn_def.parameters.clone(),
+            return_type: fn_def.return_type.clone(),
+            locals: std::mem::take(&mut self.locals),
+            blocks: std::mem::take(&mut self.blocks),
+        }
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        let id = BlockId(self.blocks.len());
+        self.blocks.push(Block {
+            id,
+            instructions: Vec::new(),
+            terminator: Terminator::Unreachable,
+        });
+        id
+    }
+
+    /// Whether control can reach `block` from the entry of the function.
+    fn reachable(&self, block: BlockId) -> bool {
+        let mut seen = vec![false; self.blocks.len()];
+        let mut pending = vec![BlockId(0)];
+        while let Some(next) = pending.pop() {
+            if std::mem::replace(&mut seen[next.0], true) {
+                continue;
+            }
+            match self.blocks[next.0].terminator {
+                Terminator::Jump(target) => pending.push(target),
+                Terminator::Branch(_, then, otherwise) => pending.extend([then, otherwise]),
+                Terminator::Return(_) | Terminator::Unreachable => {}
+            }
+        }
+        seen[block.0]
+    }
+
+    fn switch_to(&mut self, block: BlockId) {
+        self.current = block;
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.blocks[self.current.0].instructions.push(instruction);
+    }
+
+    fn terminate(&mut self, terminator: Terminator) {
+        self.blocks[self.current.0].terminator = terminator;
+    }
+
+    /// Assign `value` to a fresh temporary of type `var_type`.
+    fn assign(&mut self, var_type: VarType, value: Value) -> Operand {
+        let temp = Temp(self.next_temp);
+        self.next_temp += 1;
+        self.emit(Instruction::Let(temp, var_type, value));
+        Operand::Temp(temp)
+    }
+
+    /// Compute `value`, or only run it for its effects if it has no value.
+    fn compute(&mut self, var_type: VarType, value: Value) -> (Operand, VarType) {
+        if var_type == VarType::Void {
+            self.emit(Instruction::Eval(value));
+            return (Operand::Const(Const::Void), VarType::Void);
+        }
+        (self.assign(var_type.clone(), value), var_type)
+    }
+
+    /// A fresh slot for a value the source does not name.
+    fn slot(&mut self, var_type: VarType) -> String {
+        let name = format!("${}", self.next_slot);
+        self.next_slot += 1;
+        self.locals.push((name.clone(), var_type));
+        name
+    }
+
+    /// The type inferred for the next binding called `name`, without consuming it.
+    fn peek_inferred(&self, name: &str) -> Option<VarType> {
+        let index = self.counts.get(name).copied().unwrap_or(0);
+        self.inference.binding(self.item, name, index).cloned()
+    }
+
+    /// Bind `name` in the innermost scope to a new slot, typed as inferred or else `fallback`.
+    fn declare(&mut self, name: &str, fallback: Option<VarType>) -> (String, VarType) {
+        let index = self.counts.entry(name.to_string()).or_insert(0);
+        let inferred = self.inference.binding(self.item, name, *index).cloned();
+        let slot = match *index {
+            0 => name.to_string(),
+            n => format!("{}${}", name, n),
+        };
+        *index += 1;
+
+        let var_type = inferred.or(fallback).unwrap_or(VarType::Void);
+        self.locals.push((slot.clone(), var_type.clone()));
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.to_string(), slot.clone());
+        (slot, var_type)
+    }
+
+    fn lookup_local(&self, name: &str) -> Option<(String, VarType)> {
+        let slot = self.scopes.iter().rev().find_map(|scope| scope.get(name))?;
+        let (_, var_type) = self.locals.iter().find(|(local, _)| local == slot)?;
+        Some((slot.clone(), var_type.clone()))
+    }
+
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(HashMap::new());
+        let result = f(self);
+        self.scopes.pop();
+        result
+    }
+
+    fn lower_expr(
+        &mut self,
+        statement: &TransformedStmt,
+        expected: Option<&VarType>,
+    ) -> (Operand, VarType) {
+        let (operand, var_type) = self.lower_node(statement, expected);
+        self.types.insert(statement, var_type.clone());
+        (operand, var_type)
+    }
+
+    fn lower_node(
+        &mut self,
+        statement: &TransformedStmt,
+        expected: Option<&VarType>,
+    ) -> (Operand, VarType) {
+        match statement {
+            TransformedStmt::Orig(Statement::Ident(name)) => self.lower_ident(name),
+            TransformedStmt::Orig(Statement::Literal(literal)) => match constant(literal, expected)
+            {
+                Some((value, var_type)) => (Operand::Const(value), var_type),
+                None => unreachable!("only scalar literals stay original"),
+            },
+            TransformedStmt::Orig(other) => {
+                unreachable!("`{:?}` is lowered by the transformer", other)
+            }
+            TransformedStmt::DoBlock(statements) => self.scoped(|lowerer| {
+                let mut last = (Operand::Const(Const::Void), VarType::Void);
+                for (i, statement) in statements.iter().enumerate() {
+                    let expected = if i + 1 == statements.len() {
+                        expected
+                    } else {
+                        None
+                    };
+                    last = lowerer.lower_expr(statement, expected);
+                }
+                last
+            }),
+            TransformedStmt::Call(name, args) => self.lower_call(name, args, expected),
+            TransformedStmt::DefVar(def) => {
+                match self.lookup_local(&def.name) {
+                    Some((slot, var_type)) => {
+                        let (value, _) = self.lower_expr(&def.instruction, Some(&var_type));
+                        self.emit(Instruction::Store(Place::Local(slot), value));
+                    }
+                    None => {
+                        let inferred = self.peek_inferred(&def.name);
+                        let (value, actual) = self.lower_expr(&def.instruction, inferred.as_ref());
+                        let (slot, _) = self.declare(&def.name, Some(actual));
+                        self.emit(Instruction::Store(Place::Local(slot), value));
+                    }
+                }
+                (Operand::Const(Const::Void), VarType::Void)
+            }
+            TransformedStmt::Declare(name, var_type) => {
+                self.declare(name, Some(var_type.clone()));
+                (Operand::Const(Const::Void), VarType::Void)
+            }
+            TransformedStmt::If(condition, then) => {
+                let (condition, _) = self.lower_expr(condition, Some(&VarType::Bool));
+                let then_block = self.new_block();
+                let join = self.new_block();
+                self.terminate(Terminator::Branch(condition, then_block, join));
+
+                self.switch_to(then_block);
+                self.scoped(|lowerer| lowerer.lower_expr(then, None));
+                self.terminate(Terminator::Jump(join));
+                self.switch_to(join);
+                (Operand::Const(Const::Void), VarType::Void)
+            }
+            TransformedStmt::IfElse(condition, then, otherwise) => {
+                self.lower_if_else(condition, then, otherwise, expected)
+            }
+            TransformedStmt::For(condition, body) => {
+                let head = self.new_block();
+                self.terminate(Terminator::Jump(head));
+                self.switch_to(head);
+                let (condition, _) = self.lower_expr(condition, Some(&VarType::Bool));
+                let body_block = self.new_block();
+                let exit = self.new_block();
+                self.terminate(Terminator::Branch(condition, body_block, exit));
+
+                self.switch_to(body_block);
+                self.scoped(|lowerer| lowerer.lower_loop_body(body, head, exit));
+                self.terminate(Terminator::Jump(head));
+                self.switch_to(exit);
+                (Operand::Const(Const::Void), VarType::Void)
+            }
+            TransformedStmt::ForRange(name, range, body) => self.lower_for_range(name, range, body),
+            TransformedStmt::Break => match self.loops.last() {
+                Some(&(_, exit)) => self.lower_jump(exit),
+                None => unreachable!("`(break)` outside of a loop is rejected by the resolver"),
+            },
+            TransformedStmt::Continue => match self.loops.last() {
+                Some(&(next, _)) => self.lower_jump(next),
+                None => unreachable!("`(continue)` outside of a loop is rejected by the resolver"),
+            },
+            TransformedStmt::Return(value) => {
+                let expected = self.return_type.clone();
+                let value = value
+                    .as_ref()
+                    .map(|value| self.lower_expr(value, expected.as_ref()).0);
+                self.terminate(Terminator::Return(expected.and(value)));
+                let dead = self.new_block();
+                self.switch_to(dead);
+                (Operand::Const(Const::Void), VarType::Void)
+            }
+            TransformedStmt::Tuple(items) => {
+                let expected_items = match expected.map(|e| self.aliases.normalize(e)) {
+                    Some(VarType::Tuple(types)) if types.len() == items.len() => {
+                        types.into_iter().map(Some).collect()
+                    }
+                    _ => vec![None; items.len()],
+                };
+                let (operands, types) = items
+                    .iter()
+                    .zip(expected_items)
+                    .map(|(item, expected)| self.lower_expr(item, expected.as_ref()))
+                    .unzip();
+                self.compute(VarType::Tuple(types), Value::Tuple(operands))
+            }
+            TransformedStmt::Array(items) => {
+                let mut element = match expected.map(|e| self.aliases.normalize(e)) {
+                    Some(VarType::ArraySized(element, _))
+                    | Some(VarType::ArrayUnsized(element)) => Some(*element),
+                    _ => None,
+                };
+                let mut operands = Vec::new();
+                for item in items {
+                    let (operand, actual) = self.lower_expr(item, element.as_ref());
+                    element.get_or_insert(actual);
+                    operands.push(operand);
+                }
+                let element = element.unwrap_or(VarType::Void);
+                let var_type = VarType::ArraySized(Box::new(element), items.len());
+                self.compute(var_type, Value::Array(operands))
+            }
+            TransformedStmt::Data(tag, args) => self.lower_data(tag, args, expected),
+            TransformedStmt::Fn(_) => unreachable!("lambda lifting leaves no nested fn literals"),
+            TransformedStmt::ChainAccess { root, segments } => {
+                let (address, var_type) = self.chain_address(root, segments);
+                self.compute(var_type, Value::Load(Place::Deref(address)))
+            }
+            TransformedStmt::ChainAssign {
+                root,
+                segments,
+                value,
+            } => {
+                let (address, var_type) = self.chain_address(root, segments);
+                let (value, _) = self.lower_expr(value, Some(&var_type));
+                self.emit(Instruction::Store(Place::Deref(address), value));
+                (Operand::Const(Const::Void), VarType::Void)
+            }
+            TransformedStmt::MakeClosure { fn_name, env } => {
+                let (params, ret) = match self.globals.get(fn_name).cloned() {
+                    Some(VarType::Fn(params, ret)) if !params.is_empty() => (params, ret),
+                    _ => unreachable!("closures are built from lifted functions"),
+                };
+                let fields = self.aliases.struct_fields(&params[0]).unwrap_or_default();
+                let env = env
+                    .iter()
+                    .zip(fields)
+                    .map(|(value, (_, field))| self.lower_expr(value, Some(&field)).0)
+                    .collect();
+                let var_type = VarType::Fn(params[1..].to_vec(), ret);
+                self.compute(var_type, Value::MakeClosure(fn_name.clone(), env))
+            }
+            TransformedStmt::BoxNew(value) => {
+                let pointee = match expected.map(|e| self.aliases.normalize(e)) {
+                    Some(VarType::Ptr(pointee)) => Some(*pointee),
+                    _ => None,
+                };
+                let (value, var_type) = self.lower_expr(value, pointee.as_ref());
+                self.compute(VarType::Ptr(Box::new(var_type)), Value::Box(value))
+            }
+            TransformedStmt::BoxGet(cell) => {
+                let (cell, pointee) = self.lower_cell(cell);
+                self.compute(pointee, Value::Load(Place::Deref(cell)))
+            }
+            TransformedStmt::BoxSet(cell, value) => {
+                let (cell, pointee) = self.lower_cell(cell);
+                let (value, _) = self.lower_expr(value, Some(&pointee));
+                self.emit(Instruction::Store(Place::Deref(cell), value));
+                (Operand::Const(Const::Void), VarType::Void)
+            }
+        }
+    }
+
+    fn lower_ident(&mut self, name: &str) -> (Operand, VarType) {
+        if let Some((slot, var_type)) = self.lookup_local(name) {
+            return self.compute(var_type, Value::Load(Place::Local(slot)));
+        }
+        match self.globals.get(name).cloned() {
+            Some(var_type) if self.functions.contains(name) => {
+                (Operand::Const(Const::Fn(name.to_string())), var_type)
+            }
+            Some(var_type) => self.compute(var_type, Value::Load(Place::Global(name.to_string()))),
+            None => {
+                self.error(format!("`{}` is not defined in this module", name));
+                (Operand::Const(Const::Void), VarType::Void)
+            }
+        }
+    }
+
+    /// A boxed cell and the type of the value it holds.
+    fn lower_cell(&mut self, cell: &TransformedStmt) -> (Operand, VarType) {
+        let (cell, var_type) = self.lower_expr(cell, None);
+        match self.aliases.normalize(&var_type) {
+            VarType::Ptr(pointee) => (cell, *pointee),
+            _ => unreachable!("boxes are pointers"),
+        }
+    }
+
+    fn lower_all(&mut self, args: &[TransformedStmt], params: &[VarType]) -> Vec<Operand> {
+        args.iter()
+            .enumerate()
+            .map(|(i, arg)| self.lower_expr(arg, params.get(i)).0)
+            .collect()
+    }
+
+    /// The arguments of a call of a C function. C takes a `fn` parameter as a plain function
+    /// pointer, so the function is passed as a [`Const::Callback`] instead of a closure.
+    fn lower_c_args(&mut self, args: &[TransformedStmt], params: &[VarType]) -> Vec<Operand> {
+        let mut operands = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            let param = params.get(i).map(|param| self.aliases.normalize(param));
+            operands.push(match param {
+                Some(fn_type @ (VarType::Fn(..) | VarType::FnWithVarArgs(..))) => {
+                    self.lower_callback(arg, fn_type)
+                }
+                _ => self.lower_expr(arg, params.get(i)).0,
+            });
+        }
+        operands
+    }
+
+    fn lower_callback(&mut self, arg: &TransformedStmt, fn_type: VarType) -> Operand {
+        let function = match arg {
+            TransformedStmt::Orig(Statement::Ident(name))
+                if self.lookup_local(name).is_none() && self.functions.contains(name) =>
+            {
+                name
+            }
+            TransformedStmt::MakeClosure { fn_name, env } if env.is_empty() => fn_name,
+            _ => unreachable!("the checker rejects closures passed to C"),
+        };
+        self.types.insert(arg, fn_type);
+        Operand::Const(Const::Callback(function.clone()))
+    }
+
+    fn lower_call(
+        &mut self,
+        name: &str,
+        args: &[TransformedStmt],
+        expected: Option<&VarType>,
+    ) -> (Operand, VarType) {
+        if let Some((slot, closure_type)) = self.lookup_local(name) {
+            let VarType::Fn(params, ret) = self.aliases.normalize(&closure_type) else {
+                unreachable!("only closures are called through locals")
+            };
+            let closure = self.assign(closure_type, Value::Load(Place::Local(slot)));
+            let args = self.lower_all(args, &params);
+            return self.compute(*ret, Value::CallClosure(closure, args));
+        }
+
+        if self.functions.contains(name) {
+            let (params, ret) = match self.aliases.normalize(&self.globals[name]) {
+                VarType::Fn(params, ret) | VarType::FnWithVarArgs(params, ret) => (params, ret),
+                other => unreachable!("`{}` is not callable", other),
+            };
+            let args = match self.c_functions.contains(name) {
+                true => self.lower_c_args(args, &params),
+                false => self.lower_all(args, &params),
+            };
+            return self.compute(*ret, Value::Call(name.to_string(), args));
+        }
+
+        if let Some(alias) = self.aliases.types.get(name).cloned() {
+            if let (
+                VarType::Data(_),
+                Some(TransformedStmt::Orig(Statement::Literal(Literal::Atom(tag)))),
+            ) = (&alias, args.first())
+            {
+                let named = VarType::IdentType(name.to_string());
+                return self.lower_data(tag, &args[1..], Some(&named));
+            }
+            let params = match self.aliases.normalize(&alias) {
+                VarType::Struct(fields) => fields.into_iter().map(|(_, t)| t).collect(),
+                VarType::Tuple(types) => types,
+                other => vec![other],
+            };
+            let members = self.lower_all(args, &params);
+            let var_type = VarType::IdentType(name.to_string());
+            return self.compute(var_type.clone(), Value::Make(var_type, members));
+        }
+
+        if builtins::is_builtin_fn(name) {
+            return self.lower_builtin(name, args, expected);
+        }
+
+        // Members of C headers without an imported declaration, as function-like macros or the
+        // members of headers that could not be imported, take the type the context expects and
+        // their arguments the type of their own.
+        if builtins::split_path(name).is_some_and(|(module, _)| self.headers.contains(module)) {
+            let args = self.lower_all(args, &[]);
+            let ret = expected.cloned().unwrap_or(VarType::Void);
+            return self.compute(ret, Value::Call(name.to_string(), args));
+        }
+
+        self.error(format!("`{}` is not defined in this module", name));
+        for arg in args {
+            self.lower_expr(arg, None);
+        }
+        (Operand::Const(Const::Void), VarType::Void)
+    }
+
+    fn lower_builtin(
+        &mut self,
+        name: &str,
+        args: &[TransformedStmt],
+        expected: Option<&VarType>,
+    ) -> (Operand, VarType) {
+        if builtins::LOGICAL.contains(&name) {
+            return match name {
+                "!" | "not" => {
+                    let [arg] = args else {
+                        self.error(format!("`{}` takes 1 operand", name));
+                        return (Operand::Const(Const::Bool(false)), VarType::Bool);
+                    };
+                    let (operand, _) = self.lower_expr(arg, Some(&VarType::Bool));
+                    self.compute(VarType::Bool, Value::Unary(name.to_string(), operand))
+                }
+                _ => self.lower_short_circuit(name, args),
+            };
+        }
+
+        // Operands share one type; literals adopt the type of the first operand that has one
+        // of its own, so non-literals are lowered first. Literals emit no instructions, which
+        // keeps the evaluation order.
+        let is_literal = |s: &TransformedStmt| {
+            matches!(
+                s,
+                TransformedStmt::Orig(Statement::Literal(Literal::Int(_) | Literal::Float(_)))
+            )
+        };
+        let hint = if builtins::COMPARISON.contains(&name) {
+            None
+        } else {
+            expected.cloned()
+        };
+        let mut lowered = vec![None; args.len()];
+        let mut operand_type = None;
+        for (i, arg) in args.iter().enumerate() {
+            if !is_literal(arg) {
+                let (operand, actual) = self.lower_expr(arg, hint.as_ref());
+                operand_type.get_or_insert(actual.clone());
+                lowered[i] = Some((operand, actual));
+            }
+        }
+        let operand_type = operand_type.or(hint);
+        for (i, arg) in args.iter().enumerate() {
+            if is_literal(arg) {
+                lowered[i] = Some(self.lower_expr(arg, operand_type.as_ref()));
+            }
+        }
+        let (operands, types): (Vec<_>, Vec<_>) = lowered.into_iter().flatten().unzip();
+        let first = types.first().cloned().unwrap_or(VarType::Void);
+
+        if builtins::COMPARISON.contains(&name) {
+            // `(< a b c)` holds if every adjacent pair does.
+            let mut result: Option<Operand> = None;
+            for pair in operands.windows(2) {
+                let value = Value::Binary(name.to_string(), pair[0].clone(), pair[1].clone());
+                let holds = self.assign(VarType::Bool, value);
+                result = Some(match result {
+                    Some(previous) => self.assign(
+                        VarType::Bool,
+                        Value::Binary("&&".to_string(), previous, holds),
+                    ),
+                    None => holds,
+                });
+            }
+            return (
+                result.unwrap_or(Operand::Const(Const::Bool(true))),
+                VarType::Bool,
+            );
+        }
+
+        let mut operands = operands.into_iter();
+        let Some(mut result) = operands.next() else {
+            return (Operand::Const(Const::Void), VarType::Void);
+        };
+        if args.len() == 1 {
+            // `(+ x)` is `x`; `-` is the only other operator the checker lets take one operand.
+            if name == "+" {
+                return (result, first);
+            }
+            return self.compute(first, Value::Unary(name.to_string(), result));
+        }
+        for operand in operands {
+            let value = Value::Binary(name.to_string(), result, operand);
+            result = self.assign(first.clone(), value);
+        }
+        (result, first)
+    }
+
+    /// `&&`/`and` and `||`/`or` only evaluate operands until the result is known.
+    fn lower_short_circuit(&mut self, name: &str, args: &[TransformedStmt]) -> (Operand, VarType) {
+        let all = name == "&&" || name == "and";
+        if args.is_empty() {
+            return (Operand::Const(Const::Bool(all)), VarType::Bool);
+        }
+
+        let slot = self.slot(VarType::Bool);
+        let join = self.new_block();
+        for (i, arg) in args.iter().enumerate() {
+            let (value, _) = self.lower_expr(arg, Some(&VarType::Bool));
+            self.emit(Instruction::Store(
+                Place::Local(slot.clone()),
+                value.clone(),
+            ));
+            if i + 1 == args.len() {
+                self.terminate(Terminator::Jump(join));
+            } else {
+                let next = self.new_block();
+                let (on_true, on_false) = if all { (next, join) } else { (join, next) };
+                self.terminate(Terminator::Branch(value, on_true, on_false));
+                self.switch_to(next);
+            }
+        }
+        self.switch_to(join);
+        self.compute(VarType::Bool, Value::Load(Place::Local(slot)))
+    }
+
+    fn lower_if_else(
+        &mut self,
+        condition: &TransformedStmt,
+        then: &TransformedStmt,
+        otherwise: &TransformedStmt,
+        expected: Option<&VarType>,
+    ) -> (Operand, VarType) {
+        let (condition, _) = self.lower_expr(condition, Some(&VarType::Bool));
+        let then_block = self.new_block();
+        let else_block = self.new_block();
+        let join = self.new_block();
+        self.terminate(Terminator::Branch(condition, then_block, else_block));
+
+        self.switch_to(then_block);
+        let (then_value, then_type) = self.scoped(|lowerer| lowerer.lower_expr(then, expected));
+        let then_end = self.current;
+
+        // A branch that jumps away, like `(break)`, is void and takes the type of the other.
+        let hint = expected.cloned().unwrap_or(then_type);
+        let mut slot = (hint != VarType::Void).then(|| self.slot(hint.clone()));
+
+        self.switch_to(else_block);
+        let expected = slot.as_ref().map(|_| hint.clone());
+        let (else_value, else_type) =
+            self.scoped(|lowerer| lowerer.lower_expr(otherwise, expected.as_ref()));
+        let else_end = self.current;
+        let var_type = expected.unwrap_or(else_type);
+        if slot.is_none() && var_type != VarType::Void {
+            slot = Some(self.slot(var_type.clone()));
+        }
+        for (end, value) in [(then_end, then_value), (else_end, else_value)] {
+            self.switch_to(end);
+            if let Some(slot) = &slot {
+                self.emit(Instruction::Store(Place::Local(slot.clone()), value));
+            }
+            self.terminate(Terminator::Jump(join));
+        }
+
+        self.switch_to(join);
+        match slot {
+            Some(slot) => self.compute(var_type, Value::Load(Place::Local(slot))),
+            None => (Operand::Const(Const::Void), VarType::Void),
+        }
+    }
+
+    /// `(for (range x ...) ...)` counts a hidden slot from the start of the range and stores
+    /// its value, or the element it indexes, in a fresh `x` for every iteration. `(continue)`
+    /// jumps to the step that advances the counter.
+    fn lower_for_range(
+        &mut self,
+        name: &str,
+        range: &Range<TransformedStmt>,
+        body: &TransformedStmt,
+    ) -> (Operand, VarType) {
+        let hint = self.peek_inferred(name);
+        let counting = match range {
+            Range::Each(range) => {
+                let (range, range_type) = self.lower_expr(range, hint.as_ref());
+                match self.aliases.normalize(&range_type) {
+                    VarType::ArraySized(element, length) => {
+                        let base = self.array_base(range, range_type);
+                        let end = Operand::Const(Const::Int(length as i64, VarType::Int64));
+                        Counting::elements(base, *element, end)
+                    }
+                    VarType::ArrayUnsized(_) => {
+                        self.error(format!(
+                            "cannot iterate over `{}`: its length is not known at runtime",
+                            range_type
+                        ));
+                        return (Operand::Const(Const::Void), VarType::Void);
+                    }
+                    _ => Counting {
+                        counter_type: range_type.clone(),
+                        start: Operand::Const(Const::Int(0, range_type)),
+                        end: range,
+                        step: None,
+                        elements: None,
+                    },
+                }
+            }
+            Range::Bounds(start, end, step) => {
+                let (start, counter_type) = self.lower_expr(start, hint.as_ref());
+                let (end, _) = self.lower_expr(end, Some(&counter_type));
+                let step = step
+                    .as_ref()
+                    .map(|step| self.lower_expr(step, Some(&counter_type)).0);
+                Counting {
+                    counter_type,
+                    start,
+                    end,
+                    step,
+                    elements: None,
+                }
+            }
+            Range::Slice(array, start, end) => {
+                let (array, array_type) = self.lower_expr(array, None);
+                let (start, _) = self.lower_expr(start, Some(&VarType::Int64));
+                let (end, _) = self.lower_expr(end, Some(&VarType::Int64));
+                let (base, element) = match self.aliases.normalize(&array_type) {
+                    VarType::Ptr(element) => (array, *element),
+                    VarType::ArraySized(element, _) | VarType::ArrayUnsized(element) => {
+                        (self.array_base(array, array_type), *element)
+                    }
+                    _ => unreachable!("slices of `{}` are rejected by the checker", array_type),
+                };
+                Counting {
+                    start,
+                    ..Counting::elements(base, element, end)
+                }
+            }
+        };
+        self.lower_counting(name, counting, body);
+        (Operand::Const(Const::Void), VarType::Void)
+    }
+
+    /// The address of `array`, stored in a fresh slot so that it can be indexed.
+    fn array_base(&mut self, array: Operand, array_type: VarType) -> Operand {
+        let slot = self.slot(array_type.clone());
+        self.emit(Instruction::Store(Place::Local(slot.clone()), array));
+        self.assign(
+            VarType::Ptr(Box::new(array_type)),
+            Value::Addr(Place::Local(slot)),
+        )
+    }
+
+    fn lower_counting(&mut self, name: &str, counting: Counting, body: &TransformedStmt) {
+        let Counting {
+            counter_type,
+            start,
+            end,
+            step,
+            elements,
+        } = counting;
+        let counter = self.slot(counter_type.clone());
+        self.emit(Instruction::Store(Place::Local(counter.clone()), start));
+
+        let head = self.new_block();
+        let body_block = self.new_block();
+        let advance = self.new_block();
+        let exit = self.new_block();
+        self.terminate(Terminator::Jump(head));
+        self.switch_to(head);
+        let index = self.assign(
+            counter_type.clone(),
+            Value::Load(Place::Local(counter.clone())),
+        );
+        let more = self.more_iterations(&counter_type, &index, &end, step.as_ref());
+        self.terminate(Terminator::Branch(more, body_block, exit));
+
+        self.switch_to(body_block);
+        self.scoped(|lowerer| {
+            let (value, element) = match &elements {
+                Some((base, element)) => {
+                    let pointer = VarType::Ptr(Box::new(element.clone()));
+                    let address = lowerer.assign(pointer, Value::Index(base.clone(), index));
+                    let value = lowerer.assign(element.clone(), Value::Load(Place::Deref(address)));
+                    (value, element.clone())
+                }
+                None => (index, counter_type.clone()),
+            };
+            let (slot, _) = lowerer.declare(name, Some(element));
+            lowerer.emit(Instruction::Store(Place::Local(slot), value));
+            lowerer.lower_loop_body(body, advance, exit);
+        });
+        self.terminate(Terminator::Jump(advance));
+
+        self.switch_to(advance);
+        let index = self.assign(
+            counter_type.clone(),
+            Value::Load(Place::Local(counter.clone())),
+        );
+        let step = step.unwrap_or(Operand::Const(Const::Int(1, counter_type.clone())));
+        let next = self.assign(counter_type, Value::Binary("+".to_string(), index, step));
+        self.emit(Instruction::Store(Place::Local(counter), next));
+        self.terminate(Terminator::Jump(head));
+
+        self.switch_to(exit);
+    }
+
+    /// Whether the loop goes on with `index`: below `end` for a positive step, above it for a
+    /// negative one. A step only known at runtime tests both; a zero step never iterates.
+    fn more_iterations(
+        &mut self,
+        counter_type: &VarType,
+        index: &Operand,
+        end: &Operand,
+        step: Option<&Operand>,
+    ) -> Operand {
+        let compare = |lowerer: &mut Self, op: &str, a: &Operand, b: &Operand| {
+            lowerer.assign(
+                VarType::Bool,
+                Value::Binary(op.to_string(), a.clone(), b.clone()),
+            )
+        };
+        match step {
+            None => compare(self, "<", index, end),
+            Some(Operand::Const(Const::Int(step, _))) if *step > 0 => {
+                compare(self, "<", index, end)
+            }
+            Some(Operand::Const(Const::Int(step, _))) if *step < 0 => {
+                compare(self, ">", index, end)
+            }
+            Some(step) => {
+                let zero = Operand::Const(Const::Int(0, counter_type.clone()));
+                let rising = compare(self, ">", step, &zero);
+                let below = compare(self, "<", index, end);
+                let up = compare(self, "&&", &rising, &below);
+                let falling = compare(self, "<", step, &zero);
+                let above = compare(self, ">", index, end);
+                let down = compare(self, "&&", &falling, &above);
+                compare(self, "||", &up, &down)
+            }
+        }
+    }
+
+    /// Lower a loop body in which `(continue)` jumps to `next` and `(break)` to `exit`.
+    fn lower_loop_body(&mut self, body: &TransformedStmt, next: BlockId, exit: BlockId) {
+        self.loops.push((next, exit));
+        self.lower_expr(body, None);
+        self.loops.pop();
+    }
+
+    /// Leave the current block for the target of `(break)` or `(continue)`. Code after the jump
+    /// goes to a fresh block that nothing jumps to.
+    fn lower_jump(&mut self, target: BlockId) -> (Operand, VarType) {
+        self.terminate(Terminator::Jump(target));
+        let dead = self.new_block();
+        self.switch_to(dead);
+        (Operand::Const(Const::Void), VarType::Void)
+    }
+
+    fn lower_data(
+        &mut self,
+        tag: &str,
+        args: &[TransformedStmt],
+        expected: Option<&VarType>,
+    ) -> (Operand, VarType) {
+        let Some(data_type) = data_type(tag, expected, &self.aliases) else {
+            self.error(format!(
+                "cannot tell which data type the variant `:{}` belongs to",
+                tag
+            ));
+            return (Operand::Const(Const::Void), VarType::Void);
+        };
+
+        let variants = self.aliases.data_variants(&data_type).unwrap_or_default();
+        let fields = variants
+            .into_iter()
+            .find(|(t, _)| t == tag)
+            .map(|(_, fields)| fields)
+            .unwrap_or_default();
+        let members = self.lower_all(args, &fields);
+        self.compute(data_type, Value::Data(tag.to_string(), members))
+    }
+
+    /// The address a chain's root is stored at and the root's type. Roots that are not stored
+    /// anywhere, such as call results, are spilled to a fresh slot.
+    fn root_address(&mut self, root: &TransformedStmt) -> (Operand, VarType) {
+        let (address, var_type) = self.root_place(root);
+        self.types.insert(root, var_type.clone());
+        (address, var_type)
+    }
+
+    fn root_place(&mut self, root: &TransformedStmt) -> (Operand, VarType) {
+        match root {
+            TransformedStmt::Orig(Statement::Ident(name)) if self.lookup_local(name).is_some() => {
+                let (slot, var_type) = self.lookup_local(name).unwrap();
+                let address = VarType::Ptr(Box::new(var_type.clone()));
+                (
+                    self.assign(address, Value::Addr(Place::Local(slot))),
+                    var_type,
+                )
+            }
+            TransformedStmt::Orig(Statement::Ident(name))
+                if self.globals.contains_key(name) && !self.functions.contains(name) =>
+            {
+                let var_type = self.globals[name].clone();
+                let address = VarType::Ptr(Box::new(var_type.clone()));
+                let global = Place::Global(name.clone());
+                (self.assign(address, Value::Addr(global)), var_type)
+            }
+            TransformedStmt::BoxGet(cell) => self.lower_cell(cell),
+            other => {
+                let (value, var_type) = self.lower_expr(other, None);
+                let slot = self.slot(var_type.clone());
+                self.emit(Instruction::Store(Place::Local(slot.clone()), value));
+                let address = VarType::Ptr(Box::new(var_type.clone()));
+                (
+                    self.assign(address, Value::Addr(Place::Local(slot))),
+                    var_type,
+                )
+            }
+        }
+    }
+
+    /// The address a `$` chain designates and the type stored there. Fields of a pointer, and
+    /// elements of a pointer or an unsized array, are reached through the pointer.
+    fn chain_address(
+        &mut self,
+        root: &TransformedStmt,
+        segments: &[AccessSegment<TransformedStmt>],
+    ) -> (Operand, VarType) {
+        let (mut address, mut var_type) = self.root_address(root);
+        for segment in segments {
+            let mut current = self.aliases.normalize(&var_type);
+            let through_pointer = matches!(
+                (&current, segment),
+                (VarType::Ptr(_), _) | (VarType::ArrayUnsized(_), AccessSegment::Index(_))
+            );
+            if through_pointer {
+                address = self.assign(current.clone(), Value::Load(Place::Deref(address)));
+            }
+
+            let member = match segment {
+                AccessSegment::Field(field) => {
+                    if let VarType::Ptr(inner) = &current {
+                        current = self.aliases.normalize(inner);
+                    }
+                    let fields = match current {
+                        VarType::Struct(fields) => fields,
+                        other => unreachable!("`{}` has no fields", other),
+                    };
+                    let (_, member) = fields.into_iter().find(|(name, _)| name == field).unwrap();
+                    let pointer = VarType::Ptr(Box::new(member.clone()));
+                    address = self.assign(pointer, Value::Field(address, field.clone()));
+                    member
+                }
+                AccessSegment::Index(index) => {
+                    let member = match (current, index.as_ref()) {
+                        (
+                            VarType::ArraySized(element, _)
+                            | VarType::ArrayUnsized(element)
+                            | VarType::Ptr(element),
+                            _,
+                        ) => *element,
+                        (
+                            VarType::Tuple(types),
+                            TransformedStmt::Orig(Statement::Literal(Literal::Int(i))),
+                        ) => types[*i as usize].clone(),
+                        (other, _) => unreachable!("`{}` cannot be indexed", other),
+                    };
+                    let (index, _) = self.lower_expr(index, Some(&VarType::Int64));
+                    let pointer = VarType::Ptr(Box::new(member.clone()));
+                    address = self.assign(pointer, Value::Index(address, index));
+                    member
+                }
+            };
+            var_type = member;
+        }
+        (address, var_type)
+    }
+}
+
+/// The `data` type the variant `tag` belongs to: `expected` if it declares the variant, and
+/// otherwise the only data type that does.
+fn data_type(tag: &str, expected: Option<&VarType>, aliases: &Aliases) -> Option<VarType> {
+    let declares = |variants: &[(String, Vec<VarType>)]| variants.iter().any(|(t, _)| t == tag);
+    match expected {
+        Some(expected)
+            if aliases
+                .data_variants(expected)
+                .is_some_and(|v| declares(&v)) =>
+        {
+            Some(expected.clone())
+        }
+        _ => {
+            let candidates: Vec<_> = aliases
+                .types
+                .iter()
+                .filter(|(_, t)| matches!(t, VarType::Data(variants) if declares(variants)))
+                .map(|(name, _)| name.clone())
+                .collect();
+            match candidates.as_slice() {
+                [name] => Some(VarType::IdentType(name.clone())),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// The constant a global is initialized with and its type: a scalar literal, or a tuple, array
+/// or `data` value of constants, as constant evaluation leaves them.
+pub(crate) fn initializer(
+    value: &TransformedStmt,
+    expected: Option<&VarType>,
+    aliases: &Aliases,
+) -> Option<(Const, VarType)> {
+    let all = |items: &[TransformedStmt], types: Vec<Option<VarType>>| {
+        items
+            .iter()
+            .zip(types)
+            .map(|(item, expected)| initializer(item, expected.as_ref(), aliases))
+            .collect::<Option<(Vec<_>, Vec<_>)>>()
+    };
+    match value {
+        TransformedStmt::Orig(Statement::Literal(literal)) => constant(literal, expected),
+        TransformedStmt::Tuple(items) => {
+            let expected_items = match expected.map(|e| aliases.normalize(e)) {
+                Some(VarType::Tuple(types)) if types.len() == items.len() => {
+                    types.into_iter().map(Some).collect()
+                }
+                _ => vec![None; items.len()],
+            };
+            let (members, types) = all(items, expected_items)?;
+            let var_type = expected.cloned().unwrap_or(VarType::Tuple(types));
+            Some((Const::Tuple(members, var_type.clone()), var_type))
+        }
+        TransformedStmt::Array(items) => {
+            let element = match expected.map(|e| aliases.normalize(e)) {
+                Some(VarType::ArraySized(element, length)) if length == items.len() => {
+                    Some(*element)
+                }
+                _ => None,
+            };
+            let (members, types) = all(items, vec![element.clone(); items.len()])?;
+            let var_type = match (element, expected) {
+                (Some(_), Some(expected)) => expected.clone(),
+                _ => {
+                    let element = types.into_iter().next().unwrap_or(VarType::Void);
+                    VarType::ArraySized(Box::new(element), items.len())
+                }
+            };
+            Some((Const::Array(members, var_type.clone()), var_type))
+        }
+        TransformedStmt::Data(tag, args) => {
+            let var_type = data_type(tag, expected, aliases)?;
+            let variants = aliases.data_variants(&var_type)?;
+            let (_, fields) = variants.into_iter().find(|(t, _)| t == tag)?;
+            let (members, _) = all(args, fields.into_iter().map(Some).collect())?;
+            Some((
+                Const::Data(tag.clone(), members, var_type.clone()),
+                var_type,
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// The constant of a scalar literal and its type, given the type the context expects.
+pub(crate) fn constant(literal: &Literal, expected: Option<&VarType>) -> Option<(Const, VarType)> {
+    let numeric = expected.filter(|e| is_numeric(e));
+    Some(match literal {
+        Literal::Int(value) => match numeric {
+            Some(e) if is_float(e) => (Const::Float(*value as f64, e.clone()), e.clone()),
+            Some(e) => (Const::Int(*value, e.clone()), e.clone()),
+            None => (Const::Int(*value, VarType::Int32), VarType::Int32),
+        },
+        Literal::Float(value) => match numeric.filter(|e| is_float(e)) {
+            Some(e) => (Const::Float(*value, e.clone()), e.clone()),
+            None => (Const::Float(*value, VarType::Float64), VarType::Float64),
+        },
+        Literal::Bool(value) => (Const::Bool(*value), VarType::Bool),
+        Literal::Char(value) => (Const::Char(*value), VarType::Int8),
+        Literal::String(value) => (Const::String(value.clone()), str_type()),
+        Literal::Atom(value) => (Const::Atom(value.clone()), atom_type()),
+        _ => return None,
+    })
+}
@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests {
+    use crate::ast::VarType;
+    use crate::ir::lower::lower;
+    use crate::ir::{Const, Instruction, Operand, Place, Program, Terminator, Value};
+    use crate::parser::parser;
+    use crate::transformer::transform;
+    use chumsky::Parser;
+    use std::path::Path;
+
+    fn lowered(src: &str) -> Program {
+        let module = parser().parse(src).into_output().unwrap();
+        let transformed = transform(&module).unwrap_or_else(|errors| panic!("{:?}", errors));
+        lower(&transformed).unwrap_or_else(|errors| panic!("{:?}", errors))
+    }
+
+    /// Compare the IR of `golden/<name>.th` with `golden/<name>.ir`.
+    /// Run with `UPDATE_GOLDEN=1` to rewrite the expected output.
+    fn check_golden(name: &str) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/ir/golden");
+        let source = std::fs::read_to_string(dir.join(format!("{}.th", name))).unwrap();
+        let ir = lowered(&source).to_string();
+
+        let golden = dir.join(format!("{}.ir", name));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&golden, &ir).unwrap();
+        }
+        let expected = std::fs::read_to_string(&golden).unwrap();
+        assert_eq!(ir, expected, "golden IR of `{}` changed", name);
+    }
+
+    #[test]
+    fn test_golden_control_flow() {
+        check_golden("control_flow");
+    }
+
+    #[test]
+    fn test_golden_memory() {
+        check_golden("memory");
+    }
+
+    #[test]
+    fn test_golden_closures() {
+        check_golden("closures");
+    }
+
+    #[test]
+    fn test_shadowed_names_get_their_own_slots() {
+        let program = lowered(
+            "(def f (fn [(:x i64)] i64 (do (if true (do (def y 1) (def x 2))) (def y x) y)))",
+        );
+        let locals: Vec<_> = program.functions[0]
+            .locals
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(locals, vec!["x", "y", "y$1"]);
+        // The inner `def x` re-binds the parameter.
+        let stores = program.functions[0].blocks[1]
+            .instructions
+            .iter()
+            .filter(|i| matches!(i, Instruction::Store(Place::Local(name), _) if name == "x"))
+            .count();
+        assert_eq!(stores, 1);
+    }
+
+    #[test]
+    fn test_void_calls_are_evaluated_for_effects() {
+        let program = lowered(
+            "(def log (fn [(:n i32)] void n)) \n
+            (def f (fn [] void (log 1)))",
+        );
+        let f = &program.functions[1];
+        assert_eq!(
+            f.blocks[0].instructions,
+            vec![Instruction::Eval(Value::Call(
+                "log".into(),
+                vec![Operand::Const(Const::Int(1, VarType::Int32))]
+            ))]
+        );
+        assert_eq!(f.blocks[0].terminator, Terminator::Return(None));
+    }
+
+    #[test]
+    fn test_unsized_arrays_cannot_be_iterated() {
+        let module = parser()
+            .parse("(def f (fn [(:xs [i32])] void (for (range x xs) x)))")
+            .into_output()
+            .unwrap();
+        // The checker rejects the loop before lowering sees it.
+        let errors = transform(&module).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot iterate over `[i32]`: its length is not known; use `(range x :slice xs start end)`"
+        );
+    }
+
+    #[test]
+    fn test_operand_counts_are_checked_before_lowering() {
+        let module = parser()
+            .parse("(def f (fn [] bool (not)))")
+            .into_output()
+            .unwrap();
+        let errors = transform(&module).unwrap_err();
+        assert_eq!(errors[0].message, "`not` takes 1 operand but 0 were given");
+    }
+
+    #[test]
+    fn test_functions_are_passed_to_c_as_callbacks() {
+        let program = lowered(
+            "(def m (use \"m\")) \n
+            (def sort fn [(ptr i32) fn [(ptr i32) (ptr i32)] i32] void) \n
+            (def m/sort fn [(ptr i32) fn [(ptr i32) (ptr i32)] i32] void) \n
+            (def less (fn [(:a (ptr i32)) (:b (ptr i32))] i32 (- ($ [0] a) ($ [0] b)))) \n
+            (def f (fn [(:xs (ptr i32))] void \n
+                (do \n
+                    (sort xs less) \n
+                    (sort xs (fn [(:a (ptr i32)) (:b (ptr i32))] i32 0)) \n
+                    (m/sort xs less))))",
+        );
+        let ir = program.to_string();
+        assert!(ir.contains("call @sort(%0 callback @less)"), "{}", ir);
+        assert!(ir.contains("call @sort(%1 callback @lambda$0)"), "{}", ir);
+        // Functions of tahini modules take closures.
+        assert!(ir.contains("call @m/sort(%2 @less)"), "{}", ir);
+    }
+}
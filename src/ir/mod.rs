@@ -0,0 +1,354 @@
+//! Typed mid-level IR between the transformed AST and the backends.
+//!
+//! Functions are lists of basic blocks. Every block holds straight-line instructions and ends
+//! in one terminator, so all control flow is explicit. Intermediate results live in typed
+//! temporaries (`%N`) that are assigned exactly once; variables are memory slots that are only
+//! read and written through explicit `load`s and `store`s, like `alloca`s before `mem2reg`.
+//! `$` chains become address arithmetic (`field`, `index`) followed by a `load` or a `store`.
+//!
+//! The IR prints in a line-based text format that is meant for debugging and snapshot tests:
+//!
+//! ```text
+//! fn add-one(x: i64) -> i64 {
+//!   local x: i64
+//! bb0:
+//!   %0: i64 = load x
+//!   %1: i64 = + %0 1
+//!   ret %1
+//! }
+//! ```
+
+pub mod link;
+pub mod lower;
+
+mod lower_test;
+
+use crate::ast::VarType;
+use std::fmt;
+
+/// A temporary, printed as `%N`. Numbered per function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Temp(pub usize);
+
+/// A basic block, printed as `bbN`. Numbered per function; the entry block is `bb0`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BlockId(pub usize);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Const {
+    /// Integer and float constants carry the type the checker gave the literal.
+    Int(i64, VarType),
+    Float(f64, VarType),
+    Bool(bool),
+    Char(char),
+    String(String),
+    Atom(String),
+    /// A top-level function used as a value.
+    Fn(String),
+    /// A top-level function passed to C as a plain function pointer, which has no environment.
+    Callback(String),
+    /// Tuples, arrays and `data` values of constants, which only initialize globals. Like
+    /// numbers, they carry their type.
+    Tuple(Vec<Const>, VarType),
+    Array(Vec<Const>, VarType),
+    Data(String, Vec<Const>, VarType),
+    /// The value of a `void` expression.
+    Void,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    Temp(Temp),
+    Const(Const),
+}
+
+/// Memory that can be loaded from and stored to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Place {
+    Local(String),
+    Global(String),
+    /// The memory an address operand points to.
+    Deref(Operand),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Load(Place),
+    /// The address of a local or global.
+    Addr(Place),
+    /// The address of a field of the struct at an address. Tuples are indexed with `Index` and a
+    /// constant.
+    Field(Operand, String),
+    /// The address of an element of the array or tuple at an address.
+    Index(Operand, Operand),
+    /// A builtin operator applied to one or two operands, e.g. `+` or `!`.
+    Unary(String, Operand),
+    Binary(String, Operand, Operand),
+    /// A direct call of a top-level or external function.
+    Call(String, Vec<Operand>),
+    /// A call through a closure value.
+    CallClosure(Operand, Vec<Operand>),
+    /// A value of a named struct, tuple or other type alias from its members.
+    Make(VarType, Vec<Operand>),
+    Tuple(Vec<Operand>),
+    Array(Vec<Operand>),
+    /// A variant of a `data` type.
+    Data(String, Vec<Operand>),
+    /// A closure of a lifted function and the values of its environment struct.
+    MakeClosure(String, Vec<Operand>),
+    /// A heap cell holding a value; its address is the result.
+    Box(Operand),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instruction {
+    Let(Temp, VarType, Value),
+    Store(Place, Operand),
+    /// A value computed only for its effects, such as a call to a `void` function.
+    Eval(Value),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Terminator {
+    Jump(BlockId),
+    /// Continue at the first block if the condition is true, at the second otherwise.
+    Branch(Operand, BlockId, BlockId),
+    Return(Option<Operand>),
+    /// Control never reaches the end of the block.
+    Unreachable,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub id: BlockId,
+    pub instructions: Vec<Instruction>,
+    pub terminator: Terminator,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub parameters: Vec<(String, VarType)>,
+    pub return_type: VarType,
+    /// Every variable slot of the function, parameters first. Shadowed names are renamed to
+    /// `name$N` so that slots are unique.
+    pub locals: Vec<(String, VarType)>,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Global {
+    pub name: String,
+    pub var_type: VarType,
+    pub value: Const,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program {
+    /// C headers imported with `(use :header ...)`. Their functions are called by their
+    /// qualified name, e.g. `stdio/printf`.
+    pub headers: Vec<String>,
+    pub types: Vec<(String, VarType)>,
+    /// Functions declared with a type but no body, e.g. C functions.
+    pub externs: Vec<(String, VarType)>,
+    /// Functions and globals of other tahini modules, under their qualified name
+    /// (`math/square`). Unlike externs they are named like the program's own definitions.
+    pub imports: Vec<(String, VarType)>,
+    pub globals: Vec<Global>,
+    pub functions: Vec<Function>,
+}
+
+impl fmt::Display for Temp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+impl fmt::Display for Const {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Const::Int(value, _) => write!(f, "{}", value),
+            Const::Float(value, _) => write!(f, "{:?}", value),
+            Const::Bool(value) => write!(f, "{}", value),
+            Const::Char(value) => write!(f, "'{}'", value),
+            Const::String(value) => write!(f, "{:?}", value),
+            Const::Atom(value) => write!(f, ":{}", value),
+            Const::Fn(name) => write!(f, "@{}", name),
+            Const::Callback(name) => write!(f, "callback @{}", name),
+            Const::Tuple(members, _) => {
+                write!(f, "{{")?;
+                write_consts(f, members)?;
+                write!(f, "}}")
+            }
+            Const::Array(members, _) => {
+                write!(f, "[")?;
+                write_consts(f, members)?;
+                write!(f, "]")
+            }
+            Const::Data(tag, members, _) => {
+                write!(f, "data :{}(", tag)?;
+                write_consts(f, members)?;
+                write!(f, ")")
+            }
+            Const::Void => write!(f, "()"),
+        }
+    }
+}
+
+fn write_consts(f: &mut fmt::Formatter<'_>, members: &[Const]) -> fmt::Result {
+    for (i, member) in members.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", member)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Temp(temp) => write!(f, "{}", temp),
+            Operand::Const(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl fmt::Display for Place {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Place::Local(name) => write!(f, "{}", name),
+            Place::Global(name) => write!(f, "@{}", name),
+            Place::Deref(address) => write!(f, "*{}", address),
+        }
+    }
+}
+
+fn write_operands(f: &mut fmt::Formatter<'_>, operands: &[Operand]) -> fmt::Result {
+    for (i, operand) in operands.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", operand)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (head, operands) = match self {
+            Value::Load(place) => return write!(f, "load {}", place),
+            Value::Addr(place) => return write!(f, "addr {}", place),
+            Value::Field(address, field) => return write!(f, "field {} :{}", address, field),
+            Value::Index(address, index) => return write!(f, "index {} {}", address, index),
+            Value::Unary(op, operand) => return write!(f, "{} {}", op, operand),
+            Value::Binary(op, left, right) => return write!(f, "{} {} {}", op, left, right),
+            Value::Box(value) => return write!(f, "box {}", value),
+            Value::Call(name, args) => (format!("call @{}", name), args),
+            Value::CallClosure(closure, args) => (format!("call {}", closure), args),
+            Value::Make(var_type, members) => (format!("make {}", var_type), members),
+            Value::Tuple(items) => {
+                write!(f, "{{")?;
+                write_operands(f, items)?;
+                return write!(f, "}}");
+            }
+            Value::Array(items) => {
+                write!(f, "[")?;
+                write_operands(f, items)?;
+                return write!(f, "]");
+            }
+            Value::Data(tag, members) => (format!("data :{}", tag), members),
+            Value::MakeClosure(name, env) => (format!("closure @{}", name), env),
+        };
+        write!(f, "{}(", head)?;
+        write_operands(f, operands)?;
+        write!(f, ")")
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Let(temp, var_type, value) => {
+                write!(f, "{}: {} = {}", temp, var_type, value)
+            }
+            Instruction::Store(place, value) => write!(f, "store {} {}", place, value),
+            Instruction::Eval(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminator::Jump(target) => write!(f, "jump {}", target),
+            Terminator::Branch(condition, then, otherwise) => {
+                write!(f, "branch {} {} {}", condition, then, otherwise)
+            }
+            Terminator::Return(Some(value)) => write!(f, "ret {}", value),
+            Terminator::Return(None) => write!(f, "ret"),
+            Terminator::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fn {}(", self.name)?;
+        for (i, (name, var_type)) in self.parameters.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", name, var_type)?;
+        }
+        writeln!(f, ") -> {} {{", self.return_type)?;
+        for (name, var_type) in &self.locals {
+            writeln!(f, "  local {}: {}", name, var_type)?;
+        }
+        for block in &self.blocks {
+            writeln!(f, "{}:", block.id)?;
+            for instruction in &block.instructions {
+                writeln!(f, "  {}", instruction)?;
+            }
+            writeln!(f, "  {}", block.terminator)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// Prints the program in the IR text format: headers, type aliases, external functions,
+/// imports and globals, then one function per paragraph.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for header in &self.headers {
+            writeln!(f, "include {:?}", header)?;
+        }
+        for (name, var_type) in &self.types {
+            writeln!(f, "type {} = {}", name, var_type)?;
+        }
+        for (name, var_type) in &self.externs {
+            writeln!(f, "extern @{}: {}", name, var_type)?;
+        }
+        for (name, var_type) in &self.imports {
+            writeln!(f, "import @{}: {}", name, var_type)?;
+        }
+        for global in &self.globals {
+            writeln!(
+                f,
+                "global @{}: {} = {}",
+                global.name, global.var_type, global.value
+            )?;
+        }
+        for function in &self.functions {
+            writeln!(f)?;
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,37 @@
 pub mod ast;
+pub mod builtins;
+pub mod codegen;
+pub mod consteval;
+pub mod diagnostic;
+pub mod driver;
+pub mod header;
+pub mod interp;
+pub mod ir;
+pub mod lint;
 pub mod parser;
+pub mod repl;
+pub mod resolve;
 pub mod transformer;
+pub mod typeck;
+pub mod visit;
 
+#[cfg(test)]
+mod consteval_test;
+#[cfg(test)]
+mod driver_test;
+#[cfg(test)]
+mod header_test;
+#[cfg(test)]
+mod lint_test;
 #[cfg(test)]
 mod parser_literal_test;
 #[cfg(test)]
+mod parser_test;
+#[cfg(test)]
 mod parser_type_test;
 #[cfg(test)]
-mod parser_test;
\ No newline at end of file
+mod repl_test;
+#[cfg(test)]
+mod resolve_test;
+#[cfg(test)]
+mod visit_test;
@@ -0,0 +1,255 @@
+//! Warnings about dead and unused code.
+//!
+//! Runs on a parsed module and its [`Resolution`]. A top-level `def` is dead when it is not
+//! exported and cannot be reached from an export or from `main` through the references of
+//! reachable items. Imports, local `def`s and parameters are unused when nothing reads them;
+//! assigning a local with another `def` is not a use. Names starting with `_` are never
+//! reported as unused.
+//!
+//! A file silences lints with `(allow :unused-local :unreachable ...)`, or all of them with
+//! `(allow :all)`.
+
+use crate::ast::{Literal, Statement, TopLevelStatement};
+use crate::diagnostic::Diagnostic;
+use crate::resolve::{BindingKind, RefKind, Resolution, Target};
+use crate::visit::{walk_literal, walk_statement, Visitor};
+use std::collections::HashSet;
+
+pub const UNUSED_DEF: &str = "unused-def";
+pub const UNUSED_IMPORT: &str = "unused-import";
+pub const UNUSED_LOCAL: &str = "unused-local";
+pub const UNUSED_PARAM: &str = "unused-param";
+pub const UNREACHABLE: &str = "unreachable";
+
+pub const LINTS: &[&str] = &[
+    UNUSED_DEF,
+    UNUSED_IMPORT,
+    UNUSED_LOCAL,
+    UNUSED_PARAM,
+    UNREACHABLE,
+];
+
+/// The function a program starts in; it is used even though nothing refers to it.
+pub const ENTRY_POINT: &str = "main";
+
+/// The lint warnings for `module`, ordered by item.
+pub fn lint(module: &[TopLevelStatement], resolution: &Resolution) -> Vec<Diagnostic> {
+    let mut allowed = HashSet::new();
+    let mut diagnostics = Vec::new();
+    for (item, statement) in module.iter().enumerate() {
+        if let TopLevelStatement::Allow(lints) = statement {
+            for lint in lints {
+                if lint != "all" && !LINTS.contains(&lint.as_str()) {
+                    diagnostics.push(Diagnostic::warning(
+                        item,
+                        format!(
+                            "unknown lint `:{}`; expected `:all` or one of :{}",
+                            lint,
+                            LINTS.join(", :")
+                        ),
+                    ));
+                }
+                allowed.insert(lint.as_str());
+            }
+        }
+    }
+    if allowed.contains("all") {
+        return diagnostics;
+    }
+
+    let mut linter = Linter {
+        allowed,
+        diagnostics,
+        item: 0,
+    };
+    linter.unused_bindings(module, resolution);
+    for (item, statement) in module.iter().enumerate() {
+        linter.item = item;
+        linter.visit_top_level(statement);
+    }
+
+    let mut diagnostics = linter.diagnostics;
+    diagnostics.sort_by_key(|d| d.item);
+    diagnostics
+}
+
+struct Linter<'a> {
+    allowed: HashSet<&'a str>,
+    diagnostics: Vec<Diagnostic>,
+    item: usize,
+}
+
+impl Linter<'_> {
+    fn warn(&mut self, lint: &str, item: usize, message: String) {
+        if !self.allowed.contains(lint) {
+            self.diagnostics.push(Diagnostic::warning(item, message));
+        }
+    }
+
+    fn unused_bindings(&mut self, module: &[TopLevelStatement], resolution: &Resolution) {
+        let mut uses = vec![0; resolution.bindings.len()];
+        for reference in &resolution.references {
+            let target = match reference.target {
+                Target::Binding(id) if reference.kind != RefKind::Assign => id,
+                Target::Member { module, .. } => module,
+                _ => continue,
+            };
+            uses[target] += 1;
+        }
+        let reachable = reachable_items(module, resolution);
+
+        for (id, binding) in resolution.bindings.iter().enumerate() {
+            if binding.name.starts_with('_') {
+                continue;
+            }
+            let (lint, message) = match binding.kind {
+                BindingKind::Global | BindingKind::Function
+                    if !reachable.contains(&binding.item) =>
+                {
+                    (
+                        UNUSED_DEF,
+                        format!("`{}` is not exported and never used", binding.name),
+                    )
+                }
+                BindingKind::Module | BindingKind::Header if uses[id] == 0 => {
+                    (UNUSED_IMPORT, format!("unused import `{}`", binding.name))
+                }
+                BindingKind::Local if uses[id] == 0 => {
+                    (UNUSED_LOCAL, format!("unused local `{}`", binding.name))
+                }
+                BindingKind::Param if uses[id] == 0 => {
+                    (UNUSED_PARAM, format!("unused parameter `{}`", binding.name))
+                }
+                _ => continue,
+            };
+            self.warn(lint, binding.item, message);
+        }
+    }
+}
+
+/// The items reachable from the exports and `main`, following references between items. With
+/// `(export :all)` every item is reachable.
+fn reachable_items(module: &[TopLevelStatement], resolution: &Resolution) -> HashSet<usize> {
+    if module
+        .iter()
+        .any(|statement| matches!(statement, TopLevelStatement::ExportAll()))
+    {
+        return (0..module.len()).collect();
+    }
+
+    let mut edges = vec![Vec::new(); module.len()];
+    let mut roots = Vec::new();
+    for (item, statement) in module.iter().enumerate() {
+        if let TopLevelStatement::TopLevelDef(def) = statement {
+            if def.name == ENTRY_POINT {
+                roots.push(item);
+            }
+        }
+    }
+    for reference in &resolution.references {
+        let Target::Binding(id) = reference.target else {
+            continue;
+        };
+        let target = resolution.binding(id).item;
+        if reference.kind == RefKind::Export {
+            roots.push(target);
+        } else if target != reference.item {
+            edges[reference.item].push(target);
+        }
+    }
+
+    let mut reachable = HashSet::new();
+    while let Some(item) = roots.pop() {
+        if reachable.insert(item) {
+            roots.extend(edges[item].iter().copied());
+        }
+    }
+    reachable
+}
+
+/// Whether control never continues after `statement`: it is an endless `for` loop, whose
+/// condition is the literal `true` and whose body has no `(break)` of its own, or `(break)`,
+/// `(continue)` or `(return)`.
+pub(crate) fn diverges(statement: &Statement) -> bool {
+    match statement {
+        Statement::For(condition, body) => {
+            matches!(condition.as_ref(), Statement::Literal(Literal::Bool(true))) && !breaks(body)
+        }
+        Statement::Break | Statement::Continue | Statement::Return(_) => true,
+        Statement::DoBlock(statements) => statements.iter().any(diverges),
+        Statement::IfElse(condition, then, otherwise) => {
+            diverges(condition) || (diverges(then) && diverges(otherwise))
+        }
+        Statement::If(condition, _) => diverges(condition),
+        Statement::DefVar(def) => diverges(&def.instruction),
+        _ => false,
+    }
+}
+
+/// Whether `statement` contains a `(break)` that leaves the loop around it, not one of a
+/// nested loop or function.
+fn breaks(statement: &Statement) -> bool {
+    struct Breaks(bool);
+
+    impl Visitor for Breaks {
+        fn visit_statement(&mut self, statement: &Statement) {
+            match statement {
+                Statement::Break => self.0 = true,
+                Statement::For(condition, _) => self.visit_statement(condition),
+                Statement::ForRange(_, range, _) => {
+                    for part in range.parts() {
+                        self.visit_statement(part);
+                    }
+                }
+                _ => walk_statement(self, statement),
+            }
+        }
+
+        fn visit_literal(&mut self, literal: &Literal) {
+            if !matches!(literal, Literal::Fn(_)) {
+                walk_literal(self, literal);
+            }
+        }
+    }
+
+    let mut visitor = Breaks(false);
+    visitor.visit_statement(statement);
+    visitor.0
+}
+
+/// What ends control flow in the diverging `statement`, for the unreachable code warning.
+fn divergence(statement: &Statement) -> &'static str {
+    match statement {
+        Statement::Break => "`(break)`",
+        Statement::Continue => "`(continue)`",
+        Statement::Return(_) => "`(return)`",
+        Statement::DoBlock(statements) => statements
+            .iter()
+            .find(|statement| diverges(statement))
+            .map_or("an endless `for` loop", divergence),
+        Statement::IfElse(condition, then, _) if !diverges(condition) => divergence(then),
+        Statement::If(condition, _) | Statement::IfElse(condition, ..) => divergence(condition),
+        Statement::DefVar(def) => divergence(&def.instruction),
+        _ => "an endless `for` loop",
+    }
+}
+
+impl Visitor for Linter<'_> {
+    fn visit_statement(&mut self, statement: &Statement) {
+        if let Statement::DoBlock(statements) = statement {
+            if let Some(position) = statements.iter().position(diverges) {
+                if position + 1 < statements.len() {
+                    self.warn(
+                        UNREACHABLE,
+                        self.item,
+                        format!(
+                            "unreachable code after {}",
+                            divergence(&statements[position])
+                        ),
+                    );
+                }
+            }
+        }
+        walk_statement(self, statement)
+    }
+}
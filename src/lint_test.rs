@@ -0,0 +1,129 @@
+#[cfg(test)]
+mod tests {
+    use crate::lint::lint;
+    use crate::parser::parser;
+    use crate::resolve::resolve;
+    use chumsky::Parser;
+
+    fn warnings(src: &str) -> Vec<String> {
+        let module = parser().parse(src).into_output().unwrap();
+        let resolution = resolve(&module);
+        assert!(!resolution.has_errors(), "{:?}", resolution.diagnostics);
+        lint(&module, &resolution)
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_unexported_defs_are_dead() {
+        let src = "(def helper (fn [(:x i32)] i32 (* x 2)))
+            (def used-by-helper 3)
+            (def dead (fn [] i32 (helper used-by-helper)))
+            (def api (fn [(:x i32)] i32 (helper x)))
+            (def LIMIT 10)
+            (export api)";
+        assert_eq!(
+            warnings(src),
+            vec![
+                "`used-by-helper` is not exported and never used",
+                "`dead` is not exported and never used",
+                "`LIMIT` is not exported and never used",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_main_and_export_all_are_roots() {
+        let src = "(def square (fn [(:x i32)] i32 (* x x)))
+            (def main (fn [] i32 (square 3)))";
+        assert!(warnings(src).is_empty());
+        assert!(warnings("(def x 1) (export :all)").is_empty());
+    }
+
+    #[test]
+    fn test_unused_imports() {
+        let src = "(def math (use \"math\"))
+            (def stdio (use :header \"stdio.h\"))
+            (def io (use \"io\"))
+            (def main (fn [] f64 math/PI))
+            (export io/print)";
+        assert_eq!(warnings(src), vec!["unused import `stdio`"]);
+    }
+
+    #[test]
+    fn test_unused_locals_and_parameters() {
+        let src = "(def main (fn [(:argc i32) (:_argv i32)] i32
+              (do
+                (def kept 1)
+                (def unused 2)
+                (def overwritten 3)
+                (def overwritten 4)
+                kept)))";
+        assert_eq!(
+            warnings(src),
+            vec![
+                "unused parameter `argc`",
+                "unused local `unused`",
+                "unused local `overwritten`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unreachable_after_endless_loop() {
+        let src = "(def main (fn [] i32
+              (do
+                (for true (do))
+                0)))";
+        assert_eq!(
+            warnings(src),
+            vec!["unreachable code after an endless `for` loop"]
+        );
+    }
+
+    #[test]
+    fn test_break_ends_endless_loops() {
+        let src = "(def main (fn [] i32
+              (do
+                (for true (do (for true (break)) (break)))
+                (for (range i 3) (do (continue) (def _x i)))
+                0)))";
+        assert_eq!(warnings(src), vec!["unreachable code after `(continue)`"]);
+        // Only a `break` of the loop itself ends it.
+        let src = "(def main (fn [] i32
+              (do
+                (for true (for (range i 3) (break)))
+                0)))";
+        assert_eq!(
+            warnings(src),
+            vec!["unreachable code after an endless `for` loop"]
+        );
+    }
+
+    #[test]
+    fn test_unreachable_after_return() {
+        let src = "(def absolute (fn [(:x i32)] i32
+              (do
+                (if (< x 0) (return (- 0 x)) (do))
+                (return x)
+                0)))
+            (export absolute)";
+        assert_eq!(warnings(src), vec!["unreachable code after `(return)`"]);
+    }
+
+    #[test]
+    fn test_allow_silences_lints() {
+        let src = "(allow :unused-def :unused-param)
+            (def f (fn [(:x i32)] i32 (do (def y 1) 0)))";
+        assert_eq!(warnings(src), vec!["unused local `y`"]);
+        assert!(warnings(&format!("(allow :all) {}", src)).is_empty());
+        assert_eq!(
+            warnings("(allow :unused) (export :all)"),
+            vec![
+                "unknown lint `:unused`; expected `:all` or one of :unused-def, :unused-import, \
+                 :unused-local, :unused-param, :unreachable"
+            ]
+        );
+    }
+}
@@ -1,50 +1,141 @@
-use chumsky::Parser;
-use tahini::parser::var_type;
-use tahini::parser::parser;
-use tahini::parser::statement;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use tahini::driver::{self, Backend, Emit, Options};
+use tahini::interp::{self, ffi::Libraries};
+use tahini::repl;
 
-fn main() {
-    // Test the generic struct parser directly
-    let input = "(struct<T> (:a T) (:b f64))";
-    let result = var_type().parse(input);
-    println!("Generic struct parse result: {:?}", result);
-    
-    let input = "(struct<K V> (:a K) (:b V))";
-    let result = var_type().parse(input);
-    println!("Generic struct with multiple params: {:?}", result);
-    
-    // Original code
-    let input = "(def a 10)";
-    let result = parser().parse(input);
-    println!("Original parser result: {:?}", result);
-    
-    // Test with a super simple if statement
-    let simple_if = "(if true 1 2)";
-    let result = statement().parse(simple_if);
-    println!("Simple if statement result: {:?}", result);
-    
-    // Test with a simple call
-    let call_test = "(print 123)";
-    let result = statement().parse(call_test);
-    println!("Simple call result: {:?}", result);
-    
-    // Test with a more complex call
-    let call_test2 = "(< a b)";
-    let result = statement().parse(call_test2);
-    println!("Call with operator result: {:?}", result);
-    
-    // Test with a more complex if statement
-    let test2 = "(if (< a b) a b)";
-    let result = statement().parse(test2);
-    println!("Complex if statement result: {:?}", result);
-    
-    // Test fibonacci example
-    let test3 = "(if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))";
-    let result = statement().parse(test3);
-    println!("Fibonacci if statement result: {:?}", result);
-    
-    // Test the whole fibonacci function definition
-    let full_str = "(def fib (fn [(:n i32)] i32 (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))))";
-    let full_result = parser().parse(full_str);
-    println!("Full fibonacci function result: {:?}", full_result);
+const USAGE: &str = "\
+usage: tahini build [options] <file.th>...
+       tahini repl [-L <dir>] [-l <lib>]
+
+options:
+  --emit=ast|ir|c|h|llvm|obj|exe  what to produce (default: exe)
+  --backend=c|llvm                how to compile objects (default: c)
+  -o <file>                       the output file, `-` for standard output
+  -I <dir>                        add a header directory for the C compiler
+  -L <dir>                        add a library directory when linking, or for the REPL
+  -l <lib>                        link with a library, or load it into the REPL
+  --no-gc                         allocate with malloc instead of the Boehm GC
+";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("build") => match parse(&args[1..]) {
+            Ok((inputs, options)) => build(&inputs, &options),
+            Err(error) => {
+                eprint!("error: {}\n\n{}", error, USAGE);
+                ExitCode::FAILURE
+            }
+        },
+        Some("repl") => match libraries(&args[1..]) {
+            Ok(libraries) => repl(libraries),
+            Err(error) => {
+                eprint!("error: {}\n\n{}", error, USAGE);
+                ExitCode::FAILURE
+            }
+        },
+        Some("help" | "-h" | "--help") => {
+            print!("{}", USAGE);
+            ExitCode::SUCCESS
+        }
+        _ => {
+            eprint!("{}", USAGE);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn build(inputs: &[PathBuf], options: &Options) -> ExitCode {
+    match driver::build(inputs, options) {
+        Ok(warnings) => {
+            warnings.iter().for_each(|warning| eprintln!("{}", warning));
+            ExitCode::SUCCESS
+        }
+        Err(messages) => {
+            messages.iter().for_each(|message| eprintln!("{}", message));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run the REPL on a thread with a large stack: the interpreter recurses for every nested
+/// expression and call.
+fn repl(libraries: Libraries) -> ExitCode {
+    let session = std::thread::Builder::new()
+        .stack_size(interp::STACK_SIZE)
+        .spawn(|| repl::run(libraries, std::io::stdin().lock(), &mut std::io::stdout()));
+    match session.map(|thread| thread.join()) {
+        Ok(Ok(Ok(()))) => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    }
+}
+
+/// The libraries the REPL calls C functions in, from its `-L` and `-l` options.
+fn libraries(args: &[String]) -> Result<Libraries, String> {
+    let mut libraries = Libraries::default();
+    let (mut dirs, mut libs) = (Vec::new(), Vec::new());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, list) = match arg.get(..2) {
+            Some("-L") => ("-L", &mut dirs),
+            Some("-l") => ("-l", &mut libs),
+            _ => return Err(format!("unknown option `{}`", arg)),
+        };
+        match &arg[2..] {
+            "" => list.push(
+                args.next()
+                    .map(String::as_str)
+                    .ok_or(format!("`{}` needs a value", flag))?,
+            ),
+            value => list.push(value),
+        }
+    }
+    dirs.iter().for_each(|dir| libraries.add_dir(dir));
+    for lib in libs {
+        libraries.open(lib)?;
+    }
+    Ok(libraries)
+}
+
+fn parse(args: &[String]) -> Result<(Vec<PathBuf>, Options), String> {
+    let mut options = Options::default();
+    let mut inputs = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| match arg.strip_prefix(flag) {
+            Some("") => args
+                .next()
+                .cloned()
+                .ok_or(format!("`{}` needs a value", flag)),
+            Some(value) => Ok(value.to_string()),
+            None => unreachable!(),
+        };
+        match arg.as_str() {
+            _ if arg.starts_with("--emit=") => {
+                let kind = &arg["--emit=".len()..];
+                options.emit = Emit::parse(kind).ok_or(format!("unknown output `{}`", kind))?;
+            }
+            _ if arg.starts_with("--backend=") => {
+                options.backend = match &arg["--backend=".len()..] {
+                    "c" => Backend::C,
+                    "llvm" => Backend::Llvm,
+                    other => return Err(format!("unknown backend `{}`", other)),
+                };
+            }
+            _ if arg.starts_with("-o") => options.output = Some(PathBuf::from(value("-o")?)),
+            "--no-gc" => options.gc = false,
+            _ if arg.starts_with("-I") => options.include_dirs.push(value("-I")?),
+            _ if arg.starts_with("-L") => options.lib_dirs.push(value("-L")?),
+            _ if arg.starts_with("-l") => options.libs.push(value("-l")?),
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option `{}`", arg))
+            }
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+    if inputs.is_empty() {
+        return Err("no input files".to_string());
+    }
+    Ok((inputs, options))
 }
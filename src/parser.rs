@@ -1,10 +1,17 @@
-use crate::ast::{DefVar, FnDef, Literal, Statement, TopLevelDef, TopLevelStatement, VarType};
+use crate::ast::{
+    DefVar, FnDef, Generic, Literal, Range, Statement, TopLevelDef, TopLevelStatement, VarType,
+};
 use chumsky::prelude::*;
 
-fn ident<'a>() -> impl Parser<'a, &'a str, String> + Clone {
+pub type Span = SimpleSpan;
+
+/// Parsers report what they expected and found where they fail.
+pub type Extra<'a> = extra::Err<Rich<'a, char>>;
+
+fn ident<'a>() -> impl Parser<'a, &'a str, String, Extra<'a>> + Clone {
     let keywords = [
         "def", "fn", "if", "do", "use", "array", "ptr", "data", "struct", "tuple", "...", "true",
-        "false", "range", "for", "return", "export", "type",
+        "false", "range", "for", "break", "continue", "return", "export", "type",
     ];
 
     let forbidden_chars = [
@@ -15,8 +22,9 @@ fn ident<'a>() -> impl Parser<'a, &'a str, String> + Clone {
         .filter(move |c: &char| !forbidden_chars.contains(c) && !c.is_numeric())
         .filter(|c: &char| !c.is_whitespace());
 
+    // Primes are allowed after the first character, as in `x'` or `T'`.
     let rest = any()
-        .filter(move |c: &char| !forbidden_chars.contains(c))
+        .filter(move |c: &char| *c == '\'' || !forbidden_chars.contains(c))
         .repeated()
         .collect::<String>();
 
@@ -25,16 +33,17 @@ fn ident<'a>() -> impl Parser<'a, &'a str, String> + Clone {
         .map(|(first_char, rest): (char, String)| format!("{}{}", first_char, rest))
         .filter(move |s: &String| !keywords.contains(&s.as_str()))
         .or(operator_ident())
+        .labelled("identifier")
 }
 
-fn operator_ident<'a>() -> impl Parser<'a, &'a str, String> + Clone {
+fn operator_ident<'a>() -> impl Parser<'a, &'a str, String, Extra<'a>> + Clone {
     one_of("+*/%&|^!=<>-")
         .repeated()
         .at_least(1)
         .collect::<String>()
 }
 
-pub fn literal<'a>() -> impl Parser<'a, &'a str, Literal> + Clone {
+pub fn literal<'a>() -> impl Parser<'a, &'a str, Literal, Extra<'a>> + Clone {
     let int = text::int(10).map(|s: &str| Literal::Int(s.parse().unwrap()));
 
     let float = text::int(10)
@@ -65,8 +74,8 @@ pub fn literal<'a>() -> impl Parser<'a, &'a str, Literal> + Clone {
 }
 
 pub fn literal_with_statement<'a>(
-    inner: impl Parser<'a, &'a str, Statement> + Clone,
-) -> impl Parser<'a, &'a str, Literal> + Clone {
+    inner: impl Parser<'a, &'a str, Statement, Extra<'a>> + Clone,
+) -> impl Parser<'a, &'a str, Literal, Extra<'a>> + Clone {
     let tuple = just("{")
         .ignore_then(inner.clone().repeated().at_least(1).collect::<Vec<_>>())
         .then_ignore(just("}"))
@@ -93,12 +102,35 @@ pub fn literal_with_statement<'a>(
     ))
 }
 
-fn generics<'a>() -> impl Parser<'a, &'a str, Vec<String>> + Clone {
+/// Type parameters such as `<T U>`, optionally bounded as in `<T:numeric>`.
+fn generics<'a>() -> impl Parser<'a, &'a str, Vec<Generic>, Extra<'a>> + Clone {
+    let generic = ident()
+        .then(just(":").ignore_then(ident()).or_not())
+        .map(|(name, bound)| Generic { name, bound });
+
+    just("<")
+        .ignore_then(
+            // `>` would otherwise parse as an operator identifier.
+            just(">")
+                .padded()
+                .not()
+                .ignore_then(generic.padded())
+                .repeated()
+                .at_least(1)
+                .collect::<Vec<_>>(),
+        )
+        .then_ignore(just(">"))
+        .padded()
+}
+
+/// Type arguments of a generic call such as `(id<i32> 1)`, kept as source text.
+fn type_args<'a>() -> impl Parser<'a, &'a str, Vec<String>, Extra<'a>> + Clone {
     just("<")
         .ignore_then(
-            text::unicode::ident()
-                .map(|s: &str| s.to_string())
+            just(">")
                 .padded()
+                .not()
+                .ignore_then(ident().padded())
                 .repeated()
                 .at_least(1)
                 .collect::<Vec<_>>(),
@@ -107,7 +139,25 @@ fn generics<'a>() -> impl Parser<'a, &'a str, Vec<String>> + Clone {
         .padded()
 }
 
-pub fn var_type<'a>() -> impl Parser<'a, &'a str, VarType> + Clone {
+/// An array size: a number, the name of a constant, or a call of those, as in `(* N 2)`.
+fn const_expr<'a>() -> impl Parser<'a, &'a str, Statement, Extra<'a>> + Clone {
+    recursive(|const_expr| {
+        let call = just("(")
+            .padded()
+            .ignore_then(ident().padded())
+            .then(const_expr.padded().repeated().collect::<Vec<_>>())
+            .then_ignore(just(")"))
+            .map(|(name, args)| Statement::Call(name, args));
+
+        choice((
+            text::int(10).map(|s: &str| Statement::Literal(Literal::Int(s.parse().unwrap()))),
+            call,
+            ident().map(Statement::Ident),
+        ))
+    })
+}
+
+pub fn var_type<'a>() -> impl Parser<'a, &'a str, VarType, Extra<'a>> + Clone {
     recursive(|var_type_rec| {
         // Basic types
         let basic_type = choice((
@@ -140,6 +190,13 @@ pub fn var_type<'a>() -> impl Parser<'a, &'a str, VarType> + Clone {
                 VarType::ArraySized(Box::from(var_type), size)
             });
 
+        let array_const_sized = just("[")
+            .padded()
+            .ignore_then(var_type_rec.clone().padded())
+            .then(const_expr().padded())
+            .then_ignore(just("]"))
+            .map(|(var_type, size)| VarType::ArrayConstSized(Box::from(var_type), Box::new(size)));
+
         let array_unsized = just("[")
             .padded()
             .ignore_then(var_type_rec.clone().padded())
@@ -149,10 +206,13 @@ pub fn var_type<'a>() -> impl Parser<'a, &'a str, VarType> + Clone {
         let array_start = just("(").padded().ignore_then(just("array"));
         let array_long_form = array_start
             .ignore_then(var_type_rec.clone().padded())
-            .then(text::int(10).padded().or_not())
+            .then(const_expr().padded().or_not())
             .then_ignore(just(")"))
-            .map(|(var_type, size): (VarType, Option<&'a str>)| match size {
-                Some(size) => VarType::ArraySized(Box::from(var_type), size.parse().unwrap()),
+            .map(|(var_type, size)| match size {
+                Some(Statement::Literal(Literal::Int(size))) => {
+                    VarType::ArraySized(Box::from(var_type), size as usize)
+                }
+                Some(size) => VarType::ArrayConstSized(Box::from(var_type), Box::new(size)),
                 None => VarType::ArrayUnsized(Box::from(var_type)),
             });
 
@@ -160,17 +220,17 @@ pub fn var_type<'a>() -> impl Parser<'a, &'a str, VarType> + Clone {
             .ignore_then(generics())
             .then(text::int(10).padded().or_not())
             .then_ignore(just(")"))
-            .map(|(generic_types, size): (Vec<String>, Option<&'a str>)| {
+            .map(|(generic_types, size): (Vec<Generic>, Option<&'a str>)| {
                 if generic_types.len() != 1 {
                     panic!("Generic array must have exactly one type parameter");
                 }
 
                 match size {
                     Some(size) => VarType::GenericArraySized(
-                        generic_types[0].to_string(),
+                        generic_types[0].name.clone(),
                         size.parse().unwrap(),
                     ),
-                    None => VarType::GenericArrayUnsized(generic_types[0].to_string()),
+                    None => VarType::GenericArrayUnsized(generic_types[0].name.clone()),
                 }
             });
 
@@ -193,7 +253,7 @@ pub fn var_type<'a>() -> impl Parser<'a, &'a str, VarType> + Clone {
                     panic!("Pointer generics must have exactly one type parameter");
                 }
 
-                VarType::GenericPtr(generic_types[0].to_string())
+                VarType::GenericPtr(generic_types[0].name.clone())
             });
 
         // Tuple
@@ -225,7 +285,7 @@ pub fn var_type<'a>() -> impl Parser<'a, &'a str, VarType> + Clone {
             .then_ignore(just(")"))
             .map(|(generic_types, types)| {
                 if let Some(generics) = generic_types {
-                    VarType::GenericTuple(generics.iter().map(|s| s.to_string()).collect(), types)
+                    VarType::GenericTuple(generics, types)
                 } else {
                     VarType::Tuple(types)
                 }
@@ -247,17 +307,11 @@ pub fn var_type<'a>() -> impl Parser<'a, &'a str, VarType> + Clone {
             .then(var_type_rec.clone())
             .map(
                 |((generic_types, (args, is_va)), ret)| match (generic_types, is_va) {
-                    (Some(generics), true) => VarType::GenericFnWithVarArgs(
-                        generics.iter().map(|s| s.to_string()).collect(),
-                        args,
-                        Box::new(ret),
-                    ),
+                    (Some(generics), true) => {
+                        VarType::GenericFnWithVarArgs(generics, args, Box::new(ret))
+                    }
                     (None, true) => VarType::FnWithVarArgs(args, Box::new(ret)),
-                    (Some(generics), false) => VarType::GenericFn(
-                        generics.iter().map(|s| s.to_string()).collect(),
-                        args,
-                        Box::new(ret),
-                    ),
+                    (Some(generics), false) => VarType::GenericFn(generics, args, Box::new(ret)),
                     (None, false) => VarType::Fn(args, Box::new(ret)),
                 },
             );
@@ -270,7 +324,7 @@ pub fn var_type<'a>() -> impl Parser<'a, &'a str, VarType> + Clone {
             .then(var_type_rec.clone().padded().repeated().collect::<Vec<_>>())
             .then_ignore(just("]"));
 
-        type DataParseType<'a> = (Option<Vec<String>>, Vec<(String, Vec<VarType>)>);
+        type DataParseType<'a> = (Option<Vec<Generic>>, Vec<(String, Vec<VarType>)>);
 
         let data = just("(")
             .padded()
@@ -295,7 +349,7 @@ pub fn var_type<'a>() -> impl Parser<'a, &'a str, VarType> + Clone {
 
                 if let Some(generics) = generic_types {
                     VarType::GenericData(
-                        generics.iter().map(|s| s.to_string()).collect(),
+                        generics,
                         fields
                             .into_iter()
                             .map(|(name, types)| (name.to_string(), types))
@@ -335,7 +389,7 @@ pub fn var_type<'a>() -> impl Parser<'a, &'a str, VarType> + Clone {
             )
             .then_ignore(just(")"))
             .map(
-                |(generic_types, fields): (Option<Vec<String>>, Vec<(String, VarType)>)| {
+                |(generic_types, fields): (Option<Vec<Generic>>, Vec<(String, VarType)>)| {
                     let mut field_names = std::collections::HashSet::new();
                     for (name, _) in &fields {
                         if !field_names.insert(name.clone()) {
@@ -344,19 +398,35 @@ pub fn var_type<'a>() -> impl Parser<'a, &'a str, VarType> + Clone {
                     }
 
                     if let Some(generics) = generic_types {
-                        VarType::GenericStruct(
-                            generics.iter().map(|s| s.to_string()).collect(),
-                            fields,
-                        )
+                        VarType::GenericStruct(generics, fields)
                     } else {
                         VarType::Struct(fields)
                     }
                 },
             );
 
+        // Instantiated generic type, e.g. `pair<i32 f64>`
+        let generic_instance = ident()
+            .then(
+                just("<")
+                    .ignore_then(
+                        // `>` would otherwise parse as an operator identifier.
+                        just(">")
+                            .padded()
+                            .not()
+                            .ignore_then(var_type_rec.clone().padded())
+                            .repeated()
+                            .at_least(1)
+                            .collect::<Vec<_>>(),
+                    )
+                    .then_ignore(just(">")),
+            )
+            .map(|(name, args)| VarType::GenericInstance(name, args));
+
         choice((
             basic_type,
             array_sized,
+            array_const_sized,
             array_unsized,
             generic_array_long_form,
             array_long_form,
@@ -366,23 +436,27 @@ pub fn var_type<'a>() -> impl Parser<'a, &'a str, VarType> + Clone {
             tuple,
             fn_type,
             struct_parser,
+            generic_instance,
             ident().map(VarType::IdentType),
         ))
         .padded()
     })
 }
 
-fn top_level_var_instruction<'a>() -> impl Parser<'a, &'a str, TopLevelDef> + Clone {
+fn top_level_var_instruction<'a>() -> impl Parser<'a, &'a str, TopLevelDef, Extra<'a>> + Clone {
     let literal = literal().map(TopLevelDef::Literal);
     let typed = var_type().map(TopLevelDef::Typed);
     let function = function_statement(statement()).map(TopLevelDef::FnDef);
+    let constant = statement()
+        .filter(|s| !matches!(s, Statement::DefVar(_) | Statement::Declare(..)))
+        .map(TopLevelDef::Const);
 
-    choice((literal, typed, function))
+    choice((literal, typed, function, constant))
 }
 
 fn def_statement<'a, T>(
-    inner: impl Parser<'a, &'a str, T> + Clone,
-) -> impl Parser<'a, &'a str, DefVar<T>> + Clone
+    inner: impl Parser<'a, &'a str, T, Extra<'a>> + Clone,
+) -> impl Parser<'a, &'a str, DefVar<T>, Extra<'a>> + Clone
 where
     T: Clone,
 {
@@ -399,7 +473,7 @@ where
         })
 }
 
-fn function_parameters<'a>() -> impl Parser<'a, &'a str, (String, VarType)> + Clone {
+fn function_parameters<'a>() -> impl Parser<'a, &'a str, (String, VarType), Extra<'a>> + Clone {
     just("(")
         .padded()
         .ignore_then(just(":"))
@@ -409,10 +483,10 @@ fn function_parameters<'a>() -> impl Parser<'a, &'a str, (String, VarType)> + Cl
 }
 
 fn function_statement<'a>(
-    inner: impl Parser<'a, &'a str, Statement> + Clone,
-) -> impl Parser<'a, &'a str, FnDef> + Clone {
+    inner: impl Parser<'a, &'a str, Statement, Extra<'a>> + Clone,
+) -> impl Parser<'a, &'a str, FnDef, Extra<'a>> + Clone {
     type FnParseResult = (
-        ((Option<Vec<String>>, Vec<(String, VarType)>), VarType),
+        ((Option<Vec<Generic>>, Vec<(String, VarType)>), VarType),
         Statement,
     );
 
@@ -438,11 +512,11 @@ fn function_statement<'a>(
         )
 }
 
-pub fn statement<'a>() -> impl Parser<'a, &'a str, Statement> + Clone {
+pub fn statement<'a>() -> impl Parser<'a, &'a str, Statement, Extra<'a>> + Clone {
     recursive(|statement_rec| {
         let statement_without_def = statement_rec
             .clone()
-            .filter(|s| !matches!(s, Statement::DefVar(_)));
+            .filter(|s| !matches!(s, Statement::DefVar(_) | Statement::Declare(..)));
 
         let do_block = just("(")
             .padded()
@@ -465,13 +539,36 @@ pub fn statement<'a>() -> impl Parser<'a, &'a str, Statement> + Clone {
             .then_ignore(just(")"))
             .map(|(iter, block)| Statement::For(Box::new(iter), Box::new(block)));
 
+        let slice = just(":slice")
+            .padded()
+            .ignore_then(statement_without_def.clone().padded())
+            .then(statement_without_def.clone().padded())
+            .then(statement_without_def.clone().padded())
+            .map(|((array, start), end)| Range::Slice(array, start, end));
+
+        let bounds = statement_without_def
+            .clone()
+            .padded()
+            .repeated()
+            .at_least(1)
+            .at_most(3)
+            .collect::<Vec<_>>()
+            .map(|parts| {
+                let mut parts = parts.into_iter();
+                let first = parts.next().unwrap();
+                match parts.next() {
+                    None => Range::Each(first),
+                    Some(end) => Range::Bounds(first, end, parts.next()),
+                }
+            });
+
         let for_range_statement = just("(")
             .padded()
             .ignore_then(just("for").padded())
             .ignore_then(just("(").padded())
             .ignore_then(just("range").padded())
             .ignore_then(ident().padded())
-            .then(statement_without_def.clone())
+            .then(slice.or(bounds))
             .then_ignore(just(")"))
             .then(statement_rec.clone())
             .then_ignore(just(")"))
@@ -481,6 +578,19 @@ pub fn statement<'a>() -> impl Parser<'a, &'a str, Statement> + Clone {
 
         let for_statement = for_statement.or(for_range_statement);
 
+        let jump = just("(")
+            .padded()
+            .ignore_then(
+                just("break")
+                    .to(Statement::Break)
+                    .or(just("continue").to(Statement::Continue))
+                    .or(just("return")
+                        .ignore_then(statement_without_def.clone().padded().or_not())
+                        .map(|value| Statement::Return(value.map(Box::new))))
+                    .padded(),
+            )
+            .then_ignore(just(")"));
+
         // Call
         let call = just("(")
             .padded()
@@ -499,7 +609,7 @@ pub fn statement<'a>() -> impl Parser<'a, &'a str, Statement> + Clone {
         let generic_call = just("(")
             .padded()
             .ignore_then(ident().padded())
-            .then(generics())
+            .then(type_args())
             .then(
                 statement_without_def
                     .clone()
@@ -529,6 +639,12 @@ pub fn statement<'a>() -> impl Parser<'a, &'a str, Statement> + Clone {
 
         let def = def_statement(statement_rec.clone()).map(|def| Statement::DefVar(def.boxed()));
 
+        let declare = just("(")
+            .ignore_then(just("def").padded())
+            .ignore_then(function_parameters().padded())
+            .then_ignore(just(")"))
+            .map(|(name, var_type)| Statement::Declare(name, var_type));
+
         let dollar_operator_field = just("(")
             .ignore_then(just("$").padded())
             .ignore_then(just(":"))
@@ -565,7 +681,9 @@ pub fn statement<'a>() -> impl Parser<'a, &'a str, Statement> + Clone {
         choice((
             if_statement,
             for_statement,
+            jump,
             do_block,
+            declare,
             def,
             generic_call,
             dollar_operator,
@@ -577,7 +695,7 @@ pub fn statement<'a>() -> impl Parser<'a, &'a str, Statement> + Clone {
     })
 }
 
-pub fn parser<'a>() -> impl Parser<'a, &'a str, Vec<TopLevelStatement>> {
+fn top_level_statement<'a>() -> impl Parser<'a, &'a str, TopLevelStatement, Extra<'a>> + Clone {
     let use_statement = just("(")
         .ignore_then(just("use").padded())
         .ignore_then(just(":header").padded().or_not().map(|o| o.is_some()))
@@ -626,7 +744,33 @@ pub fn parser<'a>() -> impl Parser<'a, &'a str, Vec<TopLevelStatement>> {
         .then_ignore(just(")"))
         .map(|names: Vec<String>| TopLevelStatement::Export(names));
 
-    choice((def, type_alias, export_all, export))
+    let allow = just("(")
+        .ignore_then(just("allow").padded())
+        .ignore_then(
+            just(":")
+                .ignore_then(ident())
+                .padded()
+                .repeated()
+                .collect::<Vec<_>>(),
+        )
+        .then_ignore(just(")"))
+        .map(TopLevelStatement::Allow);
+
+    choice((def, type_alias, export_all, export, allow))
+}
+
+pub fn parser<'a>() -> impl Parser<'a, &'a str, Vec<TopLevelStatement>, Extra<'a>> {
+    top_level_statement()
+        .padded()
+        .repeated()
+        .collect::<Vec<_>>()
+}
+
+/// Like [`parser`], but pairs every top-level statement with its source span so that
+/// later passes can point diagnostics (which refer to items by index) back at the source.
+pub fn spanned_parser<'a>() -> impl Parser<'a, &'a str, Vec<(TopLevelStatement, Span)>, Extra<'a>> {
+    top_level_statement()
+        .map_with(|statement, e| (statement, e.span()))
         .padded()
         .repeated()
         .collect::<Vec<_>>()
@@ -45,4 +45,4 @@ mod tests {
         let result = literal().parse(input).into_output().unwrap();
         assert_eq!(result, Literal::Atom("my_atom".to_string()));
     }
-}
\ No newline at end of file
+}
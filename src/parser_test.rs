@@ -1,6 +1,8 @@
 #[cfg(test)]
 mod tests {
-    use crate::ast::{DefVar, FnDef, Literal, Statement, TopLevelDef, TopLevelStatement, VarType};
+    use crate::ast::{
+        DefVar, FnDef, Literal, Range, Statement, TopLevelDef, TopLevelStatement, VarType,
+    };
     use crate::parser::{parser, statement};
     use chumsky::Parser;
 
@@ -36,6 +38,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_top_level_const_def() {
+        let result = parser()
+            .parse("(def area (* PI (square 5.0)))")
+            .into_output()
+            .unwrap();
+        assert_eq!(
+            result[0],
+            TopLevelStatement::TopLevelDef(DefVar {
+                name: "area".to_string(),
+                instruction: TopLevelDef::Const(Statement::Call(
+                    "*".to_string(),
+                    vec![
+                        Statement::Ident("PI".to_string()),
+                        Statement::Call(
+                            "square".to_string(),
+                            vec![Statement::Literal(Literal::Float(5.0))]
+                        ),
+                    ]
+                ))
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_allow() {
+        let result = parser()
+            .parse("(allow :unused-local :unreachable)")
+            .into_output()
+            .unwrap();
+        assert_eq!(
+            result,
+            vec![TopLevelStatement::Allow(vec![
+                "unused-local".to_string(),
+                "unreachable".to_string()
+            ])]
+        );
+    }
+
     #[test]
     fn test_parse_top_level_use() {
         let input = "(def stdio (use :header \"stdio.h\"))\n
@@ -320,6 +361,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_generic_call_with_identifier_arguments() {
+        let input = "(convert<elem-type u8> x)";
+        let result = statement().parse(input).into_output().unwrap();
+        assert_eq!(
+            result,
+            Statement::GenericCall(
+                "convert".to_string(),
+                vec!["elem-type".to_string(), "u8".to_string()],
+                vec![Statement::Ident("x".to_string())]
+            )
+        );
+    }
+
     #[test]
     fn test_parse_dollar_operator() {
         let input = "($ [0] a)";
@@ -378,6 +433,85 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_for_range() {
+        let ident = |name: &str| Statement::Ident(name.to_string());
+        let int = |n| Statement::Literal(Literal::Int(n));
+        let range = |input: &str| match statement().parse(input).into_output().unwrap() {
+            Statement::ForRange(name, range, body) => {
+                assert_eq!((name.as_str(), *body), ("x", ident("x")));
+                *range
+            }
+            other => panic!("not a range loop: {:?}", other),
+        };
+
+        assert_eq!(range("(for (range x 10) x)"), Range::Each(int(10)));
+        assert_eq!(
+            range("(for (range x 1 n) x)"),
+            Range::Bounds(int(1), ident("n"), None)
+        );
+        assert_eq!(
+            range("(for (range x n 0 step) x)"),
+            Range::Bounds(ident("n"), int(0), Some(ident("step")))
+        );
+        assert_eq!(
+            range("(for (range x :slice xs 2 n) x)"),
+            Range::Slice(ident("xs"), int(2), ident("n"))
+        );
+        assert!(statement()
+            .parse("(for (range x 1 2 3 4) x)")
+            .into_output()
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_break_and_continue() {
+        let result = statement()
+            .parse("(for true (if done (break) (continue)))")
+            .into_output()
+            .unwrap();
+        assert_eq!(
+            result,
+            Statement::For(
+                Box::new(Statement::Literal(Literal::Bool(true))),
+                Box::new(Statement::IfElse(
+                    Box::new(Statement::Ident("done".to_string())),
+                    Box::new(Statement::Break),
+                    Box::new(Statement::Continue)
+                ))
+            )
+        );
+        assert!(statement().parse("(break 1)").into_output().is_none());
+    }
+
+    #[test]
+    fn test_parse_return() {
+        assert_eq!(
+            statement()
+                .parse("(if done (return) (return (+ x 1)))")
+                .into_output(),
+            Some(Statement::IfElse(
+                Box::new(Statement::Ident("done".to_string())),
+                Box::new(Statement::Return(None)),
+                Box::new(Statement::Return(Some(Box::new(Statement::Call(
+                    "+".to_string(),
+                    vec![
+                        Statement::Ident("x".to_string()),
+                        Statement::Literal(Literal::Int(1))
+                    ]
+                )))))
+            ))
+        );
+        assert!(statement().parse("(return 1 2)").into_output().is_none());
+        assert_eq!(
+            statement().parse("(returned 1)").into_output(),
+            Some(Statement::Call(
+                "returned".to_string(),
+                vec![Statement::Literal(Literal::Int(1))]
+            ))
+        );
+    }
+
     #[test]
     fn test_fn_literal() {
         let input = "(fn [(:a i32) (:b f64)] i32 (if (< a b) a b))";
@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use crate::ast::VarType;
+    use crate::ast::{Generic, Literal, Statement, VarType};
     use crate::parser::var_type;
     use chumsky::Parser;
 
@@ -46,6 +46,32 @@ mod tests {
         assert_eq!(result, VarType::ArraySized(Box::new(VarType::Int32), 10));
     }
 
+    #[test]
+    fn test_type_array_const_sized() {
+        let result = var_type().parse("[i32 (* N 2)]").into_output().unwrap();
+        let size = Statement::Call(
+            "*".to_string(),
+            vec![
+                Statement::Ident("N".to_string()),
+                Statement::Literal(Literal::Int(2)),
+            ],
+        );
+        assert_eq!(
+            result,
+            VarType::ArrayConstSized(Box::new(VarType::Int32), Box::new(size))
+        );
+        assert_eq!(result.to_string(), "[i32 (* N 2)]");
+
+        let long_form = var_type().parse("(array u8 N)").into_output().unwrap();
+        assert_eq!(
+            long_form,
+            VarType::ArrayConstSized(
+                Box::new(VarType::UInt8),
+                Box::new(Statement::Ident("N".to_string()))
+            )
+        );
+    }
+
     #[test]
     fn test_type_ptr() {
         let input = "(ptr i32)";
@@ -156,7 +182,7 @@ mod tests {
         assert_eq!(
             result,
             VarType::GenericFn(
-                vec!["T".to_string()],
+                vec![Generic::new("T")],
                 vec![VarType::Int32, VarType::Float64],
                 Box::new(VarType::Int32)
             )
@@ -167,7 +193,7 @@ mod tests {
         assert_eq!(
             result,
             VarType::GenericFn(
-                vec!["K".to_string(), "V".to_string()],
+                vec![Generic::new("K"), Generic::new("V")],
                 vec![VarType::Int32, VarType::Float64],
                 Box::new(VarType::Int32)
             )
@@ -178,7 +204,7 @@ mod tests {
         assert_eq!(
             result,
             VarType::GenericFn(
-                vec!["K".to_string(), "V".to_string()],
+                vec![Generic::new("K"), Generic::new("V")],
                 vec![VarType::Int32, VarType::Float64],
                 Box::new(VarType::Int32)
             )
@@ -192,7 +218,7 @@ mod tests {
         assert_eq!(
             result,
             VarType::GenericFnWithVarArgs(
-                vec!["T".to_string()],
+                vec![Generic::new("T")],
                 vec![VarType::Int32, VarType::Float64],
                 Box::new(VarType::Int32)
             )
@@ -203,7 +229,7 @@ mod tests {
         assert_eq!(
             result,
             VarType::GenericFnWithVarArgs(
-                vec!["K".to_string(), "V".to_string()],
+                vec![Generic::new("K"), Generic::new("V")],
                 vec![VarType::Int32, VarType::Float64],
                 Box::new(VarType::Int32)
             )
@@ -214,7 +240,7 @@ mod tests {
         assert_eq!(
             result,
             VarType::GenericFnWithVarArgs(
-                vec!["K".to_string(), "V".to_string()],
+                vec![Generic::new("K"), Generic::new("V")],
                 vec![VarType::Int32, VarType::Float64],
                 Box::new(VarType::Int32)
             )
@@ -241,7 +267,7 @@ mod tests {
         assert_eq!(
             result,
             VarType::GenericStruct(
-                vec!["T".to_string()],
+                vec![Generic::new("T")],
                 vec![
                     ("a".to_string(), VarType::IdentType("T".to_string())),
                     ("b".to_string(), VarType::Float64),
@@ -254,7 +280,7 @@ mod tests {
         assert_eq!(
             result,
             VarType::GenericStruct(
-                vec!["K".to_string(), "V".to_string()],
+                vec![Generic::new("K"), Generic::new("V")],
                 vec![
                     ("a".to_string(), VarType::IdentType("K".to_string())),
                     ("b".to_string(), VarType::IdentType("V".to_string())),
@@ -304,7 +330,7 @@ mod tests {
         assert_eq!(
             result,
             VarType::GenericData(
-                vec!["T".to_string()],
+                vec![Generic::new("T")],
                 vec![
                     ("a".to_string(), vec![VarType::Int32]),
                     ("b".to_string(), vec![VarType::Float64])
@@ -312,4 +338,45 @@ mod tests {
             )
         )
     }
+
+    #[test]
+    fn test_type_generic_instance() {
+        let input = "pair<i32 (ptr list<u8>)>";
+        let result = var_type().parse(input).unwrap();
+        assert_eq!(
+            result,
+            VarType::GenericInstance(
+                "pair".to_string(),
+                vec![
+                    VarType::Int32,
+                    VarType::Ptr(Box::new(VarType::GenericInstance(
+                        "list".to_string(),
+                        vec![VarType::UInt8]
+                    )))
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_type_generic_names_and_bounds() {
+        let input = "fn<elem-type T' N:integer> [elem-type T'] N";
+        let result = var_type().parse(input).unwrap();
+        assert_eq!(
+            result,
+            VarType::GenericFn(
+                vec![
+                    Generic::new("elem-type"),
+                    Generic::new("T'"),
+                    Generic::bounded("N", "integer")
+                ],
+                vec![
+                    VarType::IdentType("elem-type".to_string()),
+                    VarType::IdentType("T'".to_string())
+                ],
+                Box::new(VarType::IdentType("N".to_string()))
+            )
+        );
+        assert_eq!(result.to_string(), input);
+    }
 }
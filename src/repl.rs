@@ -0,0 +1,423 @@
+//! The interactive loop behind `tahini repl`.
+//!
+//! A session keeps the top-level forms (`def`, `type`, `use`, ...) entered so far. Every input
+//! is checked together with them as one module, so a form that does not check leaves the
+//! session as it was, and a definition replaces an earlier one of the same name. An
+//! expression is evaluated as the body of a function returning its type, with a fresh
+//! [`Interpreter`] over the module, and printed with that type.
+//!
+//! A `def` whose value is not a compile-time constant, such as `(def c (make-counter))`, is
+//! evaluated like an expression and kept as a binding of the session. Bindings are the
+//! parameters of the function an expression is evaluated in, so later inputs and `:type` see
+//! them, but top-level functions do not, and changes made to them are lost.
+//!
+//! Inputs starting with `:` are commands:
+//!
+//! - `:type expr` prints the type of `expr`
+//! - `:ast expr` prints the parsed `expr`
+//! - `:expand expr` prints the items `expr` becomes after the transformer: folded, monomorphized
+//!   and with its closures converted
+//! - `:load file.th` adds the top-level forms of a file to the session
+
+use crate::ast::{DefVar, FnDef, Statement, TopLevelDef, TopLevelStatement, VarType};
+use crate::consteval;
+use crate::diagnostic::{render_parse_error, Diagnostic};
+use crate::driver::{self, Options, Unit};
+use crate::interp::ffi::Libraries;
+use crate::interp::value::Value;
+use crate::interp::Interpreter;
+use crate::ir::Program;
+use crate::parser::{spanned_parser, statement};
+use crate::transformer::ast::Module;
+use crate::typeck::Checker;
+use chumsky::Parser;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// The function an expression is evaluated in.
+const INPUT: &str = "it'";
+
+pub struct Session {
+    /// The top-level forms accepted so far.
+    statements: Vec<TopLevelStatement>,
+    /// The values of the `def`s evaluated at run time, with their types.
+    bindings: Vec<(String, VarType, Value)>,
+    /// Import aliases and the modules they name.
+    imports: BTreeMap<String, String>,
+    /// The tahini modules imported so far, including the modules they import.
+    units: Vec<Unit>,
+    /// The directory `use` and `:load` find files relative to.
+    dir: PathBuf,
+    /// Where the functions of C headers are found.
+    libraries: Rc<Libraries>,
+    /// The C compiler and header directories that C headers are imported with.
+    options: Options,
+}
+
+impl Session {
+    pub fn new(dir: PathBuf) -> Self {
+        Session {
+            statements: Vec::new(),
+            bindings: Vec::new(),
+            imports: BTreeMap::new(),
+            units: Vec::new(),
+            dir,
+            libraries: Rc::new(Libraries::default()),
+            options: Options::default(),
+        }
+    }
+
+    /// Call the functions of C headers in `libraries`.
+    pub fn link(&mut self, libraries: Libraries) {
+        self.libraries = Rc::new(libraries);
+    }
+
+    /// Evaluate one complete input. Returns what to print, which is empty for inputs without
+    /// a value, or the errors.
+    pub fn eval(&mut self, input: &str) -> Result<String, String> {
+        let input = input.trim();
+        let Some(command) = input.strip_prefix(':') else {
+            return match spanned_parser().parse(input).into_result() {
+                Ok(forms) => {
+                    let forms: Vec<_> = forms.into_iter().map(|(form, _)| form).collect();
+                    if let [TopLevelStatement::TopLevelDef(DefVar {
+                        name,
+                        instruction: TopLevelDef::Const(value),
+                    })] = forms.as_slice()
+                    {
+                        if consteval::evaluate(&self.statements, value).is_err() {
+                            return self.bind(name, value);
+                        }
+                    }
+                    let dir = self.dir.clone();
+                    self.define(forms, &dir)
+                }
+                Err(_) => self.evaluate(&expression(input)?),
+            };
+        };
+        let (command, argument) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        match command {
+            "type" => Ok(self.type_of(&expression(argument)?)?.to_string()),
+            "ast" => Ok(format!("{:?}", expression(argument)?)),
+            "expand" => self.expand(&expression(argument)?),
+            "load" => self.load(Path::new(argument.trim())),
+            _ => Err(format!("error: unknown command `:{}`", command)),
+        }
+    }
+
+    /// Add `forms`, with imports relative to `dir`, and print the type of every definition.
+    fn define(&mut self, forms: Vec<TopLevelStatement>, dir: &Path) -> Result<String, String> {
+        let mut units = Vec::new();
+        let mut imports = self.imports.clone();
+        for form in &forms {
+            if let TopLevelStatement::Use(alias, import) = form {
+                let file = dir.join(format!("{}.th", import));
+                let loaded = driver::load(&[file]).map_err(|error| format!("error: {}", error))?;
+                imports.insert(alias.clone(), loaded[0].module.clone());
+                units.extend(loaded);
+            }
+        }
+
+        let mut statements = self.statements.clone();
+        for form in &forms {
+            if let Some(name) = defined_name(form) {
+                statements.retain(|other| defined_name(other) != Some(name));
+            }
+        }
+        statements.extend(forms.iter().cloned());
+        // A module used again is read again, with any changes made since.
+        for unit in units {
+            self.units.retain(|other| other.module != unit.module);
+            self.units.push(unit);
+        }
+
+        let unit = self.unit(statements, imports);
+        self.check(&unit)?;
+        let checker = Checker::new(&driver::declarations(&unit, &self.units, &self.options));
+        self.statements = unit.statements;
+        self.imports = unit.imports;
+        self.bindings
+            .retain(|(name, ..)| !forms.iter().any(|form| defined_name(form) == Some(name)));
+
+        let mut output = Vec::new();
+        for form in &forms {
+            if let TopLevelStatement::TopLevelDef(def) = form {
+                if let Some(var_type) = checker.globals.get(&def.name) {
+                    output.push(format!("{} : {}", def.name, var_type));
+                }
+            }
+        }
+        Ok(output.join("\n"))
+    }
+
+    /// Evaluate `expression` and print its value with its type.
+    fn evaluate(&mut self, expression: &Statement) -> Result<String, String> {
+        let var_type = self.type_of(expression)?;
+        let (value, interpreter) = self.call_input(expression, &var_type)?;
+        let interpreter = interpreter.borrow();
+        Ok(match var_type {
+            VarType::Void => String::new(),
+            _ => format!(
+                "{} : {}",
+                value.show(&var_type, interpreter.aliases()),
+                var_type
+            ),
+        })
+    }
+
+    /// Evaluate `value` and keep it as the binding `name`, which replaces any definition of
+    /// that name.
+    fn bind(&mut self, name: &str, value: &Statement) -> Result<String, String> {
+        let var_type = self.type_of(value)?;
+        if var_type == VarType::Void {
+            return Err(format!("error: `{}` is defined without a value", name));
+        }
+        let mut statements = self.statements.clone();
+        statements.retain(|form| defined_name(form) != Some(name));
+        // Definitions can't see run-time bindings, so one that uses the name keeps it defined.
+        if statements.len() < self.statements.len() {
+            self.check(&self.unit(statements.clone(), self.imports.clone()))
+                .map_err(|_| {
+                    format!(
+                        "error: `{}` is used by other definitions and can't be rebound at run time",
+                        name
+                    )
+                })?;
+        }
+        let (value, _) = self.call_input(value, &var_type)?;
+        self.statements = statements;
+        self.bindings.retain(|(other, ..)| other != name);
+        self.bindings
+            .push((name.to_string(), var_type.clone(), value));
+        Ok(format!("{} : {}", name, var_type))
+    }
+
+    /// Call [`INPUT`] with `expression` as its body and the bindings as its arguments.
+    fn call_input(
+        &self,
+        expression: &Statement,
+        var_type: &VarType,
+    ) -> Result<(Value, Rc<RefCell<Interpreter>>), String> {
+        let (module, _) = self.check(&self.with_input(expression, var_type))?;
+        let interpreter = self.interpreter(module)?;
+        let args = self.bindings.iter().map(|(_, _, value)| value.clone());
+        let value = interpreter
+            .borrow_mut()
+            .call(INPUT, args.collect())
+            .map_err(|error| format!("error: {}", error))?;
+        Ok((value, interpreter))
+    }
+
+    fn type_of(&self, expression: &Statement) -> Result<VarType, String> {
+        // The members of C headers that `expression` uses are only declared with it.
+        let unit = self.with_input(expression, &VarType::Void);
+        let mut checker = Checker::new(&driver::declarations(&unit, &self.units, &self.options));
+        let locals: Vec<_> = self
+            .bindings
+            .iter()
+            .map(|(name, var_type, _)| (name.clone(), var_type.clone()))
+            .collect();
+        let var_type = checker.type_with(expression, &locals);
+        let errors = render(&checker.diagnostics);
+        match var_type {
+            _ if !errors.is_empty() => Err(errors),
+            Some(var_type) => Ok(var_type),
+            // Calls of C functions without declarations have no known type.
+            None => Ok(VarType::Void),
+        }
+    }
+
+    /// The items of the transformed module that evaluating `expression` adds.
+    fn expand(&self, expression: &Statement) -> Result<String, String> {
+        let var_type = self.type_of(expression)?;
+        let (before, _) = self.check(&self.unit(self.statements.clone(), self.imports.clone()))?;
+        let (after, _) = self.check(&self.with_input(expression, &var_type))?;
+        let added: Vec<String> = after
+            .items
+            .iter()
+            .filter(|item| !before.items.contains(item))
+            .map(ToString::to_string)
+            .collect();
+        Ok(added.join("\n"))
+    }
+
+    /// Add the top-level forms of `path`.
+    fn load(&mut self, path: &Path) -> Result<String, String> {
+        let path = self.dir.join(path);
+        let source = std::fs::read_to_string(&path)
+            .map_err(|error| format!("error: {}: {}", path.display(), error))?;
+        let forms = spanned_parser()
+            .parse(source.as_str())
+            .into_result()
+            .map_err(|errors| {
+                let rendered = errors.iter().map(|error| {
+                    format!("{}:{}", path.display(), render_parse_error(&source, error))
+                });
+                rendered.collect::<Vec<_>>().join("\n")
+            })?;
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        self.define(forms.into_iter().map(|(form, _)| form).collect(), &dir)
+    }
+
+    /// The session with `expression` as the body of [`INPUT`], whose parameters are the
+    /// bindings.
+    fn with_input(&self, expression: &Statement, var_type: &VarType) -> Unit {
+        let mut statements = self.statements.clone();
+        let parameters = self
+            .bindings
+            .iter()
+            .map(|(name, var_type, _)| (name.clone(), var_type.clone()));
+        statements.push(TopLevelStatement::TopLevelDef(DefVar {
+            name: INPUT.to_string(),
+            instruction: TopLevelDef::FnDef(FnDef {
+                generic_types: None,
+                parameters: parameters.collect(),
+                return_type: var_type.clone(),
+                statement: expression.clone(),
+            }),
+        }));
+        self.unit(statements, self.imports.clone())
+    }
+
+    fn unit(&self, statements: Vec<TopLevelStatement>, imports: BTreeMap<String, String>) -> Unit {
+        Unit {
+            module: "repl".to_string(),
+            path: self.dir.join("repl.th"),
+            source: String::new(),
+            statements,
+            spans: Vec::new(),
+            imports,
+        }
+    }
+
+    /// Check `unit` against the imported modules. Only errors are reported: warnings such as
+    /// unused definitions are the norm in a session.
+    fn check(&self, unit: &Unit) -> Result<(Module, Program), String> {
+        let (lowered, diagnostics) = driver::analyze(unit, &self.units, &self.options);
+        lowered.ok_or_else(|| render(&diagnostics))
+    }
+
+    /// An interpreter of `module` with interpreters of every imported module attached.
+    fn interpreter(&self, module: Module) -> Result<Rc<RefCell<Interpreter>>, String> {
+        let mut modules = HashMap::new();
+        for unit in &self.units {
+            let (module, _) = self.check(unit)?;
+            modules.insert(unit.module.as_str(), self.new_interpreter(module)?);
+        }
+        let session = self.new_interpreter(module)?;
+        let importers = self.units.iter().map(|unit| {
+            let interpreter = &modules[unit.module.as_str()];
+            (interpreter, &unit.imports)
+        });
+        for (interpreter, imports) in importers.chain([(&session, &self.imports)]) {
+            for (alias, module) in imports {
+                if let Some(imported) = modules.get(module.as_str()) {
+                    interpreter.borrow_mut().import(alias, imported.clone());
+                }
+            }
+        }
+        Ok(session)
+    }
+
+    fn new_interpreter(&self, module: Module) -> Result<Rc<RefCell<Interpreter>>, String> {
+        let mut interpreter = Interpreter::new(module).map_err(|errors| render(&errors))?;
+        interpreter.link(self.libraries.clone());
+        Ok(Rc::new(RefCell::new(interpreter)))
+    }
+}
+
+fn expression(input: &str) -> Result<Statement, String> {
+    statement()
+        .parse(input.trim())
+        .into_result()
+        .map_err(|_| "error: the input does not parse".to_string())
+}
+
+/// The name a top-level form defines, which a later form of the same name replaces.
+fn defined_name(form: &TopLevelStatement) -> Option<&str> {
+    match form {
+        TopLevelStatement::TopLevelDef(def) => Some(&def.name),
+        TopLevelStatement::TypeAlias(name, _)
+        | TopLevelStatement::Use(name, _)
+        | TopLevelStatement::UseHeader(name, _) => Some(name),
+        _ => None,
+    }
+}
+
+fn render(diagnostics: &[Diagnostic]) -> String {
+    let mut errors: Vec<String> = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.is_error())
+        .map(ToString::to_string)
+        .collect();
+    errors.dedup();
+    errors.join("\n")
+}
+
+/// How many brackets `text` leaves open, outside of string and character literals. An input
+/// is complete once none are.
+pub fn depth(text: &str) -> isize {
+    let mut depth = 0;
+    let mut chars = text.chars().peekable();
+    let mut previous = ' ';
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            '"' => {
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                }
+            }
+            // A quote after the start of a name is a prime, as in `x'`.
+            '\'' if previous.is_whitespace() || "([{".contains(previous) => {
+                chars.next();
+                chars.next_if_eq(&'\'');
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+    depth
+}
+
+/// Read inputs from `input` until it ends, printing prompts and results to `output`. C
+/// functions are called in `libraries`.
+pub fn run(
+    libraries: Libraries,
+    input: impl BufRead,
+    output: &mut impl Write,
+) -> std::io::Result<()> {
+    let dir = std::env::current_dir().unwrap_or_default();
+    let mut session = Session::new(dir);
+    session.link(libraries);
+    let mut pending = String::new();
+    write!(output, "th> ")?;
+    output.flush()?;
+    for line in input.lines() {
+        pending.push_str(&line?);
+        pending.push('\n');
+        if depth(&pending) > 0 {
+            write!(output, "..> ")?;
+            output.flush()?;
+            continue;
+        }
+        let text = match session.eval(&pending) {
+            Ok(text) | Err(text) => text,
+        };
+        if !text.is_empty() {
+            writeln!(output, "{}", text)?;
+        }
+        pending.clear();
+        write!(output, "th> ")?;
+        output.flush()?;
+    }
+    writeln!(output)
+}
@@ -0,0 +1,228 @@
+#[cfg(test)]
+mod tests {
+    use crate::interp::ffi::Libraries;
+    use crate::repl::{depth, run, Session};
+
+    fn session() -> Session {
+        Session::new(std::env::temp_dir())
+    }
+
+    #[test]
+    fn test_depth() {
+        assert_eq!(depth("(def f (fn [(:x i64)]"), 2);
+        assert_eq!(depth("{1 2} [3] (f x)"), 0);
+        assert_eq!(depth("(f \"(\" ')' x')"), 0);
+        assert_eq!(depth(")"), -1);
+    }
+
+    #[test]
+    fn test_definitions_and_expressions() {
+        let mut session = session();
+        assert_eq!(
+            session.eval("(def square (fn [(:x i64)] i64 (* x x)))"),
+            Ok("square : fn [i64] i64".to_string())
+        );
+        assert_eq!(session.eval("(square 7)"), Ok("49 : i64".to_string()));
+        assert_eq!(
+            session.eval("(type point (struct (:x f64) (:y f64)))"),
+            Ok(String::new())
+        );
+        assert_eq!(
+            session.eval("{(point 1.0 2.5) [1 2] :ok}"),
+            Ok("{(point 1.0 2.5) [1 2] :ok} : {point [i32 2] atom}".to_string())
+        );
+        assert_eq!(session.eval("(if false 1)"), Ok(String::new()));
+    }
+
+    #[test]
+    fn test_errors_leave_the_session_unchanged() {
+        let mut session = session();
+        session.eval("(def n 40)").unwrap();
+        assert_eq!(
+            session.eval("(def m (+ n true))"),
+            Err("error: type mismatch in operand of `+`: expected `i32`, found `bool`".to_string())
+        );
+        assert_eq!(
+            session.eval("m"),
+            Err("error: undefined name `m`".to_string())
+        );
+        assert_eq!(
+            session.eval("(/ n 0)"),
+            Err("error: division by zero".to_string())
+        );
+
+        // A definition replaces the earlier one of its name.
+        session.eval("(def n 1.5)").unwrap();
+        assert_eq!(session.eval("n"), Ok("1.5 : f64".to_string()));
+    }
+
+    #[test]
+    fn test_definitions_evaluated_at_run_time() {
+        let mut session = session();
+        session
+            .eval(
+                "(def make-counter (fn [] fn [] i64 \n
+                    (do (def count 0) (fn [] i64 (do (def count (+ count 1)) count)))))",
+            )
+            .unwrap();
+        session
+            .eval("(type point (struct (:x f64) (:y f64)))")
+            .unwrap();
+        assert_eq!(
+            session.eval("(def c (make-counter))"),
+            Ok("c : fn [] i64".to_string())
+        );
+        assert_eq!(session.eval("(c)"), Ok("1 : i64".to_string()));
+        assert_eq!(session.eval("(c)"), Ok("2 : i64".to_string()));
+        assert_eq!(session.eval(":type c"), Ok("fn [] i64".to_string()));
+        assert_eq!(
+            session.eval("(def pt (point 1.0 2.0))"),
+            Ok("pt : point".to_string())
+        );
+        assert_eq!(session.eval("($ :y pt)"), Ok("2.0 : f64".to_string()));
+        assert_eq!(
+            session.eval("(def q [1 2 3])"),
+            Ok("q : [i32 3]".to_string())
+        );
+        assert_eq!(
+            session.eval("(def d (+ (c) ($ [2] q)))"),
+            Ok("d : i64".to_string())
+        );
+        assert_eq!(session.eval("d"), Ok("6 : i64".to_string()));
+
+        // A constant definition replaces the binding of its name.
+        session.eval("(def c 5)").unwrap();
+        assert_eq!(session.eval("c"), Ok("5 : i32".to_string()));
+    }
+
+    #[test]
+    fn test_rebinding_a_used_definition() {
+        let mut session = session();
+        session.eval("(def x 1)").unwrap();
+        session.eval("(def f (fn [] i32 x))").unwrap();
+        session
+            .eval("(def mk (fn [] fn [] i32 (fn [] i32 2)))")
+            .unwrap();
+        assert_eq!(
+            session.eval("(def x (mk))"),
+            Err(
+                "error: `x` is used by other definitions and can't be rebound at run time"
+                    .to_string()
+            )
+        );
+        assert_eq!(session.eval("(+ 1 2)"), Ok("3 : i32".to_string()));
+        assert_eq!(session.eval("(f)"), Ok("1 : i32".to_string()));
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut session = session();
+        session
+            .eval("(def add (fn [(:x i64) (:y i64)] i64 (+ x y)))")
+            .unwrap();
+        assert_eq!(session.eval(":type (add 1 2)"), Ok("i64".to_string()));
+        assert_eq!(
+            session.eval(":ast (add 1 x)"),
+            Ok(r#"Call("add", [Literal(Int(1)), Ident("x")])"#.to_string())
+        );
+        assert_eq!(
+            session.eval(":expand (fn [(:x i64)] i64 (add x 1))"),
+            Ok("(type lambda$0$env (struct))\n\
+                (def lambda$0 (fn [(:$env lambda$0$env) (:x i64)] i64 (add x 1)))\n\
+                (def it' (fn [] fn [i64] i64 (closure lambda$0)))"
+                .to_string())
+        );
+        assert_eq!(
+            session.eval(":run"),
+            Err("error: unknown command `:run`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_load_and_use() {
+        let dir = std::env::temp_dir().join(format!("tahini-repl-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(
+            dir.join("lib/math.th"),
+            "(def TAU 6.25) \n
+            (def square (fn [(:x i64)] i64 (* x x))) \n
+            (export square TAU)",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("app.th"),
+            "(def math (use \"lib/math\")) \n
+            (def area (fn [(:r i64)] i64 (* 3 (math/square r))))",
+        )
+        .unwrap();
+
+        let mut session = Session::new(dir.clone());
+        assert_eq!(
+            session.eval(":load app.th"),
+            Ok("area : fn [i64] i64".to_string())
+        );
+        assert_eq!(session.eval("(area 2)"), Ok("12 : i64".to_string()));
+        assert_eq!(session.eval("math/TAU"), Ok("6.25 : f64".to_string()));
+
+        session.eval("(def m (use \"lib/math\"))").unwrap();
+        assert_eq!(session.eval("(m/square 9)"), Ok("81 : i64".to_string()));
+
+        std::fs::write(dir.join("broken.th"), "(def one (+ 1 2)))").unwrap();
+        assert_eq!(
+            session.eval(":load broken.th"),
+            Err(format!(
+                "{}:1:18: error: unexpected ')', expected '(' or end of input",
+                dir.join("broken.th").display()
+            ))
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_c_functions() {
+        let mut session = session();
+        session
+            .eval("(def string (use :header \"string.h\"))")
+            .unwrap();
+        session
+            .eval("(def length (fn [(:text str)] u64 (string/strlen text)))")
+            .unwrap();
+        assert_eq!(
+            session.eval("(length \"hello\")"),
+            Ok("5 : u64".to_string())
+        );
+        assert_eq!(
+            session.eval("(string/strlen \"tahini\")"),
+            Ok("6 : u64".to_string())
+        );
+        session
+            .eval("(def stdlib (use :header \"stdlib.h\"))")
+            .unwrap();
+        assert_eq!(
+            session.eval("(stdlib/div 17 5)"),
+            Ok("(stdlib/div_t 3 2) : stdlib/div_t".to_string())
+        );
+        assert_eq!(
+            session.eval("stdlib/EXIT_FAILURE"),
+            Ok("1 : i32".to_string())
+        );
+        assert_eq!(
+            session.eval("(string/no_such_function 1)"),
+            Err(
+                "error: `no_such_function` is not in the C library or the libraries loaded"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_run_balances_brackets() {
+        let input = "(def inc (fn [(:x i32)] i32\n  (+ x 1)))\n(inc\n  41)\n";
+        let mut output = Vec::new();
+        run(Libraries::default(), input.as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "th> ..> inc : fn [i32] i32\nth> ..> 42 : i32\nth> \n"
+        );
+    }
+}
@@ -0,0 +1,605 @@
+//! Name resolution.
+//!
+//! Builds a scope graph over a module and records, for every name that appears in it, the
+//! binding it refers to. Value names (`def`s, parameters, loop variables, imports) and type
+//! names (`type` aliases, generic parameters) live in separate namespaces; calls fall back to
+//! the type namespace so that `(point 1.0 2.0)` resolves to the `point` constructor.
+//!
+//! Inside a function, `def` of a name that is already bound by an enclosing local scope
+//! re-binds that variable instead of introducing a new one (see `make_counter` in
+//! `docs/functional_features.md`); those uses are recorded as [`RefKind::Assign`].
+
+use crate::ast::{FnDef, Generic, Literal, Statement, TopLevelDef, TopLevelStatement, VarType};
+use crate::builtins;
+use crate::diagnostic::Diagnostic;
+use chumsky::Parser;
+use std::collections::HashMap;
+
+pub type ScopeId = usize;
+pub type BindingId = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScopeKind {
+    Module,
+    Function,
+    Block,
+    Loop,
+    Type,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BindingKind {
+    Global,
+    Function,
+    Module,
+    Header,
+    Type,
+    Generic,
+    Param,
+    Local,
+    LoopVar,
+}
+
+impl BindingKind {
+    /// Whether a binding of this kind lives inside a function body.
+    pub fn is_local(self) -> bool {
+        matches!(
+            self,
+            BindingKind::Param | BindingKind::Local | BindingKind::LoopVar
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    pub parent: Option<ScopeId>,
+    pub values: HashMap<String, BindingId>,
+    pub types: HashMap<String, BindingId>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Binding {
+    pub name: String,
+    pub kind: BindingKind,
+    pub scope: ScopeId,
+    pub item: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefKind {
+    Read,
+    Call,
+    Assign,
+    Type,
+    Export,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Binding(BindingId),
+    /// `module/member` through a `use` or `use :header` import.
+    Member {
+        module: BindingId,
+        member: String,
+    },
+    Builtin,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    pub kind: RefKind,
+    pub scope: ScopeId,
+    pub item: usize,
+    pub target: Target,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Resolution {
+    pub scopes: Vec<Scope>,
+    pub bindings: Vec<Binding>,
+    /// Every resolved name, in source order.
+    pub references: Vec<Reference>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolution {
+    pub fn binding(&self, id: BindingId) -> &Binding {
+        &self.bindings[id]
+    }
+
+    pub fn references_to(&self, id: BindingId) -> impl Iterator<Item = &Reference> {
+        self.references
+            .iter()
+            .filter(move |r| r.target == Target::Binding(id))
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+}
+
+pub fn resolve(module: &[TopLevelStatement]) -> Resolution {
+    let mut resolver = Resolver::default();
+    let root = resolver.push_scope(ScopeKind::Module, None);
+
+    for (item, statement) in module.iter().enumerate() {
+        resolver.item = item;
+        match statement {
+            TopLevelStatement::TopLevelDef(def) => {
+                let kind = match def.instruction {
+                    TopLevelDef::FnDef(_) => BindingKind::Function,
+                    _ => BindingKind::Global,
+                };
+                resolver.declare_value(root, &def.name, kind);
+            }
+            TopLevelStatement::Use(name, _) => {
+                resolver.declare_value(root, name, BindingKind::Module);
+            }
+            TopLevelStatement::UseHeader(name, _) => {
+                resolver.declare_value(root, name, BindingKind::Header);
+            }
+            TopLevelStatement::TypeAlias(name, _) => {
+                resolver.declare_type(root, name, BindingKind::Type);
+            }
+            TopLevelStatement::ExportAll()
+            | TopLevelStatement::Export(_)
+            | TopLevelStatement::Allow(_) => {}
+        }
+    }
+
+    for (item, statement) in module.iter().enumerate() {
+        resolver.item = item;
+        match statement {
+            TopLevelStatement::TypeAlias(_, var_type) => resolver.resolve_type(var_type, root),
+            TopLevelStatement::TopLevelDef(def) => match &def.instruction {
+                TopLevelDef::Literal(literal) => resolver.resolve_literal(literal, root),
+                TopLevelDef::Typed(var_type) => resolver.resolve_type(var_type, root),
+                TopLevelDef::FnDef(fn_def) => resolver.resolve_fn(fn_def, root),
+                TopLevelDef::Const(statement) => resolver.resolve_statement(statement, root),
+            },
+            TopLevelStatement::Export(names) => {
+                for name in names {
+                    resolver.resolve_value(name, root, RefKind::Export);
+                }
+            }
+            TopLevelStatement::Use(..)
+            | TopLevelStatement::UseHeader(..)
+            | TopLevelStatement::ExportAll()
+            | TopLevelStatement::Allow(_) => {}
+        }
+    }
+
+    resolver.resolution
+}
+
+#[derive(Default)]
+struct Resolver {
+    resolution: Resolution,
+    item: usize,
+}
+
+impl Resolver {
+    fn push_scope(&mut self, kind: ScopeKind, parent: Option<ScopeId>) -> ScopeId {
+        self.resolution.scopes.push(Scope {
+            kind,
+            parent,
+            values: HashMap::new(),
+            types: HashMap::new(),
+        });
+        self.resolution.scopes.len() - 1
+    }
+
+    fn new_binding(&mut self, scope: ScopeId, name: &str, kind: BindingKind) -> BindingId {
+        self.resolution.bindings.push(Binding {
+            name: name.to_string(),
+            kind,
+            scope,
+            item: self.item,
+        });
+        self.resolution.bindings.len() - 1
+    }
+
+    /// Whether `scope` is inside a loop body of the enclosing function.
+    fn in_loop(&self, scope: ScopeId) -> bool {
+        let mut current = Some(scope);
+        while let Some(id) = current {
+            let scope = &self.resolution.scopes[id];
+            match scope.kind {
+                ScopeKind::Loop => return true,
+                ScopeKind::Function | ScopeKind::Module => return false,
+                _ => current = scope.parent,
+            }
+        }
+        false
+    }
+
+    fn in_function(&self, scope: ScopeId) -> bool {
+        let mut current = Some(scope);
+        while let Some(id) = current {
+            let scope = &self.resolution.scopes[id];
+            match scope.kind {
+                ScopeKind::Function => return true,
+                ScopeKind::Module => return false,
+                _ => current = scope.parent,
+            }
+        }
+        false
+    }
+
+    fn error(&mut self, message: String) {
+        self.resolution
+            .diagnostics
+            .push(Diagnostic::error(self.item, message));
+    }
+
+    fn warning(&mut self, message: String) {
+        self.resolution
+            .diagnostics
+            .push(Diagnostic::warning(self.item, message));
+    }
+
+    fn declare_value(&mut self, scope: ScopeId, name: &str, kind: BindingKind) -> BindingId {
+        if self.resolution.scopes[scope].values.contains_key(name) {
+            self.error(format!("duplicate definition of `{}`", name));
+        } else if let Some(parent) = self.resolution.scopes[scope].parent {
+            if let Some(shadowed) = self.lookup_value(parent, name) {
+                let what = describe(self.resolution.bindings[shadowed].kind);
+                self.warning(format!("`{}` shadows {} `{}`", name, what, name));
+            }
+        }
+
+        let id = self.new_binding(scope, name, kind);
+        self.resolution.scopes[scope]
+            .values
+            .insert(name.to_string(), id);
+        id
+    }
+
+    fn declare_type(&mut self, scope: ScopeId, name: &str, kind: BindingKind) -> BindingId {
+        if self.resolution.scopes[scope].types.contains_key(name) {
+            self.error(format!("duplicate definition of type `{}`", name));
+        } else if let Some(parent) = self.resolution.scopes[scope].parent {
+            if self.lookup_type(parent, name).is_some() {
+                self.warning(format!("type parameter `{}` shadows an outer type", name));
+            }
+        }
+
+        let id = self.new_binding(scope, name, kind);
+        self.resolution.scopes[scope]
+            .types
+            .insert(name.to_string(), id);
+        id
+    }
+
+    fn lookup_value(&self, scope: ScopeId, name: &str) -> Option<BindingId> {
+        let mut current = Some(scope);
+        while let Some(id) = current {
+            let scope = &self.resolution.scopes[id];
+            if let Some(binding) = scope.values.get(name) {
+                return Some(*binding);
+            }
+            current = scope.parent;
+        }
+        None
+    }
+
+    fn lookup_type(&self, scope: ScopeId, name: &str) -> Option<BindingId> {
+        let mut current = Some(scope);
+        while let Some(id) = current {
+            let scope = &self.resolution.scopes[id];
+            if let Some(binding) = scope.types.get(name) {
+                return Some(*binding);
+            }
+            current = scope.parent;
+        }
+        None
+    }
+
+    fn record(&mut self, name: &str, kind: RefKind, scope: ScopeId, target: Target) {
+        self.resolution.references.push(Reference {
+            name: name.to_string(),
+            kind,
+            scope,
+            item: self.item,
+            target,
+        });
+    }
+
+    /// Resolve `module/member` paths; returns `None` if `name` is not a path.
+    fn resolve_path(&mut self, name: &str, kind: RefKind, scope: ScopeId) -> Option<()> {
+        let (module, member) = builtins::split_path(name)?;
+        match self.lookup_value(scope, module) {
+            Some(id)
+                if matches!(
+                    self.resolution.bindings[id].kind,
+                    BindingKind::Module | BindingKind::Header
+                ) =>
+            {
+                let target = Target::Member {
+                    module: id,
+                    member: member.to_string(),
+                };
+                self.record(name, kind, scope, target);
+            }
+            Some(_) => self.error(format!("`{}` is not a module", module)),
+            None => self.error(format!("undefined module `{}` in `{}`", module, name)),
+        }
+        Some(())
+    }
+
+    fn resolve_value(&mut self, name: &str, scope: ScopeId, kind: RefKind) {
+        if self.resolve_path(name, kind, scope).is_some() {
+            return;
+        }
+
+        if let Some(id) = self.lookup_value(scope, name) {
+            self.record(name, kind, scope, Target::Binding(id));
+        } else if matches!(kind, RefKind::Call | RefKind::Export)
+            && self.lookup_type(scope, name).is_some()
+        {
+            let id = self.lookup_type(scope, name).unwrap();
+            self.record(name, kind, scope, Target::Binding(id));
+        } else if kind == RefKind::Call && builtins::is_builtin_fn(name) {
+            self.record(name, kind, scope, Target::Builtin);
+        } else {
+            self.error(format!("undefined name `{}`", name));
+        }
+    }
+
+    fn resolve_type_name(&mut self, name: &str, scope: ScopeId) {
+        if self.resolve_path(name, RefKind::Type, scope).is_some() {
+            return;
+        }
+
+        if let Some(id) = self.lookup_type(scope, name) {
+            self.record(name, RefKind::Type, scope, Target::Binding(id));
+        } else if builtins::is_builtin_type(name) {
+            self.record(name, RefKind::Type, scope, Target::Builtin);
+        } else {
+            self.error(format!("undefined type `{}`", name));
+        }
+    }
+
+    fn resolve_generic_scope(&mut self, generics: &[Generic], scope: ScopeId) -> ScopeId {
+        let inner = self.push_scope(ScopeKind::Type, Some(scope));
+        self.declare_generics(inner, generics);
+        inner
+    }
+
+    fn declare_generics(&mut self, scope: ScopeId, generics: &[Generic]) {
+        for generic in generics {
+            if let Some(bound) = &generic.bound {
+                if !builtins::BOUNDS.contains(&bound.as_str()) {
+                    self.error(format!(
+                        "unknown bound `{}` on type parameter `{}`; expected one of {}",
+                        bound,
+                        generic.name,
+                        builtins::BOUNDS.join(", ")
+                    ));
+                }
+            }
+            self.declare_type(scope, &generic.name, BindingKind::Generic);
+        }
+    }
+
+    fn resolve_type(&mut self, var_type: &VarType, scope: ScopeId) {
+        match var_type {
+            VarType::IdentType(name)
+            | VarType::GenericArraySized(name, _)
+            | VarType::GenericArrayUnsized(name)
+            | VarType::GenericPtr(name) => self.resolve_type_name(name, scope),
+            VarType::ArraySized(inner, _) | VarType::ArrayUnsized(inner) | VarType::Ptr(inner) => {
+                self.resolve_type(inner, scope)
+            }
+            VarType::ArrayConstSized(inner, size) => {
+                self.resolve_type(inner, scope);
+                self.resolve_statement(size, scope);
+            }
+            VarType::Data(variants) => {
+                for (_, types) in variants {
+                    for var_type in types {
+                        self.resolve_type(var_type, scope);
+                    }
+                }
+            }
+            VarType::GenericData(generics, variants) => {
+                let inner = self.resolve_generic_scope(generics, scope);
+                for (_, types) in variants {
+                    for var_type in types {
+                        self.resolve_type(var_type, inner);
+                    }
+                }
+            }
+            VarType::Tuple(types) => {
+                for var_type in types {
+                    self.resolve_type(var_type, scope);
+                }
+            }
+            VarType::GenericTuple(generics, types) => {
+                let inner = self.resolve_generic_scope(generics, scope);
+                for var_type in types {
+                    self.resolve_type(var_type, inner);
+                }
+            }
+            VarType::Struct(fields) => {
+                for (_, var_type) in fields {
+                    self.resolve_type(var_type, scope);
+                }
+            }
+            VarType::GenericStruct(generics, fields) => {
+                let inner = self.resolve_generic_scope(generics, scope);
+                for (_, var_type) in fields {
+                    self.resolve_type(var_type, inner);
+                }
+            }
+            VarType::Fn(params, ret) | VarType::FnWithVarArgs(params, ret) => {
+                for var_type in params {
+                    self.resolve_type(var_type, scope);
+                }
+                self.resolve_type(ret, scope);
+            }
+            VarType::GenericInstance(name, args) => {
+                self.resolve_type_name(name, scope);
+                for var_type in args {
+                    self.resolve_type(var_type, scope);
+                }
+            }
+            VarType::GenericFn(generics, params, ret)
+            | VarType::GenericFnWithVarArgs(generics, params, ret) => {
+                let inner = self.resolve_generic_scope(generics, scope);
+                for var_type in params {
+                    self.resolve_type(var_type, inner);
+                }
+                self.resolve_type(ret, inner);
+            }
+            _ => {}
+        }
+    }
+
+    fn resolve_fn(&mut self, fn_def: &FnDef, scope: ScopeId) {
+        let fn_scope = self.push_scope(ScopeKind::Function, Some(scope));
+        self.declare_generics(
+            fn_scope,
+            fn_def.generic_types.as_deref().unwrap_or_default(),
+        );
+
+        for (name, var_type) in &fn_def.parameters {
+            self.resolve_type(var_type, fn_scope);
+            self.declare_value(fn_scope, name, BindingKind::Param);
+        }
+        self.resolve_type(&fn_def.return_type, fn_scope);
+
+        self.resolve_statement(&fn_def.statement, fn_scope);
+    }
+
+    fn resolve_literal(&mut self, literal: &Literal, scope: ScopeId) {
+        match literal {
+            Literal::Tuple(items) | Literal::Data(_, items) | Literal::Array(items) => {
+                for item in items {
+                    self.resolve_statement(item, scope);
+                }
+            }
+            Literal::Fn(fn_def) => self.resolve_fn(fn_def, scope),
+            _ => {}
+        }
+    }
+
+    fn resolve_statement(&mut self, statement: &Statement, scope: ScopeId) {
+        match statement {
+            Statement::Ident(name) => self.resolve_value(name, scope, RefKind::Read),
+            Statement::Literal(literal) => self.resolve_literal(literal, scope),
+            Statement::DoBlock(statements) => {
+                let block = self.push_scope(ScopeKind::Block, Some(scope));
+                for statement in statements {
+                    self.resolve_statement(statement, block);
+                }
+            }
+            Statement::Call(name, args) => {
+                self.resolve_value(name, scope, RefKind::Call);
+                for arg in args {
+                    self.resolve_statement(arg, scope);
+                }
+            }
+            Statement::GenericCall(name, generics, args) => {
+                self.resolve_value(name, scope, RefKind::Call);
+                for generic in generics {
+                    match crate::parser::var_type()
+                        .parse(generic.as_str())
+                        .into_output()
+                    {
+                        Some(var_type) => self.resolve_type(&var_type, scope),
+                        None => self.error(format!("invalid type argument `{}`", generic)),
+                    }
+                }
+                for arg in args {
+                    self.resolve_statement(arg, scope);
+                }
+            }
+            Statement::DefVar(def) => {
+                self.resolve_statement(&def.instruction, scope);
+                match self.lookup_value(scope, &def.name) {
+                    Some(id) if self.resolution.bindings[id].kind.is_local() => {
+                        self.record(&def.name, RefKind::Assign, scope, Target::Binding(id));
+                    }
+                    _ => {
+                        self.declare_value(scope, &def.name, BindingKind::Local);
+                    }
+                }
+            }
+            Statement::Declare(name, var_type) => {
+                self.resolve_type(var_type, scope);
+                self.declare_value(scope, name, BindingKind::Local);
+            }
+            Statement::If(condition, then) => {
+                self.resolve_statement(condition, scope);
+                let block = self.push_scope(ScopeKind::Block, Some(scope));
+                self.resolve_statement(then, block);
+            }
+            Statement::IfElse(condition, then, otherwise) => {
+                self.resolve_statement(condition, scope);
+                let block = self.push_scope(ScopeKind::Block, Some(scope));
+                self.resolve_statement(then, block);
+                let block = self.push_scope(ScopeKind::Block, Some(scope));
+                self.resolve_statement(otherwise, block);
+            }
+            Statement::For(condition, body) => {
+                self.resolve_statement(condition, scope);
+                let body_scope = self.push_scope(ScopeKind::Loop, Some(scope));
+                self.resolve_statement(body, body_scope);
+            }
+            Statement::ForRange(name, range, body) => {
+                for part in range.parts() {
+                    self.resolve_statement(part, scope);
+                }
+                let body_scope = self.push_scope(ScopeKind::Loop, Some(scope));
+                self.declare_value(body_scope, name, BindingKind::LoopVar);
+                self.resolve_statement(body, body_scope);
+            }
+            Statement::Break | Statement::Continue => {
+                if !self.in_loop(scope) {
+                    let form = if *statement == Statement::Break {
+                        "break"
+                    } else {
+                        "continue"
+                    };
+                    self.error(format!("`({})` outside of a `for` loop", form));
+                }
+            }
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.resolve_statement(value, scope);
+                }
+                if !self.in_function(scope) {
+                    self.error("`(return)` outside of a function".to_string());
+                }
+            }
+            Statement::GetField(_, target) => self.resolve_statement(target, scope),
+            Statement::GetIndexed(index, target) => {
+                self.resolve_statement(index, scope);
+                self.resolve_statement(target, scope);
+            }
+            Statement::SetField(_, target, value) => {
+                self.resolve_statement(target, scope);
+                self.resolve_statement(value, scope);
+            }
+            Statement::SetIndexed(index, target, value) => {
+                self.resolve_statement(index, scope);
+                self.resolve_statement(target, scope);
+                self.resolve_statement(value, scope);
+            }
+        }
+    }
+}
+
+fn describe(kind: BindingKind) -> &'static str {
+    match kind {
+        BindingKind::Global | BindingKind::Function => "top-level definition",
+        BindingKind::Module | BindingKind::Header => "import",
+        BindingKind::Type | BindingKind::Generic => "type",
+        BindingKind::Param => "parameter",
+        BindingKind::Local => "local",
+        BindingKind::LoopVar => "loop variable",
+    }
+}
@@ -0,0 +1,238 @@
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Severity;
+    use crate::parser::{parser, spanned_parser};
+    use crate::resolve::{resolve, BindingKind, RefKind, Resolution, Target};
+    use chumsky::Parser;
+
+    fn run(src: &str) -> Resolution {
+        let module = parser().parse(src).into_output().unwrap();
+        resolve(&module)
+    }
+
+    fn messages(resolution: &Resolution, severity: Severity) -> Vec<String> {
+        resolution
+            .diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .map(|d| d.message.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_resolves_params_and_recursion() {
+        let resolution = run("(def fib (fn [(:n i32)] i32 \n
+            (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))))");
+        assert!(resolution.diagnostics.is_empty());
+
+        let fib = resolution
+            .bindings
+            .iter()
+            .position(|b| b.name == "fib")
+            .unwrap();
+        assert_eq!(resolution.binding(fib).kind, BindingKind::Function);
+        assert_eq!(resolution.references_to(fib).count(), 2);
+
+        let n = resolution
+            .bindings
+            .iter()
+            .position(|b| b.name == "n")
+            .unwrap();
+        assert_eq!(resolution.binding(n).kind, BindingKind::Param);
+        assert_eq!(resolution.references_to(n).count(), 4);
+
+        assert!(resolution
+            .references
+            .iter()
+            .any(|r| r.name == "<" && r.target == Target::Builtin));
+    }
+
+    #[test]
+    fn test_undefined_name() {
+        let resolution = run("(def main (fn [] i32 (do (print x) 0)))");
+        assert_eq!(
+            messages(&resolution, Severity::Error),
+            vec!["undefined name `print`", "undefined name `x`"]
+        );
+    }
+
+    #[test]
+    fn test_locals_are_visible_after_def_only() {
+        let resolution = run("(def main (fn [] i32 (do (+ a 1) (def a 10) a)))");
+        assert_eq!(
+            messages(&resolution, Severity::Error),
+            vec!["undefined name `a`"]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_top_level_definition() {
+        let resolution = run("(def a 1) (def a 2) (type p i32) (type p i64)");
+        assert_eq!(
+            messages(&resolution, Severity::Error),
+            vec![
+                "duplicate definition of `a`",
+                "duplicate definition of type `p`"
+            ]
+        );
+        assert_eq!(resolution.diagnostics[0].item, 1);
+    }
+
+    #[test]
+    fn test_duplicate_parameter() {
+        let resolution = run("(def f (fn [(:a i32) (:a i32)] i32 a))");
+        assert_eq!(
+            messages(&resolution, Severity::Error),
+            vec!["duplicate definition of `a`"]
+        );
+    }
+
+    #[test]
+    fn test_local_def_rebinds_enclosing_local() {
+        let resolution = run("(def make-counter (fn [(:start i32)] fn [] i32 \n
+            (do \n
+                (def count start) \n
+                (fn [] i32 (do (def old count) (def count (+ count 1)) old)))))");
+        assert!(resolution.diagnostics.is_empty());
+
+        let count: Vec<_> = resolution
+            .bindings
+            .iter()
+            .filter(|b| b.name == "count")
+            .collect();
+        assert_eq!(count.len(), 1);
+        assert!(resolution
+            .references
+            .iter()
+            .any(|r| r.name == "count" && r.kind == RefKind::Assign));
+    }
+
+    #[test]
+    fn test_shadowing_warnings() {
+        let resolution = run("(def x 1) \n
+            (def f (fn [(:x i32)] i32 \n
+                (fn [(:x i32)] i32 x)))");
+        assert!(messages(&resolution, Severity::Error).is_empty());
+        assert_eq!(
+            messages(&resolution, Severity::Warning),
+            vec![
+                "`x` shadows top-level definition `x`",
+                "`x` shadows parameter `x`"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_loop_variable_scope() {
+        let resolution = run("(def sum (fn [(:len i32)] i32 \n
+            (do \n
+                (def result 0) \n
+                (for (range i len) (def result (+ result i))) \n
+                i)))");
+        assert_eq!(
+            messages(&resolution, Severity::Error),
+            vec!["undefined name `i`"]
+        );
+        let i = resolution.bindings.iter().find(|b| b.name == "i").unwrap();
+        assert_eq!(i.kind, BindingKind::LoopVar);
+    }
+
+    #[test]
+    fn test_break_and_continue_need_a_loop() {
+        let resolution = run("(def f (fn [(:n i32)] void \n
+            (do \n
+                (for (range i n) (if (> i 3) (break) (continue))) \n
+                (for true (def g (fn [] void (break)))) \n
+                (continue))))");
+        assert_eq!(
+            messages(&resolution, Severity::Error),
+            vec![
+                "`(break)` outside of a `for` loop",
+                "`(continue)` outside of a `for` loop"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_return_needs_a_function() {
+        let resolution = run("(def f (fn [(:n i32)] i32 (if (> n 3) (return n) n))) 
+
+            (def g (fn [] fn [] void (fn [] void (return)))) 
+
+            (def x (return 1))");
+        assert_eq!(
+            messages(&resolution, Severity::Error),
+            vec!["`(return)` outside of a function"]
+        );
+    }
+
+    #[test]
+    fn test_module_paths() {
+        let resolution = run("(def stdio (use :header \"stdio.h\")) \n
+            (def math (use \"math\")) \n
+            (def main (fn [] i32 (do (stdio/printf \"%f\" math/PI) (other/f) (main/x) 0)))");
+        assert_eq!(
+            messages(&resolution, Severity::Error),
+            vec![
+                "undefined module `other` in `other/f`",
+                "`main` is not a module"
+            ]
+        );
+        assert!(resolution.references.iter().any(|r| r.name == "math/PI"
+            && matches!(&r.target, Target::Member { member, .. } if member == "PI")));
+        assert!(resolution
+            .references
+            .iter()
+            .any(|r| r.name == "stdio/printf" && r.kind == RefKind::Call));
+    }
+
+    #[test]
+    fn test_diagnostic_spans() {
+        let src = "(def a 1)\n(def main (fn [] i32 b))";
+        let spanned = spanned_parser().parse(src).into_output().unwrap();
+        let (module, spans): (Vec<_>, Vec<_>) = spanned.into_iter().unzip();
+        let resolution = resolve(&module);
+        assert_eq!(
+            resolution.diagnostics[0].render(src, &spans),
+            "2:1: error: undefined name `b`"
+        );
+    }
+
+    #[test]
+    fn test_types_and_generics() {
+        let resolution = run("(type point (struct (:x f64) (:y f64))) \n
+            (type pair (struct<T> (:first T) (:second T))) \n
+            (type bad (ptr missing)) \n
+            (def origin (fn [] point (point 0.0 0.0))) \n
+            (def id (fn<T> [(:x T)] T x)) \n
+            (def leak (fn [(:x T)] i32 0))");
+        assert_eq!(
+            messages(&resolution, Severity::Error),
+            vec!["undefined type `missing`", "undefined type `T`"]
+        );
+        assert_eq!(resolution.diagnostics[0].item, 2);
+        assert_eq!(resolution.diagnostics[1].item, 5);
+    }
+
+    #[test]
+    fn test_exports() {
+        let resolution = run("(def a 1) (def m (use \"m\")) (export a m/b c)");
+        assert_eq!(
+            messages(&resolution, Severity::Error),
+            vec!["undefined name `c`"]
+        );
+    }
+
+    #[test]
+    fn test_generic_bounds_are_checked() {
+        let resolution = run("(def f (fn<T:numeric U:ord> [(:a T) (:b U)] T a))");
+        assert_eq!(
+            messages(&resolution, Severity::Error),
+            vec!["unknown bound `ord` on type parameter `U`; expected one of numeric, integer, float"]
+        );
+        assert!(resolution
+            .bindings
+            .iter()
+            .any(|b| b.name == "U" && b.kind == BindingKind::Generic));
+    }
+}
@@ -1,35 +1,259 @@
+use crate::ast::{DefVar, Literal, Range, Statement, TopLevelDef, TopLevelStatement, VarType};
+use crate::typeck::infer::Inference;
+use std::fmt;
+
 #[derive(Clone, Debug, PartialEq)]
-pub enum AccessSegment {
+pub enum AccessSegment<S = Statement> {
     Field(String),
-    Index(Box<crate::ast::Statement>),
+    Index(Box<S>),
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum TransformedStmt {
-    // Wrapper around original statement (for constructs untouched by this transformer)
-    Orig(crate::ast::Statement),
+    // Wrapper around original statement (for constructs untouched by this transformer:
+    // identifiers and scalar literals)
+    Orig(Statement),
+
+    DoBlock(Vec<TransformedStmt>),
+    Call(String, Vec<TransformedStmt>),
+    DefVar(DefVar<Box<TransformedStmt>>),
+    Declare(String, VarType),
+    If(Box<TransformedStmt>, Box<TransformedStmt>),
+    IfElse(
+        Box<TransformedStmt>,
+        Box<TransformedStmt>,
+        Box<TransformedStmt>,
+    ),
+    For(Box<TransformedStmt>, Box<TransformedStmt>),
+    ForRange(String, Box<Range<TransformedStmt>>, Box<TransformedStmt>),
+    Break,
+    Continue,
+    Return(Option<Box<TransformedStmt>>),
+
+    Tuple(Vec<TransformedStmt>),
+    Array(Vec<TransformedStmt>),
+    Data(String, Vec<TransformedStmt>),
+    Fn(Box<TransformedFn>),
 
     // Chained access like `a->b->c` or `a[0][i]`
     ChainAccess {
-        root: Box<crate::ast::Statement>,
-        segments: Vec<AccessSegment>,
+        root: Box<TransformedStmt>,
+        segments: Vec<AccessSegment<TransformedStmt>>,
     },
 
     // Chained assignment like `a->b->c = value`
     ChainAssign {
-        root: Box<crate::ast::Statement>,
-        segments: Vec<AccessSegment>,
-        value: Box<crate::ast::Statement>,
+        root: Box<TransformedStmt>,
+        segments: Vec<AccessSegment<TransformedStmt>>,
+        value: Box<TransformedStmt>,
     },
 
     // Closure creation after hoisting lambdas
     MakeClosure {
         fn_name: String,
-        env: Vec<crate::ast::Statement>,
+        env: Vec<TransformedStmt>,
     },
+
+    // Heap cells of captured locals that are reassigned. `BoxGet` is a place: it can be the
+    // root of a `ChainAssign`.
+    BoxNew(Box<TransformedStmt>),
+    BoxGet(Box<TransformedStmt>),
+    BoxSet(Box<TransformedStmt>, Box<TransformedStmt>),
+}
+
+/// A function after monomorphization: it has no type parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransformedFn {
+    pub parameters: Vec<(String, VarType)>,
+    pub return_type: VarType,
+    pub statement: TransformedStmt,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransformedItem {
+    // Type aliases, imports, exports and declarations without a value
+    Orig(TopLevelStatement),
+    Fn(String, TransformedFn),
+    Global(String, TransformedStmt),
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Module {
-    pub items: Vec<crate::ast::TopLevelStatement>,
+    pub items: Vec<TransformedItem>,
+    /// Types inferred for the bindings without annotation, with items numbered as in `items`.
+    pub inference: Inference,
+}
+
+fn write_all(f: &mut fmt::Formatter<'_>, statements: &[TransformedStmt]) -> fmt::Result {
+    for statement in statements {
+        write!(f, " {}", statement)?;
+    }
+    Ok(())
+}
+
+fn write_items(f: &mut fmt::Formatter<'_>, items: &[TransformedStmt]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+fn write_segments(
+    f: &mut fmt::Formatter<'_>,
+    segments: &[AccessSegment<TransformedStmt>],
+) -> fmt::Result {
+    for segment in segments {
+        match segment {
+            AccessSegment::Field(name) => write!(f, " :{}", name)?,
+            AccessSegment::Index(index) => write!(f, " [{}]", index)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_orig(f: &mut fmt::Formatter<'_>, statement: &Statement) -> fmt::Result {
+    match statement {
+        Statement::Ident(name) => write!(f, "{}", name),
+        Statement::Literal(Literal::Int(value)) => write!(f, "{}", value),
+        Statement::Literal(Literal::Float(value)) => write!(f, "{:?}", value),
+        Statement::Literal(Literal::Bool(value)) => write!(f, "{}", value),
+        Statement::Literal(Literal::Char(value)) => write!(f, "'{}'", value),
+        Statement::Literal(Literal::String(value)) => write!(f, "{:?}", value),
+        Statement::Literal(Literal::Atom(value)) => write!(f, ":{}", value),
+        other => write!(f, "{:?}", other),
+    }
+}
+
+/// Prints the lowered tree as s-expressions, close to the source syntax. `$` chains print as
+/// `(get root segments...)` and `(set root segments... value)`.
+impl fmt::Display for TransformedStmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransformedStmt::Orig(statement) => write_orig(f, statement),
+            TransformedStmt::DoBlock(statements) => {
+                write!(f, "(do")?;
+                write_all(f, statements)?;
+                write!(f, ")")
+            }
+            TransformedStmt::Call(name, args) => {
+                write!(f, "({}", name)?;
+                write_all(f, args)?;
+                write!(f, ")")
+            }
+            TransformedStmt::DefVar(def) => write!(f, "(def {} {})", def.name, def.instruction),
+            TransformedStmt::Declare(name, var_type) => write!(f, "(def (:{} {}))", name, var_type),
+            TransformedStmt::If(condition, then) => write!(f, "(if {} {})", condition, then),
+            TransformedStmt::IfElse(condition, then, otherwise) => {
+                write!(f, "(if {} {} {})", condition, then, otherwise)
+            }
+            TransformedStmt::For(condition, body) => write!(f, "(for {} {})", condition, body),
+            TransformedStmt::ForRange(name, range, body) => {
+                write!(f, "(for (range {} {}) {})", name, range, body)
+            }
+            TransformedStmt::Break => write!(f, "(break)"),
+            TransformedStmt::Continue => write!(f, "(continue)"),
+            TransformedStmt::Return(Some(value)) => write!(f, "(return {})", value),
+            TransformedStmt::Return(None) => write!(f, "(return)"),
+            TransformedStmt::Tuple(items) => {
+                write!(f, "{{")?;
+                write_items(f, items)?;
+                write!(f, "}}")
+            }
+            TransformedStmt::Array(items) => {
+                write!(f, "[")?;
+                write_items(f, items)?;
+                write!(f, "]")
+            }
+            TransformedStmt::Data(tag, args) => {
+                write!(f, "[:{}", tag)?;
+                write_all(f, args)?;
+                write!(f, "]")
+            }
+            TransformedStmt::Fn(fn_def) => write!(f, "{}", fn_def),
+            TransformedStmt::ChainAccess { root, segments } => {
+                write!(f, "(get {}", root)?;
+                write_segments(f, segments)?;
+                write!(f, ")")
+            }
+            TransformedStmt::ChainAssign {
+                root,
+                segments,
+                value,
+            } => {
+                write!(f, "(set {}", root)?;
+                write_segments(f, segments)?;
+                write!(f, " {})", value)
+            }
+            TransformedStmt::MakeClosure { fn_name, env } => {
+                write!(f, "(closure {}", fn_name)?;
+                write_all(f, env)?;
+                write!(f, ")")
+            }
+            TransformedStmt::BoxNew(value) => write!(f, "(box {})", value),
+            TransformedStmt::BoxGet(cell) => write!(f, "(unbox {})", cell),
+            TransformedStmt::BoxSet(cell, value) => write!(f, "(set-box {} {})", cell, value),
+        }
+    }
+}
+
+impl fmt::Display for TransformedFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(fn [")?;
+        for (i, (name, var_type)) in self.parameters.iter().enumerate() {
+            write!(
+                f,
+                "{}(:{} {})",
+                if i > 0 { " " } else { "" },
+                name,
+                var_type
+            )?;
+        }
+        write!(f, "] {} {})", self.return_type, self.statement)
+    }
+}
+
+impl fmt::Display for TransformedItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransformedItem::Orig(TopLevelStatement::TypeAlias(name, var_type)) => {
+                write!(f, "(type {} {})", name, var_type)
+            }
+            TransformedItem::Orig(TopLevelStatement::TopLevelDef(def)) => match &def.instruction {
+                TopLevelDef::Typed(var_type) => write!(f, "(def {} {})", def.name, var_type),
+                other => write!(f, "(def {} {:?})", def.name, other),
+            },
+            TransformedItem::Orig(TopLevelStatement::Use(name, module)) => {
+                write!(f, "(def {} (use {:?}))", name, module)
+            }
+            TransformedItem::Orig(TopLevelStatement::UseHeader(name, header)) => {
+                write!(f, "(def {} (use :header {:?}))", name, header)
+            }
+            TransformedItem::Orig(TopLevelStatement::ExportAll()) => write!(f, "(export :all)"),
+            TransformedItem::Orig(TopLevelStatement::Export(names)) => {
+                write!(f, "(export {})", names.join(" "))
+            }
+            TransformedItem::Orig(TopLevelStatement::Allow(lints)) => {
+                write!(f, "(allow")?;
+                for lint in lints {
+                    write!(f, " :{}", lint)?;
+                }
+                write!(f, ")")
+            }
+            TransformedItem::Fn(name, fn_def) => write!(f, "(def {} {})", name, fn_def),
+            TransformedItem::Global(name, value) => write!(f, "(def {} {})", name, value),
+        }
+    }
+}
+
+/// One item per line.
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.items {
+            writeln!(f, "{}", item)?;
+        }
+        Ok(())
+    }
 }
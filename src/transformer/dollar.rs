@@ -1,5 +1,10 @@
-use super::ast::AccessSegment;
-use crate::ast::Statement;
+use super::ast::{AccessSegment, Module, TransformedFn, TransformedItem, TransformedStmt};
+use super::lambda::{BOX, MAKE_CLOSURE, SET_BOX, UNBOX};
+use crate::ast::{DefVar, FnDef, Literal, Statement, TopLevelDef, TopLevelStatement};
+use crate::diagnostic::Diagnostic;
+use crate::typeck::infer::infer;
+use crate::visit::{walk_statement, Visitor};
+use std::fmt;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum DollarChain {
@@ -14,78 +19,242 @@ pub enum DollarChain {
     },
 }
 
-/// Attempt to recognise a chain of nested `$` accesses/assignments and linearise them.
-/// Returns `None` if the provided statement is **not** rooted at a `$` operator.
-pub fn try_linearize(stmt: &Statement) -> Option<DollarChain> {
-    match stmt {
-        Statement::GetField(field, target) => {
-            let mut segments = Vec::new();
-            segments.push(AccessSegment::Field(field.clone()));
-            let (root, rest) = collect_segments(target);
-            segments.extend(rest);
-            Some(DollarChain::Access {
-                root: Box::new(root),
-                segments,
-            })
-        }
-        Statement::GetIndexed(index, target) => {
-            let mut segments = Vec::new();
-            segments.push(AccessSegment::Index(index.clone()));
-            let (root, rest) = collect_segments(target);
-            segments.extend(rest);
-            Some(DollarChain::Access {
-                root: Box::new(root),
-                segments,
-            })
+/// Why a `$` form cannot be linearized.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DollarError {
+    /// A `$` assignment used as the target of another `$`, as in `($ :x ($ :y a 1))`. Only the
+    /// outermost form of a chain may assign.
+    NestedAssignment,
+}
+
+impl fmt::Display for DollarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DollarError::NestedAssignment => write!(
+                f,
+                "a `$` assignment cannot be accessed by another `$`; only the outermost `$` of a chain may assign"
+            ),
         }
+    }
+}
+
+/// Attempt to recognise a chain of nested `$` accesses/assignments and linearise them.
+/// Returns `Ok(None)` if the provided statement is **not** rooted at a `$` operator.
+///
+/// Segments are listed in evaluation order, from the root outwards: `($ :x ($ [0] a))` reads
+/// `a`, then index `0`, then field `x`. The root can be any expression, e.g. a call.
+pub fn try_linearize(stmt: &Statement) -> Result<Option<DollarChain>, DollarError> {
+    let (segment, target, value) = match stmt {
+        Statement::GetField(field, target) => (AccessSegment::Field(field.clone()), target, None),
+        Statement::GetIndexed(index, target) => (AccessSegment::Index(index.clone()), target, None),
         Statement::SetField(field, target, value) => {
-            let mut segments = Vec::new();
-            segments.push(AccessSegment::Field(field.clone()));
-            let (root, rest) = collect_segments(target);
-            segments.extend(rest);
-            Some(DollarChain::Assign {
-                root: Box::new(root),
-                segments,
-                value: value.clone(),
-            })
+            (AccessSegment::Field(field.clone()), target, Some(value))
         }
         Statement::SetIndexed(index, target, value) => {
-            let mut segments = Vec::new();
-            segments.push(AccessSegment::Index(index.clone()));
-            let (root, rest) = collect_segments(target);
-            segments.extend(rest);
-            Some(DollarChain::Assign {
-                root: Box::new(root),
-                segments,
-                value: value.clone(),
-            })
+            (AccessSegment::Index(index.clone()), target, Some(value))
         }
-        _ => None,
-    }
+        _ => return Ok(None),
+    };
+
+    let (root, mut segments) = collect_segments(target)?;
+    segments.push(segment);
+    let root = Box::new(root);
+    Ok(Some(match value {
+        None => DollarChain::Access { root, segments },
+        Some(value) => DollarChain::Assign {
+            root,
+            segments,
+            value: value.clone(),
+        },
+    }))
 }
 
 /// Helper to recursively collect segments from nested `$` operator statements.
 /// Returns the base/root expression (that is **not** another `$` op) and the
-/// segments collected **in reverse order** (closest segment first).
-fn collect_segments(stmt: &Statement) -> (Statement, Vec<AccessSegment>) {
-    match stmt {
-        Statement::GetField(field, inner) => {
-            let mut segs = vec![AccessSegment::Field(field.clone())];
-            let (root, rest) = collect_segments(inner);
-            segs.extend(rest);
-            (root, segs)
+/// segments collected in evaluation order (closest to the root first).
+fn collect_segments(stmt: &Statement) -> Result<(Statement, Vec<AccessSegment>), DollarError> {
+    let (segment, inner) = match stmt {
+        Statement::GetField(field, inner) => (AccessSegment::Field(field.clone()), inner),
+        Statement::GetIndexed(index, inner) => (AccessSegment::Index(index.clone()), inner),
+        Statement::SetField(..) | Statement::SetIndexed(..) => {
+            return Err(DollarError::NestedAssignment)
         }
-        Statement::GetIndexed(index, inner) => {
-            let mut segs = vec![AccessSegment::Index(index.clone())];
-            let (root, rest) = collect_segments(inner);
-            segs.extend(rest);
-            (root, segs)
+        _ => return Ok((stmt.clone(), Vec::new())),
+    };
+    let (root, mut segments) = collect_segments(inner)?;
+    segments.push(segment);
+    Ok((root, segments))
+}
+
+/// Reports the `$` chains of one item that cannot be linearized.
+struct Validate {
+    item: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Visitor for Validate {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match try_linearize(statement) {
+            // The inner forms of a broken chain would report the same error again.
+            Err(error) => self
+                .diagnostics
+                .push(Diagnostic::error(self.item, error.to_string())),
+            Ok(_) => walk_statement(self, statement),
         }
-        Statement::SetField(..) | Statement::SetIndexed(..) => {
-            // We should never see assignment in nested positions for a valid `$` chain.
-            // Treat as root for now.
-            (stmt.clone(), Vec::new())
+    }
+}
+
+/// Lower a module into `TransformedStmt` trees, turning every `$` chain, however deeply nested,
+/// into a `ChainAccess` or `ChainAssign`, and record the types inferred for its bindings. Fails
+/// if a chain assigns in a non-tail position.
+pub fn run(module: &[TopLevelStatement]) -> Result<Module, Vec<Diagnostic>> {
+    let mut validate = Validate {
+        item: 0,
+        diagnostics: Vec::new(),
+    };
+    for (item, statement) in module.iter().enumerate() {
+        validate.item = item;
+        validate.visit_top_level(statement);
+    }
+    if !validate.diagnostics.is_empty() {
+        return Err(validate.diagnostics);
+    }
+
+    Ok(Module {
+        items: module.iter().map(lower_item).collect(),
+        inference: infer(module),
+    })
+}
+
+fn lower_item(statement: &TopLevelStatement) -> TransformedItem {
+    match statement {
+        TopLevelStatement::TopLevelDef(def) => match &def.instruction {
+            TopLevelDef::FnDef(fn_def) => TransformedItem::Fn(def.name.clone(), lower_fn(fn_def)),
+            TopLevelDef::Literal(literal) => {
+                TransformedItem::Global(def.name.clone(), lower_literal(literal))
+            }
+            TopLevelDef::Const(constant) => {
+                TransformedItem::Global(def.name.clone(), lower(constant))
+            }
+            TopLevelDef::Typed(_) => TransformedItem::Orig(statement.clone()),
+        },
+        other => TransformedItem::Orig(other.clone()),
+    }
+}
+
+/// Lower a function. Type parameters are dropped; the module is expected to be monomorphized.
+fn lower_fn(fn_def: &FnDef) -> TransformedFn {
+    TransformedFn {
+        parameters: fn_def.parameters.clone(),
+        return_type: fn_def.return_type.clone(),
+        statement: lower(&fn_def.statement),
+    }
+}
+
+fn lower_literal(literal: &Literal) -> TransformedStmt {
+    match literal {
+        Literal::Tuple(items) => TransformedStmt::Tuple(lower_all(items)),
+        Literal::Array(items) => TransformedStmt::Array(lower_all(items)),
+        Literal::Data(tag, args) => TransformedStmt::Data(tag.clone(), lower_all(args)),
+        Literal::Fn(fn_def) => TransformedStmt::Fn(Box::new(lower_fn(fn_def))),
+        scalar => TransformedStmt::Orig(Statement::Literal(scalar.clone())),
+    }
+}
+
+fn lower_all(statements: &[Statement]) -> Vec<TransformedStmt> {
+    statements.iter().map(lower).collect()
+}
+
+fn lower_boxed(statement: &Statement) -> Box<TransformedStmt> {
+    Box::new(lower(statement))
+}
+
+fn lower_segments(segments: Vec<AccessSegment>) -> Vec<AccessSegment<TransformedStmt>> {
+    segments
+        .into_iter()
+        .map(|segment| match segment {
+            AccessSegment::Field(name) => AccessSegment::Field(name),
+            AccessSegment::Index(index) => AccessSegment::Index(lower_boxed(&index)),
+        })
+        .collect()
+}
+
+/// Lower a statement and everything nested in it. Its `$` chains must be valid; [`run`] checks
+/// them first.
+pub fn lower(statement: &Statement) -> TransformedStmt {
+    let chain = try_linearize(statement).expect("`$` chains are validated before lowering");
+    if let Some(chain) = chain {
+        return match chain {
+            DollarChain::Access { root, segments } => TransformedStmt::ChainAccess {
+                root: lower_boxed(&root),
+                segments: lower_segments(segments),
+            },
+            DollarChain::Assign {
+                root,
+                segments,
+                value,
+            } => TransformedStmt::ChainAssign {
+                root: lower_boxed(&root),
+                segments: lower_segments(segments),
+                value: lower_boxed(&value),
+            },
+        };
+    }
+
+    match statement {
+        Statement::Ident(_) => TransformedStmt::Orig(statement.clone()),
+        Statement::Literal(literal) => lower_literal(literal),
+        Statement::DoBlock(statements) => TransformedStmt::DoBlock(lower_all(statements)),
+        Statement::Call(name, args) if name == MAKE_CLOSURE => match args.split_first() {
+            Some((Statement::Ident(fn_name), env)) => TransformedStmt::MakeClosure {
+                fn_name: fn_name.clone(),
+                env: lower_all(env),
+            },
+            _ => unreachable!("closures are built by lambda lifting"),
+        },
+        Statement::Call(name, args) if name == BOX => {
+            TransformedStmt::BoxNew(lower_boxed(&args[0]))
+        }
+        Statement::Call(name, args) if name == UNBOX => {
+            TransformedStmt::BoxGet(lower_boxed(&args[0]))
+        }
+        Statement::Call(name, args) if name == SET_BOX => {
+            TransformedStmt::BoxSet(lower_boxed(&args[0]), lower_boxed(&args[1]))
+        }
+        Statement::Call(name, args) => TransformedStmt::Call(name.clone(), lower_all(args)),
+        // Monomorphization turns generic calls into plain calls to their instances.
+        Statement::GenericCall(name, _, args) => {
+            TransformedStmt::Call(name.clone(), lower_all(args))
+        }
+        Statement::DefVar(def) => TransformedStmt::DefVar(DefVar {
+            name: def.name.clone(),
+            instruction: lower_boxed(&def.instruction),
+        }),
+        Statement::Declare(name, var_type) => {
+            TransformedStmt::Declare(name.clone(), var_type.clone())
+        }
+        Statement::If(condition, then) => {
+            TransformedStmt::If(lower_boxed(condition), lower_boxed(then))
+        }
+        Statement::IfElse(condition, then, otherwise) => TransformedStmt::IfElse(
+            lower_boxed(condition),
+            lower_boxed(then),
+            lower_boxed(otherwise),
+        ),
+        Statement::For(condition, body) => {
+            TransformedStmt::For(lower_boxed(condition), lower_boxed(body))
+        }
+        Statement::ForRange(name, range, body) => {
+            TransformedStmt::ForRange(name.clone(), Box::new(range.map(lower)), lower_boxed(body))
+        }
+        Statement::Break => TransformedStmt::Break,
+        Statement::Continue => TransformedStmt::Continue,
+        Statement::Return(value) => {
+            TransformedStmt::Return(value.as_ref().map(|value| lower_boxed(value)))
         }
-        _ => (stmt.clone(), Vec::new()),
+        Statement::GetField(..)
+        | Statement::GetIndexed(..)
+        | Statement::SetField(..)
+        | Statement::SetIndexed(..) => unreachable!("`$` forms are linearized above"),
     }
 }
@@ -1,9 +1,10 @@
 #[cfg(test)]
 mod tests {
     use crate::ast::Statement;
+    use crate::parser::parser;
     use crate::parser::statement;
     use crate::transformer::ast::AccessSegment;
-    use crate::transformer::dollar::{try_linearize, DollarChain};
+    use crate::transformer::dollar::{run, try_linearize, DollarChain, DollarError};
     use chumsky::Parser;
 
     fn parse(src: &str) -> Statement {
@@ -13,7 +14,7 @@ mod tests {
     #[test]
     fn test_simple_field_access() {
         let s = parse("($ :x a)");
-        let chain = try_linearize(&s).expect("should detect");
+        let chain = try_linearize(&s).unwrap().expect("should detect");
         match chain {
             DollarChain::Access { root, segments } => {
                 assert_eq!(*root, Statement::Ident("a".into()));
@@ -26,15 +27,15 @@ mod tests {
     #[test]
     fn test_nested_field_access() {
         let s = parse("($ :x ($ :y a))");
-        let chain = try_linearize(&s).expect("detected");
+        let chain = try_linearize(&s).unwrap().expect("detected");
         match chain {
             DollarChain::Access { root, segments } => {
                 assert_eq!(*root, Statement::Ident("a".into()));
                 assert_eq!(
                     segments,
                     vec![
-                        AccessSegment::Field("x".into()),
                         AccessSegment::Field("y".into()),
+                        AccessSegment::Field("x".into()),
                     ]
                 );
             }
@@ -45,7 +46,7 @@ mod tests {
     #[test]
     fn test_assignment() {
         let s = parse("($ :x ($ :y a) 10)");
-        let chain = try_linearize(&s).expect("detected");
+        let chain = try_linearize(&s).unwrap().expect("detected");
         match chain {
             DollarChain::Assign {
                 root,
@@ -56,8 +57,8 @@ mod tests {
                 assert_eq!(
                     segments,
                     vec![
-                        AccessSegment::Field("x".into()),
                         AccessSegment::Field("y".into()),
+                        AccessSegment::Field("x".into()),
                     ]
                 );
                 assert_eq!(*value, Statement::Literal(crate::ast::Literal::Int(10)));
@@ -69,21 +70,67 @@ mod tests {
     #[test]
     fn test_mixed_segments() {
         let s = parse("($ [0] ($ :y a))");
-        let chain = try_linearize(&s).expect("detected");
+        let chain = try_linearize(&s).unwrap().expect("detected");
         match chain {
             DollarChain::Access { root, segments } => {
                 assert_eq!(*root, Statement::Ident("a".into()));
                 assert_eq!(
                     segments,
                     vec![
+                        AccessSegment::Field("y".into()),
                         AccessSegment::Index(Box::new(Statement::Literal(
                             crate::ast::Literal::Int(0)
                         ))),
-                        AccessSegment::Field("y".into()),
                     ]
                 );
             }
             _ => panic!(),
         }
     }
+
+    #[test]
+    fn test_call_root() {
+        let s = parse("($ [0] ($ :items (f a)))");
+        let chain = try_linearize(&s).unwrap().expect("detected");
+        match chain {
+            DollarChain::Access { root, segments } => {
+                assert_eq!(
+                    *root,
+                    Statement::Call("f".into(), vec![Statement::Ident("a".into())])
+                );
+                assert_eq!(segments[0], AccessSegment::Field("items".into()));
+                assert_eq!(segments.len(), 2);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_not_a_chain() {
+        assert_eq!(try_linearize(&parse("(f a)")), Ok(None));
+    }
+
+    #[test]
+    fn test_nested_assignment_is_rejected() {
+        assert_eq!(
+            try_linearize(&parse("($ :x ($ :y a 1))")),
+            Err(DollarError::NestedAssignment)
+        );
+        assert_eq!(
+            try_linearize(&parse("($ [0] ($ :x ($ :y a 1)) 2)")),
+            Err(DollarError::NestedAssignment)
+        );
+    }
+
+    #[test]
+    fn test_run_reports_nested_assignments_once() {
+        let module = parser()
+            .parse("(def f (fn [(:a i32)] void ($ :z ($ :x ($ :y a 1)))))")
+            .into_output()
+            .unwrap();
+        let errors = run(&module).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].item, 0);
+        assert_eq!(errors[0].message, DollarError::NestedAssignment.to_string());
+    }
 }
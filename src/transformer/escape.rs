@@ -0,0 +1,153 @@
+//! Escape analysis for closure captures.
+//!
+//! A local that a closure captures by value and that is later reassigned would silently fork
+//! into two variables: the closure keeps the old value and its own changes are lost when it
+//! returns. Such locals are boxed on the GC heap instead, so the defining function and every
+//! closure share one mutable cell (see [`crate::transformer::lambda`]).
+
+use crate::ast::{FnDef, Literal, Statement};
+use crate::visit::{walk_literal, walk_statement, Visitor};
+use std::collections::{HashMap, HashSet};
+
+/// The locals of `fn_def` (parameters and `def`s outside nested lambdas) that are captured by
+/// a nested lambda and reassigned anywhere, either by re-binding them with `def` or by a `$` set
+/// on them, in the function itself or in a lambda.
+pub fn boxed_locals(fn_def: &FnDef) -> HashSet<String> {
+    let parameters = fn_def
+        .parameters
+        .iter()
+        .map(|(name, _)| (name.clone(), true));
+    let mut escapes = Escapes {
+        scopes: vec![parameters.collect()],
+        depth: 0,
+        captured: HashSet::new(),
+        mutated: HashSet::new(),
+    };
+    escapes.visit_statement(&fn_def.statement);
+    escapes
+        .captured
+        .intersection(&escapes.mutated)
+        .cloned()
+        .collect()
+}
+
+struct Escapes {
+    /// Names in scope, innermost last, mapped to whether they are locals of the analyzed
+    /// function rather than of a lambda nested in it.
+    scopes: Vec<HashMap<String, bool>>,
+    /// Number of lambdas around the current statement.
+    depth: usize,
+    captured: HashSet<String>,
+    mutated: HashSet<String>,
+}
+
+impl Escapes {
+    fn is_own(&self, name: &str) -> Option<bool> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+    }
+
+    fn use_name(&mut self, name: &str) {
+        if self.depth > 0 && self.is_own(name) == Some(true) {
+            self.captured.insert(name.to_string());
+        }
+    }
+
+    fn mutate(&mut self, name: &str) {
+        if self.is_own(name) == Some(true) {
+            self.mutated.insert(name.to_string());
+        }
+        self.use_name(name);
+    }
+
+    fn bind(&mut self, name: &str) {
+        let own = self.depth == 0;
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.to_string(), own);
+    }
+
+    fn scoped(&mut self, statement: &Statement) {
+        self.scopes.push(HashMap::new());
+        self.visit_statement(statement);
+        self.scopes.pop();
+    }
+}
+
+impl Visitor for Escapes {
+    fn visit_literal(&mut self, literal: &Literal) {
+        match literal {
+            Literal::Fn(fn_def) => {
+                let parameters = fn_def.parameters.iter();
+                self.scopes
+                    .push(parameters.map(|(name, _)| (name.clone(), false)).collect());
+                self.depth += 1;
+                self.visit_statement(&fn_def.statement);
+                self.depth -= 1;
+                self.scopes.pop();
+            }
+            _ => walk_literal(self, literal),
+        }
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Ident(name) => self.use_name(name),
+            Statement::DoBlock(_) => {
+                self.scopes.push(HashMap::new());
+                walk_statement(self, statement);
+                self.scopes.pop();
+            }
+            Statement::Call(name, _) | Statement::GenericCall(name, _, _) => {
+                self.use_name(name);
+                walk_statement(self, statement);
+            }
+            Statement::DefVar(def) => {
+                self.visit_statement(&def.instruction);
+                match self.is_own(&def.name) {
+                    Some(_) => self.mutate(&def.name),
+                    None => self.bind(&def.name),
+                }
+            }
+            Statement::Declare(name, _) => self.bind(name),
+            Statement::If(condition, then) | Statement::For(condition, then) => {
+                self.visit_statement(condition);
+                self.scoped(then);
+            }
+            Statement::IfElse(condition, then, otherwise) => {
+                self.visit_statement(condition);
+                self.scoped(then);
+                self.scoped(otherwise);
+            }
+            Statement::ForRange(name, range, body) => {
+                for part in range.parts() {
+                    self.visit_statement(part);
+                }
+                self.scopes.push(HashMap::new());
+                self.bind(name);
+                self.visit_statement(body);
+                self.scopes.pop();
+            }
+            Statement::SetField(_, target, _) | Statement::SetIndexed(_, target, _) => {
+                if let Some(root) = root_name(target) {
+                    self.mutate(root);
+                }
+                walk_statement(self, statement);
+            }
+            _ => walk_statement(self, statement),
+        }
+    }
+}
+
+/// The variable at the root of a `$` chain, as `a` in `($ :x ($ [0] a))`.
+fn root_name(target: &Statement) -> Option<&str> {
+    match target {
+        Statement::Ident(name) => Some(name),
+        Statement::GetField(_, target) | Statement::GetIndexed(_, target) => root_name(target),
+        _ => None,
+    }
+}
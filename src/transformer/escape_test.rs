@@ -0,0 +1,64 @@
+#[cfg(test)]
+mod tests {
+    use crate::ast::{TopLevelDef, TopLevelStatement};
+    use crate::parser::parser;
+    use crate::transformer::escape::boxed_locals;
+    use chumsky::Parser;
+
+    fn boxed(src: &str) -> Vec<String> {
+        let module = parser().parse(src).into_output().unwrap();
+        let TopLevelStatement::TopLevelDef(def) = &module[0] else {
+            panic!("expected a definition");
+        };
+        let TopLevelDef::FnDef(fn_def) = &def.instruction else {
+            panic!("expected a function");
+        };
+        let mut names: Vec<_> = boxed_locals(fn_def).into_iter().collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_reassigned_in_closure() {
+        assert_eq!(
+            boxed(
+                "(def make-counter (fn [(:start i32)] fn [] i32
+                  (do (def count start) (fn [] i32 (do (def count (+ count 1)) count)))))"
+            ),
+            vec!["count"]
+        );
+    }
+
+    #[test]
+    fn test_reassigned_after_capture() {
+        assert_eq!(
+            boxed(
+                "(def f (fn [(:n i32)] fn [] i32
+                  (do (def g (fn [] i32 n)) (def n 2) g)))"
+            ),
+            vec!["n"]
+        );
+    }
+
+    #[test]
+    fn test_set_through_dollar() {
+        assert_eq!(
+            boxed(
+                "(def f (fn [(:p point)] fn [] void
+                  (fn [] void ($ :x p 1))))"
+            ),
+            vec!["p"]
+        );
+    }
+
+    #[test]
+    fn test_unmutated_or_uncaptured_locals_stay_unboxed() {
+        // `a` is only read by the closure, `b` is reassigned but never captured and `x` is
+        // the closure's own local.
+        assert!(boxed(
+            "(def f (fn [(:a i32)] fn [] i32
+              (do (def b 1) (def b 2) (fn [] i32 (do (def x a) (def x (+ x 1)) x)))))"
+        )
+        .is_empty());
+    }
+}
@@ -0,0 +1,433 @@
+//! Closure conversion and lambda lifting.
+//!
+//! Every `fn` literal nested in a function body is lifted to a fresh top-level function
+//! `lambda$<N>`. The variables it captures from enclosing functions are collected in an
+//! environment struct `lambda$<N>$env`, which the lifted function takes as its first parameter
+//! `$env` and unpacks into locals of the same names before running the original body. The
+//! literal itself is replaced by a [`MAKE_CLOSURE`] call carrying the function name and the
+//! captured values, which lowering turns into `TransformedStmt::MakeClosure`. This is the
+//! "function pointer + captured environment" layout of `docs/functional_features.md`.
+//!
+//! Captured locals that are reassigned anywhere (see [`escape`]) live in a heap cell named
+//! `<name>$box` instead: the defining function allocates it with [`BOX`], reads and writes go
+//! through [`UNBOX`] and [`SET_BOX`], and environments hold the cell, so every closure and the
+//! defining scope see the same value.
+//!
+//! The pass runs after monomorphization, so the module is type checked and has no generics.
+//! Types of captured variables come from the checker's view of the enclosing scopes.
+
+use super::escape;
+use crate::ast::{DefVar, FnDef, Literal, Statement, TopLevelDef, TopLevelStatement, VarType};
+use crate::typeck::Checker;
+use crate::visit::{walk_statement, Visitor};
+use std::collections::{HashMap, HashSet};
+
+pub use crate::builtins::{BOX, MAKE_CLOSURE, SET_BOX, UNBOX};
+
+/// Name of the environment parameter of lifted functions.
+pub const ENV: &str = "$env";
+
+pub fn run(module: &[TopLevelStatement]) -> Vec<TopLevelStatement> {
+    let mut lifter = Lifter {
+        checker: Checker::new(module),
+        lifted: Vec::new(),
+        next: 0,
+        scopes: Vec::new(),
+        boxed: Vec::new(),
+    };
+
+    let mut output = Vec::new();
+    for (item, statement) in module.iter().enumerate() {
+        lifter.checker.enter_item(item);
+        let statement = match statement {
+            TopLevelStatement::TopLevelDef(def) => match &def.instruction {
+                TopLevelDef::FnDef(fn_def) => TopLevelStatement::TopLevelDef(DefVar {
+                    name: def.name.clone(),
+                    instruction: TopLevelDef::FnDef(lifter.rewrite_fn(fn_def)),
+                }),
+                _ => statement.clone(),
+            },
+            other => other.clone(),
+        };
+        // Lifted functions go right before the item they came from.
+        output.append(&mut lifter.lifted);
+        output.push(statement);
+    }
+    output
+}
+
+/// The name of the lifted function for the `n`th lambda and of its environment type.
+pub fn lambda_names(n: usize) -> (String, String) {
+    (format!("lambda${}", n), format!("lambda${}$env", n))
+}
+
+/// The name of the heap cell holding the boxed local `name`.
+pub fn box_name(name: &str) -> String {
+    format!("{}$box", name)
+}
+
+fn ident(name: &str) -> Statement {
+    Statement::Ident(name.to_string())
+}
+
+fn intrinsic(name: &str, args: Vec<Statement>) -> Statement {
+    Statement::Call(name.to_string(), args)
+}
+
+fn define(name: String, value: Statement) -> Statement {
+    Statement::DefVar(DefVar {
+        name,
+        instruction: Box::new(value),
+    })
+}
+
+/// Run `prologue` before `statement`.
+fn prepend(mut prologue: Vec<Statement>, statement: Statement) -> Statement {
+    if prologue.is_empty() {
+        return statement;
+    }
+    prologue.push(statement);
+    Statement::DoBlock(prologue)
+}
+
+struct Lifter {
+    checker: Checker,
+    /// Type aliases and functions lifted out of the current item.
+    lifted: Vec<TopLevelStatement>,
+    next: usize,
+    /// Locals in scope, innermost last, mapped to whether they are boxed. Kept in step with
+    /// the checker's scopes.
+    scopes: Vec<HashMap<String, bool>>,
+    /// The locals to box of each function being rewritten, innermost last.
+    boxed: Vec<HashSet<String>>,
+}
+
+impl Lifter {
+    fn enter_scope(&mut self) {
+        self.checker.enter_scope();
+        self.scopes.push(HashMap::new());
+    }
+
+    fn exit_scope(&mut self) {
+        self.checker.exit_scope();
+        self.scopes.pop();
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains_key(name))
+    }
+
+    fn is_boxed(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Bind a new local of the current function. Returns whether it is boxed.
+    fn bind(&mut self, name: &str) -> bool {
+        let boxed = self.boxed.last().is_some_and(|boxed| boxed.contains(name));
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.to_string(), boxed);
+        boxed
+    }
+
+    /// Bind a parameter or loop variable, returning the statement that moves it into its box
+    /// if it needs one.
+    fn declare(&mut self, name: &str, var_type: Option<VarType>) -> Option<Statement> {
+        self.checker.declare(name, var_type);
+        self.bind(name)
+            .then(|| define(box_name(name), intrinsic(BOX, vec![ident(name)])))
+    }
+
+    fn rewrite_fn(&mut self, fn_def: &FnDef) -> FnDef {
+        self.boxed.push(escape::boxed_locals(fn_def));
+        self.enter_scope();
+        let prologue = fn_def
+            .parameters
+            .iter()
+            .filter_map(|(name, var_type)| self.declare(name, Some(var_type.clone())))
+            .collect();
+        let statement = self.rewrite_statement(&fn_def.statement);
+        self.exit_scope();
+        self.boxed.pop();
+
+        FnDef {
+            statement: prepend(prologue, statement),
+            ..fn_def.clone()
+        }
+    }
+
+    /// Lift a nested `fn` literal and return the closure value replacing it.
+    fn lift(&mut self, fn_def: &FnDef) -> Statement {
+        let (fn_name, env_name) = lambda_names(self.next);
+        self.next += 1;
+
+        // Boxed captures are shared: the environment holds the box rather than the value.
+        let mut fields = Vec::new();
+        for name in free_variables(fn_def, &self.checker) {
+            let var_type = self.checker.lookup_local(&name);
+            let var_type = var_type.expect("captured locals are typed");
+            fields.push(match self.is_boxed(&name) {
+                true => (box_name(&name), VarType::Ptr(Box::new(var_type))),
+                false => (name, var_type),
+            });
+        }
+
+        let body = self.rewrite_fn(fn_def);
+        let mut parameters = vec![(ENV.to_string(), VarType::IdentType(env_name.clone()))];
+        parameters.extend(body.parameters);
+        let prologue = fields
+            .iter()
+            .map(|(name, _)| {
+                let field = Statement::GetField(name.clone(), Box::new(ident(ENV)));
+                define(name.clone(), field)
+            })
+            .collect();
+
+        let mut args = vec![ident(&fn_name)];
+        args.extend(fields.iter().map(|(name, _)| ident(name)));
+
+        self.lifted.push(TopLevelStatement::TypeAlias(
+            env_name,
+            VarType::Struct(fields),
+        ));
+        self.lifted.push(TopLevelStatement::TopLevelDef(DefVar {
+            name: fn_name,
+            instruction: TopLevelDef::FnDef(FnDef {
+                generic_types: None,
+                parameters,
+                return_type: body.return_type,
+                statement: prepend(prologue, body.statement),
+            }),
+        }));
+
+        intrinsic(MAKE_CLOSURE, args)
+    }
+
+    fn rewrite_literal(&mut self, literal: &Literal) -> Statement {
+        let literal = match literal {
+            Literal::Fn(fn_def) => return self.lift(fn_def),
+            Literal::Tuple(items) => Literal::Tuple(self.rewrite_all(items)),
+            Literal::Array(items) => Literal::Array(self.rewrite_all(items)),
+            Literal::Data(tag, args) => Literal::Data(tag.clone(), self.rewrite_all(args)),
+            scalar => scalar.clone(),
+        };
+        Statement::Literal(literal)
+    }
+
+    fn rewrite_all(&mut self, statements: &[Statement]) -> Vec<Statement> {
+        let mut rewritten = Vec::with_capacity(statements.len());
+        for statement in statements {
+            match statement {
+                Statement::Declare(name, var_type) => {
+                    rewritten.extend(self.rewrite_declare(name, var_type));
+                }
+                _ => rewritten.push(self.rewrite_statement(statement)),
+            }
+        }
+        rewritten
+    }
+
+    /// A local declared without a value keeps its slot; if it is boxed, the box is made right
+    /// after it and the slot is never read again.
+    fn rewrite_declare(&mut self, name: &str, var_type: &VarType) -> Vec<Statement> {
+        let boxed = self.declare(name, Some(var_type.clone()));
+        std::iter::once(Statement::Declare(name.to_string(), var_type.clone()))
+            .chain(boxed)
+            .collect()
+    }
+
+    fn rewrite_boxed(&mut self, statement: &Statement) -> Box<Statement> {
+        Box::new(self.rewrite_statement(statement))
+    }
+
+    fn rewrite_statement(&mut self, statement: &Statement) -> Statement {
+        match statement {
+            Statement::Ident(name) if self.is_boxed(name) => {
+                intrinsic(UNBOX, vec![ident(&box_name(name))])
+            }
+            Statement::Ident(_) | Statement::Break | Statement::Continue => statement.clone(),
+            Statement::Return(value) => {
+                Statement::Return(value.as_ref().map(|value| self.rewrite_boxed(value)))
+            }
+            Statement::Declare(name, var_type) => {
+                Statement::DoBlock(self.rewrite_declare(name, var_type))
+            }
+            Statement::Literal(literal) => self.rewrite_literal(literal),
+            Statement::DoBlock(statements) => {
+                self.enter_scope();
+                let statements = self.rewrite_all(statements);
+                self.exit_scope();
+                Statement::DoBlock(statements)
+            }
+            Statement::Call(name, args) if self.is_boxed(name) => {
+                // Calls need a name, so load the boxed function into a temporary first.
+                let callee = format!("{}$fn", name);
+                let load = define(
+                    callee.clone(),
+                    intrinsic(UNBOX, vec![ident(&box_name(name))]),
+                );
+                Statement::DoBlock(vec![load, Statement::Call(callee, self.rewrite_all(args))])
+            }
+            Statement::Call(name, args) => Statement::Call(name.clone(), self.rewrite_all(args)),
+            Statement::GenericCall(name, type_args, args) => {
+                Statement::GenericCall(name.clone(), type_args.clone(), self.rewrite_all(args))
+            }
+            Statement::DefVar(var) => {
+                let value = self.rewrite_statement(&var.instruction);
+                if self.is_local(&var.name) {
+                    if self.is_boxed(&var.name) {
+                        return intrinsic(SET_BOX, vec![ident(&box_name(&var.name)), value]);
+                    }
+                    return define(var.name.clone(), value);
+                }
+
+                self.checker.declare_def(&var.name, &var.instruction);
+                match self.bind(&var.name) {
+                    true => define(box_name(&var.name), intrinsic(BOX, vec![value])),
+                    false => define(var.name.clone(), value),
+                }
+            }
+            Statement::If(condition, then) => {
+                Statement::If(self.rewrite_boxed(condition), self.rewrite_scoped(then))
+            }
+            Statement::IfElse(condition, then, otherwise) => Statement::IfElse(
+                self.rewrite_boxed(condition),
+                self.rewrite_scoped(then),
+                self.rewrite_scoped(otherwise),
+            ),
+            Statement::For(condition, body) => {
+                Statement::For(self.rewrite_boxed(condition), self.rewrite_scoped(body))
+            }
+            Statement::ForRange(name, range, body) => {
+                let element = self.checker.probe_range(range);
+                let range = Box::new(range.map(|part| self.rewrite_statement(part)));
+                self.enter_scope();
+                let prologue = self.declare(name, element).into_iter().collect();
+                let body = self.rewrite_statement(body);
+                self.exit_scope();
+                Statement::ForRange(name.clone(), range, Box::new(prepend(prologue, body)))
+            }
+            Statement::GetField(field, target) => {
+                Statement::GetField(field.clone(), self.rewrite_boxed(target))
+            }
+            Statement::GetIndexed(index, target) => {
+                Statement::GetIndexed(self.rewrite_boxed(index), self.rewrite_boxed(target))
+            }
+            Statement::SetField(field, target, value) => Statement::SetField(
+                field.clone(),
+                self.rewrite_boxed(target),
+                self.rewrite_boxed(value),
+            ),
+            Statement::SetIndexed(index, target, value) => Statement::SetIndexed(
+                self.rewrite_boxed(index),
+                self.rewrite_boxed(target),
+                self.rewrite_boxed(value),
+            ),
+        }
+    }
+
+    fn rewrite_scoped(&mut self, statement: &Statement) -> Box<Statement> {
+        self.enter_scope();
+        let statement = self.rewrite_statement(statement);
+        self.exit_scope();
+        Box::new(statement)
+    }
+}
+
+/// The locals of enclosing functions that `fn_def` refers to, in order of first use, including
+/// those only used by lambdas nested in it. Re-defining such a local with `def` assigns to it,
+/// so it counts as a use too.
+pub(crate) fn free_variables(fn_def: &FnDef, checker: &Checker) -> Vec<String> {
+    let parameters = fn_def.parameters.iter().map(|(name, _)| name.clone());
+    let mut free = FreeVariables {
+        checker,
+        scopes: vec![parameters.collect()],
+        names: Vec::new(),
+    };
+    free.visit_statement(&fn_def.statement);
+    free.names
+}
+
+struct FreeVariables<'a> {
+    checker: &'a Checker,
+    /// Names bound inside the lambda, innermost scope last.
+    scopes: Vec<HashSet<String>>,
+    names: Vec<String>,
+}
+
+impl FreeVariables<'_> {
+    fn is_bound(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    fn is_captured(&self, name: &str) -> bool {
+        !self.is_bound(name) && self.checker.lookup_local(name).is_some()
+    }
+
+    fn use_name(&mut self, name: &str) {
+        if self.is_captured(name) && !self.names.iter().any(|n| n == name) {
+            self.names.push(name.to_string());
+        }
+    }
+
+    fn scoped(&mut self, names: HashSet<String>, statement: &Statement) {
+        self.scopes.push(names);
+        self.visit_statement(statement);
+        self.scopes.pop();
+    }
+}
+
+impl Visitor for FreeVariables<'_> {
+    fn visit_fn_def(&mut self, fn_def: &FnDef) {
+        let names = fn_def.parameters.iter().map(|(n, _)| n.clone()).collect();
+        self.scoped(names, &fn_def.statement);
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Ident(name) => self.use_name(name),
+            Statement::DoBlock(_) => {
+                self.scopes.push(HashSet::new());
+                walk_statement(self, statement);
+                self.scopes.pop();
+            }
+            Statement::Call(name, _) | Statement::GenericCall(name, _, _) => {
+                self.use_name(name);
+                walk_statement(self, statement);
+            }
+            Statement::DefVar(def) => {
+                self.visit_statement(&def.instruction);
+                if self.is_captured(&def.name) {
+                    self.use_name(&def.name);
+                } else if !self.is_bound(&def.name) {
+                    self.scopes.last_mut().unwrap().insert(def.name.clone());
+                }
+            }
+            Statement::Declare(name, _) => {
+                self.scopes.last_mut().unwrap().insert(name.clone());
+            }
+            Statement::If(condition, then) | Statement::For(condition, then) => {
+                self.visit_statement(condition);
+                self.scoped(HashSet::new(), then);
+            }
+            Statement::IfElse(condition, then, otherwise) => {
+                self.visit_statement(condition);
+                self.scoped(HashSet::new(), then);
+                self.scoped(HashSet::new(), otherwise);
+            }
+            Statement::ForRange(name, range, body) => {
+                for part in range.parts() {
+                    self.visit_statement(part);
+                }
+                self.scoped(HashSet::from([name.clone()]), body);
+            }
+            _ => walk_statement(self, statement),
+        }
+    }
+}
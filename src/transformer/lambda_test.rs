@@ -0,0 +1,131 @@
+#[cfg(test)]
+mod tests {
+    use crate::parser::parser;
+    use crate::transformer::transform;
+    use chumsky::Parser;
+
+    fn lowered(src: &str) -> Vec<String> {
+        let module = parser().parse(src).into_output().unwrap();
+        let module = transform(&module).unwrap_or_else(|errors| panic!("{:?}", errors));
+        module.items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn test_capture_parameter() {
+        assert_eq!(
+            lowered("(def make-adder (fn [(:n i32)] fn [i32] i32 (fn [(:x i32)] i32 (+ x n))))"),
+            vec![
+                "(type lambda$0$env (struct (:n i32)))",
+                "(def lambda$0 (fn [(:$env lambda$0$env) (:x i32)] i32 (do (def n (get $env :n)) (+ x n))))",
+                "(def make-adder (fn [(:n i32)] fn [i32] i32 (closure lambda$0 n)))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_captures() {
+        assert_eq!(
+            lowered("(def make-one (fn [] fn [] i32 (fn [] i32 1)))"),
+            vec![
+                "(type lambda$0$env (struct))",
+                "(def lambda$0 (fn [(:$env lambda$0$env)] i32 1))",
+                "(def make-one (fn [] fn [] i32 (closure lambda$0)))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_lambdas() {
+        // `x` is captured by the outer lambda only to pass it on to the inner one.
+        assert_eq!(
+            lowered(
+                "(def add3 (fn [(:x i32)] fn [i32] fn [i32] i32
+                  (fn [(:y i32)] fn [i32] i32 (fn [(:z i32)] i32 (+ x (+ y z))))))"
+            ),
+            vec![
+                "(type lambda$1$env (struct (:x i32) (:y i32)))",
+                "(def lambda$1 (fn [(:$env lambda$1$env) (:z i32)] i32 (do (def x (get $env :x)) (def y (get $env :y)) (+ x (+ y z)))))",
+                "(type lambda$0$env (struct (:x i32)))",
+                "(def lambda$0 (fn [(:$env lambda$0$env) (:y i32)] fn [i32] i32 (do (def x (get $env :x)) (closure lambda$1 x y))))",
+                "(def add3 (fn [(:x i32)] fn [i32] fn [i32] i32 (closure lambda$0 x)))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_globals_and_shadowed_names_are_not_captured() {
+        let items = lowered(
+            "(def twice (fn [(:x i32)] i32 (* x 2)))
+            (def f (fn [(:x i32)] fn [i32] i32 (do (def k 3) (fn [(:x i32)] i32 (twice (+ x k))))))",
+        );
+        assert_eq!(items[1], "(type lambda$0$env (struct (:k i32)))");
+        assert_eq!(
+            items[3],
+            "(def f (fn [(:x i32)] fn [i32] i32 (do (def k 3) (closure lambda$0 k))))"
+        );
+    }
+
+    #[test]
+    fn test_called_captures() {
+        let items =
+            lowered("(def apply (fn [(:f fn [i32] i32)] fn [i32] i32 (fn [(:x i32)] i32 (f x))))");
+        assert_eq!(items[0], "(type lambda$0$env (struct (:f fn [i32] i32)))");
+        assert_eq!(
+            items[2],
+            "(def apply (fn [(:f fn [i32] i32)] fn [i32] i32 (closure lambda$0 f)))"
+        );
+    }
+
+    #[test]
+    fn test_reassigned_captures_are_boxed() {
+        assert_eq!(
+            lowered(
+                "(def make-counter (fn [(:start i32)] fn [] i32
+                  (do (def count start) (fn [] i32 (do (def old count) (def count (+ count 1)) old)))))"
+            ),
+            vec![
+                "(type lambda$0$env (struct (:count$box (ptr i32))))",
+                "(def lambda$0 (fn [(:$env lambda$0$env)] i32 (do (def count$box (get $env :count$box)) (do (def old (unbox count$box)) (set-box count$box (+ (unbox count$box) 1)) old))))",
+                "(def make-counter (fn [(:start i32)] fn [] i32 (do (def count$box (box start)) (closure lambda$0 count$box))))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_boxed_parameter_shared_with_nested_closures() {
+        let items = lowered(
+            "(def f (fn [(:n i32)] fn [] i32
+              (do (def g (fn [] fn [] i32 (fn [] i32 (do (def n (+ n 1)) n)))) (def n 5) (g))))",
+        );
+        assert_eq!(items[0], "(type lambda$1$env (struct (:n$box (ptr i32))))");
+        assert_eq!(
+            items[3],
+            "(def lambda$0 (fn [(:$env lambda$0$env)] fn [] i32 (do (def n$box (get $env :n$box)) (closure lambda$1 n$box))))"
+        );
+        assert_eq!(
+            items[4],
+            "(def f (fn [(:n i32)] fn [] i32 (do (def n$box (box n)) (do (def g (closure lambda$0 n$box)) (set-box n$box 5) (g)))))"
+        );
+    }
+
+    #[test]
+    fn test_boxed_dollar_targets_and_callees() {
+        let items = lowered(
+            "(type point (struct (:x i32)))
+            (def f (fn [(:p point)] fn [] i32 (do (def g (fn [] i32 ($ :x p))) ($ :x p 3) g)))",
+        );
+        assert_eq!(
+            items[3],
+            "(def f (fn [(:p point)] fn [] i32 (do (def p$box (box p)) (do (def g (closure lambda$0 p$box)) (set (unbox p$box) :x 3) g))))"
+        );
+
+        let items = lowered(
+            "(def f (fn [(:h fn [] i32)] fn [] i32
+              (do (def g (fn [] i32 (h))) (def h (fn [] i32 2)) g)))",
+        );
+        assert_eq!(
+            items[1],
+            "(def lambda$0 (fn [(:$env lambda$0$env)] i32 (do (def h$box (get $env :h$box)) (do (def h$fn (unbox h$box)) (h$fn)))))"
+        );
+    }
+}
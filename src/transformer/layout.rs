@@ -0,0 +1,281 @@
+//! Memory layout of monomorphic types, and the lowering of `$` chains to address arithmetic.
+//!
+//! Layouts follow the C rules of the LP64 targets we emit for: scalars are aligned to their
+//! size, tuples and structs lay their members out in order with padding, and a `data` value is
+//! a `u32` tag followed by the largest variant payload. Unsized arrays, `str`, atoms and C
+//! function pointers are a single pointer; a closure is a code pointer and an environment
+//! pointer.
+
+use crate::ast::{Literal, Statement, TopLevelStatement, VarType};
+use crate::transformer::ast::{AccessSegment, Module, TransformedItem, TransformedStmt};
+use crate::typeck::types::Aliases;
+
+pub const POINTER_SIZE: usize = 8;
+
+/// Recursion through by-value members that is deeper than this is an infinitely sized type.
+const MAX_NESTING: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub size: usize,
+    pub align: usize,
+}
+
+impl Layout {
+    fn scalar(size: usize) -> Self {
+        Layout { size, align: size }
+    }
+
+    /// The distance between consecutive elements of an array of this type.
+    pub fn stride(&self) -> usize {
+        round_up(self.size, self.align)
+    }
+}
+
+fn round_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+/// The type aliases declared by `module`.
+pub fn module_aliases(module: &Module) -> Aliases {
+    let mut aliases = Aliases::default();
+    for item in &module.items {
+        if let TransformedItem::Orig(TopLevelStatement::TypeAlias(name, t)) = item {
+            aliases.types.insert(name.clone(), t.clone());
+        }
+    }
+    aliases
+}
+
+pub fn layout_of(var_type: &VarType, aliases: &Aliases) -> Result<Layout, String> {
+    layout_at(var_type, aliases, 0)
+}
+
+/// Whether a value of `var_type` can hold a pointer, so that the garbage collector has to scan
+/// the memory it is stored in. Types it cannot lay out are assumed to.
+pub fn holds_pointers(var_type: &VarType, aliases: &Aliases) -> bool {
+    holds_pointers_at(var_type, aliases, 0)
+}
+
+fn holds_pointers_at(var_type: &VarType, aliases: &Aliases, depth: usize) -> bool {
+    if depth > MAX_NESTING {
+        return true;
+    }
+    let any = |members: &[VarType]| {
+        members
+            .iter()
+            .any(|member| holds_pointers_at(member, aliases, depth + 1))
+    };
+    match aliases.normalize(var_type) {
+        VarType::Int8
+        | VarType::UInt8
+        | VarType::Bool
+        | VarType::Int16
+        | VarType::UInt16
+        | VarType::Float16
+        | VarType::Int32
+        | VarType::UInt32
+        | VarType::Float32
+        | VarType::Int64
+        | VarType::UInt64
+        | VarType::Float64
+        | VarType::Int128
+        | VarType::UInt128
+        | VarType::Float128
+        | VarType::Void => false,
+        VarType::ArraySized(element, _) => holds_pointers_at(&element, aliases, depth + 1),
+        VarType::Tuple(members) => any(&members),
+        VarType::Struct(fields) => fields
+            .iter()
+            .any(|(_, member)| holds_pointers_at(member, aliases, depth + 1)),
+        VarType::Data(variants) => variants.iter().any(|(_, members)| any(members)),
+        _ => true,
+    }
+}
+
+/// The byte offset of every member of a tuple or struct, in order, and the layout of the whole.
+pub fn member_offsets(
+    members: &[VarType],
+    aliases: &Aliases,
+) -> Result<(Vec<usize>, Layout), String> {
+    members_at(members, aliases, 0)
+}
+
+fn members_at(
+    members: &[VarType],
+    aliases: &Aliases,
+    depth: usize,
+) -> Result<(Vec<usize>, Layout), String> {
+    let mut offsets = Vec::with_capacity(members.len());
+    let mut size = 0;
+    let mut align = 1;
+    for member in members {
+        let layout = layout_at(member, aliases, depth + 1)?;
+        size = round_up(size, layout.align);
+        offsets.push(size);
+        size += layout.size;
+        align = align.max(layout.align);
+    }
+    let size = round_up(size, align);
+    Ok((offsets, Layout { size, align }))
+}
+
+fn layout_at(var_type: &VarType, aliases: &Aliases, depth: usize) -> Result<Layout, String> {
+    if depth > MAX_NESTING {
+        return Err(format!("type `{}` has infinite size", var_type));
+    }
+
+    let layout = match aliases.normalize(var_type) {
+        VarType::Int8 | VarType::UInt8 | VarType::Bool => Layout::scalar(1),
+        VarType::Int16 | VarType::UInt16 | VarType::Float16 => Layout::scalar(2),
+        VarType::Int32 | VarType::UInt32 | VarType::Float32 => Layout::scalar(4),
+        VarType::Int64 | VarType::UInt64 | VarType::Float64 => Layout::scalar(8),
+        VarType::Int128 | VarType::UInt128 | VarType::Float128 => Layout::scalar(16),
+        VarType::Void => Layout { size: 0, align: 1 },
+        VarType::IdentType(name) if name == "atom" => Layout::scalar(POINTER_SIZE),
+        VarType::Ptr(_) | VarType::ArrayUnsized(_) | VarType::FnWithVarArgs(..) => {
+            Layout::scalar(POINTER_SIZE)
+        }
+        VarType::Fn(..) => Layout {
+            size: 2 * POINTER_SIZE,
+            align: POINTER_SIZE,
+        },
+        VarType::ArraySized(element, length) => {
+            let element = layout_at(&element, aliases, depth + 1)?;
+            Layout {
+                size: element.stride() * length,
+                align: element.align,
+            }
+        }
+        VarType::Tuple(members) => members_at(&members, aliases, depth)?.1,
+        VarType::Struct(fields) => {
+            let members: Vec<VarType> = fields.into_iter().map(|(_, t)| t).collect();
+            members_at(&members, aliases, depth)?.1
+        }
+        VarType::Data(variants) => {
+            let mut payload = Layout { size: 0, align: 1 };
+            for (_, members) in &variants {
+                let (_, layout) = members_at(members, aliases, depth)?;
+                payload.size = payload.size.max(layout.size);
+                payload.align = payload.align.max(layout.align);
+            }
+            let tag = Layout::scalar(4);
+            let offset = round_up(tag.size, payload.align);
+            let align = tag.align.max(payload.align);
+            Layout {
+                size: round_up(offset + payload.size, align),
+                align,
+            }
+        }
+        other => return Err(format!("type `{}` has no memory layout", other)),
+    };
+    Ok(layout)
+}
+
+/// One step of an address computation, applied to the address of a chain's root.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AddressStep {
+    /// Add a constant number of bytes.
+    Offset(usize),
+    /// Add `index * stride` bytes.
+    Index(TransformedStmt, usize),
+    /// Continue from the pointer stored at the current address.
+    Load,
+}
+
+/// Where a `$` chain points: the steps from its root's address and the type found there.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Address {
+    pub steps: Vec<AddressStep>,
+    pub var_type: VarType,
+}
+
+impl Address {
+    fn offset(&mut self, bytes: usize) {
+        if bytes == 0 {
+            return;
+        }
+        match self.steps.last_mut() {
+            Some(AddressStep::Offset(previous)) => *previous += bytes,
+            _ => self.steps.push(AddressStep::Offset(bytes)),
+        }
+    }
+}
+
+/// Lower the segments of a `ChainAccess` or `ChainAssign` whose root has type `root_type` to
+/// offsets from the root's address. Fields and indices of a pointer, or of an unsized array,
+/// first load the pointer.
+pub fn chain_address(
+    root_type: &VarType,
+    segments: &[AccessSegment<TransformedStmt>],
+    aliases: &Aliases,
+) -> Result<Address, String> {
+    let mut address = Address {
+        steps: Vec::new(),
+        var_type: root_type.clone(),
+    };
+
+    for segment in segments {
+        let mut current = aliases.normalize(&address.var_type);
+        if let (VarType::Ptr(inner), AccessSegment::Field(_)) = (&current, segment) {
+            address.steps.push(AddressStep::Load);
+            current = aliases.normalize(inner);
+        }
+
+        address.var_type = match (current, segment) {
+            (VarType::Struct(fields), AccessSegment::Field(name)) => {
+                let members: Vec<VarType> = fields.iter().map(|(_, t)| t.clone()).collect();
+                let (offsets, _) = member_offsets(&members, aliases)?;
+                let position = fields
+                    .iter()
+                    .position(|(field, _)| field == name)
+                    .ok_or_else(|| format!("no field `{}` in `{}`", name, address.var_type))?;
+                address.offset(offsets[position]);
+                members[position].clone()
+            }
+            (VarType::Tuple(members), AccessSegment::Index(index)) => {
+                let position = match index.as_ref() {
+                    TransformedStmt::Orig(Statement::Literal(Literal::Int(n)))
+                        if *n >= 0 && (*n as usize) < members.len() =>
+                    {
+                        *n as usize
+                    }
+                    _ => {
+                        return Err(format!(
+                            "tuple `{}` can only be indexed by a literal in range",
+                            address.var_type
+                        ))
+                    }
+                };
+                let (offsets, _) = member_offsets(&members, aliases)?;
+                address.offset(offsets[position]);
+                members[position].clone()
+            }
+            (VarType::ArraySized(element, _), AccessSegment::Index(index)) => {
+                let stride = layout_of(&element, aliases)?.stride();
+                address
+                    .steps
+                    .push(AddressStep::Index(index.as_ref().clone(), stride));
+                *element
+            }
+            (
+                VarType::ArrayUnsized(element) | VarType::Ptr(element),
+                AccessSegment::Index(index),
+            ) => {
+                let stride = layout_of(&element, aliases)?.stride();
+                address.steps.push(AddressStep::Load);
+                address
+                    .steps
+                    .push(AddressStep::Index(index.as_ref().clone(), stride));
+                *element
+            }
+            (_, AccessSegment::Field(name)) => {
+                return Err(format!("no field `{}` in `{}`", name, address.var_type))
+            }
+            (_, AccessSegment::Index(_)) => {
+                return Err(format!("`{}` cannot be indexed", address.var_type))
+            }
+        };
+    }
+    Ok(address)
+}
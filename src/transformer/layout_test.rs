@@ -0,0 +1,169 @@
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Literal, Statement, VarType};
+    use crate::parser::parser;
+    use crate::transformer::ast::{AccessSegment, TransformedStmt};
+    use crate::transformer::layout::{
+        chain_address, holds_pointers, layout_of, module_aliases, AddressStep, Layout,
+    };
+    use crate::transformer::transform;
+    use crate::typeck::types::Aliases;
+    use chumsky::Parser;
+
+    const TYPES: &str = "
+        (type point (struct (:x f64) (:y f64)))
+        (type cell (struct (:tag u8) (:value i32) (:next (ptr cell))))
+        (type board (struct (:size i16) (:cells [point 4]) (:origin point)))
+        (type shape (data [:circle f32] [:rect f64 f64] [:none]))
+    ";
+
+    fn aliases() -> Aliases {
+        let module = parser().parse(TYPES).into_output().unwrap();
+        module_aliases(&transform(&module).unwrap())
+    }
+
+    fn named(name: &str) -> VarType {
+        VarType::IdentType(name.into())
+    }
+
+    fn ident(name: &str) -> TransformedStmt {
+        TransformedStmt::Orig(Statement::Ident(name.into()))
+    }
+
+    fn literal(n: i64) -> TransformedStmt {
+        TransformedStmt::Orig(Statement::Literal(Literal::Int(n)))
+    }
+
+    #[test]
+    fn test_layouts_are_padded_like_c() {
+        let aliases = aliases();
+        let layout = |name| layout_of(&named(name), &aliases).unwrap();
+        assert_eq!(layout("point"), Layout { size: 16, align: 8 });
+        assert_eq!(layout("cell"), Layout { size: 16, align: 8 });
+        assert_eq!(layout("board"), Layout { size: 88, align: 8 });
+        assert_eq!(layout("shape"), Layout { size: 24, align: 8 });
+        assert_eq!(layout("str"), Layout { size: 8, align: 8 });
+        let tuple = VarType::Tuple(vec![VarType::Bool, VarType::Int16, VarType::Int8]);
+        assert_eq!(
+            layout_of(&tuple, &aliases).unwrap(),
+            Layout { size: 6, align: 2 }
+        );
+    }
+
+    #[test]
+    fn test_pointer_free_types() {
+        let aliases = aliases();
+        let holds = |name| holds_pointers(&named(name), &aliases);
+        assert!(!holds("point"));
+        assert!(!holds("board"));
+        assert!(!holds("shape"));
+        assert!(holds("cell"));
+        assert!(holds("str"));
+        assert!(holds("atom"));
+        let closure = VarType::Fn(vec![], Box::new(VarType::Void));
+        assert!(holds_pointers(
+            &VarType::Tuple(vec![VarType::Int8, closure]),
+            &aliases
+        ));
+    }
+
+    #[test]
+    fn test_infinite_types_have_no_layout() {
+        let mut aliases = Aliases::default();
+        aliases.types.insert(
+            "list".into(),
+            VarType::Struct(vec![("next".into(), named("list"))]),
+        );
+        assert_eq!(
+            layout_of(&named("list"), &aliases).unwrap_err(),
+            "type `list` has infinite size"
+        );
+    }
+
+    #[test]
+    fn test_field_and_index_offsets_are_merged() {
+        let address = chain_address(
+            &named("board"),
+            &[
+                AccessSegment::Field("cells".into()),
+                AccessSegment::Index(Box::new(ident("i"))),
+                AccessSegment::Field("y".into()),
+            ],
+            &aliases(),
+        )
+        .unwrap();
+        assert_eq!(
+            address.steps,
+            vec![
+                AddressStep::Offset(8),
+                AddressStep::Index(ident("i"), 16),
+                AddressStep::Offset(8),
+            ]
+        );
+        assert_eq!(address.var_type, VarType::Float64);
+
+        let address = chain_address(
+            &named("board"),
+            &[
+                AccessSegment::Field("origin".into()),
+                AccessSegment::Field("y".into()),
+            ],
+            &aliases(),
+        )
+        .unwrap();
+        assert_eq!(address.steps, vec![AddressStep::Offset(80)]);
+    }
+
+    #[test]
+    fn test_pointers_are_loaded() {
+        let address = chain_address(
+            &VarType::Ptr(Box::new(named("cell"))),
+            &[
+                AccessSegment::Field("next".into()),
+                AccessSegment::Field("value".into()),
+                AccessSegment::Index(Box::new(literal(0))),
+            ],
+            &aliases(),
+        );
+        assert_eq!(address.unwrap_err(), "`i32` cannot be indexed");
+
+        let address = chain_address(
+            &VarType::Ptr(Box::new(named("cell"))),
+            &[
+                AccessSegment::Field("next".into()),
+                AccessSegment::Field("value".into()),
+            ],
+            &aliases(),
+        )
+        .unwrap();
+        assert_eq!(
+            address.steps,
+            vec![
+                AddressStep::Load,
+                AddressStep::Offset(8),
+                AddressStep::Load,
+                AddressStep::Offset(4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tuples_need_literal_indices() {
+        let pair = VarType::Tuple(vec![VarType::Int8, VarType::Float64]);
+        let address = chain_address(
+            &pair,
+            &[AccessSegment::Index(Box::new(literal(1)))],
+            &aliases(),
+        );
+        assert_eq!(address.unwrap().steps, vec![AddressStep::Offset(8)]);
+        let address = chain_address(
+            &pair,
+            &[AccessSegment::Index(Box::new(ident("i")))],
+            &aliases(),
+        );
+        assert_eq!(
+            address.unwrap_err(),
+            "tuple `{i8 f64}` can only be indexed by a literal in range"
+        );
+    }
+}
@@ -1,11 +1,28 @@
 pub mod ast;
 pub mod dollar;
 mod dollar_test;
+pub mod escape;
+mod escape_test;
+pub mod lambda;
+mod lambda_test;
+pub mod layout;
+mod layout_test;
+pub mod monomorph;
+mod monomorph_test;
+mod transform_test;
+pub mod visit;
+mod visit_test;
 
 use crate::ast as orig;
+use crate::consteval;
+use crate::diagnostic::Diagnostic;
 
-pub fn transform(module: &[orig::TopLevelStatement]) -> ast::Module {
-    ast::Module {
-        items: module.to_vec(),
-    }
+/// Run the transformer pipeline: constant evaluation, monomorphization, lambda lifting, then
+/// lowering into `TransformedStmt` trees with `$` chains linearized. Fails with constants that
+/// cannot be evaluated, with the type checker's errors or with malformed `$` chains.
+pub fn transform(module: &[orig::TopLevelStatement]) -> Result<ast::Module, Vec<Diagnostic>> {
+    let folded = consteval::run(module)?;
+    let mono = monomorph::run(&folded)?;
+    let lifted = lambda::run(&mono);
+    dollar::run(&lifted)
 }
@@ -0,0 +1,449 @@
+//! Monomorphization.
+//!
+//! Every use of a generic function or generic type with concrete type arguments is replaced by
+//! a specialized copy named by [`mangle`], e.g. `(id 1)` calls `id$i32` and `pair<i32 f64>`
+//! becomes the alias `pair$i32$f64`. Type arguments are either explicit (`(id<i64> 1)`) or
+//! inferred by the type checker from the call's arguments. Each distinct instance is generated
+//! once, no matter how many call sites use it.
+//!
+//! The pass runs on a type checked module and reports the checker's errors instead of
+//! producing output. The result contains no generic definitions: specialized type aliases come
+//! first, and each generic function is replaced in place by its instances.
+
+use crate::ast::{
+    generic_names, DefVar, FnDef, Literal, Statement, TopLevelDef, TopLevelStatement, VarType,
+};
+use crate::diagnostic::Diagnostic;
+use crate::parser::var_type;
+use crate::typeck::types::{mangle, substitute};
+use crate::typeck::{self, Checker};
+use crate::visit::{noop_fold_fn_def, noop_fold_statement, Fold};
+use chumsky::Parser;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Upper bound on generated function instances, which stops polymorphic recursion such as a
+/// `f<T>` that calls `f<(ptr T)>`.
+const MAX_INSTANCES: usize = 1024;
+
+/// Instance bodies have no inferred bindings of their own; their locals take the types the
+/// checker computes for the specialized code.
+const INSTANCE_ITEM: usize = usize::MAX;
+
+pub fn run(module: &[TopLevelStatement]) -> Result<Vec<TopLevelStatement>, Vec<Diagnostic>> {
+    let errors: Vec<_> = typeck::check(module)
+        .into_iter()
+        .filter(Diagnostic::is_error)
+        .collect();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut mono = Monomorphizer::new(module);
+    let mut items: Vec<Option<TopLevelStatement>> = Vec::new();
+    for (item, statement) in module.iter().enumerate() {
+        mono.item = item;
+        mono.checker.enter_item(item);
+        items.push(mono.rewrite_item(statement));
+    }
+    mono.generate_instances();
+
+    if !mono.diagnostics.is_empty() {
+        return Err(mono.diagnostics);
+    }
+
+    let mut output = mono.type_instances;
+    for (item, statement) in items.into_iter().enumerate() {
+        match statement {
+            Some(statement) => output.push(statement),
+            None => output.extend(mono.fn_instances.remove(&item).unwrap_or_default()),
+        }
+    }
+    Ok(output)
+}
+
+struct Monomorphizer {
+    checker: Checker,
+    /// Generic top-level functions by name, with their item index.
+    generic_fns: HashMap<String, (usize, FnDef)>,
+    /// Names of all instances requested so far.
+    seen: HashSet<String>,
+    /// Function instances still to be generated: generic name, type arguments, mangled name.
+    queue: VecDeque<(String, Vec<VarType>, String)>,
+    /// Generated function instances, keyed by the item index of their generic function.
+    fn_instances: HashMap<usize, Vec<TopLevelStatement>>,
+    type_instances: Vec<TopLevelStatement>,
+    diagnostics: Vec<Diagnostic>,
+    item: usize,
+}
+
+impl Monomorphizer {
+    fn new(module: &[TopLevelStatement]) -> Self {
+        let mut generic_fns = HashMap::new();
+        for (item, statement) in module.iter().enumerate() {
+            if let TopLevelStatement::TopLevelDef(def) = statement {
+                if let TopLevelDef::FnDef(fn_def) = &def.instruction {
+                    if fn_def.generic_types.is_some() {
+                        generic_fns.insert(def.name.clone(), (item, fn_def.clone()));
+                    }
+                }
+            }
+        }
+
+        Monomorphizer {
+            checker: Checker::new(module),
+            generic_fns,
+            seen: HashSet::new(),
+            queue: VecDeque::new(),
+            fn_instances: HashMap::new(),
+            type_instances: Vec::new(),
+            diagnostics: Vec::new(),
+            item: 0,
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        self.diagnostics.push(Diagnostic::error(self.item, message));
+    }
+
+    /// The rewritten item, or `None` for generic definitions, which only survive as instances.
+    fn rewrite_item(&mut self, statement: &TopLevelStatement) -> Option<TopLevelStatement> {
+        match statement {
+            TopLevelStatement::TypeAlias(name, var_type) => {
+                if self.checker.aliases.generic_params(name).is_some() {
+                    return None;
+                }
+                let var_type = self.rewrite_type(var_type);
+                Some(TopLevelStatement::TypeAlias(name.clone(), var_type))
+            }
+            TopLevelStatement::TopLevelDef(def) => {
+                let instruction = match &def.instruction {
+                    TopLevelDef::FnDef(fn_def) if fn_def.generic_types.is_some() => return None,
+                    TopLevelDef::FnDef(fn_def) => TopLevelDef::FnDef(self.rewrite_fn(fn_def)),
+                    TopLevelDef::Literal(literal) => {
+                        TopLevelDef::Literal(self.rewrite_literal(literal))
+                    }
+                    TopLevelDef::Typed(var_type) => TopLevelDef::Typed(self.rewrite_type(var_type)),
+                    TopLevelDef::Const(statement) => {
+                        TopLevelDef::Const(self.rewrite_statement(statement))
+                    }
+                };
+                Some(TopLevelStatement::TopLevelDef(DefVar {
+                    name: def.name.clone(),
+                    instruction,
+                }))
+            }
+            other => Some(other.clone()),
+        }
+    }
+
+    fn generate_instances(&mut self) {
+        let mut generated = 0;
+        while let Some((name, type_args, mangled)) = self.queue.pop_front() {
+            let (item, generic) = self.generic_fns[&name].clone();
+            self.item = item;
+
+            generated += 1;
+            if generated > MAX_INSTANCES {
+                self.error(format!(
+                    "monomorphization of `{}` does not terminate; it instantiates itself with ever larger type arguments",
+                    name
+                ));
+                return;
+            }
+
+            let generics = generic.generic_types.as_deref().unwrap_or_default();
+            let bindings: HashMap<_, _> =
+                generic_names(generics).into_iter().zip(type_args).collect();
+            let instance = SubstituteTypes { bindings }.fold_fn_def(FnDef {
+                generic_types: None,
+                ..generic
+            });
+
+            self.checker.enter_item(INSTANCE_ITEM);
+            let instance = self.rewrite_fn(&instance);
+            self.fn_instances
+                .entry(item)
+                .or_default()
+                .push(TopLevelStatement::TopLevelDef(DefVar {
+                    name: mangled,
+                    instruction: TopLevelDef::FnDef(instance),
+                }));
+        }
+    }
+
+    /// Request the instance of the generic function `name` for `type_args`.
+    fn instantiate_fn(&mut self, name: &str, type_args: Vec<VarType>) -> String {
+        let mangled = mangle(name, &type_args);
+        if self.seen.insert(mangled.clone()) {
+            self.queue
+                .push_back((name.to_string(), type_args, mangled.clone()));
+        }
+        mangled
+    }
+
+    /// Replace instances of generic types in `var_type` by their specialized aliases.
+    fn rewrite_type(&mut self, var_type: &VarType) -> VarType {
+        match var_type {
+            VarType::GenericInstance(name, args) => {
+                let mangled = mangle(name, args);
+                // Mark the instance before rewriting its body so recursive types terminate.
+                if self.seen.insert(mangled.clone()) {
+                    if let Some(body) = self.checker.aliases.instantiate(name, args) {
+                        let body = self.rewrite_type(&body);
+                        self.type_instances
+                            .push(TopLevelStatement::TypeAlias(mangled.clone(), body));
+                    }
+                }
+                VarType::IdentType(mangled)
+            }
+            VarType::ArraySized(t, size) => {
+                VarType::ArraySized(Box::new(self.rewrite_type(t)), *size)
+            }
+            VarType::ArrayUnsized(t) => VarType::ArrayUnsized(Box::new(self.rewrite_type(t))),
+            VarType::Ptr(t) => VarType::Ptr(Box::new(self.rewrite_type(t))),
+            VarType::Tuple(ts) => VarType::Tuple(self.rewrite_types(ts)),
+            VarType::Struct(fields) => VarType::Struct(
+                fields
+                    .iter()
+                    .map(|(n, t)| (n.clone(), self.rewrite_type(t)))
+                    .collect(),
+            ),
+            VarType::Data(variants) => VarType::Data(
+                variants
+                    .iter()
+                    .map(|(n, ts)| (n.clone(), self.rewrite_types(ts)))
+                    .collect(),
+            ),
+            VarType::Fn(params, ret) => {
+                VarType::Fn(self.rewrite_types(params), Box::new(self.rewrite_type(ret)))
+            }
+            VarType::FnWithVarArgs(params, ret) => {
+                VarType::FnWithVarArgs(self.rewrite_types(params), Box::new(self.rewrite_type(ret)))
+            }
+            other => other.clone(),
+        }
+    }
+
+    fn rewrite_types(&mut self, var_types: &[VarType]) -> Vec<VarType> {
+        var_types.iter().map(|t| self.rewrite_type(t)).collect()
+    }
+
+    fn rewrite_fn(&mut self, fn_def: &FnDef) -> FnDef {
+        self.checker.enter_scope();
+        let mut parameters = Vec::new();
+        for (name, var_type) in &fn_def.parameters {
+            self.checker.declare(name, Some(var_type.clone()));
+            parameters.push((name.clone(), self.rewrite_type(var_type)));
+        }
+        let statement = self.rewrite_statement(&fn_def.statement);
+        self.checker.exit_scope();
+
+        FnDef {
+            generic_types: None,
+            parameters,
+            return_type: self.rewrite_type(&fn_def.return_type),
+            statement,
+        }
+    }
+
+    fn rewrite_literal(&mut self, literal: &Literal) -> Literal {
+        match literal {
+            Literal::Tuple(items) => Literal::Tuple(self.rewrite_all(items)),
+            Literal::Array(items) => Literal::Array(self.rewrite_all(items)),
+            Literal::Data(tag, args) => {
+                // Register the specialized alias of a generic `data` type.
+                let statement = Statement::Literal(literal.clone());
+                if let Some(var_type) = self.checker.probe(&statement, None) {
+                    self.rewrite_type(&var_type);
+                }
+                Literal::Data(tag.clone(), self.rewrite_all(args))
+            }
+            Literal::Fn(fn_def) => Literal::Fn(Box::new(self.rewrite_fn(fn_def))),
+            other => other.clone(),
+        }
+    }
+
+    fn rewrite_all(&mut self, statements: &[Statement]) -> Vec<Statement> {
+        statements
+            .iter()
+            .map(|s| self.rewrite_statement(s))
+            .collect()
+    }
+
+    /// Whether `name` refers to a generic top-level function rather than a local.
+    fn is_generic_fn(&self, name: &str) -> bool {
+        self.generic_fns.contains_key(name) && self.checker.lookup_local(name).is_none()
+    }
+
+    /// The name that a call to `name` with the given type arguments is rewritten to.
+    fn instance_name(
+        &mut self,
+        name: &str,
+        type_args: Option<&[String]>,
+        args: &[Statement],
+    ) -> String {
+        let is_fn = self.is_generic_fn(name);
+        let is_type = self.checker.lookup(name).is_none()
+            && self.checker.aliases.generic_params(name).is_some();
+        if !is_fn && !is_type {
+            return name.to_string();
+        }
+
+        let Some(type_args) = self.checker.call_type_args(name, type_args, args) else {
+            self.error(format!(
+                "cannot infer the type arguments of `{}`; pass them explicitly as `{}<...>`",
+                name, name
+            ));
+            return name.to_string();
+        };
+
+        if is_fn {
+            self.instantiate_fn(name, type_args)
+        } else {
+            match self.rewrite_type(&VarType::GenericInstance(name.to_string(), type_args)) {
+                VarType::IdentType(mangled) => mangled,
+                _ => name.to_string(),
+            }
+        }
+    }
+
+    fn rewrite_statement(&mut self, statement: &Statement) -> Statement {
+        match statement {
+            Statement::Ident(name) => {
+                if self.is_generic_fn(name) {
+                    self.error(format!(
+                        "generic function `{}` must be called or instantiated with explicit type arguments",
+                        name
+                    ));
+                }
+                statement.clone()
+            }
+            Statement::Break | Statement::Continue => statement.clone(),
+            Statement::Return(value) => Statement::Return(
+                value
+                    .as_ref()
+                    .map(|value| Box::new(self.rewrite_statement(value))),
+            ),
+            Statement::Declare(name, var_type) => {
+                let var_type = self.rewrite_type(var_type);
+                self.checker.declare(name, Some(var_type.clone()));
+                Statement::Declare(name.clone(), var_type)
+            }
+            Statement::Literal(literal) => Statement::Literal(self.rewrite_literal(literal)),
+            Statement::DoBlock(statements) => {
+                self.checker.enter_scope();
+                let statements = self.rewrite_all(statements);
+                self.checker.exit_scope();
+                Statement::DoBlock(statements)
+            }
+            Statement::Call(name, args) => {
+                let name = self.instance_name(name, None, args);
+                Statement::Call(name, self.rewrite_all(args))
+            }
+            Statement::GenericCall(name, type_args, args) => {
+                let name = self.instance_name(name, Some(type_args), args);
+                Statement::Call(name, self.rewrite_all(args))
+            }
+            Statement::DefVar(def) => {
+                let instruction = self.rewrite_statement(&def.instruction);
+                self.checker.declare_def(&def.name, &def.instruction);
+                Statement::DefVar(DefVar {
+                    name: def.name.clone(),
+                    instruction: Box::new(instruction),
+                })
+            }
+            Statement::If(condition, then) => {
+                let condition = self.rewrite_statement(condition);
+                let then = self.rewrite_scoped(then);
+                Statement::If(Box::new(condition), Box::new(then))
+            }
+            Statement::IfElse(condition, then, otherwise) => {
+                let condition = self.rewrite_statement(condition);
+                let then = self.rewrite_scoped(then);
+                let otherwise = self.rewrite_scoped(otherwise);
+                Statement::IfElse(Box::new(condition), Box::new(then), Box::new(otherwise))
+            }
+            Statement::For(condition, body) => {
+                let condition = self.rewrite_statement(condition);
+                let body = self.rewrite_scoped(body);
+                Statement::For(Box::new(condition), Box::new(body))
+            }
+            Statement::ForRange(name, range, body) => {
+                let element = self.checker.probe_range(range);
+                let range = range.map(|part| self.rewrite_statement(part));
+                self.checker.enter_scope();
+                self.checker.declare(name, element);
+                let body = self.rewrite_statement(body);
+                self.checker.exit_scope();
+                Statement::ForRange(name.clone(), Box::new(range), Box::new(body))
+            }
+            Statement::GetField(field, target) => {
+                Statement::GetField(field.clone(), Box::new(self.rewrite_statement(target)))
+            }
+            Statement::GetIndexed(index, target) => Statement::GetIndexed(
+                Box::new(self.rewrite_statement(index)),
+                Box::new(self.rewrite_statement(target)),
+            ),
+            Statement::SetField(field, target, value) => Statement::SetField(
+                field.clone(),
+                Box::new(self.rewrite_statement(target)),
+                Box::new(self.rewrite_statement(value)),
+            ),
+            Statement::SetIndexed(index, target, value) => Statement::SetIndexed(
+                Box::new(self.rewrite_statement(index)),
+                Box::new(self.rewrite_statement(target)),
+                Box::new(self.rewrite_statement(value)),
+            ),
+        }
+    }
+
+    fn rewrite_scoped(&mut self, statement: &Statement) -> Statement {
+        self.checker.enter_scope();
+        let statement = self.rewrite_statement(statement);
+        self.checker.exit_scope();
+        statement
+    }
+}
+
+/// Replaces the type parameters bound in `bindings` throughout the types written in a function.
+struct SubstituteTypes {
+    bindings: HashMap<String, VarType>,
+}
+
+impl Fold for SubstituteTypes {
+    fn fold_fn_def(&mut self, fn_def: FnDef) -> FnDef {
+        // A nested generic function redeclares its own parameters.
+        let outer = self.bindings.clone();
+        for generic in fn_def.generic_types.iter().flatten() {
+            self.bindings.remove(&generic.name);
+        }
+        let fn_def = noop_fold_fn_def(self, fn_def);
+        self.bindings = outer;
+        fn_def
+    }
+
+    fn fold_statement(&mut self, statement: Statement) -> Statement {
+        match statement {
+            Statement::GenericCall(name, type_args, args) => {
+                // Type arguments are kept as source text; re-render them after substitution.
+                let type_args = type_args
+                    .into_iter()
+                    .map(|t| {
+                        let parsed = var_type().parse(&t).into_output();
+                        match parsed {
+                            Some(var_type) => substitute(&var_type, &self.bindings).to_string(),
+                            None => t,
+                        }
+                    })
+                    .collect();
+                let args = args.into_iter().map(|a| self.fold_statement(a)).collect();
+                Statement::GenericCall(name, type_args, args)
+            }
+            other => noop_fold_statement(self, other),
+        }
+    }
+
+    fn fold_var_type(&mut self, var_type: VarType) -> VarType {
+        substitute(&var_type, &self.bindings)
+    }
+}
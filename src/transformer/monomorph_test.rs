@@ -0,0 +1,214 @@
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Statement, TopLevelDef, TopLevelStatement, VarType};
+    use crate::parser::parser;
+    use crate::transformer::monomorph::run;
+    use chumsky::Parser;
+
+    fn mono(src: &str) -> Vec<TopLevelStatement> {
+        let module = parser().parse(src).into_output().unwrap();
+        run(&module).unwrap_or_else(|errors| panic!("{:?}", errors))
+    }
+
+    fn names(module: &[TopLevelStatement]) -> Vec<String> {
+        module
+            .iter()
+            .filter_map(|statement| match statement {
+                TopLevelStatement::TypeAlias(name, _) => Some(name.clone()),
+                TopLevelStatement::TopLevelDef(def) => Some(def.name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn body<'a>(module: &'a [TopLevelStatement], name: &str) -> &'a Statement {
+        module
+            .iter()
+            .find_map(|statement| match statement {
+                TopLevelStatement::TopLevelDef(def) if def.name == name => match &def.instruction {
+                    TopLevelDef::FnDef(fn_def) => Some(&fn_def.statement),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn alias<'a>(module: &'a [TopLevelStatement], name: &str) -> &'a VarType {
+        module
+            .iter()
+            .find_map(|statement| match statement {
+                TopLevelStatement::TypeAlias(n, var_type) if n == name => Some(var_type),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn calls(statement: &Statement, out: &mut Vec<String>) {
+        match statement {
+            Statement::Call(name, args) => {
+                out.push(name.clone());
+                args.iter().for_each(|a| calls(a, out));
+            }
+            Statement::DoBlock(statements) => statements.iter().for_each(|s| calls(s, out)),
+            Statement::DefVar(def) => calls(&def.instruction, out),
+            _ => {}
+        }
+    }
+
+    fn calls_in(module: &[TopLevelStatement], name: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        calls(body(module, name), &mut out);
+        out
+    }
+
+    #[test]
+    fn test_identity_instances_dedupe() {
+        let module = mono(
+            "(def id (fn<T> [(:x T)] T x)) \n
+            (def main (fn [] i32 (do (def a (id 1)) (def b (id true)) (def c (id 2)) 0)))",
+        );
+        assert_eq!(names(&module), vec!["id$i32", "id$bool", "main"]);
+        assert_eq!(
+            calls_in(&module, "main"),
+            vec!["id$i32", "id$bool", "id$i32"]
+        );
+
+        let TopLevelStatement::TopLevelDef(def) = &module[1] else {
+            panic!("expected a definition");
+        };
+        let TopLevelDef::FnDef(instance) = &def.instruction else {
+            panic!("expected a function");
+        };
+        assert_eq!(instance.generic_types, None);
+        assert_eq!(instance.parameters, vec![("x".to_string(), VarType::Bool)]);
+        assert_eq!(instance.return_type, VarType::Bool);
+    }
+
+    #[test]
+    fn test_explicit_type_arguments() {
+        let module = mono(
+            "(def id (fn<T> [(:x T)] T x)) \n
+            (def main (fn [] i64 (id<i64> 1)))",
+        );
+        assert_eq!(names(&module), vec!["id$i64", "main"]);
+        assert_eq!(
+            *body(&module, "main"),
+            Statement::Call(
+                "id$i64".into(),
+                vec![Statement::Literal(crate::ast::Literal::Int(1))]
+            )
+        );
+    }
+
+    #[test]
+    fn test_generic_calls_inside_instances() {
+        let module = mono(
+            "(def id (fn<T> [(:x T)] T x)) \n
+            (def twice (fn<T> [(:x T)] T (id (id x)))) \n
+            (def main (fn [] f64 (twice 1.5)))",
+        );
+        assert_eq!(names(&module), vec!["id$f64", "twice$f64", "main"]);
+        assert_eq!(calls_in(&module, "twice$f64"), vec!["id$f64", "id$f64"]);
+    }
+
+    #[test]
+    fn test_generic_struct_instances() {
+        let module = mono(
+            "(type pair (struct<A B> (:a A) (:b B))) \n
+            (def p (fn [] pair<i32 f64> (pair 1 2.0))) \n
+            (def q (fn [] bool ($ :a (pair true 1))))",
+        );
+        assert_eq!(
+            names(&module),
+            vec!["pair$i32$f64", "pair$bool$i32", "p", "q"]
+        );
+        assert_eq!(
+            *alias(&module, "pair$i32$f64"),
+            VarType::Struct(vec![
+                ("a".into(), VarType::Int32),
+                ("b".into(), VarType::Float64)
+            ])
+        );
+        assert_eq!(calls_in(&module, "p"), vec!["pair$i32$f64"]);
+    }
+
+    #[test]
+    fn test_recursive_generic_type() {
+        let module = mono(
+            "(type list (struct<T> (:head T) (:tail (ptr list<T>)))) \n
+            (def xs list<u8>)",
+        );
+        assert_eq!(names(&module), vec!["list$u8", "xs"]);
+        assert_eq!(
+            *alias(&module, "list$u8"),
+            VarType::Struct(vec![
+                ("head".into(), VarType::UInt8),
+                (
+                    "tail".into(),
+                    VarType::Ptr(Box::new(VarType::IdentType("list$u8".into())))
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_generic_data_instances() {
+        let module = mono(
+            "(type option (data<T> [:some T] [:none])) \n
+            (def f (fn [] void (do (def a [:some 1]) (def b (option<f64> :some 2.0)))))",
+        );
+        assert_eq!(names(&module), vec!["option$i32", "option$f64", "f"]);
+        assert_eq!(
+            *alias(&module, "option$f64"),
+            VarType::Data(vec![
+                ("some".into(), vec![VarType::Float64]),
+                ("none".into(), vec![]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_unbound_type_parameter() {
+        let module = parser()
+            .parse("(def zero (fn<T> [] i32 0)) (def main (fn [] i32 (zero)))")
+            .into_output()
+            .unwrap();
+        let messages: Vec<_> = run(&module)
+            .unwrap_err()
+            .into_iter()
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "cannot infer type parameter(s) `T` of `zero`; pass them explicitly as `zero<...>`"
+            ]
+        );
+        assert_eq!(
+            names(&mono(
+                "(def zero (fn<T> [] i32 0)) (def main (fn [] i32 (zero<u8>)))"
+            )),
+            vec!["zero$u8", "main"]
+        );
+    }
+
+    #[test]
+    fn test_generic_function_as_value() {
+        let module = parser()
+            .parse("(def id (fn<T> [(:x T)] T x)) (def main (fn [] void (def f id)))")
+            .into_output()
+            .unwrap();
+        let messages: Vec<_> = run(&module)
+            .unwrap_err()
+            .into_iter()
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "generic function `id` must be called or instantiated with explicit type arguments"
+            ]
+        );
+    }
+}
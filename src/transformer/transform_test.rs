@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Literal, Statement};
+    use crate::parser::{parser, statement};
+    use crate::transformer::ast::{AccessSegment, TransformedStmt};
+    use crate::transformer::dollar::lower;
+    use crate::transformer::transform;
+    use chumsky::Parser;
+    use std::path::Path;
+
+    /// Compare the lowered form of `golden/<name>.th` with `golden/<name>.lowered`.
+    /// Run with `UPDATE_GOLDEN=1` to rewrite the expected output.
+    fn check_golden(name: &str) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/transformer/golden");
+        let source = std::fs::read_to_string(dir.join(format!("{}.th", name))).unwrap();
+        let module = parser().parse(&source).into_output().unwrap();
+        let lowered = transform(&module)
+            .unwrap_or_else(|errors| panic!("{:?}", errors))
+            .to_string();
+
+        let golden = dir.join(format!("{}.lowered", name));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&golden, &lowered).unwrap();
+        }
+        let expected = std::fs::read_to_string(&golden).unwrap();
+        assert_eq!(lowered, expected, "golden output of `{}` changed", name);
+    }
+
+    #[test]
+    fn test_golden_structs() {
+        check_golden("structs");
+    }
+
+    #[test]
+    fn test_golden_generics() {
+        check_golden("generics");
+    }
+
+    #[test]
+    fn test_golden_closures() {
+        check_golden("closures");
+    }
+
+    #[test]
+    fn test_nested_chains_are_lowered() {
+        let lowered = lower(&statement().parse("(f ($ :x ($ [($ :i a)] b)))").unwrap());
+        let ident = |name: &str| Box::new(TransformedStmt::Orig(Statement::Ident(name.into())));
+        assert_eq!(
+            lowered,
+            TransformedStmt::Call(
+                "f".into(),
+                vec![TransformedStmt::ChainAccess {
+                    root: ident("b"),
+                    segments: vec![
+                        AccessSegment::Index(Box::new(TransformedStmt::ChainAccess {
+                            root: ident("a"),
+                            segments: vec![AccessSegment::Field("i".into())],
+                        })),
+                        AccessSegment::Field("x".into()),
+                    ],
+                }]
+            )
+        );
+    }
+
+    #[test]
+    fn test_scalars_stay_original() {
+        assert_eq!(
+            lower(&statement().parse("42").unwrap()),
+            TransformedStmt::Orig(Statement::Literal(Literal::Int(42)))
+        );
+    }
+
+    #[test]
+    fn test_constants_are_folded_before_checking() {
+        let module = parser()
+            .parse("(def N (* 2 4)) (def first (fn [(:xs [i32 N])] i32 ($ [0] xs)))")
+            .into_output()
+            .unwrap();
+        let lowered = transform(&module)
+            .unwrap_or_else(|errors| panic!("{:?}", errors))
+            .to_string();
+        assert!(lowered.contains("(def N 8)"), "{}", lowered);
+        assert!(lowered.contains("[i32 8]"), "{}", lowered);
+    }
+
+    #[test]
+    fn test_type_errors_stop_the_pipeline() {
+        let module = parser()
+            .parse("(def f (fn [] i32 true))")
+            .into_output()
+            .unwrap();
+        let errors = transform(&module).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "type mismatch in return value of `f`: expected `i32`, found `bool`"
+        );
+    }
+}
@@ -0,0 +1,426 @@
+//! Traversals over the lowered tree of [`crate::transformer::ast`], mirroring
+//! [`crate::visit`] for the surface AST.
+//!
+//! Leaves carried over from the surface AST (`TransformedStmt::Orig`, `TransformedItem::Orig`
+//! and the types of lowered functions) are handed to `visit_orig`, `visit_orig_item` and
+//! `visit_var_type`, which do nothing by default; a pass that needs to look inside them can
+//! combine these hooks with [`crate::visit`].
+
+use super::ast::{AccessSegment, Module, TransformedFn, TransformedItem, TransformedStmt};
+use crate::ast::{DefVar, Statement, TopLevelStatement, VarType};
+
+type Segment = AccessSegment<TransformedStmt>;
+
+pub trait Visitor {
+    fn visit_module(&mut self, module: &Module) {
+        walk_module(self, module)
+    }
+
+    fn visit_item(&mut self, item: &TransformedItem) {
+        walk_item(self, item)
+    }
+
+    fn visit_fn(&mut self, fn_def: &TransformedFn) {
+        walk_fn(self, fn_def)
+    }
+
+    fn visit_stmt(&mut self, statement: &TransformedStmt) {
+        walk_stmt(self, statement)
+    }
+
+    fn visit_segment(&mut self, segment: &Segment) {
+        walk_segment(self, segment)
+    }
+
+    fn visit_var_type(&mut self, _var_type: &VarType) {}
+
+    fn visit_orig(&mut self, _statement: &Statement) {}
+
+    fn visit_orig_item(&mut self, _statement: &TopLevelStatement) {}
+}
+
+pub fn walk_module<V: Visitor + ?Sized>(visitor: &mut V, module: &Module) {
+    for item in &module.items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_item<V: Visitor + ?Sized>(visitor: &mut V, item: &TransformedItem) {
+    match item {
+        TransformedItem::Orig(statement) => visitor.visit_orig_item(statement),
+        TransformedItem::Fn(_, fn_def) => visitor.visit_fn(fn_def),
+        TransformedItem::Global(_, value) => visitor.visit_stmt(value),
+    }
+}
+
+pub fn walk_fn<V: Visitor + ?Sized>(visitor: &mut V, fn_def: &TransformedFn) {
+    for (_, var_type) in &fn_def.parameters {
+        visitor.visit_var_type(var_type);
+    }
+    visitor.visit_var_type(&fn_def.return_type);
+    visitor.visit_stmt(&fn_def.statement);
+}
+
+pub fn walk_segment<V: Visitor + ?Sized>(visitor: &mut V, segment: &Segment) {
+    match segment {
+        AccessSegment::Field(_) => {}
+        AccessSegment::Index(index) => visitor.visit_stmt(index),
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, statement: &TransformedStmt) {
+    match statement {
+        TransformedStmt::Orig(statement) => visitor.visit_orig(statement),
+        TransformedStmt::Declare(_, var_type) => visitor.visit_var_type(var_type),
+        TransformedStmt::Break | TransformedStmt::Continue => {}
+        TransformedStmt::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_stmt(value);
+            }
+        }
+        TransformedStmt::DoBlock(statements)
+        | TransformedStmt::Call(_, statements)
+        | TransformedStmt::Tuple(statements)
+        | TransformedStmt::Array(statements)
+        | TransformedStmt::Data(_, statements)
+        | TransformedStmt::MakeClosure {
+            env: statements, ..
+        } => {
+            for statement in statements {
+                visitor.visit_stmt(statement);
+            }
+        }
+        TransformedStmt::DefVar(def) => visitor.visit_stmt(&def.instruction),
+        TransformedStmt::If(condition, then) | TransformedStmt::For(condition, then) => {
+            visitor.visit_stmt(condition);
+            visitor.visit_stmt(then);
+        }
+        TransformedStmt::IfElse(condition, then, otherwise) => {
+            visitor.visit_stmt(condition);
+            visitor.visit_stmt(then);
+            visitor.visit_stmt(otherwise);
+        }
+        TransformedStmt::ForRange(_, range, body) => {
+            for part in range.parts() {
+                visitor.visit_stmt(part);
+            }
+            visitor.visit_stmt(body);
+        }
+        TransformedStmt::Fn(fn_def) => visitor.visit_fn(fn_def),
+        TransformedStmt::ChainAccess { root, segments } => {
+            visitor.visit_stmt(root);
+            for segment in segments {
+                visitor.visit_segment(segment);
+            }
+        }
+        TransformedStmt::ChainAssign {
+            root,
+            segments,
+            value,
+        } => {
+            visitor.visit_stmt(root);
+            for segment in segments {
+                visitor.visit_segment(segment);
+            }
+            visitor.visit_stmt(value);
+        }
+        TransformedStmt::BoxNew(value) | TransformedStmt::BoxGet(value) => {
+            visitor.visit_stmt(value)
+        }
+        TransformedStmt::BoxSet(cell, value) => {
+            visitor.visit_stmt(cell);
+            visitor.visit_stmt(value);
+        }
+    }
+}
+
+pub trait VisitorMut {
+    fn visit_module(&mut self, module: &mut Module) {
+        walk_mut_module(self, module)
+    }
+
+    fn visit_item(&mut self, item: &mut TransformedItem) {
+        walk_mut_item(self, item)
+    }
+
+    fn visit_fn(&mut self, fn_def: &mut TransformedFn) {
+        walk_mut_fn(self, fn_def)
+    }
+
+    fn visit_stmt(&mut self, statement: &mut TransformedStmt) {
+        walk_mut_stmt(self, statement)
+    }
+
+    fn visit_segment(&mut self, segment: &mut Segment) {
+        walk_mut_segment(self, segment)
+    }
+
+    fn visit_var_type(&mut self, _var_type: &mut VarType) {}
+
+    fn visit_orig(&mut self, _statement: &mut Statement) {}
+
+    fn visit_orig_item(&mut self, _statement: &mut TopLevelStatement) {}
+}
+
+pub fn walk_mut_module<V: VisitorMut + ?Sized>(visitor: &mut V, module: &mut Module) {
+    for item in &mut module.items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_mut_item<V: VisitorMut + ?Sized>(visitor: &mut V, item: &mut TransformedItem) {
+    match item {
+        TransformedItem::Orig(statement) => visitor.visit_orig_item(statement),
+        TransformedItem::Fn(_, fn_def) => visitor.visit_fn(fn_def),
+        TransformedItem::Global(_, value) => visitor.visit_stmt(value),
+    }
+}
+
+pub fn walk_mut_fn<V: VisitorMut + ?Sized>(visitor: &mut V, fn_def: &mut TransformedFn) {
+    for (_, var_type) in &mut fn_def.parameters {
+        visitor.visit_var_type(var_type);
+    }
+    visitor.visit_var_type(&mut fn_def.return_type);
+    visitor.visit_stmt(&mut fn_def.statement);
+}
+
+pub fn walk_mut_segment<V: VisitorMut + ?Sized>(visitor: &mut V, segment: &mut Segment) {
+    match segment {
+        AccessSegment::Field(_) => {}
+        AccessSegment::Index(index) => visitor.visit_stmt(index),
+    }
+}
+
+pub fn walk_mut_stmt<V: VisitorMut + ?Sized>(visitor: &mut V, statement: &mut TransformedStmt) {
+    match statement {
+        TransformedStmt::Orig(statement) => visitor.visit_orig(statement),
+        TransformedStmt::Declare(_, var_type) => visitor.visit_var_type(var_type),
+        TransformedStmt::Break | TransformedStmt::Continue => {}
+        TransformedStmt::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_stmt(value);
+            }
+        }
+        TransformedStmt::DoBlock(statements)
+        | TransformedStmt::Call(_, statements)
+        | TransformedStmt::Tuple(statements)
+        | TransformedStmt::Array(statements)
+        | TransformedStmt::Data(_, statements)
+        | TransformedStmt::MakeClosure {
+            env: statements, ..
+        } => {
+            for statement in statements {
+                visitor.visit_stmt(statement);
+            }
+        }
+        TransformedStmt::DefVar(def) => visitor.visit_stmt(&mut def.instruction),
+        TransformedStmt::If(condition, then) | TransformedStmt::For(condition, then) => {
+            visitor.visit_stmt(condition);
+            visitor.visit_stmt(then);
+        }
+        TransformedStmt::IfElse(condition, then, otherwise) => {
+            visitor.visit_stmt(condition);
+            visitor.visit_stmt(then);
+            visitor.visit_stmt(otherwise);
+        }
+        TransformedStmt::ForRange(_, range, body) => {
+            for part in range.parts_mut() {
+                visitor.visit_stmt(part);
+            }
+            visitor.visit_stmt(body);
+        }
+        TransformedStmt::Fn(fn_def) => visitor.visit_fn(fn_def),
+        TransformedStmt::ChainAccess { root, segments } => {
+            visitor.visit_stmt(root);
+            for segment in segments {
+                visitor.visit_segment(segment);
+            }
+        }
+        TransformedStmt::ChainAssign {
+            root,
+            segments,
+            value,
+        } => {
+            visitor.visit_stmt(root);
+            for segment in segments {
+                visitor.visit_segment(segment);
+            }
+            visitor.visit_stmt(value);
+        }
+        TransformedStmt::BoxNew(value) | TransformedStmt::BoxGet(value) => {
+            visitor.visit_stmt(value)
+        }
+        TransformedStmt::BoxSet(cell, value) => {
+            visitor.visit_stmt(cell);
+            visitor.visit_stmt(value);
+        }
+    }
+}
+
+pub trait Fold {
+    fn fold_module(&mut self, module: Module) -> Module {
+        noop_fold_module(self, module)
+    }
+
+    fn fold_item(&mut self, item: TransformedItem) -> TransformedItem {
+        noop_fold_item(self, item)
+    }
+
+    fn fold_fn(&mut self, fn_def: TransformedFn) -> TransformedFn {
+        noop_fold_fn(self, fn_def)
+    }
+
+    fn fold_stmt(&mut self, statement: TransformedStmt) -> TransformedStmt {
+        noop_fold_stmt(self, statement)
+    }
+
+    fn fold_segment(&mut self, segment: Segment) -> Segment {
+        noop_fold_segment(self, segment)
+    }
+
+    fn fold_var_type(&mut self, var_type: VarType) -> VarType {
+        var_type
+    }
+
+    fn fold_orig(&mut self, statement: Statement) -> Statement {
+        statement
+    }
+
+    fn fold_orig_item(&mut self, statement: TopLevelStatement) -> TopLevelStatement {
+        statement
+    }
+}
+
+pub fn noop_fold_module<F: Fold + ?Sized>(folder: &mut F, module: Module) -> Module {
+    Module {
+        items: module
+            .items
+            .into_iter()
+            .map(|item| folder.fold_item(item))
+            .collect(),
+        inference: module.inference,
+    }
+}
+
+pub fn noop_fold_item<F: Fold + ?Sized>(folder: &mut F, item: TransformedItem) -> TransformedItem {
+    match item {
+        TransformedItem::Orig(statement) => TransformedItem::Orig(folder.fold_orig_item(statement)),
+        TransformedItem::Fn(name, fn_def) => TransformedItem::Fn(name, folder.fold_fn(fn_def)),
+        TransformedItem::Global(name, value) => {
+            TransformedItem::Global(name, folder.fold_stmt(value))
+        }
+    }
+}
+
+pub fn noop_fold_fn<F: Fold + ?Sized>(folder: &mut F, fn_def: TransformedFn) -> TransformedFn {
+    TransformedFn {
+        parameters: fn_def
+            .parameters
+            .into_iter()
+            .map(|(name, var_type)| (name, folder.fold_var_type(var_type)))
+            .collect(),
+        return_type: folder.fold_var_type(fn_def.return_type),
+        statement: folder.fold_stmt(fn_def.statement),
+    }
+}
+
+pub fn noop_fold_segment<F: Fold + ?Sized>(folder: &mut F, segment: Segment) -> Segment {
+    match segment {
+        AccessSegment::Field(name) => AccessSegment::Field(name),
+        AccessSegment::Index(index) => AccessSegment::Index(fold_boxed(folder, index)),
+    }
+}
+
+fn fold_all<F: Fold + ?Sized>(
+    folder: &mut F,
+    statements: Vec<TransformedStmt>,
+) -> Vec<TransformedStmt> {
+    statements
+        .into_iter()
+        .map(|statement| folder.fold_stmt(statement))
+        .collect()
+}
+
+/// Fold a boxed node in place, reusing its allocation.
+fn fold_boxed<F: Fold + ?Sized>(
+    folder: &mut F,
+    mut statement: Box<TransformedStmt>,
+) -> Box<TransformedStmt> {
+    let placeholder = TransformedStmt::DoBlock(Vec::new());
+    *statement = folder.fold_stmt(std::mem::replace(&mut *statement, placeholder));
+    statement
+}
+
+fn fold_segments<F: Fold + ?Sized>(folder: &mut F, segments: Vec<Segment>) -> Vec<Segment> {
+    segments
+        .into_iter()
+        .map(|segment| folder.fold_segment(segment))
+        .collect()
+}
+
+pub fn noop_fold_stmt<F: Fold + ?Sized>(
+    folder: &mut F,
+    statement: TransformedStmt,
+) -> TransformedStmt {
+    match statement {
+        TransformedStmt::Orig(statement) => TransformedStmt::Orig(folder.fold_orig(statement)),
+        TransformedStmt::Declare(name, var_type) => {
+            TransformedStmt::Declare(name, folder.fold_var_type(var_type))
+        }
+        TransformedStmt::Break => TransformedStmt::Break,
+        TransformedStmt::Continue => TransformedStmt::Continue,
+        TransformedStmt::Return(value) => {
+            TransformedStmt::Return(value.map(|value| fold_boxed(folder, value)))
+        }
+        TransformedStmt::DoBlock(statements) => {
+            TransformedStmt::DoBlock(fold_all(folder, statements))
+        }
+        TransformedStmt::Call(name, args) => TransformedStmt::Call(name, fold_all(folder, args)),
+        TransformedStmt::DefVar(def) => TransformedStmt::DefVar(DefVar {
+            name: def.name,
+            instruction: fold_boxed(folder, def.instruction),
+        }),
+        TransformedStmt::If(condition, then) => {
+            TransformedStmt::If(fold_boxed(folder, condition), fold_boxed(folder, then))
+        }
+        TransformedStmt::IfElse(condition, then, otherwise) => TransformedStmt::IfElse(
+            fold_boxed(folder, condition),
+            fold_boxed(folder, then),
+            fold_boxed(folder, otherwise),
+        ),
+        TransformedStmt::For(condition, body) => {
+            TransformedStmt::For(fold_boxed(folder, condition), fold_boxed(folder, body))
+        }
+        TransformedStmt::ForRange(name, range, body) => TransformedStmt::ForRange(
+            name,
+            Box::new(range.into_map(|part| folder.fold_stmt(part))),
+            fold_boxed(folder, body),
+        ),
+        TransformedStmt::Tuple(items) => TransformedStmt::Tuple(fold_all(folder, items)),
+        TransformedStmt::Array(items) => TransformedStmt::Array(fold_all(folder, items)),
+        TransformedStmt::Data(tag, args) => TransformedStmt::Data(tag, fold_all(folder, args)),
+        TransformedStmt::Fn(fn_def) => TransformedStmt::Fn(Box::new(folder.fold_fn(*fn_def))),
+        TransformedStmt::ChainAccess { root, segments } => TransformedStmt::ChainAccess {
+            root: fold_boxed(folder, root),
+            segments: fold_segments(folder, segments),
+        },
+        TransformedStmt::ChainAssign {
+            root,
+            segments,
+            value,
+        } => TransformedStmt::ChainAssign {
+            root: fold_boxed(folder, root),
+            segments: fold_segments(folder, segments),
+            value: fold_boxed(folder, value),
+        },
+        TransformedStmt::MakeClosure { fn_name, env } => TransformedStmt::MakeClosure {
+            fn_name,
+            env: fold_all(folder, env),
+        },
+        TransformedStmt::BoxNew(value) => TransformedStmt::BoxNew(fold_boxed(folder, value)),
+        TransformedStmt::BoxGet(cell) => TransformedStmt::BoxGet(fold_boxed(folder, cell)),
+        TransformedStmt::BoxSet(cell, value) => {
+            TransformedStmt::BoxSet(fold_boxed(folder, cell), fold_boxed(folder, value))
+        }
+    }
+}
@@ -0,0 +1,81 @@
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Literal, Statement};
+    use crate::parser::parser;
+    use crate::transformer::ast::{Module, TransformedStmt};
+    use crate::transformer::transform;
+    use crate::transformer::visit::{
+        noop_fold_stmt, walk_module, walk_mut_stmt, walk_stmt, Fold, Visitor, VisitorMut,
+    };
+    use chumsky::Parser;
+
+    fn lowered(src: &str) -> Module {
+        let module = parser().parse(src).into_output().unwrap();
+        transform(&module).unwrap_or_else(|errors| panic!("{:?}", errors))
+    }
+
+    const SRC: &str = "(type point (struct (:x i32)))
+        (def make (fn [(:p point) (:n i32)] fn [] i32
+          (do (def count n) (fn [] i32 (do (def count (+ count ($ :x p))) count)))))";
+
+    #[derive(Default)]
+    struct Calls {
+        names: Vec<String>,
+    }
+
+    impl Visitor for Calls {
+        fn visit_stmt(&mut self, statement: &TransformedStmt) {
+            match statement {
+                TransformedStmt::Call(name, _) => self.names.push(name.clone()),
+                TransformedStmt::MakeClosure { fn_name, .. } => self.names.push(fn_name.clone()),
+                TransformedStmt::BoxNew(_) => self.names.push("box".into()),
+                _ => {}
+            }
+            walk_stmt(self, statement);
+        }
+    }
+
+    #[test]
+    fn test_visitor_reaches_lowered_nodes() {
+        let mut calls = Calls::default();
+        walk_module(&mut calls, &lowered(SRC));
+        assert_eq!(calls.names, vec!["+", "box", "lambda$0"]);
+    }
+
+    struct Double;
+
+    impl VisitorMut for Double {
+        fn visit_orig(&mut self, statement: &mut Statement) {
+            if let Statement::Literal(Literal::Int(value)) = statement {
+                *value *= 2;
+            }
+        }
+    }
+
+    struct Inline;
+
+    impl Fold for Inline {
+        fn fold_stmt(&mut self, statement: TransformedStmt) -> TransformedStmt {
+            match statement {
+                TransformedStmt::BoxGet(cell) => self.fold_stmt(*cell),
+                other => noop_fold_stmt(self, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor_mut_and_fold() {
+        let mut module = lowered("(def f (fn [] i32 (+ 1 ($ [0] [2 3]))))");
+        Double.visit_module(&mut module);
+        assert_eq!(
+            module.to_string(),
+            "(def f (fn [] i32 (+ 2 (get [4 6] [0]))))\n"
+        );
+
+        let mut stmt = TransformedStmt::BoxGet(Box::new(TransformedStmt::BoxGet(Box::new(
+            TransformedStmt::Orig(Statement::Ident("c".into())),
+        ))));
+        walk_mut_stmt(&mut Double, &mut stmt);
+        assert_eq!(Inline.fold_stmt(stmt).to_string(), "c");
+    }
+}
@@ -0,0 +1,418 @@
+#[cfg(test)]
+mod tests {
+    use crate::ast::VarType;
+    use crate::parser::{parser, statement};
+    use crate::typeck::{check, Checker};
+    use chumsky::Parser;
+
+    fn errors(src: &str) -> Vec<String> {
+        let module = parser().parse(src).into_output().unwrap();
+        check(&module).into_iter().map(|d| d.message).collect()
+    }
+
+    fn type_of(module: &str, expr: &str) -> Option<VarType> {
+        let module = parser().parse(module).into_output().unwrap();
+        let expr = statement().parse(expr).into_output().unwrap();
+        Checker::new(&module).type_of(&expr)
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(type_of("", "42"), Some(VarType::Int32));
+        assert_eq!(type_of("", "4.2"), Some(VarType::Float64));
+        assert_eq!(type_of("", "true"), Some(VarType::Bool));
+        assert_eq!(
+            type_of("", "\"hi\""),
+            Some(VarType::IdentType("str".into()))
+        );
+        assert_eq!(
+            type_of("", "{1 true}"),
+            Some(VarType::Tuple(vec![VarType::Int32, VarType::Bool]))
+        );
+        assert_eq!(
+            type_of("", "[1 2 3]"),
+            Some(VarType::ArraySized(Box::new(VarType::Int32), 3))
+        );
+    }
+
+    #[test]
+    fn test_well_typed_program() {
+        let src = "(def fib (fn [(:n i64)] i64 \n
+                (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))) \n
+            (def main (fn [] i32 (do (def x (fib 10)) 0)))";
+        assert!(errors(src).is_empty(), "{:?}", errors(src));
+    }
+
+    #[test]
+    fn test_return_type_mismatch() {
+        assert_eq!(
+            errors("(def f (fn [] i32 true))"),
+            vec!["type mismatch in return value of `f`: expected `i32`, found `bool`"]
+        );
+        assert!(errors("(def f (fn [] void true))").is_empty());
+    }
+
+    #[test]
+    fn test_call_arguments() {
+        let src = "(def add (fn [(:a i32) (:b i32)] i32 (+ a b))) \n
+            (def main (fn [] i32 (do (add 1 2.5) (add 1))))";
+        assert_eq!(
+            errors(src),
+            vec![
+                "type mismatch in argument 2 of `add`: expected `i32`, found `f64`",
+                "`add` takes 2 argument(s) but 1 were given"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_builtin_operand_counts() {
+        let src = "(def f (fn [(:a bool) (:b bool) (:x i32)] i32 (do \n
+                (not) (not a b) (! a) \n
+                (* 5) (/ x) (< x) (&& a) (<< x) \n
+                (- x) (+ x) (-) \n
+                (* x 2 3))))";
+        assert_eq!(
+            errors(src),
+            vec![
+                "`not` takes 1 operand but 0 were given",
+                "`not` takes 1 operand but 2 were given",
+                "`*` takes at least 2 operands but 1 were given",
+                "`/` takes at least 2 operands but 1 were given",
+                "`<` takes at least 2 operands but 1 were given",
+                "`&&` takes at least 2 operands but 1 were given",
+                "`<<` takes at least 2 operands but 1 were given",
+                "`-` takes at least 1 operand but 0 were given",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_if_branches_must_agree() {
+        assert_eq!(
+            errors("(def f (fn [(:c bool)] i32 (if c 1 \"no\")))"),
+            vec!["`if` branches have different types: `i32` and `str`",]
+        );
+        assert_eq!(
+            errors("(def f (fn [(:c i32)] i32 (if c 1 2)))"),
+            vec!["type mismatch in `if` condition: expected `bool`, found `i32`"]
+        );
+    }
+
+    #[test]
+    fn test_do_block_value_is_last_expression() {
+        assert_eq!(
+            type_of("", "(do (def a 1) (def b 2.0) b)"),
+            Some(VarType::Float64)
+        );
+        assert_eq!(type_of("", "(do)"), Some(VarType::Void));
+    }
+
+    #[test]
+    fn test_struct_fields() {
+        let module = "(type point (struct (:x f64) (:y f64))) \n
+            (def p (fn [] point (point 1.0 2.0))) \n
+            (def q (ptr point))";
+        assert_eq!(type_of(module, "($ :x (p))"), Some(VarType::Float64));
+        assert_eq!(type_of(module, "($ :y q)"), Some(VarType::Float64));
+
+        let src = format!(
+            "{} (def f (fn [] void (do ($ :z (p)) ($ :x (p) true) ($ :x 1))))",
+            module
+        );
+        assert_eq!(
+            errors(&src),
+            vec![
+                "`point` has no field `z`",
+                "type mismatch in field `x`: expected `f64`, found `bool`",
+                "cannot access field `x` on non-struct type `i32`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indexing() {
+        let module = "(def arr [i64 4]) (def pair {i32 bool})";
+        assert_eq!(type_of(module, "($ [0] arr)"), Some(VarType::Int64));
+        assert_eq!(type_of(module, "($ [1] pair)"), Some(VarType::Bool));
+
+        let src = format!(
+            "{} (def f (fn [(:i i32)] void (do ($ [2] pair) ($ [i] pair) ($ [true] arr))))",
+            module
+        );
+        assert_eq!(
+            errors(&src),
+            vec![
+                "index 2 is out of bounds for `{i32 bool}` with 2 element(s)",
+                "tuple `{i32 bool}` must be indexed by an integer literal",
+                "array index must be an integer, found `bool`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jumps_take_the_type_of_the_other_branch() {
+        let src = "(def stdio (use :header \"stdio.h\")) \n
+            (def f (fn [(:n i32)] i32 \n
+                (do \n
+                    (def total 0) \n
+                    (for (range i n) (if (= (% i 2) 0) (continue) (do (def total (+ total i)) total))) \n
+                    (for (range i n) (if (= i 3) (break) (stdio/printf \"%d\" i))) \n
+                    (for (range i n) (def last (if (> i 5) (break) i))) \n
+                    total)))";
+        assert!(errors(src).is_empty(), "{:?}", errors(src));
+        assert_eq!(
+            errors(
+                "(def f (fn [] void (for true (def x (if true (break) 1))))) \n
+                (def g (fn [] void (for true (if true (continue) (+ (if false 1 true) 1)))))"
+            ),
+            vec!["`if` branches have different types: `i32` and `bool`"]
+        );
+    }
+
+    #[test]
+    fn test_returns() {
+        let src = "(type point (struct (:x i32) (:y i32))) \n
+            (def f (fn [(:n i64)] point \n
+                (do \n
+                    (for (range i n) (if (= i 3) (return (point 3 4)))) \n
+                    (def g (fn [] i64 (return 7))) \n
+                    (def p (if (> n 0) (return (point 1 2)) (point 2 1))) \n
+                    (return p)))) \n
+            (def h (fn [(:n i32)] void (if (> n 0) (return) (return n))))";
+        assert!(errors(src).is_empty(), "{:?}", errors(src));
+        assert_eq!(
+            errors(
+                "(def f (fn [(:n i32)] i32 (do (if (> n 0) (return true)) n))) \n
+                (def g (fn [(:n i32)] i32 (do (if (> n 0) (return)) n)))"
+            ),
+            vec![
+                "type mismatch in return value of `f`: expected `i32`, found `bool`",
+                "missing return value of `g`: `(return)` has no value of type `i32`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ranges() {
+        let src = "(def f (fn [(:xs [i32 4]) (:ys [u8]) (:p (ptr f64)) (:n u16)] void \n
+            (do \n
+                (for (range x xs) (def a (+ x 1))) \n
+                (for (range y :slice ys 0 2) (def b y)) \n
+                (for (range z :slice p 1 n) (def c z)) \n
+                (for (range i 10 n 2) (def d i)) \n
+                (for (range j n) (if (> j 3) (break) (continue))))))";
+        assert!(errors(src).is_empty(), "{:?}", errors(src));
+
+        assert_eq!(
+            errors("(def f (fn [(:ys [u8]) (:n i64) (:x f64)] void (do \n
+                (for (range y ys) y) \n
+                (for (range i 0 n 1.5) i) \n
+                (for (range i x 3) i) \n
+                (for (range b :slice true 0 1) b))))"),
+            vec![
+                "cannot iterate over `[u8]`: its length is not known; use `(range x :slice xs start end)`",
+                "type mismatch in range bound: expected `i64`, found `f64`",
+                "range bounds must be integers, found `f64`",
+                "cannot slice `bool`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_literals_adopt_expected_type() {
+        let src = "(def f (fn [(:x i64)] i64 (+ x 1))) \n
+            (def g (fn [(:x f32)] bool (< 1.5 x)))";
+        assert!(errors(src).is_empty(), "{:?}", errors(src));
+        assert_eq!(
+            errors("(def f (fn [(:x i64)] i64 (+ x 1.5)))"),
+            vec!["type mismatch in operand of `+`: expected `i64`, found `f64`"]
+        );
+    }
+
+    #[test]
+    fn test_data_literals() {
+        let module = "(type option (data [:some i32] [:none])) \n
+            (def f (fn [] option [:some true])) \n
+            (def g (fn [] option [:nope]))";
+        assert_eq!(
+            type_of(module, "[:some 1]"),
+            Some(VarType::IdentType("option".into()))
+        );
+        let src = parser().parse(module).into_output().unwrap();
+        let messages: Vec<_> = check(&src).into_iter().map(|d| d.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "type mismatch in variant `:some`: expected `i32`, found `bool`",
+                "`option` has no variant `:nope`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_local_rebinding_keeps_type() {
+        assert_eq!(
+            errors("(def f (fn [] void (do (def a 1) (def a true))))"),
+            vec!["type mismatch in assignment to `a`: expected `i32`, found `bool`"]
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        let src = "(def make-adder (fn [(:n i32)] fn [i32] i32 \n
+                (fn [(:x i32)] i32 (+ x n)))) \n
+            (def add5 (fn [] i32 (do (def f (make-adder 5)) (f 10))))";
+        assert!(errors(src).is_empty(), "{:?}", errors(src));
+        assert_eq!(
+            type_of(src, "(make-adder 1)"),
+            Some(VarType::Fn(vec![VarType::Int32], Box::new(VarType::Int32)))
+        );
+        assert_eq!(
+            errors("(def f (fn [] fn [i32] i32 (fn [(:x i32)] bool true)))"),
+            vec!["type mismatch in return value of `f`: expected `fn [i32] i32`, found `fn [i32] bool`"]
+        );
+    }
+
+    #[test]
+    fn test_generic_functions() {
+        let module = "(def id (fn<T> [(:x T)] T x)) \n
+            (def first (fn<T> [(:xs [T 2])] T ($ [0] xs)))";
+        assert_eq!(type_of(module, "(id true)"), Some(VarType::Bool));
+        assert_eq!(type_of(module, "(id<i64> 1)"), Some(VarType::Int64));
+        assert_eq!(type_of(module, "(first [1.5 2.5])"), Some(VarType::Float64));
+
+        let src = format!(
+            "{} (def zero (fn<T> [] i32 0)) \n
+            (def f (fn [] void (do (zero) (id<i32 bool> 1) (id<bool> 1) (first<i32> 1))))",
+            module
+        );
+        assert_eq!(
+            errors(&src),
+            vec![
+                "cannot infer type parameter(s) `T` of `zero`; pass them explicitly as `zero<...>`",
+                "`id` takes 1 type argument(s) but 2 were given",
+                "type mismatch in argument 1 of `id`: expected `bool`, found `i32`",
+                "type mismatch in argument 1 of `first`: expected `[i32 2]`, found `i32`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generic_types() {
+        let module = "(type pair (struct<A B> (:a A) (:b B))) \n
+            (type option (data<T> [:some T] [:none]))";
+        let instance =
+            |name: &str, args: Vec<VarType>| Some(VarType::GenericInstance(name.into(), args));
+        assert_eq!(
+            type_of(module, "(pair 1 true)"),
+            instance("pair", vec![VarType::Int32, VarType::Bool])
+        );
+        assert_eq!(
+            type_of(module, "($ :b (pair<u8 f32> 1 2.0))"),
+            Some(VarType::Float32)
+        );
+        assert_eq!(
+            type_of(module, "[:some 'c']"),
+            instance("option", vec![VarType::Int8])
+        );
+        assert_eq!(
+            type_of(module, "(option<u16> :some 1)"),
+            instance("option", vec![VarType::UInt16])
+        );
+        assert_eq!(
+            errors(&format!("{} (def f (fn [] pair<i32 i32> (pair 1 true)))", module)),
+            vec!["type mismatch in return value of `f`: expected `pair<i32 i32>`, found `pair<i32 bool>`"]
+        );
+    }
+
+    #[test]
+    fn test_generic_bounds() {
+        let module = "(def sum (fn<T:numeric> [(:a T) (:b T)] T (+ a b 1))) \n
+            (def shift (fn<N:integer> [(:n N)] N (sum (<< n 1) n)))";
+        assert!(errors(module).is_empty(), "{:?}", errors(module));
+        assert_eq!(type_of(module, "(sum 1.5 2.5)"), Some(VarType::Float64));
+        assert_eq!(type_of(module, "(shift<u8> 1)"), Some(VarType::UInt8));
+
+        let src = format!(
+            "{} (def f (fn [] void (do (sum true false) (shift 1.5) (shift<f32> 1.0))))",
+            module
+        );
+        assert_eq!(
+            errors(&src),
+            vec![
+                "`bool` does not satisfy the bound `numeric` of type parameter `T` of `sum`",
+                "`f64` does not satisfy the bound `integer` of type parameter `N` of `shift`",
+                "`f32` does not satisfy the bound `integer` of type parameter `N` of `shift`",
+            ]
+        );
+        assert_eq!(
+            errors("(def add (fn<T> [(:a T) (:b T)] T (+ a b)))"),
+            vec!["`+` cannot be applied to `T`"]
+        );
+        assert_eq!(
+            errors("(def f (fn<F:float> [(:x F)] F (<< x 1)))"),
+            vec!["`<<` cannot be applied to `F`"]
+        );
+    }
+
+    #[test]
+    fn test_curry_and_compose() {
+        let module = "(def curry (fn<A B C> [(:f fn [A B] C)] fn [A] fn [B] C \n
+                (fn [(:x A)] fn [B] C (fn [(:y B)] C (f x y))))) \n
+            (def compose (fn<A B C> [(:f fn [A] B) (:g fn [B] C)] fn [A] C \n
+                (fn [(:x A)] C (g (f x))))) \n
+            (def scale (fn<T:numeric> [(:k T)] fn [T] T (fn [(:x T)] T (* k x)))) \n
+            (def add (fn [(:x i32) (:y i32)] i32 (+ x y))) \n
+            (def even (fn [(:x i32)] bool (= (% x 2) 0)))";
+        assert!(errors(module).is_empty(), "{:?}", errors(module));
+
+        let function = |params: Vec<VarType>, ret: VarType| VarType::Fn(params, Box::new(ret));
+        assert_eq!(
+            type_of(module, "(curry add)"),
+            Some(function(
+                vec![VarType::Int32],
+                function(vec![VarType::Int32], VarType::Int32)
+            ))
+        );
+        assert_eq!(
+            type_of(module, "(compose (scale 3) even)"),
+            Some(function(vec![VarType::Int32], VarType::Bool))
+        );
+        assert_eq!(
+            type_of(module, "(scale 0.5)"),
+            Some(function(vec![VarType::Float64], VarType::Float64))
+        );
+    }
+
+    #[test]
+    fn test_closures_passed_to_c() {
+        let module = "(def m (use \"m\")) \n
+            (def sort fn [(ptr i32) fn [(ptr i32) (ptr i32)] i32] void) \n
+            (def m/sort fn [(ptr i32) fn [(ptr i32) (ptr i32)] i32] void) \n
+            (def less (fn [(:a (ptr i32)) (:b (ptr i32))] i32 (- ($ [0] a) ($ [0] b)))) \n
+            (def f (fn [(:xs (ptr i32)) (:k i32) (:by fn [(ptr i32) (ptr i32)] i32)] void \n
+                (do \n
+                    (sort xs less) \n
+                    (sort xs (fn [(:a (ptr i32)) (:b (ptr i32))] i32 0)) \n
+                    (sort xs (fn [(:a (ptr i32)) (:b (ptr i32))] i32 (* k (less a b)))) \n
+                    (sort xs by) \n
+                    (m/sort xs by))))";
+        let hint = "pass a top-level function or a lambda that captures nothing";
+        assert_eq!(
+            errors(module),
+            vec![
+                format!(
+                    "argument 2 of `sort` is a C function pointer, which has no room for the \
+                     variables the lambda captures (`k`); {}",
+                    hint
+                ),
+                format!(
+                    "argument 2 of `sort` is a C function pointer, which has no room for the \
+                     environment of a closure value; {}",
+                    hint
+                ),
+            ]
+        );
+    }
+}
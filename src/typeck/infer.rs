@@ -0,0 +1,684 @@
+//! Local type inference.
+//!
+//! Every binding without an annotation (`def` locals and top-level literal `def`s) gets a type
+//! variable that is unified with the types of the places it flows into. Integer and float
+//! literals start out as constrained variables: float literals only unify with floating-point
+//! types, integer literals with integer and floating-point types, as the checker lets `1` stand
+//! for a float. They default to `i32` and `f64` once the whole module has been walked, so in
+//!
+//! ```lisp
+//! (def x 42)
+//! (def f (fn [(:y i64)] i64 (+ x y)))
+//! ```
+//!
+//! `x` is inferred as `i64` rather than defaulting to `i32`.
+//!
+//! The intrinsics of closure conversion (`$box`, `$make_closure`, ...) are understood too, so
+//! the module can be inferred again after lambda lifting.
+//!
+//! Inference never reports type errors itself; conflicting constraints keep the first
+//! solution and the checker reports the mismatch against it.
+
+use super::types::{atom_type, is_float, is_integer, str_type, Aliases};
+use crate::ast::{FnDef, Literal, Range, Statement, TopLevelDef, TopLevelStatement, VarType};
+use crate::builtins;
+use crate::lint::diverges;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum VarKind {
+    General,
+    Integer,
+    Float,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Ty {
+    Var(usize),
+    Known(VarType),
+    Fn(Vec<Ty>, Box<Ty>, bool),
+    Tuple(Vec<Ty>),
+    Array(Box<Ty>, Option<usize>),
+    Ptr(Box<Ty>),
+}
+
+/// The inferred type of one binding. Bindings are identified by the top-level item they are
+/// declared in, their name, and how many bindings of that name precede them in the item.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InferredBinding {
+    pub item: usize,
+    pub name: String,
+    pub index: usize,
+    pub var_type: Option<VarType>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Inference {
+    pub globals: HashMap<String, VarType>,
+    pub bindings: Vec<InferredBinding>,
+}
+
+impl Inference {
+    pub fn binding(&self, item: usize, name: &str, index: usize) -> Option<&VarType> {
+        self.bindings
+            .iter()
+            .find(|b| b.item == item && b.name == name && b.index == index)
+            .and_then(|b| b.var_type.as_ref())
+    }
+
+    /// The type of the first binding called `name` in `item`, or of the global `name`.
+    /// This is what an editor hover over a binding shows.
+    pub fn type_of_binding(&self, item: usize, name: &str) -> Option<&VarType> {
+        self.binding(item, name, 0)
+            .or_else(|| self.globals.get(name))
+    }
+}
+
+pub fn infer(module: &[TopLevelStatement]) -> Inference {
+    let mut inferer = Inferer::default();
+    for statement in module {
+        if let TopLevelStatement::TypeAlias(name, var_type) = statement {
+            inferer.aliases.types.insert(name.clone(), var_type.clone());
+        }
+    }
+
+    for statement in module {
+        if let TopLevelStatement::TopLevelDef(def) = statement {
+            let ty = match &def.instruction {
+                TopLevelDef::FnDef(fn_def) => Ty::Known(super::fn_type(fn_def)),
+                TopLevelDef::Typed(var_type) => Ty::Known(var_type.clone()),
+                TopLevelDef::Literal(_) | TopLevelDef::Const(_) => inferer.fresh(VarKind::General),
+            };
+            inferer.globals.insert(def.name.clone(), ty);
+        }
+    }
+
+    for (item, statement) in module.iter().enumerate() {
+        inferer.item = item;
+        inferer.counts.clear();
+        if let TopLevelStatement::TopLevelDef(def) = statement {
+            match &def.instruction {
+                TopLevelDef::Literal(literal) => {
+                    let ty = inferer.infer_literal(literal);
+                    let global = inferer.globals[&def.name].clone();
+                    inferer.unify(&global, &ty);
+                }
+                TopLevelDef::FnDef(fn_def) => {
+                    inferer.infer_fn(fn_def);
+                }
+                TopLevelDef::Const(statement) => {
+                    let ty = inferer.infer_statement(statement);
+                    let global = inferer.globals[&def.name].clone();
+                    inferer.unify(&global, &ty);
+                }
+                TopLevelDef::Typed(_) => {}
+            }
+        }
+    }
+
+    let mut inference = Inference::default();
+    for (name, ty) in inferer.globals.clone() {
+        if let Some(var_type) = inferer.zonk(&ty) {
+            inference.globals.insert(name, var_type);
+        }
+    }
+    for (item, name, index, ty) in std::mem::take(&mut inferer.bindings) {
+        inference.bindings.push(InferredBinding {
+            item,
+            name,
+            index,
+            var_type: inferer.zonk(&ty),
+        });
+    }
+    inference
+}
+
+#[derive(Default)]
+struct Inferer {
+    aliases: Aliases,
+    vars: Vec<(VarKind, Option<Ty>)>,
+    globals: HashMap<String, Ty>,
+    scopes: Vec<HashMap<String, Ty>>,
+    bindings: Vec<(usize, String, usize, Ty)>,
+    counts: HashMap<String, usize>,
+    /// Return types of the functions being inferred, innermost last.
+    returns: Vec<VarType>,
+    item: usize,
+}
+
+impl Inferer {
+    fn fresh(&mut self, kind: VarKind) -> Ty {
+        self.vars.push((kind, None));
+        Ty::Var(self.vars.len() - 1)
+    }
+
+    /// Follow solved variables until reaching an unsolved variable or a non-variable type.
+    fn shallow(&self, ty: &Ty) -> Ty {
+        let mut current = ty.clone();
+        while let Ty::Var(var) = current {
+            match &self.vars[var].1 {
+                Some(solution) => current = solution.clone(),
+                None => return Ty::Var(var),
+            }
+        }
+        current
+    }
+
+    /// Break a known type into its structural form so it can unify with inferred structure.
+    fn structure(&self, var_type: &VarType) -> Ty {
+        match self.aliases.normalize(var_type) {
+            VarType::Fn(params, ret) => Ty::Fn(
+                params.iter().map(|p| self.structure(p)).collect(),
+                Box::new(self.structure(&ret)),
+                false,
+            ),
+            VarType::FnWithVarArgs(params, ret) => Ty::Fn(
+                params.iter().map(|p| self.structure(p)).collect(),
+                Box::new(self.structure(&ret)),
+                true,
+            ),
+            VarType::Tuple(types) => Ty::Tuple(types.iter().map(|t| self.structure(t)).collect()),
+            VarType::ArraySized(element, size) => {
+                Ty::Array(Box::new(self.structure(&element)), Some(size))
+            }
+            VarType::ArrayUnsized(element) => Ty::Array(Box::new(self.structure(&element)), None),
+            VarType::Ptr(element) => Ty::Ptr(Box::new(self.structure(&element))),
+            _ => Ty::Known(var_type.clone()),
+        }
+    }
+
+    fn var_accepts(&self, kind: VarKind, var_type: &VarType) -> bool {
+        let normal = self.aliases.normalize(var_type);
+        match kind {
+            VarKind::General => true,
+            VarKind::Integer => is_integer(&normal) || is_float(&normal),
+            VarKind::Float => is_float(&normal),
+        }
+    }
+
+    fn occurs(&self, var: usize, ty: &Ty) -> bool {
+        match self.shallow(ty) {
+            Ty::Var(other) => other == var,
+            Ty::Known(_) => false,
+            Ty::Fn(params, ret, _) => {
+                params.iter().any(|p| self.occurs(var, p)) || self.occurs(var, &ret)
+            }
+            Ty::Tuple(types) => types.iter().any(|t| self.occurs(var, t)),
+            Ty::Array(element, _) | Ty::Ptr(element) => self.occurs(var, &element),
+        }
+    }
+
+    fn unify(&mut self, a: &Ty, b: &Ty) {
+        let a = self.shallow(a);
+        let b = self.shallow(b);
+        match (&a, &b) {
+            (Ty::Var(x), Ty::Var(y)) if x == y => {}
+            (Ty::Var(x), Ty::Var(y)) => {
+                // Merge literal kinds: an integer literal mixed with a float literal is a float.
+                let kind = match (self.vars[*x].0, self.vars[*y].0) {
+                    (VarKind::General, other) | (other, VarKind::General) => other,
+                    (VarKind::Float, _) | (_, VarKind::Float) => VarKind::Float,
+                    _ => VarKind::Integer,
+                };
+                self.vars[*y].0 = kind;
+                self.vars[*x].1 = Some(b.clone());
+            }
+            (Ty::Var(var), other) | (other, Ty::Var(var)) => {
+                let kind = self.vars[*var].0;
+                let accepted = match other {
+                    Ty::Known(var_type) => self.var_accepts(kind, var_type),
+                    _ => kind == VarKind::General,
+                };
+                if accepted && !self.occurs(*var, other) {
+                    self.vars[*var].1 = Some(other.clone());
+                }
+            }
+            (Ty::Known(x), Ty::Known(y)) => {
+                let (x, y) = (self.structure(x), self.structure(y));
+                if !matches!((&x, &y), (Ty::Known(_), Ty::Known(_))) {
+                    self.unify(&x, &y);
+                }
+            }
+            (Ty::Known(known), other) | (other, Ty::Known(known)) => {
+                let structure = self.structure(known);
+                if !matches!(structure, Ty::Known(_)) {
+                    let other = other.clone();
+                    self.unify(&structure, &other);
+                }
+            }
+            (Ty::Fn(xs, x, _), Ty::Fn(ys, y, _)) => {
+                for (x, y) in xs.clone().iter().zip(ys.clone().iter()) {
+                    self.unify(x, y);
+                }
+                self.unify(&x.clone(), &y.clone());
+            }
+            (Ty::Tuple(xs), Ty::Tuple(ys)) => {
+                for (x, y) in xs.clone().iter().zip(ys.clone().iter()) {
+                    self.unify(x, y);
+                }
+            }
+            (Ty::Array(x, _), Ty::Array(y, _)) | (Ty::Ptr(x), Ty::Ptr(y)) => {
+                self.unify(&x.clone(), &y.clone());
+            }
+            _ => {}
+        }
+    }
+
+    /// Substitute solutions and apply literal defaults.
+    fn zonk(&self, ty: &Ty) -> Option<VarType> {
+        match self.shallow(ty) {
+            Ty::Var(var) => match self.vars[var].0 {
+                VarKind::General => None,
+                VarKind::Integer => Some(VarType::Int32),
+                VarKind::Float => Some(VarType::Float64),
+            },
+            Ty::Known(var_type) => Some(var_type),
+            Ty::Fn(params, ret, var_args) => {
+                let params = params
+                    .iter()
+                    .map(|p| self.zonk(p))
+                    .collect::<Option<Vec<_>>>()?;
+                let ret = Box::new(self.zonk(&ret)?);
+                Some(if var_args {
+                    VarType::FnWithVarArgs(params, ret)
+                } else {
+                    VarType::Fn(params, ret)
+                })
+            }
+            Ty::Tuple(types) => Some(VarType::Tuple(
+                types
+                    .iter()
+                    .map(|t| self.zonk(t))
+                    .collect::<Option<Vec<_>>>()?,
+            )),
+            Ty::Array(element, Some(size)) => {
+                Some(VarType::ArraySized(Box::new(self.zonk(&element)?), size))
+            }
+            Ty::Array(element, None) => Some(VarType::ArrayUnsized(Box::new(self.zonk(&element)?))),
+            Ty::Ptr(element) => Some(VarType::Ptr(Box::new(self.zonk(&element)?))),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Ty> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| self.globals.get(name))
+            .cloned()
+    }
+
+    fn lookup_local(&self, name: &str) -> Option<Ty> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    fn bind(&mut self, name: &str, ty: Ty) {
+        let index = self.counts.entry(name.to_string()).or_insert(0);
+        self.bindings
+            .push((self.item, name.to_string(), *index, ty.clone()));
+        *index += 1;
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), ty);
+        }
+    }
+
+    fn with_scope<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(HashMap::new());
+        let result = f(self);
+        self.scopes.pop();
+        result
+    }
+
+    fn infer_fn(&mut self, fn_def: &FnDef) -> Ty {
+        self.with_scope(|inferer| {
+            for (param, var_type) in &fn_def.parameters {
+                inferer.bind(param, Ty::Known(var_type.clone()));
+            }
+            inferer.returns.push(fn_def.return_type.clone());
+            let body = inferer.infer_statement(&fn_def.statement);
+            inferer.returns.pop();
+            if fn_def.return_type != VarType::Void && !diverges(&fn_def.statement) {
+                inferer.unify(&Ty::Known(fn_def.return_type.clone()), &body);
+            }
+        });
+        self.structure(&super::fn_type(fn_def))
+    }
+
+    fn infer_literal(&mut self, literal: &Literal) -> Ty {
+        match literal {
+            Literal::Int(_) => self.fresh(VarKind::Integer),
+            Literal::Float(_) => self.fresh(VarKind::Float),
+            Literal::Bool(_) => Ty::Known(VarType::Bool),
+            Literal::Char(_) => Ty::Known(VarType::Int8),
+            Literal::String(_) => Ty::Known(str_type()),
+            Literal::Atom(_) => Ty::Known(atom_type()),
+            Literal::Tuple(items) => {
+                Ty::Tuple(items.iter().map(|i| self.infer_statement(i)).collect())
+            }
+            Literal::Array(items) => {
+                let element = self.fresh(VarKind::General);
+                for item in items {
+                    let ty = self.infer_statement(item);
+                    self.unify(&element, &ty);
+                }
+                Ty::Array(Box::new(element), Some(items.len()))
+            }
+            Literal::Data(tag, args) => {
+                let args: Vec<_> = args.iter().map(|a| self.infer_statement(a)).collect();
+                let mut candidates: Vec<_> = self
+                    .aliases
+                    .types
+                    .iter()
+                    .filter_map(|(name, var_type)| match var_type {
+                        VarType::Data(variants) => variants
+                            .iter()
+                            .find(|(t, _)| t == tag)
+                            .map(|(_, fields)| (name.clone(), fields.clone())),
+                        _ => None,
+                    })
+                    .collect();
+                if candidates.len() != 1 {
+                    return self.fresh(VarKind::General);
+                }
+                let (name, fields) = candidates.pop().unwrap();
+                for (arg, field) in args.iter().zip(fields) {
+                    self.unify(arg, &Ty::Known(field));
+                }
+                Ty::Known(VarType::IdentType(name))
+            }
+            Literal::Fn(fn_def) => self.infer_fn(fn_def),
+        }
+    }
+
+    fn infer_call(&mut self, name: &str, args: &[Statement]) -> Ty {
+        let arg_types: Vec<_> = args.iter().map(|a| self.infer_statement(a)).collect();
+
+        if let Some(ty) = self.infer_intrinsic(name, &arg_types) {
+            return ty;
+        }
+
+        if let Some(callee) = self.lookup(name) {
+            let callee = match self.shallow(&callee) {
+                Ty::Known(known) => self.structure(&known),
+                other => other,
+            };
+            return match callee {
+                Ty::Fn(params, ret, _) => {
+                    for (param, arg) in params.iter().zip(&arg_types) {
+                        self.unify(param, arg);
+                    }
+                    *ret
+                }
+                Ty::Var(_) => {
+                    let ret = self.fresh(VarKind::General);
+                    let fn_ty = Ty::Fn(arg_types, Box::new(ret.clone()), false);
+                    self.unify(&callee, &fn_ty);
+                    ret
+                }
+                _ => self.fresh(VarKind::General),
+            };
+        }
+
+        if let Some(alias) = self.aliases.types.get(name).cloned() {
+            // Instances of generic types are left to the checker, which binds their parameters.
+            if self.aliases.generic_params(name).is_some() {
+                return self.fresh(VarKind::General);
+            }
+            let params = match self.aliases.normalize(&alias) {
+                VarType::Struct(fields) => fields.into_iter().map(|(_, t)| t).collect(),
+                VarType::Tuple(types) => types,
+                other => vec![other],
+            };
+            for (param, arg) in params.into_iter().zip(&arg_types) {
+                self.unify(&Ty::Known(param), arg);
+            }
+            return Ty::Known(VarType::IdentType(name.to_string()));
+        }
+
+        if builtins::LOGICAL.contains(&name) {
+            for arg in &arg_types {
+                self.unify(&Ty::Known(VarType::Bool), arg);
+            }
+            return Ty::Known(VarType::Bool);
+        }
+
+        if builtins::is_builtin_fn(name) {
+            let first = arg_types.first().cloned();
+            let pointer = first
+                .as_ref()
+                .and_then(|f| self.zonk(f))
+                .is_some_and(|t| matches!(self.aliases.normalize(&t), VarType::Ptr(_)));
+            if !pointer {
+                for pair in arg_types.windows(2) {
+                    self.unify(&pair[0], &pair[1]);
+                }
+            }
+            if builtins::COMPARISON.contains(&name) {
+                return Ty::Known(VarType::Bool);
+            }
+            return first.unwrap_or_else(|| self.fresh(VarKind::General));
+        }
+
+        self.fresh(VarKind::General)
+    }
+
+    fn infer_intrinsic(&mut self, name: &str, arg_types: &[Ty]) -> Option<Ty> {
+        match name {
+            builtins::BOX => Some(Ty::Ptr(Box::new(arg_types.first()?.clone()))),
+            builtins::UNBOX => {
+                let element = self.fresh(VarKind::General);
+                self.unify(arg_types.first()?, &Ty::Ptr(Box::new(element.clone())));
+                Some(element)
+            }
+            builtins::SET_BOX => {
+                let [cell, value] = arg_types else {
+                    return None;
+                };
+                self.unify(cell, &Ty::Ptr(Box::new(value.clone())));
+                Some(Ty::Known(VarType::Void))
+            }
+            // The lifted function takes the environment first; the closure takes the rest.
+            builtins::MAKE_CLOSURE => {
+                let (lifted, captured) = arg_types.split_first()?;
+                let lifted = match self.shallow(lifted) {
+                    Ty::Known(known) => self.structure(&known),
+                    other => other,
+                };
+                let Ty::Fn(params, ret, _) = lifted else {
+                    return Some(self.fresh(VarKind::General));
+                };
+                let env = params.first().and_then(|env| self.zonk(env));
+                let fields = env.and_then(|env| self.aliases.struct_fields(&env));
+                for ((_, field), value) in fields.unwrap_or_default().into_iter().zip(captured) {
+                    self.unify(&Ty::Known(field), value);
+                }
+                Some(Ty::Fn(
+                    params.get(1..).unwrap_or_default().to_vec(),
+                    ret,
+                    false,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn field_type(&mut self, field: &str, target: &Statement) -> Ty {
+        let target = self.infer_statement(target);
+        let fields = self
+            .zonk(&target)
+            .and_then(|t| self.aliases.struct_fields(&t));
+        match fields.and_then(|fields| fields.into_iter().find(|(name, _)| name == field)) {
+            Some((_, var_type)) => Ty::Known(var_type),
+            None => self.fresh(VarKind::General),
+        }
+    }
+
+    fn element_type(&mut self, index: &Statement, target: &Statement) -> Ty {
+        let index_ty = self.infer_statement(index);
+        let target = self.infer_statement(target);
+        match self.shallow(&target) {
+            Ty::Array(element, _) | Ty::Ptr(element) => {
+                let index_var = self.fresh(VarKind::Integer);
+                self.unify(&index_var, &index_ty);
+                *element
+            }
+            Ty::Tuple(types) => match index {
+                Statement::Literal(Literal::Int(i)) => types
+                    .get(*i as usize)
+                    .cloned()
+                    .unwrap_or_else(|| self.fresh(VarKind::General)),
+                _ => self.fresh(VarKind::General),
+            },
+            Ty::Known(known) => match self.aliases.normalize(&known) {
+                VarType::ArraySized(element, _)
+                | VarType::ArrayUnsized(element)
+                | VarType::Ptr(element) => Ty::Known(*element),
+                VarType::Tuple(types) => match index {
+                    Statement::Literal(Literal::Int(i)) => types
+                        .get(*i as usize)
+                        .map(|t| Ty::Known(t.clone()))
+                        .unwrap_or_else(|| self.fresh(VarKind::General)),
+                    _ => self.fresh(VarKind::General),
+                },
+                _ => self.fresh(VarKind::General),
+            },
+            _ => self.fresh(VarKind::General),
+        }
+    }
+
+    /// The element type of an iterated array, or `iterated` itself for an integer range. With
+    /// `slice`, pointers are iterated too.
+    fn iterated_element(&mut self, iterated: Ty, slice: bool) -> Ty {
+        match self.shallow(&iterated) {
+            Ty::Array(element, _) => *element,
+            Ty::Known(known) => match self.aliases.normalize(&known) {
+                VarType::ArraySized(element, _) | VarType::ArrayUnsized(element) => {
+                    Ty::Known(*element)
+                }
+                VarType::Ptr(element) if slice => Ty::Known(*element),
+                _ => iterated,
+            },
+            _ => iterated,
+        }
+    }
+
+    fn infer_statement(&mut self, statement: &Statement) -> Ty {
+        match statement {
+            Statement::Ident(name) => self
+                .lookup(name)
+                .unwrap_or_else(|| self.fresh(VarKind::General)),
+            Statement::Literal(literal) => self.infer_literal(literal),
+            Statement::DoBlock(statements) => self.with_scope(|inferer| {
+                let mut last = Ty::Known(VarType::Void);
+                for statement in statements {
+                    last = inferer.infer_statement(statement);
+                }
+                last
+            }),
+            Statement::Call(name, args) => self.infer_call(name, args),
+            Statement::GenericCall(_, _, args) => {
+                for arg in args {
+                    self.infer_statement(arg);
+                }
+                self.fresh(VarKind::General)
+            }
+            Statement::DefVar(def) => {
+                let value = self.infer_statement(&def.instruction);
+                match self.lookup_local(&def.name) {
+                    Some(existing) => self.unify(&existing, &value),
+                    None => self.bind(&def.name, value),
+                }
+                Ty::Known(VarType::Void)
+            }
+            Statement::Declare(name, var_type) => {
+                self.bind(name, Ty::Known(var_type.clone()));
+                Ty::Known(VarType::Void)
+            }
+            Statement::If(condition, then) => {
+                let condition = self.infer_statement(condition);
+                self.unify(&Ty::Known(VarType::Bool), &condition);
+                self.with_scope(|inferer| inferer.infer_statement(then));
+                Ty::Known(VarType::Void)
+            }
+            Statement::IfElse(condition, then, otherwise) => {
+                let condition = self.infer_statement(condition);
+                self.unify(&Ty::Known(VarType::Bool), &condition);
+                let then_ty = self.with_scope(|inferer| inferer.infer_statement(then));
+                let else_ty = self.with_scope(|inferer| inferer.infer_statement(otherwise));
+                // A branch that jumps away, like `(break)`, takes the type of the other one.
+                match (diverges(then), diverges(otherwise)) {
+                    (true, _) => else_ty,
+                    (false, true) => then_ty,
+                    (false, false) => {
+                        self.unify(&then_ty, &else_ty);
+                        then_ty
+                    }
+                }
+            }
+            Statement::For(condition, body) => {
+                let condition = self.infer_statement(condition);
+                self.unify(&Ty::Known(VarType::Bool), &condition);
+                self.with_scope(|inferer| inferer.infer_statement(body));
+                Ty::Known(VarType::Void)
+            }
+            Statement::ForRange(name, range, body) => {
+                let element = match range.as_ref() {
+                    Range::Each(range) => {
+                        let range = self.infer_statement(range);
+                        self.iterated_element(range, false)
+                    }
+                    Range::Bounds(start, end, step) => {
+                        let start = self.infer_statement(start);
+                        let end = self.infer_statement(end);
+                        self.unify(&start, &end);
+                        if let Some(step) = step {
+                            let step = self.infer_statement(step);
+                            self.unify(&start, &step);
+                        }
+                        start
+                    }
+                    Range::Slice(array, start, end) => {
+                        let array = self.infer_statement(array);
+                        self.infer_statement(start);
+                        self.infer_statement(end);
+                        self.iterated_element(array, true)
+                    }
+                };
+                self.with_scope(|inferer| {
+                    inferer.bind(name, element);
+                    inferer.infer_statement(body);
+                });
+                Ty::Known(VarType::Void)
+            }
+            Statement::Break | Statement::Continue => Ty::Known(VarType::Void),
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    let value = self.infer_statement(value);
+                    match self.returns.last().cloned() {
+                        Some(VarType::Void) | None => {}
+                        Some(return_type) => self.unify(&Ty::Known(return_type), &value),
+                    }
+                }
+                Ty::Known(VarType::Void)
+            }
+            Statement::GetField(field, target) => self.field_type(field, target),
+            Statement::GetIndexed(index, target) => self.element_type(index, target),
+            Statement::SetField(field, target, value) => {
+                let field = self.field_type(field, target);
+                let value = self.infer_statement(value);
+                self.unify(&field, &value);
+                Ty::Known(VarType::Void)
+            }
+            Statement::SetIndexed(index, target, value) => {
+                let element = self.element_type(index, target);
+                let value = self.infer_statement(value);
+                self.unify(&element, &value);
+                Ty::Known(VarType::Void)
+            }
+        }
+    }
+}
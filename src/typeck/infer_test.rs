@@ -0,0 +1,129 @@
+#[cfg(test)]
+mod tests {
+    use crate::ast::VarType;
+    use crate::parser::parser;
+    use crate::typeck::check;
+    use crate::typeck::infer::{infer, Inference};
+    use chumsky::Parser;
+
+    fn run(src: &str) -> Inference {
+        let module = parser().parse(src).into_output().unwrap();
+        infer(&module)
+    }
+
+    #[test]
+    fn test_literal_defaults() {
+        let inference = run("(def x 42) (def y 3.14) (def s \"hello\") \n
+            (def f (fn [] void (do (def z (+ x 10)) (def w (* 2 y)))))");
+        assert_eq!(inference.globals["x"], VarType::Int32);
+        assert_eq!(inference.globals["y"], VarType::Float64);
+        assert_eq!(inference.globals["s"], VarType::IdentType("str".into()));
+        assert_eq!(inference.type_of_binding(3, "z"), Some(&VarType::Int32));
+        assert_eq!(inference.type_of_binding(3, "w"), Some(&VarType::Float64));
+    }
+
+    #[test]
+    fn test_literal_takes_width_from_use() {
+        let src = "(def x 42) \n
+            (def f (fn [(:y i64)] i64 (+ x y))) \n
+            (def g (fn [] u8 (do (def a 1) (def b a) b)))";
+        let inference = run(src);
+        assert_eq!(inference.globals["x"], VarType::Int64);
+        assert_eq!(inference.type_of_binding(2, "a"), Some(&VarType::UInt8));
+        assert_eq!(inference.type_of_binding(2, "b"), Some(&VarType::UInt8));
+
+        let module = parser().parse(src).into_output().unwrap();
+        assert!(check(&module).is_empty(), "{:?}", check(&module));
+    }
+
+    #[test]
+    fn test_int_literal_mixed_with_float_is_float() {
+        let inference = run("(def f (fn [] void (do (def a 1) (def b (+ a 2.5)))))");
+        assert_eq!(inference.type_of_binding(0, "a"), Some(&VarType::Float64));
+        assert_eq!(inference.type_of_binding(0, "b"), Some(&VarType::Float64));
+    }
+
+    #[test]
+    fn test_through_function_return_types() {
+        let inference = run("(def fib (fn [(:n i64)] i64 n)) \n
+            (def main (fn [] i32 (do (def r (fib 10)) (def ok (< r 3)) 0)))");
+        assert_eq!(inference.type_of_binding(1, "r"), Some(&VarType::Int64));
+        assert_eq!(inference.type_of_binding(1, "ok"), Some(&VarType::Bool));
+    }
+
+    #[test]
+    fn test_through_closures() {
+        let inference = run("(def make-adder (fn [(:n i16)] fn [i16] i16 \n
+                (fn [(:x i16)] i16 (+ x n)))) \n
+            (def main (fn [] i32 (do (def add5 (make-adder 5)) (def r (add5 1)) 0)))");
+        assert_eq!(
+            inference.type_of_binding(1, "add5"),
+            Some(&VarType::Fn(vec![VarType::Int16], Box::new(VarType::Int16)))
+        );
+        assert_eq!(inference.type_of_binding(1, "r"), Some(&VarType::Int16));
+
+        let inference = run("(def main (fn [] void (do \n
+                (def n 3) \n
+                (def twice (fn [(:x u64)] u64 (* x n))))))");
+        assert_eq!(inference.type_of_binding(0, "n"), Some(&VarType::UInt64));
+    }
+
+    #[test]
+    fn test_rebinding_and_indexes() {
+        let inference = run("(def f (fn [(:x i32)] void (do \n
+                (def a 1) \n
+                (def a (+ a x)) \n
+                (def g (fn [(:a i64)] i64 a)))))");
+        assert_eq!(inference.binding(0, "a", 0), Some(&VarType::Int32));
+        assert_eq!(inference.binding(0, "a", 1), Some(&VarType::Int64));
+        assert_eq!(inference.binding(0, "x", 0), Some(&VarType::Int32));
+    }
+
+    #[test]
+    fn test_arrays_and_loops() {
+        let inference = run("(def f (fn [(:out [u16 3])] void (do \n
+                (def xs [1 2 3]) \n
+                ($ [0] out ($ [0] xs)) \n
+                (for (range i xs) (def last i)))))");
+        assert_eq!(
+            inference.type_of_binding(0, "xs"),
+            Some(&VarType::ArraySized(Box::new(VarType::UInt16), 3))
+        );
+        assert_eq!(inference.type_of_binding(0, "i"), Some(&VarType::UInt16));
+        assert_eq!(inference.type_of_binding(0, "last"), Some(&VarType::UInt16));
+    }
+
+    #[test]
+    fn test_range_bounds_and_slices() {
+        let inference = run("(def f (fn [(:n u8) (:p (ptr i16))] void (do \n
+                (for (range i 0 n) (def a i)) \n
+                (for (range x :slice p 0 4) (def b x)))))");
+        assert_eq!(inference.type_of_binding(0, "i"), Some(&VarType::UInt8));
+        assert_eq!(inference.type_of_binding(0, "a"), Some(&VarType::UInt8));
+        assert_eq!(inference.type_of_binding(0, "b"), Some(&VarType::Int16));
+    }
+
+    #[test]
+    fn test_unknowns_stay_unknown() {
+        let inference = run("(def stdio (use :header \"stdio.h\")) \n
+            (def f (fn [] void (def c (stdio/getchar))))");
+        assert_eq!(inference.type_of_binding(1, "c"), None);
+    }
+
+    #[test]
+    fn test_closure_intrinsics_after_lifting() {
+        let module = parser()
+            .parse(
+                "(def make (fn [] fn [] i64 \n
+                    (do (def n 0) (fn [] i64 (do (def n (+ n 1)) n)))))",
+            )
+            .into_output()
+            .unwrap();
+        let lifted = crate::transformer::lambda::run(&module);
+        let inference = infer(&lifted);
+        let boxed = VarType::Ptr(Box::new(VarType::Int64));
+        // `lambda$0$env`, `lambda$0`, then `make`.
+        assert_eq!(inference.type_of_binding(1, "n$box"), Some(&boxed));
+        assert_eq!(inference.type_of_binding(2, "n$box"), Some(&boxed));
+    }
+}
@@ -0,0 +1,409 @@
+//! Definite initialization.
+//!
+//! A local declared with `(def (:x T))` has no value until a `def` assigns it, and a struct
+//! local can also be filled in field by field with `$`. The analysis follows every path through
+//! a function and reports reads of a local or field that is not assigned on all paths leading
+//! to the read. Locals bound with a value, parameters and loop variables always have one.
+//!
+//! The analysis is conservative where the control flow depends on values: any `for` loop may
+//! run zero times, except `(for true ...)`, which is only left through `(break)`. Elements of
+//! an array are not tracked, so writing one does not initialize the array. A closure reads the
+//! locals it captures when it is created.
+//!
+//! [`missing_value`] is the other half: the checker uses it to require that every path through
+//! a function with a return type ends in a value.
+
+use super::types::Aliases;
+use crate::ast::{FnDef, Literal, Statement, TopLevelDef, TopLevelStatement, VarType};
+use crate::diagnostic::Diagnostic;
+use crate::lint::diverges;
+use std::collections::{HashMap, HashSet};
+
+/// The initialization errors of every function and constant in `module`.
+pub fn check(module: &[TopLevelStatement], aliases: &Aliases) -> Vec<Diagnostic> {
+    let mut analysis = Analysis {
+        aliases,
+        diagnostics: Vec::new(),
+        item: 0,
+        vars: Vec::new(),
+        scopes: Vec::new(),
+        state: State::Reachable(HashSet::new()),
+        loops: Vec::new(),
+    };
+    for (item, statement) in module.iter().enumerate() {
+        analysis.item = item;
+        if let TopLevelStatement::TopLevelDef(def) = statement {
+            match &def.instruction {
+                TopLevelDef::FnDef(fn_def) => analysis.function(fn_def),
+                TopLevelDef::Const(statement) => analysis.scoped(|a| a.statement(statement)),
+                TopLevelDef::Literal(literal) => analysis.literal(literal),
+                TopLevelDef::Typed(_) => {}
+            }
+        }
+    }
+    analysis.diagnostics
+}
+
+/// Why `statement`, the body of a function, can finish without a value: the construct that
+/// ends a path without one. `None` if every path ends in a value or never ends.
+pub fn missing_value(statement: &Statement) -> Option<&'static str> {
+    if diverges(statement) {
+        return None;
+    }
+    match statement {
+        Statement::DoBlock(statements) => match statements.last() {
+            Some(last) => missing_value(last),
+            None => Some("an empty `do` block"),
+        },
+        Statement::IfElse(_, then, otherwise) => {
+            missing_value(then).or_else(|| missing_value(otherwise))
+        }
+        Statement::If(..) => Some("an `if` without `else`"),
+        Statement::For(..) | Statement::ForRange(..) => Some("a `for` loop"),
+        Statement::DefVar(_) | Statement::Declare(..) => Some("a `def`"),
+        Statement::SetField(..) | Statement::SetIndexed(..) => Some("a `$` assignment"),
+        _ => None,
+    }
+}
+
+type VarId = usize;
+
+/// A tracked local and the fields leading to part of it: `p` and `["pos", "x"]` for the field
+/// `x` of the field `pos` of `p`.
+type Path = (VarId, Vec<String>);
+
+/// What is initialized at a point of the program, or nothing if the point cannot be reached.
+#[derive(Clone, Debug)]
+enum State {
+    Unreachable,
+    Reachable(HashSet<Path>),
+}
+
+/// How a `$` chain rooted at a tracked local touches it.
+enum Access {
+    /// The value at the path is used as a whole, e.g. a pointer that is dereferenced.
+    Read(Vec<String>),
+    /// The path is assigned as a whole.
+    Write(Vec<String>),
+    /// An element of the array or tuple at the path is assigned, which needs no value.
+    Element,
+}
+
+struct Analysis<'a> {
+    aliases: &'a Aliases,
+    diagnostics: Vec<Diagnostic>,
+    item: usize,
+    /// The name and type of every local declared without a value.
+    vars: Vec<(String, VarType)>,
+    /// Locals in scope, innermost last; `None` for locals that always have a value.
+    scopes: Vec<HashMap<String, Option<VarId>>>,
+    state: State,
+    /// The states at the `(break)`s of the enclosing loops, innermost last.
+    loops: Vec<Vec<State>>,
+}
+
+impl Analysis<'_> {
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(HashMap::new());
+        let result = f(self);
+        self.scopes.pop();
+        result
+    }
+
+    fn bind(&mut self, name: &str, var: Option<VarId>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), var);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Option<VarId>> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+
+    /// The tracked local `name`, if it is one.
+    fn tracked(&self, name: &str) -> Option<VarId> {
+        self.lookup(name).flatten()
+    }
+
+    fn function(&mut self, fn_def: &FnDef) {
+        let loops = std::mem::take(&mut self.loops);
+        self.scoped(|analysis| {
+            for (param, _) in &fn_def.parameters {
+                analysis.bind(param, None);
+            }
+            analysis.statement(&fn_def.statement);
+        });
+        self.loops = loops;
+    }
+
+    /// A closure is checked where it is created, with the locals it can capture; what it
+    /// assigns does not count outside of it.
+    fn closure(&mut self, fn_def: &FnDef) {
+        let state = self.state.clone();
+        self.function(fn_def);
+        self.state = state;
+    }
+
+    fn literal(&mut self, literal: &Literal) {
+        match literal {
+            Literal::Tuple(items) | Literal::Data(_, items) | Literal::Array(items) => {
+                for item in items {
+                    self.statement(item);
+                }
+            }
+            Literal::Fn(fn_def) => self.closure(fn_def),
+            _ => {}
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Ident(name) => {
+                if let Some(var) = self.tracked(name) {
+                    self.read(var, Vec::new());
+                }
+            }
+            Statement::Literal(literal) => self.literal(literal),
+            Statement::DoBlock(statements) => self.scoped(|analysis| {
+                for statement in statements {
+                    analysis.statement(statement);
+                }
+            }),
+            Statement::Call(name, args) | Statement::GenericCall(name, _, args) => {
+                if let Some(var) = self.tracked(name) {
+                    self.read(var, Vec::new());
+                }
+                for arg in args {
+                    self.statement(arg);
+                }
+            }
+            Statement::DefVar(def) => {
+                self.statement(&def.instruction);
+                match self.lookup(&def.name) {
+                    Some(Some(var)) => self.write(var, Vec::new()),
+                    Some(None) => {}
+                    None => self.bind(&def.name, None),
+                }
+            }
+            Statement::Declare(name, var_type) => {
+                self.vars.push((name.clone(), var_type.clone()));
+                self.bind(name, Some(self.vars.len() - 1));
+            }
+            Statement::If(condition, then) => {
+                self.statement(condition);
+                let skipped = self.state.clone();
+                self.scoped(|analysis| analysis.statement(then));
+                self.state = join(&skipped, &self.state);
+            }
+            Statement::IfElse(condition, then, otherwise) => {
+                self.statement(condition);
+                let before = self.state.clone();
+                self.scoped(|analysis| analysis.statement(then));
+                let after_then = std::mem::replace(&mut self.state, before);
+                self.scoped(|analysis| analysis.statement(otherwise));
+                self.state = join(&after_then, &self.state);
+            }
+            Statement::For(condition, body) => {
+                self.statement(condition);
+                let endless = matches!(condition.as_ref(), Statement::Literal(Literal::Bool(true)));
+                let skipped = match endless {
+                    true => State::Unreachable,
+                    false => self.state.clone(),
+                };
+                self.lower_loop(skipped, |analysis| analysis.statement(body));
+            }
+            Statement::ForRange(name, range, body) => {
+                for part in range.parts() {
+                    self.statement(part);
+                }
+                let skipped = self.state.clone();
+                self.lower_loop(skipped, |analysis| {
+                    analysis.bind(name, None);
+                    analysis.statement(body);
+                });
+            }
+            Statement::Break => {
+                let state = std::mem::replace(&mut self.state, State::Unreachable);
+                if let Some(breaks) = self.loops.last_mut() {
+                    breaks.push(state);
+                }
+            }
+            Statement::Continue => self.state = State::Unreachable,
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.statement(value);
+                }
+                self.state = State::Unreachable;
+            }
+            Statement::GetField(..) | Statement::GetIndexed(..) => self.access(statement, None),
+            Statement::SetField(_, _, value) | Statement::SetIndexed(_, _, value) => {
+                self.access(statement, Some(value))
+            }
+        }
+    }
+
+    /// Run a loop body from the current state. After the loop, what is initialized is what
+    /// every exit has: `skipped` when the body does not run, and each `(break)`.
+    fn lower_loop(&mut self, skipped: State, body: impl FnOnce(&mut Self)) {
+        self.loops.push(Vec::new());
+        self.scoped(body);
+        let breaks = self.loops.pop().unwrap_or_default();
+        self.state = breaks
+            .iter()
+            .fold(skipped, |state, exit| join(&state, exit));
+    }
+
+    /// A `$` chain, with `value` if it assigns.
+    fn access(&mut self, statement: &Statement, value: Option<&Statement>) {
+        let mut segments = Vec::new();
+        let mut root = statement;
+        loop {
+            match root {
+                Statement::GetField(field, target) | Statement::SetField(field, target, _) => {
+                    segments.push(Ok(field.as_str()));
+                    root = target;
+                }
+                Statement::GetIndexed(index, target) | Statement::SetIndexed(index, target, _) => {
+                    segments.push(Err(index.as_ref()));
+                    root = target;
+                }
+                _ => break,
+            }
+        }
+        segments.reverse();
+
+        let var = match root {
+            Statement::Ident(name) => self.tracked(name),
+            _ => None,
+        };
+        if var.is_none() {
+            self.statement(root);
+        }
+        for index in segments.iter().filter_map(|segment| segment.err()) {
+            self.statement(index);
+        }
+        if let Some(value) = value {
+            self.statement(value);
+        }
+
+        let Some(var) = var else {
+            return;
+        };
+        let fields: Vec<_> = segments.iter().map(|segment| segment.ok()).collect();
+        match self.classify(var, &fields, value.is_some()) {
+            Access::Read(path) => self.read(var, path),
+            Access::Write(path) => self.write(var, path),
+            Access::Element => {}
+        }
+    }
+
+    /// Follow `segments` (field names, or `None` for indices) from the local `var` through
+    /// struct values for as long as they stay inside its storage.
+    fn classify(&self, var: VarId, segments: &[Option<&str>], write: bool) -> Access {
+        let mut var_type = self.vars[var].1.clone();
+        let mut path = Vec::new();
+        for (i, segment) in segments.iter().enumerate() {
+            let last = i + 1 == segments.len();
+            match (segment, self.aliases.normalize(&var_type)) {
+                (Some(field), VarType::Struct(fields)) => {
+                    match fields.into_iter().find(|(name, _)| name == field) {
+                        Some((_, field_type)) => var_type = field_type,
+                        None => return Access::Read(path),
+                    }
+                    path.push(field.to_string());
+                }
+                (None, VarType::ArraySized(..) | VarType::ArrayUnsized(_) | VarType::Tuple(_))
+                    if write && last =>
+                {
+                    return Access::Element
+                }
+                _ => return Access::Read(path),
+            }
+        }
+        match write {
+            true => Access::Write(path),
+            false => Access::Read(path),
+        }
+    }
+
+    fn write(&mut self, var: VarId, path: Vec<String>) {
+        if let State::Reachable(initialized) = &mut self.state {
+            initialized.insert((var, path));
+        }
+    }
+
+    fn read(&mut self, var: VarId, path: Vec<String>) {
+        let State::Reachable(initialized) = &self.state else {
+            return;
+        };
+        if is_initialized(self.aliases, &self.vars, initialized, var, &path) {
+            return;
+        }
+        let name = &self.vars[var].0;
+        let message = match path.is_empty() {
+            true => format!("`{}` is read before it is initialized", name),
+            false => format!(
+                "field `{}` of `{}` is read before it is initialized",
+                path.join("."),
+                name
+            ),
+        };
+        self.diagnostics.push(Diagnostic::error(self.item, message));
+        // Report every uninitialized read once.
+        self.write(var, path);
+    }
+}
+
+/// Whether the part of `var` at `path` has a value: it or a part containing it was assigned,
+/// or it is a struct and all of its fields have a value.
+fn is_initialized(
+    aliases: &Aliases,
+    vars: &[(String, VarType)],
+    initialized: &HashSet<Path>,
+    var: VarId,
+    path: &[String],
+) -> bool {
+    if (0..=path.len()).any(|end| initialized.contains(&(var, path[..end].to_vec()))) {
+        return true;
+    }
+
+    let mut var_type = vars[var].1.clone();
+    for field in path {
+        let Some(fields) = struct_fields(aliases, &var_type) else {
+            return false;
+        };
+        match fields.into_iter().find(|(name, _)| name == field) {
+            Some((_, field_type)) => var_type = field_type,
+            None => return false,
+        }
+    }
+    match struct_fields(aliases, &var_type) {
+        Some(fields) if !fields.is_empty() => fields.iter().all(|(field, _)| {
+            let mut field_path = path.to_vec();
+            field_path.push(field.clone());
+            is_initialized(aliases, vars, initialized, var, &field_path)
+        }),
+        _ => false,
+    }
+}
+
+/// The fields of a struct value; unlike [`Aliases::struct_fields`], not through a pointer.
+fn struct_fields(aliases: &Aliases, var_type: &VarType) -> Option<Vec<(String, VarType)>> {
+    match aliases.normalize(var_type) {
+        VarType::Struct(fields) => Some(fields),
+        _ => None,
+    }
+}
+
+/// What is initialized where two paths meet: what both of them initialize.
+fn join(a: &State, b: &State) -> State {
+    match (a, b) {
+        (State::Unreachable, other) | (other, State::Unreachable) => other.clone(),
+        (State::Reachable(a), State::Reachable(b)) => {
+            State::Reachable(a.intersection(b).cloned().collect())
+        }
+    }
+}
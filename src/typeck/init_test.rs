@@ -0,0 +1,76 @@
+#[cfg(test)]
+mod tests {
+    use crate::parser::parser;
+    use crate::typeck::check;
+    use chumsky::Parser;
+
+    fn errors(src: &str) -> Vec<String> {
+        let module = parser().parse(src).into_output().unwrap();
+        check(&module).into_iter().map(|d| d.message).collect()
+    }
+
+    #[test]
+    fn test_read_before_assignment() {
+        let src = "(def f (fn [] i32 (do (def (:x i32)) (+ x 1))))";
+        assert_eq!(errors(src), vec!["`x` is read before it is initialized"]);
+
+        let src = "(def f (fn [] i32 (do (def (:x i32)) (def x 2) (+ x 1))))";
+        assert!(errors(src).is_empty(), "{:?}", errors(src));
+    }
+
+    #[test]
+    fn test_branches_must_all_assign() {
+        let src = "(def f (fn [(:c bool)] i32 (do (def (:x i32)) (if c (do (def x 1))) x)))";
+        assert_eq!(errors(src), vec!["`x` is read before it is initialized"]);
+
+        let src = "(def f (fn [(:c bool)] i32 \n
+                (do (def (:x i32)) (if c (do (def x 1)) (do (def x 2))) x)))";
+        assert!(errors(src).is_empty(), "{:?}", errors(src));
+    }
+
+    #[test]
+    fn test_loops() {
+        // A loop may not run at all.
+        let src = "(def f (fn [(:c bool)] i32 (do (def (:x i32)) (for c (do (def x 1))) x)))";
+        assert_eq!(errors(src), vec!["`x` is read before it is initialized"]);
+
+        // An endless loop is only left through `(break)`.
+        let src = "(def f (fn [] i32 (do (def (:x i32)) (for true (do (def x 1) (break))) x)))";
+        assert!(errors(src).is_empty(), "{:?}", errors(src));
+    }
+
+    #[test]
+    fn test_struct_fields() {
+        let module = "(type point (struct (:x f64) (:y f64)))";
+        let src = format!(
+            "{} (def f (fn [] f64 (do (def (:p point)) ($ :x p 1.0) ($ :y p))))",
+            module
+        );
+        assert_eq!(
+            errors(&src),
+            vec!["field `y` of `p` is read before it is initialized"]
+        );
+
+        // A struct is initialized once all of its fields are.
+        let src = format!(
+            "{} (def g (fn [(:q point)] void (do)))
+                (def f (fn [] void (do (def (:p point)) ($ :x p 1.0) ($ :y p 2.0) (g p))))",
+            module
+        );
+        assert!(errors(&src).is_empty(), "{:?}", errors(&src));
+    }
+
+    #[test]
+    fn test_missing_return_value() {
+        assert_eq!(
+            errors("(def f (fn [(:c bool)] i32 (if c 1)))"),
+            vec!["missing return value of `f`: an `if` without `else` can finish without a value of type `i32`"]
+        );
+        assert_eq!(
+            errors("(def f (fn [] i32 (do (def x 1))))"),
+            vec!["missing return value of `f`: a `def` can finish without a value of type `i32`"]
+        );
+        assert!(errors("(def f (fn [] i32 (for true (do))))").is_empty());
+        assert!(errors("(def f (fn [(:c bool)] void (if c 1)))").is_empty());
+    }
+}
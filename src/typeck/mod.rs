@@ -0,0 +1,1232 @@
+//! Static type checking.
+//!
+//! The checker is bidirectional: where the surrounding code expects a type (an annotated
+//! parameter, a function's return type, the other operand of an arithmetic call) it is pushed
+//! down into the expression, which is how untyped integer and float literals pick up a
+//! concrete width. Without an expectation integer literals default to `i32` and float literals
+//! to `f64`.
+//!
+//! Expressions whose type cannot be known yet (members of imported modules) check as `None`
+//! and are compatible with everything, so a single unknown does not cascade into unrelated
+//! errors.
+//!
+//! Calls to generic functions and constructors of generic types bind their type parameters
+//! from explicit type arguments (`(id<i64> 1)`) or from the types of the arguments, and
+//! check as the instantiated type, e.g. `pair<i32 bool>`.
+//!
+//! Unannotated bindings take the type computed by [`infer`], so a literal bound with `def`
+//! is checked at the width its later uses require.
+//!
+//! Reads of locals declared without a value are checked by [`init`], after the types.
+
+pub mod infer;
+pub mod init;
+pub mod types;
+
+mod check_test;
+mod infer_test;
+mod init_test;
+
+use crate::ast::{
+    generic_names, FnDef, Generic, Literal, Range, Statement, TopLevelDef, TopLevelStatement,
+    VarType,
+};
+use crate::builtins;
+use crate::diagnostic::Diagnostic;
+use crate::lint::diverges;
+use crate::parser::var_type;
+use crate::transformer::lambda::free_variables;
+use chumsky::Parser;
+use infer::Inference;
+use std::collections::{HashMap, HashSet};
+use types::{atom_type, implies, mentions, satisfies, str_type, substitute, Aliases};
+
+/// Type check a whole module and return every diagnostic found.
+pub fn check(module: &[TopLevelStatement]) -> Vec<Diagnostic> {
+    let mut checker = Checker::new(module);
+    checker.check_module(module);
+    let mut diagnostics = checker.diagnostics;
+    diagnostics.extend(init::check(module, &checker.aliases));
+    diagnostics
+}
+
+pub struct Checker {
+    pub aliases: Aliases,
+    pub globals: HashMap<String, VarType>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub inference: Inference,
+    scopes: Vec<HashMap<String, VarType>>,
+    /// Bindings seen so far per name in the current item, to look up inferred types.
+    counts: HashMap<String, usize>,
+    /// Type parameters of the generic functions being checked, innermost last.
+    generics: Vec<Generic>,
+    /// Top-level functions, defined or declared.
+    functions: HashSet<String>,
+    /// Declared functions that are not members of tahini modules, which C defines.
+    c_functions: HashSet<String>,
+    /// What `(return)` is checked against in the functions being checked, innermost last:
+    /// the context for mismatches and the return type.
+    returns: Vec<(String, VarType)>,
+    item: usize,
+}
+
+impl Checker {
+    /// Collect the type aliases and the declared types of all top-level definitions.
+    pub fn new(module: &[TopLevelStatement]) -> Self {
+        let mut checker = Checker {
+            aliases: Aliases::default(),
+            globals: HashMap::new(),
+            diagnostics: Vec::new(),
+            inference: infer::infer(module),
+            scopes: Vec::new(),
+            counts: HashMap::new(),
+            generics: Vec::new(),
+            functions: HashSet::new(),
+            c_functions: HashSet::new(),
+            returns: Vec::new(),
+            item: 0,
+        };
+
+        let mut modules = HashSet::new();
+        for statement in module {
+            match statement {
+                TopLevelStatement::TypeAlias(name, var_type) => {
+                    checker.aliases.types.insert(name.clone(), var_type.clone());
+                }
+                TopLevelStatement::Use(name, _) => {
+                    modules.insert(name.as_str());
+                }
+                _ => {}
+            }
+        }
+
+        for (item, statement) in module.iter().enumerate() {
+            checker.item = item;
+            if let TopLevelStatement::TopLevelDef(def) = statement {
+                let var_type = match &def.instruction {
+                    TopLevelDef::FnDef(fn_def) => Some(fn_type(fn_def)),
+                    TopLevelDef::Typed(var_type) => Some(var_type.clone()),
+                    TopLevelDef::Literal(literal) => {
+                        let inferred = checker.inference.globals.get(&def.name).cloned();
+                        checker.check_literal(literal, inferred.as_ref())
+                    }
+                    // Other globals may not be known yet; the module is checked in full later.
+                    TopLevelDef::Const(_) => checker.inference.globals.get(&def.name).cloned(),
+                };
+                match &def.instruction {
+                    TopLevelDef::FnDef(_) => {
+                        checker.functions.insert(def.name.clone());
+                    }
+                    TopLevelDef::Typed(VarType::Fn(..) | VarType::FnWithVarArgs(..)) => {
+                        checker.functions.insert(def.name.clone());
+                        let module = builtins::split_path(&def.name).map(|(alias, _)| alias);
+                        if !module.is_some_and(|alias| modules.contains(alias)) {
+                            checker.c_functions.insert(def.name.clone());
+                        }
+                    }
+                    _ => {}
+                }
+                if let Some(var_type) = var_type {
+                    checker.globals.insert(def.name.clone(), var_type);
+                }
+            }
+        }
+
+        checker.diagnostics.clear();
+        checker
+    }
+
+    pub fn check_module(&mut self, module: &[TopLevelStatement]) {
+        for (item, statement) in module.iter().enumerate() {
+            self.enter_item(item);
+            if let TopLevelStatement::TopLevelDef(def) = statement {
+                match &def.instruction {
+                    TopLevelDef::FnDef(fn_def) => self.check_fn(Some(&def.name), fn_def),
+                    TopLevelDef::Literal(literal) => {
+                        let inferred = self.globals.get(&def.name).cloned();
+                        self.check_literal(literal, inferred.as_ref());
+                    }
+                    TopLevelDef::Const(statement) => {
+                        let inferred = self.globals.get(&def.name).cloned();
+                        self.check_statement(statement, inferred.as_ref());
+                    }
+                    TopLevelDef::Typed(_) => {}
+                }
+            }
+        }
+    }
+
+    /// The type of `statement` in the module's global environment.
+    pub fn type_of(&mut self, statement: &Statement) -> Option<VarType> {
+        self.check_statement(statement, None)
+    }
+
+    /// The type of `statement` where `locals` are bound, as the parameters of a function are.
+    pub fn type_with(
+        &mut self,
+        statement: &Statement,
+        locals: &[(String, VarType)],
+    ) -> Option<VarType> {
+        self.with_scope(|checker| {
+            for (name, var_type) in locals {
+                checker.bind(name, var_type.clone());
+            }
+            checker.check_statement(statement, None)
+        })
+    }
+
+    fn error(&mut self, message: String) {
+        self.diagnostics.push(Diagnostic::error(self.item, message));
+    }
+
+    pub(crate) fn lookup(&self, name: &str) -> Option<VarType> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| self.globals.get(name))
+            .cloned()
+    }
+
+    pub(crate) fn lookup_local(&self, name: &str) -> Option<VarType> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    /// The inferred type of the next binding called `name` in the current item.
+    fn next_inferred(&mut self, name: &str) -> Option<VarType> {
+        let index = self.counts.entry(name.to_string()).or_insert(0);
+        let inferred = self.inference.binding(self.item, name, *index).cloned();
+        *index += 1;
+        inferred
+    }
+
+    fn bind(&mut self, name: &str, var_type: VarType) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), var_type);
+        }
+    }
+
+    /// Start checking the top-level item at index `item`. Passes that walk the module after
+    /// checking use this together with the scope helpers below to see the same environment.
+    pub(crate) fn enter_item(&mut self, item: usize) {
+        self.item = item;
+        self.counts.clear();
+        self.scopes.clear();
+    }
+
+    pub(crate) fn enter_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub(crate) fn exit_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Bind a parameter or loop variable of the current scope.
+    pub(crate) fn declare(&mut self, name: &str, var_type: Option<VarType>) {
+        let inferred = self.next_inferred(name);
+        if let Some(var_type) = inferred.or(var_type) {
+            self.bind(name, var_type);
+        }
+    }
+
+    /// Bind `name` to the value of `def name value` unless it re-binds an existing local.
+    pub(crate) fn declare_def(&mut self, name: &str, value: &Statement) {
+        if self.lookup_local(name).is_none() {
+            let inferred = self.next_inferred(name);
+            let actual = self.probe(value, inferred.as_ref());
+            if let Some(var_type) = inferred.or(actual) {
+                self.bind(name, var_type);
+            }
+        }
+    }
+
+    /// The type of `statement` without reporting diagnostics or consuming inferred bindings.
+    pub(crate) fn probe(
+        &mut self,
+        statement: &Statement,
+        expected: Option<&VarType>,
+    ) -> Option<VarType> {
+        let counts = self.counts.clone();
+        let diagnostics = self.diagnostics.len();
+        let var_type = self.check_statement(statement, expected);
+        self.counts = counts;
+        self.diagnostics.truncate(diagnostics);
+        var_type
+    }
+
+    /// [`Checker::range_element`] without reporting diagnostics or consuming inferred bindings.
+    pub(crate) fn probe_range(&mut self, range: &Range<Statement>) -> Option<VarType> {
+        let counts = self.counts.clone();
+        let diagnostics = self.diagnostics.len();
+        let element = self.range_element(range);
+        self.counts = counts;
+        self.diagnostics.truncate(diagnostics);
+        element
+    }
+
+    fn with_scope<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(HashMap::new());
+        let result = f(self);
+        self.scopes.pop();
+        result
+    }
+
+    /// Report a mismatch unless `actual` fits `expected`.
+    fn expect(&mut self, expected: &VarType, actual: &Option<VarType>, context: &str) {
+        if let Some(actual) = actual {
+            if !self.aliases.same(expected, actual) {
+                self.error(format!(
+                    "type mismatch in {}: expected `{}`, found `{}`",
+                    context, expected, actual
+                ));
+            }
+        }
+    }
+
+    fn check_fn(&mut self, name: Option<&str>, fn_def: &FnDef) {
+        let outer_generics = self.generics.len();
+        self.generics
+            .extend(fn_def.generic_types.iter().flatten().cloned());
+        self.with_scope(|checker| {
+            for (param, var_type) in &fn_def.parameters {
+                checker.next_inferred(param);
+                checker.bind(param, var_type.clone());
+            }
+
+            let expected = match fn_def.return_type {
+                VarType::Void => None,
+                ref return_type => Some(return_type.clone()),
+            };
+            let context = match name {
+                Some(name) => format!("return value of `{}`", name),
+                None => "return value of closure".to_string(),
+            };
+            checker
+                .returns
+                .push((context.clone(), fn_def.return_type.clone()));
+            let body = checker.check_statement(&fn_def.statement, expected.as_ref());
+            checker.returns.pop();
+
+            if let Some(expected) = expected {
+                match init::missing_value(&fn_def.statement) {
+                    Some(construct) => checker.error(format!(
+                        "missing {}: {} can finish without a value of type `{}`",
+                        context, construct, expected
+                    )),
+                    None if diverges(&fn_def.statement) => {}
+                    None => checker.expect(&expected, &body, &context),
+                }
+            }
+        });
+        self.generics.truncate(outer_generics);
+    }
+
+    fn check_literal(&mut self, literal: &Literal, expected: Option<&VarType>) -> Option<VarType> {
+        let expected_normal = expected.map(|e| self.aliases.normalize(e));
+        match literal {
+            Literal::Int(_) => match expected {
+                Some(e) if self.satisfies(e, "numeric") => Some(e.clone()),
+                _ => Some(VarType::Int32),
+            },
+            Literal::Float(_) => match expected {
+                Some(e) if self.satisfies(e, "float") => Some(e.clone()),
+                _ => Some(VarType::Float64),
+            },
+            Literal::Bool(_) => Some(VarType::Bool),
+            Literal::Char(_) => Some(VarType::Int8),
+            Literal::String(_) => Some(str_type()),
+            Literal::Atom(_) => Some(atom_type()),
+            Literal::Tuple(items) => {
+                let expected_items = match &expected_normal {
+                    Some(VarType::Tuple(types)) if types.len() == items.len() => {
+                        types.iter().map(Some).collect()
+                    }
+                    _ => vec![None; items.len()],
+                };
+                let types: Vec<_> = items
+                    .iter()
+                    .zip(expected_items)
+                    .map(|(item, expected)| self.check_statement(item, expected))
+                    .collect();
+                let types: Vec<_> = types.into_iter().collect::<Option<_>>()?;
+                match expected {
+                    Some(e) if self.aliases.same(e, &VarType::Tuple(types.clone())) => {
+                        Some(e.clone())
+                    }
+                    _ => Some(VarType::Tuple(types)),
+                }
+            }
+            Literal::Array(items) => {
+                let mut element = match &expected_normal {
+                    Some(VarType::ArraySized(element, _))
+                    | Some(VarType::ArrayUnsized(element)) => Some((**element).clone()),
+                    _ => None,
+                };
+                for item in items {
+                    let actual = self.check_statement(item, element.as_ref());
+                    match &element {
+                        Some(element) => self.expect(element, &actual, "array element"),
+                        None => element = actual,
+                    }
+                }
+                match element {
+                    Some(element) => Some(VarType::ArraySized(Box::new(element), items.len())),
+                    None if items.is_empty() => {
+                        self.error("cannot infer the element type of an empty array".to_string());
+                        None
+                    }
+                    None => None,
+                }
+            }
+            Literal::Data(tag, args) => self.check_data(tag, args, expected),
+            Literal::Fn(fn_def) => {
+                self.check_fn(None, fn_def);
+                Some(fn_type(fn_def))
+            }
+        }
+    }
+
+    fn check_data(
+        &mut self,
+        tag: &str,
+        args: &[Statement],
+        expected: Option<&VarType>,
+    ) -> Option<VarType> {
+        // Without an expected type, look for the unique `data` alias that declares the tag.
+        let data_type = match expected {
+            Some(expected) if self.aliases.data_variants(expected).is_some() => expected.clone(),
+            _ => {
+                let mut candidates: Vec<_> = self
+                    .aliases
+                    .types
+                    .iter()
+                    .filter(|(_, var_type)| match var_type {
+                        VarType::Data(variants) | VarType::GenericData(_, variants) => {
+                            variants.iter().any(|(t, _)| t == tag)
+                        }
+                        _ => false,
+                    })
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                candidates.sort();
+                match candidates.as_slice() {
+                    [name] if self.aliases.generic_params(name).is_some() => {
+                        return self.check_generic_data(name, tag, args);
+                    }
+                    [name] => VarType::IdentType(name.clone()),
+                    [] => {
+                        for arg in args {
+                            self.check_statement(arg, None);
+                        }
+                        if expected.is_some() {
+                            self.error(format!("no data type declares the variant `:{}`", tag));
+                        }
+                        return None;
+                    }
+                    _ => {
+                        for arg in args {
+                            self.check_statement(arg, None);
+                        }
+                        return None;
+                    }
+                }
+            }
+        };
+
+        let variants = self.aliases.data_variants(&data_type).unwrap_or_default();
+        let Some((_, fields)) = variants.iter().find(|(t, _)| t == tag) else {
+            self.error(format!("`{}` has no variant `:{}`", data_type, tag));
+            return None;
+        };
+
+        if fields.len() != args.len() {
+            self.error(format!(
+                "variant `:{}` takes {} value(s) but {} were given",
+                tag,
+                fields.len(),
+                args.len()
+            ));
+        }
+        for (arg, field) in args.iter().zip(fields) {
+            let actual = self.check_statement(arg, Some(field));
+            self.expect(field, &actual, &format!("variant `:{}`", tag));
+        }
+
+        Some(data_type)
+    }
+
+    fn check_arity(&mut self, callee: &str, params: usize, var_args: bool, args: usize) {
+        let arity_ok = if var_args {
+            args >= params
+        } else {
+            args == params
+        };
+        if !arity_ok {
+            self.error(format!(
+                "`{}` takes {}{} argument(s) but {} were given",
+                callee,
+                if var_args { "at least " } else { "" },
+                params,
+                args
+            ));
+        }
+    }
+
+    /// A variant of the generic `data` type `name`, with its type parameters bound from `args`.
+    fn check_generic_data(&mut self, name: &str, tag: &str, args: &[Statement]) -> Option<VarType> {
+        let generics = self.aliases.generic_params(name)?;
+        let fields = match &self.aliases.types[name] {
+            VarType::GenericData(_, variants) => variants
+                .iter()
+                .find(|(t, _)| t == tag)
+                .map(|(_, fields)| fields.clone())
+                .unwrap_or_default(),
+            _ => return None,
+        };
+        let bindings =
+            self.infer_type_args(&format!(":{}", tag), &generics, &fields, false, args)?;
+        let type_args = generics.iter().map(|g| bindings[&g.name].clone()).collect();
+        Some(VarType::GenericInstance(name.to_string(), type_args))
+    }
+
+    fn check_args(&mut self, callee: &str, params: &[VarType], var_args: bool, args: &[Statement]) {
+        self.check_arity(callee, params.len(), var_args, args.len());
+
+        for (i, arg) in args.iter().enumerate() {
+            match params.get(i) {
+                Some(param) => {
+                    let actual = self.check_statement(arg, Some(param));
+                    self.expect(
+                        param,
+                        &actual,
+                        &format!("argument {} of `{}`", i + 1, callee),
+                    );
+                }
+                None => {
+                    self.check_statement(arg, None);
+                }
+            }
+        }
+    }
+
+    fn check_builtin(
+        &mut self,
+        name: &str,
+        args: &[Statement],
+        expected: Option<&VarType>,
+    ) -> Option<VarType> {
+        // `!` negates one operand and `-` and `+` may take one; the rest combine two or more.
+        let arity = match name {
+            "!" | "not" => (args.len() != 1).then_some("1 operand"),
+            "-" | "+" => args.is_empty().then_some("at least 1 operand"),
+            _ => (args.len() < 2).then_some("at least 2 operands"),
+        };
+        if let Some(arity) = arity {
+            self.error(format!(
+                "`{}` takes {} but {} were given",
+                name,
+                arity,
+                args.len()
+            ));
+        }
+
+        // Operands of arithmetic and comparisons share one type; literals adopt the type of
+        // the first operand that has one of its own.
+        let is_literal = |s: &Statement| {
+            matches!(
+                s,
+                Statement::Literal(Literal::Int(_)) | Statement::Literal(Literal::Float(_))
+            )
+        };
+
+        let operand_hint = if builtins::COMPARISON.contains(&name) {
+            None
+        } else {
+            expected.cloned()
+        };
+        let mut operand = None;
+        let mut types = vec![None; args.len()];
+        for (i, arg) in args.iter().enumerate() {
+            if !is_literal(arg) {
+                types[i] = self.check_statement(arg, operand_hint.as_ref());
+                if operand.is_none() {
+                    operand = types[i].clone();
+                }
+            }
+        }
+        let operand = operand.or(operand_hint);
+        for (i, arg) in args.iter().enumerate() {
+            if is_literal(arg) {
+                types[i] = self.check_statement(arg, operand.as_ref());
+            }
+        }
+
+        if builtins::LOGICAL.contains(&name) {
+            for actual in &types {
+                self.expect(&VarType::Bool, actual, &format!("operand of `{}`", name));
+            }
+            return Some(VarType::Bool);
+        }
+
+        let Some(first) = types.iter().flatten().next().cloned() else {
+            return if builtins::COMPARISON.contains(&name) {
+                Some(VarType::Bool)
+            } else {
+                None
+            };
+        };
+        let normal = self.aliases.normalize(&first);
+
+        // Pointer arithmetic: `(+ ptr n)`.
+        if matches!(normal, VarType::Ptr(_)) && (name == "+" || name == "-") {
+            for actual in types.iter().skip(1).flatten() {
+                if !self.satisfies(actual, "integer") {
+                    self.error(format!("cannot offset a pointer by `{}`", actual));
+                }
+            }
+            return Some(first);
+        }
+
+        for actual in &types {
+            self.expect(&first, actual, &format!("operand of `{}`", name));
+        }
+
+        if builtins::COMPARISON.contains(&name) {
+            return Some(VarType::Bool);
+        }
+
+        let valid = if builtins::BITWISE.contains(&name) {
+            self.satisfies(&first, "integer")
+        } else {
+            self.satisfies(&first, "numeric")
+        };
+        if !valid {
+            self.error(format!("`{}` cannot be applied to `{}`", name, first));
+        }
+        Some(first)
+    }
+
+    fn check_call(
+        &mut self,
+        name: &str,
+        args: &[Statement],
+        expected: Option<&VarType>,
+    ) -> Option<VarType> {
+        if let Some(callee) = self.lookup(name) {
+            let c_function = self.c_functions.contains(name) && self.lookup_local(name).is_none();
+            return match self.aliases.normalize(&callee) {
+                VarType::Fn(params, ret) => {
+                    self.check_args(name, &params, false, args);
+                    if c_function {
+                        self.check_callbacks(name, &params, args);
+                    }
+                    Some(*ret)
+                }
+                VarType::FnWithVarArgs(params, ret) => {
+                    self.check_args(name, &params, true, args);
+                    if c_function {
+                        self.check_callbacks(name, &params, args);
+                    }
+                    Some(*ret)
+                }
+                VarType::GenericFn(generics, params, ret) => {
+                    let bindings = self.infer_type_args(name, &generics, &params, false, args)?;
+                    Some(substitute(&ret, &bindings))
+                }
+                VarType::GenericFnWithVarArgs(generics, params, ret) => {
+                    let bindings = self.infer_type_args(name, &generics, &params, true, args)?;
+                    Some(substitute(&ret, &bindings))
+                }
+                other => {
+                    self.error(format!("`{}` of type `{}` is not callable", name, other));
+                    None
+                }
+            };
+        }
+
+        if let Some(alias) = self.aliases.types.get(name).cloned() {
+            return self.check_constructor(name, &alias, args);
+        }
+
+        if builtins::is_builtin_fn(name) {
+            return self.check_builtin(name, args, expected);
+        }
+
+        // Imported members and unresolved names; the resolver reports the latter.
+        for arg in args {
+            self.check_statement(arg, None);
+        }
+        None
+    }
+
+    /// Report closures passed to the `fn` parameters of the C function `callee`. C takes those
+    /// as plain function pointers, which have no room for an environment, so only top-level
+    /// functions and lambdas that capture nothing can be passed.
+    fn check_callbacks(&mut self, callee: &str, params: &[VarType], args: &[Statement]) {
+        for (i, (param, arg)) in params.iter().zip(args).enumerate() {
+            if !matches!(
+                self.aliases.normalize(param),
+                VarType::Fn(..) | VarType::FnWithVarArgs(..)
+            ) {
+                continue;
+            }
+            let captured = match arg {
+                Statement::Ident(name)
+                    if self.lookup_local(name).is_none() && self.functions.contains(name) =>
+                {
+                    continue;
+                }
+                Statement::Literal(Literal::Fn(fn_def)) => {
+                    let names: Vec<_> = free_variables(fn_def, self)
+                        .iter()
+                        .map(|name| format!("`{}`", name))
+                        .collect();
+                    if names.is_empty() {
+                        continue;
+                    }
+                    format!("the variables the lambda captures ({})", names.join(", "))
+                }
+                _ => "the environment of a closure value".to_string(),
+            };
+            self.error(format!(
+                "argument {} of `{}` is a C function pointer, which has no room for {}; pass a \
+                 top-level function or a lambda that captures nothing",
+                i + 1,
+                callee,
+                captured
+            ));
+        }
+    }
+
+    fn check_constructor(
+        &mut self,
+        name: &str,
+        alias: &VarType,
+        args: &[Statement],
+    ) -> Option<VarType> {
+        if let Some((tag, values)) = variant_args(alias, args) {
+            return match alias {
+                VarType::GenericData(..) => self.check_generic_data(name, tag, values),
+                _ => self.check_data(tag, values, Some(&VarType::IdentType(name.to_string()))),
+            };
+        }
+
+        if let Some(generics) = self.aliases.generic_params(name) {
+            let params = constructor_params(alias);
+            let bindings = self.infer_type_args(name, &generics, &params, false, args)?;
+            let type_args = generics.iter().map(|g| bindings[&g.name].clone()).collect();
+            return Some(VarType::GenericInstance(name.to_string(), type_args));
+        }
+
+        let params = constructor_params(&self.aliases.normalize(alias));
+        self.check_args(name, &params, false, args);
+        Some(VarType::IdentType(name.to_string()))
+    }
+
+    /// Bind the type parameters `generics` of `callee` from the types of `args`, then check
+    /// the arguments against the instantiated parameter types.
+    fn infer_type_args(
+        &mut self,
+        callee: &str,
+        generics: &[Generic],
+        params: &[VarType],
+        var_args: bool,
+        args: &[Statement],
+    ) -> Option<HashMap<String, VarType>> {
+        let names = generic_names(generics);
+        let mut bindings = HashMap::new();
+        let mut actuals = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            let actual = match params.get(i) {
+                Some(param) if !mentions(param, &names) => self.check_statement(arg, Some(param)),
+                _ => self.check_statement(arg, None),
+            };
+            if let (Some(param), Some(actual)) = (params.get(i), &actual) {
+                self.aliases
+                    .bind_params(param, actual, &names, &mut bindings);
+            }
+            actuals.push(actual);
+        }
+
+        let unbound: Vec<_> = names
+            .iter()
+            .filter(|g| !bindings.contains_key(*g))
+            .map(|g| format!("`{}`", g))
+            .collect();
+        if !unbound.is_empty() {
+            self.error(format!(
+                "cannot infer type parameter(s) {} of `{}`; pass them explicitly as `{}<...>`",
+                unbound.join(", "),
+                callee,
+                callee
+            ));
+            return None;
+        }
+        self.check_bounds(callee, generics, &bindings);
+
+        let params: Vec<_> = params.iter().map(|p| substitute(p, &bindings)).collect();
+        self.check_arity(callee, params.len(), var_args, args.len());
+        for (i, (param, actual)) in params.iter().zip(&actuals).enumerate() {
+            self.expect(
+                param,
+                actual,
+                &format!("argument {} of `{}`", i + 1, callee),
+            );
+        }
+        Some(bindings)
+    }
+
+    fn check_generic_call(
+        &mut self,
+        name: &str,
+        type_args: &[String],
+        args: &[Statement],
+    ) -> Option<VarType> {
+        let mut parsed = Vec::new();
+        for type_arg in type_args {
+            match parse_type_arg(type_arg) {
+                Some(var_type) => parsed.push(var_type),
+                None => {
+                    self.error(format!("invalid type argument `{}`", type_arg));
+                    return None;
+                }
+            }
+        }
+
+        let (generics, params, var_args, ret) = match self.lookup(name) {
+            Some(callee) => match self.aliases.normalize(&callee) {
+                VarType::GenericFn(generics, params, ret) => (generics, params, false, *ret),
+                VarType::GenericFnWithVarArgs(generics, params, ret) => {
+                    (generics, params, true, *ret)
+                }
+                other => {
+                    self.error(format!("`{}` of type `{}` is not generic", name, other));
+                    return None;
+                }
+            },
+            None => match self.aliases.generic_params(name) {
+                Some(generics) => {
+                    let alias = self.aliases.types[name].clone();
+                    let instance = VarType::GenericInstance(name.to_string(), parsed.clone());
+                    if let Some((tag, values)) = variant_args(&alias, args) {
+                        if generics.len() == parsed.len() {
+                            let bindings =
+                                generic_names(&generics).into_iter().zip(parsed).collect();
+                            self.check_bounds(name, &generics, &bindings);
+                            return self.check_data(tag, values, Some(&instance));
+                        }
+                    }
+                    (generics, constructor_params(&alias), false, instance)
+                }
+                None if self.aliases.types.contains_key(name) => {
+                    self.error(format!("type `{}` is not generic", name));
+                    return None;
+                }
+                None => {
+                    for arg in args {
+                        self.check_statement(arg, None);
+                    }
+                    return None;
+                }
+            },
+        };
+
+        if generics.len() != parsed.len() {
+            self.error(format!(
+                "`{}` takes {} type argument(s) but {} were given",
+                name,
+                generics.len(),
+                parsed.len()
+            ));
+            return None;
+        }
+
+        let bindings: HashMap<_, _> = generic_names(&generics).into_iter().zip(parsed).collect();
+        self.check_bounds(name, &generics, &bindings);
+        let params: Vec<_> = params.iter().map(|p| substitute(p, &bindings)).collect();
+        self.check_args(name, &params, var_args, args);
+        Some(substitute(&ret, &bindings))
+    }
+
+    /// The explicit type arguments of a call to `name`, or the ones inferred from `args`, if
+    /// `name` is a generic function or type. Used by monomorphization after checking.
+    pub(crate) fn call_type_args(
+        &mut self,
+        name: &str,
+        type_args: Option<&[String]>,
+        args: &[Statement],
+    ) -> Option<Vec<VarType>> {
+        if let Some(type_args) = type_args {
+            return type_args.iter().map(|t| parse_type_arg(t)).collect();
+        }
+
+        let (generics, params) = match self.lookup(name) {
+            Some(callee) => match self.aliases.normalize(&callee) {
+                VarType::GenericFn(generics, params, _)
+                | VarType::GenericFnWithVarArgs(generics, params, _) => (generics, params),
+                _ => return None,
+            },
+            // A generic type constructor checks as the instance it builds.
+            None => {
+                let call = Statement::Call(name.to_string(), args.to_vec());
+                return match self.probe(&call, None)? {
+                    VarType::GenericInstance(_, type_args) => Some(type_args),
+                    _ => None,
+                };
+            }
+        };
+
+        let names = generic_names(&generics);
+        let mut bindings = HashMap::new();
+        for (param, arg) in params.iter().zip(args) {
+            let expected = (!mentions(param, &names)).then_some(param);
+            if let Some(actual) = self.probe(arg, expected) {
+                self.aliases
+                    .bind_params(param, &actual, &names, &mut bindings);
+            }
+        }
+        names.iter().map(|g| bindings.get(g).cloned()).collect()
+    }
+
+    /// Report type arguments that do not meet the bounds of their type parameters.
+    fn check_bounds(
+        &mut self,
+        callee: &str,
+        generics: &[Generic],
+        bindings: &HashMap<String, VarType>,
+    ) {
+        for generic in generics {
+            let (Some(bound), Some(actual)) = (&generic.bound, bindings.get(&generic.name)) else {
+                continue;
+            };
+            if !self.satisfies(actual, bound) {
+                self.error(format!(
+                    "`{}` does not satisfy the bound `{}` of type parameter `{}` of `{}`",
+                    actual, bound, generic.name, callee
+                ));
+            }
+        }
+    }
+
+    /// Whether `var_type` meets `bound`, either as a concrete type or as a type parameter in
+    /// scope whose own bound implies it.
+    fn satisfies(&self, var_type: &VarType, bound: &str) -> bool {
+        if let VarType::IdentType(name) = var_type {
+            if let Some(generic) = self.generics.iter().rev().find(|g| &g.name == name) {
+                return generic
+                    .bound
+                    .as_deref()
+                    .is_some_and(|have| implies(have, bound));
+            }
+        }
+        satisfies(&self.aliases.normalize(var_type), bound)
+    }
+
+    fn check_index(&mut self, index: &Statement, target: &Statement) -> Option<VarType> {
+        let target_type = self.check_statement(target, None)?;
+        match self.aliases.normalize(&target_type) {
+            VarType::ArraySized(element, _) | VarType::ArrayUnsized(element) => {
+                self.check_integer_index(index);
+                Some(*element)
+            }
+            VarType::Ptr(element) => {
+                self.check_integer_index(index);
+                Some(*element)
+            }
+            VarType::Tuple(types) => match index {
+                Statement::Literal(Literal::Int(i)) => match types.get(*i as usize) {
+                    Some(element) => Some(element.clone()),
+                    None => {
+                        self.error(format!(
+                            "index {} is out of bounds for `{}` with {} element(s)",
+                            i,
+                            target_type,
+                            types.len()
+                        ));
+                        None
+                    }
+                },
+                _ => {
+                    self.error(format!(
+                        "tuple `{}` must be indexed by an integer literal",
+                        target_type
+                    ));
+                    None
+                }
+            },
+            _ => {
+                self.error(format!("cannot index into `{}`", target_type));
+                None
+            }
+        }
+    }
+
+    fn check_integer_index(&mut self, index: &Statement) {
+        if let Some(actual) = self.check_statement(index, Some(&VarType::Int64)) {
+            if !self.satisfies(&actual, "integer") {
+                self.error(format!(
+                    "array index must be an integer, found `{}`",
+                    actual
+                ));
+            }
+        }
+    }
+
+    fn check_field(&mut self, field: &str, target: &Statement) -> Option<VarType> {
+        let target_type = self.check_statement(target, None)?;
+        let Some(fields) = self.aliases.struct_fields(&target_type) else {
+            self.error(format!(
+                "cannot access field `{}` on non-struct type `{}`",
+                field, target_type
+            ));
+            return None;
+        };
+        match fields.into_iter().find(|(name, _)| name == field) {
+            Some((_, var_type)) => Some(var_type),
+            None => {
+                self.error(format!("`{}` has no field `{}`", target_type, field));
+                None
+            }
+        }
+    }
+
+    /// The type of the loop variable of `(for (range x ...) ...)`.
+    pub(crate) fn range_element(&mut self, range: &Range<Statement>) -> Option<VarType> {
+        match range {
+            Range::Each(range) => {
+                let range_type = self.check_statement(range, None)?;
+                match self.aliases.normalize(&range_type) {
+                    VarType::ArraySized(element, _) => Some(*element),
+                    VarType::ArrayUnsized(_) => {
+                        self.error(format!(
+                            "cannot iterate over `{}`: its length is not known; use `(range x :slice xs start end)`",
+                            range_type
+                        ));
+                        None
+                    }
+                    _ if self.satisfies(&range_type, "integer") => Some(range_type),
+                    _ => {
+                        self.error(format!("cannot iterate over `{}`", range_type));
+                        None
+                    }
+                }
+            }
+            Range::Bounds(..) => {
+                // Integer literals take the type of the first bound that is not a literal.
+                let parts = range.parts();
+                let anchor = parts
+                    .iter()
+                    .position(|part| !matches!(part, Statement::Literal(Literal::Int(_))))
+                    .unwrap_or(0);
+                let bound_type = self.check_statement(parts[anchor], None)?;
+                if !self.satisfies(&bound_type, "integer") {
+                    self.error(format!(
+                        "range bounds must be integers, found `{}`",
+                        bound_type
+                    ));
+                    return None;
+                }
+                for (i, part) in parts.into_iter().enumerate() {
+                    if i != anchor {
+                        let actual = self.check_statement(part, Some(&bound_type));
+                        self.expect(&bound_type, &actual, "range bound");
+                    }
+                }
+                Some(bound_type)
+            }
+            Range::Slice(array, start, end) => {
+                let array_type = self.check_statement(array, None);
+                self.check_integer_index(start);
+                self.check_integer_index(end);
+                let array_type = array_type?;
+                match self.aliases.normalize(&array_type) {
+                    VarType::ArraySized(element, _)
+                    | VarType::ArrayUnsized(element)
+                    | VarType::Ptr(element) => Some(*element),
+                    _ => {
+                        self.error(format!("cannot slice `{}`", array_type));
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_condition(&mut self, condition: &Statement, context: &str) {
+        let actual = self.check_statement(condition, Some(&VarType::Bool));
+        self.expect(&VarType::Bool, &actual, context);
+    }
+
+    pub(crate) fn check_statement(
+        &mut self,
+        statement: &Statement,
+        expected: Option<&VarType>,
+    ) -> Option<VarType> {
+        match statement {
+            Statement::Ident(name) => self.lookup(name),
+            Statement::Literal(literal) => self.check_literal(literal, expected),
+            Statement::DoBlock(statements) => self.with_scope(|checker| {
+                let mut last = Some(VarType::Void);
+                for (i, statement) in statements.iter().enumerate() {
+                    let expected = if i + 1 == statements.len() {
+                        expected
+                    } else {
+                        None
+                    };
+                    last = checker.check_statement(statement, expected);
+                }
+                last
+            }),
+            Statement::Call(name, args) => self.check_call(name, args, expected),
+            Statement::GenericCall(name, type_args, args) => {
+                self.check_generic_call(name, type_args, args)
+            }
+            Statement::DefVar(def) => {
+                match self.lookup_local(&def.name) {
+                    Some(existing) => {
+                        let actual = self.check_statement(&def.instruction, Some(&existing));
+                        self.expect(&existing, &actual, &format!("assignment to `{}`", def.name));
+                    }
+                    None => {
+                        let inferred = self.next_inferred(&def.name);
+                        let actual = self.check_statement(&def.instruction, inferred.as_ref());
+                        if let Some(var_type) = inferred.or(actual) {
+                            self.bind(&def.name, var_type);
+                        }
+                    }
+                }
+                Some(VarType::Void)
+            }
+            Statement::Declare(name, var_type) => {
+                self.next_inferred(name);
+                self.bind(name, var_type.clone());
+                Some(VarType::Void)
+            }
+            Statement::If(condition, then) => {
+                self.check_condition(condition, "`if` condition");
+                self.with_scope(|checker| checker.check_statement(then, None));
+                Some(VarType::Void)
+            }
+            Statement::IfElse(condition, then, otherwise) => {
+                self.check_condition(condition, "`if` condition");
+                let then_type = self.with_scope(|checker| checker.check_statement(then, expected));
+                let hint = match diverges(then) {
+                    true => expected.cloned(),
+                    false => expected.cloned().or_else(|| then_type.clone()),
+                };
+                let else_type =
+                    self.with_scope(|checker| checker.check_statement(otherwise, hint.as_ref()));
+                match (then_type, else_type) {
+                    // A branch that jumps away, like `(break)`, takes the type of the other one.
+                    (then_type, else_type) if diverges(then) => else_type.or(then_type),
+                    (then_type, else_type) if diverges(otherwise) => then_type.or(else_type),
+                    (Some(then_type), Some(else_type)) => {
+                        if !self.aliases.same(&then_type, &else_type) {
+                            self.error(format!(
+                                "`if` branches have different types: `{}` and `{}`",
+                                then_type, else_type
+                            ));
+                        }
+                        Some(then_type)
+                    }
+                    (then_type, else_type) => then_type.or(else_type),
+                }
+            }
+            Statement::For(condition, body) => {
+                self.check_condition(condition, "`for` condition");
+                self.with_scope(|checker| checker.check_statement(body, None));
+                Some(VarType::Void)
+            }
+            Statement::ForRange(name, range, body) => {
+                let element = self.range_element(range);
+                self.with_scope(|checker| {
+                    checker.next_inferred(name);
+                    if let Some(element) = element {
+                        checker.bind(name, element);
+                    }
+                    checker.check_statement(body, None);
+                });
+                Some(VarType::Void)
+            }
+            Statement::Break | Statement::Continue => Some(VarType::Void),
+            Statement::Return(value) => {
+                let (context, return_type) = self.returns.last().cloned()?;
+                let expected = (return_type != VarType::Void).then_some(&return_type);
+                match (value, expected) {
+                    (Some(value), expected) => {
+                        let actual = self.check_statement(value, expected);
+                        if let Some(expected) = expected {
+                            self.expect(expected, &actual, &context);
+                        }
+                    }
+                    (None, Some(expected)) => self.error(format!(
+                        "missing {}: `(return)` has no value of type `{}`",
+                        context, expected
+                    )),
+                    (None, None) => {}
+                }
+                Some(VarType::Void)
+            }
+            Statement::GetField(field, target) => self.check_field(field, target),
+            Statement::GetIndexed(index, target) => self.check_index(index, target),
+            Statement::SetField(field, target, value) => {
+                let field_type = self.check_field(field, target);
+                let actual = self.check_statement(value, field_type.as_ref());
+                if let Some(field_type) = field_type {
+                    self.expect(&field_type, &actual, &format!("field `{}`", field));
+                }
+                Some(VarType::Void)
+            }
+            Statement::SetIndexed(index, target, value) => {
+                let element = self.check_index(index, target);
+                let actual = self.check_statement(value, element.as_ref());
+                if let Some(element) = element {
+                    self.expect(&element, &actual, "indexed assignment");
+                }
+                Some(VarType::Void)
+            }
+        }
+    }
+}
+
+/// The parameter types of the constructor of the type alias `alias`.
+fn constructor_params(alias: &VarType) -> Vec<VarType> {
+    match alias {
+        VarType::Struct(fields) | VarType::GenericStruct(_, fields) => {
+            fields.iter().map(|(_, t)| t.clone()).collect()
+        }
+        VarType::Tuple(types) | VarType::GenericTuple(_, types) => types.clone(),
+        other => vec![other.clone()],
+    }
+}
+
+/// The tag and values of a `data` constructor call such as `(option :some 1)`.
+fn variant_args<'a>(alias: &VarType, args: &'a [Statement]) -> Option<(&'a str, &'a [Statement])> {
+    match (alias, args.split_first()) {
+        (
+            VarType::Data(_) | VarType::GenericData(..),
+            Some((Statement::Literal(Literal::Atom(tag)), values)),
+        ) => Some((tag, values)),
+        _ => None,
+    }
+}
+
+/// Parse a type argument of a generic call, e.g. `i32` in `(id<i32> 1)`.
+fn parse_type_arg(source: &str) -> Option<VarType> {
+    var_type().parse(source).into_output()
+}
+
+/// The type of a function value defined by `fn_def`.
+pub fn fn_type(fn_def: &FnDef) -> VarType {
+    let params = fn_def.parameters.iter().map(|(_, t)| t.clone()).collect();
+    let ret = Box::new(fn_def.return_type.clone());
+    match &fn_def.generic_types {
+        Some(generics) => VarType::GenericFn(generics.clone(), params, ret),
+        None => VarType::Fn(params, ret),
+    }
+}
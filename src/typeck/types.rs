@@ -0,0 +1,430 @@
+//! Helpers for comparing and classifying `VarType`s.
+
+use crate::ast::{generic_names, Generic, VarType};
+use std::collections::HashMap;
+
+/// Nominal type names are compared structurally once their aliases are expanded; recursive
+/// types (e.g. a `tree` that contains `tree`) stop expanding at this depth.
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+pub fn str_type() -> VarType {
+    VarType::IdentType("str".to_string())
+}
+
+pub fn atom_type() -> VarType {
+    VarType::IdentType("atom".to_string())
+}
+
+pub fn is_integer(var_type: &VarType) -> bool {
+    matches!(
+        var_type,
+        VarType::Int8
+            | VarType::Int16
+            | VarType::Int32
+            | VarType::Int64
+            | VarType::Int128
+            | VarType::UInt8
+            | VarType::UInt16
+            | VarType::UInt32
+            | VarType::UInt64
+            | VarType::UInt128
+    )
+}
+
+pub fn is_float(var_type: &VarType) -> bool {
+    matches!(
+        var_type,
+        VarType::Float16 | VarType::Float32 | VarType::Float64 | VarType::Float128
+    )
+}
+
+pub fn is_numeric(var_type: &VarType) -> bool {
+    is_integer(var_type) || is_float(var_type)
+}
+
+/// Whether the concrete type `var_type` meets the bound `bound` of a type parameter.
+pub fn satisfies(var_type: &VarType, bound: &str) -> bool {
+    match bound {
+        "numeric" => is_numeric(var_type),
+        "integer" => is_integer(var_type),
+        "float" => is_float(var_type),
+        _ => false,
+    }
+}
+
+/// Whether a type parameter bounded by `have` meets the bound `want`.
+pub fn implies(have: &str, want: &str) -> bool {
+    have == want || (want == "numeric" && (have == "integer" || have == "float"))
+}
+
+/// Type aliases in scope, keyed by name.
+#[derive(Clone, Debug, Default)]
+pub struct Aliases {
+    pub types: HashMap<String, VarType>,
+}
+
+impl Aliases {
+    /// Expand aliases at the head of `var_type` until it is no longer a named alias.
+    /// `str` is the builtin alias for `(ptr i8)`.
+    pub fn normalize(&self, var_type: &VarType) -> VarType {
+        let mut current = var_type.clone();
+        for _ in 0..MAX_EXPANSION_DEPTH {
+            match &current {
+                VarType::IdentType(name) if name == "str" => {
+                    return VarType::Ptr(Box::new(VarType::Int8));
+                }
+                VarType::IdentType(name) => match self.types.get(name) {
+                    Some(target) => current = target.clone(),
+                    None => return current,
+                },
+                VarType::GenericInstance(name, args) => match self.instantiate(name, args) {
+                    Some(target) => current = target,
+                    None => return current,
+                },
+                _ => return current,
+            }
+        }
+        current
+    }
+
+    pub fn same(&self, a: &VarType, b: &VarType) -> bool {
+        self.same_at(a, b, 0)
+    }
+
+    fn same_at(&self, a: &VarType, b: &VarType, depth: usize) -> bool {
+        if a == b {
+            return true;
+        }
+        if depth > MAX_EXPANSION_DEPTH {
+            return false;
+        }
+
+        let depth = depth + 1;
+        let a = self.normalize(a);
+        let b = self.normalize(b);
+        let all = |xs: &[VarType], ys: &[VarType]| {
+            xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| self.same_at(x, y, depth))
+        };
+
+        match (&a, &b) {
+            // `(ptr void)` converts to and from every pointer, as in C.
+            (VarType::Ptr(x), VarType::Ptr(y)) => {
+                **x == VarType::Void || **y == VarType::Void || self.same_at(x, y, depth)
+            }
+            (VarType::ArraySized(x, n), VarType::ArraySized(y, m)) => {
+                n == m && self.same_at(x, y, depth)
+            }
+            (VarType::ArrayUnsized(x), VarType::ArrayUnsized(y))
+            | (VarType::ArrayUnsized(x), VarType::ArraySized(y, _)) => self.same_at(x, y, depth),
+            (VarType::Tuple(xs), VarType::Tuple(ys)) => all(xs, ys),
+            (VarType::Struct(xs), VarType::Struct(ys)) => {
+                xs.len() == ys.len()
+                    && xs
+                        .iter()
+                        .zip(ys)
+                        .all(|((n, x), (m, y))| n == m && self.same_at(x, y, depth))
+            }
+            (VarType::Data(xs), VarType::Data(ys)) => {
+                xs.len() == ys.len()
+                    && xs
+                        .iter()
+                        .zip(ys)
+                        .all(|((n, x), (m, y))| n == m && all(x, y))
+            }
+            (VarType::Fn(xs, x), VarType::Fn(ys, y))
+            | (VarType::FnWithVarArgs(xs, x), VarType::FnWithVarArgs(ys, y)) => {
+                all(xs, ys) && self.same_at(x, y, depth)
+            }
+            _ => a == b,
+        }
+    }
+
+    pub fn struct_fields(&self, var_type: &VarType) -> Option<Vec<(String, VarType)>> {
+        match self.normalize(var_type) {
+            VarType::Struct(fields) => Some(fields),
+            VarType::Ptr(inner) => match self.normalize(&inner) {
+                VarType::Struct(fields) => Some(fields),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The `data` variants of `var_type`, if it is (an alias of) a `data` type.
+    pub fn data_variants(&self, var_type: &VarType) -> Option<Vec<(String, Vec<VarType>)>> {
+        match self.normalize(var_type) {
+            VarType::Data(variants) => Some(variants),
+            _ => None,
+        }
+    }
+}
+
+/// Replace the type parameters in `var_type` by their bindings in `substitution`.
+pub fn substitute(var_type: &VarType, substitution: &HashMap<String, VarType>) -> VarType {
+    let sub = |t: &VarType| substitute(t, substitution);
+    let subs = |ts: &[VarType]| ts.iter().map(sub).collect::<Vec<_>>();
+    // Nested generic binders shadow the outer parameters they redeclare.
+    let inner = |generics: &[Generic]| {
+        let mut inner = substitution.clone();
+        for generic in generics {
+            inner.remove(&generic.name);
+        }
+        inner
+    };
+
+    match var_type {
+        VarType::IdentType(name) => substitution
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| var_type.clone()),
+        VarType::GenericPtr(name) => match substitution.get(name) {
+            Some(t) => VarType::Ptr(Box::new(t.clone())),
+            None => var_type.clone(),
+        },
+        VarType::GenericArraySized(name, size) => match substitution.get(name) {
+            Some(t) => VarType::ArraySized(Box::new(t.clone()), *size),
+            None => var_type.clone(),
+        },
+        VarType::GenericArrayUnsized(name) => match substitution.get(name) {
+            Some(t) => VarType::ArrayUnsized(Box::new(t.clone())),
+            None => var_type.clone(),
+        },
+        VarType::ArraySized(t, size) => VarType::ArraySized(Box::new(sub(t)), *size),
+        VarType::ArrayUnsized(t) => VarType::ArrayUnsized(Box::new(sub(t))),
+        VarType::Ptr(t) => VarType::Ptr(Box::new(sub(t))),
+        VarType::Tuple(ts) => VarType::Tuple(subs(ts)),
+        VarType::Struct(fields) => {
+            VarType::Struct(fields.iter().map(|(n, t)| (n.clone(), sub(t))).collect())
+        }
+        VarType::Data(variants) => VarType::Data(
+            variants
+                .iter()
+                .map(|(n, ts)| (n.clone(), subs(ts)))
+                .collect(),
+        ),
+        VarType::Fn(params, ret) => VarType::Fn(subs(params), Box::new(sub(ret))),
+        VarType::FnWithVarArgs(params, ret) => {
+            VarType::FnWithVarArgs(subs(params), Box::new(sub(ret)))
+        }
+        VarType::GenericInstance(name, args) => VarType::GenericInstance(name.clone(), subs(args)),
+        VarType::GenericTuple(generics, ts) => {
+            let inner = inner(generics);
+            VarType::GenericTuple(
+                generics.clone(),
+                ts.iter().map(|t| substitute(t, &inner)).collect(),
+            )
+        }
+        VarType::GenericStruct(generics, fields) => {
+            let inner = inner(generics);
+            VarType::GenericStruct(
+                generics.clone(),
+                fields
+                    .iter()
+                    .map(|(n, t)| (n.clone(), substitute(t, &inner)))
+                    .collect(),
+            )
+        }
+        VarType::GenericData(generics, variants) => {
+            let inner = inner(generics);
+            VarType::GenericData(
+                generics.clone(),
+                variants
+                    .iter()
+                    .map(|(n, ts)| {
+                        (
+                            n.clone(),
+                            ts.iter().map(|t| substitute(t, &inner)).collect(),
+                        )
+                    })
+                    .collect(),
+            )
+        }
+        VarType::GenericFn(generics, params, ret) => {
+            let inner = inner(generics);
+            VarType::GenericFn(
+                generics.clone(),
+                params.iter().map(|t| substitute(t, &inner)).collect(),
+                Box::new(substitute(ret, &inner)),
+            )
+        }
+        VarType::GenericFnWithVarArgs(generics, params, ret) => {
+            let inner = inner(generics);
+            VarType::GenericFnWithVarArgs(
+                generics.clone(),
+                params.iter().map(|t| substitute(t, &inner)).collect(),
+                Box::new(substitute(ret, &inner)),
+            )
+        }
+        _ => var_type.clone(),
+    }
+}
+
+/// Whether `var_type` mentions one of the type parameters `params`.
+pub fn mentions(var_type: &VarType, params: &[String]) -> bool {
+    let any = |ts: &[VarType]| ts.iter().any(|t| mentions(t, params));
+    match var_type {
+        VarType::IdentType(name)
+        | VarType::GenericPtr(name)
+        | VarType::GenericArraySized(name, _)
+        | VarType::GenericArrayUnsized(name) => params.contains(name),
+        VarType::ArraySized(t, _) | VarType::ArrayUnsized(t) | VarType::Ptr(t) => {
+            mentions(t, params)
+        }
+        VarType::Tuple(ts) | VarType::GenericTuple(_, ts) | VarType::GenericInstance(_, ts) => {
+            any(ts)
+        }
+        VarType::Struct(fields) | VarType::GenericStruct(_, fields) => {
+            fields.iter().any(|(_, t)| mentions(t, params))
+        }
+        VarType::Data(variants) | VarType::GenericData(_, variants) => {
+            variants.iter().any(|(_, ts)| any(ts))
+        }
+        VarType::Fn(ps, r)
+        | VarType::FnWithVarArgs(ps, r)
+        | VarType::GenericFn(_, ps, r)
+        | VarType::GenericFnWithVarArgs(_, ps, r) => any(ps) || mentions(r, params),
+        _ => false,
+    }
+}
+
+impl Aliases {
+    /// Bind the type parameters `params` by matching `pattern` against `actual`.
+    /// Parameters that are already bound keep their first binding.
+    pub fn bind_params(
+        &self,
+        pattern: &VarType,
+        actual: &VarType,
+        params: &[String],
+        bindings: &mut HashMap<String, VarType>,
+    ) {
+        let bind = |name: &String, t: VarType, bindings: &mut HashMap<String, VarType>| {
+            if params.contains(name) && !bindings.contains_key(name) {
+                bindings.insert(name.clone(), t);
+            }
+        };
+
+        match pattern {
+            VarType::IdentType(name) if params.contains(name) => {
+                bind(name, actual.clone(), bindings);
+                return;
+            }
+            VarType::GenericInstance(name, args) => {
+                if let VarType::GenericInstance(other, actual_args) = actual {
+                    if name == other {
+                        for (p, a) in args.iter().zip(actual_args) {
+                            self.bind_params(p, a, params, bindings);
+                        }
+                    }
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        let actual = self.normalize(actual);
+        match (pattern, &actual) {
+            (VarType::GenericPtr(name), VarType::Ptr(t))
+            | (VarType::GenericArraySized(name, _), VarType::ArraySized(t, _))
+            | (VarType::GenericArrayUnsized(name), VarType::ArrayUnsized(t))
+            | (VarType::GenericArrayUnsized(name), VarType::ArraySized(t, _)) => {
+                bind(name, (**t).clone(), bindings)
+            }
+            (VarType::Ptr(p), VarType::Ptr(a))
+            | (VarType::ArraySized(p, _), VarType::ArraySized(a, _))
+            | (VarType::ArrayUnsized(p), VarType::ArrayUnsized(a))
+            | (VarType::ArrayUnsized(p), VarType::ArraySized(a, _)) => {
+                self.bind_params(p, a, params, bindings)
+            }
+            (VarType::Tuple(ps), VarType::Tuple(as_)) => {
+                for (p, a) in ps.iter().zip(as_) {
+                    self.bind_params(p, a, params, bindings);
+                }
+            }
+            (VarType::Fn(ps, p), VarType::Fn(as_, a))
+            | (VarType::FnWithVarArgs(ps, p), VarType::FnWithVarArgs(as_, a)) => {
+                for (p, a) in ps.iter().zip(as_) {
+                    self.bind_params(p, a, params, bindings);
+                }
+                self.bind_params(p, a, params, bindings);
+            }
+            (VarType::Struct(ps), VarType::Struct(as_)) => {
+                for ((_, p), (_, a)) in ps.iter().zip(as_) {
+                    self.bind_params(p, a, params, bindings);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The generic parameters of the alias `name`, if it names a generic type.
+    pub fn generic_params(&self, name: &str) -> Option<Vec<Generic>> {
+        match self.types.get(name)? {
+            VarType::GenericStruct(params, _)
+            | VarType::GenericTuple(params, _)
+            | VarType::GenericData(params, _) => Some(params.clone()),
+            _ => None,
+        }
+    }
+
+    /// Instantiate the generic alias `name` with `args`.
+    pub fn instantiate(&self, name: &str, args: &[VarType]) -> Option<VarType> {
+        let (params, body) = match self.types.get(name)? {
+            VarType::GenericStruct(params, fields) => (params, VarType::Struct(fields.clone())),
+            VarType::GenericTuple(params, types) => (params, VarType::Tuple(types.clone())),
+            VarType::GenericData(params, variants) => (params, VarType::Data(variants.clone())),
+            _ => return None,
+        };
+        if params.len() != args.len() {
+            return None;
+        }
+        let substitution = generic_names(params)
+            .into_iter()
+            .zip(args.iter().cloned())
+            .collect();
+        Some(substitute(&body, &substitution))
+    }
+}
+
+/// A stable name for `name` instantiated with `args`, e.g. `pair$i32$ptr.u8`. The separators
+/// cannot appear in tahini identifiers, so mangled names never collide with user names.
+pub fn mangle(name: &str, args: &[VarType]) -> String {
+    let mut mangled = name.to_string();
+    for arg in args {
+        mangled.push('$');
+        mangled.push_str(&mangle_type(arg));
+    }
+    mangled
+}
+
+fn mangle_type(var_type: &VarType) -> String {
+    let list = |ts: &[VarType]| ts.iter().map(mangle_type).collect::<Vec<_>>().join(".");
+    match var_type {
+        VarType::IdentType(name) => name.clone(),
+        VarType::ArraySized(t, size) => format!("arr{}.{}", size, mangle_type(t)),
+        VarType::ArrayUnsized(t) => format!("arr.{}", mangle_type(t)),
+        VarType::Ptr(t) => format!("ptr.{}", mangle_type(t)),
+        VarType::Tuple(ts) => format!("tup{}.{}", ts.len(), list(ts)),
+        VarType::Fn(ps, r) => format!("fn{}.{}.{}", ps.len(), list(ps), mangle_type(r)),
+        VarType::FnWithVarArgs(ps, r) => format!("fnv{}.{}.{}", ps.len(), list(ps), mangle_type(r)),
+        VarType::Struct(fields) => format!(
+            "struct{}.{}",
+            fields.len(),
+            fields
+                .iter()
+                .map(|(n, t)| format!("{}.{}", n, mangle_type(t)))
+                .collect::<Vec<_>>()
+                .join(".")
+        ),
+        VarType::Data(variants) => format!(
+            "data{}.{}",
+            variants.len(),
+            variants
+                .iter()
+                .map(|(n, ts)| format!("{}{}.{}", n, ts.len(), list(ts)))
+                .collect::<Vec<_>>()
+                .join(".")
+        ),
+        VarType::GenericInstance(name, args) => format!("({})", mangle(name, args)),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,603 @@
+//! Traversals over the AST of [`crate::ast`].
+//!
+//! [`Visitor`] and [`VisitorMut`] walk a tree by reference, [`Fold`] consumes it and builds a
+//! new one. Every method defaults to visiting the node's children through the matching `walk_*`,
+//! `walk_mut_*` or `noop_fold_*` function, so a pass overrides only the nodes it cares about
+//! and calls the default function to keep descending.
+
+use crate::ast::{
+    DefVar, FnDef, Generic, Literal, Statement, TopLevelDef, TopLevelStatement, VarType,
+};
+
+pub trait Visitor {
+    fn visit_top_level(&mut self, statement: &TopLevelStatement) {
+        walk_top_level(self, statement)
+    }
+
+    fn visit_top_level_def(&mut self, def: &TopLevelDef) {
+        walk_top_level_def(self, def)
+    }
+
+    fn visit_fn_def(&mut self, fn_def: &FnDef) {
+        walk_fn_def(self, fn_def)
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement)
+    }
+
+    fn visit_literal(&mut self, literal: &Literal) {
+        walk_literal(self, literal)
+    }
+
+    fn visit_var_type(&mut self, var_type: &VarType) {
+        walk_var_type(self, var_type)
+    }
+
+    fn visit_generic(&mut self, _generic: &Generic) {}
+}
+
+pub fn walk_module<V: Visitor + ?Sized>(visitor: &mut V, module: &[TopLevelStatement]) {
+    for statement in module {
+        visitor.visit_top_level(statement);
+    }
+}
+
+pub fn walk_top_level<V: Visitor + ?Sized>(visitor: &mut V, statement: &TopLevelStatement) {
+    match statement {
+        TopLevelStatement::TypeAlias(_, var_type) => visitor.visit_var_type(var_type),
+        TopLevelStatement::TopLevelDef(def) => visitor.visit_top_level_def(&def.instruction),
+        TopLevelStatement::Use(..)
+        | TopLevelStatement::UseHeader(..)
+        | TopLevelStatement::ExportAll()
+        | TopLevelStatement::Export(_)
+        | TopLevelStatement::Allow(_) => {}
+    }
+}
+
+pub fn walk_top_level_def<V: Visitor + ?Sized>(visitor: &mut V, def: &TopLevelDef) {
+    match def {
+        TopLevelDef::Literal(literal) => visitor.visit_literal(literal),
+        TopLevelDef::Typed(var_type) => visitor.visit_var_type(var_type),
+        TopLevelDef::FnDef(fn_def) => visitor.visit_fn_def(fn_def),
+        TopLevelDef::Const(statement) => visitor.visit_statement(statement),
+    }
+}
+
+pub fn walk_fn_def<V: Visitor + ?Sized>(visitor: &mut V, fn_def: &FnDef) {
+    for generic in fn_def.generic_types.iter().flatten() {
+        visitor.visit_generic(generic);
+    }
+    for (_, var_type) in &fn_def.parameters {
+        visitor.visit_var_type(var_type);
+    }
+    visitor.visit_var_type(&fn_def.return_type);
+    visitor.visit_statement(&fn_def.statement);
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match statement {
+        Statement::Ident(_) | Statement::Break | Statement::Continue => {}
+        Statement::Literal(literal) => visitor.visit_literal(literal),
+        Statement::DoBlock(statements)
+        | Statement::Call(_, statements)
+        | Statement::GenericCall(_, _, statements) => {
+            for statement in statements {
+                visitor.visit_statement(statement);
+            }
+        }
+        Statement::DefVar(def) => visitor.visit_statement(&def.instruction),
+        Statement::Declare(_, var_type) => visitor.visit_var_type(var_type),
+        Statement::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_statement(value);
+            }
+        }
+        Statement::If(condition, then) | Statement::For(condition, then) => {
+            visitor.visit_statement(condition);
+            visitor.visit_statement(then);
+        }
+        Statement::IfElse(condition, then, otherwise) => {
+            visitor.visit_statement(condition);
+            visitor.visit_statement(then);
+            visitor.visit_statement(otherwise);
+        }
+        Statement::ForRange(_, range, body) => {
+            for part in range.parts() {
+                visitor.visit_statement(part);
+            }
+            visitor.visit_statement(body);
+        }
+        Statement::GetField(_, target) => visitor.visit_statement(target),
+        Statement::GetIndexed(index, target) => {
+            visitor.visit_statement(index);
+            visitor.visit_statement(target);
+        }
+        Statement::SetField(_, target, value) => {
+            visitor.visit_statement(target);
+            visitor.visit_statement(value);
+        }
+        Statement::SetIndexed(index, target, value) => {
+            visitor.visit_statement(index);
+            visitor.visit_statement(target);
+            visitor.visit_statement(value);
+        }
+    }
+}
+
+pub fn walk_literal<V: Visitor + ?Sized>(visitor: &mut V, literal: &Literal) {
+    match literal {
+        Literal::Tuple(items) | Literal::Data(_, items) | Literal::Array(items) => {
+            for item in items {
+                visitor.visit_statement(item);
+            }
+        }
+        Literal::Fn(fn_def) => visitor.visit_fn_def(fn_def),
+        Literal::Int(_)
+        | Literal::Float(_)
+        | Literal::Bool(_)
+        | Literal::Char(_)
+        | Literal::String(_)
+        | Literal::Atom(_) => {}
+    }
+}
+
+pub fn walk_var_type<V: Visitor + ?Sized>(visitor: &mut V, var_type: &VarType) {
+    let (generics, children) = type_children(var_type);
+    for generic in generics {
+        visitor.visit_generic(generic);
+    }
+    for child in children {
+        visitor.visit_var_type(child);
+    }
+    if let VarType::ArrayConstSized(_, size) = var_type {
+        visitor.visit_statement(size);
+    }
+}
+
+/// The type parameters a type declares and the types nested directly in it.
+fn type_children(var_type: &VarType) -> (&[Generic], Vec<&VarType>) {
+    match var_type {
+        VarType::ArraySized(inner, _)
+        | VarType::ArrayConstSized(inner, _)
+        | VarType::ArrayUnsized(inner)
+        | VarType::Ptr(inner) => (&[], vec![inner]),
+        VarType::Data(variants) => (&[], variants.iter().flat_map(|(_, ts)| ts).collect()),
+        VarType::GenericData(generics, variants) => {
+            (generics, variants.iter().flat_map(|(_, ts)| ts).collect())
+        }
+        VarType::Tuple(types) | VarType::GenericInstance(_, types) => (&[], types.iter().collect()),
+        VarType::GenericTuple(generics, types) => (generics, types.iter().collect()),
+        VarType::Struct(fields) => (&[], fields.iter().map(|(_, t)| t).collect()),
+        VarType::GenericStruct(generics, fields) => {
+            (generics, fields.iter().map(|(_, t)| t).collect())
+        }
+        VarType::Fn(params, ret) | VarType::FnWithVarArgs(params, ret) => {
+            (&[], params.iter().chain([&**ret]).collect())
+        }
+        VarType::GenericFn(generics, params, ret)
+        | VarType::GenericFnWithVarArgs(generics, params, ret) => {
+            (generics, params.iter().chain([&**ret]).collect())
+        }
+        _ => (&[], Vec::new()),
+    }
+}
+
+pub trait VisitorMut {
+    fn visit_top_level(&mut self, statement: &mut TopLevelStatement) {
+        walk_mut_top_level(self, statement)
+    }
+
+    fn visit_top_level_def(&mut self, def: &mut TopLevelDef) {
+        walk_mut_top_level_def(self, def)
+    }
+
+    fn visit_fn_def(&mut self, fn_def: &mut FnDef) {
+        walk_mut_fn_def(self, fn_def)
+    }
+
+    fn visit_statement(&mut self, statement: &mut Statement) {
+        walk_mut_statement(self, statement)
+    }
+
+    fn visit_literal(&mut self, literal: &mut Literal) {
+        walk_mut_literal(self, literal)
+    }
+
+    fn visit_var_type(&mut self, var_type: &mut VarType) {
+        walk_mut_var_type(self, var_type)
+    }
+
+    fn visit_generic(&mut self, _generic: &mut Generic) {}
+}
+
+pub fn walk_mut_module<V: VisitorMut + ?Sized>(visitor: &mut V, module: &mut [TopLevelStatement]) {
+    for statement in module {
+        visitor.visit_top_level(statement);
+    }
+}
+
+pub fn walk_mut_top_level<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    statement: &mut TopLevelStatement,
+) {
+    match statement {
+        TopLevelStatement::TypeAlias(_, var_type) => visitor.visit_var_type(var_type),
+        TopLevelStatement::TopLevelDef(def) => visitor.visit_top_level_def(&mut def.instruction),
+        TopLevelStatement::Use(..)
+        | TopLevelStatement::UseHeader(..)
+        | TopLevelStatement::ExportAll()
+        | TopLevelStatement::Export(_)
+        | TopLevelStatement::Allow(_) => {}
+    }
+}
+
+pub fn walk_mut_top_level_def<V: VisitorMut + ?Sized>(visitor: &mut V, def: &mut TopLevelDef) {
+    match def {
+        TopLevelDef::Literal(literal) => visitor.visit_literal(literal),
+        TopLevelDef::Typed(var_type) => visitor.visit_var_type(var_type),
+        TopLevelDef::FnDef(fn_def) => visitor.visit_fn_def(fn_def),
+        TopLevelDef::Const(statement) => visitor.visit_statement(statement),
+    }
+}
+
+pub fn walk_mut_fn_def<V: VisitorMut + ?Sized>(visitor: &mut V, fn_def: &mut FnDef) {
+    for generic in fn_def.generic_types.iter_mut().flatten() {
+        visitor.visit_generic(generic);
+    }
+    for (_, var_type) in &mut fn_def.parameters {
+        visitor.visit_var_type(var_type);
+    }
+    visitor.visit_var_type(&mut fn_def.return_type);
+    visitor.visit_statement(&mut fn_def.statement);
+}
+
+pub fn walk_mut_statement<V: VisitorMut + ?Sized>(visitor: &mut V, statement: &mut Statement) {
+    match statement {
+        Statement::Ident(_) | Statement::Break | Statement::Continue => {}
+        Statement::Literal(literal) => visitor.visit_literal(literal),
+        Statement::DoBlock(statements)
+        | Statement::Call(_, statements)
+        | Statement::GenericCall(_, _, statements) => {
+            for statement in statements {
+                visitor.visit_statement(statement);
+            }
+        }
+        Statement::DefVar(def) => visitor.visit_statement(&mut def.instruction),
+        Statement::Declare(_, var_type) => visitor.visit_var_type(var_type),
+        Statement::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_statement(value);
+            }
+        }
+        Statement::If(condition, then) | Statement::For(condition, then) => {
+            visitor.visit_statement(condition);
+            visitor.visit_statement(then);
+        }
+        Statement::IfElse(condition, then, otherwise) => {
+            visitor.visit_statement(condition);
+            visitor.visit_statement(then);
+            visitor.visit_statement(otherwise);
+        }
+        Statement::ForRange(_, range, body) => {
+            for part in range.parts_mut() {
+                visitor.visit_statement(part);
+            }
+            visitor.visit_statement(body);
+        }
+        Statement::GetField(_, target) => visitor.visit_statement(target),
+        Statement::GetIndexed(index, target) => {
+            visitor.visit_statement(index);
+            visitor.visit_statement(target);
+        }
+        Statement::SetField(_, target, value) => {
+            visitor.visit_statement(target);
+            visitor.visit_statement(value);
+        }
+        Statement::SetIndexed(index, target, value) => {
+            visitor.visit_statement(index);
+            visitor.visit_statement(target);
+            visitor.visit_statement(value);
+        }
+    }
+}
+
+pub fn walk_mut_literal<V: VisitorMut + ?Sized>(visitor: &mut V, literal: &mut Literal) {
+    match literal {
+        Literal::Tuple(items) | Literal::Data(_, items) | Literal::Array(items) => {
+            for item in items {
+                visitor.visit_statement(item);
+            }
+        }
+        Literal::Fn(fn_def) => visitor.visit_fn_def(fn_def),
+        Literal::Int(_)
+        | Literal::Float(_)
+        | Literal::Bool(_)
+        | Literal::Char(_)
+        | Literal::String(_)
+        | Literal::Atom(_) => {}
+    }
+}
+
+pub fn walk_mut_var_type<V: VisitorMut + ?Sized>(visitor: &mut V, var_type: &mut VarType) {
+    let (generics, children) = type_children_mut(var_type);
+    for generic in generics {
+        visitor.visit_generic(generic);
+    }
+    for child in children {
+        visitor.visit_var_type(child);
+    }
+    if let VarType::ArrayConstSized(_, size) = var_type {
+        visitor.visit_statement(size);
+    }
+}
+
+fn type_children_mut(var_type: &mut VarType) -> (&mut [Generic], Vec<&mut VarType>) {
+    match var_type {
+        VarType::ArraySized(inner, _)
+        | VarType::ArrayConstSized(inner, _)
+        | VarType::ArrayUnsized(inner)
+        | VarType::Ptr(inner) => (&mut [], vec![inner]),
+        VarType::Data(variants) => (
+            &mut [],
+            variants.iter_mut().flat_map(|(_, ts)| ts).collect(),
+        ),
+        VarType::GenericData(generics, variants) => (
+            generics,
+            variants.iter_mut().flat_map(|(_, ts)| ts).collect(),
+        ),
+        VarType::Tuple(types) | VarType::GenericInstance(_, types) => {
+            (&mut [], types.iter_mut().collect())
+        }
+        VarType::GenericTuple(generics, types) => (generics, types.iter_mut().collect()),
+        VarType::Struct(fields) => (&mut [], fields.iter_mut().map(|(_, t)| t).collect()),
+        VarType::GenericStruct(generics, fields) => {
+            (generics, fields.iter_mut().map(|(_, t)| t).collect())
+        }
+        VarType::Fn(params, ret) | VarType::FnWithVarArgs(params, ret) => {
+            (&mut [], params.iter_mut().chain([&mut **ret]).collect())
+        }
+        VarType::GenericFn(generics, params, ret)
+        | VarType::GenericFnWithVarArgs(generics, params, ret) => {
+            (generics, params.iter_mut().chain([&mut **ret]).collect())
+        }
+        _ => (&mut [], Vec::new()),
+    }
+}
+
+pub trait Fold {
+    fn fold_top_level(&mut self, statement: TopLevelStatement) -> TopLevelStatement {
+        noop_fold_top_level(self, statement)
+    }
+
+    fn fold_top_level_def(&mut self, def: TopLevelDef) -> TopLevelDef {
+        noop_fold_top_level_def(self, def)
+    }
+
+    fn fold_fn_def(&mut self, fn_def: FnDef) -> FnDef {
+        noop_fold_fn_def(self, fn_def)
+    }
+
+    fn fold_statement(&mut self, statement: Statement) -> Statement {
+        noop_fold_statement(self, statement)
+    }
+
+    fn fold_literal(&mut self, literal: Literal) -> Literal {
+        noop_fold_literal(self, literal)
+    }
+
+    fn fold_var_type(&mut self, var_type: VarType) -> VarType {
+        noop_fold_var_type(self, var_type)
+    }
+
+    fn fold_generic(&mut self, generic: Generic) -> Generic {
+        generic
+    }
+}
+
+pub fn noop_fold_module<F: Fold + ?Sized>(
+    folder: &mut F,
+    module: Vec<TopLevelStatement>,
+) -> Vec<TopLevelStatement> {
+    module
+        .into_iter()
+        .map(|statement| folder.fold_top_level(statement))
+        .collect()
+}
+
+pub fn noop_fold_top_level<F: Fold + ?Sized>(
+    folder: &mut F,
+    statement: TopLevelStatement,
+) -> TopLevelStatement {
+    match statement {
+        TopLevelStatement::TypeAlias(name, var_type) => {
+            TopLevelStatement::TypeAlias(name, folder.fold_var_type(var_type))
+        }
+        TopLevelStatement::TopLevelDef(def) => TopLevelStatement::TopLevelDef(DefVar {
+            name: def.name,
+            instruction: folder.fold_top_level_def(def.instruction),
+        }),
+        other => other,
+    }
+}
+
+pub fn noop_fold_top_level_def<F: Fold + ?Sized>(folder: &mut F, def: TopLevelDef) -> TopLevelDef {
+    match def {
+        TopLevelDef::Literal(literal) => TopLevelDef::Literal(folder.fold_literal(literal)),
+        TopLevelDef::Typed(var_type) => TopLevelDef::Typed(folder.fold_var_type(var_type)),
+        TopLevelDef::FnDef(fn_def) => TopLevelDef::FnDef(folder.fold_fn_def(fn_def)),
+        TopLevelDef::Const(statement) => TopLevelDef::Const(folder.fold_statement(statement)),
+    }
+}
+
+pub fn noop_fold_fn_def<F: Fold + ?Sized>(folder: &mut F, fn_def: FnDef) -> FnDef {
+    FnDef {
+        generic_types: fn_def
+            .generic_types
+            .map(|generics| fold_generics(folder, generics)),
+        parameters: fn_def
+            .parameters
+            .into_iter()
+            .map(|(name, var_type)| (name, folder.fold_var_type(var_type)))
+            .collect(),
+        return_type: folder.fold_var_type(fn_def.return_type),
+        statement: folder.fold_statement(fn_def.statement),
+    }
+}
+
+fn fold_all<F: Fold + ?Sized>(folder: &mut F, statements: Vec<Statement>) -> Vec<Statement> {
+    statements
+        .into_iter()
+        .map(|statement| folder.fold_statement(statement))
+        .collect()
+}
+
+fn fold_boxed<F: Fold + ?Sized>(folder: &mut F, mut statement: Box<Statement>) -> Box<Statement> {
+    let placeholder = Statement::DoBlock(Vec::new());
+    *statement = folder.fold_statement(std::mem::replace(&mut *statement, placeholder));
+    statement
+}
+
+/// Fold a boxed node in place, reusing its allocation.
+fn fold_type<F: Fold + ?Sized>(folder: &mut F, mut var_type: Box<VarType>) -> Box<VarType> {
+    *var_type = folder.fold_var_type(std::mem::replace(&mut *var_type, VarType::Void));
+    var_type
+}
+
+fn fold_types<F: Fold + ?Sized>(folder: &mut F, types: Vec<VarType>) -> Vec<VarType> {
+    types
+        .into_iter()
+        .map(|var_type| folder.fold_var_type(var_type))
+        .collect()
+}
+
+fn fold_fields<F: Fold + ?Sized>(
+    folder: &mut F,
+    fields: Vec<(String, VarType)>,
+) -> Vec<(String, VarType)> {
+    fields
+        .into_iter()
+        .map(|(name, var_type)| (name, folder.fold_var_type(var_type)))
+        .collect()
+}
+
+fn fold_variants<F: Fold + ?Sized>(
+    folder: &mut F,
+    variants: Vec<(String, Vec<VarType>)>,
+) -> Vec<(String, Vec<VarType>)> {
+    variants
+        .into_iter()
+        .map(|(tag, types)| (tag, fold_types(folder, types)))
+        .collect()
+}
+
+fn fold_generics<F: Fold + ?Sized>(folder: &mut F, generics: Vec<Generic>) -> Vec<Generic> {
+    generics
+        .into_iter()
+        .map(|generic| folder.fold_generic(generic))
+        .collect()
+}
+
+pub fn noop_fold_statement<F: Fold + ?Sized>(folder: &mut F, statement: Statement) -> Statement {
+    match statement {
+        Statement::Ident(name) => Statement::Ident(name),
+        Statement::Declare(name, var_type) => {
+            Statement::Declare(name, folder.fold_var_type(var_type))
+        }
+        Statement::Break => Statement::Break,
+        Statement::Continue => Statement::Continue,
+        Statement::Return(value) => Statement::Return(value.map(|value| fold_boxed(folder, value))),
+        Statement::Literal(literal) => Statement::Literal(folder.fold_literal(literal)),
+        Statement::DoBlock(statements) => Statement::DoBlock(fold_all(folder, statements)),
+        Statement::Call(name, args) => Statement::Call(name, fold_all(folder, args)),
+        Statement::GenericCall(name, type_args, args) => {
+            Statement::GenericCall(name, type_args, fold_all(folder, args))
+        }
+        Statement::DefVar(def) => Statement::DefVar(DefVar {
+            name: def.name,
+            instruction: fold_boxed(folder, def.instruction),
+        }),
+        Statement::If(condition, then) => {
+            Statement::If(fold_boxed(folder, condition), fold_boxed(folder, then))
+        }
+        Statement::IfElse(condition, then, otherwise) => Statement::IfElse(
+            fold_boxed(folder, condition),
+            fold_boxed(folder, then),
+            fold_boxed(folder, otherwise),
+        ),
+        Statement::For(condition, body) => {
+            Statement::For(fold_boxed(folder, condition), fold_boxed(folder, body))
+        }
+        Statement::ForRange(name, range, body) => Statement::ForRange(
+            name,
+            Box::new(range.into_map(|part| folder.fold_statement(part))),
+            fold_boxed(folder, body),
+        ),
+        Statement::GetField(field, target) => {
+            Statement::GetField(field, fold_boxed(folder, target))
+        }
+        Statement::GetIndexed(index, target) => {
+            Statement::GetIndexed(fold_boxed(folder, index), fold_boxed(folder, target))
+        }
+        Statement::SetField(field, target, value) => {
+            Statement::SetField(field, fold_boxed(folder, target), fold_boxed(folder, value))
+        }
+        Statement::SetIndexed(index, target, value) => Statement::SetIndexed(
+            fold_boxed(folder, index),
+            fold_boxed(folder, target),
+            fold_boxed(folder, value),
+        ),
+    }
+}
+
+pub fn noop_fold_literal<F: Fold + ?Sized>(folder: &mut F, literal: Literal) -> Literal {
+    match literal {
+        Literal::Tuple(items) => Literal::Tuple(fold_all(folder, items)),
+        Literal::Data(tag, args) => Literal::Data(tag, fold_all(folder, args)),
+        Literal::Array(items) => Literal::Array(fold_all(folder, items)),
+        Literal::Fn(fn_def) => Literal::Fn(Box::new(folder.fold_fn_def(*fn_def))),
+        scalar => scalar,
+    }
+}
+
+pub fn noop_fold_var_type<F: Fold + ?Sized>(folder: &mut F, var_type: VarType) -> VarType {
+    match var_type {
+        VarType::ArraySized(inner, size) => VarType::ArraySized(fold_type(folder, inner), size),
+        VarType::ArrayConstSized(inner, size) => {
+            VarType::ArrayConstSized(fold_type(folder, inner), fold_boxed(folder, size))
+        }
+        VarType::ArrayUnsized(inner) => VarType::ArrayUnsized(fold_type(folder, inner)),
+        VarType::Ptr(inner) => VarType::Ptr(fold_type(folder, inner)),
+        VarType::Data(variants) => VarType::Data(fold_variants(folder, variants)),
+        VarType::GenericData(generics, variants) => VarType::GenericData(
+            fold_generics(folder, generics),
+            fold_variants(folder, variants),
+        ),
+        VarType::Tuple(types) => VarType::Tuple(fold_types(folder, types)),
+        VarType::GenericTuple(generics, types) => {
+            VarType::GenericTuple(fold_generics(folder, generics), fold_types(folder, types))
+        }
+        VarType::Struct(fields) => VarType::Struct(fold_fields(folder, fields)),
+        VarType::GenericStruct(generics, fields) => {
+            VarType::GenericStruct(fold_generics(folder, generics), fold_fields(folder, fields))
+        }
+        VarType::Fn(params, ret) => VarType::Fn(fold_types(folder, params), fold_type(folder, ret)),
+        VarType::FnWithVarArgs(params, ret) => {
+            VarType::FnWithVarArgs(fold_types(folder, params), fold_type(folder, ret))
+        }
+        VarType::GenericFn(generics, params, ret) => VarType::GenericFn(
+            fold_generics(folder, generics),
+            fold_types(folder, params),
+            fold_type(folder, ret),
+        ),
+        VarType::GenericFnWithVarArgs(generics, params, ret) => VarType::GenericFnWithVarArgs(
+            fold_generics(folder, generics),
+            fold_types(folder, params),
+            fold_type(folder, ret),
+        ),
+        VarType::GenericInstance(name, args) => {
+            VarType::GenericInstance(name, fold_types(folder, args))
+        }
+        leaf => leaf,
+    }
+}
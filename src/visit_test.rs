@@ -0,0 +1,101 @@
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Literal, Statement, TopLevelStatement, VarType};
+    use crate::parser::parser;
+    use crate::visit::{
+        noop_fold_module, noop_fold_statement, walk_module, walk_mut_module, walk_statement,
+        walk_var_type, Fold, Visitor, VisitorMut,
+    };
+    use chumsky::Parser;
+
+    fn parse(src: &str) -> Vec<TopLevelStatement> {
+        parser().parse(src).into_output().unwrap()
+    }
+
+    const SRC: &str = "(type pair (struct<T> (:a T) (:b (ptr T))))
+        (def f (fn [(:p pair<i32>)] fn [i32] i32
+          (do (def k {1 [x]}) (fn [(:y i32)] i32 (+ ($ :a p) y)))))";
+
+    #[derive(Default)]
+    struct Collect {
+        idents: Vec<String>,
+        types: Vec<String>,
+    }
+
+    impl Visitor for Collect {
+        fn visit_statement(&mut self, statement: &Statement) {
+            if let Statement::Ident(name) = statement {
+                self.idents.push(name.clone());
+            }
+            walk_statement(self, statement);
+        }
+
+        fn visit_var_type(&mut self, var_type: &VarType) {
+            if let VarType::IdentType(name) = var_type {
+                self.types.push(name.clone());
+            }
+            walk_var_type(self, var_type);
+        }
+    }
+
+    #[test]
+    fn test_visitor_reaches_nested_nodes() {
+        let mut collect = Collect::default();
+        walk_module(&mut collect, &parse(SRC));
+        assert_eq!(collect.idents, vec!["x", "p", "y"]);
+        assert_eq!(collect.types, vec!["T", "T"]);
+    }
+
+    struct Rename;
+
+    impl VisitorMut for Rename {
+        fn visit_statement(&mut self, statement: &mut Statement) {
+            match statement {
+                Statement::Ident(name) => name.push('\''),
+                _ => crate::visit::walk_mut_statement(self, statement),
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor_mut_rewrites_in_place() {
+        let mut module = parse("(def f (fn [(:x i32)] i32 (do (def y x) (+ x y))))");
+        walk_mut_module(&mut Rename, &mut module);
+        assert_eq!(
+            module,
+            parse("(def f (fn [(:x i32)] i32 (do (def y x') (+ x' y'))))")
+        );
+    }
+
+    struct Widen;
+
+    impl Fold for Widen {
+        fn fold_statement(&mut self, statement: Statement) -> Statement {
+            match statement {
+                Statement::Literal(Literal::Int(value)) => {
+                    Statement::Literal(Literal::Int(value * 10))
+                }
+                other => noop_fold_statement(self, other),
+            }
+        }
+
+        fn fold_var_type(&mut self, var_type: VarType) -> VarType {
+            match var_type {
+                VarType::Int32 => VarType::Int64,
+                other => crate::visit::noop_fold_var_type(self, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_fold_rebuilds_the_tree() {
+        let module = noop_fold_module(
+            &mut Widen,
+            parse("(def f (fn [(:x [i32 2])] fn [i32] i32 (fn [(:y i32)] i32 {1 [2]})))"),
+        );
+        assert_eq!(
+            module,
+            parse("(def f (fn [(:x [i64 2])] fn [i64] i64 (fn [(:y i64)] i64 {10 [20]})))")
+        );
+    }
+}